@@ -0,0 +1,149 @@
+use std::{env, path::PathBuf, str::FromStr, sync::Arc};
+
+use semver::Version;
+
+use crate::{
+    commands::{arguments::Arguments, command_handler::CommandHandler, install},
+    config::Config,
+    errors::{ClickError, CommandError},
+    http::{HTTPRequest, NetworkMode},
+    installer::Installer as PackageInstaller,
+    reporter::Reporter,
+    types::{Lockfile, VersionData},
+    versions::Versions,
+};
+
+/// The registry packages are resolved against and downloaded from, the one set by `CLICK_REGISTRY`, .clickrc or .npmrc.
+#[derive(Clone)]
+pub struct Registry {
+    client: reqwest::Client,
+}
+
+impl Registry {
+    pub fn new() -> Result<Self, ClickError> {
+        Ok(Self {
+            client: Config::client()?,
+        })
+    }
+
+    /// Every published version of a package, lowest first. Versions that are not valid semver are left out.
+    pub async fn versions(&self, package_name: &str) -> Result<Vec<String>, ClickError> {
+        let package_data =
+            HTTPRequest::package_data(self.client.clone(), package_name, NetworkMode::Online)
+                .await?;
+
+        let mut versions = package_data
+            .versions
+            .into_keys()
+            .filter_map(|version| Version::from_str(&version).ok())
+            .collect::<Vec<_>>();
+        versions.sort();
+
+        Ok(versions.iter().map(Version::to_string).collect())
+    }
+
+    /// A version as it was published, with its dependencies and the tarball it is downloaded from.
+    pub async fn version_data(
+        &self,
+        package_name: &str,
+        version: &str,
+    ) -> Result<VersionData, ClickError> {
+        let version_data =
+            HTTPRequest::version_data(self.client.clone(), package_name, &version.to_string())
+                .await?;
+
+        Ok(version_data)
+    }
+}
+
+/// Resolves specs the way `click install` does, such as `react@^18`, `react@beta` or `react` for the latest version.
+pub struct Resolver {
+    registry: Registry,
+}
+
+impl Resolver {
+    pub fn new(registry: Registry) -> Self {
+        Self { registry }
+    }
+
+    /// Returns the version the spec installs. Like npm, prereleases are only resolved to if the range asks for one.
+    pub async fn resolve(&self, package_spec: &str) -> Result<VersionData, ClickError> {
+        let client = self.registry.client.clone();
+        let (package_name, semantic_version) = PackageInstaller::parse_package_spec(
+            client.clone(),
+            package_spec.to_string(),
+            NetworkMode::Online,
+        )
+        .await?;
+
+        let semantic_version = semantic_version.as_ref();
+        let full_version = Versions::resolve_full_version(semantic_version);
+        let version_data = PackageInstaller::get_version_data(
+            client,
+            &package_name,
+            full_version.as_ref(),
+            semantic_version,
+            NetworkMode::Online,
+        )
+        .await?;
+
+        Ok(version_data)
+    }
+}
+
+/// Installs the dependencies of a project the way `click install` does, without printing anything. What the install
+/// does can be followed with a reporter instead. Installs work relative to the project, so the current directory of the
+/// process is changed to it.
+#[derive(Default)]
+pub struct Installer {
+    pub directory: PathBuf,   // The project, with the package.json to install from
+    pub frozen: bool,         // Fail rather than change the lockfile, as `click ci` does
+    pub production: bool,     // Skip devDependencies
+    pub ignore_scripts: bool, // Do not run the lifecycle scripts of packages
+    pub reporter: Option<Arc<dyn Reporter>>, // Receives every event of the install as it happens
+}
+
+impl Installer {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Installs every dependency in package.json. Returns the lockfile it was installed from.
+    pub async fn install(&self) -> Result<Lockfile, ClickError> {
+        self.run(&[]).await
+    }
+
+    /// Saves the specs to package.json, such as `react@^18`, and installs them along with every other dependency.
+    pub async fn add(&self, package_specs: &[&str]) -> Result<Lockfile, ClickError> {
+        self.run(package_specs).await
+    }
+
+    async fn run(&self, package_specs: &[&str]) -> Result<Lockfile, ClickError> {
+        env::set_current_dir(&self.directory).map_err(CommandError::FailedToReadFile)?;
+
+        let flags = [
+            (self.frozen, "--frozen-lockfile"),
+            (self.production, "--production"),
+            (self.ignore_scripts, "--ignore-scripts"),
+            (true, "--quiet-success"),
+        ];
+        let args = flags
+            .into_iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, flag)| flag)
+            .chain(package_specs.iter().copied())
+            .map(str::to_string);
+
+        let mut handler = install::InstallHandler::default();
+        handler.parse(&mut Arguments::new(&install::USAGE, args))?;
+        if let Some(reporter) = &self.reporter {
+            handler.report_to(reporter.clone());
+        }
+        handler.execute().await?;
+
+        Ok(Lockfile::read()?)
+    }
+}
@@ -0,0 +1,340 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    str::FromStr,
+};
+
+use semver::Version;
+
+use crate::{
+    commands::update::UpdateHandler,
+    errors::CommandError,
+    http::HTTPRequest,
+    http::NetworkMode,
+    installer::Installer,
+    manifest::{DependencyKind, Manifest},
+    source::Source,
+    types::{Advisory, AuditFix, Lockfile, Vulnerability},
+    versions::{Versions, EMPTY_VERSION},
+};
+
+/// The bumps `click audit fix` makes, along with the vulnerable packages that no bump can fix.
+#[derive(Default)]
+pub struct FixPlan {
+    pub fixes: Vec<(DependencyKind, AuditFix)>, // With the field of package.json the package is in
+    pub unfixable: Vec<String>,                 // Formatted as package@version
+}
+
+/// Checks the packages in the lockfile against the advisories of the registry. The whole graph is sent at once to the
+/// bulk advisory endpoint, which only answers with the advisories that affect any of the versions sent.
+pub struct Audit;
+impl Audit {
+    /// Sends every locked package to the registry and returns each one an advisory affects, most severe first.
+    /// `registry` is where the advisories are asked for, which can be another than the one packages are installed from.
+    pub async fn run(
+        client: reqwest::Client,
+        registry: &str,
+        lockfile: &Lockfile,
+    ) -> Result<Vec<Vulnerability>, CommandError> {
+        let versions = Self::versions(lockfile);
+        if versions.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let advisories = HTTPRequest::bulk_advisories(client, registry, &versions).await?;
+        let paths = Self::paths(lockfile);
+
+        let mut vulnerabilities = Vec::new();
+        for (package_name, advisories) in advisories {
+            let Some(package_versions) = versions.get(&package_name) else {
+                continue;
+            };
+
+            for version in package_versions {
+                let package = Versions::stringify(&package_name, version);
+                let parsed_version = Version::from_str(version).unwrap_or(EMPTY_VERSION);
+
+                // A range that cannot be parsed is assumed to affect the version, as the registry said it affects one
+                let affected = advisories.iter().filter(|advisory| {
+                    Versions::parse_semantic_version(&advisory.vulnerable_versions)
+                        .map_or(true, |range| range.matches(&parsed_version))
+                });
+
+                for advisory in affected {
+                    vulnerabilities.push(Vulnerability {
+                        package: package.to_string(),
+                        advisory: advisory.clone(),
+                        paths: paths.get(&package).cloned().unwrap_or_default(),
+                    });
+                }
+            }
+        }
+
+        vulnerabilities.sort_by(|a, b| {
+            b.advisory
+                .severity
+                .cmp(&a.advisory.severity)
+                .then_with(|| a.package.cmp(&b.package))
+                .then_with(|| a.advisory.id.cmp(&b.advisory.id))
+        });
+
+        Ok(vulnerabilities)
+    }
+
+    /// Every locked version of each package, which is the body of the bulk advisory request.
+    fn versions(lockfile: &Lockfile) -> BTreeMap<String, Vec<String>> {
+        let mut versions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for package in lockfile.packages.keys() {
+            let (package_name, version) = Versions::parse_raw_package_details(package.to_string());
+            versions.entry(package_name).or_default().push(version);
+        }
+
+        versions
+    }
+
+    /// How each locked package is depended on, as the shortest path to it from each package requested in package.json,
+    /// such as `express > body-parser > qs`.
+    pub fn paths(lockfile: &Lockfile) -> HashMap<String, Vec<String>> {
+        let roots = lockfile.requested.values().collect::<BTreeSet<_>>();
+
+        let mut paths: HashMap<String, Vec<String>> = HashMap::new();
+        for root in roots {
+            let mut queue = VecDeque::from([(root.to_string(), Self::name(root))]);
+            let mut visited = BTreeSet::from([root.to_string()]);
+
+            while let Some((package, path)) = queue.pop_front() {
+                let package_paths = paths.entry(package.to_string()).or_default();
+                if !package_paths.contains(&path) {
+                    package_paths.push(path.to_string());
+                }
+
+                for (_, dependency) in lockfile.direct_dependencies(&package) {
+                    if visited.insert(dependency.to_string()) {
+                        let dependency_path = format!("{path} > {}", Self::name(&dependency));
+                        queue.push_back((dependency.to_string(), dependency_path));
+                    }
+                }
+            }
+        }
+
+        paths
+    }
+
+    /// Works out the fewest version bumps to packages in package.json that clear the vulnerabilities. A vulnerable
+    /// package in package.json is bumped to the lowest version its range allows that none of its advisories affect.
+    /// A vulnerable dependency of one is fixed by bumping the package it is depended on through to the newest version its
+    /// range allows, which resolves its dependencies again. With `force` versions outside the ranges are allowed, and the
+    /// range is bumped to the new version.
+    pub async fn plan_fixes(
+        client: reqwest::Client,
+        manifest: &Manifest,
+        lockfile: &Lockfile,
+        vulnerabilities: &[Vulnerability],
+        force: bool,
+    ) -> Result<FixPlan, CommandError> {
+        let mut direct = HashMap::new();
+        for kind in [
+            DependencyKind::Production,
+            DependencyKind::Development,
+            DependencyKind::Optional,
+        ] {
+            for (package_name, range) in manifest.dependencies(kind.field()) {
+                if Source::from_spec(&range).is_some() {
+                    continue; // Only packages from the registry can be bumped
+                }
+
+                direct.insert(package_name, (kind.clone(), range));
+            }
+        }
+
+        let mut vulnerable: BTreeMap<String, Vec<&Vulnerability>> = BTreeMap::new();
+        for vulnerability in vulnerabilities {
+            vulnerable
+                .entry(Self::name(&vulnerability.package))
+                .or_default()
+                .push(vulnerability);
+        }
+
+        let mut plan = FixPlan::default();
+        let mut fixes: BTreeMap<String, (DependencyKind, AuditFix)> = BTreeMap::new();
+        for (package_name, vulnerabilities) in vulnerable {
+            let mut fixed = false;
+
+            if let Some((kind, range)) = direct.get(&package_name) {
+                let current =
+                    Self::locked_version(client.clone(), lockfile, &package_name, range).await?;
+                let advisories = vulnerabilities
+                    .iter()
+                    .map(|vulnerability| &vulnerability.advisory);
+                let safe_version = Self::lowest_safe_version(
+                    client.clone(),
+                    &package_name,
+                    range,
+                    current.as_deref(),
+                    advisories.collect(),
+                    force,
+                )
+                .await?;
+
+                if let (Some(current), Some(version)) = (current, safe_version) {
+                    let fix = AuditFix {
+                        name: package_name.to_string(),
+                        from: current,
+                        range: Self::fixed_range(range, &version, force),
+                        to: version,
+                        fixes: Self::packages(&vulnerabilities),
+                    };
+                    Self::add_fix(&mut fixes, kind.clone(), fix);
+                    fixed = true;
+                }
+            }
+
+            // Dependencies of packages in package.json are fixed by resolving the package in package.json again
+            let roots = vulnerabilities
+                .iter()
+                .flat_map(|vulnerability| vulnerability.paths.iter())
+                .filter_map(|path| path.split_once(" > ").map(|(root, _)| root.to_string()))
+                .collect::<BTreeSet<_>>();
+
+            for root in roots {
+                let Some((kind, range)) = direct.get(&root) else {
+                    continue;
+                };
+                let Some(current) =
+                    Self::locked_version(client.clone(), lockfile, &root, range).await?
+                else {
+                    continue;
+                };
+
+                let semantic_version = match force {
+                    true => None,
+                    false => {
+                        Installer::parse_range(client.clone(), &root, range, NetworkMode::Online)
+                            .await?
+                    }
+                };
+                let version =
+                    UpdateHandler::newest_version(client.clone(), &root, semantic_version.as_ref())
+                        .await?;
+                if !Versions::is_newer(&version, &current) {
+                    continue;
+                }
+
+                let fix = AuditFix {
+                    name: root.to_string(),
+                    from: current,
+                    range: Self::fixed_range(range, &version, force),
+                    to: version,
+                    fixes: Self::packages(&vulnerabilities),
+                };
+                Self::add_fix(&mut fixes, kind.clone(), fix);
+                fixed = true;
+            }
+
+            if !fixed {
+                plan.unfixable.extend(Self::packages(&vulnerabilities));
+            }
+        }
+
+        plan.fixes = fixes.into_values().collect();
+        plan.unfixable.sort();
+        plan.unfixable.dedup();
+
+        Ok(plan)
+    }
+
+    /// Records a bump, a package bumped for more than one vulnerability is bumped to the highest version needed.
+    fn add_fix(
+        fixes: &mut BTreeMap<String, (DependencyKind, AuditFix)>,
+        kind: DependencyKind,
+        fix: AuditFix,
+    ) {
+        let Some((_, existing)) = fixes.get_mut(&fix.name) else {
+            fixes.insert(fix.name.to_string(), (kind, fix));
+            return;
+        };
+
+        if Versions::is_newer(&fix.to, &existing.to) {
+            existing.to = fix.to;
+            existing.range = fix.range;
+        }
+
+        existing.fixes.extend(fix.fixes);
+        existing.fixes.sort();
+        existing.fixes.dedup();
+    }
+
+    /// A version outside the range can only be installed by bumping the range, which is only done with `force`.
+    fn fixed_range(range: &str, version: &str, force: bool) -> String {
+        let version = Version::from_str(version).unwrap_or(EMPTY_VERSION);
+        match Versions::parse_semantic_version(range) {
+            Ok(semantic_version) if force && !semantic_version.matches(&version) => {
+                format!("^{version}")
+            }
+            _ => range.to_string(),
+        }
+    }
+
+    fn packages(vulnerabilities: &[&Vulnerability]) -> Vec<String> {
+        vulnerabilities
+            .iter()
+            .map(|vulnerability| vulnerability.package.to_string())
+            .collect()
+    }
+
+    /// The version a package in package.json is locked to, or None if it has not been installed.
+    async fn locked_version(
+        client: reqwest::Client,
+        lockfile: &Lockfile,
+        package_name: &String,
+        range: &str,
+    ) -> Result<Option<String>, CommandError> {
+        let semantic_version =
+            Installer::parse_range(client, package_name, range, NetworkMode::Online).await?;
+
+        Ok(lockfile.locked_version(&Lockfile::spec(package_name, semantic_version.as_ref())))
+    }
+
+    /// The lowest version newer than the current one that none of the advisories affect, within the range unless `force`
+    /// is set. Prereleases are never picked.
+    async fn lowest_safe_version(
+        client: reqwest::Client,
+        package_name: &str,
+        range: &str,
+        current: Option<&str>,
+        advisories: Vec<&Advisory>,
+        force: bool,
+    ) -> Result<Option<String>, CommandError> {
+        let package_data =
+            HTTPRequest::package_data(client, package_name, NetworkMode::Online).await?;
+        let range = Versions::parse_semantic_version(range).ok();
+        let vulnerable_ranges = advisories
+            .iter()
+            .filter_map(|advisory| {
+                Versions::parse_semantic_version(&advisory.vulnerable_versions).ok()
+            })
+            .collect::<Vec<_>>();
+
+        let mut versions = package_data
+            .versions
+            .keys()
+            .filter_map(|version| Version::from_str(version).ok())
+            .filter(|version| version.pre.is_empty())
+            .filter(|version| {
+                current.is_none_or(|current| Versions::is_newer(&version.to_string(), current))
+            })
+            .filter(|version| force || range.as_ref().is_some_and(|range| range.matches(version)))
+            .filter(|version| {
+                !vulnerable_ranges
+                    .iter()
+                    .any(|vulnerable| vulnerable.matches(version))
+            })
+            .collect::<Vec<_>>();
+        versions.sort();
+
+        Ok(versions.first().map(Version::to_string))
+    }
+
+    fn name(package: &str) -> String {
+        Versions::parse_raw_package_details(package.to_string()).0
+    }
+}
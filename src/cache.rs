@@ -1,18 +1,26 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::{self as fs_sync, File},
-    io::{ErrorKind, Read, Seek, SeekFrom},
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    path::Path,
     str::FromStr,
 };
 
 use lazy_static::lazy_static;
-use semver::{Comparator, Version};
+use semver::Version;
 use tokio::fs;
 
 use crate::{
+    config::Config,
     errors::CommandError,
+    http::HTTPRequest,
+    integrity::Integrity,
+    isolated::IsolatedLayout,
+    manifest::{Manifest, DEPENDENCY_FIELDS},
+    tarball_cache::{TarballCache, VerifyOutcome},
     types::PackageLock,
-    versions::{Versions, EMPTY_VERSION, LATEST},
+    util::directory_size,
+    versions::{VersionRange, Versions, EMPTY_VERSION, LATEST},
 };
 
 pub struct CachedVersion {
@@ -20,7 +28,7 @@ pub struct CachedVersion {
     pub is_latest: bool,
 }
 
-pub type CachedVersions = HashMap<String, CachedVersion>;
+pub type CachedVersions = HashMap<String, Vec<CachedVersion>>; // Every cached version of each package
 
 lazy_static! {
     pub static ref CACHE_DIRECTORY: String = format!(
@@ -35,11 +43,15 @@ lazy_static! {
 
 pub struct Cache;
 impl Cache {
-    /// Returns a hashmap, each key is formatted as package@version
-    /// and the value is a boolean of whether the package is the latest version or not.
+    /// Returns a hashmap, each key is a package name and the value is every version of it in the cache,
+    /// including whether each version was the latest version when it was installed.
     pub fn get_cached_versions() -> CachedVersions {
-        let dir_contents =
-            fs_sync::read_dir(CACHE_DIRECTORY.to_string()).expect("Failed to read cache directory");
+        // Nothing has been cached yet the first time click is run, or after the cache is cleared
+        let dir_contents = match fs_sync::read_dir(CACHE_DIRECTORY.to_string()) {
+            Ok(dir_contents) => dir_contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return HashMap::new(),
+            Err(err) => panic!("Failed to read cache directory ({err})"),
+        };
 
         let mut cached_versions = HashMap::new();
 
@@ -47,11 +59,14 @@ impl Cache {
             let entry = entry.expect("Failed to get directory entry");
             let filename = entry.file_name().to_string_lossy().to_string();
 
-            let mut lock_file = File::open(format!(
-                "{}/{}/package/click-lock.json",
-                *CACHE_DIRECTORY, filename
-            ))
-            .expect("Failed to read package lock file");
+            let lock_file = File::open(Self::lock_path(&filename));
+
+            // Lockfiles are written once an install completes, so entries without one are from an interrupted install
+            let mut lock_file = match lock_file {
+                Ok(lock_file) => lock_file,
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => panic!("Failed to read package lock file ({err})"),
+            };
 
             // This is not an ideal method but it beats parsing the JSON of every installed package
             let start_byte = 12;
@@ -60,14 +75,16 @@ impl Cache {
             let bytes_length = end_byte - start_byte + 1;
             let mut buf = vec![0; bytes_length];
 
-            lock_file.seek(SeekFrom::Start(start_byte as u64)).unwrap();
-            lock_file.read_exact(&mut buf).unwrap();
-
-            let is_latest_str = String::from_utf8(buf).unwrap();
-            let is_latest = is_latest_str == "true";
+            // A truncated lockfile is still listed so that using it reports the corruption instead of panicking here
+            let is_latest = lock_file.seek(SeekFrom::Start(start_byte as u64)).is_ok()
+                && lock_file.read_exact(&mut buf).is_ok()
+                && buf == b"true";
 
             let (name, version) = Versions::parse_raw_package_details(filename);
-            cached_versions.insert(name, CachedVersion { version, is_latest });
+            cached_versions
+                .entry(name)
+                .or_insert_with(Vec::new)
+                .push(CachedVersion { version, is_latest });
         }
 
         cached_versions
@@ -78,7 +95,7 @@ impl Cache {
     pub async fn exists(
         package_name: &String,
         version: Option<&String>,
-        semantic_version: Option<&Comparator>,
+        semantic_version: Option<&VersionRange>,
     ) -> Result<(bool, Option<String>), CommandError> {
         if let Some(version) = version {
             if version == LATEST {
@@ -92,11 +109,15 @@ impl Cache {
             ));
         }
 
-        let mut cache_entries = fs::read_dir(CACHE_DIRECTORY.to_string())
-            .await
-            .map_err(CommandError::NoCacheDirectory)?;
+        // Nothing has been cached yet if the first package is still being downloaded
+        let mut cache_entries = match fs::read_dir(CACHE_DIRECTORY.to_string()).await {
+            Ok(cache_entries) => cache_entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok((false, None)),
+            Err(err) => return Err(CommandError::NoCacheDirectory(err)),
+        };
 
         let semantic_version = semantic_version.unwrap();
+        let mut matched_version: Option<String> = None;
 
         while let Some(cache_entry) = cache_entries
             .next_entry()
@@ -105,64 +126,295 @@ impl Cache {
             .unwrap()
         {
             let filename = cache_entry.file_name().to_string_lossy().to_string();
-            if !filename.starts_with(package_name) {
+            if !filename.starts_with(package_name) || !Self::has_lock(&filename) {
                 continue;
             }
 
             let (_, entry_version) = Versions::parse_raw_package_details(filename);
 
+            // Packages from git have the commit as build metadata, a range of the registry is never resolved to one
             let version = &Version::from_str(entry_version.as_str()).unwrap_or(EMPTY_VERSION);
-            if semantic_version.matches(version) {
-                return Ok((true, Some(entry_version)));
+            if !semantic_version.matches(version) || !version.build.is_empty() {
+                continue;
+            }
+
+            // Directory order is not stable, so keep looking for the highest matching version
+            match &matched_version {
+                Some(matched) if !Versions::is_newer(&entry_version, matched) => continue,
+                _ => matched_version = Some(entry_version),
             }
         }
 
-        Ok((false, None))
+        Ok((matched_version.is_some(), matched_version))
+    }
+
+    /// Package string is formated as package@version
+    /// Returns false if the package was extracted by an install that has not completed yet.
+    pub fn has_lock(package: &String) -> bool {
+        Path::new(&Self::lock_path(package)).exists()
     }
 
     pub fn is_in_cache(package: &String, version: &String) -> bool {
-        let cached_version = CACHED_VERSIONS.get(package);
-        match cached_version {
-            Some(ver) if &ver.version == version => true,
-            _ => false,
-        }
+        let cached_versions = CACHED_VERSIONS.get(package);
+
+        // Packages cached since CACHED_VERSIONS was first read will only be found by their lockfile
+        matches!(cached_versions, Some(versions) if versions.iter().any(|ver| &ver.version == version))
+            || Self::has_lock(&Versions::stringify(package, version))
     }
 
     /// Checks if the latest version exists in the cache.
     /// This is checked by reading if the package lock has the latest property as true.
     pub fn get_latest_version_in_cache(package_name: &String) -> Option<String> {
-        let cached_version = CACHED_VERSIONS.get(package_name);
-        match cached_version {
-            Some(ver) if ver.is_latest => Some(ver.version.to_string()),
-            _ => None,
-        }
+        let cached_versions = CACHED_VERSIONS.get(package_name)?;
+
+        cached_versions
+            .iter()
+            .filter(|ver| ver.is_latest)
+            .map(|ver| &ver.version)
+            .reduce(|highest, version| {
+                if Versions::is_newer(version, highest) {
+                    version
+                } else {
+                    highest
+                }
+            })
+            .map(|version| version.to_string())
+    }
+
+    /// Package string is formated as package@version
+    /// Returns the range and package@version of each dependency the package itself depends on. `locked_dependencies`
+    /// lists indirect dependencies too, so the direct ones are found from the package.json of the package in the cache.
+    /// If the package is not cached every locked dependency is returned, with its exact version as the range.
+    pub fn direct_dependencies(
+        package: &String,
+        locked_dependencies: &[String],
+    ) -> Vec<(String, String)> {
+        let manifest_path = format!("{}/{}/package/package.json", *CACHE_DIRECTORY, package);
+        let Ok(Some(manifest)) = Manifest::read_from(Path::new(&manifest_path)) else {
+            return locked_dependencies
+                .iter()
+                .map(|dependency| {
+                    let (_, version) = Versions::parse_raw_package_details(dependency.to_string());
+                    (version, dependency.to_string())
+                })
+                .collect();
+        };
+
+        DEPENDENCY_FIELDS
+            .iter()
+            .filter(|field| **field != "devDependencies")
+            .flat_map(|field| manifest.dependencies(field))
+            .filter_map(|(dependency_name, range)| {
+                IsolatedLayout::select(locked_dependencies, &dependency_name, &range)
+                    .map(|dependency| (range, dependency))
+            })
+            .collect()
     }
 
     /// Package string is formated as package@version
-    pub fn load_cached_version(package: String) {
-        let lockfile_raw = fs_sync::read_to_string(format!(
-            "{}/{}/package/click-lock.json",
-            *CACHE_DIRECTORY, package
-        ))
-        .expect("Failed to read package lockfile");
-
-        let lockfile = serde_json::from_str::<PackageLock>(lockfile_raw.as_str()).unwrap();
-        let mut dependencies = lockfile.dependencies;
-        dependencies.push(package);
-
-        for dependency in dependencies {
-            let (package_name, _) = Versions::parse_raw_package_details(dependency.to_string());
-
-            let result = symlink::symlink_dir(
-                format!("{}/{}/package", *CACHE_DIRECTORY, dependency),
-                format!("./node_modules/{}", package_name),
-            );
-
-            match result {
+    /// Returns the package and every dependency recorded in its lockfile.
+    pub fn cached_packages(package: &String) -> Result<Vec<String>, CommandError> {
+        let mut packages = Self::read_lock(package)?.dependencies;
+        packages.push(package.to_string());
+
+        Ok(packages)
+    }
+
+    /// Package string is formated as package@version
+    fn lock_path(package: &String) -> String {
+        format!("{}/{}/package/click-lock.json", *CACHE_DIRECTORY, package)
+    }
+
+    /// Package string is formated as package@version
+    pub fn read_lock(package: &String) -> Result<PackageLock, CommandError> {
+        let path = Self::lock_path(package);
+        let lockfile_raw =
+            fs_sync::read_to_string(&path).map_err(CommandError::FailedToReadPackageLock)?;
+
+        // The serde error includes the line and column the lockfile stopped making sense at
+        serde_json::from_str::<PackageLock>(lockfile_raw.as_str()).map_err(|err| {
+            CommandError::CorruptLockfile {
+                path,
+                detail: err.to_string(),
+            }
+        })
+    }
+
+    /// Package string is formated as package@version
+    pub fn write_lock(package: &String, package_lock: &PackageLock) -> Result<(), CommandError> {
+        let mut package_lock_file =
+            File::create(Self::lock_path(package)).map_err(CommandError::FailedToCreateFile)?;
+
+        let package_lock_string = serde_json::to_string(package_lock)
+            .map_err(CommandError::FailedToSerializePackageLock)?;
+
+        package_lock_file
+            .write_all(package_lock_string.as_bytes())
+            .map_err(CommandError::FailedToWriteFile)
+    }
+
+    /// Package string is formated as package@version
+    /// Computes the digest of the package's extracted files, see Integrity::directory().
+    pub fn content_integrity(package: &String) -> Result<String, CommandError> {
+        let package_path = format!("{}/{}/package", *CACHE_DIRECTORY, package);
+        Integrity::directory(Path::new(&package_path)).map_err(CommandError::FailedToReadFile)
+    }
+
+    /// Deletes every lockfile in the cache that can't be parsed and returns the packages they belonged to.
+    /// Without a lockfile the package is treated as an interrupted install, so the next install downloads it
+    /// again and writes a fresh lockfile from the registry metadata. The lockfiles of packages that depend on them
+    /// are deleted too, as a cached package is installed along with every dependency its lockfile lists.
+    pub fn remove_corrupt_locks() -> Result<Vec<String>, CommandError> {
+        let dir_contents = fs_sync::read_dir(CACHE_DIRECTORY.to_string())
+            .map_err(CommandError::NoCacheDirectory)?;
+
+        let mut removed = Vec::new();
+        let mut intact = Vec::new();
+        for entry in dir_contents {
+            let entry = entry.map_err(CommandError::FailedDirectoryEntry)?;
+            let package = entry.file_name().to_string_lossy().to_string();
+
+            if !Self::has_lock(&package) {
+                continue;
+            }
+
+            match Self::read_lock(&package) {
+                Err(CommandError::CorruptLockfile { path, .. }) => {
+                    fs_sync::remove_file(path).map_err(CommandError::FailedToWriteFile)?;
+                    removed.push(package);
+                }
+                Ok(package_lock) => intact.push((package, package_lock.dependencies)),
+                Err(err) => return Err(err),
+            }
+        }
+
+        // Lockfiles list indirect dependencies too, so one pass finds every dependent
+        let corrupt = removed.clone();
+        for (package, dependencies) in intact {
+            if dependencies
+                .iter()
+                .any(|dependency| corrupt.contains(dependency))
+            {
+                fs_sync::remove_file(Self::lock_path(&package))
+                    .map_err(CommandError::FailedToWriteFile)?;
+                removed.push(package);
+            }
+        }
+
+        removed.sort();
+        Ok(removed)
+    }
+
+    /// Rehashes the extracted files of every cached package and removes those that no longer match the digest recorded
+    /// when they were installed, or whose lockfile can't be parsed, so that the next install extracts them again.
+    /// Packages installed before the digest was recorded, or by an install that has not completed, are left as they are.
+    pub fn verify_packages() -> Result<VerifyOutcome, CommandError> {
+        let mut outcome = VerifyOutcome::default();
+        for package in Self::entries()? {
+            if !Self::has_lock(&package) {
+                continue;
+            }
+
+            let intact = match Self::read_lock(&package) {
+                Ok(PackageLock {
+                    content_integrity: Some(recorded),
+                    ..
+                }) => Self::content_integrity(&package)? == recorded,
                 Ok(_) => continue,
-                Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
-                Err(err) => panic!("{}", err),
+                Err(CommandError::CorruptLockfile { .. }) => false,
+                Err(err) => return Err(err),
+            };
+            if intact {
+                outcome.verified += 1;
+                continue;
             }
+
+            outcome.reclaimed += Self::remove(&package)?;
+            outcome.removed.push(package);
         }
+
+        outcome.removed.sort();
+        Ok(outcome)
+    }
+
+    /// Removes every cached package that is not in `referenced`, each formatted as package@version.
+    /// Returns the removed packages and the bytes they took. Packages being extracted by an install are left alone.
+    pub fn remove_unreferenced(
+        referenced: &HashSet<String>,
+    ) -> Result<(Vec<String>, u64), CommandError> {
+        let mut removed = Vec::new();
+        let mut reclaimed = 0;
+        for package in Self::entries()? {
+            if referenced.contains(&package) || !Self::has_lock(&package) {
+                continue;
+            }
+
+            reclaimed += Self::remove(&package)?;
+            removed.push(package);
+        }
+
+        removed.sort();
+        Ok((removed, reclaimed))
+    }
+
+    /// Package string is formated as package@version
+    /// Returns the bytes the package took.
+    fn remove(package: &String) -> Result<u64, CommandError> {
+        let path = Path::new(CACHE_DIRECTORY.as_str()).join(package);
+        let size = directory_size(&path);
+        fs_sync::remove_dir_all(&path).map_err(CommandError::FailedToWriteFile)?;
+
+        Ok(size)
+    }
+
+    /// Returns the name of every entry in the cache, formatted as package@version.
+    fn entries() -> Result<Vec<String>, CommandError> {
+        fs_sync::read_dir(CACHE_DIRECTORY.to_string())
+            .map_err(CommandError::NoCacheDirectory)?
+            .map(|entry| {
+                entry
+                    .map(|entry| entry.file_name().to_string_lossy().to_string())
+                    .map_err(CommandError::FailedDirectoryEntry)
+            })
+            .collect()
+    }
+
+    /// Records the SHA-512 integrity of a cached package whose lockfile only has a SHA-1 shasum (or nothing at all).
+    /// As the extracted files are all that is kept in the package cache, the tarball is read from the tarball cache
+    /// or downloaded again to be hashed.
+    /// If `force` is true the integrity is recomputed even if it has already been recorded.
+    pub async fn upgrade_integrity(
+        client: reqwest::Client,
+        package: String,
+        force: bool,
+    ) -> Result<(), CommandError> {
+        let mut package_lock = Self::read_lock(&package)?;
+        if package_lock.integrity.is_some() && !force {
+            return Ok(());
+        }
+
+        let (name, version) = Versions::parse_raw_package_details(package.to_string());
+        let version_data = HTTPRequest::version_data(client.clone(), &name, &version).await?;
+        let bytes = match TarballCache::read(&version_data.dist).await {
+            Some(bytes) => bytes,
+            None => {
+                let tarball = Config::get()?.tarball_url(&name, &version_data.dist.tarball);
+                HTTPRequest::get_bytes(client, tarball).await?
+            }
+        };
+
+        let shasum = Integrity::shasum(&bytes);
+        if package_lock
+            .shasum
+            .as_ref()
+            .is_some_and(|recorded| recorded != &shasum)
+        {
+            return Err(CommandError::IntegrityMismatch(package));
+        }
+
+        package_lock.shasum = Some(shasum);
+        package_lock.integrity = Some(Integrity::sha512(&bytes));
+
+        Self::write_lock(&package, &package_lock)
     }
 }
@@ -0,0 +1,122 @@
+use std::{
+    collections::HashSet,
+    env, fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use lazy_static::lazy_static;
+use log::warn;
+
+use crate::{
+    errors::CommandError,
+    integrity::Integrity,
+    lockfile::{LOCKFILE_PATH, TOML_LOCKFILE_PATH},
+    types::{CacheReferences, Lockfile},
+};
+
+pub type References = (HashSet<String>, HashSet<String>); // The packages and tarballs locked by recorded projects
+
+lazy_static! {
+    // Next to the package cache rather than in it, whose entries are all named `package@version`
+    static ref INDEX_PATH: PathBuf = dirs::cache_dir()
+        .expect("Failed to find cache directory")
+        .join("click-index.json");
+}
+
+/// Keeps track of the projects that link packages from the cache, so that `click cache verify` can tell which cached
+/// packages none of them use anymore. A project is recorded whenever its lockfile is written.
+pub struct CacheIndex;
+impl CacheIndex {
+    /// Returns an empty index if no project has been recorded yet.
+    pub fn read() -> Result<CacheReferences, CommandError> {
+        let index_raw = match fs::read_to_string(&*INDEX_PATH) {
+            Ok(index_raw) => index_raw,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(CacheReferences::default()),
+            Err(err) => return Err(CommandError::FailedToReadFile(err)),
+        };
+
+        serde_json::from_str::<CacheReferences>(&index_raw).map_err(|err| {
+            CommandError::CorruptCacheIndex(INDEX_PATH.display().to_string(), err.to_string())
+        })
+    }
+
+    /// The index is written to a temporary file first, so that a reader never sees half of it.
+    fn write(references: &CacheReferences) -> Result<(), io::Error> {
+        let index_string = serde_json::to_string_pretty(references)?;
+        let temporary_path = INDEX_PATH.with_extension(format!("{}.tmp", std::process::id()));
+
+        if let Some(parent) = INDEX_PATH.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&temporary_path, index_string + "\n")?;
+        fs::rename(&temporary_path, &*INDEX_PATH)
+    }
+
+    /// Records the project in the current directory. Failing to is only warned about, as the index is only used by
+    /// `click cache verify`, which does not remove anything while the index can't be read.
+    pub fn record_project() {
+        let project = match env::current_dir().and_then(|directory| directory.canonicalize()) {
+            Ok(project) => project.display().to_string(),
+            Err(err) => {
+                return warn!("the project could not be recorded in the cache index ({err})")
+            }
+        };
+
+        let mut references = match Self::read() {
+            Ok(references) => references,
+            Err(err) => {
+                return warn!("the project could not be recorded in the cache index ({err})")
+            }
+        };
+        if !references.projects.insert(project) {
+            return;
+        }
+
+        if let Err(err) = Self::write(&references) {
+            warn!("the project could not be recorded in the cache index ({err})");
+        }
+    }
+
+    /// Returns every package locked by a recorded project, formatted as package@version, and the tarballs they were
+    /// downloaded as, formatted as `<algorithm>/<hex digest>`. Projects whose lockfile is gone are forgotten.
+    /// Returns None if no project has been recorded, as then every cached package would look unused.
+    pub fn referenced() -> Result<Option<References>, CommandError> {
+        let mut references = Self::read()?;
+        let mut packages = HashSet::new();
+        let mut tarballs = HashSet::new();
+        let mut forgotten = Vec::new();
+
+        for project in references.projects.iter() {
+            let lockfile = match Lockfile::read_from(&Path::new(project).join(LOCKFILE_PATH))? {
+                Some(lockfile) => Some(lockfile),
+                None => Lockfile::read_from(&Path::new(project).join(TOML_LOCKFILE_PATH))?,
+            };
+            let Some(lockfile) = lockfile else {
+                forgotten.push(project.to_string());
+                continue;
+            };
+
+            for (package, locked_package) in lockfile.packages {
+                let hashes = locked_package
+                    .integrity
+                    .iter()
+                    .flat_map(|integrity| Integrity::hex_hashes(integrity));
+                tarballs.extend(hashes.map(|(algorithm, digest)| format!("{algorithm}/{digest}")));
+                packages.insert(package);
+            }
+        }
+
+        if !forgotten.is_empty() {
+            for project in forgotten {
+                references.projects.remove(&project);
+            }
+            Self::write(&references).map_err(CommandError::FailedToWriteFile)?;
+        }
+
+        match references.projects.is_empty() {
+            true => Ok(None),
+            false => Ok(Some((packages, tarballs))),
+        }
+    }
+}
@@ -0,0 +1,376 @@
+use std::collections::VecDeque;
+
+use crate::{config::Config, errors::ParseError, logger::Logger, output::Output};
+
+/// A flag a command accepts, shown in its `--help`.
+pub struct Flag {
+    pub names: &'static [&'static str], // Such as ["-D", "--save-dev"], commands match on the first long name
+    pub value: Option<&'static str>,    // Name of the value the flag takes, None if it takes none
+    pub description: &'static str,
+}
+
+impl Flag {
+    pub const fn new(names: &'static [&'static str], description: &'static str) -> Self {
+        Self {
+            names,
+            value: None,
+            description,
+        }
+    }
+
+    pub const fn with_value(
+        names: &'static [&'static str],
+        value: &'static str,
+        description: &'static str,
+    ) -> Self {
+        Self {
+            names,
+            value: Some(value),
+            description,
+        }
+    }
+
+    /// The name commands match the flag on, the first long name.
+    pub(crate) fn long_name(&self) -> &'static str {
+        self.names
+            .iter()
+            .find(|name| name.starts_with("--"))
+            .unwrap_or(&self.names[0])
+    }
+
+    /// Such as `-D, --save-dev` or `--registry <url>`.
+    fn signature(&self) -> String {
+        let names = self.names.join(", ");
+        match self.value {
+            Some(value) => format!("{names} <{value}>"),
+            None => names,
+        }
+    }
+}
+
+/// Options every command accepts, taken out before the command sees its arguments.
+pub const GLOBAL_FLAGS: &[Flag] = &[
+    Flag::new(&["-h", "--help"], "Print the help of the command"),
+    Flag::new(
+        &["-v", "--verbose"],
+        "Log what click is doing, such as each request to the registry, twice or -vv to log everything",
+    ),
+    Flag::new(&["-q", "--quiet"], "Only print errors"),
+    Flag::new(&["--json"], "Print the result as JSON, anything else goes to stderr"),
+    Flag::with_value(&["--registry"], "url", "Use this registry rather than the configured one"),
+];
+
+/// How a command is used, which its arguments are parsed with and its `--help` is made from.
+pub struct Usage {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub arguments: &'static str, // Shown after the command, such as `[package[@range]]...`
+    pub description: &'static str,
+    pub flags: &'static [Flag],
+    pub passthrough: bool, // Unknown flags are arguments, to be passed on such as to the script of `click run`
+}
+
+impl Usage {
+    pub fn matches(&self, command: &str) -> bool {
+        self.name == command || self.aliases.contains(&command)
+    }
+
+    /// The help of the command, printed with `click <command> --help`.
+    pub fn help(&self) -> String {
+        let mut help = format!("Usage: click {}", self.name);
+        if !self.arguments.is_empty() {
+            help.push_str(&format!(" {}", self.arguments));
+        }
+        if !self.flags.is_empty() {
+            help.push_str(" [options]");
+        }
+
+        help.push_str(&format!("\n\n{}\n", self.description));
+
+        if !self.aliases.is_empty() {
+            help.push_str(&format!("\nAliases: {}\n", self.aliases.join(", ")));
+        }
+
+        if !self.flags.is_empty() {
+            help.push_str(&format!("\nOptions:\n{}", Self::describe(self.flags)));
+        }
+
+        help.push_str(&format!(
+            "\nGlobal options:\n{}",
+            Self::describe(GLOBAL_FLAGS)
+        ));
+        help
+    }
+
+    /// The help of click itself, which lists every command.
+    pub fn overview(commands: &[&Usage]) -> String {
+        let width = commands
+            .iter()
+            .map(|usage| Self::names(usage).len())
+            .max()
+            .unwrap_or_default();
+
+        let mut overview = String::from("Usage: click <command> [options]\n\nCommands:\n");
+        for usage in commands {
+            overview.push_str(&format!(
+                "  {:width$}  {}\n",
+                Self::names(usage),
+                usage.description
+            ));
+        }
+
+        overview.push_str(&format!(
+            "\nGlobal options:\n{}\nRun `click <command> --help` for the options of a command.",
+            Self::describe(GLOBAL_FLAGS)
+        ));
+        overview
+    }
+
+    fn names(usage: &Usage) -> String {
+        [usage.name]
+            .iter()
+            .chain(usage.aliases)
+            .copied()
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Lists flags with their descriptions lined up.
+    fn describe(flags: &[Flag]) -> String {
+        let width = flags
+            .iter()
+            .map(|flag| flag.signature().len())
+            .max()
+            .unwrap_or_default();
+
+        flags
+            .iter()
+            .map(|flag| format!("  {:width$}  {}\n", flag.signature(), flag.description))
+            .collect()
+    }
+
+    fn flag(&self, name: &str) -> Option<&Flag> {
+        self.flags.iter().find(|flag| flag.names.contains(&name))
+    }
+}
+
+/// One argument of a command.
+#[derive(Debug, PartialEq)]
+pub enum Arg {
+    Flag(&'static str), // A flag of the command by its long name, whichever of its names was given
+    Positional(String),
+    Separator, // `--`, anything after it is positional
+}
+
+impl Arg {
+    /// The error for an argument the command does not expect where it was given.
+    pub fn unexpected(self) -> ParseError {
+        match self {
+            Arg::Flag(flag) => ParseError::UnknownFlag(flag.to_string()),
+            Arg::Positional(arg) => ParseError::UnexpectedArgument(arg),
+            Arg::Separator => ParseError::UnexpectedArgument(String::from("--")),
+        }
+    }
+}
+
+/// The arguments given after a command, checked against the flags in its usage. Flags that take a value accept it
+/// as the next argument or after `=`, such as `--registry=<url>`. Global options are handled here, wherever they are.
+pub struct Arguments {
+    usage: &'static Usage,
+    args: VecDeque<String>,
+    pending_value: Option<(&'static str, String)>, // Given after `=` to this flag, until the command reads it
+    separated: bool,                               // `--` has been given
+    positional: bool, // A positional argument has been given, which ends global options of passthrough commands
+}
+
+impl Arguments {
+    pub fn new(usage: &'static Usage, args: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            usage,
+            args: args.into_iter().collect(),
+            pending_value: None,
+            separated: false,
+            positional: false,
+        }
+    }
+
+    /// Whether `-h` or `--help` was given, which prints the help instead of running the command.
+    /// Passthrough commands only take it before their first argument, as it could be meant for a script.
+    pub fn wants_help(&self) -> bool {
+        self.args
+            .iter()
+            .take_while(|arg| *arg != "--")
+            .take_while(|arg| !self.usage.passthrough || arg.starts_with('-'))
+            .any(|arg| arg == "-h" || arg == "--help")
+    }
+
+    /// Returns the next argument, or None once there are none left. Fails on flags the command does not accept.
+    pub fn next(&mut self) -> Result<Option<Arg>, ParseError> {
+        if let Some((flag, value)) = self.pending_value.take() {
+            return Err(ParseError::InvalidArgument(flag.to_string(), value));
+        }
+
+        while let Some(arg) = self.args.pop_front() {
+            if self.separated {
+                return Ok(Some(Arg::Positional(arg)));
+            }
+
+            if arg == "--" {
+                self.separated = true;
+                return Ok(Some(Arg::Separator));
+            }
+
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(value)),
+                _ => (arg.as_str(), None),
+            };
+
+            // Passthrough commands take global options only before their first argument, later ones are passed on
+            let takes_global_options = !(self.usage.passthrough && self.positional);
+            if takes_global_options && self.global_option(name, value)? {
+                continue;
+            }
+
+            if let Some(flag) = self.usage.flag(name) {
+                if let Some(value) = value {
+                    if flag.value.is_none() {
+                        return Err(ParseError::InvalidArgument(
+                            flag.long_name().to_string(),
+                            value.to_string(),
+                        ));
+                    }
+                    self.pending_value = Some((flag.long_name(), value.to_string()));
+                }
+
+                return Ok(Some(Arg::Flag(flag.long_name())));
+            }
+
+            if arg.starts_with('-') && arg.len() > 1 && !self.usage.passthrough {
+                return Err(ParseError::UnknownFlag(arg));
+            }
+
+            self.positional = true;
+            return Ok(Some(Arg::Positional(arg)));
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the value of the flag just returned by next().
+    pub fn value(&mut self, flag: &str) -> Result<String, ParseError> {
+        if let Some((_, value)) = self.pending_value.take() {
+            return Ok(value);
+        }
+
+        let missing = || match self.usage.flag(flag) {
+            Some(flag) => ParseError::MissingArgument(flag.signature()),
+            None => ParseError::MissingArgument(flag.to_string()),
+        };
+
+        match self.args.front() {
+            Some(arg) if arg != "--" => Ok(self.args.pop_front().unwrap_or_default()),
+            _ => Err(missing()),
+        }
+    }
+
+    /// Takes every argument that is left as it is, for commands that pass them on.
+    pub fn rest(&mut self) -> Vec<String> {
+        self.args.drain(..).collect()
+    }
+
+    /// Handles the options every command accepts, returns false if the argument is not one of them.
+    fn global_option(&mut self, name: &str, value: Option<&str>) -> Result<bool, ParseError> {
+        match name {
+            "-h" | "--help" => {} // Handled before the command is parsed, see wants_help()
+            "--json" => Output::use_json(),
+            "--registry" => {
+                let registry = match value {
+                    Some(value) => value.to_string(),
+                    None => self
+                        .args
+                        .pop_front()
+                        .ok_or(ParseError::MissingArgument(String::from("url")))?,
+                };
+                Config::use_registry(registry);
+            }
+            name if value.is_none() && Logger::parse_flag(name) => {}
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USAGE: Usage = Usage {
+        name: "test",
+        aliases: &[],
+        arguments: "",
+        description: "",
+        flags: &[
+            Flag::new(&["-D", "--save-dev"], ""),
+            Flag::with_value(&["--depth"], "n", ""),
+        ],
+        passthrough: false,
+    };
+
+    fn arguments(args: &[&str]) -> Arguments {
+        Arguments::new(&USAGE, args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_flags_by_their_long_name() {
+        let mut args = arguments(&["-D", "react", "--depth=2", "--depth", "3"]);
+        assert_eq!(
+            args.next().expect("Valid arguments"),
+            Some(Arg::Flag("--save-dev"))
+        );
+        assert_eq!(
+            args.next().expect("Valid arguments"),
+            Some(Arg::Positional(String::from("react")))
+        );
+        assert_eq!(
+            args.next().expect("Valid arguments"),
+            Some(Arg::Flag("--depth"))
+        );
+        assert_eq!(
+            args.value("--depth").expect("Valid value"),
+            String::from("2")
+        );
+        assert_eq!(
+            args.next().expect("Valid arguments"),
+            Some(Arg::Flag("--depth"))
+        );
+        assert_eq!(
+            args.value("--depth").expect("Valid value"),
+            String::from("3")
+        );
+        assert_eq!(args.next().expect("Valid arguments"), None);
+    }
+
+    #[test]
+    fn rejects_unknown_flags_and_missing_values() {
+        assert!(arguments(&["--save-devv"]).next().is_err());
+        assert!(arguments(&["--save-dev=true"]).next().is_err());
+
+        let mut args = arguments(&["--depth"]);
+        assert_eq!(
+            args.next().expect("Valid arguments"),
+            Some(Arg::Flag("--depth"))
+        );
+        assert!(args.value("--depth").is_err());
+    }
+
+    #[test]
+    fn treats_everything_after_the_separator_as_positional() {
+        let mut args = arguments(&["--", "-D"]);
+        assert_eq!(args.next().expect("Valid arguments"), Some(Arg::Separator));
+        assert_eq!(
+            args.next().expect("Valid arguments"),
+            Some(Arg::Positional(String::from("-D")))
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use async_trait::async_trait;
+use log::warn;
+
+use crate::{
+    audit::Audit,
+    config::Config,
+    errors::{CommandError, ParseError},
+    http::NetworkMode,
+    installer::Installer,
+    manifest::Manifest,
+    output::Output,
+    transaction::Transaction,
+    types::{AuditFixResult, AuditSummary, Lockfile, Severity, Vulnerability},
+    versions::Versions,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+    install::InstallHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "audit",
+    aliases: &[],
+    arguments: "[fix]",
+    description:
+        "Check the packages in the lockfile for security advisories, exits with 1 if any are found. `click audit fix` bumps the vulnerable packages and installs again",
+    flags: &[
+        Flag::with_value(
+            &["--audit-level"],
+            "info|low|moderate|high|critical",
+            "Only report advisories of this severity or higher",
+        ),
+        Flag::with_value(
+            &["--audit-registry"],
+            "url",
+            "Ask this registry for advisories rather than the one packages are installed from",
+        ),
+        Flag::new(
+            &["--force"],
+            "With fix, allow bumps outside the ranges in package.json",
+        ),
+        Flag::new(
+            &["--summary-only"],
+            "Only print how many vulnerabilities there are of each severity",
+        ),
+    ],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct AuditHandler {
+    audit_level: Option<Severity>, // Advisories less severe than this are left out, set with --audit-level.
+    fix: bool, // Bump the vulnerable packages and install again, set with `click audit fix`.
+    force: bool, // Allow fixes that bump the ranges in package.json, set with --force.
+    audit_registry: Option<String>, // Where advisories are asked for, the registry if None. Set with --audit-registry.
+    summary_only: bool, // Print the counts of AuditSummary rather than every vulnerability, set with --summary-only.
+}
+
+impl AuditHandler {
+    async fn audit(
+        &self,
+        client: reqwest::Client,
+        lockfile: &Lockfile,
+    ) -> Result<Vec<Vulnerability>, CommandError> {
+        let registry = match &self.audit_registry {
+            Some(audit_registry) => audit_registry,
+            None => &Config::get()?.registry,
+        };
+        let mut vulnerabilities = Audit::run(client, registry, lockfile).await?;
+
+        if let Some(audit_level) = self.audit_level {
+            vulnerabilities.retain(|vulnerability| vulnerability.advisory.severity >= audit_level);
+        }
+
+        Ok(vulnerabilities)
+    }
+
+    /// Locks each package to the version that fixes it, bumping its range in package.json with --force, then installs
+    /// again the same way `click update` does and audits the new lockfile.
+    async fn fix(&self, client: reqwest::Client) -> Result<(), CommandError> {
+        let mut manifest = Manifest::read()?.ok_or(CommandError::NoManifest)?;
+        let mut lockfile = Lockfile::read()?;
+
+        let vulnerabilities = self.audit(client.clone(), &lockfile).await?;
+        if vulnerabilities.is_empty() {
+            Output::message("Found no vulnerabilities to fix");
+            return Output::json(&AuditFixResult {
+                fixed: Vec::new(),
+                remaining: Vec::new(),
+            });
+        }
+
+        let plan = Audit::plan_fixes(
+            client.clone(),
+            &manifest,
+            &lockfile,
+            &vulnerabilities,
+            self.force,
+        )
+        .await?;
+
+        let mut manifest_changed = false;
+        for (kind, fix) in plan.fixes.iter() {
+            Output::message(format!(
+                "Updating '{}' from {} to {}",
+                fix.name, fix.from, fix.to
+            ));
+
+            let (_, range) = manifest
+                .dependencies(kind.field())
+                .into_iter()
+                .find(|(package_name, _)| *package_name == fix.name)
+                .unwrap_or_default();
+            let semantic_version =
+                Installer::parse_range(client.clone(), &fix.name, &range, NetworkMode::Online)
+                    .await?;
+            let mut spec = Lockfile::spec(&fix.name, semantic_version.as_ref());
+
+            if fix.range != range {
+                lockfile.requested.remove(&spec);
+                let semantic_version = Versions::parse_semantic_version(&fix.range)
+                    .map_err(CommandError::InvalidPackageSpec)?;
+                spec = Lockfile::spec(&fix.name, Some(&semantic_version));
+
+                manifest.set_dependency(kind, &fix.name, fix.range.to_string());
+                manifest_changed = true;
+            }
+
+            lockfile
+                .requested
+                .insert(spec, Versions::stringify(&fix.name, &fix.to));
+        }
+
+        if !plan.fixes.is_empty() {
+            let transaction = Transaction::begin()?;
+            lockfile.write()?;
+            if manifest_changed {
+                manifest.write()?;
+            }
+
+            if let Err(err) = InstallHandler::nested().execute().await {
+                InstallHandler::rollback(transaction);
+                return Err(err);
+            }
+
+            // The vulnerable versions are no longer needed by anything
+            lockfile = Lockfile::read()?;
+            lockfile.prune();
+            lockfile.write()?;
+        }
+
+        for package in plan.unfixable.iter() {
+            match self.force {
+                true => warn!("no version of '{package}' fixes it"),
+                false => warn!("no version of '{package}' the ranges allow fixes it, try --force"),
+            }
+        }
+
+        let result = AuditFixResult {
+            fixed: plan.fixes.into_iter().map(|(_, fix)| fix).collect(),
+            remaining: self.audit(client, &lockfile).await?,
+        };
+        Output::json(&result)?;
+
+        let remaining = result.remaining;
+        if remaining.is_empty() {
+            Output::message("Fixed every vulnerability");
+            return Ok(());
+        }
+
+        if !Output::is_json() {
+            Self::print_vulnerabilities(&remaining);
+        }
+        Output::message(Self::summary(&remaining));
+
+        Err(CommandError::Vulnerabilities(remaining.len()))
+    }
+
+    /// Lists the vulnerabilities under a heading for each severity, most severe first.
+    fn print_vulnerabilities(vulnerabilities: &[Vulnerability]) {
+        for severity in Severity::ALL.into_iter().rev() {
+            let group = vulnerabilities
+                .iter()
+                .filter(|vulnerability| vulnerability.advisory.severity == severity)
+                .collect::<Vec<_>>();
+
+            if group.is_empty() {
+                continue;
+            }
+
+            println!("{} ({})", severity.as_str(), group.len());
+            for vulnerability in group {
+                println!(
+                    "  {}  {}",
+                    vulnerability.package, vulnerability.advisory.title
+                );
+                println!("    {}", vulnerability.advisory.url);
+                for path in vulnerability.paths.iter() {
+                    println!("    {path}");
+                }
+            }
+            println!();
+        }
+    }
+
+    /// Every severity, including those no vulnerability has.
+    fn counts(vulnerabilities: &[Vulnerability]) -> AuditSummary {
+        let severities = Severity::ALL
+            .into_iter()
+            .map(|severity| {
+                let count = vulnerabilities
+                    .iter()
+                    .filter(|vulnerability| vulnerability.advisory.severity == severity)
+                    .count();
+                (severity, count)
+            })
+            .collect();
+
+        AuditSummary {
+            vulnerabilities: vulnerabilities.len(),
+            severities,
+        }
+    }
+
+    /// Such as `Found 3 vulnerability(s) (1 critical, 2 moderate)`.
+    fn summary(vulnerabilities: &[Vulnerability]) -> String {
+        let counts = Self::counts(vulnerabilities)
+            .severities
+            .into_iter()
+            .rev()
+            .filter(|(_, count)| *count > 0)
+            .map(|(severity, count)| format!("{count} {}", severity.as_str()))
+            .collect::<Vec<_>>();
+
+        format!(
+            "Found {} vulnerability(s) ({})",
+            vulnerabilities.len(),
+            counts.join(", ")
+        )
+    }
+}
+
+#[async_trait]
+impl CommandHandler for AuditHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--audit-level") => {
+                    let level = args.value("--audit-level")?;
+                    self.audit_level = Some(Severity::parse(&level).ok_or(
+                        ParseError::InvalidArgument(String::from("--audit-level"), level),
+                    )?);
+                }
+                Arg::Flag("--audit-registry") => {
+                    let audit_registry = args.value("--audit-registry")?;
+                    if reqwest::Url::parse(&audit_registry).is_err() {
+                        return Err(ParseError::InvalidArgument(
+                            String::from("--audit-registry"),
+                            audit_registry,
+                        ));
+                    }
+
+                    self.audit_registry = Some(audit_registry.trim_end_matches('/').to_string());
+                }
+                Arg::Flag("--force") => self.force = true,
+                Arg::Flag("--summary-only") => self.summary_only = true,
+                Arg::Positional(command) if command == "fix" && !self.fix => self.fix = true,
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        // The install of `click audit fix` uses the same config, as it is only loaded once
+        Config::load()?;
+
+        let client = Config::client()?;
+        if self.fix {
+            return self.fix(client).await;
+        }
+
+        let lockfile = Lockfile::read()?;
+        let vulnerabilities = self.audit(client, &lockfile).await?;
+
+        match self.summary_only {
+            true => Output::json(&Self::counts(&vulnerabilities))?,
+            false => Output::json(&vulnerabilities)?,
+        }
+
+        if vulnerabilities.is_empty() {
+            Output::message(format!(
+                "Found no vulnerabilities in {} package(s)",
+                lockfile.packages.len()
+            ));
+            return Ok(());
+        }
+
+        if !Output::is_json() && !self.summary_only {
+            Self::print_vulnerabilities(&vulnerabilities);
+        }
+        Output::message(Self::summary(&vulnerabilities));
+
+        Err(CommandError::Vulnerabilities(vulnerabilities.len()))
+    }
+}
@@ -0,0 +1,343 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    config::Config,
+    errors::{CommandError, ParseError},
+    manifest::PACKAGE_JSON_PATH,
+    output::Output,
+    types::{BenchResult, BenchTiming},
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "bench",
+    aliases: &["benchmark"],
+    arguments: "",
+    description:
+        "Time installs of the project with a cold cache, a warm cache and from the lockfile, in a copy of it",
+    flags: &[
+        Flag::with_value(&["--runs"], "n", "How many times each install is run"),
+        Flag::new(
+            &["--compare"],
+            "Also time npm, yarn and pnpm, those of them that are on PATH",
+        ),
+    ],
+    passthrough: false,
+};
+
+const DEFAULT_RUNS: usize = 3;
+const SCENARIOS: [&str; 3] = ["cold cache", "warm cache", "lockfile"];
+
+// Copied into the benchmarked project and into the home of every install, so they use the same registry and auth
+const RC_FILES: [&str; 4] = [".npmrc", ".clickrc", ".yarnrc", ".yarnrc.yml"];
+
+/// A package manager to time, and where it keeps what it installs from.
+struct Tool {
+    name: &'static str,
+    program: PathBuf,
+    version: String,
+    args: &'static [&'static str],
+    lockfile: &'static str,
+}
+
+impl Tool {
+    fn click() -> Result<Self, CommandError> {
+        Ok(Self {
+            name: "click",
+            program: env::current_exe().map_err(CommandError::ComandFailedError)?,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            args: &["install"],
+            lockfile: "click-lock.json",
+        })
+    }
+
+    /// The other package managers, those of them that are on PATH.
+    fn others() -> Vec<Result<Self, &'static str>> {
+        [
+            (
+                "npm",
+                &["install", "--no-audit", "--no-fund"][..],
+                "package-lock.json",
+            ),
+            ("yarn", &["install"][..], "yarn.lock"),
+            ("pnpm", &["install"][..], "pnpm-lock.yaml"),
+        ]
+        .into_iter()
+        .map(|(name, args, lockfile)| {
+            let program = PathBuf::from(if cfg!(windows) {
+                format!("{name}.cmd")
+            } else {
+                name.to_string()
+            });
+
+            Command::new(&program)
+                .arg("--version")
+                .stdin(Stdio::null())
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .map(|output| Self {
+                    name,
+                    program,
+                    version: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+                    args,
+                    lockfile,
+                })
+                .ok_or(name)
+        })
+        .collect()
+    }
+
+    /// Runs an install in the project, with `home` as the home directory and every cache in it. Nothing it prints is
+    /// shown, only whether it succeeded.
+    fn install(&self, project: &Path, home: &Path) -> Result<Duration, CommandError> {
+        let mut command = Command::new(&self.program);
+        command
+            .args(self.args)
+            .current_dir(project)
+            .env("HOME", home)
+            .env("USERPROFILE", home)
+            .env("XDG_CACHE_HOME", home.join(".cache"))
+            .env("XDG_DATA_HOME", home.join(".local/share"))
+            .env("npm_config_cache", home.join(".npm"))
+            .env("YARN_CACHE_FOLDER", home.join(".yarn-cache"))
+            .env("npm_config_store_dir", home.join(".pnpm-store"))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        if self.name == "click" {
+            command.args(["--registry", &Config::get()?.registry]);
+        }
+
+        let started = Instant::now();
+        let status = command.status().map_err(CommandError::ComandFailedError)?;
+        if !status.success() {
+            return Err(CommandError::ExecutableFailed(
+                self.name.to_string(),
+                status.code().unwrap_or(1),
+            ));
+        }
+
+        Ok(started.elapsed())
+    }
+}
+
+#[derive(Default)]
+pub struct BenchHandler {
+    runs: Option<usize>, // How many times each install is timed, DEFAULT_RUNS if None.
+    compare: bool,       // Also time the other package managers on PATH, set with --compare.
+}
+
+impl BenchHandler {
+    /// Copies package.json and the rc files of the project to a directory of its own, so that installing there leaves
+    /// node_modules and the lockfile of the project as they are.
+    fn copy_project(project: &Path) -> Result<(), CommandError> {
+        fs::create_dir_all(project).map_err(CommandError::FailedToCreateFile)?;
+        fs::copy(PACKAGE_JSON_PATH, project.join("package.json"))
+            .map_err(CommandError::FailedToCreateFile)?;
+        Self::copy_rc_files(Path::new("."), project)
+    }
+
+    fn copy_rc_files(from: &Path, to: &Path) -> Result<(), CommandError> {
+        for rc_file in RC_FILES {
+            if from.join(rc_file).is_file() {
+                fs::copy(from.join(rc_file), to.join(rc_file))
+                    .map_err(CommandError::FailedToCreateFile)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A home directory for installs, with nothing cached in it yet.
+    fn create_home(home: &Path) -> Result<(), CommandError> {
+        if home.exists() {
+            fs::remove_dir_all(home).map_err(CommandError::FailedToWriteFile)?;
+        }
+        fs::create_dir_all(home).map_err(CommandError::FailedToCreateFile)?;
+
+        match dirs::home_dir() {
+            Some(user_home) => Self::copy_rc_files(&user_home, home),
+            None => Ok(()),
+        }
+    }
+
+    fn remove(path: &Path) -> Result<(), CommandError> {
+        let removed = match path.is_dir() {
+            true => fs::remove_dir_all(path),
+            false => fs::remove_file(path),
+        };
+
+        match removed {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(CommandError::FailedToWriteFile(err))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Every cold install starts from an empty home, and the last one leaves the cache warm for the rest. Warm installs
+    /// resolve the project again, while the lockfile left by the last of them is installed from by the rest.
+    fn bench(&self, tool: &Tool, directory: &Path) -> Result<Vec<BenchTiming>, CommandError> {
+        let runs = self.runs.unwrap_or(DEFAULT_RUNS);
+        let project = directory.join("project");
+        let home = directory.join("home");
+        let node_modules = project.join("node_modules");
+        let lockfile = project.join(tool.lockfile);
+        Self::copy_project(&project)?;
+
+        let mut timings = Vec::new();
+        for scenario in SCENARIOS {
+            Output::message(format!("Timing {} {scenario} installs..", tool.name));
+
+            let mut durations = Vec::new();
+            for _ in 0..runs {
+                if scenario == "cold cache" {
+                    Self::create_home(&home)?;
+                }
+                if scenario != "lockfile" {
+                    Self::remove(&lockfile)?;
+                }
+                Self::remove(&node_modules)?;
+
+                durations.push(tool.install(&project, &home)?.as_millis());
+            }
+
+            timings.push(BenchTiming {
+                tool: tool.name.to_string(),
+                version: tool.version.to_string(),
+                scenario: scenario.to_string(),
+                mean_ms: durations.iter().sum::<u128>() / durations.len() as u128,
+                min_ms: durations.iter().copied().min().unwrap_or_default(),
+                max_ms: durations.iter().copied().max().unwrap_or_default(),
+                runs_ms: durations,
+            });
+        }
+
+        Ok(timings)
+    }
+
+    fn format_duration(ms: u128) -> String {
+        match ms {
+            ms if ms < 1000 => format!("{ms}ms"),
+            ms => format!("{:.2}s", ms as f64 / 1000.0),
+        }
+    }
+
+    fn print_table(result: &BenchResult) {
+        let mut rows = vec![std::iter::once(String::new())
+            .chain(SCENARIOS.iter().map(|scenario| scenario.to_string()))
+            .collect::<Vec<_>>()];
+
+        for timings in result.timings.chunks(SCENARIOS.len()) {
+            let Some(first) = timings.first() else {
+                continue;
+            };
+
+            rows.push(
+                std::iter::once(format!("{} {}", first.tool, first.version))
+                    .chain(timings.iter().map(|timing| {
+                        format!(
+                            "{} ({} .. {})",
+                            Self::format_duration(timing.mean_ms),
+                            Self::format_duration(timing.min_ms),
+                            Self::format_duration(timing.max_ms)
+                        )
+                    }))
+                    .collect(),
+            );
+        }
+
+        let widths = (0..=SCENARIOS.len())
+            .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+            .collect::<Vec<_>>();
+        for row in rows {
+            let cells = row
+                .iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect::<Vec<_>>();
+            Output::message(cells.join("  ").trim_end());
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler for BenchHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--compare") => self.compare = true,
+                Arg::Flag("--runs") => {
+                    let runs = args.value("--runs")?;
+
+                    self.runs = match runs.parse::<usize>() {
+                        Ok(runs) if runs > 0 => Some(runs),
+                        _ => return Err(ParseError::InvalidArgument(String::from("--runs"), runs)),
+                    };
+                }
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Installs are run in a copy of the project in the temporary directory, each package manager in its own, and with
+    /// the home directory pointed elsewhere so that no cache of the user is used or changed. Only package.json and the
+    /// rc files are copied, so dependencies on other directories of the project can't be installed by the benchmark.
+    async fn execute(&self) -> Result<(), CommandError> {
+        if !Path::new(PACKAGE_JSON_PATH).exists() {
+            return Err(CommandError::NoManifest);
+        }
+
+        let mut tools = vec![Tool::click()?];
+        let mut skipped = Vec::new();
+        if self.compare {
+            for tool in Tool::others() {
+                match tool {
+                    Ok(tool) => tools.push(tool),
+                    Err(name) => skipped.push(name.to_string()),
+                }
+            }
+        }
+        if !skipped.is_empty() {
+            Output::message(format!("Skipping {}, not on PATH", skipped.join(", ")));
+        }
+
+        let directory = env::temp_dir().join(format!("click-bench-{}", process::id()));
+        let timings = tools
+            .iter()
+            .map(|tool| self.bench(tool, &directory.join(tool.name)))
+            .collect::<Result<Vec<_>, _>>();
+        let _ = fs::remove_dir_all(&directory);
+
+        let result = BenchResult {
+            runs: self.runs.unwrap_or(DEFAULT_RUNS),
+            timings: timings?.into_iter().flatten().collect(),
+            skipped,
+        };
+
+        if Output::is_json() {
+            return Output::json(&result);
+        }
+
+        Output::message(format!(
+            "Mean time of {} install(s), with the fastest and slowest:",
+            result.runs
+        ));
+        Self::print_table(&result);
+        Ok(())
+    }
+}
@@ -0,0 +1,285 @@
+use std::{fs, sync::Arc};
+
+use async_trait::async_trait;
+use log::warn;
+
+use crate::{
+    cache::Cache,
+    cache_index::CacheIndex,
+    config::Config,
+    errors::{CommandError, ParseError},
+    http::NetworkMode,
+    installer::{CacheOptions, Installer, PackageInfo, DEFAULT_CONCURRENCY},
+    metadata_cache::MetadataCache,
+    output::Output,
+    platform::Platform,
+    reporter::SilentReporter,
+    tarball_cache::TarballCache,
+    types::{CacheAddResult, CacheVerifyResult},
+    util::Size,
+    versions::{Strategy, Versions},
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "cache",
+    aliases: &[],
+    arguments: "add <package[@range]>...|clean|verify",
+    description: "Add packages to the cache without installing them, or clean the download caches. verify rehashes every cached tarball and package, removing those that are corrupt or that no installed project uses",
+    flags: &[Flag::with_value(
+        &["--from-file"],
+        "path",
+        "Add the specs listed in this file, one per line",
+    )],
+    passthrough: false,
+};
+
+#[derive(Default)]
+enum CacheSubcommand {
+    #[default]
+    Add,
+    Clean,
+    Verify,
+}
+
+#[derive(Default)]
+pub struct CacheHandler {
+    subcommand: CacheSubcommand,
+    package_specs: Vec<String>, // Each spec is formatted as package@version
+    spec_file: Option<String>,  // File with one package spec per line.
+}
+
+impl CacheHandler {
+    /// Blank lines and lines starting with # are ignored.
+    fn read_spec_file(path: &String) -> Result<Vec<String>, CommandError> {
+        let specs_raw = fs::read_to_string(path).map_err(CommandError::FailedToReadFile)?;
+
+        Ok(specs_raw
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.to_string())
+            .collect())
+    }
+
+    fn parse_add(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--from-file") => self.spec_file = Some(args.value("--from-file")?),
+                Arg::Positional(package_spec) => self.package_specs.push(package_spec),
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        if self.package_specs.is_empty() && self.spec_file.is_none() {
+            return Err(ParseError::MissingArgument(String::from("package name")));
+        }
+
+        Ok(())
+    }
+
+    async fn add_all(&self) -> Result<(), CommandError> {
+        Config::load()?;
+        let client = Config::client()?;
+
+        let mut package_specs = self.package_specs.clone();
+        if let Some(spec_file) = &self.spec_file {
+            package_specs.extend(Self::read_spec_file(spec_file)?);
+        }
+
+        let mut result = CacheAddResult::default();
+        for package_spec in package_specs {
+            match Self::add(client.clone(), package_spec).await? {
+                (package, true) => result.added.push(package),
+                (package, false) => result.skipped.push(package),
+            }
+        }
+
+        Output::message(format!(
+            "{} added to the cache, {} skipped",
+            result.added.len(),
+            result.skipped.len()
+        ));
+
+        Output::json(&result)
+    }
+
+    /// Download caches are removed, extracted packages are kept as node_modules of projects link to them.
+    async fn clean() -> Result<(), CommandError> {
+        let tarballs_removed = TarballCache::clean()
+            .await
+            .map_err(CommandError::FailedToCleanCache)?;
+        let packuments_removed = MetadataCache::clean()
+            .await
+            .map_err(CommandError::FailedToCleanCache)?;
+
+        let removed = tarballs_removed || packuments_removed;
+        match removed {
+            true => Output::message("Removed cached tarballs and packuments"),
+            false => Output::message("The cache is already empty"),
+        }
+
+        Output::json(&serde_json::json!({ "removed": removed }))
+    }
+
+    /// Corrupt tarballs and packages are removed so that the next install downloads them again. Packages and tarballs
+    /// that no project in the cache index locks are then removed, unless no project has been recorded yet.
+    async fn verify() -> Result<(), CommandError> {
+        let tarballs = TarballCache::verify()
+            .await
+            .map_err(CommandError::FailedToVerifyCache)?;
+        let packages = Cache::verify_packages()?;
+
+        let mut result = CacheVerifyResult {
+            verified: tarballs.verified + packages.verified,
+            reclaimed: tarballs.reclaimed + packages.reclaimed,
+            ..Default::default()
+        };
+        result.corrupt = tarballs
+            .removed
+            .into_iter()
+            .chain(packages.removed)
+            .collect();
+        for entry in result.corrupt.iter() {
+            Output::message(format!("Removed corrupt '{entry}'"));
+        }
+
+        match CacheIndex::referenced()? {
+            Some((referenced_packages, referenced_tarballs)) => {
+                let (removed_packages, package_bytes) =
+                    Cache::remove_unreferenced(&referenced_packages)?;
+                let (removed_tarballs, tarball_bytes) =
+                    TarballCache::remove_unreferenced(&referenced_tarballs)
+                        .await
+                        .map_err(CommandError::FailedToVerifyCache)?;
+
+                result.reclaimed += package_bytes + tarball_bytes;
+                result.unreferenced = removed_packages.into_iter().chain(removed_tarballs).collect();
+                for entry in result.unreferenced.iter() {
+                    Output::message(format!("Removed unused '{entry}'"));
+                }
+            }
+            None => warn!("no project has been installed since the cache index was added, so unused packages are kept"),
+        }
+
+        Output::message(format!(
+            "{} verified, {} corrupt and {} unused removed, {} reclaimed",
+            result.verified,
+            result.corrupt.len(),
+            result.unreferenced.len(),
+            Size(result.reclaimed)
+        ));
+
+        Output::json(&result)
+    }
+
+    /// Downloads the package and its dependencies into the cache without linking them into node_modules.
+    /// Returns the package formatted as package@version, and false if it was already cached. The version is resolved
+    /// from the registry first, as the cache can't tell whether what it has is still the newest the spec allows.
+    async fn add(
+        client: reqwest::Client,
+        package_spec: String,
+    ) -> Result<(String, bool), CommandError> {
+        let (package_name, semantic_version) =
+            Installer::parse_package_spec(client.clone(), package_spec, NetworkMode::Online)
+                .await?;
+
+        let semantic_version = semantic_version.as_ref();
+        let full_version = Versions::resolve_full_version(semantic_version);
+        let full_version = full_version.as_ref();
+
+        let version_data = Installer::get_version_data(
+            client.clone(),
+            &package_name,
+            full_version,
+            semantic_version,
+            NetworkMode::Online,
+        )
+        .await?;
+
+        let stringified = Versions::stringify(&version_data.name, &version_data.version);
+
+        let (is_cached, _) =
+            Cache::exists(&package_name, Some(&version_data.version), None).await?;
+        if is_cached {
+            Output::message(format!("Skipped '{stringified}' (already cached)"));
+            return Ok((stringified, false));
+        }
+
+        let package_info = PackageInfo {
+            version_data,
+            is_latest: Versions::is_latest(full_version),
+            stringified: stringified.to_string(),
+        };
+
+        let options = CacheOptions {
+            integrity_allowlist: None,
+            resumable: false,
+            keep_going: false,
+            lockfile: None,
+            concurrency: DEFAULT_CONCURRENCY,
+            network_mode: NetworkMode::Online,
+            legacy_peer_deps: false,
+            platform: Platform::default(),
+            engine_strict: false,
+            strict_signatures: false,
+            reporter: Arc::new(SilentReporter),
+            strategy: Strategy::Highest,
+        };
+
+        let outcome = Installer::cache_package(client, package_info, options).await;
+        if let Some(err) = outcome.errors.into_iter().next() {
+            return Err(err);
+        }
+
+        Output::message(format!(
+            "Added '{}' ({} packages downloaded)",
+            stringified,
+            outcome.downloaded.len()
+        ));
+
+        Ok((stringified, true))
+    }
+}
+
+#[async_trait]
+impl CommandHandler for CacheHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        let subcommand = match args.next()? {
+            Some(Arg::Positional(subcommand)) => subcommand,
+            Some(arg) => return Err(arg.unexpected()),
+            None => {
+                return Err(ParseError::MissingArgument(String::from(
+                    "cache subcommand",
+                )))
+            }
+        };
+
+        self.subcommand = match subcommand.as_str() {
+            "add" => CacheSubcommand::Add,
+            "clean" => CacheSubcommand::Clean,
+            "verify" => CacheSubcommand::Verify,
+            _ => return Err(ParseError::SubcommandNotFound(subcommand)),
+        };
+
+        match self.subcommand {
+            CacheSubcommand::Add => self.parse_add(args),
+            CacheSubcommand::Clean | CacheSubcommand::Verify => match args.next()? {
+                Some(arg) => Err(arg.unexpected()),
+                None => Ok(()),
+            },
+        }
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        match self.subcommand {
+            CacheSubcommand::Add => self.add_all().await,
+            CacheSubcommand::Clean => Self::clean().await,
+            CacheSubcommand::Verify => Self::verify().await,
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::{fs, io::ErrorKind};
+
+use async_trait::async_trait;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    installer::MODULES_DIRECTORY,
+    output::Output,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+    install::InstallHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "ci",
+    aliases: &["clean-install"],
+    arguments: "",
+    description:
+        "Remove node_modules and install exactly the versions in the lockfile, which has to match package.json",
+    flags: &[
+        Flag::new(
+            &["--production"],
+            "Skip devDependencies, also set by NODE_ENV=production",
+        ),
+        Flag::new(
+            &["--ignore-scripts"],
+            "Do not run the lifecycle scripts of packages",
+        ),
+        Flag::new(
+            &["--trusted-scripts"],
+            "Only run the scripts of packages in trustedDependencies",
+        ),
+    ],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct CiHandler {
+    production: bool,      // Skip devDependencies, also enabled by NODE_ENV=production.
+    ignore_scripts: bool,  // Do not run the lifecycle scripts of installed packages.
+    trusted_scripts: bool, // Only run the lifecycle scripts of packages in trustedDependencies.
+}
+
+impl CiHandler {
+    fn remove_modules() -> Result<(), CommandError> {
+        match fs::remove_dir_all(MODULES_DIRECTORY) {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                Err(CommandError::FailedToRemoveModules(err))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler for CiHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--production") => self.production = true,
+                Arg::Flag("--ignore-scripts") => self.ignore_scripts = true,
+                Arg::Flag("--trusted-scripts") => self.trusted_scripts = true,
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The lockfile is checked against package.json before node_modules is removed, so that a lockfile that is out of
+    /// date leaves node_modules as it was. Nothing is resolved, every range is installed at the version it is locked to.
+    async fn execute(&self) -> Result<(), CommandError> {
+        let install_handler =
+            InstallHandler::frozen(self.production, self.ignore_scripts, self.trusted_scripts);
+        install_handler.locked_packages()?;
+
+        Self::remove_modules()?;
+        Output::message("Removed node_modules, installing from the lockfile..");
+
+        install_handler.execute().await
+    }
+}
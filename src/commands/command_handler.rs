@@ -1,43 +1,211 @@
 use async_trait::async_trait;
 use std::env::Args;
 
-use crate::errors::{
-    CommandError,
-    ParseError::{self, CommandNotFound},
+use crate::{
+    config::Config,
+    errors::{
+        ClickError, CommandError,
+        ParseError::{self, CommandNotFound},
+    },
+    logger::Logger,
+    output::Output,
+    suggest::Suggestions,
 };
 
-use super::install::InstallHandler;
-use super::exec::RunFileHandler;
+use super::arguments::{Arguments, Usage};
+use super::audit::{self, AuditHandler};
+use super::bench::{self, BenchHandler};
+use super::cache::{self, CacheHandler};
+use super::ci::{self, CiHandler};
+use super::completions::{self, CompletionsHandler};
+use super::config::{self, ConfigHandler};
+use super::create::{self, CreateHandler};
+use super::dedupe::{self, DedupeHandler};
+use super::doctor::{self, DoctorHandler};
+use super::du::{self, DiskUsageHandler};
+use super::exec::{self, RunFileHandler};
+use super::graph::{self, GraphHandler};
+use super::info::{self, InfoHandler};
+use super::init::{self, InitHandler};
+use super::install::{self, InstallHandler};
+use super::licenses::{self, LicensesHandler};
+use super::list::{self, ListHandler};
+use super::login::{self, LoginHandler};
+use super::logout::{self, LogoutHandler};
+use super::outdated::{self, OutdatedHandler};
+use super::pack::{self, PackHandler};
+use super::patch::{self, PatchHandler};
+use super::patch_commit::{self, PatchCommitHandler};
+use super::prune::{self, PruneHandler};
+use super::publish::{self, PublishHandler};
+use super::rebuild::{self, RebuildHandler};
+use super::resolve::{self, ResolveHandler};
+use super::run::{self, RunScriptHandler};
+use super::sbom::{self, SbomHandler};
+use super::search::{self, SearchHandler};
+use super::self_update::{self, SelfUpdateHandler};
+use super::uninstall::{self, UninstallHandler};
+use super::update::{self, UpdateHandler};
+use super::verify::{self, VerifyHandler};
+use super::version::{self, VersionHandler};
+use super::why::{self, WhyHandler};
+
+// In the order they are listed by `click --help`
+pub const COMMANDS: [&Usage; 36] = [
+    &init::USAGE,
+    &create::USAGE,
+    &install::USAGE,
+    &ci::USAGE,
+    &uninstall::USAGE,
+    &update::USAGE,
+    &dedupe::USAGE,
+    &prune::USAGE,
+    &outdated::USAGE,
+    &audit::USAGE,
+    &licenses::USAGE,
+    &sbom::USAGE,
+    &list::USAGE,
+    &why::USAGE,
+    &graph::USAGE,
+    &du::USAGE,
+    &search::USAGE,
+    &info::USAGE,
+    &run::USAGE,
+    &exec::USAGE,
+    &rebuild::USAGE,
+    &patch::USAGE,
+    &patch_commit::USAGE,
+    &version::USAGE,
+    &pack::USAGE,
+    &publish::USAGE,
+    &login::USAGE,
+    &logout::USAGE,
+    &config::USAGE,
+    &cache::USAGE,
+    &resolve::USAGE,
+    &verify::USAGE,
+    &doctor::USAGE,
+    &self_update::USAGE,
+    &bench::USAGE,
+    &completions::USAGE,
+];
 
 #[async_trait]
 pub trait CommandHandler {
-    fn parse(&mut self, args: &mut Args) -> Result<(), ParseError>;
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError>;
     async fn execute(&self) -> Result<(), CommandError>;
 }
 
-pub async fn handle_args(mut args: Args) -> Result<(), ParseError> {
+/// Parses the arguments and runs the command they give, returning the error that click should exit with.
+pub async fn handle_args(mut args: Args) -> Result<(), ClickError> {
     args.next(); // Remove initial binary argument
 
-    let command = match args.next() {
-        Some(command) => command,
-        None => {
-            println!("Use: click <command> [options]\n  click install <package_name> [semver]\n  click exec <file name>");
+    // Global options can also come before the command, such as `click -v install`
+    let mut command = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => Output::use_json(),
+            "--registry" => Config::use_registry(
+                args.next()
+                    .ok_or(ParseError::MissingArgument(String::from("url")))?,
+            ),
+            flag if flag.starts_with("--registry=") => {
+                Config::use_registry(flag.trim_start_matches("--registry=").to_string())
+            }
+            flag if Logger::parse_flag(flag) => {}
+            _ => {
+                command = Some(arg);
+                break;
+            }
+        }
+    }
+
+    let command = match command.as_deref() {
+        None | Some("-h" | "--help") => {
+            println!("{}", Usage::overview(&COMMANDS));
             return Ok(());
         }
+        // `click help <command>` is the same as `click <command> --help`
+        Some("help") => match args.next() {
+            Some(command) => {
+                println!("{}", find_usage(&command)?.help());
+                return Ok(());
+            }
+            None => {
+                println!("{}", Usage::overview(&COMMANDS));
+                return Ok(());
+            }
+        },
+        Some(command) => command.to_lowercase(),
     };
 
-    let mut command_handler: Box<dyn CommandHandler> = match command.to_lowercase().as_str() {
+    let usage = find_usage(&command)?;
+    Config::use_command(usage.name);
+
+    // Flags set in the config come first, so that those on the command line take precedence
+    let config_args = Config::command_args(usage)?;
+    let mut arguments = Arguments::new(usage, config_args.into_iter().chain(args));
+    if arguments.wants_help() {
+        println!("{}", usage.help());
+        return Ok(());
+    }
+
+    let mut command_handler: Box<dyn CommandHandler> = match usage.name {
+        "init" => Box::<InitHandler>::default(),
+        "create" => Box::<CreateHandler>::default(),
         "install" => Box::<InstallHandler>::default(),
+        "ci" => Box::<CiHandler>::default(),
+        "uninstall" => Box::<UninstallHandler>::default(),
+        "update" => Box::<UpdateHandler>::default(),
+        "dedupe" => Box::<DedupeHandler>::default(),
+        "prune" => Box::<PruneHandler>::default(),
+        "outdated" => Box::<OutdatedHandler>::default(),
+        "audit" => Box::<AuditHandler>::default(),
+        "licenses" => Box::<LicensesHandler>::default(),
+        "sbom" => Box::<SbomHandler>::default(),
+        "graph" => Box::<GraphHandler>::default(),
+        "du" => Box::<DiskUsageHandler>::default(),
+        "version" => Box::<VersionHandler>::default(),
+        "doctor" => Box::<DoctorHandler>::default(),
+        "list" => Box::<ListHandler>::default(),
+        "why" => Box::<WhyHandler>::default(),
+        "search" => Box::<SearchHandler>::default(),
+        "info" => Box::<InfoHandler>::default(),
+        "run" => Box::<RunScriptHandler>::default(),
         "exec" => Box::<RunFileHandler>::default(),
-        _ => return Err(CommandNotFound(command.to_string())),
+        "rebuild" => Box::<RebuildHandler>::default(),
+        "patch" => Box::<PatchHandler>::default(),
+        "patch-commit" => Box::<PatchCommitHandler>::default(),
+        "pack" => Box::<PackHandler>::default(),
+        "publish" => Box::<PublishHandler>::default(),
+        "login" => Box::<LoginHandler>::default(),
+        "logout" => Box::<LogoutHandler>::default(),
+        "config" => Box::<ConfigHandler>::default(),
+        "cache" => Box::<CacheHandler>::default(),
+        "resolve" => Box::<ResolveHandler>::default(),
+        "verify" => Box::<VerifyHandler>::default(),
+        "self-update" => Box::<SelfUpdateHandler>::default(),
+        "bench" => Box::<BenchHandler>::default(),
+        "completions" => Box::<CompletionsHandler>::default(),
+        _ => unreachable!("Every command in COMMANDS has a handler"),
     };
 
-    command_handler.parse(&mut args)?;
-    let command_result = command_handler.execute().await;
-
-    if let Err(e) = command_result {
-        println!("Command error: {e}");
-    }
+    command_handler.parse(&mut arguments)?;
+    command_handler.execute().await?;
 
     Ok(())
 }
+
+fn find_usage(command: &str) -> Result<&'static Usage, ParseError> {
+    COMMANDS
+        .into_iter()
+        .find(|usage| usage.matches(command))
+        .ok_or_else(|| {
+            let names = COMMANDS.iter().flat_map(|usage| {
+                [usage.name]
+                    .into_iter()
+                    .chain(usage.aliases.iter().copied())
+            });
+            CommandNotFound(command.to_string(), Suggestions::closest(command, names))
+        })
+}
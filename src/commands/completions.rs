@@ -0,0 +1,348 @@
+use async_trait::async_trait;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    installer::Installer,
+    manifest::Manifest,
+    scripts::Scripts,
+    versions::Versions,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage, GLOBAL_FLAGS},
+    command_handler::{CommandHandler, COMMANDS},
+};
+
+pub const USAGE: Usage = Usage {
+    name: "completions",
+    aliases: &[],
+    arguments: "<bash|zsh|fish|powershell>",
+    description: "Print a completion script for a shell, such as `source <(click completions bash)`",
+    flags: &[Flag::with_value(
+        &["--list"],
+        "packages|scripts",
+        "Print the installed packages or the scripts in package.json, which the completion scripts use",
+    )],
+    passthrough: false,
+};
+
+#[derive(Default, Clone, Copy)]
+enum Shell {
+    #[default]
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// Names the completion scripts ask click for while completing, as they would be slow or awkward to find in a shell.
+#[derive(Clone, Copy)]
+enum CompletionList {
+    Packages, // Installed packages, for `click uninstall`
+    Scripts,  // Scripts in package.json, for `click run`
+}
+
+#[derive(Default)]
+pub struct CompletionsHandler {
+    shell: Shell,
+    list: Option<CompletionList>, // Print these names rather than a completion script, set with --list.
+}
+
+impl CompletionsHandler {
+    /// The commands that complete names from a list, along with the list.
+    fn dynamic_list(usage: &Usage) -> Option<&'static str> {
+        match usage.name {
+            "uninstall" | "rebuild" => Some("packages"),
+            "run" => Some("scripts"),
+            _ => None,
+        }
+    }
+
+    /// Every name a command and its aliases can be given as.
+    fn command_names(usage: &Usage) -> Vec<&'static str> {
+        [usage.name]
+            .into_iter()
+            .chain(usage.aliases.iter().copied())
+            .collect()
+    }
+
+    /// Every flag the command accepts, including global options.
+    fn flag_names(usage: &Usage) -> Vec<&'static str> {
+        usage
+            .flags
+            .iter()
+            .chain(GLOBAL_FLAGS)
+            .flat_map(|flag| flag.names.iter().copied())
+            .collect()
+    }
+
+    fn bash() -> String {
+        let commands = COMMANDS
+            .iter()
+            .flat_map(|usage| Self::command_names(usage))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut cases = String::new();
+        for usage in COMMANDS {
+            let mut words = Self::flag_names(usage).join(" ");
+            if let Some(list) = Self::dynamic_list(usage) {
+                words.push_str(&format!(" $(click completions --list {list} 2>/dev/null)"));
+            }
+
+            cases.push_str(&format!(
+                "        {}) words=\"{words}\" ;;\n",
+                Self::command_names(usage).join("|")
+            ));
+        }
+
+        format!(
+            r#"_click() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    if [ "$COMP_CWORD" -eq 1 ]; then
+        COMPREPLY=($(compgen -W "{commands}" -- "$cur"))
+        return
+    fi
+
+    local words
+    case "${{COMP_WORDS[1]}}" in
+{cases}    esac
+    COMPREPLY=($(compgen -W "$words" -- "$cur"))
+}}
+complete -o default -F _click click"#
+        )
+    }
+
+    fn zsh() -> String {
+        let commands = COMMANDS
+            .iter()
+            .flat_map(|usage| {
+                Self::command_names(usage).into_iter().map(|name| {
+                    format!(
+                        "        '{name}:{}'",
+                        usage.description.replace('\'', "'\\''")
+                    )
+                })
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut cases = String::new();
+        for usage in COMMANDS {
+            let mut words = Self::flag_names(usage).join(" ");
+            if let Some(list) = Self::dynamic_list(usage) {
+                words.push_str(&format!(" $(click completions --list {list} 2>/dev/null)"));
+            }
+
+            cases.push_str(&format!(
+                "        {}) compadd -- {words} ;;\n",
+                Self::command_names(usage).join("|")
+            ));
+        }
+
+        format!(
+            r#"#compdef click
+
+_click() {{
+    if (( CURRENT == 2 )); then
+        local -a commands
+        commands=(
+{commands}
+        )
+        _describe 'command' commands
+        return
+    fi
+
+    case "${{words[2]}}" in
+{cases}    esac
+    _files
+}}
+
+compdef _click click"#
+        )
+    }
+
+    fn fish() -> String {
+        let mut lines = vec![String::from("complete -c click -f")];
+
+        for usage in COMMANDS {
+            let names = Self::command_names(usage).join(" ");
+            let condition = format!("__fish_seen_subcommand_from {names}");
+
+            lines.push(format!(
+                "complete -c click -n __fish_use_subcommand -a '{names}' -d '{}'",
+                Self::fish_escape(usage.description)
+            ));
+
+            for flag in usage.flags {
+                lines.push(format!(
+                    "complete -c click -n '{condition}' {}",
+                    Self::fish_flag(flag)
+                ));
+            }
+
+            if let Some(list) = Self::dynamic_list(usage) {
+                lines.push(format!(
+                    "complete -c click -n '{condition}' -a '(click completions --list {list} 2>/dev/null)'"
+                ));
+            }
+
+            if usage.name == "exec" {
+                lines.push(format!("complete -c click -n '{condition}' -F"));
+            }
+        }
+
+        for flag in GLOBAL_FLAGS {
+            lines.push(format!("complete -c click {}", Self::fish_flag(flag)));
+        }
+
+        lines.join("\n")
+    }
+
+    /// Such as `-s D -l save-dev -d 'Save to devDependencies'`, `-r` if the flag takes a value.
+    fn fish_flag(flag: &Flag) -> String {
+        let mut options = flag
+            .names
+            .iter()
+            .map(|name| match name.strip_prefix("--") {
+                Some(long) => format!("-l {long}"),
+                None => format!("-s {}", name.trim_start_matches('-')),
+            })
+            .collect::<Vec<_>>();
+
+        if flag.value.is_some() {
+            options.push(String::from("-r"));
+        }
+        options.push(format!("-d '{}'", Self::fish_escape(flag.description)));
+
+        options.join(" ")
+    }
+
+    fn fish_escape(text: &str) -> String {
+        text.replace('\\', "\\\\").replace('\'', "\\'")
+    }
+
+    fn powershell() -> String {
+        let quote = |words: Vec<&str>| {
+            words
+                .iter()
+                .map(|word| format!("'{}'", word.replace('\'', "''")))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let commands = quote(
+            COMMANDS
+                .iter()
+                .flat_map(|usage| Self::command_names(usage))
+                .collect(),
+        );
+
+        let mut cases = String::new();
+        for usage in COMMANDS {
+            let mut words = format!("@({})", quote(Self::flag_names(usage)));
+            if let Some(list) = Self::dynamic_list(usage) {
+                words.push_str(&format!(" + @(click completions --list {list} 2>$null)"));
+            }
+
+            cases.push_str(&format!(
+                "            {{ $_ -in {} }} {{ {words} }}\n",
+                quote(Self::command_names(usage))
+            ));
+        }
+
+        format!(
+            r#"Register-ArgumentCompleter -Native -CommandName click -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+
+    $words = @($commandAst.CommandElements | ForEach-Object {{ $_.ToString() }})
+    if ($words.Count -eq 1 -or ($words.Count -eq 2 -and $wordToComplete)) {{
+        $candidates = @({commands})
+    }} else {{
+        $candidates = switch ($words[1]) {{
+{cases}        }}
+    }}
+
+    $candidates | Where-Object {{ $_ -like "$wordToComplete*" }} | ForEach-Object {{
+        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+    }}
+}}"#
+        )
+    }
+
+    fn print_list(list: CompletionList) -> Result<(), CommandError> {
+        let names = match list {
+            CompletionList::Packages => Installer::linked_packages()?
+                .into_iter()
+                .map(|package| Versions::parse_raw_package_details(package).0)
+                .collect::<Vec<_>>(),
+            CompletionList::Scripts => match Manifest::read()? {
+                Some(manifest) => Scripts::scripts(&manifest)
+                    .into_iter()
+                    .map(|(script_name, _)| script_name)
+                    .collect(),
+                None => Vec::new(),
+            },
+        };
+
+        for name in names {
+            println!("{name}");
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CommandHandler for CompletionsHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        let mut shell = None;
+
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--list") => {
+                    let list = args.value("--list")?;
+                    self.list = Some(match list.as_str() {
+                        "packages" => CompletionList::Packages,
+                        "scripts" => CompletionList::Scripts,
+                        _ => return Err(ParseError::InvalidArgument(String::from("--list"), list)),
+                    });
+                }
+                Arg::Positional(name) if shell.is_none() => {
+                    shell = Some(match name.as_str() {
+                        "bash" => Shell::Bash,
+                        "zsh" => Shell::Zsh,
+                        "fish" => Shell::Fish,
+                        "powershell" | "pwsh" => Shell::Powershell,
+                        _ => return Err(ParseError::InvalidArgument(String::from("shell"), name)),
+                    });
+                }
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        match (shell, self.list) {
+            (Some(shell), _) => self.shell = shell,
+            (None, Some(_)) => {}
+            (None, None) => return Err(ParseError::MissingArgument(String::from("shell"))),
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        if let Some(list) = self.list {
+            return Self::print_list(list);
+        }
+
+        let script = match self.shell {
+            Shell::Bash => Self::bash(),
+            Shell::Zsh => Self::zsh(),
+            Shell::Fish => Self::fish(),
+            Shell::Powershell => Self::powershell(),
+        };
+        println!("{script}");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+use async_trait::async_trait;
+use log::warn;
+
+use crate::{
+    config::Config,
+    errors::{CommandError, ParseError},
+    output::Output,
+    types::ConfigChange,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::{CommandHandler, COMMANDS},
+};
+
+pub const USAGE: Usage = Usage {
+    name: "config",
+    aliases: &["c"],
+    arguments: "get <key>|set <key> <value>|delete <key>|list",
+    description:
+        "Print, set or remove keys of the config, such as `click config set registry <url>`. set and delete edit the .clickrc of the user, or of the project with --location project",
+    flags: &[
+        Flag::with_value(
+            &["--location"],
+            "user|project",
+            "The .clickrc to edit, the one in the home directory by default",
+        ),
+        Flag::with_value(
+            &["--section"],
+            "command",
+            "Use the key of a command, such as linker in the [install] section",
+        ),
+    ],
+    passthrough: false,
+};
+
+#[derive(Default)]
+enum ConfigSubcommand {
+    Get(String),
+    Set(String, String),
+    Delete(String),
+    #[default]
+    List,
+}
+
+#[derive(Default)]
+pub struct ConfigHandler {
+    subcommand: ConfigSubcommand,
+    project: bool, // Edit the .clickrc of the project, set with --location project.
+    section: Option<String>, // The command the key is for, set with --section.
+}
+
+impl ConfigHandler {
+    /// A key or value with a line break or a key with `=` would change other keys once written.
+    fn valid(key: &str, value: &str) -> Result<(), ParseError> {
+        if key.is_empty() || key.contains(['=', '\n', '[']) {
+            return Err(ParseError::InvalidArgument(
+                String::from("key"),
+                key.to_string(),
+            ));
+        }
+        if value.contains('\n') {
+            return Err(ParseError::InvalidArgument(
+                String::from("value"),
+                value.to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<(), CommandError> {
+        let value = Config::value(key, self.section.as_deref())?
+            .ok_or_else(|| CommandError::ConfigKeyNotSet(key.to_string()))?;
+        let value = Config::display_value(key, &value);
+
+        match Output::is_json() {
+            true => Output::json(&serde_json::json!({ "key": key, "value": value })),
+            false => {
+                println!("{value}");
+                Ok(())
+            }
+        }
+    }
+
+    fn list() -> Result<(), CommandError> {
+        let settings = Config::settings()?;
+        if Output::is_json() {
+            return Output::json(&settings);
+        }
+
+        let rows = settings
+            .iter()
+            .map(|setting| {
+                let key = match &setting.command {
+                    Some(command) => format!("[{command}] {}", setting.key),
+                    None => setting.key.to_string(),
+                };
+                [key, setting.value.to_string(), setting.source.to_string()]
+            })
+            .collect::<Vec<_>>();
+        let rows = rows
+            .iter()
+            .map(|row| row.each_ref().map(String::as_str))
+            .collect::<Vec<_>>();
+
+        Output::table(["KEY", "VALUE", "SOURCE"], &rows);
+        Ok(())
+    }
+
+    fn set(&self, key: &str, value: Option<&str>) -> Result<(), CommandError> {
+        let path = Config::config_file_path(self.project)?;
+        let found = Config::set_key(&path, self.section.as_deref(), key, value)?;
+
+        match (value, found) {
+            (Some(value), _) => Output::message(format!(
+                "Set '{key}' to '{}' in '{}'",
+                Config::display_value(key, value),
+                path.display()
+            )),
+            (None, true) => Output::message(format!("Removed '{key}' from '{}'", path.display())),
+            (None, false) => warn!("'{key}' is not set in '{}'", path.display()),
+        }
+
+        Output::json(&ConfigChange {
+            key: key.to_string(),
+            command: self.section.clone(),
+            value: value.map(|value| Config::display_value(key, value)),
+            path: path.display().to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl CommandHandler for ConfigHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        let mut positional = Vec::new();
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--location") => {
+                    let location = args.value("--location")?;
+                    self.project = match location.as_str() {
+                        "user" => false,
+                        "project" => true,
+                        _ => {
+                            return Err(ParseError::InvalidArgument(
+                                String::from("--location"),
+                                location,
+                            ))
+                        }
+                    };
+                }
+                Arg::Flag("--section") => {
+                    let section = args.value("--section")?;
+                    let usage = COMMANDS
+                        .into_iter()
+                        .find(|usage| usage.matches(&section))
+                        .ok_or(ParseError::InvalidArgument(
+                            String::from("--section"),
+                            section,
+                        ))?;
+                    self.section = Some(usage.name.to_string());
+                }
+                Arg::Positional(value) => positional.push(value),
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let subcommand = positional
+            .next()
+            .ok_or(ParseError::MissingArgument(String::from(
+                "config subcommand",
+            )))?;
+        let mut argument = |name: &str| {
+            positional
+                .next()
+                .ok_or_else(|| ParseError::MissingArgument(name.to_string()))
+        };
+
+        self.subcommand = match subcommand.as_str() {
+            "get" => ConfigSubcommand::Get(argument("key")?),
+            "set" => {
+                let key = argument("key")?;
+                let value = argument("value")?;
+                Self::valid(&key, &value)?;
+                ConfigSubcommand::Set(key, value)
+            }
+            "delete" | "rm" => ConfigSubcommand::Delete(argument("key")?),
+            "list" | "ls" => ConfigSubcommand::List,
+            _ => return Err(ParseError::SubcommandNotFound(subcommand)),
+        };
+
+        match positional.next() {
+            Some(value) => Err(Arg::Positional(value).unexpected()),
+            None => Ok(()),
+        }
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        match &self.subcommand {
+            ConfigSubcommand::Get(key) => self.get(key),
+            ConfigSubcommand::Set(key, value) => self.set(key, Some(value)),
+            ConfigSubcommand::Delete(key) => self.set(key, None),
+            ConfigSubcommand::List => Self::list(),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use async_trait::async_trait;
+
+use crate::{
+    config::Config,
+    errors::{CommandError, ParseError},
+};
+
+use super::{
+    arguments::{Arg, Arguments, Usage},
+    command_handler::CommandHandler,
+    exec::RunFileHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "create",
+    aliases: &[],
+    arguments: "<initializer[@range]> [args]...",
+    description:
+        "Start a project with the create- package of an initializer, such as create-vite for `click create vite my-app`. It is installed into the cache to run it, the same as with exec",
+    flags: &[],
+    passthrough: true,
+};
+
+#[derive(Default)]
+pub struct CreateHandler {
+    initializer: String, // Such as vite, @scope or @scope/name, optionally with a range
+    args: Vec<String>,   // Passed on to the executable of the create- package.
+}
+
+impl CreateHandler {
+    /// The package an initializer stands for, the same as with `npm create`: `vite` is `create-vite`, `@scope` is
+    /// `@scope/create` and `@scope/name` is `@scope/create-name`. A range stays on the package.
+    fn package_spec(initializer: &str) -> String {
+        // The scope of a name starts with @ too, so the range is after the next one
+        let (name, range) = match initializer.strip_prefix('@') {
+            Some(scoped) => match scoped.split_once('@') {
+                Some((name, range)) => (format!("@{name}"), Some(range)),
+                None => (initializer.to_string(), None),
+            },
+            None => match initializer.split_once('@') {
+                Some((name, range)) => (name.to_string(), Some(range)),
+                None => (initializer.to_string(), None),
+            },
+        };
+
+        let package_name = match name.split_once('/') {
+            Some((scope, name)) => format!("{scope}/create-{name}"),
+            None if name.starts_with('@') => format!("{name}/create"),
+            None => format!("create-{name}"),
+        };
+
+        match range {
+            Some(range) => format!("{package_name}@{range}"),
+            None => package_name,
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler for CreateHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Separator if self.initializer.is_empty() => {}
+                // Everything after the initializer is passed on, even flags click has
+                Arg::Positional(initializer) => {
+                    self.initializer = initializer;
+                    self.args = args.rest();
+                    break;
+                }
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        if self.initializer.is_empty() {
+            return Err(ParseError::MissingArgument(String::from("initializer")));
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        Config::load()?;
+
+        let package_spec = Self::package_spec(&self.initializer);
+        RunFileHandler::package(package_spec, self.args.clone())
+            .run_package()
+            .await
+    }
+}
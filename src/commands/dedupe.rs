@@ -0,0 +1,148 @@
+use std::{fs, path::Path};
+
+use async_trait::async_trait;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    hoisted::{HoistedLayout, HoistedPlan},
+    installer::{Installer, MODULES_DIRECTORY},
+    output::Output,
+    shims::ShimPlatform,
+    transaction::Transaction,
+    types::{DedupeResult, Lockfile, PlacedPackage},
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+    install::InstallHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "dedupe",
+    aliases: &["ddp"],
+    arguments: "",
+    description:
+        "Lay out node_modules again from the lockfile, so each version is installed once where it can be",
+    flags: &[Flag::new(
+        &["--dry-run"],
+        "Print what would be removed and added without changing anything",
+    )],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct DedupeHandler {
+    dry_run: bool, // Only print the changes, set with --dry-run.
+}
+
+impl DedupeHandler {
+    /// Packages of the isolated layout each have their own dependencies already, so there is nothing to dedupe.
+    fn uses_isolated_layout() -> bool {
+        let Ok(entries) = fs::read_dir(MODULES_DIRECTORY) else {
+            return false;
+        };
+
+        entries.flatten().any(|entry| {
+            fs::read_link(entry.path())
+                .is_ok_and(|target| target.components().any(|part| part.as_os_str() == ".click"))
+        })
+    }
+
+    /// The packages whose location changes between what is installed and the plan.
+    fn changes(installed: &HoistedPlan, plan: &HoistedPlan) -> DedupeResult {
+        let placed = |(location, package): (&Vec<String>, &String)| PlacedPackage {
+            path: HoistedLayout::path(location)
+                .strip_prefix("./")
+                .unwrap_or(Path::new(""))
+                .to_string_lossy()
+                .to_string(),
+            package: package.to_string(),
+        };
+
+        let differs = |from: &HoistedPlan, to: &HoistedPlan| {
+            from.placements
+                .iter()
+                .filter(|(location, package)| to.placements.get(*location) != Some(package))
+                .map(placed)
+                .collect::<Vec<_>>()
+        };
+
+        DedupeResult {
+            removed: differs(installed, plan),
+            added: differs(plan, installed),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler for DedupeHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--dry-run") => self.dry_run = true,
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Plans the layout of everything in the lockfile the same way an install does and changes node_modules to match it,
+    /// removing packages at the top that nothing needs anymore.
+    async fn execute(&self) -> Result<(), CommandError> {
+        if Self::uses_isolated_layout() {
+            Output::message("node_modules uses the isolated layout, which has no duplicates");
+            return Output::json(&DedupeResult::default());
+        }
+
+        // Everything in node_modules would be removed if there were nothing locked
+        let lockfile = Lockfile::read()?;
+        if lockfile.requested.is_empty() {
+            Output::message("Nothing is locked to lay out node_modules from, run install first");
+            return Output::json(&DedupeResult::default());
+        }
+
+        let plan = HoistedPlan::from_lockfile(&lockfile);
+        let result = Self::changes(&HoistedLayout::installed()?, &plan);
+
+        if result.removed.is_empty() && result.added.is_empty() {
+            Output::message("node_modules is already deduplicated");
+            return Output::json(&result);
+        }
+
+        if !self.dry_run {
+            let transaction = Transaction::begin()?;
+            let linked = HoistedLayout::link(&plan)
+                .and_then(|_| HoistedLayout::remove_extraneous(&plan))
+                .and_then(|_| Installer::link_bins(ShimPlatform::default()));
+
+            if let Err(err) = linked {
+                InstallHandler::rollback(transaction);
+                return Err(err);
+            }
+        }
+
+        match self.dry_run {
+            true => Output::message(format!(
+                "Dry run, {} package(s) would be removed and {} added in node_modules:",
+                result.removed.len(),
+                result.added.len()
+            )),
+            false => Output::message(format!(
+                "Removed {} and added {} package(s) in node_modules:",
+                result.removed.len(),
+                result.added.len()
+            )),
+        }
+
+        for placed in result.removed.iter() {
+            Output::message(format!("  - {} ({})", placed.path, placed.package));
+        }
+        for placed in result.added.iter() {
+            Output::message(format!("  + {} ({})", placed.path, placed.package));
+        }
+
+        Output::json(&result)
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    process::Command,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    cache::CACHE_DIRECTORY,
+    config::Config,
+    engines::Engines,
+    errors::{CommandError, ParseError},
+    http::HTTPRequest,
+    installer::Installer,
+    manifest::{Manifest, PACKAGE_JSON_PATH},
+    output::Output,
+    types::{DoctorCheck, Lockfile},
+    util::{directory_size, Size},
+    versions::Versions,
+};
+
+use super::{
+    arguments::{Arguments, Usage},
+    command_handler::CommandHandler,
+    install::InstallHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "doctor",
+    aliases: &[],
+    arguments: "",
+    description:
+        "Check the registry, the cache, Node and npm, and that node_modules matches the lockfile, exits with 1 if any check fails",
+    flags: &[],
+    passthrough: false,
+};
+
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+const SLOW_PING: Duration = Duration::from_secs(2); // A registry slower than this to answer is pointed out
+
+#[derive(Default)]
+pub struct DoctorHandler;
+
+impl DoctorHandler {
+    fn check(name: &str, result: Result<String, (String, String)>) -> DoctorCheck {
+        let (passed, detail, fix) = match result {
+            Ok(detail) => (true, detail, None),
+            Err((detail, fix)) => (false, detail, Some(fix)),
+        };
+
+        DoctorCheck {
+            name: name.to_string(),
+            passed,
+            detail,
+            fix,
+        }
+    }
+
+    async fn registry() -> Result<String, (String, String)> {
+        let config = Config::get()
+            .map_err(|err| (err.to_string(), String::from("Fix the config in .npmrc")))?;
+        let registry = config.registry.to_string();
+        let client = config
+            .client_builder()
+            .timeout(PING_TIMEOUT)
+            .build()
+            .map_err(|err| {
+                let fix = String::from("Check the cafile and proxy set in .npmrc");
+                (err.to_string(), fix)
+            })?;
+
+        match HTTPRequest::ping(client, &registry).await {
+            Ok(latency) if latency > SLOW_PING => Ok(format!(
+                "'{registry}' answered in {}ms, which is slow",
+                latency.as_millis()
+            )),
+            Ok(latency) => Ok(format!(
+                "'{registry}' answered in {}ms",
+                latency.as_millis()
+            )),
+            Err(err) => Err((
+                format!("'{registry}' could not be reached ({err})"),
+                String::from(
+                    "Check the network connection, or the registry and proxy set in .npmrc",
+                ),
+            )),
+        }
+    }
+
+    /// The cache has to be writable, as every install links packages from it.
+    fn cache() -> Result<String, (String, String)> {
+        let cache_directory = Path::new(CACHE_DIRECTORY.as_str());
+        let fix = format!(
+            "Make '{}' writable by this user, or remove it to start over",
+            cache_directory.display()
+        );
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.subsec_nanos());
+        let probe = cache_directory.join(format!(".doctor-{nanos}"));
+        fs::create_dir_all(cache_directory)
+            .and_then(|_| fs::write(&probe, b""))
+            .and_then(|_| fs::remove_file(&probe))
+            .map_err(|err| {
+                let detail = format!("'{}' is not writable ({err})", cache_directory.display());
+                (detail, fix.to_string())
+            })?;
+
+        let packages = fs::read_dir(cache_directory).map_or(0, |entries| entries.count());
+        Ok(format!(
+            "'{}' is writable, with {packages} package(s) taking {}",
+            cache_directory.display(),
+            Size(directory_size(cache_directory))
+        ))
+    }
+
+    fn bin_path() -> Result<String, (String, String)> {
+        match Installer::bin_directory_not_on_path() {
+            None => Ok(String::from("node_modules/.bin is on PATH")),
+            Some(bin_directory) if !bin_directory.exists() => Ok(String::from(
+                "Nothing is installed in node_modules/.bin yet",
+            )),
+            Some(bin_directory) => Err((
+                format!("'{}' is not on PATH", bin_directory.display()),
+                String::from(
+                    "Run the binaries of packages with `click exec` or `click run`, or add it to PATH",
+                ),
+            )),
+        }
+    }
+
+    fn node() -> Result<String, (String, String)> {
+        let node_version = Engines::node_version().ok_or((
+            String::from("Node is not on PATH"),
+            String::from("Install Node from https://nodejs.org"),
+        ))?;
+
+        // The range the project needs, such as `"engines": { "node": ">=18" }`
+        let manifest = Manifest::read().ok().flatten();
+        let required = manifest.as_ref().and_then(Manifest::node_engine);
+        match required {
+            Some(required)
+                if Versions::parse_semantic_version(required)
+                    .is_ok_and(|range| !range.matches(node_version)) =>
+            {
+                Err((
+                    format!(
+                        "Node v{node_version} is not in the range {required} package.json requires"
+                    ),
+                    format!("Install a version of Node in {required}"),
+                ))
+            }
+            _ => Ok(format!("Node v{node_version}")),
+        }
+    }
+
+    fn npm() -> Result<String, (String, String)> {
+        let npm_command = if cfg!(windows) { "npm.cmd" } else { "npm" };
+        Command::new(npm_command)
+            .arg("--version")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| format!("npm v{}", String::from_utf8_lossy(&output.stdout).trim()))
+            .ok_or((
+                String::from("npm is not on PATH"),
+                String::from("Install npm along with Node, it is needed by packages that run npm in their scripts"),
+            ))
+    }
+
+    /// The lockfile has to lock every range in package.json, the same way `click ci` requires it to.
+    fn lockfile() -> Result<String, (String, String)> {
+        if !Path::new(PACKAGE_JSON_PATH).exists() {
+            return Ok(String::from("There is no package.json in this directory"));
+        }
+
+        let fix = String::from("Run `click install` to update it");
+        match InstallHandler::default().locked_packages() {
+            Ok(packages) => Ok(format!(
+                "Every one of the {} package(s) in package.json is locked",
+                packages.len()
+            )),
+            Err(err) => Err((err.to_string(), fix)),
+        }
+    }
+
+    /// Each package in the lockfile that is depended on from package.json has to be linked into node_modules, at one
+    /// of the versions it is locked to.
+    fn node_modules() -> Result<String, (String, String)> {
+        let fix = String::from("Run `click install` to install the locked versions");
+        let lockfile = Lockfile::read().map_err(|err| (err.to_string(), fix.to_string()))?;
+        let linked = Installer::linked_packages()
+            .map_err(|err| (err.to_string(), fix.to_string()))?
+            .into_iter()
+            .map(Versions::parse_raw_package_details)
+            .collect::<HashMap<_, _>>();
+
+        let mut locked: HashMap<String, Vec<String>> = HashMap::new();
+        for package in lockfile.requested.values() {
+            let (package_name, version) = Versions::parse_raw_package_details(package.to_string());
+            locked.entry(package_name).or_default().push(version);
+        }
+
+        let mut mismatched = Vec::new();
+        for (package_name, versions) in locked.iter() {
+            match linked.get(package_name) {
+                Some(linked) if versions.contains(linked) => {}
+                Some(linked) => mismatched.push(format!(
+                    "{package_name} ({linked} is installed, {} is locked)",
+                    versions.join(" or ")
+                )),
+                None => mismatched.push(format!("{package_name} (not installed)")),
+            }
+        }
+        mismatched.sort();
+
+        match mismatched.is_empty() {
+            true => Ok(format!(
+                "The {} package(s) in node_modules match the lockfile",
+                linked.len()
+            )),
+            false => Err((
+                format!(
+                    "node_modules does not match the lockfile: {}",
+                    mismatched.join(", ")
+                ),
+                fix,
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler for DoctorHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        if let Some(arg) = args.next()? {
+            return Err(arg.unexpected());
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        Config::load()?;
+
+        let checks = vec![
+            Self::check("registry", Self::registry().await),
+            Self::check("cache", Self::cache()),
+            Self::check("PATH", Self::bin_path()),
+            Self::check("node", Self::node()),
+            Self::check("npm", Self::npm()),
+            Self::check("lockfile", Self::lockfile()),
+            Self::check("node_modules", Self::node_modules()),
+        ];
+
+        Output::json(&checks)?;
+        if !Output::is_json() {
+            for check in checks.iter() {
+                let status = if check.passed { "ok" } else { "fail" };
+                println!("{status:>4}  {}: {}", check.name, check.detail);
+                if let Some(fix) = &check.fix {
+                    println!("      {fix}");
+                }
+            }
+        }
+
+        let failed = checks.iter().filter(|check| !check.passed).count();
+        if failed > 0 {
+            return Err(CommandError::FailedChecks(failed));
+        }
+
+        Output::message("Every check passed");
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::ErrorKind,
+    path::Path,
+    str::FromStr,
+};
+
+use async_trait::async_trait;
+use semver::Version;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    hoisted::{HoistedLayout, HoistedPlan},
+    installer::Installer,
+    isolated::{IsolatedLayout, ISOLATED_DIRECTORY},
+    output::Output,
+    patch::Patches,
+    types::{DiskUsageResult, DuplicatedPackage, Lockfile, PackageUsage},
+    util::{directory_size, Size},
+    versions::{Versions, EMPTY_VERSION},
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "du",
+    aliases: &["disk-usage"],
+    arguments: "",
+    description:
+        "Print how much disk each installed package takes, which are installed at more than one version and what dedupe would save",
+    flags: &[
+        Flag::with_value(&["--top"], "n", "Only list this many of the largest packages"),
+        Flag::new(&["-g", "--global"], "Report on the global packages"),
+    ],
+    passthrough: false,
+};
+
+const DEFAULT_TOP: usize = 20;
+
+#[derive(Default)]
+pub struct DiskUsageHandler {
+    top: Option<usize>, // How many of the largest packages are listed, DEFAULT_TOP if None. Every one is in --json.
+    global: bool,       // Report on the global packages rather than those of the working directory.
+}
+
+impl DiskUsageHandler {
+    /// Every version installed in node_modules, formatted as package@version, to how many places it is installed at.
+    /// Packages of the isolated layout are each installed once in node_modules/.click.
+    fn installed() -> Result<BTreeMap<String, usize>, CommandError> {
+        let mut copies = BTreeMap::new();
+        for package in HoistedLayout::installed()?.placements.into_values() {
+            *copies.entry(package).or_default() += 1;
+        }
+
+        let store = match fs::read_dir(ISOLATED_DIRECTORY) {
+            Ok(store) => store,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(copies),
+            Err(err) => return Err(CommandError::FailedToReadFile(err)),
+        };
+
+        for entry in store {
+            let entry = entry.map_err(CommandError::FailedDirectoryEntry)?;
+            let key = entry.file_name().to_string_lossy().to_string();
+            if !key.starts_with('.') {
+                copies.insert(IsolatedLayout::package_from_key(&key), 1);
+            }
+        }
+
+        Ok(copies)
+    }
+
+    /// The versions a dedupe would lay out, None if there is nothing locked to plan the layout from. The isolated layout
+    /// has nothing to dedupe, as every version is only installed once.
+    fn planned() -> Result<Option<HashSet<String>>, CommandError> {
+        if Path::new(ISOLATED_DIRECTORY).exists() {
+            return Ok(None);
+        }
+
+        let lockfile = Lockfile::read()?;
+        if lockfile.requested.is_empty() {
+            return Ok(None);
+        }
+
+        let plan = HoistedPlan::from_lockfile(&lockfile);
+        Ok(Some(plan.placements.into_values().collect()))
+    }
+
+    fn duplicates(packages: &[PackageUsage]) -> Vec<DuplicatedPackage> {
+        let mut versions = BTreeMap::<String, Vec<&PackageUsage>>::new();
+        for usage in packages {
+            let (name, _) = Versions::parse_raw_package_details(usage.package.to_string());
+            versions.entry(name).or_default().push(usage);
+        }
+
+        versions
+            .into_iter()
+            .filter(|(_, usages)| usages.len() > 1)
+            .map(|(name, usages)| {
+                let mut versions = usages
+                    .iter()
+                    .map(|usage| Versions::parse_raw_package_details(usage.package.to_string()).1)
+                    .collect::<Vec<_>>();
+                versions.sort_by_cached_key(|version| {
+                    Version::from_str(version).unwrap_or(EMPTY_VERSION)
+                });
+
+                DuplicatedPackage {
+                    name,
+                    versions,
+                    size: usages.iter().map(|usage| usage.size).sum(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl CommandHandler for DiskUsageHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--global") => self.global = true,
+                Arg::Flag("--top") => {
+                    let top = args.value("--top")?;
+
+                    self.top =
+                        Some(top.parse::<usize>().map_err(|_| {
+                            ParseError::InvalidArgument(String::from("--top"), top)
+                        })?);
+                }
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Packages are linked from the cache, so the size of a version is that of its files in the cache, or of its
+    /// patched copy. Node_modules itself only takes the links.
+    async fn execute(&self) -> Result<(), CommandError> {
+        if self.global {
+            Installer::use_global_prefix()?;
+        }
+
+        let mut packages = Self::installed()?
+            .into_iter()
+            .map(|(package, copies)| PackageUsage {
+                size: directory_size(&Patches::source(&package)),
+                package,
+                copies,
+            })
+            .collect::<Vec<_>>();
+        packages.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.package.cmp(&b.package)));
+
+        let dedupe_savings = match Self::planned()? {
+            Some(planned) => packages
+                .iter()
+                .filter(|usage| !planned.contains(&usage.package))
+                .map(|usage| usage.size)
+                .sum(),
+            None => 0,
+        };
+
+        let result = DiskUsageResult {
+            size: packages.iter().map(|usage| usage.size).sum(),
+            duplicates: Self::duplicates(&packages),
+            dedupe_savings,
+            store_savings: packages
+                .iter()
+                .map(|usage| usage.size * (usage.copies as u64 - 1))
+                .sum(),
+            packages,
+        };
+
+        if Output::is_json() {
+            return Output::json(&result);
+        }
+
+        if result.packages.is_empty() {
+            Output::message("Nothing is installed in node_modules");
+            return Ok(());
+        }
+
+        let top = self.top.unwrap_or(DEFAULT_TOP);
+        Output::message(format!(
+            "{} package(s) are installed, taking {}. The largest:",
+            result.packages.len(),
+            Size(result.size)
+        ));
+        for usage in result.packages.iter().take(top) {
+            let copies = match usage.copies {
+                1 => String::new(),
+                copies => format!(", installed at {copies} places"),
+            };
+            Output::message(format!(
+                "  {:>9}  {}{copies}",
+                Size(usage.size).to_string(),
+                usage.package
+            ));
+        }
+
+        if !result.duplicates.is_empty() {
+            Output::message(format!(
+                "{} package(s) are installed at more than one version:",
+                result.duplicates.len()
+            ));
+            for duplicate in result.duplicates.iter() {
+                Output::message(format!(
+                    "  {} {} ({})",
+                    duplicate.name,
+                    duplicate.versions.join(", "),
+                    Size(duplicate.size)
+                ));
+            }
+        }
+
+        if result.dedupe_savings > 0 {
+            Output::message(format!(
+                "Running dedupe would save {}",
+                Size(result.dedupe_savings)
+            ));
+        }
+        if result.store_savings > 0 {
+            Output::message(format!(
+                "Linking from the cache saves {} over copying packages to every place they are installed",
+                Size(result.store_savings)
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -1,40 +1,330 @@
-use crate::errors::{CommandError, ParseError};
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
 use async_trait::async_trait;
-use std::env::Args;
-use std::process::Command;
-use std::io;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use semver::Version;
 
-use super::command_handler::CommandHandler;
+use crate::{
+    config::Config,
+    errors::{CommandError, ParseError},
+    installer::MODULES_DIRECTORY,
+    manifest::{DependencyKind, Manifest},
+    scripts::Scripts,
+    types::PackageExecutables,
+    versions::{Versions, LATEST},
+};
 
-#[derive(Default)]
-pub struct RunFileHandler {
-    file_name: String,
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+    install::InstallHandler,
+    run::RunScriptHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "exec",
+    aliases: &["dlx", "x"],
+    arguments: "<file name | package[@range]> [args]...",
+    description:
+        "Run a file with Node, or the executable of a package. A package that is not in node_modules is installed into the cache to run it",
+    flags: &[
+        Flag::with_value(
+            &["--cache"],
+            "keep|clean",
+            "Keep a package installed to run it for the next run, the default, or remove it once it exits",
+        ),
+        Flag::with_value(
+            &["--bin"],
+            "name",
+            "The executable of the package to run, when it is not named after the package",
+        ),
+    ],
+    passthrough: true,
+};
+
+lazy_static! {
+    // Each package installed to run it has a directory here with its own package.json, lockfile and node_modules
+    static ref EXEC_DIRECTORY: PathBuf = dirs::cache_dir()
+        .expect("Failed to find cache directory")
+        .join("click-exec");
 }
 
-#[async_trait]
-impl CommandHandler for RunFileHandler {
-    fn parse(&mut self, args: &mut Args) -> Result<(), ParseError> {
-        let parsed_args = args
-            .next()
-            .ok_or(ParseError::MissingArgument(String::from("file name")))?;
+/// What happens to a package installed to run it once it exits.
+#[derive(Default, Clone, Copy, PartialEq)]
+enum CachePolicy {
+    #[default]
+    Keep, // Later runs of the same package and range use it without installing it again
+    Clean, // Removed, so the next run installs it again
+}
 
-        self.file_name = parsed_args;
+#[derive(Default)]
+pub struct RunFileHandler {
+    file_name: String, // A file to run with Node, or else the package whose executable is run.
+    args: Vec<String>, // Passed on to the file or executable, everything after the file name.
+    cache: CachePolicy,
+    bin: Option<String>, // The executable of the package to run, set with --bin, see default_executable() if None.
+}
 
-        Ok(())
+impl RunFileHandler {
+    /// Runs the executable of a package rather than a file, such as a `create-*` package for `click create`.
+    pub fn package(package_spec: String, args: Vec<String>) -> Self {
+        Self {
+            file_name: package_spec,
+            args,
+            cache: CachePolicy::Keep,
+            bin: None,
+        }
     }
-    async fn execute(&self) -> Result<(), CommandError> {
+
+    fn run_file(&self) -> Result<(), CommandError> {
         let cmd = Command::new("node")
             .args(["--preserve-symlinks", &self.file_name])
+            .args(&self.args)
             .status()
             .map_err(CommandError::ComandFailedError)?;
 
         if !(cmd.success()) {
             let error_message = "Something went wrong";
 
-            let error = io::Error::new(io::ErrorKind::Other, error_message);
+            let error = io::Error::other(error_message);
             return Err(CommandError::ComandFailedError(error));
         }
 
         Ok(())
     }
+
+    /// The package in node_modules of the working directory, if it is there and its version is in the range.
+    /// A dist-tag other than `latest` always installs the package, as its version is not known without the registry.
+    fn installed_locally(
+        package_name: &str,
+        range: &str,
+    ) -> Result<Option<PackageExecutables>, CommandError> {
+        let Some(package) = Self::read_installed(package_name)? else {
+            return Ok(None);
+        };
+
+        let in_range = match range {
+            LATEST => true,
+            range if Versions::is_dist_tag(range) => false,
+            range => {
+                let version = Version::from_str(&package.version).ok();
+                let range = Versions::parse_semantic_version(range).ok();
+                matches!((version, range), (Some(version), Some(range)) if range.matches(&version))
+            }
+        };
+
+        Ok(in_range.then_some(package))
+    }
+
+    fn read_installed(package_name: &str) -> Result<Option<PackageExecutables>, CommandError> {
+        let path = Path::new(MODULES_DIRECTORY)
+            .join(package_name)
+            .join("package.json");
+
+        let package_raw = match fs::read_to_string(path) {
+            Ok(package_raw) => package_raw,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(CommandError::FailedToReadFile(err)),
+        };
+
+        serde_json::from_str(&package_raw)
+            .map(Some)
+            .map_err(CommandError::FailedToParseManifest)
+    }
+
+    /// Where a package is installed to run it, named after the package and range so that each range gets its own.
+    fn exec_directory(package_name: &str, range: &str) -> PathBuf {
+        let name = format!("{package_name}@{range}").replace('/', "+").replace(
+            |char: char| !char.is_ascii_alphanumeric() && !"@+.-_^~".contains(char),
+            "_",
+        );
+
+        EXEC_DIRECTORY.join(name)
+    }
+
+    /// Installs the package into its directory in the cache unless it is there already, which is left as the working
+    /// directory so that the executables in its node_modules are the ones found.
+    async fn install(
+        package_name: &str,
+        range: &str,
+        directory: &Path,
+    ) -> Result<PackageExecutables, CommandError> {
+        fs::create_dir_all(directory).map_err(CommandError::FailedToCreateFile)?;
+        env::set_current_dir(directory).map_err(CommandError::FailedToReadFile)?;
+
+        if let Some(package) = Self::read_installed(package_name)? {
+            return Ok(package);
+        }
+
+        info!("Installing '{package_name}@{range}' to run it..");
+
+        let mut manifest = Manifest::default();
+        manifest.set_dependency(&DependencyKind::Production, package_name, range.to_string());
+        manifest.write()?;
+
+        InstallHandler::quiet().execute().await?;
+
+        Self::read_installed(package_name)?
+            .ok_or_else(|| CommandError::PackageNotInstalled(package_name.to_string()))
+    }
+
+    /// The executable given with --bin, or else the default one.
+    fn executable(
+        &self,
+        package_name: &str,
+        package: &PackageExecutables,
+    ) -> Result<String, CommandError> {
+        let Some(bin) = &self.bin else {
+            return Self::default_executable(package_name, package);
+        };
+
+        let links = Self::links(package_name, package);
+        match links.contains_key(bin) {
+            true => Ok(bin.to_string()),
+            false => Err(CommandError::ExecutableNotFound(
+                package_name.to_string(),
+                bin.to_string(),
+                links.into_keys().collect::<Vec<_>>().join(", "),
+            )),
+        }
+    }
+
+    fn links(package_name: &str, package: &PackageExecutables) -> BTreeMap<String, String> {
+        package
+            .bin
+            .as_ref()
+            .map(|bin| bin.links(package_name))
+            .unwrap_or_default()
+    }
+
+    /// The executable named after the package without its scope, or the only one it has, the same one npx runs.
+    fn default_executable(
+        package_name: &str,
+        package: &PackageExecutables,
+    ) -> Result<String, CommandError> {
+        let links = Self::links(package_name, package);
+        let unscoped_name = package_name.rsplit('/').next().unwrap_or(package_name);
+
+        if links.contains_key(unscoped_name) {
+            return Ok(unscoped_name.to_string());
+        }
+
+        let names = links.into_keys().collect::<Vec<_>>();
+        match names.as_slice() {
+            [] => Err(CommandError::NoExecutable(package_name.to_string())),
+            [name] => Ok(name.to_string()),
+            names => Err(CommandError::AmbiguousExecutable(
+                package_name.to_string(),
+                names.join(", "),
+            )),
+        }
+    }
+
+    /// Runs an executable from node_modules/.bin of the working directory, in the directory click was run in.
+    fn run_executable(&self, executable: &str, directory: &Path) -> Result<(), CommandError> {
+        let mut command = RunScriptHandler::quote(executable);
+        for arg in self.args.iter() {
+            command = format!("{command} {}", RunScriptHandler::quote(arg));
+        }
+
+        let status = Scripts::shell_command(&command)
+            .current_dir(directory)
+            .env("PATH", Scripts::path_with_bins()?)
+            .status()
+            .map_err(CommandError::ComandFailedError)?;
+
+        // An executable killed by a signal has no exit code
+        if !status.success() {
+            return Err(CommandError::ExecutableFailed(
+                executable.to_string(),
+                status.code().unwrap_or(1),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub async fn run_package(&self) -> Result<(), CommandError> {
+        let (package_name, range) = Versions::parse_raw_package_details(self.file_name.to_string());
+        let working_directory = env::current_dir().map_err(CommandError::FailedToReadFile)?;
+
+        if let Some(package) = Self::installed_locally(&package_name, &range)? {
+            let executable = self.executable(&package_name, &package)?;
+            return self.run_executable(&executable, &working_directory);
+        }
+
+        let directory = Self::exec_directory(&package_name, &range);
+        let result = match Self::install(&package_name, &range, &directory).await {
+            Ok(package) => self
+                .executable(&package_name, &package)
+                .and_then(|executable| self.run_executable(&executable, &working_directory)),
+            Err(err) => {
+                // A failed install is not kept, so that the next run installs it again
+                let _ = fs::remove_dir_all(&directory);
+                return Err(err);
+            }
+        };
+
+        if self.cache == CachePolicy::Clean {
+            if let Err(err) = fs::remove_dir_all(&directory) {
+                warn!("failed to remove '{}' ({err})", directory.display());
+            }
+        }
+
+        result
+    }
+}
+
+#[async_trait]
+impl CommandHandler for RunFileHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--cache") => {
+                    self.cache = match args.value("--cache")?.as_str() {
+                        "keep" => CachePolicy::Keep,
+                        "clean" => CachePolicy::Clean,
+                        policy => {
+                            return Err(ParseError::InvalidArgument(
+                                String::from("--cache"),
+                                policy.to_string(),
+                            ))
+                        }
+                    };
+                }
+                Arg::Flag("--bin") => self.bin = Some(args.value("--bin")?),
+                Arg::Separator if self.file_name.is_empty() => {}
+                // Everything after the file name is passed on, even flags click has
+                Arg::Positional(file_name) => {
+                    self.file_name = file_name;
+                    self.args = args.rest();
+                    break;
+                }
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        if self.file_name.is_empty() {
+            return Err(ParseError::MissingArgument(String::from("file name")));
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        if Path::new(&self.file_name).is_file() {
+            return self.run_file();
+        }
+
+        Config::load()?;
+        self.run_package().await
+    }
 }
@@ -0,0 +1,165 @@
+use async_trait::async_trait;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    manifest::Manifest,
+    output::Output,
+    types::{DependencyGraph, GraphEdge, GraphNode, Lockfile},
+    versions::Versions,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "graph",
+    aliases: &[],
+    arguments: "",
+    description:
+        "Print the resolved dependency graph of the lockfile, with each dependency labeled by the range it is depended on with",
+    flags: &[Flag::with_value(
+        &["--format"],
+        "dot|json",
+        "Print the graph for Graphviz or as JSON. Defaults to dot, or json with --json",
+    )],
+    passthrough: false,
+};
+
+const PROJECT_NODE: &str = "."; // The id of the project in the graph, which depends on the packages in package.json
+
+#[derive(Default)]
+pub struct GraphHandler {
+    json: bool, // Print the graph as JSON rather than DOT, set with --format json.
+}
+
+impl GraphHandler {
+    fn graph(lockfile: &Lockfile) -> Result<DependencyGraph, CommandError> {
+        let manifest = Manifest::read()?;
+        let field = |field: &str| {
+            manifest
+                .as_ref()
+                .and_then(|manifest| manifest.field(field))
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        let mut nodes = vec![GraphNode {
+            id: PROJECT_NODE.to_string(),
+            name: field("name"),
+            version: field("version"),
+        }];
+        let mut edges = lockfile
+            .requested
+            .iter()
+            .map(|(spec, package)| {
+                let (_, range) = Versions::parse_raw_package_details(spec.to_string());
+                GraphEdge {
+                    from: PROJECT_NODE.to_string(),
+                    to: package.to_string(),
+                    range,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for package in lockfile.packages.keys() {
+            let (name, version) = Versions::parse_raw_package_details(package.to_string());
+            nodes.push(GraphNode {
+                id: package.to_string(),
+                name,
+                version,
+            });
+
+            edges.extend(lockfile.direct_dependencies(package).into_iter().map(
+                |(range, dependency)| GraphEdge {
+                    from: package.to_string(),
+                    to: dependency,
+                    range,
+                },
+            ));
+        }
+
+        Ok(DependencyGraph { nodes, edges })
+    }
+
+    /// Writes the graph in the DOT language of Graphviz, such as `click graph | dot -Tsvg > graph.svg`.
+    fn dot(graph: &DependencyGraph) -> String {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+
+        let mut dot = String::from("digraph dependencies {\n");
+        for node in graph.nodes.iter() {
+            match node.id == PROJECT_NODE {
+                true => {
+                    let label = match node.name.is_empty() {
+                        true => PROJECT_NODE.to_string(),
+                        false => Versions::stringify(&node.name, &node.version),
+                    };
+                    dot += &format!(
+                        "  {} [label={}, shape=box];\n",
+                        quote(&node.id),
+                        quote(&label)
+                    );
+                }
+                false => dot += &format!("  {};\n", quote(&node.id)),
+            }
+        }
+        for edge in graph.edges.iter() {
+            dot += &format!(
+                "  {} -> {} [label={}];\n",
+                quote(&edge.from),
+                quote(&edge.to),
+                quote(&edge.range)
+            );
+        }
+        dot += "}";
+
+        dot
+    }
+}
+
+#[async_trait]
+impl CommandHandler for GraphHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--format") => {
+                    let format = args.value("--format")?;
+                    self.json = match format.as_str() {
+                        "dot" => false,
+                        "json" => true,
+                        _ => {
+                            return Err(ParseError::InvalidArgument(
+                                String::from("--format"),
+                                format,
+                            ))
+                        }
+                    };
+                }
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        let lockfile = Lockfile::read()?;
+        let graph = Self::graph(&lockfile)?;
+
+        if Output::is_json() {
+            return Output::json(&graph);
+        }
+
+        match self.json {
+            true => {
+                let graph_string = serde_json::to_string_pretty(&graph)
+                    .map_err(CommandError::FailedToSerializeOutput)?;
+                println!("{graph_string}");
+            }
+            false => println!("{}", Self::dot(&graph)),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use semver::Version;
+use serde_json::{Map, Value};
+
+use crate::{
+    config::Config,
+    errors::{CommandError, ParseError},
+    http::HTTPRequest,
+    output::Output,
+    util::Size,
+    versions::Versions,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "info",
+    aliases: &["view", "show"],
+    arguments: "<package[@range]> [field]",
+    description:
+        "Print the metadata of a package from the registry, or only the field given such as `dist.tarball`",
+    flags: &[],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct InfoHandler {
+    package_name: String,
+    range: String,         // A range or dist-tag, `latest` if none is given.
+    field: Option<String>, // Dot separated, array items are selected by their index such as `maintainers.0`.
+}
+
+impl InfoHandler {
+    /// The highest version the range matches, a dist-tag is looked up instead.
+    fn resolve_version(&self, packument: &Value) -> Result<String, CommandError> {
+        if Versions::is_dist_tag(&self.range) {
+            return packument["dist-tags"][&self.range]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| {
+                    CommandError::UnknownDistTag(
+                        self.package_name.to_string(),
+                        self.range.to_string(),
+                    )
+                });
+        }
+
+        let range = Versions::parse_semantic_version(&self.range)
+            .map_err(CommandError::InvalidPackageSpec)?;
+        let includes_prerelease = range.includes_prerelease();
+
+        packument["versions"]
+            .as_object()
+            .into_iter()
+            .flat_map(|versions| versions.keys())
+            .filter_map(|version| Version::from_str(version).ok())
+            .filter(|version| includes_prerelease || version.pre.is_empty())
+            .filter(|version| range.matches(version))
+            .max()
+            .map(|version| version.to_string())
+            .ok_or(CommandError::InvalidVersion)
+    }
+
+    /// The fields of the packument with those of the version on top, the same as npm shows them. `versions` is a list
+    /// of every version rather than their metadata.
+    fn view(packument: &Value, version: &str) -> Value {
+        let mut view = packument.as_object().cloned().unwrap_or_default();
+
+        let mut versions = packument["versions"]
+            .as_object()
+            .into_iter()
+            .flat_map(|versions| versions.keys())
+            .collect::<Vec<_>>();
+        versions.sort_by_cached_key(|version| Version::from_str(version).ok());
+        let versions = versions
+            .into_iter()
+            .map(|version| Value::from(version.as_str()));
+        view.insert(String::from("versions"), versions.collect());
+
+        if let Some(version_fields) = packument["versions"][version].as_object() {
+            view.extend(version_fields.clone());
+        }
+
+        Value::Object(view)
+    }
+
+    fn select<'a>(view: &'a Value, field: &str) -> Option<&'a Value> {
+        field.split('.').try_fold(view, |value, key| match value {
+            Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+            value => value.get(key),
+        })
+    }
+
+    /// Strings are printed without quotes so that they can be used in scripts as they are.
+    fn print_value(value: &Value) -> Result<(), CommandError> {
+        match value {
+            Value::String(value) => println!("{value}"),
+            value => println!(
+                "{}",
+                serde_json::to_string_pretty(value)
+                    .map_err(CommandError::FailedToSerializeOutput)?
+            ),
+        }
+
+        Ok(())
+    }
+
+    fn print_summary(view: &Value) {
+        let text = |field: &str| view[field].as_str().unwrap_or_default().to_string();
+        let object = |field: &str| view[field].as_object().cloned().unwrap_or_else(Map::new);
+
+        let license = match &view["license"] {
+            Value::Object(license) => license["type"].as_str().unwrap_or_default().to_string(),
+            license => license.as_str().unwrap_or("none").to_string(),
+        };
+        let dependencies = object("dependencies");
+        let version_count = view["versions"].as_array().map_or(0, Vec::len);
+
+        println!(
+            "{}@{} | {license} | deps: {} | versions: {version_count}",
+            text("name"),
+            text("version"),
+            dependencies.len()
+        );
+        for line in [text("description"), text("homepage")] {
+            if !line.is_empty() {
+                println!("{line}");
+            }
+        }
+
+        let dist = object("dist");
+        println!("\ndist");
+        for field in ["tarball", "shasum", "integrity"] {
+            if let Some(value) = dist.get(field).and_then(Value::as_str) {
+                println!(".{field}: {value}");
+            }
+        }
+        if let Some(unpacked_size) = dist.get("unpackedSize").and_then(Value::as_u64) {
+            println!(".unpackedSize: {}", Size(unpacked_size));
+        }
+
+        if !dependencies.is_empty() {
+            println!("\ndependencies:");
+            for (name, range) in dependencies.iter() {
+                println!("{name}: {}", range.as_str().unwrap_or_default());
+            }
+        }
+
+        let maintainers = view["maintainers"].as_array().cloned().unwrap_or_default();
+        if !maintainers.is_empty() {
+            println!("\nmaintainers:");
+            for maintainer in maintainers {
+                match (maintainer["name"].as_str(), maintainer["email"].as_str()) {
+                    (Some(name), Some(email)) => println!("- {name} <{email}>"),
+                    (Some(name), None) => println!("- {name}"),
+                    _ => println!("- {}", maintainer.as_str().unwrap_or_default()),
+                }
+            }
+        }
+
+        println!("\ndist-tags:");
+        for (tag, version) in object("dist-tags").iter() {
+            println!("{tag}: {}", version.as_str().unwrap_or_default());
+        }
+
+        if let Some(published) = view["time"][text("version")].as_str() {
+            println!("\npublished {published}");
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler for InfoHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Positional(package) if self.package_name.is_empty() => {
+                    (self.package_name, self.range) = Versions::parse_raw_package_details(package);
+                }
+                Arg::Positional(field) if self.field.is_none() => self.field = Some(field),
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        if self.package_name.is_empty() {
+            return Err(ParseError::MissingArgument(String::from("package")));
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        Config::load()?;
+
+        let packument =
+            HTTPRequest::full_package_data(Config::client()?, &self.package_name).await?;
+        let version = self.resolve_version(&packument)?;
+        let view = Self::view(&packument, &version);
+
+        let Some(field) = &self.field else {
+            Output::json(&view)?;
+            if !Output::is_json() {
+                Self::print_summary(&view);
+            }
+            return Ok(());
+        };
+
+        let value = Self::select(&view, field).ok_or_else(|| {
+            CommandError::FieldNotFound(
+                Versions::stringify(&self.package_name, &version),
+                field.to_string(),
+            )
+        })?;
+
+        Output::json(value)?;
+        if !Output::is_json() {
+            Self::print_value(value)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use std::{env, str::FromStr};
+
+use async_trait::async_trait;
+use semver::Version;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    manifest::{Manifest, OrderedJson, PACKAGE_JSON_PATH},
+    output::Output,
+    prompt::Prompt,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "init",
+    aliases: &[],
+    arguments: "",
+    description: "Create a package.json, or edit the one in the working directory",
+    flags: &[Flag::new(
+        &["-y", "--yes"],
+        "Write the defaults without asking anything",
+    )],
+    passthrough: false,
+};
+
+const DEFAULT_VERSION: &str = "1.0.0";
+const DEFAULT_MAIN: &str = "index.js";
+const DEFAULT_LICENSE: &str = "ISC";
+const MAX_NAME_LENGTH: usize = 214;
+
+#[derive(Default)]
+pub struct InitHandler {
+    yes: bool, // Write the defaults without asking anything, set with -y.
+}
+
+impl InitHandler {
+    /// Package names are lowercase and URL safe, scoped names have a single slash after the scope.
+    fn is_valid_name(name: &str) -> bool {
+        let unscoped = match name.strip_prefix('@') {
+            Some(scoped) => match scoped.split_once('/') {
+                Some((scope, unscoped)) if !scope.is_empty() => unscoped,
+                _ => return false,
+            },
+            None => name,
+        };
+
+        !unscoped.is_empty()
+            && name.len() <= MAX_NAME_LENGTH
+            && !unscoped.starts_with(['.', '_'])
+            && unscoped.chars().all(|character| {
+                character.is_ascii_lowercase()
+                    || character.is_ascii_digit()
+                    || "-._~".contains(character)
+            })
+    }
+
+    /// The name of the working directory made into a valid package name, used when package.json has none.
+    fn default_name() -> String {
+        let directory = env::current_dir()
+            .ok()
+            .and_then(|directory| Some(directory.file_name()?.to_string_lossy().to_lowercase()))
+            .unwrap_or_default();
+
+        let name = directory
+            .chars()
+            .map(
+                |character| match Self::is_valid_name(&character.to_string()) {
+                    true => character,
+                    false => '-',
+                },
+            )
+            .collect::<String>();
+        let name = name.trim_start_matches(['-', '.', '_']);
+
+        match name.is_empty() {
+            true => String::from("package"),
+            false => name.chars().take(MAX_NAME_LENGTH).collect(),
+        }
+    }
+
+    /// Asks until the answer is valid, the default is always accepted as it came from the package.json being edited.
+    fn ask_valid(
+        question: &str,
+        default: &str,
+        is_valid: fn(&str) -> bool,
+        invalid: &str,
+    ) -> Result<String, CommandError> {
+        loop {
+            let answer = Prompt::ask(question, default)?;
+            if answer == default || is_valid(&answer) {
+                return Ok(answer);
+            }
+
+            eprintln!("{invalid}");
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler for InitHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--yes") => self.yes = true,
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// An existing package.json is edited rather than replaced, its values are the defaults and its other fields are kept.
+    async fn execute(&self) -> Result<(), CommandError> {
+        let mut manifest = Manifest::read()?.unwrap_or_default();
+
+        let default = |field: &str, default: String| {
+            manifest
+                .field(field)
+                .map(|value| value.to_string())
+                .unwrap_or(default)
+        };
+
+        let defaults = [
+            ("name", default("name", Self::default_name())),
+            ("version", default("version", DEFAULT_VERSION.to_string())),
+            ("description", default("description", String::new())),
+            ("main", default("main", DEFAULT_MAIN.to_string())),
+            ("license", default("license", DEFAULT_LICENSE.to_string())),
+        ];
+
+        let mut fields = Vec::new();
+        for (field, default) in defaults {
+            let value = match (self.yes, field) {
+                (true, _) => default,
+                (false, "name") => Self::ask_valid(
+                    "package name",
+                    &default,
+                    Self::is_valid_name,
+                    "Package names can only contain lowercase letters, digits, '-', '.', '_' and '~'",
+                )?,
+                (false, "version") => Self::ask_valid(
+                    "version",
+                    &default,
+                    |version| Version::from_str(version).is_ok(),
+                    "Versions have to be valid semver, such as 1.0.0",
+                )?,
+                (false, "main") => Prompt::ask("entry point", &default)?,
+                (false, field) => Prompt::ask(field, &default)?,
+            };
+
+            fields.push((field, value));
+        }
+
+        for (field, value) in fields {
+            manifest.set_field(field, OrderedJson::String(value));
+        }
+
+        manifest.write()?;
+
+        let path = env::current_dir()
+            .map(|directory| directory.join("package.json").display().to_string())
+            .unwrap_or(PACKAGE_JSON_PATH.to_string());
+        match Output::is_json() {
+            true => Output::message(format!("Wrote to {path}")),
+            false => Output::message(format!("Wrote to {path}:\n\n{}", manifest.serialize()?)),
+        }
+
+        Output::json(&manifest.json)
+    }
+}
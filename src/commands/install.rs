@@ -1,133 +1,2200 @@
 use std::{
-    collections::HashMap,
-    env::Args,
-    fs::File,
-    io::Write,
-    sync::{mpsc::channel, Arc, Mutex},
+    collections::{BTreeMap, HashSet},
+    fs, path,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 use async_trait::async_trait;
-use semver::Comparator;
+use log::{debug, error, info, warn};
+use semver::{Op, Version};
 
 use crate::{
-    cache::{Cache, CACHE_DIRECTORY},
+    cache::Cache,
+    config::Config,
     errors::{CommandError, ParseError},
-    installer::{DependencyMapMutex, InstallContext, Installer, PackageBytes, PackageInfo},
-    util::{self, TaskAllocator},
-    versions::Versions,
+    hoisted::{HoistedLayout, HoistedPlan},
+    http::NetworkMode,
+    installer::{CacheOptions, CacheOutcome, Installer, Linker, PackageInfo, DEFAULT_CONCURRENCY},
+    isolated::IsolatedLayout,
+    lockfile::LockfileFormat,
+    logger::Logger,
+    manifest::{DependencyKind, Manifest, PACKAGE_JSON_PATH},
+    npm_lockfile::{NpmLockfile, NPM_LOCKFILE_PATH},
+    output::Output,
+    patch::Patches,
+    peers::PeerDependencies,
+    plan::{InstallPlan, PlanOptions},
+    platform::Platform,
+    pnpm_lockfile::{PnpmLockfile, PNPM_LOCKFILE_PATH},
+    prompt::Prompt,
+    reporter::{Broadcast, JsonReporter, ProgressEvent, Reporter, TerminalReporter},
+    scripts::Scripts,
+    shims::ShimPlatform,
+    source::Source,
+    transaction::Transaction,
+    types::{
+        DependencyMap, InstallReport, InstallSummary, InstallWarning, IntegrityAllowlist, Lockfile,
+        ReportedPackage, WarningCode,
+    },
+    util::{Size, TaskAllocator},
+    versions::{Strategy, VersionRange, Versions, LATEST},
+    workspaces::{Workspace, Workspaces},
+    yarn_lockfile::{YarnLockfile, YARN_LOCKFILE_PATH},
 };
 
-use super::command_handler::CommandHandler;
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+    prune::PruneHandler,
+    run::RunScriptHandler,
+};
 
-#[derive(Default)]
+pub const USAGE: Usage = Usage {
+    name: "install",
+    aliases: &["i", "add"],
+    arguments: "[package[@range]]...",
+    description: "Install packages and save them to package.json, or everything in package.json without a package",
+    flags: &[
+        Flag::new(&["-D", "--save-dev", "--dev"], "Save to devDependencies"),
+        Flag::new(&["-O", "--save-optional"], "Save to optionalDependencies"),
+        Flag::with_value(
+            &["--save-to"],
+            "group",
+            "Save to this group of package.json, such as buildDependencies",
+        ),
+        Flag::new(&["-E", "--save-exact"], "Save the exact version rather than a caret range"),
+        Flag::new(&["--save"], "Save to package.json, the default unless save=false is set"),
+        Flag::new(&["--no-save"], "Do not save to package.json"),
+        Flag::new(&["--dry-run"], "Print what would be added, updated and downloaded without installing"),
+        Flag::new(
+            &["--confirm"],
+            "Print how much would be downloaded first, and ask if it is more than confirm-size",
+        ),
+        Flag::new(&["--production"], "Skip devDependencies, also set by NODE_ENV=production"),
+        Flag::with_value(
+            &["--include"],
+            "group",
+            "Also install this group of package.json, such as buildDependencies",
+        ),
+        Flag::new(&["--ignore-scripts"], "Do not run the lifecycle scripts of packages"),
+        Flag::new(
+            &["--trusted-scripts"],
+            "Only run the scripts of packages in trustedDependencies, asking about the rest",
+        ),
+        Flag::new(&["-g", "--global"], "Install into the global directory"),
+        Flag::new(&["--offline"], "Only install what is in the cache"),
+        Flag::new(&["--prefer-offline"], "Use cached metadata without asking the registry"),
+        Flag::with_value(&["--linker"], "hoisted|isolated", "How packages are laid out in node_modules"),
+        Flag::new(&["--legacy-peer-deps"], "Do not install peer dependencies"),
+        Flag::new(
+            &["--prefer-lowest"],
+            "Install the lowest versions the ranges allow, to check the minimums they declare",
+        ),
+        Flag::with_value(&["--force-platform"], "os/cpu", "Install for another platform"),
+        Flag::with_value(
+            &["--target-platform"],
+            "unix|windows",
+            "Write the executables in node_modules/.bin for this platform",
+        ),
+        Flag::new(&["--no-bin-links"], "Do not link the executables of packages into node_modules/.bin"),
+        Flag::new(
+            &["--ignore-lockfile-urls"],
+            "Download locked packages from the registry rather than the URLs in the lockfile",
+        ),
+        Flag::new(
+            &["--production-only-lockfile"],
+            "Also write a lockfile without devDependencies, such as for a deployment image",
+        ),
+        Flag::with_value(
+            &["--lockfile-out"],
+            "path",
+            "Where --production-only-lockfile writes it, click-lock.production.json by default",
+        ),
+        Flag::with_value(
+            &["--lockfile-format"],
+            "json|toml",
+            "Write the lockfile as click-lock.json or click-lock.toml, rather than as the project has it",
+        ),
+        Flag::new(&["--engine-strict"], "Refuse versions that do not support the installed Node"),
+        Flag::new(
+            &["--no-engine-strict"],
+            "Only warn about versions that do not support the installed Node, even if engine-strict is set",
+        ),
+        Flag::new(&["--strict-signatures"], "Refuse versions the registry has not validly signed"),
+        Flag::with_value(
+            &["-w", "--workspace", "--filter"],
+            "workspace",
+            "Install for the workspaces this selects rather than the root",
+        ),
+        Flag::with_value(&["--concurrency"], "n", "How many registry requests are made at once"),
+        Flag::with_value(&["--report"], "path", "Write a JSON report of the install to this file"),
+        Flag::with_value(
+            &["--integrity-allowlist"],
+            "path",
+            "Only install versions whose integrity is listed in this file",
+        ),
+        Flag::new(&["--keep-going"], "Attempt every package even after failures"),
+        Flag::new(&["--frozen-lockfile"], "Fail rather than change the lockfile"),
+        Flag::new(&["--regenerate-lockfile"], "Discard corrupt lockfiles and rebuild them"),
+        Flag::new(&["--upgrade-integrity"], "Recompute the integrity of cached packages"),
+        Flag::new(&["--quiet-success"], "Print nothing if the install succeeds"),
+        Flag::new(
+            &["--watch"],
+            "Keep installing as package.json and the package.json of workspaces change",
+        ),
+        Flag::with_value(
+            &["--reporter"],
+            "terminal|json",
+            "Draw the progress, or print every event of the install as a line of JSON to stderr",
+        ),
+    ],
+    passthrough: false,
+};
+
+#[derive(Clone, Default)]
 pub struct InstallHandler {
-    package_name: String,
-    semantic_version: Option<Comparator>, // If None then assume latest version.
+    requested: Vec<RequestedPackage>, // If empty then install everything in package.json.
+    dependency_kind: DependencyKind,  // The package.json field the package is saved to.
+    production: bool, // Skip devDependencies when installing from package.json, also enabled by NODE_ENV=production.
+    include: Vec<DependencyKind>, // The custom groups also installed from package.json, set with --include.
+    quiet_success: bool,          // Print nothing to stdout if the install succeeds.
+    upgrade_integrity: bool, // Recompute the integrity of cached packages even if it is already recorded.
+    integrity_allowlist_path: Option<String>, // Only install package versions whose integrity is listed in this file.
+    report_path: Option<String>,              // Write a JSON report of the install to this file.
+    keep_going: bool,                         // Attempt every package even after failures.
+    regenerate_lockfile: bool, // Discard corrupt lockfiles, the project lockfile is rebuilt and cached packages are downloaded again.
+    concurrency: Option<usize>, // Maximum number of registry requests in flight, DEFAULT_CONCURRENCY if None.
+    ignore_scripts: bool,       // Do not run the lifecycle scripts of installed packages.
+    trusted_scripts: bool, // Only run the lifecycle scripts of packages in trustedDependencies, set with --trusted-scripts.
+    global: bool,          // Install into the global directory rather than the working directory.
+    network_mode: NetworkMode, // Whether cached metadata and tarballs are used without asking the registry, set by --prefer-offline and --offline.
+    linker: Linker,            // How packages are laid out in node_modules, set with --linker.
+    legacy_peer_deps: bool, // Do not install peer dependencies, and only warn when they are not satisfied.
+    strategy: Strategy, // Which version a range resolves to, the lowest with --prefer-lowest. Locked versions are then ignored.
+    platform: Platform, // The platform packages are installed for, the current one unless --force-platform is given.
+    target_platform: Option<ShimPlatform>, // The platform node_modules/.bin is written for, that of `platform` if None.
+    no_bin_links: bool, // Do not link executables into node_modules/.bin, which is recorded in the lockfile.
+    ignore_lockfile_urls: bool, // Download locked packages from the registry rather than their resolved URL, such as a mirror.
+    lockfile_format: Option<LockfileFormat>, // What the lockfile is written as, the format the project has if None.
+    production_only_lockfile: bool, // Also write a lockfile without devDependencies, to lockfile_out.
+    lockfile_out: Option<String>, // Where the production-only lockfile is written, PRODUCTION_LOCKFILE_PATH if None.
+    engine_strict: Option<bool>, // Refuse versions that do not support the installed Node, engine-strict in .npmrc if None.
+    strict_signatures: bool, // Refuse versions that are not signed by the registry, or whose signature or attestations are not valid.
+    workspaces: Vec<String>, // Install for the workspaces these select rather than the root, set with -w and --filter.
+    save_exact: bool, // Save the exact version installed rather than a caret range, also enabled by save-exact in .npmrc.
+    save: Option<bool>, // Save installed packages to package.json, `save` in the config if None, which is true by default.
+    dry_run: bool, // Print what the install would do without installing anything or changing package.json.
+    confirm: bool, // Print how much the install downloads before downloading it, asking first past confirm-size.
+    reporter: Arc<TerminalReporter>, // Draws the progress of the install, unless --quiet-success is given.
+    subscriber: Option<Arc<dyn Reporter>>, // Also receives every event, set with --reporter json or by the library.
+    nested: bool, // Installing as part of another command, which prints its own result with --json and rolls back itself.
+    frozen: bool, // Fail rather than change the lockfile, set by --frozen-lockfile and `click ci`.
+    watch: bool, // Keep node_modules in step with package.json until click is stopped, set with --watch.
 }
 
-impl InstallHandler {
-    fn write_lockfiles(dependency_map_mux: DependencyMapMutex) -> Result<(), CommandError> {
-        let dependency_map = dependency_map_mux.lock().unwrap();
+type PackageSpec = (String, Source);
+
+/// What a requested package resolved to, before anything is downloaded or linked.
+enum Resolution {
+    Link(path::PathBuf), // A directory depended on with link:, which is linked as it is
+    Cached(String),      // Already in the cache, formatted as package@version
+    Download(Box<PackageInfo>, Option<InstallWarning>), // Along with a warning if it does not support the installed Node
+}
+
+// The names of the workspaces, and the range of each dependency to install from package.json and the workspaces
+type WatchedDependencies = (Vec<String>, BTreeMap<String, String>);
 
-        for (package_name, package_lock) in dependency_map.iter() {
-            let mut package_lock_file = File::create(format!(
-                "{}/{}/package/click-lock.json",
-                *CACHE_DIRECTORY, package_name
-            ))
-            .map_err(CommandError::FailedToCreateFile)?;
+const WATCH_INTERVAL: Duration = Duration::from_millis(500); // How often package.json is read with --watch
+const PRODUCTION_LOCKFILE_PATH: &str = "./click-lock.production.json"; // Written with --production-only-lockfile
 
-            let package_lock_string = serde_json::to_string(package_lock)
-                .map_err(CommandError::FailedToSerializePackageLock)?;
+/// Reads the lockfile of another package manager, see InstallHandler::read_lockfile().
+type ImportLockfile = fn(&[PackageSpec]) -> Result<Option<Lockfile>, CommandError>; // Package name and where it is installed from
 
-            package_lock_file
-                .write_all(package_lock_string.as_bytes())
-                .map_err(CommandError::FailedToWriteFile)?;
+/// A package given to install by name, such as `react@^18`.
+#[derive(Clone)]
+struct RequestedPackage {
+    name: String,
+    semantic_version: Option<VersionRange>, // If None then assume latest version.
+    dist_tag: Option<String>, // A dist-tag such as `beta` given instead of a range, resolved to a version before installing.
+    raw_version: Option<String>, // The range as it was given, saved to package.json. If None the installed version is saved as a caret range.
+    source: Option<Source>, // Where the package is fetched from when it is not the registry, such as a git repository.
+}
+
+impl RequestedPackage {
+    fn parse(package_details: String) -> Result<Self, ParseError> {
+        // A package given only by where it is fetched from is named by its package.json once it has been fetched
+        let (name, raw_version) = match Source::from_spec(&package_details) {
+            Some(_) => (String::new(), package_details.to_string()),
+            None => Versions::parse_raw_package_details(package_details.to_string()),
+        };
+
+        if let Some(source) = Source::from_spec(&raw_version) {
+            return Ok(Self {
+                name,
+                semantic_version: None,
+                dist_tag: None,
+                raw_version: Some(raw_version),
+                source: Some(source),
+            });
         }
 
-        Ok(())
+        if Versions::is_dist_tag(&raw_version) && raw_version != LATEST {
+            return Ok(Self {
+                name,
+                semantic_version: None,
+                dist_tag: Some(raw_version),
+                raw_version: None,
+                source: None,
+            });
+        }
+
+        let (name, semantic_version) = Versions::parse_semantic_package_details(package_details)?;
+        Ok(Self {
+            name,
+            raw_version: semantic_version.is_some().then_some(raw_version),
+            semantic_version,
+            dist_tag: None,
+            source: None,
+        })
     }
 }
 
-#[async_trait]
-impl CommandHandler for InstallHandler {
-    fn parse(&mut self, args: &mut Args) -> Result<(), ParseError> {
-        let package_details = args
-            .next()
-            .ok_or(ParseError::MissingArgument(String::from("package name")))?;
+/// What an install did, used to write the install report.
+#[derive(Default)]
+struct InstallOutcome {
+    downloaded: DependencyMap,
+    cached: Vec<String>,
+    warnings: Vec<InstallWarning>,
+    errors: Vec<String>,
+}
 
-        let (package_name, semantic_version) =
-            Versions::parse_semantic_package_details(package_details)?;
-        self.package_name = package_name;
-        self.semantic_version = semantic_version;
+impl InstallOutcome {
+    /// Warnings are printed as they happen, except with `--json` where they are only in the result. One that was
+    /// already given, such as for a package reached through two dependents, is not given again.
+    fn warn(&mut self, warning: InstallWarning) {
+        if self.warnings.contains(&warning) {
+            return;
+        }
+
+        if !Output::is_json() {
+            warn!("{}", warning.message);
+        }
+        self.warnings.push(warning);
+    }
+}
+
+impl InstallHandler {
+    /// The allowlist is a JSON object mapping package@version to its expected integrity.
+    fn read_allowlist(path: &String) -> Result<IntegrityAllowlist, CommandError> {
+        let allowlist_raw =
+            fs::read_to_string(path).map_err(CommandError::FailedToReadAllowlist)?;
+        serde_json::from_str::<IntegrityAllowlist>(&allowlist_raw)
+            .map_err(CommandError::FailedToParseAllowlist)
+    }
+
+    /// Installs as part of another command, such as `click update`, which prints its own result rather than the install report.
+    pub fn nested() -> Self {
+        Self {
+            nested: true,
+            ..Default::default()
+        }
+    }
+
+    /// Installs as part of another command without printing anything, such as the package `click exec` runs.
+    pub fn quiet() -> Self {
+        Self {
+            nested: true,
+            quiet_success: true,
+            ..Default::default()
+        }
+    }
+
+    /// Sends every event of the install to the reporter as well, such as for a front-end embedding click.
+    pub fn report_to(&mut self, reporter: Arc<dyn Reporter>) {
+        self.subscriber = Some(reporter);
+    }
+
+    fn terminal_reporter(&self) -> Arc<TerminalReporter> {
+        Arc::new(TerminalReporter::new(
+            !self.quiet_success && !Logger::is_quiet() && self.subscriber.is_none(),
+        ))
+    }
+
+    /// The terminal reporter always receives the events, as it counts what was downloaded for the summary.
+    fn reporter(&self) -> Arc<dyn Reporter> {
+        match &self.subscriber {
+            Some(subscriber) => {
+                Arc::new(Broadcast(vec![self.reporter.clone(), subscriber.clone()]))
+            }
+            None => self.reporter.clone(),
+        }
+    }
+
+    /// Installs exactly the versions in the lockfile, as `click ci` does.
+    pub fn frozen(production: bool, ignore_scripts: bool, trusted_scripts: bool) -> Self {
+        Self {
+            production,
+            ignore_scripts,
+            trusted_scripts,
+            frozen: true,
+            ..Default::default()
+        }
+    }
+
+    /// The report is made whether or not the install succeeded.
+    fn report(
+        outcome: InstallOutcome,
+        result: &Result<(), CommandError>,
+        duration: Duration,
+    ) -> InstallReport {
+        let mut packages = outcome
+            .downloaded
+            .into_iter()
+            .map(|(package, package_lock)| ReportedPackage {
+                package,
+                cached: false,
+                integrity: package_lock.integrity,
+                dependencies: package_lock.dependencies,
+                deprecated: package_lock.deprecated,
+            })
+            .collect::<Vec<_>>();
+
+        for package in outcome.cached {
+            let package_lock = Cache::read_lock(&package).ok();
+            packages.push(ReportedPackage {
+                package,
+                cached: true,
+                integrity: package_lock
+                    .as_ref()
+                    .and_then(|lock| lock.integrity.clone()),
+                dependencies: package_lock
+                    .as_ref()
+                    .map(|lock| lock.dependencies.clone())
+                    .unwrap_or_default(),
+                deprecated: package_lock.and_then(|lock| lock.deprecated),
+            });
+        }
+
+        packages.sort_by(|a, b| a.package.cmp(&b.package));
+
+        let mut errors = outcome.errors;
+        if let Err(err) = result {
+            if errors.is_empty() {
+                errors.push(err.to_string());
+            }
+        }
+
+        let cached = packages.iter().filter(|package| package.cached).count();
+        InstallReport {
+            success: result.is_ok(),
+            duration_ms: duration.as_millis(),
+            summary: InstallSummary {
+                total: packages.len(),
+                downloaded: packages.len() - cached,
+                cached,
+            },
+            packages,
+            warnings: outcome.warnings,
+            errors,
+        }
+    }
+
+    /// Errors from the rollback are only logged, the error the install failed with is the one that is returned.
+    pub fn rollback(transaction: Transaction) {
+        match transaction.rollback() {
+            Ok(_) => info!("Rolled back the install, node_modules is as it was before"),
+            Err(err) => error!("{err}"),
+        }
+    }
+
+    fn write_report(path: &String, report: &InstallReport) -> Result<(), CommandError> {
+        let report_string =
+            serde_json::to_string_pretty(report).map_err(CommandError::FailedToSerializeReport)?;
+
+        fs::write(path, report_string).map_err(CommandError::FailedToWriteFile)
+    }
+
+    /// Reported and printed once an install has succeeded. Packages added to the cache include those extracted from the
+    /// tarball cache, which are not counted in the size downloaded.
+    fn finish(&self, outcome: &InstallOutcome, duration: Duration) {
+        let installed = outcome.downloaded.len() + outcome.cached.len();
+        self.reporter().report(ProgressEvent::Finished {
+            installed,
+            added: outcome.downloaded.len(),
+            downloaded_bytes: self.reporter.downloaded_bytes(),
+            duration_ms: duration.as_millis(),
+        });
+        if installed == 0 || self.quiet_success {
+            return;
+        }
+
+        Output::message(format!(
+            "Installed {installed} package(s), {} added to the cache and {} downloaded, in {:.1}s",
+            outcome.downloaded.len(),
+            Size(self.reporter.downloaded_bytes()),
+            duration.as_secs_f64()
+        ));
+    }
+
+    /// With `--regenerate-lockfile` a corrupt project lockfile is treated as if there was none, so it is rebuilt by this install.
+    /// The first install of a project from package.json imports the versions locked by the package manager it was
+    /// installed with before, from package-lock.json, yarn.lock or pnpm-lock.yaml in that order, see NpmLockfile.
+    fn read_lockfile(&self, packages: &[PackageSpec]) -> Result<Lockfile, CommandError> {
+        if self.requested.is_empty() && !Lockfile::exists() {
+            let requested = packages
+                .iter()
+                .filter(|(_, source)| source.is_registry())
+                .cloned()
+                .collect::<Vec<_>>();
+
+            let importers: [(&str, ImportLockfile); 3] = [
+                (NPM_LOCKFILE_PATH, NpmLockfile::import),
+                (YARN_LOCKFILE_PATH, YarnLockfile::import),
+                (PNPM_LOCKFILE_PATH, PnpmLockfile::import),
+            ];
+
+            for (path, import) in importers {
+                if let Some(lockfile) = import(&requested)? {
+                    if !self.quiet_success {
+                        Output::message(format!("Imported the versions locked in '{path}'"));
+                    }
+
+                    return Ok(lockfile);
+                }
+            }
+        }
+
+        match Lockfile::read() {
+            Err(CommandError::CorruptLockfile { .. }) if self.regenerate_lockfile => {
+                info!(
+                    "Discarded corrupt '{}', it will be regenerated",
+                    LockfileFormat::current().path()
+                );
+                Ok(Lockfile::new())
+            }
+            result => result,
+        }
+    }
+
+    /// With `--ignore-scripts` packages that declare install scripts are listed for the user to audit,
+    /// as they may not work without them. Packages linked from the cache are included using their lockfile.
+    fn packages_with_install_scripts(outcome: &InstallOutcome) -> Vec<String> {
+        let mut packages = outcome
+            .downloaded
+            .iter()
+            .filter(|(_, package_lock)| package_lock.has_install_script)
+            .map(|(package, _)| package.to_string())
+            .collect::<Vec<_>>();
+
+        packages.extend(outcome.cached.iter().filter_map(|package| {
+            let package_lock = Cache::read_lock(package).ok()?;
+            package_lock.has_install_script.then(|| package.to_string())
+        }));
+
+        packages.sort();
+        packages
+    }
+
+    /// Packages the registry has deprecated, with the message each was deprecated with. Packages linked from the cache
+    /// are included using their lockfile.
+    fn deprecated_packages(outcome: &InstallOutcome) -> Vec<(String, String)> {
+        let mut packages = outcome
+            .downloaded
+            .iter()
+            .filter_map(|(package, package_lock)| {
+                Some((package.to_string(), package_lock.deprecated.clone()?))
+            })
+            .collect::<Vec<_>>();
+
+        packages.extend(outcome.cached.iter().filter_map(|package| {
+            let package_lock = Cache::read_lock(package).ok()?;
+            Some((package.to_string(), package_lock.deprecated?))
+        }));
+
+        packages.sort();
+        packages
+    }
+
+    /// Cached packages are not downloaded again, so those with only a SHA-1 shasum in their lockfile
+    /// are migrated to SHA-512 integrity here. With `--upgrade-integrity` every cached package is rehashed.
+    /// The migration needs version data from the registry, so it is left for the next install that is not offline.
+    async fn upgrade_cached_integrity(
+        &self,
+        client: reqwest::Client,
+        packages: Vec<String>,
+    ) -> Result<(), CommandError> {
+        if self.network_mode == NetworkMode::Offline {
+            return Ok(());
+        }
+
+        let handles = packages
+            .into_iter()
+            .map(|package| {
+                TaskAllocator::add_task(Cache::upgrade_integrity(
+                    client.clone(),
+                    package,
+                    self.upgrade_integrity,
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.await.expect("Failed to join integrity task")?;
+        }
 
         Ok(())
     }
 
-    async fn execute(&self) -> Result<(), CommandError> {
-        // In future we could automatically find a version that is valid for both limits to save storage, but that's not neccessary right now
-        println!("Installing '{}'..", self.package_name);
+    /// The dependency group given to a flag such as `--save-to`, which has to be a valid name for a group.
+    fn group(flag: &str, args: &mut Arguments) -> Result<DependencyKind, ParseError> {
+        let group = args.value(flag)?;
 
-        let client = reqwest::Client::new();
-        let semantic_version = self.semantic_version.as_ref();
-        let full_version = Versions::resolve_full_version(semantic_version);
-        let full_version = full_version.as_ref();
+        DependencyKind::parse(&group)
+            .ok_or_else(|| ParseError::InvalidArgument(flag.to_string(), group))
+    }
+
+    /// The workspaces selected with `-w` or `--filter`, None if the install is for the whole project.
+    fn selected_workspaces(&self) -> Result<Option<Vec<Workspace>>, CommandError> {
+        if self.workspaces.is_empty() {
+            return Ok(None);
+        }
+
+        Workspaces::filter(Workspaces::discover()?, &self.workspaces).map(Some)
+    }
+
+    /// The package.json files whose dependencies are installed, which are those of the selected workspaces,
+    /// or the root along with every workspace if none were selected.
+    fn manifests<'a>(
+        root: &'a Manifest,
+        workspaces: &'a [Workspace],
+        selected: Option<&'a [Workspace]>,
+    ) -> Vec<&'a Manifest> {
+        match selected {
+            Some(selected) => selected
+                .iter()
+                .map(|workspace| &workspace.manifest)
+                .collect(),
+            None => [root]
+                .into_iter()
+                .chain(workspaces.iter().map(|workspace| &workspace.manifest))
+                .collect(),
+        }
+    }
+
+    /// Every dependency and devDependency in package.json, and in the package.json of every workspace, used when install
+    /// is run without a package. With `--production` devDependencies are left out. Dependencies on a workspace are
+    /// left out too, as the workspace is linked instead.
+    async fn manifest_packages(
+        &self,
+        client: reqwest::Client,
+        workspaces: &[Workspace],
+        selected: Option<&[Workspace]>,
+    ) -> Result<Vec<PackageSpec>, CommandError> {
+        let mut packages: Vec<PackageSpec> = Vec::new();
+        for (package_name, range) in self.manifest_dependencies(workspaces, selected)? {
+            let source =
+                Installer::parse_source(client.clone(), &package_name, &range, self.network_mode)
+                    .await?;
+
+            packages.push((package_name, source));
+        }
+
+        Ok(packages)
+    }
 
-        let (is_cached, cached_version) =
-            Cache::exists(&self.package_name, full_version, semantic_version).await?;
+    /// The name and range of each dependency to install from package.json, the first range of a package wins.
+    fn manifest_dependencies(
+        &self,
+        workspaces: &[Workspace],
+        selected: Option<&[Workspace]>,
+    ) -> Result<Vec<(String, String)>, CommandError> {
+        let manifest = Manifest::read()?.ok_or(CommandError::NoManifest)?;
+        let manifests = Self::manifests(&manifest, workspaces, selected);
+
+        let mut kinds = vec![DependencyKind::Production, DependencyKind::Optional];
+        if !self.production {
+            kinds.push(DependencyKind::Development);
+        }
+        kinds.extend(self.include.iter().cloned());
+
+        let dependencies = manifests
+            .iter()
+            .flat_map(|manifest| kinds.iter().map(|kind| manifest.dependencies(kind.field())))
+            .flatten();
+
+        let mut filtered: Vec<(String, String)> = Vec::new();
+        for (package_name, range) in dependencies {
+            // Workspaces depend on each other with the workspace: protocol, they are linked rather than installed
+            if let Some(Source::Workspace(range)) = Source::from_spec(&range) {
+                Workspaces::resolve(workspaces, &package_name, &range)?;
+                continue;
+            }
+
+            if filtered.iter().any(|(name, _)| name == &package_name)
+                || workspaces
+                    .iter()
+                    .any(|workspace| workspace.name == package_name)
+            {
+                continue;
+            }
+
+            filtered.push((package_name, range));
+        }
+
+        Ok(filtered)
+    }
+
+    /// The dependencies in package.json as the specs they are locked under, without asking the registry anything.
+    /// Fails if there is no lockfile, or if it does not have every range in package.json.
+    pub fn locked_packages(&self) -> Result<Vec<PackageSpec>, CommandError> {
+        if !Lockfile::exists() {
+            return Err(CommandError::NoLockfile);
+        }
+
+        let lockfile = Lockfile::read()?;
+        let workspaces = Workspaces::discover()?;
+        let selected = self.selected_workspaces()?;
+
+        let mut packages: Vec<PackageSpec> = Vec::new();
+        let mut unlocked = Vec::new();
+        for (package_name, range) in self.manifest_dependencies(&workspaces, selected.as_deref())? {
+            match Self::locked_source(&lockfile, &package_name, &range) {
+                Some(source) => packages.push((package_name, source)),
+                None => unlocked.push(format!("{package_name}@{range}")),
+            }
+        }
+
+        if !unlocked.is_empty() {
+            return Err(CommandError::LockfileOutOfSync(unlocked));
+        }
+
+        Ok(packages)
+    }
+
+    /// Where a dependency is locked from, or None if it is not locked or the version it is locked to is missing
+    /// from the lockfile. A dist-tag is locked under the exact version it pointed at, which can only be told apart
+    /// without the registry when the package is locked under one exact version.
+    fn locked_source(lockfile: &Lockfile, package_name: &String, range: &str) -> Option<Source> {
+        let semantic_version = if let Some(source) = Source::from_spec(range) {
+            if let Source::Link(path) = &source {
+                let linked = lockfile.links.get(package_name);
+                return (linked == Some(&path.display().to_string())).then_some(source);
+            }
+
+            let package = lockfile.requested.get(&source.spec(package_name))?;
+            return lockfile.packages.contains_key(package).then_some(source);
+        } else if range == LATEST {
+            None
+        } else if Versions::is_dist_tag(range) {
+            let mut exact = lockfile.requested.keys().filter_map(|spec| {
+                let (name, semantic_version) =
+                    Versions::parse_semantic_package_details(spec.to_string()).ok()?;
+                let semantic_version = semantic_version?;
+                let is_exact = semantic_version.comparator()?.op == Op::Exact;
+                (&name == package_name && is_exact).then_some(semantic_version)
+            });
+
+            let semantic_version = exact.next()?;
+            if exact.next().is_some() {
+                return None;
+            }
+            Some(semantic_version)
+        } else {
+            Some(Versions::parse_semantic_version(range).ok()?)
+        };
+
+        let package = lockfile
+            .requested
+            .get(&Lockfile::spec(package_name, semantic_version.as_ref()))?;
+        lockfile
+            .packages
+            .contains_key(package)
+            .then_some(Source::Registry(semantic_version))
+    }
+
+    /// The optionalDependencies of package.json, which are skipped rather than failing the install if they fail.
+    /// A package that is installed by name always has to install, even when it is saved with --save-optional.
+    fn optional_packages(
+        &self,
+        workspaces: &[Workspace],
+        selected: Option<&[Workspace]>,
+    ) -> Result<HashSet<String>, CommandError> {
+        if !self.requested.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let manifest = Manifest::read()?.ok_or(CommandError::NoManifest)?;
+
+        Ok(Self::manifests(&manifest, workspaces, selected)
+            .into_iter()
+            .flat_map(|manifest| manifest.dependencies(DependencyKind::Optional.field()))
+            .map(|(package_name, _)| package_name)
+            .collect())
+    }
+
+    /// A dist-tag is installed at the version it points at now, which is what gets locked and saved.
+    /// Dist-tags need the metadata of their package, so every package is resolved at the same time.
+    async fn requested_packages(
+        &self,
+        client: reqwest::Client,
+    ) -> Result<Vec<PackageSpec>, CommandError> {
+        let handles = self
+            .requested
+            .iter()
+            .cloned()
+            .map(|requested| {
+                let client = client.clone();
+                let network_mode = self.network_mode;
+
+                TaskAllocator::add_task(async move {
+                    let source = match (requested.source, &requested.dist_tag) {
+                        // Only fetching it tells what a package given by its URL is named
+                        (Some(source), _) if requested.name.is_empty() => {
+                            let version_data = source
+                                .version_data(client, &requested.name, None, network_mode)
+                                .await?;
+                            return Ok((version_data.name, source));
+                        }
+                        (Some(source), _) => source,
+                        (None, Some(dist_tag)) => Source::Registry(
+                            Installer::parse_range(client, &requested.name, dist_tag, network_mode)
+                                .await?,
+                        ),
+                        (None, None) => Source::Registry(requested.semantic_version),
+                    };
+
+                    Ok::<_, CommandError>((requested.name, source))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut packages = Vec::new();
+        for handle in handles {
+            packages.push(handle.await.expect("Failed to join resolve task")?);
+        }
+
+        Ok(packages)
+    }
+
+    async fn install(&self, outcome: &mut InstallOutcome) -> Result<(), CommandError> {
+        // This has to happen before the cache is first read, which resolving the packages can do, otherwise the
+        // corrupt entries would still be listed
+        if self.regenerate_lockfile && !self.dry_run {
+            for package in Cache::remove_corrupt_locks()? {
+                info!("Removed the cached lockfile of '{package}', it will be installed again");
+            }
+        }
+
+        let client = Config::client()?;
+        let workspaces = Workspaces::discover()?;
+        let selected = self.selected_workspaces()?;
+        let packages = match self.requested.is_empty() {
+            false => self.requested_packages(client.clone()).await?,
+            true if self.frozen => self.locked_packages()?,
+            true => {
+                self.manifest_packages(client.clone(), &workspaces, selected.as_deref())
+                    .await?
+            }
+        };
+        let optional_packages = self.optional_packages(&workspaces, selected.as_deref())?;
+
+        if self.requested.is_empty() && !self.quiet_success {
+            Output::message(format!(
+                "Installing {} package(s) from package.json..",
+                packages.len()
+            ));
+        }
+
+        if self.dry_run {
+            return self.dry_run(client, &packages).await;
+        }
+
+        if self.confirm {
+            self.confirm(client.clone(), &packages).await?;
+        }
+
+        self.run_project_scripts(&["preinstall"])?;
+
+        let integrity_allowlist = match &self.integrity_allowlist_path {
+            Some(path) => Some(Arc::new(Self::read_allowlist(path)?)),
+            None => None,
+        };
+
+        let mut lockfile = self.read_lockfile(&packages)?;
+        let original_lockfile = self.frozen.then(|| lockfile.clone());
 
         Installer::create_modules_dir();
 
-        if is_cached {
-            let version = cached_version.expect("Could not resolve version of cached package");
-            Cache::load_cached_version(Versions::stringify(&self.package_name, &version));
+        let mut failed = Vec::new();
+        let mut unsaved = HashSet::new(); // Requested packages that failed with --keep-going, they are not saved
+        let mut result = self
+            .install_packages(
+                client.clone(),
+                integrity_allowlist.clone(),
+                &mut lockfile,
+                &packages,
+                &optional_packages,
+                outcome,
+            )
+            .await
+            .map(|(package_failed, package_unsaved)| {
+                failed = package_failed;
+                unsaved = package_unsaved;
+            });
+
+        // Specs of package.json that changed since it was last installed are no longer requested. Only a full install
+        // has every spec of package.json to tell.
+        if result.is_ok() && self.requested.is_empty() && selected.is_none() && !self.production {
+            let specs = packages
+                .iter()
+                .map(|(package_name, source)| source.spec(package_name))
+                .collect();
+            lockfile.retain_requested(&specs);
+        }
+
+        // Overrides come after, so that a version they force is not unified away
+        lockfile.unify_versions();
+
+        if result.is_ok() {
+            result = self
+                .install_overrides(
+                    client.clone(),
+                    integrity_allowlist.clone(),
+                    &mut lockfile,
+                    outcome,
+                )
+                .await;
+        }
+
+        // Packages downloaded for one dependency are found in the cache by those after it
+        outcome.cached.sort();
+        outcome.cached.dedup();
+        outcome
+            .cached
+            .retain(|package| !outcome.downloaded.contains_key(package));
+
+        Patches::lock(&mut lockfile)?;
+
+        // Anything the install changed in the lockfile is rolled back along with node_modules
+        if let (Ok(_), Some(original_lockfile)) = (&result, &original_lockfile) {
+            let changes = original_lockfile.changes(&lockfile);
+            if !changes.is_empty() {
+                return Err(CommandError::LockfileOutOfSync(changes));
+            }
+        }
+
+        // Packages that were installed are locked and linked even if one after them failed, which is kept with
+        // --keep-going and rolled back otherwise. A frozen lockfile is left as it is, even rewritten unchanged.
+        lockfile.no_bin_links = self.no_bin_links;
+        if !self.frozen && (!lockfile.requested.is_empty() || !lockfile.links.is_empty()) {
+            lockfile.write_as(self.lockfile_format.unwrap_or_else(LockfileFormat::current))?;
+        }
+
+        if self.linker == Linker::Hoisted {
+            HoistedLayout::link(&HoistedPlan::from_lockfile(&lockfile))?;
+        }
+
+        Workspaces::link(&workspaces)?;
+        // Packages that failed to download are not in the cache to link the executables of, so why they failed is reported
+        let linked = Installer::link_bins(
+            self.target_platform
+                .unwrap_or_else(|| ShimPlatform::of(&self.platform)),
+        );
+
+        result?;
+        linked?;
 
+        self.check_peer_dependencies(outcome)?;
+
+        if !self.ignore_scripts {
+            failed.extend(self.run_lifecycle_scripts(outcome)?);
+        }
+
+        // Requested packages are only saved once all of them have installed, or with --keep-going those that did
+        if !failed.is_empty() {
+            self.save_requested(&mut lockfile, &packages, &unsaved, selected.as_deref())?;
+            return Err(CommandError::PartialInstall(failed));
+        }
+
+        self.apply_patches(&lockfile)?;
+        Installer::clear_install_state();
+
+        self.save_requested(&mut lockfile, &packages, &unsaved, selected.as_deref())?;
+
+        if self.production_only_lockfile {
+            self.write_production_lockfile(&lockfile, &workspaces, selected.as_deref())?;
+        }
+
+        self.run_project_scripts(&["install", "postinstall"])
+    }
+
+    /// Writes the lockfile without the devDependencies of package.json and whatever only they depend on, once the
+    /// requested packages are saved. A package that is also a dependency is kept, along with everything it locks.
+    fn write_production_lockfile(
+        &self,
+        lockfile: &Lockfile,
+        workspaces: &[Workspace],
+        selected: Option<&[Workspace]>,
+    ) -> Result<(), CommandError> {
+        let names = |production| -> Result<HashSet<String>, CommandError> {
+            let handler = Self {
+                production,
+                ..self.clone()
+            };
+            Ok(handler
+                .manifest_dependencies(workspaces, selected)?
+                .into_iter()
+                .map(|(package_name, _)| package_name)
+                .collect())
+        };
+
+        let production = names(true)?;
+        let development = names(false)?
+            .into_iter()
+            .filter(|package_name| !production.contains(package_name))
+            .collect::<HashSet<_>>();
+
+        let path = self
+            .lockfile_out
+            .as_deref()
+            .unwrap_or(PRODUCTION_LOCKFILE_PATH);
+        lockfile
+            .without(&development)
+            .write_to(path::Path::new(path))?;
+
+        if !self.quiet_success {
+            Output::message(format!(
+                "Wrote the lockfile without devDependencies to '{path}'"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Saves the requested packages to package.json, other than those in `unsaved`, which failed with --keep-going.
+    /// Each is locked under the range it is saved with from then on, rather than the one it was installed with.
+    fn save_requested(
+        &self,
+        lockfile: &mut Lockfile,
+        packages: &[PackageSpec],
+        unsaved: &HashSet<String>,
+        selected: Option<&[Workspace]>,
+    ) -> Result<(), CommandError> {
+        if !self.save.unwrap_or(Config::get()?.save) {
             return Ok(());
         }
 
-        let version_data = Installer::get_version_data(
-            client.clone(),
-            &self.package_name,
-            full_version,
-            semantic_version,
-        )
-        .await?;
+        let mut replaced = false;
+        for (requested, (package_name, source)) in self.requested.iter().zip(packages.iter()) {
+            if unsaved.contains(package_name) {
+                continue;
+            }
+
+            let range =
+                self.save_to_manifest(lockfile, requested, package_name, source, selected)?;
+            if let Some(spec) = Self::saved_spec(package_name, &range) {
+                replaced |= lockfile.replace_requested(&source.spec(package_name), spec);
+            }
+        }
+
+        match replaced {
+            true => lockfile.write_as(self.lockfile_format.unwrap_or_else(LockfileFormat::current)),
+            false => Ok(()),
+        }
+    }
+
+    /// The spec a package saved to package.json with a range is installed as, the same as manifest_packages() parses
+    /// it. None for a dist-tag other than `latest`, which is only known once the registry is asked.
+    fn saved_spec(package_name: &String, range: &str) -> Option<String> {
+        if let Some(source) = Source::from_spec(range) {
+            return Some(source.spec(package_name));
+        }
+
+        match range {
+            LATEST => Some(Lockfile::spec(package_name, None)),
+            range => Versions::parse_semantic_version(range)
+                .ok()
+                .map(|semantic_version| Lockfile::spec(package_name, Some(&semantic_version))),
+        }
+    }
+
+    /// Resolves everything the install would add, update and download, along with the size of each tarball it would
+    /// download. Nothing is downloaded or changed.
+    async fn plan(
+        &self,
+        client: reqwest::Client,
+        packages: &[PackageSpec],
+    ) -> Result<InstallPlan, CommandError> {
+        let lockfile = self.read_lockfile(packages)?;
+        let options = PlanOptions {
+            lockfile: &lockfile,
+            network_mode: self.network_mode,
+            platform: &self.platform,
+            legacy_peer_deps: self.legacy_peer_deps,
+            linker: self.linker,
+            strategy: self.strategy,
+        };
+
+        // Only packages from the registry can be resolved without fetching them
+        let registry_packages = packages
+            .iter()
+            .filter_map(|(package_name, source)| match source {
+                Source::Registry(semantic_version) => {
+                    Some((package_name.to_string(), semantic_version.clone()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut plan = InstallPlan::resolve(client.clone(), &registry_packages, options).await?;
+        if self.network_mode != NetworkMode::Offline {
+            plan.measure_tarballs(client, self.concurrency.unwrap_or(DEFAULT_CONCURRENCY))
+                .await;
+        }
+
+        Ok(plan)
+    }
+
+    /// Describes how much is downloaded, such as `1.2 MB, 4.5 MB unpacked`. Returns None if neither size is known.
+    fn describe_size(tarball_size: Option<u64>, unpacked_size: Option<u64>) -> Option<String> {
+        match (tarball_size, unpacked_size) {
+            (Some(tarball_size), Some(unpacked_size)) => Some(format!(
+                "{}, {} unpacked",
+                Size(tarball_size),
+                Size(unpacked_size)
+            )),
+            (Some(tarball_size), None) => Some(Size(tarball_size).to_string()),
+            (None, Some(unpacked_size)) => Some(format!("{} unpacked", Size(unpacked_size))),
+            (None, None) => None,
+        }
+    }
+
+    /// With `--confirm` the install is resolved before anything is downloaded, to print how much it downloads. One that
+    /// downloads more than `confirm-size` has to be confirmed, and is refused when there is nobody to ask. Tarballs
+    /// whose size the registry does not tell are counted at their unpacked size.
+    async fn confirm(
+        &self,
+        client: reqwest::Client,
+        packages: &[PackageSpec],
+    ) -> Result<(), CommandError> {
+        let plan = self.plan(client, packages).await?;
+        if plan.downloads.is_empty() {
+            return Ok(());
+        }
+
+        let size = plan
+            .downloads
+            .iter()
+            .filter_map(|download| download.tarball_size.or(download.unpacked_size))
+            .sum::<u64>();
+        let described = Self::describe_size(plan.tarball_size(), plan.download_size())
+            .unwrap_or(String::from("of unknown size"));
+        Output::message(format!(
+            "{} new package(s) to download, {described}",
+            plan.downloads.len()
+        ));
+
+        let confirm_size = Config::get()?.confirm_size;
+        if size <= confirm_size {
+            return Ok(());
+        }
+
+        let question = format!(
+            "That is more than {} (confirm-size), install anyway? y/n",
+            Size(confirm_size)
+        );
+        match !self.nested
+            && Prompt::is_interactive()
+            && Prompt::ask(&question, "n")?.eq_ignore_ascii_case("y")
+        {
+            true => Ok(()),
+            false => Err(CommandError::InstallNotConfirmed(Size(size).to_string())),
+        }
+    }
+
+    /// Resolves the install and prints what it would add, update and download, without changing anything.
+    async fn dry_run(
+        &self,
+        client: reqwest::Client,
+        packages: &[PackageSpec],
+    ) -> Result<(), CommandError> {
+        let plan = self.plan(client, packages).await?;
+
+        if plan.added.is_empty() && plan.updated.is_empty() {
+            Output::message("Dry run, nothing would be added or updated");
+        } else {
+            Output::message(format!(
+                "Dry run, {} package(s) would be added and {} updated:",
+                plan.added.len(),
+                plan.updated.len()
+            ));
+        }
+
+        for planned in plan.added.iter() {
+            Output::message(format!("  + {}", planned.package));
+        }
+
+        for planned in plan.updated.iter() {
+            let previous = planned.previous.as_deref().unwrap_or_default();
+            Output::message(format!("  ~ {} (from {previous})", planned.package));
+        }
+
+        let sourced = packages
+            .iter()
+            .filter(|(_, source)| !matches!(source, Source::Registry(_)));
+        for (package_name, source) in sourced {
+            Output::message(format!(
+                "  {package_name} would be fetched from {source} when installed"
+            ));
+        }
 
-        let (bytes_sender, bytes_receiver) = channel::<PackageBytes>();
+        for package in plan.skipped_optional.iter() {
+            Output::message(format!(
+                "  Optional dependency '{package}' would be skipped as it could not be resolved"
+            ));
+        }
+
+        if plan.downloads.is_empty() {
+            Output::message("Everything is in the cache, nothing would be downloaded");
+            return Output::json(&plan);
+        }
+
+        match Self::describe_size(plan.tarball_size(), plan.download_size()) {
+            Some(size) => Output::message(format!(
+                "{} package(s) would be downloaded, {size}:",
+                plan.downloads.len()
+            )),
+            None => Output::message(format!(
+                "{} package(s) would be downloaded:",
+                plan.downloads.len()
+            )),
+        }
+
+        for download in plan.downloads.iter() {
+            match Self::describe_size(download.tarball_size, download.unpacked_size) {
+                Some(size) => Output::message(format!("  {} ({size})", download.package)),
+                None => Output::message(format!("  {}", download.package)),
+            }
+        }
 
-        TaskAllocator::add_blocking(move || {
-            while let Ok((package_dest, bytes)) = bytes_receiver.recv() {
-                util::extract_tarball(bytes, package_dest).unwrap();
+        Output::json(&plan)
+    }
+
+    /// Peer dependencies that were installed can still conflict with another version at the top of node_modules, which
+    /// fails the install unless `--legacy-peer-deps` is given. Those are only warned about, as are peers that are missing.
+    fn check_peer_dependencies(&self, outcome: &mut InstallOutcome) -> Result<(), CommandError> {
+        for issue in PeerDependencies::check(self.linker)? {
+            if issue.installed.is_some() && !self.legacy_peer_deps {
+                return Err(CommandError::PeerConflict(issue.to_string()));
             }
+
+            outcome.warn(InstallWarning::new(
+                WarningCode::PeerDependency,
+                Some(&issue.package),
+                issue.to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Patches are applied once install scripts have run, the same as patch-package, so they cannot change what a script
+    /// builds. The packages whose patched copy was made or removed are linked again.
+    fn apply_patches(&self, lockfile: &Lockfile) -> Result<(), CommandError> {
+        let changed = Patches::apply(lockfile)?;
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        match self.linker {
+            Linker::Hoisted => HoistedLayout::link(&HoistedPlan::from_lockfile(lockfile))?,
+            Linker::Isolated => {
+                for package in changed.iter() {
+                    if lockfile.packages.contains_key(package) {
+                        IsolatedLayout::relink(package)?;
+                    }
+                }
+            }
+        }
+
+        if !self.quiet_success {
+            for package in changed
+                .iter()
+                .filter(|package| lockfile.patches.contains_key(*package))
+            {
+                Output::message(format!(
+                    "Patched {package} with '{}'",
+                    Patches::path(package).display()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs scripts of the project itself such as `preinstall`, the same as npm does around an install.
+    /// Installs for other commands and global installs are not installs of the project.
+    fn run_project_scripts(&self, script_names: &[&str]) -> Result<(), CommandError> {
+        if self.ignore_scripts || self.nested || self.global {
+            return Ok(());
+        }
+
+        let Some(manifest) = Manifest::read()? else {
+            return Ok(());
+        };
+        for script_name in script_names {
+            RunScriptHandler::run_if_present(&manifest, script_name, None)?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs the lifecycle scripts of every package in node_modules that has not had them run yet, which is usually
+    /// only those that were just downloaded. Dependencies are run first, as scripts often use them.
+    /// Returns the packages whose scripts failed, which is only ever not empty with `--keep-going`.
+    fn run_lifecycle_scripts(
+        &self,
+        outcome: &mut InstallOutcome,
+    ) -> Result<Vec<String>, CommandError> {
+        let mut packages = Vec::new();
+        for package in Installer::linked_packages()? {
+            if !Cache::read_lock(&package)?.install_scripts_run {
+                packages.push(package);
+            }
+        }
+
+        let packages = Scripts::in_dependency_order(packages)?;
+
+        let mut failed = Vec::new();
+        for package in self.trusted_packages(packages, outcome)? {
+            match Scripts::run_lifecycle(&package, self.quiet_success, self.reporter().as_ref()) {
+                Ok(_) => continue,
+                Err(err) if self.keep_going => {
+                    error!("{err}");
+                    outcome.errors.push(err.to_string());
+                    failed.push(package);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(failed)
+    }
+
+    /// With `--trusted-scripts`, or once package.json has `trustedDependencies`, only the packages listed there have
+    /// their scripts run, the same as bun. Others are asked about when stdin is a terminal and added to the list if
+    /// they are approved, the rest are skipped with a warning and asked about again on the next install.
+    fn trusted_packages(
+        &self,
+        packages: Vec<String>,
+        outcome: &mut InstallOutcome,
+    ) -> Result<Vec<String>, CommandError> {
+        let mut manifest = Manifest::read()?;
+        let trusted = manifest.as_ref().and_then(Manifest::trusted_dependencies);
+        if !self.trusted_scripts && trusted.is_none() {
+            return Ok(packages);
+        }
+
+        let trusted = trusted.unwrap_or_default();
+        let interactive = !self.nested && Prompt::is_interactive();
+
+        let mut run = Vec::new();
+        let mut approved = Vec::new();
+        let mut skipped = Vec::new();
+        for package in packages {
+            let (package_name, _) = Versions::parse_raw_package_details(package.to_string());
+            if trusted.contains(&package_name) {
+                run.push(package);
+                continue;
+            }
+
+            let scripts = Scripts::lifecycle_scripts(&package_name)?
+                .into_iter()
+                .map(|(lifecycle, script)| format!("{lifecycle}: {script}"))
+                .collect::<Vec<_>>();
+            let question = format!(
+                "'{package}' has install scripts ({}), run them and trust it from now on? y/n",
+                scripts.join(", ")
+            );
+
+            match interactive && Prompt::ask(&question, "n")?.eq_ignore_ascii_case("y") {
+                true => {
+                    approved.push(package_name);
+                    run.push(package);
+                }
+                false => skipped.push(package),
+            }
+        }
+
+        if !approved.is_empty() {
+            let manifest = manifest.get_or_insert_with(Manifest::default);
+            for package_name in approved.iter() {
+                manifest.trust_dependency(package_name);
+            }
+            manifest.write()?;
+
+            Output::message(format!(
+                "Added {} to trustedDependencies in package.json",
+                approved.join(", ")
+            ));
+        }
+
+        if !skipped.is_empty() {
+            outcome.warn(InstallWarning::new(
+                WarningCode::ScriptsNotRun,
+                None,
+                format!(
+                    "{} package(s) have install scripts, which were not run as they are not in trustedDependencies: {}",
+                    skipped.len(),
+                    skipped.join(", ")
+                ),
+            ));
+        }
+
+        Ok(run)
+    }
+
+    /// Lists an installed package in package.json, which is created if the project does not have one.
+    /// With `-w` it is listed in the package.json of each selected workspace instead, it is still installed at the root.
+    /// A range that was given is saved as it is, otherwise the installed version is saved as a caret range,
+    /// or as it is with `--save-exact`. An exact version that was given is saved as a caret range too, the same as npm.
+    /// Returns the range it was saved with.
+    fn save_to_manifest(
+        &self,
+        lockfile: &Lockfile,
+        requested: &RequestedPackage,
+        package_name: &String,
+        source: &Source,
+        selected: Option<&[Workspace]>,
+    ) -> Result<String, CommandError> {
+        // A package linked with link: is not locked to a version, so it is only looked up when the version is saved
+        let spec = source.spec(package_name);
+        let locked_version = || {
+            lockfile
+                .locked_version(&spec)
+                .expect("Installed package should be locked")
+        };
+
+        let save_exact = self.save_exact || Config::get()?.save_exact;
+        let range = match &requested.raw_version {
+            Some(raw_version) if requested.source.is_some() => raw_version.to_string(),
+            _ if save_exact => locked_version(),
+            Some(raw_version) if Version::from_str(raw_version).is_err() => raw_version.to_string(),
+            _ => format!("^{}", locked_version()),
+        };
+
+        let Some(selected) = selected else {
+            let mut manifest = Manifest::read()?.unwrap_or_default();
+            manifest.set_dependency(&self.dependency_kind, package_name, range.to_string());
+            manifest.write()?;
+
+            if !self.quiet_success {
+                Output::message(format!(
+                    "Saved '{package_name}' to {}",
+                    self.dependency_kind.field()
+                ));
+            }
+
+            return Ok(range);
+        };
+
+        for workspace in selected {
+            let path = workspace.path.join(PACKAGE_JSON_PATH);
+            let mut manifest = Manifest::read_from(&path)?.unwrap_or_default();
+            manifest.set_dependency(&self.dependency_kind, package_name, range.to_string());
+            manifest.write_to(&path)?;
+
+            if !self.quiet_success {
+                Output::message(format!(
+                    "Saved '{package_name}' to {} of '{}'",
+                    self.dependency_kind.field(),
+                    workspace.name
+                ));
+            }
+        }
+
+        Ok(range)
+    }
+
+    /// Installs every requested package along with its dependencies and locks them, as a whole. Each is resolved
+    /// first, at the same time, and those that are not cached are then downloaded together, so one that fails leaves
+    /// nothing installed unless `--keep-going` is set. Returns the packages that failed to install and the requested
+    /// packages they were for, which are only ever not empty with `--keep-going`.
+    async fn install_packages(
+        &self,
+        client: reqwest::Client,
+        integrity_allowlist: Option<Arc<IntegrityAllowlist>>,
+        lockfile: &mut Lockfile,
+        packages: &[PackageSpec],
+        optional_packages: &HashSet<String>,
+        outcome: &mut InstallOutcome,
+    ) -> Result<(Vec<String>, HashSet<String>), CommandError> {
+        let mut failed = Vec::new();
+        let mut unsaved = HashSet::new();
+
+        let resolutions = self
+            .resolve_packages(client.clone(), Arc::new(lockfile.clone()), packages)
+            .await;
+
+        let mut resolved = Vec::new(); // Linked and cached packages, along with the spec each is locked under
+        let mut downloads = Vec::new(); // The same for packages to download, with the package@version each resolved to
+        let mut package_infos = Vec::new();
+        let mut optional = HashSet::new();
+        for ((package_name, source), resolution) in packages.iter().zip(resolutions) {
+            let spec = source.spec(package_name);
+            match resolution {
+                Ok(Resolution::Download(package_info, warning)) => {
+                    if let Some(warning) = warning {
+                        outcome.warn(warning);
+                    }
+                    if optional_packages.contains(package_name) {
+                        optional.insert(package_info.stringified.to_string());
+                    }
+
+                    downloads.push((package_name, spec, package_info.stringified.to_string()));
+                    package_infos.push(*package_info);
+                }
+                Ok(resolution) => resolved.push((package_name, spec, resolution)),
+                Err(err) if optional_packages.contains(package_name) => {
+                    outcome.warn(InstallWarning::new(
+                        WarningCode::OptionalSkipped,
+                        None,
+                        format!("skipped optional dependency '{package_name}' as it failed to install ({err})"),
+                    ));
+                }
+                Err(err) if self.keep_going => {
+                    error!("{err}");
+                    outcome.errors.push(err.to_string());
+                    failed.push(package_name.to_string());
+                    unsaved.insert(package_name.to_string());
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        let CacheOutcome {
+            downloaded,
+            cached,
+            failed: package_failed,
+            failed_roots,
+            skipped_optional,
+            warnings,
+            mut errors,
+        } = match package_infos.is_empty() {
+            true => CacheOutcome::default(),
+            false => {
+                self.cache_packages(
+                    client.clone(),
+                    integrity_allowlist,
+                    lockfile,
+                    package_infos,
+                    &optional,
+                )
+                .await?
+            }
+        };
+
+        for warning in warnings {
+            outcome.warn(warning);
+        }
+        for (package, reason) in skipped_optional.iter() {
+            outcome.warn(InstallWarning::new(
+                WarningCode::OptionalSkipped,
+                Some(package),
+                format!(
+                    "skipped optional dependency '{package}' as it failed to install ({reason})"
+                ),
+            ));
+        }
+
+        outcome
+            .errors
+            .extend(errors.iter().map(|err| err.to_string()));
+
+        // Nothing has been linked yet, and no lockfile was written in the cache unless --keep-going is set
+        if !errors.is_empty() && (!self.keep_going || package_failed.is_empty()) {
+            return Err(errors.remove(0));
+        }
+
+        for err in errors.iter() {
+            error!("{err}");
+        }
+
+        for (package_name, spec, resolution) in resolved {
+            match resolution {
+                Resolution::Link(path) => self.link_local(lockfile, package_name, &path)?,
+                Resolution::Cached(stringified) => {
+                    lockfile.links.remove(package_name);
+                    let linked = self.linker.link(stringified.to_string())?;
+                    outcome.cached.extend(linked.iter().cloned());
+                    self.upgrade_cached_integrity(client.clone(), linked)
+                        .await?;
+
+                    lockfile.record(spec, &stringified)?;
+                }
+                Resolution::Download(..) => {
+                    unreachable!("packages to download are installed above")
+                }
+            }
+        }
+
+        for (package_name, spec, stringified) in downloads {
+            lockfile.links.remove(package_name);
+            if skipped_optional.contains_key(&stringified) {
+                continue;
+            }
+
+            // With --keep-going the lockfiles of everything that succeeded have been written, so those can still be linked
+            if downloaded.contains_key(&stringified) {
+                self.linker.link(stringified.to_string())?;
+            }
+
+            // Those that failed, or depend on a package that did, are neither locked nor saved
+            match failed_roots.contains(&stringified) {
+                true => {
+                    unsaved.insert(package_name.to_string());
+                }
+                false => lockfile.record(spec, &stringified)?,
+            }
+        }
+
+        outcome.downloaded.extend(downloaded);
+        outcome.cached.extend(cached.iter().cloned());
+        failed.extend(package_failed);
+
+        if errors.is_empty() {
+            self.upgrade_cached_integrity(client, cached).await?;
+        }
+
+        Ok((failed, unsaved))
+    }
+
+    /// Resolves every requested package at the same time, each to the version it installs and whether it is cached.
+    async fn resolve_packages(
+        &self,
+        client: reqwest::Client,
+        lockfile: Arc<Lockfile>,
+        packages: &[PackageSpec],
+    ) -> Vec<Result<Resolution, CommandError>> {
+        let handles = packages
+            .iter()
+            .cloned()
+            .map(|(package_name, source)| {
+                let handler = self.clone();
+                let client = client.clone();
+                let lockfile = Arc::clone(&lockfile);
+
+                TaskAllocator::add_task(async move {
+                    handler
+                        .resolve_package(client, &lockfile, &package_name, &source)
+                        .await
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut resolutions = Vec::new();
+        for handle in handles {
+            resolutions.push(handle.await.expect("Failed to join resolve task"));
+        }
+
+        resolutions
+    }
+
+    /// Resolves a requested package to the version it installs. Nothing is downloaded other than what it takes to tell
+    /// the version, such as a package from a tarball URL.
+    async fn resolve_package(
+        &self,
+        client: reqwest::Client,
+        lockfile: &Lockfile,
+        package_name: &String,
+        source: &Source,
+    ) -> Result<Resolution, CommandError> {
+        // In future we could automatically find a version that is valid for both limits to save storage, but that's not neccessary right now
+        if !self.quiet_success {
+            Output::message(format!("Installing '{}'..", package_name));
+        }
+        self.reporter().report(ProgressEvent::ResolutionStarted {
+            package: package_name,
         });
 
-        let dependency_map_mux = Arc::new(Mutex::new(HashMap::new()));
+        if let Source::Link(path) = source {
+            return Ok(Resolution::Link(path.to_path_buf()));
+        }
 
-        let install_context = InstallContext {
-            client,
-            bytes_sender,
-            dependency_map_mux: Arc::clone(&dependency_map_mux),
+        let semantic_version = source.semantic_version();
+        let requested_version = match source.is_registry() {
+            true => {
+                Installer::full_version(
+                    client.clone(),
+                    package_name,
+                    semantic_version,
+                    self.strategy,
+                    self.network_mode,
+                )
+                .await?
+            }
+            false => None,
+        };
+
+        // A spec that has been installed before resolves to the same version it did then, unless the lowest is wanted
+        let spec = source.spec(package_name);
+        let full_version = match source {
+            // Packed again, as the directory may have changed since it was locked
+            Source::File(_) => Some(
+                source
+                    .version_data(client.clone(), package_name, None, self.network_mode)
+                    .await?
+                    .version,
+            ),
+            _ if self.strategy == Strategy::Lowest => requested_version.to_owned(),
+            _ => lockfile
+                .locked_version(&spec)
+                .or(requested_version.to_owned()),
+        };
+        let full_version = full_version.as_ref();
+
+        // A package from anywhere but the registry is only known to be cached once it is locked
+        let can_use_cache = source.is_registry() || full_version.is_some();
+        let (is_cached, cached_version) = match can_use_cache && self.strategy == Strategy::Highest
+        {
+            true => Cache::exists(package_name, full_version, semantic_version).await?,
+            false => (false, None),
+        };
+
+        if is_cached {
+            let version = cached_version.expect("Could not resolve version of cached package");
+            let stringified = Versions::stringify(package_name, &version);
+            debug!("'{package_name}' resolved to {stringified}, which is cached");
+
+            return Ok(Resolution::Cached(stringified));
+        }
+
+        let locked_version_data = full_version
+            .filter(|_| !self.ignore_lockfile_urls)
+            .and_then(|version| lockfile.version_data(package_name, version));
+
+        let version_data = match locked_version_data {
+            Some(version_data) => version_data,
+            None => {
+                source
+                    .version_data(
+                        client.clone(),
+                        package_name,
+                        full_version,
+                        self.network_mode,
+                    )
+                    .await?
+            }
         };
 
         let stringified = Versions::stringify(&version_data.name, &version_data.version);
+        debug!("'{package_name}' resolved to {stringified}");
+
+        if !self.platform.supports(&version_data) {
+            return Err(CommandError::UnsupportedPlatform(
+                stringified,
+                self.platform.to_string(),
+            ));
+        }
+
+        let engine_strict = self.engine_strict.unwrap_or(Config::get()?.engine_strict);
+        let warning = Installer::check_engines(&stringified, &version_data, engine_strict)?;
 
         let package_info = PackageInfo {
             version_data,
-            is_latest: Versions::is_latest(full_version),
-            stringified: stringified.to_string(),
+            is_latest: Versions::is_latest(requested_version.as_ref()),
+            stringified,
+        };
+
+        Ok(Resolution::Download(Box::new(package_info), warning))
+    }
+
+    /// Downloads the packages along with everything they depend on into the cache, see Installer::cache_packages().
+    async fn cache_packages(
+        &self,
+        client: reqwest::Client,
+        integrity_allowlist: Option<Arc<IntegrityAllowlist>>,
+        lockfile: &Lockfile,
+        package_infos: Vec<PackageInfo>,
+        optional: &HashSet<String>,
+    ) -> Result<CacheOutcome, CommandError> {
+        let engine_strict = self.engine_strict.unwrap_or(Config::get()?.engine_strict);
+        let options = CacheOptions {
+            integrity_allowlist,
+            resumable: true,
+            keep_going: self.keep_going,
+            lockfile: match (self.strategy, self.ignore_lockfile_urls) {
+                (Strategy::Highest, false) => Some(Arc::new(lockfile.clone())),
+                (Strategy::Highest, true) => Some(Arc::new(lockfile.without_urls())),
+                (Strategy::Lowest, _) => None,
+            },
+            concurrency: self.concurrency.unwrap_or(DEFAULT_CONCURRENCY),
+            network_mode: self.network_mode,
+            legacy_peer_deps: self.legacy_peer_deps,
+            platform: self.platform.clone(),
+            engine_strict,
+            strict_signatures: self.strict_signatures,
+            reporter: self.reporter(),
+            strategy: self.strategy,
+        };
+
+        let outcome = Installer::cache_packages(client, package_infos, optional, options).await;
+        self.reporter.clear();
+        Ok(outcome)
+    }
+
+    /// Installs the version each override in package.json forces, see Manifest::overrides(), and points every locked
+    /// dependency on the package at it. Overrides are locked apart from the packages requested, by how they are keyed
+    /// in package.json, and the version locked for one is kept for as long as it is in the range of the override.
+    async fn install_overrides(
+        &self,
+        client: reqwest::Client,
+        integrity_allowlist: Option<Arc<IntegrityAllowlist>>,
+        lockfile: &mut Lockfile,
+        outcome: &mut InstallOutcome,
+    ) -> Result<(), CommandError> {
+        let overrides = Manifest::read()?
+            .map(|manifest| manifest.overrides())
+            .unwrap_or_default();
+        if overrides.is_empty() && lockfile.overrides.is_empty() {
+            return Ok(());
+        }
+
+        // The isolated layout links the dependencies each package was cached with
+        if !overrides.is_empty() && self.linker == Linker::Isolated {
+            outcome.warn(InstallWarning::new(
+                WarningCode::OverridesIgnored,
+                None,
+                String::from("overrides are only applied with the hoisted linker"),
+            ));
+        }
+
+        let mut locked = lockfile.clone();
+        let mut resolved = BTreeMap::new();
+        for (key, range) in overrides {
+            let (_, overridden) = Manifest::split_override(&key);
+            let (package_name, _) = Versions::parse_raw_package_details(overridden.to_string());
+            let source =
+                Installer::parse_source(client.clone(), &package_name, &range, self.network_mode)
+                    .await?;
+            let spec = source.spec(&package_name);
+
+            locked.requested.clear();
+            if let Some(package) = lockfile.overrides.get(&key) {
+                let (_, version) = Versions::parse_raw_package_details(package.to_string());
+                let in_range = source.semantic_version().is_none_or(|semantic_version| {
+                    Version::from_str(&version)
+                        .is_ok_and(|version| semantic_version.matches(&version))
+                });
+
+                if in_range {
+                    locked
+                        .requested
+                        .insert(spec.to_string(), package.to_string());
+                }
+            }
+
+            let (failed, _) = self
+                .install_packages(
+                    client.clone(),
+                    integrity_allowlist.clone(),
+                    &mut locked,
+                    &[(package_name, source)],
+                    &HashSet::new(),
+                    outcome,
+                )
+                .await?;
+            if !failed.is_empty() {
+                return Err(CommandError::PartialInstall(failed));
+            }
+
+            if let Some(package) = locked.requested.get(&spec) {
+                resolved.insert(key, package.to_string());
+            }
+        }
+
+        lockfile.packages = locked.packages;
+        lockfile.overrides = resolved;
+        lockfile.apply_overrides();
+        Ok(())
+    }
+
+    /// Links a directory depended on with `link:` at the top of node_modules as it is, the same way as a workspace, so
+    /// changes to it are seen straight away. It installs its own dependencies, and it is locked by its path.
+    fn link_local(
+        &self,
+        lockfile: &mut Lockfile,
+        package_name: &String,
+        path: &path::Path,
+    ) -> Result<(), CommandError> {
+        let source = Source::Link(path.to_path_buf());
+        if Manifest::read_from(&path.join(PACKAGE_JSON_PATH))?.is_none() {
+            return Err(CommandError::InvalidPackageSource(
+                source.to_string(),
+                String::from("it has no package.json"),
+            ));
+        }
+
+        Workspaces::link_directory(package_name, path)?;
+        debug!("'{package_name}' linked to {}", path.display());
+
+        lockfile
+            .links
+            .insert(package_name.to_string(), path.display().to_string());
+        Ok(())
+    }
+}
+
+impl InstallHandler {
+    /// What package.json and the package.json of each workspace list, compared between reads by --watch.
+    fn watched_dependencies(&self) -> Result<WatchedDependencies, CommandError> {
+        let workspaces = Workspaces::discover()?;
+        let selected = self.selected_workspaces()?;
+        let dependencies = self.manifest_dependencies(&workspaces, selected.as_deref())?;
+
+        Ok((
+            workspaces
+                .into_iter()
+                .map(|workspace| workspace.name)
+                .collect(),
+            dependencies.into_iter().collect(),
+        ))
+    }
+
+    /// A copy of the handler that installs once, with a reporter of its own so that each install sums up only what it
+    /// did. Packages installed for a change in package.json are already saved to it.
+    fn once(&self, requested: Vec<RequestedPackage>, no_save: bool) -> Self {
+        Self {
+            requested,
+            save: match no_save {
+                true => Some(false),
+                false => self.save,
+            },
+            watch: false,
+            reporter: self.terminal_reporter(),
+            ..self.clone()
+        }
+    }
+
+    /// Installs the dependencies whose range changed or that were added, then prunes those that were removed or whose
+    /// range changed. Everything is installed the first time, and whenever the workspaces change, as they are linked
+    /// by a full install.
+    async fn sync(
+        &self,
+        previous: Option<&WatchedDependencies>,
+        current: &WatchedDependencies,
+    ) -> Result<(), CommandError> {
+        let Some((_, previous)) = previous.filter(|(workspaces, _)| workspaces == &current.0)
+        else {
+            return self.once(Vec::new(), false).execute().await;
         };
 
-        Installer::install_package(
-            install_context,
-            package_info,
-            Arc::new(Mutex::new(Vec::new())),
-        )?;
+        let changed = current
+            .1
+            .iter()
+            .filter(|(package_name, range)| previous.get(*package_name) != Some(range))
+            .collect::<Vec<_>>();
+        let removed = previous
+            .keys()
+            .filter(|package_name| !current.1.contains_key(*package_name))
+            .collect::<Vec<_>>();
+
+        Output::message("package.json changed:");
+        for (package_name, range) in changed.iter() {
+            Output::message(format!("  + {package_name}@{range}"));
+        }
+        for package_name in removed.iter() {
+            Output::message(format!("  - {package_name}"));
+        }
+
+        if !changed.is_empty() {
+            let requested = changed
+                .iter()
+                .map(|(package_name, range)| {
+                    RequestedPackage::parse(format!("{package_name}@{range}"))
+                        .map_err(CommandError::InvalidPackageSpec)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            self.once(requested, true).execute().await?;
+        }
+
+        // A changed range leaves the version it used to lock behind
+        if !removed.is_empty()
+            || changed
+                .iter()
+                .any(|(package_name, _)| previous.contains_key(*package_name))
+        {
+            PruneHandler::new(self.production, self.dry_run)
+                .execute()
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Keeps node_modules in step with package.json until click is stopped. A failed install is reported and the next
+    /// change is waited for, as is a package.json that does not parse, such as one that is only half saved.
+    async fn watch(&self) -> Result<(), CommandError> {
+        if self.global {
+            Installer::use_global_prefix()?;
+        }
+
+        let mut watched: Option<WatchedDependencies> = None;
+        let mut requested = self.requested.clone();
+
+        loop {
+            match self.watched_dependencies() {
+                Ok(current) if watched.as_ref() != Some(&current) => {
+                    let result = match requested.is_empty() {
+                        true => self.sync(watched.as_ref(), &current).await,
+                        // Packages given on the command line are installed and saved first
+                        false => {
+                            self.once(std::mem::take(&mut requested), false)
+                                .execute()
+                                .await
+                        }
+                    };
+                    if let Err(err) = result {
+                        error!("{err}");
+                    }
+
+                    // Installing can save to package.json, which is not a change to install again
+                    watched = self.watched_dependencies().ok().or(Some(current));
+                    Output::message("Watching package.json for changes, press Ctrl+C to stop");
+                }
+                Ok(_) => {}
+                Err(err) => debug!("package.json could not be read ({err})"),
+            }
+
+            tokio::time::sleep(WATCH_INTERVAL).await;
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler for InstallHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        let mut package_details = Vec::new();
+
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--quiet-success") => self.quiet_success = true,
+                Arg::Flag("--reporter") => {
+                    let reporter = args.value("--reporter")?;
+                    self.subscriber = match reporter.as_str() {
+                        "terminal" => None,
+                        "json" => Some(Arc::new(JsonReporter)),
+                        _ => {
+                            return Err(ParseError::InvalidArgument(
+                                String::from("--reporter"),
+                                reporter,
+                            ))
+                        }
+                    };
+                }
+                Arg::Flag("--save-dev") => self.dependency_kind = DependencyKind::Development,
+                Arg::Flag("--save-optional") => self.dependency_kind = DependencyKind::Optional,
+                Arg::Flag("--save-to") => self.dependency_kind = Self::group("--save-to", args)?,
+                Arg::Flag("--production") => self.production = true,
+                Arg::Flag("--include") => {
+                    let group = Self::group("--include", args)?;
+                    if !self.include.contains(&group) {
+                        self.include.push(group);
+                    }
+                }
+                Arg::Flag("--upgrade-integrity") => self.upgrade_integrity = true,
+                Arg::Flag("--keep-going") => self.keep_going = true,
+                Arg::Flag("--frozen-lockfile") => self.frozen = true,
+                Arg::Flag("--regenerate-lockfile") => self.regenerate_lockfile = true,
+                Arg::Flag("--ignore-scripts") => self.ignore_scripts = true,
+                Arg::Flag("--no-bin-links") => self.no_bin_links = true,
+                Arg::Flag("--ignore-lockfile-urls") => self.ignore_lockfile_urls = true,
+                Arg::Flag("--trusted-scripts") => self.trusted_scripts = true,
+                Arg::Flag("--global") => self.global = true,
+                Arg::Flag("--prefer-offline") => self.network_mode = NetworkMode::PreferOffline,
+                Arg::Flag("--offline") => self.network_mode = NetworkMode::Offline,
+                Arg::Flag("--legacy-peer-deps") => self.legacy_peer_deps = true,
+                Arg::Flag("--prefer-lowest") => self.strategy = Strategy::Lowest,
+                Arg::Flag("--engine-strict") => self.engine_strict = Some(true),
+                Arg::Flag("--no-engine-strict") => self.engine_strict = Some(false),
+                Arg::Flag("--strict-signatures") => self.strict_signatures = true,
+                Arg::Flag("--save-exact") => self.save_exact = true,
+                Arg::Flag("--save") => self.save = Some(true),
+                Arg::Flag("--no-save") => self.save = Some(false),
+                Arg::Flag("--dry-run") => self.dry_run = true,
+                Arg::Flag("--confirm") => self.confirm = true,
+                Arg::Flag("--watch") => self.watch = true,
+                Arg::Flag("--workspace") => self.workspaces.push(args.value("--workspace")?),
+                Arg::Flag("--report") => self.report_path = Some(args.value("--report")?),
+                Arg::Flag("--concurrency") => {
+                    let concurrency = args.value("--concurrency")?;
+
+                    self.concurrency = match concurrency.parse::<usize>() {
+                        Ok(concurrency) if concurrency > 0 => Some(concurrency),
+                        _ => {
+                            return Err(ParseError::InvalidArgument(
+                                String::from("--concurrency"),
+                                concurrency,
+                            ))
+                        }
+                    };
+                }
+                Arg::Flag("--force-platform") => {
+                    let platform = args.value("--force-platform")?;
 
-        // Blocks the main thread however it's not going to have a huge performance impact on tokio
-        TaskAllocator::block_until_done();
+                    self.platform = Platform::parse(&platform).ok_or(
+                        ParseError::InvalidArgument(String::from("--force-platform"), platform),
+                    )?;
+                }
+                Arg::Flag("--integrity-allowlist") => {
+                    self.integrity_allowlist_path = Some(args.value("--integrity-allowlist")?)
+                }
+                Arg::Flag("--target-platform") => {
+                    let platform = args.value("--target-platform")?;
+
+                    self.target_platform = Some(ShimPlatform::parse(&platform).ok_or(
+                        ParseError::InvalidArgument(String::from("--target-platform"), platform),
+                    )?);
+                }
+                Arg::Flag("--production-only-lockfile") => self.production_only_lockfile = true,
+                Arg::Flag("--lockfile-out") => {
+                    self.lockfile_out = Some(args.value("--lockfile-out")?)
+                }
+                Arg::Flag("--lockfile-format") => {
+                    let format = args.value("--lockfile-format")?;
+
+                    self.lockfile_format = Some(LockfileFormat::parse(&format).ok_or(
+                        ParseError::InvalidArgument(String::from("--lockfile-format"), format),
+                    )?);
+                }
+                Arg::Flag("--linker") => {
+                    self.linker = match args.value("--linker")?.as_str() {
+                        "hoisted" => Linker::Hoisted,
+                        "isolated" => Linker::Isolated,
+                        linker => {
+                            return Err(ParseError::InvalidArgument(
+                                String::from("--linker"),
+                                linker.to_string(),
+                            ))
+                        }
+                    };
+                }
+                Arg::Positional(arg) => package_details.push(arg),
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        self.reporter = self.terminal_reporter();
+
+        // Paths are given relative to where click was run, which global installs move away from
+        if self.global {
+            let paths = [&mut self.report_path, &mut self.integrity_allowlist_path];
+            for path in paths.into_iter().flatten() {
+                if let Ok(absolute) = path::absolute(&path) {
+                    *path = absolute.to_string_lossy().to_string();
+                }
+            }
+        }
 
-        Self::write_lockfiles(dependency_map_mux)?;
-        Cache::load_cached_version(stringified);
+        // Without a package everything in package.json is installed
+        for package_details in package_details {
+            self.requested
+                .push(RequestedPackage::parse(package_details)?);
+        }
 
         Ok(())
     }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        if self.watch {
+            return self.watch().await;
+        }
+
+        let started = Instant::now();
+        let mut outcome = InstallOutcome::default();
+
+        if self.global {
+            Installer::use_global_prefix()?;
+        }
+
+        // Loaded after changing to the global directory, so that a project .npmrc does not apply to global installs
+        Config::load()?;
+
+        // A dry run does not change anything, and a nested install is rolled back by the command it is part of
+        let transaction = match self.dry_run || self.nested {
+            true => None,
+            false => Some(Transaction::begin()?),
+        };
+
+        let result = self.install(&mut outcome).await;
+
+        // With --keep-going the packages that did install are kept, anything else that failed is undone
+        if let (Err(err), Some(transaction)) = (&result, transaction) {
+            if !matches!(err, CommandError::PartialInstall(_)) {
+                Self::rollback(transaction);
+            }
+        }
+
+        if result.is_ok() && !self.dry_run {
+            self.finish(&outcome, started.elapsed());
+        }
+
+        if let (true, Some(bin_directory)) = (self.global, Installer::bin_directory_not_on_path()) {
+            Output::message(format!(
+                "Executables of global packages are linked into '{}', add it to PATH to run them",
+                bin_directory.display()
+            ));
+        }
+
+        let install_scripts = match self.ignore_scripts {
+            true => Self::packages_with_install_scripts(&outcome),
+            false => Vec::new(),
+        };
+        if !install_scripts.is_empty() {
+            outcome.warn(InstallWarning::new(
+                WarningCode::ScriptsNotRun,
+                None,
+                format!(
+                    "{} package(s) have install scripts, which were not run as --ignore-scripts was given: {}",
+                    install_scripts.len(),
+                    install_scripts.join(", ")
+                ),
+            ));
+        }
+
+        // Listed together at the end, so they are not lost among the progress of the install
+        let deprecated = Self::deprecated_packages(&outcome);
+        if !deprecated.is_empty() && !Output::is_json() {
+            let listed = deprecated
+                .iter()
+                .map(|(package, message)| format!("\n  {package}: {message}"))
+                .collect::<String>();
+            warn!(
+                "{} deprecated package(s) were installed:{listed}",
+                deprecated.len()
+            );
+        }
+        outcome
+            .warnings
+            .extend(deprecated.into_iter().map(|(package, message)| {
+                InstallWarning::new(
+                    WarningCode::Deprecated,
+                    Some(&package),
+                    format!("'{package}' is deprecated: {message}"),
+                )
+            }));
+
+        let report = Self::report(outcome, &result, started.elapsed());
+        if let Some(report_path) = &self.report_path {
+            Self::write_report(report_path, &report)?;
+        }
+
+        // A dry run prints its plan instead
+        if !self.nested && !self.dry_run {
+            Output::json(&report)?;
+        }
+
+        result
+    }
 }
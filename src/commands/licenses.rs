@@ -0,0 +1,117 @@
+use async_trait::async_trait;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    licenses::{LicensePolicy, Licenses},
+    output::Output,
+    types::{LicenseGroup, LicenseReport, Lockfile},
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "licenses",
+    aliases: &[],
+    arguments: "",
+    description:
+        "List the licenses of the packages in the lockfile, exits with 1 if --allow or --deny rule any of them out",
+    flags: &[
+        Flag::with_value(
+            &["--allow"],
+            "licenses",
+            "Comma separated SPDX identifiers, a package under any other license fails the command",
+        ),
+        Flag::with_value(
+            &["--deny"],
+            "licenses",
+            "Comma separated SPDX identifiers, a package under any of them fails the command",
+        ),
+    ],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct LicensesHandler {
+    policy: LicensePolicy, // The licenses given with --allow and --deny.
+}
+
+impl LicensesHandler {
+    fn print_groups(groups: &[LicenseGroup]) {
+        for group in groups {
+            println!("{} ({})", group.license, group.packages.len());
+            for package in group.packages.iter() {
+                println!("  {package}");
+            }
+            println!();
+        }
+    }
+
+    fn split(list: String) -> Vec<String> {
+        list.split(',')
+            .map(|license| license.trim().to_string())
+            .filter(|license| !license.is_empty())
+            .collect()
+    }
+}
+
+#[async_trait]
+impl CommandHandler for LicensesHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--allow") => self
+                    .policy
+                    .allow
+                    .extend(Self::split(args.value("--allow")?)),
+                Arg::Flag("--deny") => self.policy.deny.extend(Self::split(args.value("--deny")?)),
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        let lockfile = Lockfile::read()?;
+        let licenses = Licenses::collect(&lockfile)?;
+
+        let (disallowed, licenses): (Vec<_>, Vec<_>) = match self.policy.is_empty() {
+            true => (Vec::new(), licenses),
+            false => licenses
+                .into_iter()
+                .partition(|group| !self.policy.permits(&group.license)),
+        };
+        let report = LicenseReport {
+            licenses,
+            disallowed,
+        };
+        Output::json(&report)?;
+
+        if !Output::is_json() {
+            Self::print_groups(&report.licenses);
+            if !report.disallowed.is_empty() {
+                println!("Not allowed:");
+                Self::print_groups(&report.disallowed);
+            }
+        }
+        Output::message(format!(
+            "Found {} license(s) in {} package(s)",
+            report.licenses.len() + report.disallowed.len(),
+            lockfile.packages.len()
+        ));
+
+        if report.disallowed.is_empty() {
+            return Ok(());
+        }
+
+        let count = report
+            .disallowed
+            .iter()
+            .map(|group| group.packages.len())
+            .sum();
+        Err(CommandError::DisallowedLicenses(count))
+    }
+}
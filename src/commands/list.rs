@@ -0,0 +1,187 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use async_trait::async_trait;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    installer::Installer,
+    output::Output,
+    tree::DependencyTree,
+    types::InstalledPackage,
+    workspaces::Workspaces,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "list",
+    aliases: &["ls", "tree"],
+    arguments: "",
+    description: "Print the tree of installed packages",
+    flags: &[
+        Flag::with_value(
+            &["--depth"],
+            "n",
+            "Only list dependencies this many levels below the top",
+        ),
+        Flag::new(&["-g", "--global"], "List the global packages"),
+        Flag::new(
+            &["--group-by-registry"],
+            "List every installed package under the registry it was resolved from",
+        ),
+        Flag::with_value(
+            &["-w", "--workspace", "--filter"],
+            "workspace",
+            "List the dependencies of the workspaces this selects rather than the root",
+        ),
+    ],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct ListHandler {
+    depth: Option<usize>, // Only list dependencies this many levels below the top level, every level if None.
+    global: bool,         // List the global packages rather than those of the working directory.
+    workspaces: Vec<String>, // List the dependencies of the workspaces these select, set with -w and --filter.
+    group_by_registry: bool, // List the packages under the registry each was resolved from rather than as a tree.
+}
+
+// Packages that are linked, or were cached before the registry was recorded
+const UNKNOWN_REGISTRY: &str = "(unknown)";
+
+impl ListHandler {
+    fn label(package: &InstalledPackage) -> String {
+        let label = match &package.version {
+            Some(version) => format!("{}@{}", package.name, version),
+            None => format!("{} (missing)", package.name),
+        };
+
+        match package.circular {
+            true => format!("{label} (circular)"),
+            false => label,
+        }
+    }
+
+    /// Every installed package below the trees formatted as package@version, under the registry it was resolved from.
+    /// Missing packages are left out, and a package depended on more than once is listed once.
+    fn group_by_registry(trees: &[InstalledPackage]) -> BTreeMap<String, BTreeSet<String>> {
+        fn add(package: &InstalledPackage, groups: &mut BTreeMap<String, BTreeSet<String>>) {
+            for dependency in package.dependencies.iter() {
+                if let Some(version) = &dependency.version {
+                    let registry = dependency.registry.as_deref().unwrap_or(UNKNOWN_REGISTRY);
+                    groups
+                        .entry(registry.to_string())
+                        .or_default()
+                        .insert(format!("{}@{}", dependency.name, version));
+                }
+
+                add(dependency, groups);
+            }
+        }
+
+        let mut groups = BTreeMap::new();
+        trees.iter().for_each(|tree| add(tree, &mut groups));
+        groups
+    }
+
+    fn output_groups(trees: &[InstalledPackage]) -> Result<(), CommandError> {
+        let groups = Self::group_by_registry(trees);
+        if Output::is_json() {
+            return Output::json(&groups);
+        }
+
+        for (registry, packages) in groups {
+            println!("{registry}");
+            packages.iter().for_each(|package| println!("  {package}"));
+        }
+
+        Ok(())
+    }
+
+    fn print_tree(tree: &InstalledPackage) {
+        // The project itself does not need a version, so it is never missing
+        match &tree.version {
+            Some(version) => println!("{}@{}", tree.name, version),
+            None => println!("{}", tree.name),
+        }
+        Self::print_dependencies(tree, "");
+    }
+
+    /// `prefix` is drawn in front of every line of this level, continuing the branches of the levels above it.
+    fn print_dependencies(package: &InstalledPackage, prefix: &str) {
+        let count = package.dependencies.len();
+        for (index, dependency) in package.dependencies.iter().enumerate() {
+            let is_last = index + 1 == count;
+            let (branch, continuation) = match is_last {
+                true => ("└── ", "    "),
+                false => ("├── ", "│   "),
+            };
+
+            println!("{prefix}{branch}{}", Self::label(dependency));
+            Self::print_dependencies(dependency, &format!("{prefix}{continuation}"));
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler for ListHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--global") => self.global = true,
+                Arg::Flag("--group-by-registry") => self.group_by_registry = true,
+                Arg::Flag("--workspace") => self.workspaces.push(args.value("--workspace")?),
+                Arg::Flag("--depth") => {
+                    let depth = args.value("--depth")?;
+
+                    self.depth = Some(depth.parse::<usize>().map_err(|_| {
+                        ParseError::InvalidArgument(String::from("--depth"), depth)
+                    })?);
+                }
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        if self.global {
+            Installer::use_global_prefix()?;
+        }
+
+        if !self.workspaces.is_empty() {
+            let trees = Workspaces::filter(Workspaces::discover()?, &self.workspaces)?
+                .iter()
+                .map(|workspace| DependencyTree::read_workspace(workspace, self.depth))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if self.group_by_registry {
+                return Self::output_groups(&trees);
+            }
+
+            if Output::is_json() {
+                return Output::json(&trees);
+            }
+
+            trees.iter().for_each(Self::print_tree);
+            return Ok(());
+        }
+
+        let tree = DependencyTree::read(self.depth)?;
+
+        if self.group_by_registry {
+            return Self::output_groups(&[tree]);
+        }
+
+        if Output::is_json() {
+            return Output::json(&tree);
+        }
+
+        Self::print_tree(&tree);
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+use async_trait::async_trait;
+
+use crate::{
+    config::Config,
+    errors::{CommandError, ParseError},
+    http::{HTTPRequest, LoginOutcome},
+    output::Output,
+    prompt::Prompt,
+    types::LoginResult,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "login",
+    aliases: &["adduser"],
+    arguments: "",
+    description: "Log in to the registry and save the token to the user .npmrc",
+    flags: &[Flag::with_value(
+        &["--auth-type"],
+        "web|legacy",
+        "Log in in the browser, or with a username and password",
+    )],
+    passthrough: false,
+};
+
+/// How `click login` gets a token from the registry.
+#[derive(Clone, Copy, Default, PartialEq)]
+enum AuthType {
+    #[default]
+    Web, // In the browser, falling back to a password if the registry does not support it
+    Legacy, // With a username and password, asking for a one-time password if the account needs one
+}
+
+#[derive(Default)]
+pub struct LoginHandler {
+    auth_type: AuthType, // Set with --auth-type.
+}
+
+impl LoginHandler {
+    /// Returns None if the registry does not support logging in in the browser.
+    async fn web_login(
+        client: reqwest::Client,
+        registry: &str,
+    ) -> Result<Option<String>, CommandError> {
+        let Some(web_login) = HTTPRequest::web_login(client.clone(), registry).await? else {
+            return Ok(None);
+        };
+
+        Output::message(format!(
+            "Open {} in a browser to log in, waiting until it is done..",
+            web_login.login_url
+        ));
+
+        HTTPRequest::finish_web_login(client, &web_login.done_url)
+            .await
+            .map(Some)
+    }
+
+    async fn password_login(
+        client: reqwest::Client,
+        registry: &str,
+    ) -> Result<String, CommandError> {
+        let username = Prompt::ask("Username", "")?;
+        let password = Prompt::ask_hidden("Password")?;
+        if username.is_empty() || password.is_empty() {
+            return Err(CommandError::LoginFailed(String::from(
+                "a username and password are needed",
+            )));
+        }
+
+        let mut otp = None;
+        loop {
+            let outcome = HTTPRequest::password_login(
+                client.clone(),
+                registry,
+                &username,
+                &password,
+                otp.as_deref(),
+            )
+            .await?;
+
+            match outcome {
+                LoginOutcome::LoggedIn(token) => return Ok(token),
+                LoginOutcome::OtpRequired if otp.is_some() => return Err(CommandError::InvalidOtp),
+                LoginOutcome::OtpRequired => {
+                    let answer = Prompt::ask(
+                        "This account uses two-factor authentication, enter a one-time password",
+                        "",
+                    )?;
+                    if answer.is_empty() {
+                        return Err(CommandError::OtpRequired);
+                    }
+                    otp = Some(answer);
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler for LoginHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--auth-type") => {
+                    self.auth_type = match args.value("--auth-type")?.as_str() {
+                        "web" => AuthType::Web,
+                        "legacy" => AuthType::Legacy,
+                        auth_type => {
+                            return Err(ParseError::InvalidArgument(
+                                String::from("--auth-type"),
+                                auth_type.to_string(),
+                            ))
+                        }
+                    }
+                }
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The token is saved under the registry it is for, so logging in to another registry keeps the token of this one.
+    async fn execute(&self) -> Result<(), CommandError> {
+        let registry = Config::load()?.registry.to_string();
+        let client = Config::client()?;
+
+        let web_token = match self.auth_type {
+            AuthType::Web => Self::web_login(client.clone(), &registry).await?,
+            AuthType::Legacy => None,
+        };
+        let token = match web_token {
+            Some(token) => token,
+            None => Self::password_login(client, &registry).await?,
+        };
+
+        let (npmrc_path, _) = Config::set_user_key(&Config::token_key(&registry), Some(&token))?;
+        Output::message(format!("Logged in to '{registry}'"));
+
+        Output::json(&LoginResult {
+            registry,
+            npmrc: npmrc_path.display().to_string(),
+        })
+    }
+}
@@ -0,0 +1,66 @@
+use async_trait::async_trait;
+use log::warn;
+
+use crate::{
+    config::Config,
+    errors::{CommandError, ParseError},
+    http::HTTPRequest,
+    output::Output,
+    types::LoginResult,
+};
+
+use super::{
+    arguments::{Arguments, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "logout",
+    aliases: &[],
+    arguments: "",
+    description: "Revoke the token of the registry and remove it from the user .npmrc",
+    flags: &[],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct LogoutHandler;
+
+#[async_trait]
+impl CommandHandler for LogoutHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        match args.next()? {
+            Some(arg) => Err(arg.unexpected()),
+            None => Ok(()),
+        }
+    }
+
+    /// The token is removed from the user .npmrc even if the registry fails to revoke it, as it is no longer wanted.
+    async fn execute(&self) -> Result<(), CommandError> {
+        let config = Config::load()?;
+        let registry = config.registry.to_string();
+
+        let token = config
+            .authorization(&format!("{registry}/"))
+            .and_then(|authorization| authorization.strip_prefix("Bearer "))
+            .ok_or_else(|| CommandError::NotLoggedIn(registry.to_string()))?;
+
+        if let Err(err) = HTTPRequest::revoke_token(Config::client()?, &registry, token).await {
+            warn!("{err}");
+        }
+
+        let (npmrc_path, found) = Config::set_user_key(&Config::token_key(&registry), None)?;
+        match found {
+            true => Output::message(format!("Logged out of '{registry}'")),
+            false => warn!(
+                "the token of '{registry}' is not in '{}', it has been revoked but has to be removed from where it is configured",
+                npmrc_path.display()
+            ),
+        }
+
+        Output::json(&LoginResult {
+            registry,
+            npmrc: npmrc_path.display().to_string(),
+        })
+    }
+}
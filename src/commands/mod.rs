@@ -1,3 +1,38 @@
+pub mod arguments;
+pub mod audit;
+pub mod bench;
+pub mod cache;
+pub mod ci;
 pub mod command_handler;
+pub mod completions;
+pub mod config;
+pub mod create;
+pub mod dedupe;
+pub mod doctor;
+pub mod du;
+pub mod exec;
+pub mod graph;
+pub mod info;
+pub mod init;
 pub mod install;
-pub mod exec;
\ No newline at end of file
+pub mod licenses;
+pub mod list;
+pub mod login;
+pub mod logout;
+pub mod outdated;
+pub mod pack;
+pub mod patch;
+pub mod patch_commit;
+pub mod prune;
+pub mod publish;
+pub mod rebuild;
+pub mod resolve;
+pub mod run;
+pub mod sbom;
+pub mod search;
+pub mod self_update;
+pub mod uninstall;
+pub mod update;
+pub mod verify;
+pub mod version;
+pub mod why;
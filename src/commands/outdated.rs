@@ -0,0 +1,265 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use async_trait::async_trait;
+use semver::Version;
+
+use crate::{
+    config::Config,
+    errors::{CommandError, ParseError},
+    http::NetworkMode,
+    installer::Installer,
+    manifest::{DependencyKind, Manifest},
+    output::Output,
+    source::Source,
+    types::{Lockfile, OutdatedPackage, OutdatedSummary},
+    util::TaskAllocator,
+    versions::Versions,
+    workspaces::Workspaces,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+    update::UpdateHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "outdated",
+    aliases: &[],
+    arguments: "",
+    description:
+        "List the packages in package.json that have newer versions, exits with 1 if there are any",
+    flags: &[Flag::new(
+        &["--summary-only"],
+        "Only print how many packages are a patch, minor or major version behind",
+    )],
+    passthrough: false,
+};
+
+const ROOT_LABEL: &str = "(root)"; // Shown as the workspace of the root package.json when it has no name
+
+#[derive(Default)]
+pub struct OutdatedHandler {
+    summary_only: bool, // Print the counts of OutdatedSummary rather than every package, set with --summary-only.
+}
+
+impl OutdatedHandler {
+    /// Every dependency of the root package.json and of each workspace, with the workspace it is in.
+    /// Dependencies on a workspace are left out, as the workspace is linked rather than installed.
+    fn dependencies() -> Result<Vec<(String, String, String)>, CommandError> {
+        let manifest = Manifest::read()?.ok_or(CommandError::NoManifest)?;
+        let workspaces = Workspaces::discover()?;
+
+        let root_name = manifest.field("name").unwrap_or(ROOT_LABEL).to_string();
+        let manifests = [(root_name, &manifest)].into_iter().chain(
+            workspaces
+                .iter()
+                .map(|workspace| (workspace.name.to_string(), &workspace.manifest)),
+        );
+
+        let mut dependencies = Vec::new();
+        for (workspace_name, manifest) in manifests {
+            for kind in [
+                DependencyKind::Production,
+                DependencyKind::Development,
+                DependencyKind::Optional,
+            ] {
+                for (package_name, range) in manifest.dependencies(kind.field()) {
+                    // Packages that are not from the registry have no newer versions to compare with
+                    if Source::from_spec(&range).is_some()
+                        || workspaces
+                            .iter()
+                            .any(|workspace| workspace.name == package_name)
+                    {
+                        continue;
+                    }
+
+                    dependencies.push((workspace_name.to_string(), package_name, range));
+                }
+            }
+        }
+
+        Ok(dependencies)
+    }
+
+    /// Looks up the version the range allows and the latest version of a package, the current version is the one the
+    /// lockfile has for the range, or the one in node_modules if the range is not locked. Returns None if the package is
+    /// up to date. The latest compatible version is the newest that `^current` allows, which can be newer than the
+    /// wanted version when the range is narrower than a caret, such as `~1.2.0` or `1.2.0`.
+    pub async fn check(
+        client: reqwest::Client,
+        lockfile: &Lockfile,
+        installed: Option<String>,
+        workspace: String,
+        package_name: String,
+        range: String,
+    ) -> Result<Option<OutdatedPackage>, CommandError> {
+        let semantic_version =
+            Installer::parse_range(client.clone(), &package_name, &range, NetworkMode::Online)
+                .await?;
+        let spec = Lockfile::spec(&package_name, semantic_version.as_ref());
+        let current = lockfile.locked_version(&spec).or(installed);
+
+        let wanted =
+            UpdateHandler::newest_version(client.clone(), &package_name, semantic_version.as_ref())
+                .await?;
+        let compatible =
+            Versions::parse_semantic_version(&format!("^{}", current.as_ref().unwrap_or(&wanted)))
+                .ok();
+        let latest_compatible = match &compatible {
+            Some(compatible) => {
+                UpdateHandler::newest_version(client.clone(), &package_name, Some(compatible))
+                    .await?
+            }
+            None => wanted.to_string(),
+        };
+        let latest = UpdateHandler::newest_version(client, &package_name, None).await?;
+
+        let is_outdated = match &current {
+            Some(current) => {
+                Versions::is_newer(&wanted, current) || Versions::is_newer(&latest, current)
+            }
+            None => true,
+        };
+
+        Ok(is_outdated.then_some(OutdatedPackage {
+            name: package_name,
+            current,
+            wanted,
+            latest_compatible,
+            latest,
+            workspace,
+        }))
+    }
+
+    /// Packages are counted by the first part of the version that differs between the current and latest versions.
+    fn summary(packages: &[OutdatedPackage]) -> OutdatedSummary {
+        let mut summary = OutdatedSummary {
+            outdated: packages.len(),
+            ..Default::default()
+        };
+
+        for package in packages {
+            let versions = package.current.as_deref().and_then(|current| {
+                Some((
+                    Version::from_str(current).ok()?,
+                    Version::from_str(&package.latest).ok()?,
+                ))
+            });
+
+            match versions {
+                None => summary.missing += 1,
+                Some((current, latest)) if current.major != latest.major => summary.major += 1,
+                Some((current, latest)) if current.minor != latest.minor => summary.minor += 1,
+                Some(_) => summary.patch += 1,
+            }
+        }
+
+        summary
+    }
+
+    /// Such as `3 outdated: 1 patch, 0 minor, 2 major`, along with how many are missing if any are.
+    fn print_summary(summary: &OutdatedSummary) {
+        let mut counts = format!(
+            "{} outdated: {} patch, {} minor, {} major",
+            summary.outdated, summary.patch, summary.minor, summary.major
+        );
+        if summary.missing > 0 {
+            counts.push_str(&format!(", {} missing", summary.missing));
+        }
+
+        Output::message(counts);
+    }
+
+    fn print_table(packages: &[OutdatedPackage]) {
+        let rows = packages
+            .iter()
+            .map(|package| {
+                [
+                    package.name.as_str(),
+                    package.current.as_deref().unwrap_or("missing"),
+                    package.wanted.as_str(),
+                    package.latest_compatible.as_str(),
+                    package.latest.as_str(),
+                    package.workspace.as_str(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        Output::table(
+            [
+                "Package",
+                "Current",
+                "Wanted",
+                "Compatible",
+                "Latest",
+                "Workspace",
+            ],
+            &rows,
+        );
+    }
+}
+
+#[async_trait]
+impl CommandHandler for OutdatedHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--summary-only") => self.summary_only = true,
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        Config::load()?;
+
+        let client = Config::client()?;
+        let lockfile = Arc::new(Lockfile::read()?);
+        let installed = Installer::linked_packages()?
+            .into_iter()
+            .map(Versions::parse_raw_package_details)
+            .collect::<HashMap<_, _>>();
+
+        // Every package is looked up at the same time, the registry is the slow part
+        let handles = Self::dependencies()?
+            .into_iter()
+            .map(|(workspace, package_name, range)| {
+                let client = client.clone();
+                let lockfile = lockfile.clone();
+                let installed = installed.get(&package_name).cloned();
+
+                TaskAllocator::add_task(async move {
+                    Self::check(client, &lockfile, installed, workspace, package_name, range).await
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut outdated = Vec::new();
+        for handle in handles {
+            outdated.extend(handle.await.expect("Failed to join outdated task")?);
+        }
+        outdated.sort_by(|a, b| (&a.name, &a.workspace).cmp(&(&b.name, &b.workspace)));
+
+        if self.summary_only {
+            let summary = Self::summary(&outdated);
+            Output::json(&summary)?;
+            Self::print_summary(&summary);
+        } else {
+            Output::json(&outdated)?;
+
+            if outdated.is_empty() {
+                Output::message("Everything is up to date");
+            } else if !Output::is_json() {
+                Self::print_table(&outdated);
+            }
+        }
+
+        match outdated.is_empty() {
+            true => Ok(()),
+            false => Err(CommandError::OutdatedPackages(outdated.len())),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::{fs, path::Path};
+
+use async_trait::async_trait;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    integrity::Integrity,
+    manifest::Manifest,
+    output::Output,
+    pack::Pack,
+    types::PackResult,
+    util::Size,
+    workspaces::Workspaces,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+    run::RunScriptHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "pack",
+    aliases: &[],
+    arguments: "",
+    description: "Create a tarball of the package in this directory, the same as the one publishing it would upload",
+    flags: &[
+        Flag::new(
+            &["--dry-run"],
+            "List what would be packed without writing the tarball",
+        ),
+        Flag::with_value(
+            &["--pack-destination"],
+            "directory",
+            "Write the tarball to this directory rather than this one",
+        ),
+    ],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct PackHandler {
+    dry_run: bool,               // Only list the files, set with --dry-run.
+    destination: Option<String>, // The directory the tarball is written to, the working directory if None.
+}
+
+impl PackHandler {
+    /// Packs the project in the working directory between its prepack and postpack scripts, without writing the tarball.
+    pub fn pack() -> Result<(PackResult, Vec<u8>), CommandError> {
+        // prepack usually builds the files that are packed, so package.json is read again after it
+        let manifest = Manifest::read()?.ok_or(CommandError::NoManifest)?;
+        RunScriptHandler::run_if_present(&manifest, "prepack", None)?;
+        let manifest = Manifest::read()?.ok_or(CommandError::NoManifest)?;
+        let field = |field: &str| {
+            manifest
+                .field(field)
+                .map(str::to_string)
+                .ok_or_else(|| CommandError::MissingManifestField(field.to_string()))
+        };
+        let name = field("name")?;
+        let version = field("version")?;
+
+        let published = Workspaces::published_manifest(&manifest)?
+            .map(|published| published.serialize())
+            .transpose()?
+            .map(|published| published + "\n");
+
+        let root = Path::new(".");
+        let mut files = Pack::files(root, &manifest)?;
+        if let Some(published) = &published {
+            for file in files.iter_mut().filter(|file| file.path == "package.json") {
+                file.size = published.len() as u64;
+            }
+        }
+        let tarball = Pack::tarball(root, &files, published.as_deref())?;
+
+        let result = PackResult {
+            filename: Pack::filename(&name, &version),
+            name,
+            version,
+            size: tarball.len() as u64,
+            unpacked_size: files.iter().map(|file| file.size).sum(),
+            shasum: Integrity::shasum(&tarball),
+            integrity: Integrity::sha512(&tarball),
+            files,
+        };
+
+        RunScriptHandler::run_if_present(&manifest, "postpack", None)?;
+        Ok((result, tarball))
+    }
+
+    /// Lists the files with their sizes followed by the details of the tarball, the same as npm.
+    pub fn print_contents(result: &PackResult) {
+        Output::message(format!("Package: {}@{}", result.name, result.version));
+
+        let sizes = result
+            .files
+            .iter()
+            .map(|file| Size(file.size).to_string())
+            .collect::<Vec<_>>();
+        let width = sizes.iter().map(String::len).max().unwrap_or_default();
+        for (file, size) in result.files.iter().zip(sizes) {
+            Output::message(format!("  {size:>width$}  {}", file.path));
+        }
+
+        Output::message(format!("Filename: {}", result.filename));
+        Output::message(format!("Package size: {}", Size(result.size)));
+        Output::message(format!("Unpacked size: {}", Size(result.unpacked_size)));
+        Output::message(format!("Shasum: {}", result.shasum));
+        Output::message(format!("Integrity: {}", result.integrity));
+        Output::message(format!("Total files: {}", result.files.len()));
+    }
+}
+
+#[async_trait]
+impl CommandHandler for PackHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--dry-run") => self.dry_run = true,
+                Arg::Flag("--pack-destination") => {
+                    self.destination = Some(args.value("--pack-destination")?)
+                }
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        let (result, tarball) = Self::pack()?;
+        Self::print_contents(&result);
+
+        if !self.dry_run {
+            let destination = Path::new(self.destination.as_deref().unwrap_or("."));
+            fs::create_dir_all(destination).map_err(CommandError::FailedToCreateFile)?;
+            fs::write(destination.join(&result.filename), tarball)
+                .map_err(CommandError::FailedToWriteFile)?;
+        }
+
+        Output::json(&result)
+    }
+}
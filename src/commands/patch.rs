@@ -0,0 +1,117 @@
+use std::{env, path::PathBuf};
+
+use async_trait::async_trait;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    installer::Installer,
+    isolated::IsolatedLayout,
+    output::Output,
+    patch::Patches,
+    types::{Lockfile, PatchEdit},
+    versions::{Versions, LATEST},
+};
+
+use super::{
+    arguments::{Arg, Arguments, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "patch",
+    aliases: &[],
+    arguments: "<package[@version]>",
+    description:
+        "Copy an installed package to a temporary directory to edit it, the changes are saved as a patch in patches/ with `click patch-commit <directory>` and applied by every install",
+    flags: &[],
+    passthrough: false,
+};
+
+const EDIT_DIRECTORY: &str = "click-patch"; // In the temporary directory, with a directory for each package being edited
+
+#[derive(Default)]
+pub struct PatchHandler {
+    package_name: String,
+    version: Option<String>, // The version installed at the top of node_modules if None
+}
+
+impl PatchHandler {
+    /// Package string is formated as package@version
+    /// A package only installed nested in others has to be the only version installed, or be given with its version.
+    fn package(&self) -> Result<String, CommandError> {
+        let lockfile = Lockfile::read()?;
+        let not_installed = || CommandError::PackageNotInstalled(self.package_name.to_string());
+
+        if let Some(version) = &self.version {
+            let package = Versions::stringify(&self.package_name, version);
+            return match lockfile.packages.contains_key(&package) {
+                true => Ok(package),
+                false => Err(not_installed()),
+            };
+        }
+
+        let linked = Installer::linked_packages()?.into_iter().find(|package| {
+            Versions::parse_raw_package_details(package.to_string()).0 == self.package_name
+        });
+        if let Some(package) = linked {
+            return Ok(package);
+        }
+
+        let mut locked = lockfile.packages.keys().filter(|package| {
+            Versions::parse_raw_package_details(package.to_string()).0 == self.package_name
+        });
+        match (locked.next(), locked.next()) {
+            (Some(package), None) => Ok(package.to_string()),
+            _ => Err(not_installed()),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler for PatchHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Positional(package) if self.package_name.is_empty() => {
+                    let (package_name, version) = Versions::parse_raw_package_details(package);
+                    self.package_name = package_name;
+                    self.version = (version != LATEST).then_some(version);
+                }
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        if self.package_name.is_empty() {
+            return Err(ParseError::MissingArgument(String::from("package")));
+        }
+
+        Ok(())
+    }
+
+    /// The package is copied as it is installed, so a package that is already patched is edited with its patch.
+    async fn execute(&self) -> Result<(), CommandError> {
+        let package = self.package()?;
+
+        // The directory is named after the package, which is how `click patch-commit` knows what it is a copy of
+        let directory: PathBuf = env::temp_dir()
+            .join(EDIT_DIRECTORY)
+            .join(IsolatedLayout::key(&package));
+        if directory.exists() {
+            return Err(CommandError::PatchInProgress(
+                directory.display().to_string(),
+            ));
+        }
+
+        Patches::copy_for_editing(&package, &directory)
+            .map_err(CommandError::FailedToCreateFile)?;
+
+        Output::message(format!(
+            "Edit {package} in '{0}', then save the changes with `click patch-commit '{0}'`",
+            directory.display()
+        ));
+        Output::json(&PatchEdit {
+            package,
+            directory: directory.display().to_string(),
+        })
+    }
+}
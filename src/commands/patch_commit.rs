@@ -0,0 +1,113 @@
+use std::{fs, path::PathBuf};
+
+use async_trait::async_trait;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    isolated::IsolatedLayout,
+    output::Output,
+    patch::{Patch, Patches, PATCHES_DIRECTORY},
+    types::PatchCommit,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Usage},
+    command_handler::CommandHandler,
+    install::InstallHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "patch-commit",
+    aliases: &[],
+    arguments: "<directory>",
+    description:
+        "Save the changes made to a package copied with `click patch` as a patch in patches/, then install to apply it. The patch is removed if nothing was changed",
+    flags: &[],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct PatchCommitHandler {
+    directory: PathBuf, // The copy `click patch` made, which is removed once its patch is saved
+}
+
+#[async_trait]
+impl CommandHandler for PatchCommitHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Positional(directory) if self.directory.as_os_str().is_empty() => {
+                    self.directory = PathBuf::from(&directory);
+                    if !self.directory.is_dir() {
+                        return Err(ParseError::InvalidArgument(
+                            String::from("directory"),
+                            directory,
+                        ));
+                    }
+                }
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        if self.directory.as_os_str().is_empty() {
+            return Err(ParseError::MissingArgument(String::from("directory")));
+        }
+
+        Ok(())
+    }
+
+    /// The patch is made against the package as it is in the cache, so it has every change including those of an earlier
+    /// patch of the package.
+    async fn execute(&self) -> Result<(), CommandError> {
+        let key = self
+            .directory
+            .canonicalize()
+            .map_err(CommandError::FailedToReadFile)?
+            .file_name()
+            .map(|key| key.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let package = IsolatedLayout::package_from_key(&key);
+
+        let original = Patches::cache_path(&package);
+        if !original.is_dir() {
+            return Err(CommandError::PackageNotInstalled(package));
+        }
+
+        let diff =
+            Patch::diff(&original, &self.directory).map_err(CommandError::FailedToReadFile)?;
+        let path = Patches::path(&package);
+        let saved = match diff.is_empty() {
+            true if path.exists() => {
+                fs::remove_file(&path).map_err(CommandError::FailedToWriteFile)?;
+                Output::message(format!(
+                    "{package} is not changed anymore, removed '{}'",
+                    path.display()
+                ));
+                None
+            }
+            true => {
+                Output::message(format!(
+                    "{package} is not changed, there is nothing to save"
+                ));
+                None
+            }
+            false => {
+                fs::create_dir_all(PATCHES_DIRECTORY).map_err(CommandError::FailedToCreateFile)?;
+                fs::write(&path, diff).map_err(CommandError::FailedToWriteFile)?;
+                Output::message(format!(
+                    "Saved the changes to {package} as '{}'",
+                    path.display()
+                ));
+                Some(path.display().to_string())
+            }
+        };
+
+        fs::remove_dir_all(&self.directory).map_err(CommandError::FailedToWriteFile)?;
+        InstallHandler::nested().execute().await?;
+
+        Output::json(&PatchCommit {
+            package,
+            path: saved,
+        })
+    }
+}
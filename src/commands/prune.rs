@@ -0,0 +1,273 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    hoisted::HoistedLayout,
+    installer::{Installer, MODULES_DIRECTORY},
+    isolated::{IsolatedLayout, ISOLATED_DIRECTORY},
+    manifest::{DependencyKind, Manifest},
+    output::Output,
+    shims::ShimPlatform,
+    source::Source,
+    transaction::Transaction,
+    types::{Lockfile, PlacedPackage},
+    versions::Versions,
+    workspaces::Workspaces,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+    install::InstallHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "prune",
+    aliases: &[],
+    arguments: "",
+    description: "Remove the packages in node_modules that nothing in package.json depends on",
+    flags: &[
+        Flag::new(
+            &["--production"],
+            "Remove devDependencies too, also set by NODE_ENV=production",
+        ),
+        Flag::new(
+            &["--dry-run"],
+            "Print what would be removed without changing anything",
+        ),
+    ],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct PruneHandler {
+    production: bool, // Also remove what is only needed by devDependencies, set with --production.
+    dry_run: bool,    // Only print what would be removed, set with --dry-run.
+}
+
+impl PruneHandler {
+    /// Prunes as part of another command, such as `click install --watch` once a dependency is removed.
+    pub fn new(production: bool, dry_run: bool) -> Self {
+        Self {
+            production,
+            dry_run,
+        }
+    }
+
+    /// The name of every package in package.json and in the package.json of each workspace, without devDependencies
+    /// with `--production`. Custom groups such as buildDependencies are kept, even when they were not installed. Dependencies on a workspace are left out, as the workspace is linked instead.
+    fn listed_packages(&self) -> Result<BTreeSet<(String, String)>, CommandError> {
+        let manifest = Manifest::read()?.ok_or(CommandError::NoManifest)?;
+        let workspaces = Workspaces::discover()?;
+
+        let mut kinds = vec![DependencyKind::Production, DependencyKind::Optional];
+        if !self.production {
+            kinds.push(DependencyKind::Development);
+        }
+
+        let manifests = [&manifest]
+            .into_iter()
+            .chain(workspaces.iter().map(|workspace| &workspace.manifest));
+
+        let mut listed = BTreeSet::new();
+        for manifest in manifests {
+            for kind in kinds.iter().chain(manifest.groups().iter()) {
+                for (package_name, range) in manifest.dependencies(kind.field()) {
+                    if Source::from_spec(&range).is_some_and(|source| source.is_workspace())
+                        || workspaces
+                            .iter()
+                            .any(|workspace| workspace.name == package_name)
+                    {
+                        continue;
+                    }
+
+                    listed.insert((package_name, range));
+                }
+            }
+        }
+
+        Ok(listed)
+    }
+
+    /// The specs in the lockfile of the packages in package.json. A range is found by the spec it is locked under, the
+    /// specs of a range that has to be resolved first, such as a dist-tag, are found by the name of the package.
+    fn requested_specs(lockfile: &Lockfile, listed: &BTreeSet<(String, String)>) -> Vec<String> {
+        let mut specs = Vec::new();
+        for (package_name, range) in listed {
+            if let Ok(semantic_version) = Versions::parse_semantic_version(range) {
+                let spec = Lockfile::spec(package_name, Some(&semantic_version));
+                if lockfile.requested.contains_key(&spec) {
+                    specs.push(spec);
+                    continue;
+                }
+            }
+
+            specs.extend(
+                lockfile
+                    .requested
+                    .keys()
+                    .filter(|spec| {
+                        &Versions::parse_raw_package_details(spec.to_string()).0 == package_name
+                    })
+                    .cloned(),
+            );
+        }
+
+        specs
+    }
+
+    /// Every package the specs were locked to along with everything they depend on, formatted as package@version.
+    fn reachable_packages(lockfile: &Lockfile, specs: &[String]) -> HashSet<String> {
+        let mut reachable = HashSet::new();
+        for spec in specs {
+            let Some(package) = lockfile.requested.get(spec) else {
+                continue;
+            };
+
+            reachable.insert(package.to_string());
+            if let Some(locked_package) = lockfile.packages.get(package) {
+                reachable.extend(locked_package.dependencies.iter().cloned());
+            }
+        }
+
+        reachable
+    }
+
+    /// Every package in node_modules and where it is, with either layout. Packages of the isolated layout are in
+    /// node_modules/.click, with a link at the top of node_modules to those requested.
+    fn installed_packages() -> Result<Vec<(String, PathBuf)>, CommandError> {
+        let mut installed = HoistedLayout::installed()?
+            .placements
+            .into_iter()
+            .map(|(location, package)| (package, HoistedLayout::path(&location)))
+            .collect::<Vec<_>>();
+
+        for package in Installer::linked_packages()? {
+            let (package_name, _) = Versions::parse_raw_package_details(package.to_string());
+            let path = Path::new(MODULES_DIRECTORY).join(&package_name);
+            if !installed
+                .iter()
+                .any(|(_, installed_path)| installed_path == &path)
+            {
+                installed.push((package, path));
+            }
+        }
+
+        let entries = match fs::read_dir(ISOLATED_DIRECTORY) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(installed),
+            Err(err) => return Err(CommandError::FailedToReadFile(err)),
+        };
+
+        for entry in entries {
+            let entry = entry.map_err(CommandError::FailedDirectoryEntry)?;
+            let key = entry.file_name().to_string_lossy().to_string();
+            installed.push((IsolatedLayout::package_from_key(&key), entry.path()));
+        }
+
+        Ok(installed)
+    }
+}
+
+#[async_trait]
+impl CommandHandler for PruneHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--production") => self.production = true,
+                Arg::Flag("--dry-run") => self.dry_run = true,
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes everything in node_modules that the packages in package.json do not depend on, found from the lockfile.
+    /// Specs in the lockfile of packages that are no longer in package.json are removed from it too, unless only
+    /// devDependencies are being removed with `--production`, as they are still wanted for development.
+    async fn execute(&self) -> Result<(), CommandError> {
+        let mut lockfile = Lockfile::read()?;
+        let listed = self.listed_packages()?;
+        let specs = Self::requested_specs(&lockfile, &listed);
+        let reachable = Self::reachable_packages(&lockfile, &specs);
+
+        let mut extraneous = Self::installed_packages()?
+            .into_iter()
+            .filter(|(package, _)| !reachable.contains(package))
+            .collect::<Vec<_>>();
+        extraneous.sort_by(|a, b| a.1.cmp(&b.1));
+
+        // Anything nested in a package that is removed goes with it
+        let mut removed: Vec<(String, PathBuf)> = Vec::new();
+        for (package, path) in extraneous {
+            if !removed
+                .iter()
+                .any(|(_, removed_path)| path.starts_with(removed_path))
+            {
+                removed.push((package, path));
+            }
+        }
+
+        let result = removed
+            .iter()
+            .map(|(package, path)| PlacedPackage {
+                path: path
+                    .strip_prefix("./")
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .to_string(),
+                package: package.to_string(),
+            })
+            .collect::<Vec<_>>();
+
+        let has_stale_specs = !self.production && lockfile.requested.len() > specs.len();
+        if result.is_empty() && !has_stale_specs {
+            Output::message("Nothing to prune, everything in node_modules is needed");
+            return Output::json(&result);
+        }
+
+        match self.dry_run {
+            true => Output::message(format!(
+                "Dry run, {} package(s) would be removed:",
+                result.len()
+            )),
+            false => Output::message(format!("Removed {} package(s):", result.len())),
+        }
+        for placed in result.iter() {
+            Output::message(format!("  - {} ({})", placed.path, placed.package));
+        }
+
+        if self.dry_run {
+            return Output::json(&result);
+        }
+
+        let transaction = Transaction::begin()?;
+        let pruned = removed
+            .iter()
+            .try_for_each(|(_, path)| {
+                HoistedLayout::remove(path).map_err(CommandError::FailedToRemoveLink)
+            })
+            .and_then(|_| Installer::link_bins(ShimPlatform::default()));
+
+        if let Err(err) = pruned {
+            InstallHandler::rollback(transaction);
+            return Err(err);
+        }
+
+        if has_stale_specs {
+            lockfile.requested.retain(|spec, _| specs.contains(spec));
+            lockfile.prune();
+            lockfile.write()?;
+        }
+
+        Output::json(&result)
+    }
+}
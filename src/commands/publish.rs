@@ -0,0 +1,224 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::json;
+
+use crate::{
+    config::Config,
+    errors::{CommandError, ParseError},
+    http::{HTTPRequest, PublishOutcome},
+    manifest::{Manifest, OrderedJson},
+    output::Output,
+    prompt::Prompt,
+    types::{PackResult, PublishResult},
+    versions::{Versions, LATEST},
+    workspaces::Workspaces,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+    pack::PackHandler,
+    run::RunScriptHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "publish",
+    aliases: &[],
+    arguments: "",
+    description: "Pack the package in this directory and publish it to the registry",
+    flags: &[
+        Flag::with_value(
+            &["--tag"],
+            "tag",
+            "The dist-tag to publish the version under, latest by default",
+        ),
+        Flag::with_value(
+            &["--access"],
+            "public|restricted",
+            "Who can install a scoped package",
+        ),
+        Flag::with_value(
+            &["--otp"],
+            "code",
+            "A one-time password, for accounts that use two-factor authentication",
+        ),
+        Flag::new(
+            &["--dry-run"],
+            "Pack and print what would be published without uploading it",
+        ),
+    ],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct PublishHandler {
+    tag: Option<String>, // The dist-tag pointed at the published version, latest if None.
+    access: Option<String>, // Either public or restricted, None to leave it to the registry.
+    otp: Option<String>, // Sent with the upload, otherwise it is asked for if the registry wants one.
+    dry_run: bool,       // Only pack and print what would be published, set with --dry-run.
+}
+
+impl PublishHandler {
+    /// The packument the registry expects for a new version, which has the package.json of the version with the
+    /// details of its tarball, and the tarball itself as a base64 encoded attachment.
+    fn body(
+        &self,
+        manifest: &Manifest,
+        registry: &str,
+        result: &PackResult,
+        tarball: &[u8],
+    ) -> Result<Vec<u8>, CommandError> {
+        let name = &result.name;
+        let version = &result.version;
+        let attachment_name = format!("{name}-{version}.tgz");
+
+        let mut version_manifest =
+            serde_json::to_value(&manifest.json).map_err(CommandError::FailedToSerializeRequest)?;
+        version_manifest["_id"] = json!(format!("{name}@{version}"));
+        version_manifest["dist"] = json!({
+            "shasum": result.shasum,
+            "integrity": result.integrity,
+            "tarball": format!("{registry}/{name}/-/{attachment_name}"),
+        });
+
+        let body = json!({
+            "_id": name,
+            "name": name,
+            "description": manifest.field("description"),
+            "dist-tags": { self.tag(): version },
+            "versions": { version: version_manifest },
+            "access": self.access,
+            "_attachments": {
+                attachment_name: {
+                    "content_type": "application/octet-stream",
+                    "data": STANDARD.encode(tarball),
+                    "length": tarball.len(),
+                },
+            },
+        });
+
+        serde_json::to_vec(&body).map_err(CommandError::FailedToSerializeRequest)
+    }
+
+    /// The upload is sent again with a one-time password if the registry asks for one, which is entered on the
+    /// terminal unless --otp gave one already.
+    async fn upload(
+        &self,
+        client: reqwest::Client,
+        package_name: &str,
+        body: Vec<u8>,
+    ) -> Result<(), CommandError> {
+        let mut otp = self.otp.clone();
+        let mut asked = false;
+        loop {
+            match HTTPRequest::publish(client.clone(), package_name, body.clone(), otp.as_deref())
+                .await?
+            {
+                PublishOutcome::Published => return Ok(()),
+                PublishOutcome::OtpRequired if otp.is_some() => {
+                    return Err(CommandError::InvalidOtp)
+                }
+                PublishOutcome::OtpRequired if asked => return Err(CommandError::OtpRequired),
+                PublishOutcome::OtpRequired => {
+                    asked = true;
+                    let answer = Prompt::ask(
+                        "This account uses two-factor authentication, enter a one-time password",
+                        "",
+                    )?;
+                    otp = (!answer.is_empty()).then_some(answer);
+                }
+            }
+        }
+    }
+
+    fn tag(&self) -> &str {
+        self.tag.as_deref().unwrap_or(LATEST)
+    }
+}
+
+#[async_trait]
+impl CommandHandler for PublishHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--tag") => {
+                    // A tag that is also a range could not be told apart from it when installing
+                    let tag = args.value("--tag")?;
+                    if !Versions::is_dist_tag(&tag) {
+                        return Err(ParseError::InvalidArgument(String::from("--tag"), tag));
+                    }
+                    self.tag = Some(tag);
+                }
+                Arg::Flag("--access") => {
+                    let access = args.value("--access")?;
+                    if !matches!(access.as_str(), "public" | "restricted") {
+                        return Err(ParseError::InvalidArgument(
+                            String::from("--access"),
+                            access,
+                        ));
+                    }
+                    self.access = Some(access);
+                }
+                Arg::Flag("--otp") => self.otp = Some(args.value("--otp")?),
+                Arg::Flag("--dry-run") => self.dry_run = true,
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        let config = Config::load()?;
+
+        let manifest = Manifest::read()?.ok_or(CommandError::NoManifest)?;
+        RunScriptHandler::run_if_present(&manifest, "prepublishOnly", None)?;
+
+        let (result, tarball) = PackHandler::pack()?;
+        if matches!(manifest.json.get("private"), Some(OrderedJson::Bool(true))) {
+            return Err(CommandError::PrivatePackage(result.name));
+        }
+
+        let registry = config.registry_for(&result.name).to_string();
+        let package = Versions::stringify(&result.name, &result.version);
+        PackHandler::print_contents(&result);
+
+        match self.dry_run {
+            true => Output::message(format!(
+                "Dry run, would publish {package} to '{registry}' with tag {}",
+                self.tag()
+            )),
+            false => {
+                if config.authorization(&format!("{registry}/")).is_none() {
+                    return Err(CommandError::NotLoggedIn(registry));
+                }
+
+                Output::message(format!(
+                    "Publishing {package} to '{registry}' with tag {}..",
+                    self.tag()
+                ));
+
+                let published = Workspaces::published_manifest(&manifest)?;
+                let body = self.body(
+                    published.as_ref().unwrap_or(&manifest),
+                    &registry,
+                    &result,
+                    &tarball,
+                )?;
+                self.upload(Config::client()?, &result.name, body).await?;
+
+                Output::message(format!("Published {package}"));
+
+                RunScriptHandler::run_if_present(&manifest, "publish", None)?;
+                RunScriptHandler::run_if_present(&manifest, "postpublish", None)?;
+            }
+        }
+
+        Output::json(&PublishResult {
+            registry,
+            tag: self.tag().to_string(),
+            access: self.access.clone(),
+            tarball: result,
+        })
+    }
+}
@@ -0,0 +1,83 @@
+use async_trait::async_trait;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    installer::Installer,
+    output::Output,
+    reporter::SilentReporter,
+    scripts::Scripts,
+    versions::Versions,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "rebuild",
+    aliases: &[],
+    arguments: "[package]..",
+    description:
+        "Run the install scripts of the packages in node_modules again, such as to build native addons after switching Node versions. Every package with a build step is rebuilt unless packages are given",
+    flags: &[],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct RebuildHandler {
+    package_names: Vec<String>, // Only these are rebuilt if any are given
+}
+
+#[async_trait]
+impl CommandHandler for RebuildHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Positional(package_name) => self.package_names.push(package_name),
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Packages are rebuilt whether or not their scripts have already run, in the same order as an install runs them.
+    async fn execute(&self) -> Result<(), CommandError> {
+        let linked = Installer::linked_packages()?
+            .into_iter()
+            .map(|package| {
+                let (package_name, _) = Versions::parse_raw_package_details(package.to_string());
+                (package_name, package)
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(package_name) = self
+            .package_names
+            .iter()
+            .find(|package_name| !linked.iter().any(|(name, _)| name == *package_name))
+        {
+            return Err(CommandError::PackageNotInstalled(package_name.to_string()));
+        }
+
+        let selected = linked
+            .into_iter()
+            .filter(|(package_name, _)| {
+                self.package_names.is_empty() || self.package_names.contains(package_name)
+            })
+            .map(|(_, package)| package)
+            .collect();
+
+        let packages = Scripts::in_dependency_order(selected)?;
+        for package in packages.iter() {
+            Scripts::run_lifecycle(package, false, &SilentReporter)?;
+        }
+
+        match packages.is_empty() {
+            true => Output::message("Nothing to rebuild, no package has install scripts"),
+            false => Output::message(format!("Rebuilt {} package(s)", packages.len())),
+        }
+
+        Output::json(&packages)
+    }
+}
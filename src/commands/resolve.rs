@@ -0,0 +1,185 @@
+use std::{fs, sync::Arc};
+
+use async_trait::async_trait;
+
+use crate::{
+    cache::Cache,
+    config::Config,
+    errors::{CommandError, ParseError},
+    http::NetworkMode,
+    installer::{CacheOptions, Installer, PackageInfo, DEFAULT_CONCURRENCY},
+    output::Output,
+    platform::Platform,
+    reporter::SilentReporter,
+    types::{ResolvedGraph, ResolvedPackage},
+    versions::{Strategy, Versions, LATEST},
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "resolve",
+    aliases: &[],
+    arguments: "<package[@range]>",
+    description: "Resolve the dependency graph of a package into the cache and print it",
+    flags: &[Flag::with_value(
+        &["--output"],
+        "path",
+        "Write the graph as JSON to this file",
+    )],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct ResolveHandler {
+    package_name: String,
+    range: String, // A range or dist-tag, resolved once the registry can be asked.
+    output_path: Option<String>, // Write the graph as JSON to this file instead of printing it.
+}
+
+impl ResolveHandler {
+    /// Resolves the package into the cache, which is where resolution reads lockfiles from, but never touches node_modules.
+    /// Returns the resolved package formatted as package@version.
+    async fn resolve(&self, client: reqwest::Client) -> Result<String, CommandError> {
+        let semantic_version = Installer::parse_range(
+            client.clone(),
+            &self.package_name,
+            &self.range,
+            NetworkMode::Online,
+        )
+        .await?;
+        let semantic_version = semantic_version.as_ref();
+        let full_version = Versions::resolve_full_version(semantic_version);
+        let full_version = full_version.as_ref();
+
+        let (is_cached, cached_version) =
+            Cache::exists(&self.package_name, full_version, semantic_version).await?;
+
+        if is_cached {
+            let version = cached_version.expect("Could not resolve version of cached package");
+            return Ok(Versions::stringify(&self.package_name, &version));
+        }
+
+        let version_data = Installer::get_version_data(
+            client.clone(),
+            &self.package_name,
+            full_version,
+            semantic_version,
+            NetworkMode::Online,
+        )
+        .await?;
+
+        let stringified = Versions::stringify(&version_data.name, &version_data.version);
+
+        let package_info = PackageInfo {
+            version_data,
+            is_latest: Versions::is_latest(full_version),
+            stringified: stringified.to_string(),
+        };
+
+        // Resuming relies on the install state file in node_modules, which resolving must not create
+        let options = CacheOptions {
+            integrity_allowlist: None,
+            resumable: false,
+            keep_going: false,
+            lockfile: None,
+            concurrency: DEFAULT_CONCURRENCY,
+            network_mode: NetworkMode::Online,
+            legacy_peer_deps: false,
+            platform: Platform::default(),
+            engine_strict: false,
+            strict_signatures: false,
+            reporter: Arc::new(SilentReporter),
+            strategy: Strategy::Highest,
+        };
+
+        let outcome = Installer::cache_package(client, package_info, options).await;
+        if let Some(err) = outcome.errors.into_iter().next() {
+            return Err(err);
+        }
+
+        Ok(stringified)
+    }
+
+    /// Builds the graph from the lockfiles of the package and everything it depends on.
+    fn graph(root: String) -> Result<ResolvedGraph, CommandError> {
+        let mut graph = ResolvedGraph {
+            root: root.to_string(),
+            packages: Default::default(),
+        };
+
+        for package in Cache::cached_packages(&root)? {
+            let package_lock = Cache::read_lock(&package)?;
+            graph.packages.insert(
+                package,
+                ResolvedPackage {
+                    integrity: package_lock.integrity,
+                    dependencies: package_lock.dependencies,
+                },
+            );
+        }
+
+        Ok(graph)
+    }
+
+    fn print_graph(graph: &ResolvedGraph) {
+        for (package, resolved_package) in graph.packages.iter() {
+            println!("{package}");
+            for dependency in resolved_package.dependencies.iter() {
+                println!("  {dependency}");
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl CommandHandler for ResolveHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        let mut package_details = None;
+
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--output") => self.output_path = Some(args.value("--output")?),
+                Arg::Positional(arg) => package_details = Some(arg),
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        let package_details =
+            package_details.ok_or(ParseError::MissingArgument(String::from("package name")))?;
+
+        let (package_name, range) = Versions::parse_raw_package_details(package_details);
+
+        // Ranges are parsed straight away so that a mistyped one is reported before anything is requested
+        if !Versions::is_dist_tag(&range) && range != LATEST {
+            Versions::parse_semantic_version(&range)?;
+        }
+
+        self.package_name = package_name;
+        self.range = range;
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        Config::load()?;
+        let client = Config::client()?;
+
+        let root = self.resolve(client).await?;
+        let graph = Self::graph(root)?;
+
+        let Some(output_path) = &self.output_path else {
+            if !Output::is_json() {
+                Self::print_graph(&graph);
+            }
+            return Output::json(&graph);
+        };
+
+        let graph_string =
+            serde_json::to_string_pretty(&graph).map_err(CommandError::FailedToSerializeGraph)?;
+        fs::write(output_path, graph_string).map_err(CommandError::FailedToWriteFile)
+    }
+}
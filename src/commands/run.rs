@@ -0,0 +1,419 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    process::{Command, ExitStatus, Stdio},
+    thread,
+};
+
+use async_trait::async_trait;
+use tokio::task::JoinSet;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    manifest::Manifest,
+    output::Output,
+    scripts::Scripts,
+    workspaces::{Workspace, Workspaces},
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "run",
+    aliases: &["run-script"],
+    arguments: "[script] [args]...",
+    description:
+        "Run a script from package.json along with its pre and post scripts, such as prebuild and postbuild, or list them without a script",
+    flags: &[
+        Flag::with_value(
+            &["-w", "--workspace", "--filter"],
+            "workspace",
+            "Run the script in the workspaces this selects, by name or by a path starting with .",
+        ),
+        Flag::new(
+            &["-r", "--recursive"],
+            "Run the script in every workspace that has it, after the workspaces they depend on",
+        ),
+        Flag::new(
+            &["--parallel"],
+            "Run the script in workspaces at the same time, each once the workspaces it depends on have finished",
+        ),
+        Flag::with_value(
+            &["--jobs"],
+            "count",
+            "How many scripts --parallel runs at once, the number of CPUs by default",
+        ),
+    ],
+    passthrough: true,
+};
+
+#[derive(Default)]
+pub struct RunScriptHandler {
+    script_name: Option<String>, // If None then list every script instead.
+    script_args: Vec<String>,    // Appended to the script, anything after `--` is passed on as is.
+    workspaces: Vec<String>, // Run the script in the workspaces these select rather than the root, set with -w and --filter.
+    recursive: bool, // Run the script in every workspace that has it, with what they print prefixed by their name.
+    parallel: bool, // Run the script in workspaces at the same time, each after the workspaces it depends on.
+    jobs: Option<usize>, // How many scripts run at once with --parallel, the number of CPUs if None.
+}
+
+impl RunScriptHandler {
+    /// Arguments are quoted so that the shell passes them on unchanged.
+    #[cfg(unix)]
+    pub fn quote(arg: &str) -> String {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+
+    #[cfg(windows)]
+    pub fn quote(arg: &str) -> String {
+        format!("\"{}\"", arg.replace('"', "\"\""))
+    }
+
+    fn list_scripts(manifest: &Manifest) {
+        let scripts = Scripts::scripts(manifest);
+        if scripts.is_empty() {
+            Output::message("There are no scripts in package.json");
+            return;
+        }
+
+        Output::message("Scripts available with `click run <script>`:");
+        for (script_name, script) in scripts {
+            Output::message(format!("  {script_name}\n    {script}"));
+        }
+    }
+
+    /// Appends the arguments given to the script.
+    fn with_args(&self, mut script: String) -> String {
+        for arg in self.script_args.iter() {
+            script = format!("{script} {}", Self::quote(arg));
+        }
+
+        script
+    }
+
+    /// The pre hook, the script with the arguments given and the post hook, the ones package.json has in the order
+    /// they run. Hooks are not given the arguments.
+    fn with_hooks(
+        &self,
+        manifest: &Manifest,
+        script_name: &str,
+        script: String,
+    ) -> Vec<(String, String)> {
+        let scripts = Scripts::scripts(manifest);
+        let hook = |hook_name: String| scripts.iter().find(|(name, _)| *name == hook_name).cloned();
+
+        [
+            hook(format!("pre{script_name}")),
+            Some((script_name.to_string(), self.with_args(script))),
+            hook(format!("post{script_name}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// What is printed before a script runs, hooks are named.
+    fn describe(script_name: &str, name: &str, script: &str) -> String {
+        match name == script_name {
+            true => script.to_string(),
+            false => format!("{name}: {script}"),
+        }
+    }
+
+    fn command(
+        script_name: &str,
+        script: &str,
+        directory: &Path,
+        workspace: Option<&Path>,
+    ) -> Result<Command, CommandError> {
+        let mut command = Scripts::shell_command(script);
+        command
+            .current_dir(directory)
+            .env("PATH", Scripts::workspace_path_with_bins(workspace)?)
+            .env("npm_lifecycle_event", script_name);
+
+        Ok(command)
+    }
+
+    /// A script killed by a signal has no exit code.
+    fn check_status(script_name: &str, status: ExitStatus) -> Result<(), CommandError> {
+        match status.success() {
+            true => Ok(()),
+            false => Err(CommandError::ScriptFailed(
+                script_name.to_string(),
+                status.code().unwrap_or(1),
+            )),
+        }
+    }
+
+    /// Runs a script in the given directory, which is the root of the project unless it is run in a workspace.
+    pub fn run(
+        script_name: &str,
+        script: &str,
+        directory: &Path,
+        workspace: Option<&Path>,
+    ) -> Result<(), CommandError> {
+        let status = Self::command(script_name, script, directory, workspace)?
+            .stdout(Output::script_stdout())
+            .status()
+            .map_err(CommandError::ComandFailedError)?;
+
+        Self::check_status(script_name, status)
+    }
+
+    /// Runs a script of package.json such as `preversion` if it has one, the same as npm runs the hooks of its commands.
+    pub fn run_if_present(
+        manifest: &Manifest,
+        script_name: &str,
+        workspace: Option<&Path>,
+    ) -> Result<(), CommandError> {
+        let Some((_, script)) = Scripts::scripts(manifest)
+            .into_iter()
+            .find(|(name, _)| name == script_name)
+        else {
+            return Ok(());
+        };
+
+        Output::message(format!("> {script_name}: {script}"));
+        Self::run(
+            script_name,
+            &script,
+            workspace.unwrap_or(Path::new(".")),
+            workspace,
+        )
+    }
+
+    /// Runs a script in a workspace with each line it prints prefixed by the name of the workspace, so that the output
+    /// of scripts running at the same time can be told apart.
+    fn run_prefixed(
+        script_name: &str,
+        script: &str,
+        workspace: &Workspace,
+    ) -> Result<(), CommandError> {
+        let mut child = Self::command(script_name, script, &workspace.path, Some(&workspace.path))?
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(CommandError::ComandFailedError)?;
+
+        let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+        thread::scope(|scope| {
+            if let Some(stdout) = stdout {
+                scope.spawn(|| Self::prefix_lines(stdout, &workspace.name, Output::is_json()));
+            }
+            if let Some(stderr) = stderr {
+                scope.spawn(|| Self::prefix_lines(stderr, &workspace.name, true));
+            }
+        });
+
+        let status = child.wait().map_err(CommandError::ComandFailedError)?;
+        Self::check_status(script_name, status)
+    }
+
+    /// Each line is printed at once, so lines of different scripts are not mixed together.
+    fn prefix_lines(output: impl Read, prefix: &str, stderr: bool) {
+        let mut reader = BufReader::new(output);
+        let mut line = Vec::new();
+        while reader
+            .read_until(b'\n', &mut line)
+            .is_ok_and(|read| read > 0)
+        {
+            let text = String::from_utf8_lossy(&line);
+            match stderr {
+                true => eprintln!("{prefix} | {}", text.trim_end()),
+                false => println!("{prefix} | {}", text.trim_end()),
+            }
+            line.clear();
+        }
+    }
+
+    /// Runs the script and its hooks in a workspace, prefixing what they print when scripts run in many workspaces.
+    fn run_in_workspace(
+        script_name: &str,
+        workspace: &Workspace,
+        scripts: &[(String, String)],
+        prefixed: bool,
+    ) -> Result<(), CommandError> {
+        for (name, script) in scripts {
+            let description = Self::describe(script_name, name, script);
+            match prefixed {
+                true => {
+                    Output::message(format!("{} | > {description}", workspace.name));
+                    Self::run_prefixed(name, script, workspace)?;
+                }
+                false => {
+                    Output::message(format!("> {}: {description}", workspace.name));
+                    Self::run(name, script, &workspace.path, Some(&workspace.path))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the script in every selected workspace that has it, after the workspaces they depend on.
+    async fn run_in_workspaces(&self, script_name: &str) -> Result<(), CommandError> {
+        let workspaces = match self.recursive {
+            true => Workspaces::discover()?,
+            false => Workspaces::filter(Workspaces::discover()?, &self.workspaces)?,
+        };
+
+        let mut runs = Vec::new();
+        for workspace in Workspaces::topological_order(workspaces)? {
+            let Some((_, script)) = Scripts::scripts(&workspace.manifest)
+                .into_iter()
+                .find(|(name, _)| name == script_name)
+            else {
+                continue;
+            };
+
+            let scripts = self.with_hooks(&workspace.manifest, script_name, script);
+            runs.push((workspace, scripts));
+        }
+
+        if runs.is_empty() {
+            return Err(CommandError::ScriptNotFound(script_name.to_string()));
+        }
+
+        let count = runs.len();
+        match self.parallel {
+            true => self.run_in_parallel(script_name, runs).await?,
+            false => {
+                for (workspace, scripts) in runs.iter() {
+                    Self::run_in_workspace(script_name, workspace, scripts, self.recursive)?;
+                }
+            }
+        }
+
+        if self.recursive || self.parallel {
+            Output::message(format!("Ran '{script_name}' in {count} workspace(s)"));
+        }
+        Ok(())
+    }
+
+    /// Runs scripts in up to --jobs workspaces at once, each once the workspaces it depends on have finished. Once a
+    /// script fails no more are started, and its error is returned after the running ones finish.
+    async fn run_in_parallel(
+        &self,
+        script_name: &str,
+        mut pending: Vec<(Workspace, Vec<(String, String)>)>,
+    ) -> Result<(), CommandError> {
+        let jobs = self
+            .jobs
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, usize::from));
+
+        let mut running: Vec<String> = Vec::new();
+        let mut tasks = JoinSet::new();
+        let mut result = Ok(());
+        loop {
+            while result.is_ok() && tasks.len() < jobs {
+                // Workspaces are in topological order, so one is always ready while none are running
+                let ready = pending.iter().position(|(workspace, _)| {
+                    !pending
+                        .iter()
+                        .map(|(other, _)| &other.name)
+                        .chain(running.iter())
+                        .any(|name| *name != workspace.name && workspace.depends_on(name))
+                });
+                let Some(ready) = ready else {
+                    break;
+                };
+
+                let (workspace, scripts) = pending.remove(ready);
+                let script_name = script_name.to_string();
+                running.push(workspace.name.to_string());
+                tasks.spawn_blocking(move || {
+                    let result = Self::run_in_workspace(&script_name, &workspace, &scripts, true);
+                    (workspace.name, result)
+                });
+            }
+
+            let Some(finished) = tasks.join_next().await else {
+                break;
+            };
+            let (workspace_name, workspace_result) = finished.expect("Failed to join script task");
+            running.retain(|name| *name != workspace_name);
+            if result.is_ok() {
+                result = workspace_result;
+            }
+        }
+
+        result
+    }
+}
+
+#[async_trait]
+impl CommandHandler for RunScriptHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        let mut after_path_selector = false;
+
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--workspace") => {
+                    let selector = args.value("--workspace")?;
+                    after_path_selector = selector.starts_with('.');
+                    self.workspaces.push(selector);
+                    continue;
+                }
+                Arg::Flag("--recursive") => self.recursive = true,
+                Arg::Flag("--parallel") => self.parallel = true,
+                Arg::Flag("--jobs") => {
+                    let jobs = args.value("--jobs")?;
+                    self.jobs = match jobs.parse::<usize>() {
+                        Ok(jobs) if jobs > 0 => Some(jobs),
+                        _ => return Err(ParseError::InvalidArgument(String::from("--jobs"), jobs)),
+                    };
+                }
+                // The separator is only there so that the arguments are not mistaken for flags of click
+                Arg::Separator => {
+                    self.script_args.extend(args.rest());
+                    break;
+                }
+                // An unquoted path pattern is expanded by the shell, into one argument for every directory it matches
+                Arg::Positional(path) if after_path_selector && Path::new(&path).is_dir() => {
+                    self.workspaces.push(path);
+                    continue;
+                }
+                Arg::Positional(arg) if self.script_name.is_none() => self.script_name = Some(arg),
+                Arg::Positional(arg) => self.script_args.push(arg),
+                arg => return Err(arg.unexpected()),
+            }
+
+            after_path_selector = false;
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        let manifest = Manifest::read()?.ok_or(CommandError::NoManifest)?;
+
+        let script_name = match &self.script_name {
+            Some(script_name) => script_name,
+            None => {
+                Self::list_scripts(&manifest);
+                return Ok(());
+            }
+        };
+
+        if self.recursive || !self.workspaces.is_empty() {
+            return self.run_in_workspaces(script_name).await;
+        }
+
+        let script = Scripts::scripts(&manifest)
+            .into_iter()
+            .find(|(name, _)| name == script_name)
+            .map(|(_, script)| script)
+            .ok_or(CommandError::ScriptNotFound(script_name.to_string()))?;
+
+        for (name, script) in self.with_hooks(&manifest, script_name, script) {
+            Output::message(format!("> {}", Self::describe(script_name, &name, &script)));
+            Self::run(&name, &script, Path::new("."), None)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use async_trait::async_trait;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    manifest::Manifest,
+    sbom::{Sbom, SbomFormat},
+    types::Lockfile,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "sbom",
+    aliases: &[],
+    arguments: "",
+    description:
+        "Print a software bill of materials of the packages in the lockfile, with their versions, hashes, licenses and dependencies",
+    flags: &[Flag::with_value(
+        &["--format"],
+        "cyclonedx|spdx",
+        "The format of the bill of materials, CycloneDX 1.5 or SPDX 2.3 JSON. Defaults to cyclonedx",
+    )],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct SbomHandler {
+    format: SbomFormat, // Set with --format.
+}
+
+#[async_trait]
+impl CommandHandler for SbomHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--format") => {
+                    let format = args.value("--format")?;
+                    self.format = SbomFormat::parse(&format).ok_or(ParseError::InvalidArgument(
+                        String::from("--format"),
+                        format,
+                    ))?;
+                }
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        let manifest = Manifest::read()?.ok_or(CommandError::NoManifest)?;
+        let lockfile = Lockfile::read()?;
+
+        // The document is JSON whether or not --json is given
+        let sbom = Sbom::generate(self.format, &manifest, &lockfile)?;
+        let sbom_string =
+            serde_json::to_string_pretty(&sbom).map_err(CommandError::FailedToSerializeOutput)?;
+        println!("{sbom_string}");
+
+        Ok(())
+    }
+}
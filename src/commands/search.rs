@@ -0,0 +1,193 @@
+use async_trait::async_trait;
+
+use crate::{
+    config::Config,
+    errors::{CommandError, ParseError},
+    http::HTTPRequest,
+    output::Output,
+    types::SearchResult,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "search",
+    aliases: &["s", "find"],
+    arguments: "<text>...",
+    description: "Search the registry for packages",
+    flags: &[
+        Flag::with_value(
+            &["--limit"],
+            "n",
+            "How many packages to show, 20 by default",
+        ),
+        Flag::with_value(
+            &["--from"],
+            "n",
+            "How many packages to skip, for the next page",
+        ),
+        Flag::with_value(
+            &["--quality"],
+            "weight",
+            "How much quality counts in the score, from 0 to 1",
+        ),
+        Flag::with_value(
+            &["--popularity"],
+            "weight",
+            "How much popularity counts in the score, from 0 to 1",
+        ),
+        Flag::with_value(
+            &["--maintenance"],
+            "weight",
+            "How much maintenance counts in the score, from 0 to 1",
+        ),
+    ],
+    passthrough: false,
+};
+
+const DEFAULT_LIMIT: usize = 20;
+const DESCRIPTION_WIDTH: usize = 60; // Longer descriptions are cut short in the table
+
+#[derive(Default)]
+pub struct SearchHandler {
+    text: Vec<String>,    // The words to search for, which are searched for together.
+    limit: Option<usize>, // DEFAULT_LIMIT if None.
+    from: usize,
+    weights: Vec<(&'static str, f64)>, // Of quality, popularity and maintenance, the registry decides those not given.
+}
+
+impl SearchHandler {
+    fn parameters(&self) -> Vec<(&str, String)> {
+        let mut parameters = vec![
+            ("text", self.text.join(" ")),
+            ("size", self.limit.unwrap_or(DEFAULT_LIMIT).to_string()),
+            ("from", self.from.to_string()),
+        ];
+
+        parameters.extend(
+            self.weights
+                .iter()
+                .map(|(name, weight)| (*name, weight.to_string())),
+        );
+
+        parameters
+    }
+
+    fn print_table(results: &[SearchResult]) {
+        let rows = results
+            .iter()
+            .map(|result| {
+                [
+                    result.name.to_string(),
+                    result.version.to_string(),
+                    Self::shorten(result.description.as_deref().unwrap_or_default()),
+                    format!("{:.2}", result.score),
+                    result
+                        .weekly_downloads
+                        .map(|downloads| downloads.to_string())
+                        .unwrap_or_default(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let rows = rows
+            .iter()
+            .map(|row| row.each_ref().map(String::as_str))
+            .collect::<Vec<_>>();
+
+        Output::table(["Name", "Version", "Description", "Score", "Weekly"], &rows);
+    }
+
+    fn shorten(description: &str) -> String {
+        match description.chars().count() > DESCRIPTION_WIDTH {
+            true => {
+                let shortened = description
+                    .chars()
+                    .take(DESCRIPTION_WIDTH - 1)
+                    .collect::<String>();
+                format!("{}…", shortened.trim_end())
+            }
+            false => description.to_string(),
+        }
+    }
+
+    fn parse_weight(args: &mut Arguments, flag: &'static str) -> Result<f64, ParseError> {
+        let weight = args.value(flag)?;
+
+        match weight.parse::<f64>() {
+            Ok(parsed) if (0.0..=1.0).contains(&parsed) => Ok(parsed),
+            _ => Err(ParseError::InvalidArgument(flag.to_string(), weight)),
+        }
+    }
+
+    fn parse_count(args: &mut Arguments, flag: &'static str) -> Result<usize, ParseError> {
+        let count = args.value(flag)?;
+        count
+            .parse::<usize>()
+            .map_err(|_| ParseError::InvalidArgument(flag.to_string(), count))
+    }
+}
+
+#[async_trait]
+impl CommandHandler for SearchHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--limit") => self.limit = Some(Self::parse_count(args, "--limit")?),
+                Arg::Flag("--from") => self.from = Self::parse_count(args, "--from")?,
+                Arg::Flag(flag @ ("--quality" | "--popularity" | "--maintenance")) => {
+                    let weight = Self::parse_weight(args, flag)?;
+                    self.weights.push((flag.trim_start_matches("--"), weight));
+                }
+                Arg::Positional(text) => self.text.push(text),
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        if self.text.is_empty() {
+            return Err(ParseError::MissingArgument(String::from("text")));
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        Config::load()?;
+
+        let response = HTTPRequest::search(Config::client()?, &self.parameters()).await?;
+        let results = response
+            .objects
+            .into_iter()
+            .map(|object| SearchResult {
+                name: object.package.name,
+                version: object.package.version,
+                description: object.package.description,
+                score: object.score.final_score,
+                weekly_downloads: object.downloads.and_then(|downloads| downloads.weekly),
+            })
+            .collect::<Vec<_>>();
+
+        Output::json(&results)?;
+
+        if results.is_empty() {
+            Output::message(format!("No packages match '{}'", self.text.join(" ")));
+            return Ok(());
+        }
+
+        if !Output::is_json() {
+            Self::print_table(&results);
+        }
+
+        Output::message(format!(
+            "Showing {} to {} of {} package(s)",
+            self.from + 1,
+            self.from + results.len(),
+            response.total
+        ));
+
+        Ok(())
+    }
+}
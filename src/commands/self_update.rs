@@ -0,0 +1,340 @@
+use std::{
+    env::{self, consts},
+    fs, io,
+    path::Path,
+    process,
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    config::Config,
+    errors::{CommandError, ParseError},
+    http::HTTPRequest,
+    integrity::Integrity,
+    output::Output,
+    types::{Release, SelfUpdateResult},
+    versions::Versions,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "self-update",
+    aliases: &[],
+    arguments: "",
+    description:
+        "Replace click with its latest release, set update-endpoint to get releases from elsewhere",
+    flags: &[Flag::new(
+        &["--check"],
+        "Only print whether a newer release is available",
+    )],
+    passthrough: false,
+};
+
+const CHECKSUMS_ASSET: &str = "SHA256SUMS"; // Checksums of every binary of a release, as written by `sha256sum`
+
+#[derive(Default)]
+pub struct SelfUpdateHandler {
+    check: bool, // Only print whether there is a newer release, set with --check.
+}
+
+impl SelfUpdateHandler {
+    /// Binaries are released named after the platform they are built for, such as `click-x86_64-linux` or
+    /// `click-aarch64-macos`, and `click-x86_64-windows.exe` on Windows.
+    fn asset_name() -> String {
+        format!(
+            "click-{}-{}{}",
+            consts::ARCH,
+            consts::OS,
+            consts::EXE_SUFFIX
+        )
+    }
+
+    /// The SHA-256 digest of a binary, from its `<binary>.sha256` asset or from the `SHA256SUMS` asset of the release.
+    /// Either has a line with the hex digest followed by the name of the binary, a `.sha256` can have the digest alone.
+    async fn checksum(
+        client: reqwest::Client,
+        release: &Release,
+        asset_name: &str,
+    ) -> Result<String, CommandError> {
+        let checksum_name = format!("{asset_name}.sha256");
+        let checksum_assets = release
+            .assets
+            .iter()
+            .filter(|asset| asset.name == checksum_name || asset.name == CHECKSUMS_ASSET);
+
+        for checksum_asset in checksum_assets {
+            let checksums =
+                HTTPRequest::get_bytes(client.clone(), checksum_asset.browser_download_url.clone())
+                    .await?;
+
+            let digest = String::from_utf8_lossy(&checksums)
+                .lines()
+                .find_map(|line| {
+                    let mut words = line.split_whitespace();
+                    let digest = words.next()?;
+
+                    // `sha256sum` marks files it read in binary mode with a `*`
+                    match words.next().map(|name| name.trim_start_matches('*')) {
+                        Some(name) if name == asset_name => Some(digest.to_lowercase()),
+                        None if checksum_asset.name == checksum_name => Some(digest.to_lowercase()),
+                        _ => None,
+                    }
+                });
+
+            if let Some(digest) = digest {
+                return Ok(digest);
+            }
+        }
+
+        Err(CommandError::MissingChecksum(asset_name.to_string()))
+    }
+
+    /// Replaces click with the binary, unless its digest is not the checksum published with it.
+    fn replace_verified(
+        binary: &[u8],
+        checksum: &str,
+        asset_name: String,
+    ) -> Result<(), CommandError> {
+        if Integrity::hex_digest("sha256", binary).as_deref() != Some(checksum) {
+            return Err(CommandError::IntegrityMismatch(asset_name));
+        }
+
+        Self::replace_executable(binary).map_err(CommandError::FailedToReplaceExecutable)
+    }
+
+    /// The new binary is written next to the running one and then renamed over it, so that click is never left half
+    /// written. It is given the permissions of the binary it replaces.
+    fn replace_executable(binary: &[u8]) -> Result<(), io::Error> {
+        let executable = env::current_exe()?.canonicalize()?;
+        let temporary = executable.with_file_name(format!(".click.{}.tmp", process::id()));
+        fs::write(&temporary, binary)?;
+
+        let replaced = fs::metadata(&executable)
+            .and_then(|metadata| fs::set_permissions(&temporary, metadata.permissions()))
+            .and_then(|_| Self::swap(&temporary, &executable));
+
+        if replaced.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        replaced
+    }
+
+    #[cfg(not(windows))]
+    fn swap(binary: &Path, executable: &Path) -> Result<(), io::Error> {
+        fs::rename(binary, executable)
+    }
+
+    /// Windows does not let a running executable be replaced, but does let it be renamed out of the way. The old one
+    /// is removed by the next update, as it is still running now.
+    #[cfg(windows)]
+    fn swap(binary: &Path, executable: &Path) -> Result<(), io::Error> {
+        let old = executable.with_extension("old.exe");
+        let _ = fs::remove_file(&old);
+
+        fs::rename(executable, &old)?;
+        fs::rename(binary, executable).inspect_err(|_| {
+            let _ = fs::rename(&old, executable);
+        })
+    }
+}
+
+#[async_trait]
+impl CommandHandler for SelfUpdateHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--check") => self.check = true,
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The binary is only replaced once it matches the checksum published with it.
+    async fn execute(&self) -> Result<(), CommandError> {
+        let client = Config::client()?;
+        let release =
+            HTTPRequest::latest_release(client.clone(), &Config::get()?.update_endpoint).await?;
+
+        let current = env!("CARGO_PKG_VERSION");
+        let latest = release.tag_name.trim_start_matches('v').to_string();
+        let mut result = SelfUpdateResult {
+            current: current.to_string(),
+            latest: latest.to_string(),
+            updated: false,
+        };
+
+        if !Versions::is_newer(&latest, current) {
+            Output::message(format!("click {current} is the latest version"));
+            return Output::json(&result);
+        }
+
+        if self.check {
+            Output::message(format!(
+                "click {latest} is available, {current} is installed. Run `click self-update` to update"
+            ));
+            return Output::json(&result);
+        }
+
+        let asset_name = Self::asset_name();
+        let Some(asset) = release.assets.iter().find(|asset| asset.name == asset_name) else {
+            return Err(CommandError::NoReleaseBinary(format!(
+                "{}/{}",
+                consts::OS,
+                consts::ARCH
+            )));
+        };
+        let checksum = Self::checksum(client.clone(), &release, &asset_name).await?;
+
+        Output::message(format!("Downloading click {latest}.."));
+        let binary = HTTPRequest::get_bytes(client, asset.browser_download_url.clone()).await?;
+        Self::replace_verified(&binary, &checksum, asset_name)?;
+        result.updated = true;
+
+        Output::message(format!("Updated click from {current} to {latest}"));
+        Output::json(&result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use crate::types::ReleaseAsset;
+
+    use super::*;
+
+    const DIGEST: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    /// Serves the assets of a release from a local server, one request per connection.
+    fn serve_release(assets: &[(&str, &str)]) -> Release {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let files: Vec<(String, String)> = assets
+            .iter()
+            .map(|(name, body)| (format!("/{name}"), body.to_string()))
+            .collect();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 4096];
+                let read = stream.read(&mut request).unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..read]);
+                let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+                let response = match files.iter().find(|(name, _)| name == path) {
+                    Some((_, body)) => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    ),
+                    None => {
+                        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_string()
+                    }
+                };
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        Release {
+            tag_name: "v1.0.0".to_string(),
+            assets: assets
+                .iter()
+                .map(|(name, _)| ReleaseAsset {
+                    name: name.to_string(),
+                    browser_download_url: format!("{url}/{name}"),
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn checksum_is_read_from_the_checksums_of_the_release() {
+        let checksums = format!(
+            "{}  click-aarch64-macos\n{}  *click-x86_64-linux\n",
+            "0".repeat(64),
+            DIGEST.to_uppercase()
+        );
+        let release = serve_release(&[
+            ("click-x86_64-linux", "test"),
+            (CHECKSUMS_ASSET, &checksums),
+        ]);
+
+        let checksum =
+            SelfUpdateHandler::checksum(reqwest::Client::new(), &release, "click-x86_64-linux")
+                .await;
+        assert_eq!(checksum.unwrap(), DIGEST);
+    }
+
+    #[tokio::test]
+    async fn checksum_is_read_from_the_checksum_of_the_binary() {
+        let named = format!("{DIGEST}  click-x86_64-linux\n");
+        let release = serve_release(&[("click-x86_64-linux.sha256", &named)]);
+        let checksum =
+            SelfUpdateHandler::checksum(reqwest::Client::new(), &release, "click-x86_64-linux")
+                .await;
+        assert_eq!(checksum.unwrap(), DIGEST);
+
+        // A checksum file of the binary alone can leave its name out
+        let release = serve_release(&[("click-x86_64-linux.sha256", DIGEST)]);
+        let checksum =
+            SelfUpdateHandler::checksum(reqwest::Client::new(), &release, "click-x86_64-linux")
+                .await;
+        assert_eq!(checksum.unwrap(), DIGEST);
+    }
+
+    #[tokio::test]
+    async fn binary_without_a_checksum_is_not_trusted() {
+        let checksums = format!("{DIGEST}  click-aarch64-macos\n");
+        let release = serve_release(&[
+            ("click-x86_64-linux", "test"),
+            (CHECKSUMS_ASSET, &checksums),
+        ]);
+
+        let checksum =
+            SelfUpdateHandler::checksum(reqwest::Client::new(), &release, "click-x86_64-linux")
+                .await;
+        assert!(
+            matches!(checksum, Err(CommandError::MissingChecksum(name)) if name == "click-x86_64-linux")
+        );
+
+        let release = serve_release(&[("click-x86_64-linux", "test")]);
+        let checksum =
+            SelfUpdateHandler::checksum(reqwest::Client::new(), &release, "click-x86_64-linux")
+                .await;
+        assert!(matches!(checksum, Err(CommandError::MissingChecksum(_))));
+    }
+
+    #[test]
+    fn binary_is_not_replaced_when_its_digest_differs() {
+        let executable = env::current_exe().unwrap();
+        let before = fs::read(&executable).unwrap();
+
+        let replaced = SelfUpdateHandler::replace_verified(
+            b"tampered",
+            DIGEST,
+            "click-x86_64-linux".to_string(),
+        );
+        assert!(
+            matches!(replaced, Err(CommandError::IntegrityMismatch(name)) if name == "click-x86_64-linux")
+        );
+
+        assert_eq!(fs::read(&executable).unwrap(), before);
+        let temporaries = fs::read_dir(executable.parent().unwrap())
+            .unwrap()
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(".click."))
+            .count();
+        assert_eq!(temporaries, 0);
+    }
+}
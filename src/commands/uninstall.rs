@@ -0,0 +1,223 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    cache::Cache,
+    errors::{CommandError, ParseError},
+    installer::{Installer, MODULES_DIRECTORY},
+    manifest::{Manifest, DEPENDENCY_FIELDS},
+    output::Output,
+    shims::ShimPlatform,
+    types::{Lockfile, UninstallResult},
+    versions::Versions,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "uninstall",
+    aliases: &["remove", "rm", "un"],
+    arguments: "<package_name>...",
+    description: "Remove packages from node_modules and package.json, along with dependencies nothing else needs",
+    flags: &[Flag::new(&["-g", "--global"], "Uninstall global packages")],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct UninstallHandler {
+    package_names: Vec<String>,
+    global: bool, // Uninstall from the global directory rather than the working directory.
+}
+
+impl UninstallHandler {
+    /// Returns the name of every package that is still needed once the packages being uninstalled are gone.
+    /// These are the packages listed in package.json, those that were installed directly (nothing else depends on them),
+    /// and everything they depend on. `linked` maps each package name to the package@version linked into node_modules.
+    fn required_packages(
+        &self,
+        linked: &HashMap<String, String>,
+        manifest: Option<&Manifest>,
+    ) -> Result<HashSet<String>, CommandError> {
+        let mut dependencies = HashMap::new();
+        for (package_name, package) in linked.iter() {
+            let dependency_names = Cache::read_lock(package)?
+                .dependencies
+                .into_iter()
+                .map(|dependency| Versions::parse_raw_package_details(dependency).0)
+                .collect::<Vec<_>>();
+
+            dependencies.insert(package_name.to_string(), dependency_names);
+        }
+
+        // Dependencies of the packages being uninstalled count here, otherwise they would look like direct installs
+        let dependents = dependencies.values().flatten().collect::<HashSet<_>>();
+
+        let mut required = linked
+            .keys()
+            .filter(|package_name| !self.package_names.contains(package_name))
+            .filter(|package_name| {
+                !dependents.contains(package_name)
+                    || manifest.is_some_and(|manifest| Self::is_listed(manifest, package_name))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut visited = HashSet::new();
+        while let Some(package_name) = required.pop() {
+            if !visited.insert(package_name.to_string()) {
+                continue;
+            }
+
+            if let Some(dependency_names) = dependencies.get(&package_name) {
+                required.extend(dependency_names.iter().cloned());
+            }
+        }
+
+        Ok(visited)
+    }
+
+    fn is_listed(manifest: &Manifest, package_name: &String) -> bool {
+        DEPENDENCY_FIELDS.iter().any(|field| {
+            manifest
+                .dependencies(field)
+                .iter()
+                .any(|(name, _)| name == package_name)
+        })
+    }
+
+    /// A package with dependencies nested in it is a directory of links with the hoisted layout, see HoistedLayout.
+    /// The directory of its scope is removed along with its last package.
+    fn unlink(package_name: &String) -> Result<(), CommandError> {
+        let path = Path::new(MODULES_DIRECTORY).join(package_name);
+        let result = match path.is_symlink() {
+            true => symlink::remove_symlink_dir(&path),
+            false => fs::remove_dir_all(&path),
+        };
+        result.map_err(CommandError::FailedToRemoveLink)?;
+
+        if let Some(scope) = path.parent().filter(|_| package_name.starts_with('@')) {
+            // Fails while other packages of the scope are left, which is expected
+            let _ = fs::remove_dir(scope);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CommandHandler for UninstallHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--global") => self.global = true,
+                Arg::Positional(package_name) => self.package_names.push(package_name),
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        if self.package_names.is_empty() {
+            return Err(ParseError::MissingArgument(String::from("package name")));
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        if self.global {
+            Installer::use_global_prefix()?;
+        }
+
+        let mut manifest = Manifest::read()?;
+
+        let linked = Installer::linked_packages()?
+            .into_iter()
+            .map(|package| {
+                (
+                    Versions::parse_raw_package_details(package.to_string()).0,
+                    package,
+                )
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut listed = false;
+        for package_name in self.package_names.iter() {
+            let removed_from_manifest = manifest
+                .as_mut()
+                .is_some_and(|manifest| manifest.remove_dependency(package_name));
+
+            if !removed_from_manifest && !linked.contains_key(package_name) {
+                return Err(CommandError::PackageNotInstalled(package_name.to_string()));
+            }
+
+            listed |= removed_from_manifest;
+        }
+
+        let required = self.required_packages(&linked, manifest.as_ref())?;
+
+        let mut result = UninstallResult::default();
+        for (package_name, package) in linked.iter() {
+            if required.contains(package_name) {
+                if self.package_names.contains(package_name) {
+                    Output::message(format!("Kept '{package}' as other packages depend on it"));
+                    result.kept.push(package.to_string());
+                }
+                continue;
+            }
+
+            Self::unlink(package_name)?;
+
+            match self.package_names.contains(package_name) {
+                true => {
+                    Output::message(format!("Removed '{package}'"));
+                    result.removed.push(package.to_string());
+                }
+                false => result.orphaned.push(package.to_string()),
+            }
+        }
+
+        match result.orphaned.len() {
+            0 => {}
+            1 => Output::message("Removed 1 dependency that is no longer needed"),
+            orphaned => Output::message(format!(
+                "Removed {orphaned} dependencies that are no longer needed"
+            )),
+        }
+
+        Installer::link_bins(ShimPlatform::default())?;
+
+        if let (Some(manifest), true) = (manifest, listed) {
+            manifest.write()?;
+            Output::message("Updated package.json");
+        }
+
+        let mut lockfile = Lockfile::read()?;
+        let mut locked = false;
+        for package_name in self.package_names.iter() {
+            // Directories linked with link: are not in the cache, so they are only known from the lockfile
+            if let Some(path) = lockfile.links.get(package_name) {
+                Self::unlink(package_name)?;
+
+                let package = format!("{package_name}@link:{path}");
+                Output::message(format!("Removed '{package}'"));
+                result.removed.push(package);
+            }
+
+            locked |= lockfile.remove_requested(package_name);
+        }
+
+        if locked {
+            lockfile.prune();
+            lockfile.write()?;
+        }
+
+        Output::json(&result)
+    }
+}
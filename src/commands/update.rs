@@ -0,0 +1,531 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+use log::warn;
+
+use crate::{
+    config::Config,
+    errors::{CommandError, ParseError},
+    http::NetworkMode,
+    installer::Installer,
+    manifest::{DependencyKind, Manifest, PACKAGE_JSON_PATH},
+    output::Output,
+    prompt::{Key, Prompt},
+    source::Source,
+    transaction::Transaction,
+    types::{Lockfile, OutdatedPackage, UpdatedPackage},
+    util::TaskAllocator,
+    versions::{VersionRange, Versions, LATEST},
+    workspaces::Workspaces,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+    install::InstallHandler,
+    outdated::OutdatedHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "update",
+    aliases: &["upgrade", "up"],
+    arguments: "",
+    description: "Update the packages in package.json to the newest versions their ranges allow",
+    flags: &[
+        Flag::new(
+            &["--latest"],
+            "Update to the latest versions, bumping the ranges in package.json",
+        ),
+        Flag::new(
+            &["-i", "--interactive"],
+            "Pick the packages to update and whether to the wanted or the latest version",
+        ),
+        Flag::with_value(
+            &["-w", "--workspace", "--filter"],
+            "workspace",
+            "Update the dependencies of the workspaces this selects rather than the root",
+        ),
+    ],
+    passthrough: false,
+};
+
+const INTERACTIVE_HELP: &str =
+    "Space to select, left and right for the wanted or latest version (marked with *), a for all, enter to update, q to cancel";
+
+/// An outdated package in package.json that can be picked with --interactive.
+struct UpdateChoice {
+    manifest: usize, // The package.json it is listed in, of those being updated
+    kind: DependencyKind,
+    range: String,
+    package: OutdatedPackage,
+    latest: bool, // Update to the latest version rather than the newest one the range allows
+    selected: bool,
+}
+
+impl UpdateChoice {
+    fn version(&self) -> &String {
+        match self.latest {
+            true => &self.package.latest,
+            false => &self.package.wanted,
+        }
+    }
+
+    /// Whether the range allows a newer version than the current one, otherwise only the latest version is an update.
+    fn has_wanted(&self) -> bool {
+        self.package
+            .current
+            .as_ref()
+            .is_none_or(|current| Versions::is_newer(&self.package.wanted, current))
+    }
+}
+
+#[derive(Default)]
+pub struct UpdateHandler {
+    latest: bool, // Ignore the ranges in package.json and update to the latest version of every package.
+    interactive: bool, // Pick the packages to update from the outdated ones, set with --interactive.
+    workspaces: Vec<String>, // Update the dependencies of the workspaces these select rather than the root, set with -w and --filter.
+}
+
+// A package.json whose dependencies are updated, and the path it is written back to
+type UpdatedManifest = (PathBuf, Manifest);
+
+impl UpdateHandler {
+    /// The package.json of each workspace selected with `-w` or `--filter`, or the root package.json if none were.
+    fn manifests(&self) -> Result<Vec<UpdatedManifest>, CommandError> {
+        if self.workspaces.is_empty() {
+            let manifest = Manifest::read()?.ok_or(CommandError::NoManifest)?;
+            return Ok(vec![(PathBuf::from(PACKAGE_JSON_PATH), manifest)]);
+        }
+
+        Ok(
+            Workspaces::filter(Workspaces::discover()?, &self.workspaces)?
+                .into_iter()
+                .map(|workspace| (workspace.path.join(PACKAGE_JSON_PATH), workspace.manifest))
+                .collect(),
+        )
+    }
+
+    /// Returns the highest version of a package the registry has that matches the range.
+    pub async fn newest_version(
+        client: reqwest::Client,
+        package_name: &str,
+        semantic_version: Option<&VersionRange>,
+    ) -> Result<String, CommandError> {
+        let full_version = Versions::resolve_full_version(semantic_version);
+        let version_data = Installer::get_version_data(
+            client,
+            package_name,
+            full_version.as_ref(),
+            semantic_version,
+            NetworkMode::Online,
+        )
+        .await?;
+
+        Ok(version_data.version)
+    }
+
+    /// Locks the spec of a package in package.json to a new version. With `bump`, its range in the given field of
+    /// package.json is bumped to the version. Returns None if it is already locked to it.
+    fn lock(
+        lockfile: &mut Lockfile,
+        package_name: String,
+        range: &str,
+        semantic_version: Option<&VersionRange>,
+        version: String,
+        bump: Option<(&mut Manifest, &DependencyKind)>,
+    ) -> Result<Option<UpdatedPackage>, CommandError> {
+        let mut spec = Lockfile::spec(&package_name, semantic_version);
+
+        let locked_version = lockfile.locked_version(&spec);
+        if locked_version.as_ref() == Some(&version) {
+            return Ok(None);
+        }
+
+        let from = locked_version.unwrap_or(String::from("nothing"));
+        Output::message(format!(
+            "Updating '{package_name}' from {from} to {version}"
+        ));
+
+        // The new version might not match the current range, so the range is bumped to it
+        if let Some((manifest, kind)) = bump.filter(|_| range != LATEST) {
+            let range = format!("^{version}");
+            lockfile.requested.remove(&spec);
+            let semantic_version = Versions::parse_semantic_version(&range)
+                .map_err(CommandError::InvalidPackageSpec)?;
+            spec = Lockfile::spec(&package_name, Some(&semantic_version));
+
+            manifest.set_dependency(kind, &package_name, range);
+        }
+
+        lockfile
+            .requested
+            .insert(spec, Versions::stringify(&package_name, &version));
+
+        Ok(Some(UpdatedPackage {
+            name: package_name,
+            from,
+            to: version,
+        }))
+    }
+
+    /// Looks up every outdated package in the package.json files the same way `click outdated` does.
+    async fn outdated(
+        &self,
+        client: reqwest::Client,
+        manifests: &[UpdatedManifest],
+        lockfile: Arc<Lockfile>,
+    ) -> Result<Vec<UpdateChoice>, CommandError> {
+        let mut handles = Vec::new();
+        let kinds = [
+            DependencyKind::Production,
+            DependencyKind::Development,
+            DependencyKind::Optional,
+        ];
+        for (index, (_, manifest)) in manifests.iter().enumerate() {
+            for kind in kinds.iter() {
+                for (package_name, range) in manifest.dependencies(kind.field()) {
+                    if Source::from_spec(&range).is_some() {
+                        continue;
+                    }
+
+                    let client = client.clone();
+                    let lockfile = lockfile.clone();
+                    let kind = kind.clone();
+                    handles.push(TaskAllocator::add_task(async move {
+                        let package = OutdatedHandler::check(
+                            client,
+                            &lockfile,
+                            None,
+                            String::new(),
+                            package_name,
+                            range.to_string(),
+                        )
+                        .await?;
+                        Ok::<_, CommandError>(package.map(|package| (index, kind, range, package)))
+                    }));
+                }
+            }
+        }
+
+        let mut choices = Vec::new();
+        for handle in handles {
+            let Some((manifest, kind, range, package)) =
+                handle.await.expect("Failed to join outdated task")?
+            else {
+                continue;
+            };
+
+            let mut choice = UpdateChoice {
+                manifest,
+                kind,
+                range,
+                package,
+                latest: self.latest,
+                selected: false,
+            };
+            choice.latest |= !choice.has_wanted();
+            choices.push(choice);
+        }
+
+        Ok(choices)
+    }
+
+    /// The outdated packages in columns, each with the marker of the row given.
+    fn rows(
+        choices: &[UpdateChoice],
+        marker: impl Fn(usize, &UpdateChoice) -> String,
+    ) -> Vec<String> {
+        let cells = choices
+            .iter()
+            .enumerate()
+            .map(|(index, choice)| {
+                let target = |version: &String, latest: bool| match choice.latest == latest {
+                    true => format!("*{version}"),
+                    false => version.to_string(),
+                };
+
+                [
+                    marker(index, choice),
+                    choice.package.name.to_string(),
+                    choice
+                        .package
+                        .current
+                        .clone()
+                        .unwrap_or(String::from("missing")),
+                    match choice.has_wanted() {
+                        true => target(&choice.package.wanted, false),
+                        false => choice.package.wanted.to_string(),
+                    },
+                    target(&choice.package.latest, true),
+                    choice.kind.field().to_string(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let rows = cells
+            .iter()
+            .map(|row| row.each_ref().map(String::as_str))
+            .collect::<Vec<_>>();
+
+        Output::columns(
+            ["", "Package", "Current", "Wanted", "Latest", "Field"],
+            &rows,
+        )
+    }
+
+    /// Lets the packages to update be picked with the arrow keys, returning false if it was cancelled. Without a
+    /// terminal to read keys from, the numbers of the packages are asked for instead.
+    fn choose(choices: &mut [UpdateChoice]) -> Result<bool, CommandError> {
+        let Some(_raw_mode) = Prompt::raw_mode() else {
+            return Self::choose_by_number(choices);
+        };
+
+        let mut cursor = 0;
+        let mut drawn_lines = 0;
+        loop {
+            let mut lines = vec![INTERACTIVE_HELP.to_string()];
+            lines.extend(Self::rows(choices, |index, choice| {
+                let pointer = if index == cursor { '>' } else { ' ' };
+                let checkbox = if choice.selected { 'x' } else { ' ' };
+                format!("{pointer} [{checkbox}]")
+            }));
+
+            // Raw mode does not return the cursor to the start of the line on a newline
+            let erase = match drawn_lines {
+                0 => String::new(),
+                lines => format!("\x1b[{lines}A\r\x1b[J"),
+            };
+            Prompt::write(&format!("{erase}{}\r\n", lines.join("\r\n")))?;
+            drawn_lines = lines.len();
+
+            let choice = &mut choices[cursor];
+            match Prompt::read_key()? {
+                Key::Up => cursor = cursor.checked_sub(1).unwrap_or(choices.len() - 1),
+                Key::Down => cursor = (cursor + 1) % choices.len(),
+                Key::Space => choice.selected = !choice.selected,
+                Key::Left if choice.has_wanted() => {
+                    choice.latest = false;
+                    choice.selected = true;
+                }
+                Key::Right => {
+                    choice.latest = true;
+                    choice.selected = true;
+                }
+                Key::Char('a') => {
+                    let selected = !choices.iter().all(|choice| choice.selected);
+                    choices
+                        .iter_mut()
+                        .for_each(|choice| choice.selected = selected);
+                }
+                Key::Enter => return Ok(true),
+                Key::Cancel => return Ok(false),
+                _ => {}
+            }
+        }
+    }
+
+    fn choose_by_number(choices: &mut [UpdateChoice]) -> Result<bool, CommandError> {
+        let lines = Self::rows(choices, |index, _| (index + 1).to_string());
+        Prompt::write(&format!("{}\n", lines.join("\n")))?;
+
+        let answer = Prompt::ask("Numbers of the packages to update, separated by spaces", "")?;
+        for number in answer.split_whitespace() {
+            let choice = number
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| choices.get_mut(number.checked_sub(1)?));
+            match choice {
+                Some(choice) => choice.selected = true,
+                None => warn!("'{number}' is not one of the packages listed"),
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Installs again with the lockfile that has the updated versions locked, see execute(). `changed` are the
+    /// package.json files with ranges bumped to the new versions.
+    async fn install(
+        lockfile: Lockfile,
+        changed: Vec<UpdatedManifest>,
+        updated: Vec<UpdatedPackage>,
+    ) -> Result<(), CommandError> {
+        if updated.is_empty() {
+            Output::message("Everything is up to date");
+            return Output::json(&updated);
+        }
+
+        // The transaction only puts back the root package.json, so those of workspaces are kept here
+        let transaction = Transaction::begin()?;
+        let originals = changed
+            .iter()
+            .map(|(path, _)| Ok((path, fs::read(path)?)))
+            .collect::<Result<Vec<_>, std::io::Error>>()
+            .map_err(CommandError::FailedToReadFile)?;
+
+        lockfile.write()?;
+        for (path, manifest) in changed.iter() {
+            manifest.write_to(path)?;
+        }
+
+        if let Err(err) = InstallHandler::nested().execute().await {
+            InstallHandler::rollback(transaction);
+            for (path, contents) in originals {
+                fs::write(path, contents).map_err(CommandError::FailedToWriteFile)?;
+            }
+            return Err(err);
+        }
+
+        // The versions that were updated from are no longer needed by anything
+        let mut lockfile = Lockfile::read()?;
+        lockfile.prune();
+        lockfile.write()?;
+
+        Output::message(format!("Updated {} package(s)", updated.len()));
+        Output::json(&updated)
+    }
+
+    /// Updates the packages picked with --interactive.
+    async fn interactive(
+        &self,
+        client: reqwest::Client,
+        mut manifests: Vec<UpdatedManifest>,
+        lockfile: Lockfile,
+    ) -> Result<(), CommandError> {
+        let lockfile = Arc::new(lockfile);
+        let mut choices = self
+            .outdated(client.clone(), &manifests, lockfile.clone())
+            .await?;
+        choices.sort_by(|a, b| a.package.name.cmp(&b.package.name));
+        let mut lockfile = Arc::unwrap_or_clone(lockfile);
+
+        if choices.is_empty() {
+            return Self::install(lockfile, Vec::new(), Vec::new()).await;
+        }
+
+        let chosen = Self::choose(&mut choices)?;
+        if !chosen || choices.iter().all(|choice| !choice.selected) {
+            Output::message("Nothing was updated");
+            return Output::json(&Vec::<UpdatedPackage>::new());
+        }
+
+        let mut updated = Vec::new();
+        let mut changed = vec![false; manifests.len()];
+        for choice in choices.into_iter().filter(|choice| choice.selected) {
+            let semantic_version = Installer::parse_range(
+                client.clone(),
+                &choice.package.name,
+                &choice.range,
+                NetworkMode::Online,
+            )
+            .await?;
+
+            // Only the latest version can be outside of the range
+            let bump = choice.latest && choice.package.latest != choice.package.wanted;
+            changed[choice.manifest] |= bump && choice.range != LATEST;
+
+            let version = choice.version().to_string();
+            updated.extend(Self::lock(
+                &mut lockfile,
+                choice.package.name,
+                &choice.range,
+                semantic_version.as_ref(),
+                version,
+                bump.then_some((&mut manifests[choice.manifest].1, &choice.kind)),
+            )?);
+        }
+
+        Self::install(lockfile, Self::changed(manifests, changed), updated).await
+    }
+
+    fn changed(manifests: Vec<UpdatedManifest>, changed: Vec<bool>) -> Vec<UpdatedManifest> {
+        manifests
+            .into_iter()
+            .zip(changed)
+            .filter_map(|(manifest, changed)| changed.then_some(manifest))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl CommandHandler for UpdateHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--latest") => self.latest = true,
+                Arg::Flag("--interactive") => self.interactive = true,
+                Arg::Flag("--workspace") => self.workspaces.push(args.value("--workspace")?),
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every package in package.json that has a newer version is locked to it, then everything is installed again,
+    /// which downloads the locked versions as they are not in the cache yet. Only the packages in package.json are updated,
+    /// their dependencies are resolved the same way as any other install.
+    async fn execute(&self) -> Result<(), CommandError> {
+        // The install afterwards uses the same config, as it is only loaded once
+        Config::load()?;
+
+        let mut manifests = self.manifests()?;
+        let mut lockfile = Lockfile::read()?;
+        let client = Config::client()?;
+
+        if self.interactive {
+            return self.interactive(client, manifests, lockfile).await;
+        }
+
+        let mut updated = Vec::new();
+        let mut changed = vec![false; manifests.len()];
+        let kinds = [
+            DependencyKind::Production,
+            DependencyKind::Development,
+            DependencyKind::Optional,
+        ];
+        for (index, (_, manifest)) in manifests.iter_mut().enumerate() {
+            for kind in kinds.iter() {
+                for (package_name, range) in manifest.dependencies(kind.field()) {
+                    match Source::from_spec(&range) {
+                        Some(Source::Workspace(_)) => continue, // Linked rather than installed
+                        Some(_) => continue, // Updated by installing it again, which fetches it again
+                        None => {}
+                    }
+
+                    let semantic_version = Installer::parse_range(
+                        client.clone(),
+                        &package_name,
+                        &range,
+                        NetworkMode::Online,
+                    )
+                    .await?;
+
+                    let newest_semantic_version = match self.latest {
+                        true => None,
+                        false => semantic_version.as_ref(),
+                    };
+                    let version = Self::newest_version(
+                        client.clone(),
+                        &package_name,
+                        newest_semantic_version,
+                    )
+                    .await?;
+
+                    if let Some(package) = Self::lock(
+                        &mut lockfile,
+                        package_name,
+                        &range,
+                        semantic_version.as_ref(),
+                        version,
+                        self.latest.then_some((&mut *manifest, kind)),
+                    )? {
+                        changed[index] |= self.latest && range != LATEST;
+                        updated.push(package);
+                    }
+                }
+            }
+        }
+
+        Self::install(lockfile, Self::changed(manifests, changed), updated).await
+    }
+}
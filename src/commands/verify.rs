@@ -0,0 +1,72 @@
+use async_trait::async_trait;
+
+use crate::{
+    cache::Cache,
+    errors::{CommandError, ParseError},
+    installer::Installer,
+    output::Output,
+    types::VerifyResult,
+};
+
+use super::{
+    arguments::{Arguments, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "verify",
+    aliases: &[],
+    arguments: "",
+    description: "Check that installed packages have not changed since they were installed",
+    flags: &[],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct VerifyHandler;
+
+#[async_trait]
+impl CommandHandler for VerifyHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        if let Some(arg) = args.next()? {
+            return Err(arg.unexpected());
+        }
+
+        Ok(())
+    }
+
+    /// Recomputes the digest of every installed package and compares it with the one recorded in its lockfile at install time.
+    async fn execute(&self) -> Result<(), CommandError> {
+        let mut result = VerifyResult::default();
+        let mut drifted = Vec::new();
+
+        for package in Installer::linked_packages()? {
+            let recorded = match Cache::read_lock(&package)?.content_integrity {
+                Some(recorded) => recorded,
+                None => {
+                    Output::message(format!(
+                        "Skipped '{package}' (installed before content integrity was recorded)"
+                    ));
+                    result.skipped.push(package);
+                    continue;
+                }
+            };
+
+            if Cache::content_integrity(&package)? != recorded {
+                Output::message(format!("'{package}' has changed since it was installed"));
+                drifted.push(package);
+                continue;
+            }
+
+            result.verified.push(package);
+        }
+
+        if !drifted.is_empty() {
+            return Err(CommandError::DriftedPackages(drifted));
+        }
+
+        Output::message(format!("{} package(s) verified", result.verified.len()));
+
+        Output::json(&result)
+    }
+}
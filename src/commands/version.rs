@@ -0,0 +1,159 @@
+use std::{
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use async_trait::async_trait;
+use semver::{Prerelease, Version};
+
+use crate::{
+    errors::{CommandError, ParseError},
+    manifest::{Manifest, OrderedJson, PACKAGE_JSON_PATH},
+    output::Output,
+    types::VersionBump,
+    versions::VersionIncrement,
+};
+
+use super::{
+    arguments::{Arg, Arguments, Flag, Usage},
+    command_handler::CommandHandler,
+    run::RunScriptHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "version",
+    aliases: &[],
+    arguments: "<major|minor|patch|premajor|preminor|prepatch|prerelease|version>",
+    description:
+        "Bump the version in package.json, running the preversion, version and postversion scripts, then commit and tag it with git",
+    flags: &[
+        Flag::new(
+            &["--no-git-tag-version"],
+            "Only change package.json, without a git commit and tag",
+        ),
+        Flag::with_value(
+            &["--preid"],
+            "identifier",
+            "The prerelease identifier of a pre increment, such as beta for 1.0.1-beta.0",
+        ),
+        Flag::with_value(
+            &["-m", "--message"],
+            "message",
+            "The message of the commit and tag, with %s replaced by the version. Defaults to the version",
+        ),
+    ],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct VersionHandler {
+    increment: Option<VersionIncrement>,
+    preid: Option<String>, // The first identifier of a prerelease, set with --preid.
+    message: Option<String>, // The message of the commit and tag, set with --message.
+    no_git_tag_version: bool, // Leave git alone, set with --no-git-tag-version.
+}
+
+impl VersionHandler {
+    /// Runs git in the working directory, returning what it printed.
+    fn git(args: &[&str]) -> Result<String, CommandError> {
+        let output = Command::new("git")
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(CommandError::FailedToRunGit)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let detail = stderr.lines().last().unwrap_or("it exited with an error");
+            return Err(CommandError::GitCommandFailed(
+                args.first().unwrap_or(&"").to_string(),
+                detail.trim().to_string(),
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+#[async_trait]
+impl CommandHandler for VersionHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Flag("--no-git-tag-version") => self.no_git_tag_version = true,
+                Arg::Flag("--preid") => {
+                    let preid = args.value("--preid")?;
+                    if Prerelease::new(&preid).is_err() {
+                        return Err(ParseError::InvalidArgument(String::from("--preid"), preid));
+                    }
+                    self.preid = Some(preid);
+                }
+                Arg::Flag("--message") => self.message = Some(args.value("--message")?),
+                Arg::Positional(increment) if self.increment.is_none() => {
+                    self.increment = Some(VersionIncrement::parse(&increment).ok_or(
+                        ParseError::InvalidArgument(String::from("version"), increment),
+                    )?);
+                }
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        if self.increment.is_none() {
+            return Err(ParseError::MissingArgument(String::from("version")));
+        }
+
+        Ok(())
+    }
+
+    /// The same steps as `npm version`: the working directory has to be clean, preversion runs before package.json is
+    /// changed and version after, so that it can add files to the commit, then the commit and tag are made and
+    /// postversion runs last.
+    async fn execute(&self) -> Result<(), CommandError> {
+        let Some(increment) = &self.increment else {
+            return Ok(());
+        };
+
+        let mut manifest = Manifest::read()?.ok_or(CommandError::NoManifest)?;
+        let from = manifest
+            .field("version")
+            .ok_or_else(|| CommandError::MissingManifestField(String::from("version")))?
+            .to_string();
+        let version = Version::from_str(&from).map_err(|_| CommandError::InvalidVersion)?;
+
+        let to = increment.apply(&version, self.preid.as_deref()).to_string();
+        if to == from {
+            return Err(CommandError::VersionUnchanged(to));
+        }
+
+        // Outside of a repository there is nothing to commit, the same as with --no-git-tag-version
+        let use_git = !self.no_git_tag_version
+            && Self::git(&["rev-parse", "--is-inside-work-tree"])
+                .is_ok_and(|inside| inside == "true");
+        if use_git && !Self::git(&["status", "--porcelain"])?.is_empty() {
+            return Err(CommandError::GitDirty);
+        }
+
+        RunScriptHandler::run_if_present(&manifest, "preversion", None)?;
+
+        manifest.set_field("version", OrderedJson::String(to.to_string()));
+        manifest.write()?;
+
+        RunScriptHandler::run_if_present(&manifest, "version", None)?;
+
+        let mut tag = None;
+        if use_git {
+            let message = self.message.as_deref().unwrap_or("%s").replace("%s", &to);
+            let version_tag = format!("v{to}");
+
+            Self::git(&["add", PACKAGE_JSON_PATH])?;
+            Self::git(&["commit", "-m", &message])?;
+            Self::git(&["tag", "-a", &version_tag, "-m", &message])?;
+            tag = Some(version_tag);
+        }
+
+        RunScriptHandler::run_if_present(&manifest, "postversion", None)?;
+
+        Output::message(format!("v{to}"));
+        Output::json(&VersionBump { from, to, tag })
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    str::FromStr,
+};
+
+use async_trait::async_trait;
+use semver::Version;
+
+use crate::{
+    errors::{CommandError, ParseError},
+    output::Output,
+    types::{DependencyLink, InstallReason, Lockfile},
+    versions::{Versions, EMPTY_VERSION, LATEST},
+};
+
+use super::{
+    arguments::{Arg, Arguments, Usage},
+    command_handler::CommandHandler,
+};
+
+pub const USAGE: Usage = Usage {
+    name: "why",
+    aliases: &["explain"],
+    arguments: "<package[@range]>",
+    description: "Print every chain of dependencies from package.json down to a locked package",
+    flags: &[],
+    passthrough: false,
+};
+
+#[derive(Default)]
+pub struct WhyHandler {
+    package_name: String,
+    range: Option<String>, // Only explain the locked versions this matches, every version if None.
+}
+
+impl WhyHandler {
+    /// The locked versions of the package the range matches, formatted as package@version.
+    fn targets(&self, lockfile: &Lockfile) -> Result<HashSet<String>, CommandError> {
+        let range = match &self.range {
+            Some(range) => Some(
+                Versions::parse_semantic_version(range)
+                    .map_err(CommandError::InvalidPackageSpec)?,
+            ),
+            None => None,
+        };
+
+        Ok(lockfile
+            .packages
+            .keys()
+            .filter(|package| {
+                let (package_name, version) =
+                    Versions::parse_raw_package_details(package.to_string());
+                let version = Version::from_str(&version).unwrap_or(EMPTY_VERSION);
+
+                package_name == self.package_name
+                    && range.as_ref().is_none_or(|range| range.matches(&version))
+            })
+            .cloned()
+            .collect())
+    }
+
+    /// Every package that depends on one of the targets, directly or through other packages, including the targets.
+    /// Only these are followed when looking for chains, so the rest of the graph is not walked.
+    fn leads_to_targets(
+        graph: &HashMap<String, Vec<(String, String)>>,
+        targets: &HashSet<String>,
+    ) -> HashSet<String> {
+        let mut dependents: HashMap<&String, Vec<&String>> = HashMap::new();
+        for (package, dependencies) in graph.iter() {
+            for (_, dependency) in dependencies {
+                dependents.entry(dependency).or_default().push(package);
+            }
+        }
+
+        let mut leads = targets.clone();
+        let mut queue = targets.iter().collect::<VecDeque<_>>();
+        while let Some(package) = queue.pop_front() {
+            for dependent in dependents.get(package).into_iter().flatten() {
+                if leads.insert(dependent.to_string()) {
+                    queue.push_back(dependent);
+                }
+            }
+        }
+
+        leads
+    }
+
+    /// Follows the dependencies of the last package of the chain, recording the chain whenever it reaches a target.
+    /// A package already in the chain is not followed again, so circular dependencies end.
+    fn walk(
+        graph: &HashMap<String, Vec<(String, String)>>,
+        leads: &HashSet<String>,
+        targets: &HashSet<String>,
+        chain: &mut Vec<(String, String)>,
+        chains: &mut HashMap<String, Vec<Vec<DependencyLink>>>,
+    ) {
+        let Some((_, package)) = chain.last().cloned() else {
+            return;
+        };
+
+        if targets.contains(&package) {
+            let links = chain
+                .iter()
+                .map(|(range, package)| {
+                    let (name, version) = Versions::parse_raw_package_details(package.to_string());
+                    DependencyLink {
+                        name,
+                        range: range.to_string(),
+                        version,
+                    }
+                })
+                .collect();
+            chains.entry(package).or_default().push(links);
+            return;
+        }
+
+        for (range, dependency) in graph.get(&package).into_iter().flatten() {
+            if !leads.contains(dependency) || chain.iter().any(|(_, package)| package == dependency)
+            {
+                continue;
+            }
+
+            chain.push((range.to_string(), dependency.to_string()));
+            Self::walk(graph, leads, targets, chain, chains);
+            chain.pop();
+        }
+    }
+
+    /// Such as `express@^4.18.0 (4.18.2) > body-parser@1.20.1 (1.20.1)`.
+    fn format_chain(chain: &[DependencyLink]) -> String {
+        chain
+            .iter()
+            .map(|link| format!("{}@{} ({})", link.name, link.range, link.version))
+            .collect::<Vec<_>>()
+            .join(" > ")
+    }
+}
+
+#[async_trait]
+impl CommandHandler for WhyHandler {
+    fn parse(&mut self, args: &mut Arguments) -> Result<(), ParseError> {
+        while let Some(arg) = args.next()? {
+            match arg {
+                Arg::Positional(package) if self.package_name.is_empty() => {
+                    let (package_name, range) = Versions::parse_raw_package_details(package);
+                    self.package_name = package_name;
+                    self.range = (range != LATEST).then_some(range);
+                }
+                arg => return Err(arg.unexpected()),
+            }
+        }
+
+        if self.package_name.is_empty() {
+            return Err(ParseError::MissingArgument(String::from("package")));
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self) -> Result<(), CommandError> {
+        let lockfile = Lockfile::read()?;
+
+        let targets = self.targets(&lockfile)?;
+        if targets.is_empty() {
+            return Err(CommandError::PackageNotInstalled(
+                self.package_name.to_string(),
+            ));
+        }
+
+        let graph = lockfile
+            .packages
+            .keys()
+            .map(|package| (package.to_string(), lockfile.direct_dependencies(package)))
+            .collect::<HashMap<_, _>>();
+        let leads = Self::leads_to_targets(&graph, &targets);
+
+        let mut chains = HashMap::new();
+        for (spec, package) in lockfile.requested.iter() {
+            if !leads.contains(package) {
+                continue;
+            }
+
+            let (_, range) = Versions::parse_raw_package_details(spec.to_string());
+            Self::walk(
+                &graph,
+                &leads,
+                &targets,
+                &mut vec![(range, package.to_string())],
+                &mut chains,
+            );
+        }
+
+        let mut reasons = targets
+            .into_iter()
+            .map(|package| InstallReason {
+                chains: chains.remove(&package).unwrap_or_default(),
+                package,
+            })
+            .collect::<Vec<_>>();
+        reasons.sort_by(|a, b| a.package.cmp(&b.package));
+
+        Output::json(&reasons)?;
+        if Output::is_json() {
+            return Ok(());
+        }
+
+        for reason in reasons.iter() {
+            println!("{}", reason.package);
+            if reason.chains.is_empty() {
+                println!("  nothing in package.json depends on it");
+            }
+            for chain in reason.chains.iter() {
+                println!("  {}", Self::format_chain(chain));
+            }
+        }
+
+        Ok(())
+    }
+}
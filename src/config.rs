@@ -0,0 +1,1052 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{
+    commands::arguments::Usage,
+    errors::CommandError,
+    http::{REGISTRY_URL, RELEASES_URL},
+    keychain::Keychain,
+    types::ConfigSetting,
+};
+
+const NPMRC_NAME: &str = ".npmrc";
+const CLICKRC_NAME: &str = ".clickrc";
+const SYSTEM_CLICKRC_PATH: &str = "/etc/clickrc"; // Shared by every user of the machine
+const AUTH_TOKEN_VARIABLE: &str = "CLICK_AUTH_TOKEN"; // Token for the default registry, such as one provided by CI
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+/// The value of each setting that nothing else sets, the lowest layer of the config.
+const DEFAULTS: &[(&str, &str)] = &[
+    ("registry", REGISTRY_URL),
+    ("fetch-retries", "2"),
+    ("fetch-retry-factor", "4"),
+    ("fetch-retry-mintimeout", "1000"),
+    ("fetch-retry-maxtimeout", "10000"),
+    ("engine-strict", "false"),
+    ("save", "true"),
+    ("save-exact", "false"),
+    ("strict-ssl", "true"),
+    ("update-endpoint", RELEASES_URL),
+    ("confirm-size", "50000000"),
+];
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+static LAYERS: OnceLock<ConfigLayers> = OnceLock::new();
+static REGISTRY_FLAG: OnceLock<String> = OnceLock::new();
+static COMMAND: OnceLock<&'static str> = OnceLock::new();
+
+/// A key set in a config file.
+struct ConfigEntry {
+    command: Option<String>, // The `[command]` section it is in, it only applies to that command
+    key: String,
+    value: String,
+    npmrc: bool, // Set in an `.npmrc`, where keys mean what they do to npm rather than flags of click
+    path: PathBuf, // The file it is set in
+}
+
+/// Every config file, read once, with the keys of each command section ahead of the rest of its file.
+/// The files are in order of precedence, the project `.clickrc` and `.npmrc`, then those of the user, then the
+/// system `/etc/clickrc`. Only environment variables and flags take precedence over them, and the built in defaults
+/// are used for what none of them set.
+struct ConfigLayers {
+    entries: Vec<ConfigEntry>,
+}
+
+impl ConfigLayers {
+    fn get() -> Result<&'static ConfigLayers, CommandError> {
+        if let Some(layers) = LAYERS.get() {
+            return Ok(layers);
+        }
+
+        let mut paths = vec![
+            (PathBuf::from(CLICKRC_NAME), false),
+            (PathBuf::from(NPMRC_NAME), true),
+        ];
+        paths.extend(Config::user_clickrc_path().map(|path| (path, false)));
+        paths.extend(Config::user_npmrc_path().map(|path| (path, true)));
+        paths.push((PathBuf::from(SYSTEM_CLICKRC_PATH), false));
+
+        let mut entries = Vec::new();
+        for (path, npmrc) in paths {
+            let (mut sections, global): (Vec<_>, Vec<_>) = Config::read_config_file(&path, npmrc)?
+                .into_iter()
+                .rev() // The last of the same key in a file is the one that is used
+                .partition(|entry| entry.command.is_some());
+
+            sections.extend(global);
+            entries.extend(sections);
+        }
+
+        Ok(LAYERS.get_or_init(|| ConfigLayers { entries }))
+    }
+
+    /// The keys of every file that apply to a command, highest precedence first.
+    fn entries(&self, command: Option<&str>) -> Vec<(String, String)> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.command.is_none() || entry.command.as_deref() == command)
+            .map(|entry| (entry.key.to_string(), entry.value.to_string()))
+            .collect()
+    }
+
+    /// The value of a setting for a command, from `CLICK_<KEY>` or `npm_config_<key>` such as `CLICK_SAVE_EXACT` for
+    /// `save-exact`, then the files, then the defaults.
+    fn value(&self, key: &str, command: Option<&str>) -> Option<String> {
+        let variable = key.replace('-', "_");
+        Config::env(&format!("CLICK_{}", variable.to_uppercase()))
+            .or_else(|| Config::env(&format!("npm_config_{variable}")))
+            .or_else(|| Config::env(&format!("NPM_CONFIG_{}", variable.to_uppercase())))
+            .or_else(|| self.file_value(key, command, true))
+            .or_else(|| {
+                DEFAULTS
+                    .iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, value)| value.to_string())
+            })
+    }
+
+    /// The value of a flag of a command, from `CLICK_<KEY>` then the `.clickrc` files. npm gives some keys another
+    /// meaning, such as `cache`, so `.npmrc` and `npm_config_<key>` are left out, other than `NODE_ENV=production`
+    /// setting `--production` the same as with npm.
+    fn flag_value(&self, key: &str, command: &str) -> Option<String> {
+        let node_env = (key == "production")
+            .then(|| Config::env("NODE_ENV").filter(|node_env| node_env == "production"))
+            .flatten()
+            .map(|_| String::from("true"));
+
+        Config::env(&format!("CLICK_{}", key.replace('-', "_").to_uppercase()))
+            .or(node_env)
+            .or_else(|| self.file_value(key, Some(command), false))
+    }
+
+    fn file_value(&self, key: &str, command: Option<&str>, npmrc: bool) -> Option<String> {
+        self.entries
+            .iter()
+            .filter(|entry| npmrc || !entry.npmrc)
+            .filter(|entry| entry.command.is_none() || entry.command.as_deref() == command)
+            .find(|entry| entry.key == key)
+            .map(|entry| entry.value.to_string())
+    }
+}
+
+/// Settings shared by every command, resolved once from the command line, environment variables, `.clickrc` and
+/// `.npmrc` files, see ConfigLayers.
+pub struct Config {
+    pub registry: String,                       // Without a trailing slash
+    mirrors: Vec<String>, // `mirrors`, registries tried in order when `registry` is unavailable, without trailing slashes
+    scoped_registries: HashMap<String, String>, // Registry of each scope configured with `@scope:registry=`, such as `@acme`
+    credentials: Vec<(String, String)>, // `Authorization` header of each registry, keyed by its URL without a scheme
+    pub retry_policy: RetryPolicy,
+    pub engine_strict: bool, // `engine-strict`, refuse versions whose `engines.node` the installed Node does not satisfy
+    pub save: bool, // `save`, save installed packages to package.json, false to only install them as with --no-save
+    pub save_exact: bool, // `save-exact`, save installed packages to package.json at their exact version rather than a caret range
+    proxies: Vec<reqwest::Proxy>, // `proxy` and `https-proxy`, or the HTTP_PROXY and HTTPS_PROXY environment variables
+    certificates: Vec<reqwest::Certificate>, // `cafile`, trusted along with the built in root certificates
+    strict_ssl: bool, // `strict-ssl`, verify the certificates of registries and proxies
+    pub update_endpoint: String, // `update-endpoint`, where `click self-update` finds the latest release
+    pub confirm_size: u64, // `confirm-size`, in bytes, `install --confirm` asks before downloading more than this
+}
+
+/// How failed registry requests are retried, configured with the same `.npmrc` keys as npm.
+/// The delay before each retry grows by `factor` from `min_timeout`, up to `max_timeout`.
+pub struct RetryPolicy {
+    pub retries: u32, // `fetch-retries`, the number of retries after the first attempt
+    pub factor: u32,  // `fetch-retry-factor`
+    pub min_timeout: Duration, // `fetch-retry-mintimeout`, in milliseconds
+    pub max_timeout: Duration, // `fetch-retry-maxtimeout`, in milliseconds
+}
+
+impl RetryPolicy {
+    /// Returns how long to wait before a retry, `attempt` being the number of attempts made so far.
+    /// Up to half of the delay is random, so that parallel requests that failed together are not retried together.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .min_timeout
+            .saturating_mul(self.factor.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_timeout);
+
+        // Good enough as a source of jitter without pulling in a random number generator
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.subsec_nanos());
+
+        delay / 2 + (delay / 2).mul_f64(nanos as f64 / 1_000_000_000.0)
+    }
+}
+
+impl Config {
+    /// Sets the registry given with `--registry`, which takes precedence over any other. Only the first one given is used.
+    pub fn use_registry(registry: String) {
+        let _ = REGISTRY_FLAG.set(registry);
+    }
+
+    /// Sets the command being run, so that the keys in its section of the config files apply.
+    pub fn use_command(command: &'static str) {
+        let _ = COMMAND.set(command);
+    }
+
+    /// The flags of a command that are set in the config, such as `linker=isolated` in the `[install]` section of
+    /// `.clickrc` or `CLICK_IGNORE_SCRIPTS=true`, as arguments to parse before those on the command line. Flags on the
+    /// command line come later, so they take precedence.
+    pub fn command_args(usage: &Usage) -> Result<Vec<String>, CommandError> {
+        let layers = ConfigLayers::get()?;
+
+        let mut args = Vec::new();
+        for flag in usage.flags {
+            let name = flag.long_name();
+            let Some(value) = layers.flag_value(name.trim_start_matches('-'), usage.name) else {
+                continue;
+            };
+
+            match (flag.value, value.parse::<bool>()) {
+                (Some(_), _) => args.push(format!("{name}={value}")),
+                (None, Ok(true)) => args.push(name.to_string()),
+                (None, Ok(false)) => {}
+                (None, Err(_)) => {
+                    return Err(CommandError::InvalidConfigValue(name.to_string(), value))
+                }
+            }
+        }
+
+        Ok(args)
+    }
+
+    /// Loads the config, `--registry` taking precedence over every layer of it, see ConfigLayers. Commands that talk
+    /// to the registry should call this before anything is requested so that a bad config is reported first, it does
+    /// nothing if the config has already been loaded.
+    pub fn load() -> Result<&'static Config, CommandError> {
+        if let Some(config) = CONFIG.get() {
+            return Ok(config);
+        }
+
+        let layers = ConfigLayers::get()?;
+        let command = COMMAND.get().copied();
+        let npmrc = layers.entries(command);
+        let value = |key: &str| layers.value(key, command);
+
+        let registry = REGISTRY_FLAG
+            .get()
+            .cloned()
+            .or_else(|| value("registry"))
+            .unwrap_or_else(|| REGISTRY_URL.to_string());
+
+        if reqwest::Url::parse(&registry).is_err() {
+            return Err(CommandError::InvalidRegistry(registry));
+        }
+
+        let mut scoped_registries = HashMap::new();
+        for (key, scoped_registry) in npmrc.iter() {
+            let Some(scope) = key.strip_suffix(":registry") else {
+                continue;
+            };
+
+            if !scope.starts_with('@') || reqwest::Url::parse(scoped_registry).is_err() {
+                return Err(CommandError::InvalidRegistry(scoped_registry.to_string()));
+            }
+
+            // Project keys come first, so a scope configured in both uses the project registry
+            scoped_registries
+                .entry(scope.to_string())
+                .or_insert_with(|| scoped_registry.trim_end_matches('/').to_string());
+        }
+
+        let registry = registry.trim_end_matches('/').to_string();
+        let keychain = value("keychain-service")
+            .filter(|service| !service.is_empty())
+            .map(|service| Keychain::system(&service));
+        let credentials = Self::credentials(&npmrc, &registry, keychain.as_ref())?;
+
+        // Every setting that is parsed has a default
+        let setting = |key: &str| {
+            let value = value(key).unwrap_or_default();
+            value
+                .parse::<u64>()
+                .map_err(|_| CommandError::InvalidConfigValue(key.to_string(), value))
+        };
+
+        let retry_policy = RetryPolicy {
+            retries: setting("fetch-retries")? as u32,
+            factor: setting("fetch-retry-factor")? as u32,
+            min_timeout: Duration::from_millis(setting("fetch-retry-mintimeout")?),
+            max_timeout: Duration::from_millis(setting("fetch-retry-maxtimeout")?),
+        };
+
+        let flag = |key: &str| {
+            let value = value(key).unwrap_or_default();
+            value
+                .parse::<bool>()
+                .map_err(|_| CommandError::InvalidConfigValue(key.to_string(), value))
+        };
+
+        let engine_strict = flag("engine-strict")?;
+        let save = flag("save")?;
+        let save_exact = flag("save-exact")?;
+        let strict_ssl = flag("strict-ssl")?;
+
+        // The same fallbacks as npm, HTTPS_PROXY is used for https registries and HTTP_PROXY for both
+        let from_env = |names: &[&str]| names.iter().find_map(|name| Self::env(name));
+        let https_proxy = value("https-proxy")
+            .or_else(|| from_env(&["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy"]));
+        let http_proxy = value("proxy").or_else(|| from_env(&["HTTP_PROXY", "http_proxy"]));
+        let no_proxy = value("noproxy").or_else(|| from_env(&["NO_PROXY", "no_proxy"]));
+
+        let proxies = Self::proxies(http_proxy, https_proxy, no_proxy)?;
+
+        let mut mirrors = Vec::new();
+        for mirror in value("mirrors")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+        {
+            if mirror.is_empty() {
+                continue;
+            }
+            if reqwest::Url::parse(mirror).is_err() {
+                return Err(CommandError::InvalidRegistry(mirror.to_string()));
+            }
+            mirrors.push(mirror.trim_end_matches('/').to_string());
+        }
+
+        let update_endpoint = value("update-endpoint").unwrap_or_default();
+        if reqwest::Url::parse(&update_endpoint).is_err() {
+            return Err(CommandError::InvalidConfigValue(
+                String::from("update-endpoint"),
+                update_endpoint,
+            ));
+        }
+
+        let certificates = match value("cafile") {
+            Some(cafile) => Self::read_cafile(&cafile)?,
+            None => Vec::new(),
+        };
+
+        let config = Config {
+            registry,
+            mirrors,
+            scoped_registries,
+            credentials,
+            retry_policy,
+            engine_strict,
+            save,
+            save_exact,
+            proxies,
+            certificates,
+            strict_ssl,
+            update_endpoint,
+            confirm_size: setting("confirm-size")?,
+        };
+
+        Ok(CONFIG.get_or_init(|| config))
+    }
+
+    /// Loads the config without a `--registry` flag if no command has loaded it yet.
+    pub fn get() -> Result<&'static Config, CommandError> {
+        Self::load()
+    }
+
+    /// Returns a client that goes through the configured proxies and trusts the configured certificates, which every
+    /// request should be made with.
+    pub fn client() -> Result<reqwest::Client, CommandError> {
+        Self::get()?
+            .client_builder()
+            .build()
+            .map_err(CommandError::FailedToCreateClient)
+    }
+
+    /// The builder of `client`, for a client that needs more settings such as a timeout.
+    pub fn client_builder(&self) -> reqwest::ClientBuilder {
+        // The proxy environment variables have already been read, so reqwest is kept from reading them again
+        let mut builder = reqwest::Client::builder()
+            .no_proxy()
+            .danger_accept_invalid_certs(!self.strict_ssl);
+
+        for proxy in self.proxies.iter() {
+            builder = builder.proxy(proxy.clone());
+        }
+        for certificate in self.certificates.iter() {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+
+        builder
+    }
+
+    /// Returns the registry a package is fetched from, which is the one configured for its scope if it has one.
+    pub fn registry_for(&self, package_name: &str) -> &str {
+        let scope = package_name
+            .split_once('/')
+            .map(|(scope, _)| scope)
+            .filter(|scope| scope.starts_with('@'));
+
+        scope
+            .and_then(|scope| self.scoped_registries.get(scope))
+            .unwrap_or(&self.registry)
+    }
+
+    /// Tarballs published to the public registry are fetched from the registry of the package instead, as mirrors serve
+    /// packuments that still point at the public registry, the same as npm does.
+    pub fn tarball_url(&self, package_name: &str, tarball: &str) -> String {
+        match tarball.strip_prefix(REGISTRY_URL) {
+            Some(path) if path.starts_with('/') => {
+                format!("{}{}", self.registry_for(package_name), path)
+            }
+            _ => tarball.to_string(),
+        }
+    }
+
+    /// Returns the URL followed by the same URL on each mirror, if it is under the registry configured with `registry`.
+    /// Scoped registries are not mirrored.
+    pub fn mirrored_urls(&self, url: &str) -> Vec<String> {
+        let mut urls = vec![url.to_string()];
+        if let Some(path) = url
+            .strip_prefix(&self.registry)
+            .filter(|path| path.is_empty() || path.starts_with(['/', '?']))
+        {
+            urls.extend(self.mirrors.iter().map(|mirror| format!("{mirror}{path}")));
+        }
+
+        urls
+    }
+
+    /// Returns the `Authorization` header for a request to a URL, if credentials are configured for a registry it is under.
+    /// The most specific registry is used when several match, such as one at a path of another.
+    pub fn authorization(&self, url: &str) -> Option<&str> {
+        let url = Self::registry_key(url);
+
+        self.credentials
+            .iter()
+            .filter(|(registry, _)| url.starts_with(registry.as_str()))
+            .max_by_key(|(registry, _)| registry.len())
+            .map(|(_, authorization)| authorization.as_str())
+    }
+
+    /// The key of the token of a registry in `.npmrc`, such as `//registry.npmjs.org/:_authToken`.
+    pub fn token_key(registry: &str) -> String {
+        format!(
+            "{}/:_authToken",
+            Self::registry_key(registry).trim_end_matches('/')
+        )
+    }
+
+    /// Sets a key in the user `.npmrc`, or removes it if the value is None, keeping the rest of the file as it is.
+    /// Returns the path of the file and whether the key was in it.
+    pub fn set_user_key(key: &str, value: Option<&str>) -> Result<(PathBuf, bool), CommandError> {
+        let path = Self::user_npmrc_path().ok_or(CommandError::NoHomeDirectory)?;
+        let found = Self::set_key(&path, None, key, value)?;
+        Ok((path, found))
+    }
+
+    /// The `.clickrc` that `click config` edits, the one of the user or of the project.
+    pub fn config_file_path(project: bool) -> Result<PathBuf, CommandError> {
+        match project {
+            true => Ok(PathBuf::from(CLICKRC_NAME)),
+            false => Self::user_clickrc_path().ok_or(CommandError::NoHomeDirectory),
+        }
+    }
+
+    /// Sets a key in a config file, in the section of a command if one is given, or removes it if the value is None.
+    /// The rest of the file is kept as it is, a new key goes at the end of its section. Returns whether the key was in it.
+    pub fn set_key(
+        path: &Path,
+        command: Option<&str>,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<bool, CommandError> {
+        let config_raw = match fs::read_to_string(path) {
+            Ok(config_raw) => config_raw,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                return Err(CommandError::FailedToReadConfig(
+                    path.display().to_string(),
+                    err,
+                ))
+            }
+        };
+
+        let mut found = false;
+        let mut lines = Vec::new();
+        let mut section = None;
+        let mut section_end = command.is_none().then_some(0); // Where a new key of the section goes
+        for line in config_raw.lines() {
+            if let Some(line_section) = Self::section(line) {
+                section = Some(line_section);
+                lines.push(line.to_string());
+                if section == command {
+                    section_end = Some(lines.len());
+                }
+                continue;
+            }
+
+            if section != command {
+                lines.push(line.to_string());
+                continue;
+            }
+
+            match line.split_once('=') {
+                Some((line_key, _)) if line_key.trim() == key => found = true,
+                _ => {
+                    lines.push(line.to_string());
+                    if !line.trim().is_empty() {
+                        section_end = Some(lines.len());
+                    }
+                }
+            }
+        }
+
+        if let Some(value) = value {
+            let line = format!("{key}={value}");
+            match (section_end, command) {
+                (Some(section_end), _) => lines.insert(section_end, line),
+                (None, Some(command)) => {
+                    if !lines.is_empty() {
+                        lines.push(String::new());
+                    }
+                    lines.push(format!("[{command}]"));
+                    lines.push(line);
+                }
+                (None, None) => lines.push(line),
+            }
+        }
+
+        let mut config_raw = lines.join("\n");
+        if !config_raw.is_empty() {
+            config_raw.push('\n');
+        }
+
+        fs::write(path, config_raw).map_err(CommandError::FailedToWriteFile)?;
+        Ok(found)
+    }
+
+    /// The value a key has for a command, or for every command if none is given, in the same order of precedence
+    /// the config is loaded with.
+    pub fn value(key: &str, command: Option<&str>) -> Result<Option<String>, CommandError> {
+        Ok(ConfigLayers::get()?.value(key, command))
+    }
+
+    /// Every key set in a config file, other than those a file with a higher precedence sets too, then the defaults
+    /// none of them set.
+    pub fn settings() -> Result<Vec<ConfigSetting>, CommandError> {
+        let layers = ConfigLayers::get()?;
+
+        let mut settings: Vec<ConfigSetting> = Vec::new();
+        for entry in layers.entries.iter() {
+            if settings
+                .iter()
+                .any(|setting| setting.key == entry.key && setting.command == entry.command)
+            {
+                continue;
+            }
+
+            settings.push(ConfigSetting {
+                key: entry.key.to_string(),
+                command: entry.command.clone(),
+                value: Self::display_value(&entry.key, &entry.value),
+                source: entry.path.display().to_string(),
+            });
+        }
+
+        for (key, value) in DEFAULTS {
+            if !settings
+                .iter()
+                .any(|setting| setting.key == *key && setting.command.is_none())
+            {
+                settings.push(ConfigSetting {
+                    key: key.to_string(),
+                    command: None,
+                    value: value.to_string(),
+                    source: String::from("default"),
+                });
+            }
+        }
+
+        Ok(settings)
+    }
+
+    /// Credentials are not printed, the same as with npm.
+    pub fn display_value(key: &str, value: &str) -> String {
+        match ["_authToken", "_auth", "_password"]
+            .iter()
+            .any(|protected| key.ends_with(protected))
+        {
+            true => String::from("(protected)"),
+            false => value.to_string(),
+        }
+    }
+
+    /// Credentials are keyed by registry as `//host/path/:_authToken=`, `//host/path/:_auth=` or
+    /// `//host/path/:username=` with `//host/path/:_password=`, the password being base64 encoded as npm expects.
+    /// Keys without a registry, and the `CLICK_AUTH_TOKEN` environment variable, are used for the default registry.
+    /// A token in the keychain, if `keychain-service` is set, takes precedence over all of them, see Keychain.
+    fn credentials(
+        npmrc: &[(String, String)],
+        registry: &str,
+        keychain: Option<&Keychain>,
+    ) -> Result<Vec<(String, String)>, CommandError> {
+        let default_registry = format!("{}/", Self::registry_key(registry));
+
+        let mut registries = vec![default_registry.clone()];
+        registries.extend(
+            npmrc
+                .iter()
+                .filter_map(|(key, _)| key.strip_prefix("//"))
+                .filter_map(|key| key.rsplit_once(':'))
+                .map(|(registry, _)| format!("//{}/", registry.trim_end_matches('/'))),
+        );
+        // Scoped registries can have a token in the keychain without any key of their own
+        if keychain.is_some() {
+            registries.extend(
+                npmrc
+                    .iter()
+                    .filter(|(key, _)| key.starts_with('@') && key.ends_with(":registry"))
+                    .map(|(_, url)| format!("{}/", Self::registry_key(url.trim_end_matches('/')))),
+            );
+        }
+        registries.sort();
+        registries.dedup();
+
+        let mut credentials = Vec::new();
+        for registry in registries {
+            let is_default = registry == default_registry;
+            let value = |name: &str| {
+                let unscoped = is_default.then(|| Self::find(npmrc, name)).flatten();
+                Self::find(npmrc, &format!("{registry}:{name}"))
+                    .or_else(|| {
+                        Self::find(npmrc, &format!("{}:{name}", registry.trim_end_matches('/')))
+                    })
+                    .or(unscoped)
+                    .filter(|value| !value.is_empty())
+            };
+
+            let stored = keychain.and_then(|keychain| keychain.token(&registry));
+            let token = match is_default {
+                true => stored
+                    .or_else(|| Self::env(AUTH_TOKEN_VARIABLE))
+                    .or_else(|| value("_authToken").map(str::to_string)),
+                false => stored.or_else(|| value("_authToken").map(str::to_string)),
+            };
+
+            let authorization = if let Some(token) = token {
+                format!("Bearer {token}")
+            } else if let Some(auth) = value("_auth") {
+                format!("Basic {auth}")
+            } else if let (Some(username), Some(password)) = (value("username"), value("_password"))
+            {
+                let password = STANDARD
+                    .decode(password)
+                    .ok()
+                    .and_then(|password| String::from_utf8(password).ok())
+                    .ok_or(CommandError::InvalidPassword(registry.to_string()))?;
+
+                format!(
+                    "Basic {}",
+                    STANDARD.encode(format!("{username}:{password}"))
+                )
+            } else {
+                continue;
+            };
+
+            credentials.push((registry, authorization));
+        }
+
+        Ok(credentials)
+    }
+
+    /// Proxies for http and https URLs, which hosts matching `no_proxy` are requested without, such as
+    /// `localhost,.internal.test` or `10.0.0.0/8`.
+    fn proxies(
+        http_proxy: Option<String>,
+        https_proxy: Option<String>,
+        no_proxy: Option<String>,
+    ) -> Result<Vec<reqwest::Proxy>, CommandError> {
+        let mut proxies = Vec::new();
+        if let Some(http_proxy) = http_proxy {
+            let proxy = reqwest::Proxy::http(&http_proxy)
+                .map_err(|_| CommandError::InvalidConfigValue(String::from("proxy"), http_proxy))?;
+            proxies.push(proxy);
+        }
+        if let Some(https_proxy) = https_proxy {
+            let proxy = reqwest::Proxy::https(&https_proxy).map_err(|_| {
+                CommandError::InvalidConfigValue(String::from("https-proxy"), https_proxy)
+            })?;
+            proxies.push(proxy);
+        }
+
+        Ok(proxies
+            .into_iter()
+            .map(|proxy| {
+                proxy.no_proxy(no_proxy.as_deref().and_then(reqwest::NoProxy::from_string))
+            })
+            .collect())
+    }
+
+    /// A cafile holds one or more PEM certificates, such as the root of a proxy that intercepts TLS.
+    fn read_cafile(cafile: &str) -> Result<Vec<reqwest::Certificate>, CommandError> {
+        let pem = fs::read_to_string(cafile)
+            .map_err(|err| CommandError::FailedToReadCaFile(cafile.to_string(), err))?;
+
+        let certificates = pem
+            .split_inclusive(PEM_END)
+            .filter(|block| block.contains(PEM_END))
+            .map(|block| reqwest::Certificate::from_pem(block.trim().as_bytes()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| CommandError::InvalidCaFile(cafile.to_string()))?;
+
+        if certificates.is_empty() {
+            return Err(CommandError::InvalidCaFile(cafile.to_string()));
+        }
+
+        Ok(certificates)
+    }
+
+    /// Registries are identified by their URL without the scheme, such as `//registry.npmjs.org/`.
+    fn registry_key(url: &str) -> String {
+        let url = url.split_once("://").map_or(url, |(_, url)| url);
+        format!("//{}", url.trim_start_matches('/'))
+    }
+
+    /// Replaces `${NAME}` with the value of the environment variable, so that tokens do not have to be written to the file.
+    /// Variables that are not set are replaced with nothing.
+    fn expand_variables(value: &str) -> String {
+        let mut expanded = String::new();
+        let mut rest = value;
+        while let Some((before, after)) = rest.split_once("${") {
+            let Some((name, after)) = after.split_once('}') else {
+                break;
+            };
+
+            expanded.push_str(before);
+            expanded.push_str(&Self::env(name).unwrap_or_default());
+            rest = after;
+        }
+
+        expanded.push_str(rest);
+        expanded
+    }
+
+    fn find<'a>(npmrc: &'a [(String, String)], key: &str) -> Option<&'a str> {
+        npmrc
+            .iter()
+            .find(|(npmrc_key, _)| npmrc_key == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn env(name: &str) -> Option<String> {
+        env::var(name).ok().filter(|value| !value.is_empty())
+    }
+
+    fn user_npmrc_path() -> Option<PathBuf> {
+        match Self::env("NPM_CONFIG_USERCONFIG") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(dirs::home_dir()?.join(NPMRC_NAME)),
+        }
+    }
+
+    fn user_clickrc_path() -> Option<PathBuf> {
+        match Self::env("CLICK_USERCONFIG") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(dirs::home_dir()?.join(CLICKRC_NAME)),
+        }
+    }
+
+    /// Config files are ini files, lines starting with `#` or `;` are comments. Keys after a `[command]` line only
+    /// apply to that command, npm does not use sections so `.npmrc` files are read the same way.
+    /// Returns nothing if the file does not exist.
+    fn read_config_file(path: &Path, npmrc: bool) -> Result<Vec<ConfigEntry>, CommandError> {
+        let config_raw = match fs::read_to_string(path) {
+            Ok(config_raw) => config_raw,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(CommandError::FailedToReadConfig(
+                    path.display().to_string(),
+                    err,
+                ))
+            }
+        };
+
+        let mut command = None;
+        let mut entries = Vec::new();
+        for line in config_raw.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+
+            if let Some(section) = Self::section(line) {
+                command = Some(section.to_string());
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+
+            entries.push(ConfigEntry {
+                command: command.clone(),
+                key: key.trim().to_string(),
+                value: Self::expand_variables(value),
+                npmrc,
+                path: path.to_path_buf(),
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// The command of a `[command]` line.
+    fn section(line: &str) -> Option<&str> {
+        line.trim()
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+            .map(str::trim)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::keychain::SecretStore;
+
+    /// Stands in for the keychain of the OS, with the tokens of each service by registry.
+    struct Stored(HashMap<(&'static str, &'static str), &'static str>);
+
+    impl SecretStore for Stored {
+        fn get(&self, service: &str, account: &str) -> Option<String> {
+            self.0
+                .get(&(service, account))
+                .map(|token| token.to_string())
+        }
+    }
+
+    fn npmrc(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn uses_tokens_in_the_keychain_before_the_config() {
+        let keychain = Keychain::new(
+            "click",
+            Stored(HashMap::from([
+                (("click", "//registry.test/"), "stored-token"),
+                (("click", "//scoped.test/npm/"), "scoped-token"),
+                (("other", "//other.test/"), "other-token"),
+            ])),
+        );
+        let npmrc = npmrc(&[
+            ("//registry.test/:_authToken", "config-token"),
+            ("@scope:registry", "https://scoped.test/npm/"),
+            ("//other.test/:_authToken", "other-config-token"),
+        ]);
+
+        let credentials = Config::credentials(&npmrc, "https://registry.test", Some(&keychain));
+        assert_eq!(
+            credentials.unwrap(),
+            [
+                (
+                    String::from("//other.test/"),
+                    String::from("Bearer other-config-token")
+                ),
+                (
+                    String::from("//registry.test/"),
+                    String::from("Bearer stored-token")
+                ),
+                (
+                    String::from("//scoped.test/npm/"),
+                    String::from("Bearer scoped-token")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_config_when_the_keychain_has_no_token() {
+        let keychain = Keychain::new(
+            "click",
+            Stored(HashMap::from([(("click", "//registry.test/"), "")])),
+        );
+        let npmrc = npmrc(&[("//registry.test/:_authToken", "config-token")]);
+
+        let with_keychain = Config::credentials(&npmrc, "https://registry.test", Some(&keychain));
+        let without_keychain = Config::credentials(&npmrc, "https://registry.test", None);
+        assert_eq!(
+            with_keychain.unwrap(),
+            [(
+                String::from("//registry.test/"),
+                String::from("Bearer config-token")
+            )]
+        );
+        assert_eq!(
+            without_keychain.unwrap(),
+            [(
+                String::from("//registry.test/"),
+                String::from("Bearer config-token")
+            )]
+        );
+
+        let empty = Keychain::new("click", Stored(HashMap::new()));
+        assert!(
+            Config::credentials(&[], "https://registry.test", Some(&empty))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    /// A self-signed certificate, as a CA that intercepts TLS would have.
+    fn certificate(name: &str) -> String {
+        use openssl::{
+            asn1::Asn1Time,
+            ec::{EcGroup, EcKey},
+            hash::MessageDigest,
+            nid::Nid,
+            pkey::PKey,
+            x509::{X509Builder, X509NameBuilder},
+        };
+
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_text("CN", name).unwrap();
+        let subject = subject.build();
+
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&subject).unwrap();
+        builder.set_issuer_name(&subject).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+
+        String::from_utf8(builder.build().to_pem().unwrap()).unwrap()
+    }
+
+    fn cafile(name: &str, contents: &str) -> String {
+        let path = env::temp_dir().join(format!("click-cafile-test-{name}-{}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path.display().to_string()
+    }
+
+    #[test]
+    fn reads_every_certificate_of_a_cafile() {
+        // Bundles such as those of the OS have a comment before each certificate
+        let bundle = format!(
+            "# Proxy Root\n{}\n# Internal Root\n{}",
+            certificate("Proxy Root"),
+            certificate("Internal Root")
+        );
+        let path = cafile("bundle", &bundle);
+        assert_eq!(Config::read_cafile(&path).unwrap().len(), 2);
+
+        let path = cafile("single", &certificate("Proxy Root"));
+        assert_eq!(Config::read_cafile(&path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rejects_a_cafile_without_valid_certificates() {
+        let garbage = cafile("garbage", "not a certificate\n");
+        assert!(
+            matches!(Config::read_cafile(&garbage), Err(CommandError::InvalidCaFile(path)) if path == garbage)
+        );
+
+        let corrupt = cafile(
+            "corrupt",
+            &format!(
+                "{}\n-----BEGIN CERTIFICATE-----\nbm90IGEgY2VydGlmaWNhdGU=\n{PEM_END}\n",
+                certificate("Proxy Root")
+            ),
+        );
+        assert!(matches!(
+            Config::read_cafile(&corrupt),
+            Err(CommandError::InvalidCaFile(_))
+        ));
+
+        let missing = format!("{garbage}.missing");
+        assert!(matches!(
+            Config::read_cafile(&missing),
+            Err(CommandError::FailedToReadCaFile(..))
+        ));
+    }
+
+    /// Answers every request on a port of its own with its name, returns its address.
+    fn answer_with(name: &'static str) -> String {
+        use std::{
+            io::{Read, Write},
+            net::TcpListener,
+            thread,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let _ = stream.read(&mut [0; 4096]);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{name}",
+                    name.len()
+                );
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn requests_hosts_in_no_proxy_directly() {
+        let registry = answer_with("registry");
+        let proxy = format!("http://{}", answer_with("proxy"));
+        let (_, port) = registry.split_once(':').unwrap();
+
+        let fetch = |url: String, no_proxy: Option<&str>| {
+            let proxies = Config::proxies(
+                Some(proxy.to_string()),
+                Some(proxy.to_string()),
+                no_proxy.map(str::to_string),
+            )
+            .unwrap();
+            async move {
+                let mut builder = reqwest::Client::builder().no_proxy();
+                for proxy in proxies {
+                    builder = builder.proxy(proxy);
+                }
+                let response = builder.build().unwrap().get(url).send().await.unwrap();
+                response.text().await.unwrap()
+            }
+        };
+
+        let url = format!("http://{registry}/");
+        assert_eq!(fetch(url.to_string(), None).await, "proxy");
+        assert_eq!(fetch(url.to_string(), Some("127.0.0.1")).await, "registry");
+        assert_eq!(
+            fetch(url.to_string(), Some("10.0.0.0/8,127.0.0.0/8")).await,
+            "registry"
+        );
+        assert_eq!(
+            fetch(url.to_string(), Some("registry.test,.internal.test")).await,
+            "proxy"
+        );
+
+        // Host names are matched whole, not as part of a longer name
+        let url = format!("http://localhost:{port}/");
+        assert_eq!(fetch(url.to_string(), Some("localhost")).await, "registry");
+        assert_eq!(fetch(url.to_string(), Some("host")).await, "proxy");
+    }
+}
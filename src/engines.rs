@@ -0,0 +1,38 @@
+use std::{process::Command, str::FromStr};
+
+use lazy_static::lazy_static;
+use semver::Version;
+
+use crate::{types::VersionData, versions::Versions};
+
+lazy_static! {
+    // Detected once as it runs Node, None if it is not on PATH
+    static ref NODE_VERSION: Option<Version> = Command::new("node")
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| {
+            let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            Version::from_str(version.trim_start_matches('v')).ok()
+        });
+}
+
+/// Versions can require a version of Node in the `node` field of `engines`, which is warned about when it is not the
+/// one installed, or refused with `engine-strict`.
+pub struct Engines;
+impl Engines {
+    pub fn node_version() -> Option<&'static Version> {
+        NODE_VERSION.as_ref()
+    }
+
+    /// Returns the range of Node a version requires if the installed Node is not in it. Nothing is checked if Node is
+    /// not installed, or if the range cannot be parsed, as old packages often have ranges npm never enforced.
+    pub fn unsatisfied_node_range(version_data: &VersionData) -> Option<String> {
+        let node_version = Self::node_version()?;
+        let required = version_data.node_engine()?;
+        let range = Versions::parse_semantic_version(required).ok()?;
+
+        (!range.matches(node_version)).then(|| required.to_string())
+    }
+}
@@ -1,14 +1,85 @@
 use std::io::Error;
+
+use serde::Serialize;
 use thiserror::Error;
 
+use crate::suggest::Suggestions;
+
+/// What kind of failure an error is, each kind exits click with its own code so that scripts can tell them apart.
+/// The codes are stable, new kinds get new codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorKind {
+    Other,      // Anything else, such as failing to write to node_modules
+    Usage, // Invalid arguments or config, or something missing that the command needs such as package.json
+    Network, // The registry could not be reached or sent something that could not be read
+    Resolution, // No version satisfies a range, or the versions picked conflict
+    Integrity, // A tarball or the lockfile does not match what it should be
+    Script, // A script failed, `click run` exits with the code of the script itself
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Other => 1,
+            Self::Usage => 2,
+            Self::Network => 3,
+            Self::Resolution => 4,
+            Self::Integrity => 5,
+            Self::Script => 6,
+        }
+    }
+}
+
+/// Any error click can fail with, which main prints and exits with.
+#[derive(Error, Debug)]
+pub enum ClickError {
+    #[error("Failed to parse command: {0}")]
+    Parse(#[from] ParseError),
+    #[error("Command error: {0}")]
+    Command(#[from] CommandError),
+}
+
+impl ClickError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Parse(_) => ErrorKind::Usage,
+            Self::Command(error) => error.kind(),
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Parse(_) => ErrorKind::Usage.exit_code(),
+            Self::Command(error) => error.exit_code(),
+        }
+    }
+
+    /// The message without what failed in front of it, such as for the JSON output.
+    pub fn message(&self) -> String {
+        match self {
+            Self::Parse(error) => error.to_string(),
+            Self::Command(error) => error.to_string(),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ParseError {
-    #[error("command '{0}' not found")]
-    CommandNotFound(String),
+    #[error("command '{0}' not found{}", Suggestions::hint(.1))]
+    CommandNotFound(String, Vec<String>), // The command, and those it may be a typo of
     #[error("missing argument: '{0}'")]
     MissingArgument(String),
+    #[error("subcommand '{0}' not found")]
+    SubcommandNotFound(String),
+    #[error("unknown flag: '{0}'")]
+    UnknownFlag(String),
+    #[error("unexpected argument: '{0}'")]
+    UnexpectedArgument(String),
     #[error("invalid version notation ({0})")]
     InvalidVersionNotation(semver::Error),
+    #[error("invalid value for '{0}': '{1}'")]
+    InvalidArgument(String, String),
 }
 
 #[derive(Error, Debug)]
@@ -17,24 +88,291 @@ pub enum CommandError {
     HTTPFailed(reqwest::Error),
     #[error("failed to parse http data to struct via json ({0})")]
     ParsingFailed(serde_json::Error),
-    #[error("failed to get http response text ({0})")]
-    FailedResponseText(reqwest::Error),
     #[error("failed to get http response bytes ({0})")]
     FailedResponseBytes(reqwest::Error),
     #[error("the package version you provided was invalid or does not exist")]
     InvalidVersion,
+    #[error("'{0}' has no dist-tag named '{1}'")]
+    UnknownDistTag(String, String),
+    #[error("no tag or branch of '{0}' matches")]
+    GitRefNotFound(String),
+    #[error("failed to fetch '{0}' with git ({1})")]
+    GitFailed(String, String),
+    #[error("failed to run git, is it installed? ({0})")]
+    FailedToRunGit(Error),
+    #[error("'{0}' can not be installed as {1}")]
+    InvalidPackageSource(String, String),
+    #[error("'{0}' has no field '{1}'")]
+    FieldNotFound(String, String),
+    #[error("'{0}' does not support the platform {1}, use --force-platform to install it for another one")]
+    UnsupportedPlatform(String, String),
+    #[error("'{package}' requires Node {required}, but {installed} is installed and engine-strict is set")]
+    UnsupportedEngine {
+        package: String,
+        required: String,
+        installed: String,
+    },
     #[error("failed to extract tar file ({0})")]
     ExtractionFailed(Error),
+    #[error("invalid registry URL '{0}'")]
+    InvalidRegistry(String),
+    #[error("failed to read the config file '{0}' ({1})")]
+    FailedToReadConfig(String, Error),
+    #[error("the _password for registry '{0}' in .npmrc is not base64 encoded")]
+    InvalidPassword(String),
+    #[error("'{0}' is not set in the config")]
+    ConfigKeyNotSet(String),
+    #[error("invalid value for '{0}' in the config: '{1}'")]
+    InvalidConfigValue(String, String),
+    #[error("failed to read the cafile '{0}' ({1})")]
+    FailedToReadCaFile(String, Error),
+    #[error("the cafile '{0}' does not hold any valid PEM certificate")]
+    InvalidCaFile(String),
+    #[error("failed to create the HTTP client ({0})")]
+    FailedToCreateClient(reqwest::Error),
+    #[error("the registry is unavailable, '{url}' failed {attempts} time(s) ({reason})")]
+    RegistryUnavailable {
+        url: String,
+        attempts: u32,
+        reason: String,
+    },
+    #[error("could not find the home directory for global installs")]
+    NoHomeDirectory,
     #[error("could not find cache directory ({0})")]
     NoCacheDirectory(Error),
+    #[error("'{0}' is not in the cache, which is all that can be installed with --offline")]
+    NotInOfflineCache(String),
+    #[error("failed to clean the cache ({0})")]
+    FailedToCleanCache(Error),
+    #[error("failed to verify the cache ({0})")]
+    FailedToVerifyCache(Error),
     #[error("failed to get directory entry ({0})")]
     FailedDirectoryEntry(Error),
     #[error("failed to create file ({0})")]
     FailedToCreateFile(Error),
+    #[error("failed to read file ({0})")]
+    FailedToReadFile(Error),
     #[error("failed to write file ({0})")]
     FailedToWriteFile(Error),
+    #[error("failed to read input ({0})")]
+    FailedToReadInput(Error),
     #[error("failed to serialize package lock ({0})")]
     FailedToSerializePackageLock(serde_json::Error),
+    #[error("failed to serialize install state ({0})")]
+    FailedToSerializeInstallState(serde_json::Error),
+    #[error("failed to serialize install report ({0})")]
+    FailedToSerializeReport(serde_json::Error),
+    #[error("failed to serialize resolved graph ({0})")]
+    FailedToSerializeGraph(serde_json::Error),
+    #[error("failed to serialize request ({0})")]
+    FailedToSerializeRequest(serde_json::Error),
+    #[error("failed to serialize output ({0})")]
+    FailedToSerializeOutput(serde_json::Error),
+    #[error("failed to read package lock ({0})")]
+    FailedToReadPackageLock(Error),
+    #[error("lockfile '{path}' is corrupt ({detail}), run install with --regenerate-lockfile to rebuild it")]
+    CorruptLockfile { path: String, detail: String },
+    #[error("the cache index '{0}' is corrupt ({1}), remove it and install each project again to rebuild it")]
+    CorruptCacheIndex(String, String),
+    #[error("there is no lockfile in this directory, run install to create it")]
+    NoLockfile,
+    #[error("the lockfile is out of date ({}), run install to update it", .0.join(", "))]
+    LockfileOutOfSync(Vec<String>),
+    #[error("failed to remove node_modules ({0})")]
+    FailedToRemoveModules(Error),
+    #[error("package.json has no '{0}', which is needed to pack it")]
+    MissingManifestField(String),
+    #[error("failed to pack the tarball ({0})")]
+    FailedToPack(Error),
+    #[error("'{0}' is private, remove \"private\" from package.json to publish it")]
+    PrivatePackage(String),
+    #[error("not logged in to '{0}', run `click login` first")]
+    NotLoggedIn(String),
+    #[error("the registry wants a one-time password, give it with --otp")]
+    OtpRequired,
+    #[error("the one-time password was not accepted")]
+    InvalidOtp,
+    #[error("failed to publish ({0})")]
+    PublishFailed(String),
+    #[error("failed to log in ({0})")]
+    LoginFailed(String),
+    #[error("failed to revoke the token ({0})")]
+    LogoutFailed(String),
+    #[error("integrity check failed for '{0}'")]
+    IntegrityMismatch(String),
+    #[error("the integrity of '{0}' is not in the allowlist")]
+    IntegrityNotAllowlisted(String),
+    #[error("'{0}' is not signed by the registry")]
+    UnsignedPackage(String),
+    #[error("the signature of '{0}' is not valid ({1})")]
+    InvalidSignature(String, String),
+    #[error("failed to read integrity allowlist ({0})")]
+    FailedToReadAllowlist(Error),
+    #[error("failed to parse integrity allowlist ({0})")]
+    FailedToParseAllowlist(serde_json::Error),
+    #[error("invalid package spec ({0})")]
+    InvalidPackageSpec(ParseError),
+    #[error("{} package(s) failed to install: {}", .0.len(), .0.join(", "))]
+    PartialInstall(Vec<String>),
+    #[error("{0}, run install with --legacy-peer-deps to install it anyway")]
+    PeerConflict(String),
+    #[error("{} package(s) have changed since they were installed: {}", .0.len(), .0.join(", "))]
+    DriftedPackages(Vec<String>),
+    #[error("{0} package(s) are outdated")]
+    OutdatedPackages(usize),
+    #[error("{0} vulnerability(s) were found")]
+    Vulnerabilities(usize),
+    #[error("{0} package(s) have a license that is not allowed")]
+    DisallowedLicenses(usize),
+    #[error("{0} check(s) failed")]
+    FailedChecks(usize),
+    #[error("the version is already {0}")]
+    VersionUnchanged(String),
+    #[error("the git working directory has uncommitted changes, commit them first or use --no-git-tag-version")]
+    GitDirty,
+    #[error("git {0} failed ({1})")]
+    GitCommandFailed(String, String),
+    #[error("there is no package.json in this directory")]
+    NoManifest,
+    #[error("the workspace at '{0}' has no name in its package.json")]
+    UnnamedWorkspace(String),
+    #[error("more than one workspace is named '{0}'")]
+    DuplicateWorkspace(String),
+    #[error("no workspace matches '{0}'")]
+    WorkspaceNotFound(String),
+    #[error("workspaces depend on each other in a cycle: {0}")]
+    WorkspaceCycle(String),
+    #[error(
+        "'{0}' is depended on with workspace:, but no workspace of the project is named '{0}'"
+    )]
+    UnknownWorkspace(String),
+    #[error("the workspace '{0}' is at version {2}, which does not match workspace:{1}")]
+    WorkspaceVersionMismatch(String, String, String),
+    #[error("failed to parse package.json ({0})")]
+    FailedToParseManifest(serde_json::Error),
+    #[error("failed to serialize package.json ({0})")]
+    FailedToSerializeManifest(serde_json::Error),
+    #[error("failed to roll back node_modules ({0})")]
+    FailedToRollBack(Error),
+    #[error("failed to remove link from node_modules ({0})")]
+    FailedToRemoveLink(Error),
+    #[error("failed to link executable '{0}' ({1})")]
+    FailedToLinkBin(String, Error),
+    #[error("package '{0}' is not installed")]
+    PackageNotInstalled(String),
     #[error("command failed ({0})")]
-    ComandFailedError(Error)
+    ComandFailedError(Error),
+    #[error("there is no script named '{0}' in package.json")]
+    ScriptNotFound(String),
+    #[error("script '{0}' exited with code {1}")]
+    ScriptFailed(String, i32),
+    #[error("'{0}' exited with code {1}")]
+    ExecutableFailed(String, i32),
+    #[error("'{0}' has no executable to run")]
+    NoExecutable(String),
+    #[error("'{0}' has more than one executable ({1}), and none is named after it")]
+    AmbiguousExecutable(String, String),
+    #[error("'{0}' has no executable named '{1}', it has: {2}")]
+    ExecutableNotFound(String, String, String),
+    #[error("failed to read the patch '{0}', {1}")]
+    InvalidPatch(String, String),
+    #[error("the patch '{0}' does not apply, {1}")]
+    FailedToApplyPatch(String, String),
+    #[error(
+        "'{0}' is already being edited, commit it with `click patch-commit` or remove it first"
+    )]
+    PatchInProgress(String),
+    #[error("the install was not confirmed, it would download {0}")]
+    InstallNotConfirmed(String),
+    #[error("package '{0}' is not in the registry{}", Suggestions::hint(.1))]
+    PackageNotFound(String, Vec<String>), // The package, and similarly named packages the registry has
+    #[error("the latest release of click has no binary for {0}")]
+    NoReleaseBinary(String),
+    #[error("the release has no checksum to verify '{0}' with")]
+    MissingChecksum(String),
+    #[error("failed to replace the click executable ({0})")]
+    FailedToReplaceExecutable(Error),
+    #[error("{script} script of '{package}' exited with code {code}")]
+    LifecycleScriptFailed {
+        package: String,
+        script: String,
+        code: i32,
+    },
+}
+
+impl CommandError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::HTTPFailed(_)
+            | Self::ParsingFailed(_)
+            | Self::FailedResponseBytes(_)
+            | Self::RegistryUnavailable { .. }
+            | Self::NotInOfflineCache(_)
+            | Self::GitFailed(..) => ErrorKind::Network,
+            Self::InvalidVersion
+            | Self::UnknownDistTag(..)
+            | Self::GitRefNotFound(_)
+            | Self::UnsupportedPlatform(..)
+            | Self::NoReleaseBinary(_)
+            | Self::PackageNotFound(..)
+            | Self::UnsupportedEngine { .. }
+            | Self::PeerConflict(_)
+            | Self::WorkspaceCycle(_)
+            | Self::UnknownWorkspace(_)
+            | Self::WorkspaceVersionMismatch(..) => ErrorKind::Resolution,
+            Self::IntegrityMismatch(_)
+            | Self::MissingChecksum(_)
+            | Self::IntegrityNotAllowlisted(_)
+            | Self::UnsignedPackage(_)
+            | Self::InvalidSignature(..)
+            | Self::CorruptLockfile { .. }
+            | Self::CorruptCacheIndex(..)
+            | Self::LockfileOutOfSync(_)
+            | Self::DriftedPackages(_) => ErrorKind::Integrity,
+            Self::ComandFailedError(_)
+            | Self::ScriptFailed(..)
+            | Self::ExecutableFailed(..)
+            | Self::LifecycleScriptFailed { .. } => ErrorKind::Script,
+            Self::InvalidRegistry(_)
+            | Self::InvalidPassword(_)
+            | Self::InvalidConfigValue(..)
+            | Self::ConfigKeyNotSet(_)
+            | Self::InvalidCaFile(_)
+            | Self::InvalidPackageSpec(_)
+            | Self::NoManifest
+            | Self::NoLockfile
+            | Self::MissingManifestField(_)
+            | Self::VersionUnchanged(_)
+            | Self::GitDirty
+            | Self::FieldNotFound(..)
+            | Self::PrivatePackage(_)
+            | Self::NotLoggedIn(_)
+            | Self::OtpRequired
+            | Self::InvalidOtp
+            | Self::UnnamedWorkspace(_)
+            | Self::DuplicateWorkspace(_)
+            | Self::WorkspaceNotFound(_)
+            | Self::PackageNotInstalled(_)
+            | Self::ScriptNotFound(_)
+            | Self::NoExecutable(_)
+            | Self::AmbiguousExecutable(..)
+            | Self::ExecutableNotFound(..)
+            | Self::InvalidPatch(..)
+            | Self::FailedToApplyPatch(..)
+            | Self::PatchInProgress(_)
+            | Self::InstallNotConfirmed(_)
+            | Self::InvalidPackageSource(..)
+            | Self::FailedToParseManifest(_)
+            | Self::FailedToParseAllowlist(_) => ErrorKind::Usage,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    /// The exit code of a failed script is passed on, so click can be used in place of the script itself.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::ScriptFailed(_, code) | Self::ExecutableFailed(_, code) => *code,
+            _ => self.kind().exit_code(),
+        }
+    }
 }
@@ -0,0 +1,439 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    errors::CommandError, installer::MODULES_DIRECTORY, patch::Patches, types::Lockfile,
+    versions::Versions,
+};
+
+const PACKAGE_JSON_NAME: &str = "package.json";
+
+/// Where each package goes in node_modules, keyed by the names of the packages it is nested in followed by its own,
+/// such as `[foo, bar]` for `node_modules/foo/node_modules/bar`.
+#[derive(Default)]
+pub struct HoistedPlan {
+    pub placements: BTreeMap<Vec<String>, String>, // To the package formatted as package@version
+}
+
+/// A dependency found from a package the same way Node finds it, recorded so that a package placed later does not
+/// shadow it with another version.
+struct Resolution {
+    from: Vec<String>,  // The location of the package that depends on it
+    found: Vec<String>, // The location it was found at
+}
+
+impl HoistedPlan {
+    /// Plans the layout of everything in the lockfile, the packages requested in package.json are the roots.
+    pub fn from_lockfile(lockfile: &Lockfile) -> Self {
+        let roots = lockfile.requested.values().cloned().collect::<Vec<_>>();
+        let dependencies = lockfile
+            .packages
+            .keys()
+            .map(|package| {
+                let dependencies = lockfile
+                    .direct_dependencies(package)
+                    .into_iter()
+                    .map(|(_, dependency)| dependency)
+                    .collect();
+
+                (package.to_string(), dependencies)
+            })
+            .collect();
+
+        Self::new(&roots, &dependencies)
+    }
+
+    /// Packages are placed a level at a time, starting with the roots at the top of node_modules. Each dependency goes
+    /// as high up as it can: it is reused if Node would already find the same version from the package that depends on
+    /// it, and otherwise placed at the highest location where it is found first and does not hide another version from
+    /// a package placed before it. A dependency that conflicts at every level above is nested in its dependent.
+    /// `dependencies` has the direct dependencies of each package, formatted as package@version.
+    pub fn new(roots: &[String], dependencies: &HashMap<String, Vec<String>>) -> Self {
+        let mut plan = Self::default();
+
+        // When more than one version of a package is requested the highest one is at the top
+        let mut top_level: BTreeMap<String, String> = BTreeMap::new();
+        for root in roots {
+            let (name, version) = Versions::parse_raw_package_details(root.to_string());
+            match top_level.get(&name) {
+                Some(selected) if !Versions::is_newer(&version, selected) => continue,
+                _ => top_level.insert(name, version),
+            };
+        }
+
+        let mut queue = VecDeque::new();
+        for (name, version) in top_level {
+            let package = Versions::stringify(&name, &version);
+            plan.placements.insert(vec![name.to_string()], package);
+            queue.push_back(vec![name]);
+        }
+
+        let mut resolutions: HashMap<String, Vec<Resolution>> = HashMap::new();
+        while let Some(location) = queue.pop_front() {
+            let package = &plan.placements[&location];
+            let mut package_dependencies = dependencies.get(package).cloned().unwrap_or_default();
+            package_dependencies.sort();
+            package_dependencies.dedup();
+
+            for dependency in package_dependencies {
+                let (name, _) = Versions::parse_raw_package_details(dependency.to_string());
+
+                let found = plan.resolve(&location, &name);
+                if let Some(found) = found
+                    .as_ref()
+                    .filter(|found| plan.placements[*found] == dependency)
+                {
+                    resolutions.entry(name).or_default().push(Resolution {
+                        from: location.clone(),
+                        found: found.clone(),
+                    });
+                    continue;
+                }
+
+                // Anything above a conflicting version would be hidden by it, so only deeper locations are tried
+                let shallowest = found.map_or(0, |found| found.len());
+                let name_resolutions = resolutions.entry(name.to_string()).or_default();
+                let candidate = (shallowest..=location.len())
+                    .map(|depth| {
+                        let mut candidate = location[..depth].to_vec();
+                        candidate.push(name.to_string());
+                        candidate
+                    })
+                    .find(|candidate| !Self::hides(&plan, name_resolutions, candidate, &dependency))
+                    .unwrap_or_else(|| {
+                        let mut candidate = location.clone();
+                        candidate.push(name.to_string());
+                        candidate
+                    });
+
+                name_resolutions.push(Resolution {
+                    from: location.clone(),
+                    found: candidate.clone(),
+                });
+                plan.placements.insert(candidate.clone(), dependency);
+                queue.push_back(candidate);
+            }
+        }
+
+        plan
+    }
+
+    /// Finds a package from a location the same way Node does, by looking in the node_modules of the location and then
+    /// in those of each location above it. Returns the location it was found at, or None if it is nowhere.
+    fn resolve(&self, location: &[String], name: &String) -> Option<Vec<String>> {
+        (0..=location.len()).rev().find_map(|depth| {
+            let mut candidate = location[..depth].to_vec();
+            candidate.push(name.to_string());
+            self.placements
+                .contains_key(&candidate)
+                .then_some(candidate)
+        })
+    }
+
+    /// Whether placing a package at the candidate location would hide another version of it from a package that found
+    /// it further up.
+    fn hides(
+        plan: &Self,
+        resolutions: &[Resolution],
+        candidate: &[String],
+        package: &String,
+    ) -> bool {
+        let parent = &candidate[..candidate.len() - 1];
+
+        resolutions.iter().any(|resolution| {
+            resolution.from.starts_with(parent)
+                && resolution.found.len() < candidate.len()
+                && &plan.placements[&resolution.found] != package
+        })
+    }
+
+    /// Packages that other packages are nested in, which have to be directories rather than links.
+    fn directories(&self) -> BTreeSet<Vec<String>> {
+        self.placements
+            .keys()
+            .flat_map(|location| (1..location.len()).map(|depth| location[..depth].to_vec()))
+            .collect()
+    }
+}
+
+/// The hoisted layout, which is the default, installs a version depended on by more than one package once at the top
+/// of node_modules. Packages are linked from the cache, so a package with dependencies nested in it is a directory of
+/// links to its files instead, with its own node_modules next to them. Node finds packages through the links with
+/// `--preserve-symlinks`, see `click exec`.
+pub struct HoistedLayout;
+impl HoistedLayout {
+    /// Lays out node_modules as planned. Locations that are not in the plan are left as they are, apart from what is
+    /// nested in a package, which is always laid out again.
+    pub fn link(plan: &HoistedPlan) -> Result<(), CommandError> {
+        let directories = plan.directories();
+
+        // Locations sort after the packages they are nested in, so a directory is always there before what goes in it
+        for (location, package) in plan.placements.iter() {
+            let path = Self::path(location);
+            let result = match directories.contains(location) {
+                true => Self::link_directory(package, &path),
+                false => Self::link_package(package, &path),
+            };
+
+            result.map_err(CommandError::FailedToCreateFile)?;
+        }
+
+        Ok(())
+    }
+
+    /// Every package installed with the hoisted layout and where it is, the same as a plan. Links that are not to the
+    /// cache, such as those of workspaces or of the isolated layout, are left out.
+    pub fn installed() -> Result<HoistedPlan, CommandError> {
+        let mut plan = HoistedPlan::default();
+        Self::read_modules(Path::new(MODULES_DIRECTORY), &[], &mut plan)
+            .map_err(CommandError::FailedToReadFile)?;
+
+        Ok(plan)
+    }
+
+    /// Removes the packages at the top of node_modules that are not in the plan.
+    pub fn remove_extraneous(plan: &HoistedPlan) -> Result<(), CommandError> {
+        for location in Self::installed()?.placements.keys() {
+            if location.len() > 1 || plan.placements.contains_key(location) {
+                continue;
+            }
+
+            Self::remove(&Self::path(location)).map_err(CommandError::FailedToRemoveLink)?;
+        }
+
+        Ok(())
+    }
+
+    /// Such as `node_modules/foo/node_modules/bar` for `[foo, bar]`.
+    pub fn path(location: &[String]) -> PathBuf {
+        let mut path = PathBuf::from(MODULES_DIRECTORY);
+        for (index, name) in location.iter().enumerate() {
+            if index > 0 {
+                path.push("node_modules");
+            }
+            path.push(name);
+        }
+
+        path
+    }
+
+    /// Package string is formated as package@version
+    /// Returns the package a path in node_modules has installed, either as a link to the cache or as a directory of links.
+    fn installed_package(path: &Path) -> Option<String> {
+        let target = match fs::read_link(path) {
+            Ok(target) => target,
+            Err(_) => fs::read_link(path.join(PACKAGE_JSON_NAME))
+                .ok()?
+                .parent()?
+                .to_path_buf(),
+        };
+
+        // Dependencies bundled in a package are linked from its node_modules in the cache, which is not a package
+        Patches::package_of(target.parent()?)
+    }
+
+    fn read_modules(
+        directory: &Path,
+        parent: &[String],
+        plan: &mut HoistedPlan,
+    ) -> Result<(), io::Error> {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            let file_name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            // Such as node_modules/.bin and node_modules/.click
+            if file_name.starts_with('.') {
+                continue;
+            }
+
+            // Packages of a scope are in its directory
+            if file_name.starts_with('@') && !path.is_symlink() {
+                for scoped in fs::read_dir(&path)? {
+                    let scoped_path = scoped?.path();
+                    let scoped_name = scoped_path
+                        .file_name()
+                        .map(|name| format!("{file_name}/{}", name.to_string_lossy()))
+                        .unwrap_or_default();
+
+                    Self::read_package(&scoped_path, parent, scoped_name, plan)?;
+                }
+                continue;
+            }
+
+            Self::read_package(&path, parent, file_name, plan)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_package(
+        path: &Path,
+        parent: &[String],
+        name: String,
+        plan: &mut HoistedPlan,
+    ) -> Result<(), io::Error> {
+        let Some(package) = Self::installed_package(path) else {
+            return Ok(());
+        };
+
+        let mut location = parent.to_vec();
+        location.push(name);
+
+        if !path.is_symlink() {
+            Self::read_modules(&path.join("node_modules"), &location, plan)?;
+        }
+
+        plan.placements.insert(location, package);
+        Ok(())
+    }
+
+    /// Package string is formated as package@version
+    /// A package with nothing nested in it is a link to the cache, or to its patched copy, the link is replaced if it is
+    /// to another version.
+    fn link_package(package: &str, path: &Path) -> Result<(), io::Error> {
+        let target = Patches::source(package);
+        if fs::read_link(path).is_ok_and(|existing| existing == target) {
+            return Ok(());
+        }
+
+        Self::remove(path)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        symlink::symlink_dir(target, path)
+    }
+
+    /// Package string is formated as package@version
+    /// A package with dependencies nested in it is a directory with a link to each of its files in the cache, so that
+    /// its node_modules belongs to the project. Its node_modules is made again, with links to any dependencies bundled
+    /// in the package, and what is nested in it is linked after it.
+    fn link_directory(package: &String, path: &Path) -> Result<(), io::Error> {
+        if Self::installed_package(path).as_ref() != Some(package) || path.is_symlink() {
+            Self::remove(path)?;
+        }
+
+        let modules_directory = path.join("node_modules");
+        Self::remove(&modules_directory)?;
+        fs::create_dir_all(&modules_directory)?;
+
+        let source = Patches::source(package);
+        for entry in fs::read_dir(&source)? {
+            let entry = entry?;
+            let destination = path.join(entry.file_name());
+
+            if entry.file_name() == "node_modules" {
+                for bundled in fs::read_dir(entry.path())? {
+                    let bundled = bundled?;
+                    symlink::symlink_auto(
+                        bundled.path(),
+                        modules_directory.join(bundled.file_name()),
+                    )?;
+                }
+                continue;
+            }
+
+            // Links to the cache are replaced once the package is patched, and the other way around
+            if fs::read_link(&destination).is_ok_and(|existing| existing != entry.path()) {
+                Self::remove(&destination)?;
+            }
+            if destination.symlink_metadata().is_err() {
+                symlink::symlink_auto(entry.path(), destination)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes a link or a directory, a directory of links only has the links removed and not what they are to.
+    pub fn remove(path: &Path) -> Result<(), io::Error> {
+        let metadata = match path.symlink_metadata() {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        match metadata.is_symlink() {
+            true => symlink::remove_symlink_auto(path),
+            false if metadata.is_dir() => fs::remove_dir_all(path),
+            false => fs::remove_file(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(roots: &[&str], dependencies: &[(&str, &[&str])]) -> Vec<(String, String)> {
+        let roots = roots
+            .iter()
+            .map(|root| root.to_string())
+            .collect::<Vec<_>>();
+        let dependencies = dependencies
+            .iter()
+            .map(|(package, dependencies)| {
+                let dependencies = dependencies.iter().map(|dependency| dependency.to_string());
+                (package.to_string(), dependencies.collect())
+            })
+            .collect();
+
+        HoistedPlan::new(&roots, &dependencies)
+            .placements
+            .into_iter()
+            .map(|(location, package)| (location.join("/"), package))
+            .collect()
+    }
+
+    fn placed(placements: &[(&str, &str)]) -> Vec<(String, String)> {
+        placements
+            .iter()
+            .map(|(location, package)| (location.to_string(), package.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn hoists_shared_versions_once() {
+        let placements = plan(
+            &["a@1.0.0", "b@1.0.0"],
+            &[("a@1.0.0", &["c@1.0.0"]), ("b@1.0.0", &["c@1.0.0"])],
+        );
+
+        assert_eq!(
+            placements,
+            placed(&[("a", "a@1.0.0"), ("b", "b@1.0.0"), ("c", "c@1.0.0")])
+        );
+    }
+
+    #[test]
+    fn nests_conflicting_versions() {
+        let placements = plan(
+            &["a@1.0.0", "c@2.0.0"],
+            &[("a@1.0.0", &["b@1.0.0"]), ("b@1.0.0", &["c@1.0.0"])],
+        );
+
+        // b is hoisted to the top, but c@2.0.0 is already there so the c of b is nested in it
+        assert_eq!(
+            placements,
+            placed(&[
+                ("a", "a@1.0.0"),
+                ("b", "b@1.0.0"),
+                ("b/c", "c@1.0.0"),
+                ("c", "c@2.0.0"),
+            ])
+        );
+    }
+}
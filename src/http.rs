@@ -1,61 +1,714 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    time::{Duration, Instant},
+};
+
 use bytes::Bytes;
+use log::{debug, info, warn};
+use reqwest::{Method, StatusCode};
 
 use crate::{
+    config::Config,
     errors::CommandError::{self, *},
-    types::{PackageData, VersionData},
+    metadata_cache::{CachedPackument, MetadataCache},
+    suggest::Suggestions,
+    types::{
+        Attestations, BulkAdvisories, PackageData, RegistryError, RegistryKeys, Release,
+        SearchResponse, TokenResponse, VersionData, WebLogin,
+    },
 };
 
-pub const REGISTRY_URL: &str = "https://registry.npmjs.org";
+pub const REGISTRY_URL: &str = "https://registry.npmjs.org"; // Used unless another registry is configured
+pub const RELEASES_URL: &str = "https://api.github.com/repos/StarNumber12046/click/releases/latest"; // Used unless another update endpoint is configured
+const ADVISORIES_ROUTE: &str = "/-/npm/v1/security/advisories/bulk";
+const WEB_LOGIN_ROUTE: &str = "/-/v1/login";
+const USER_ROUTE: &str = "/-/user";
+const SEARCH_ROUTE: &str = "/-/v1/search";
+const PING_ROUTE: &str = "/-/ping";
+const NOT_FOUND_SEARCH_SIZE: usize = 20; // Search results that packages similarly named to a missing one are picked from
+const KEYS_ROUTE: &str = "/-/npm/v1/keys";
+
+// The abbreviated packument only has what is needed to install each version, falling back to the full one
+const PACKUMENT_ACCEPT: &str =
+    "application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8, */*";
+const VERSION_ACCEPT: &str = "application/json"; // Versions have no abbreviated form
+const FULL_PACKUMENT_ACCEPT: &str = "application/json";
+
+/// How much an install may rely on the network, rather than what was cached by previous installs.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum NetworkMode {
+    #[default]
+    Online, // Cached packuments are revalidated with the registry
+    PreferOffline, // Cached packuments and tarballs are used as they are, only what is not cached is requested
+    Offline,       // Nothing is requested, anything that is not cached fails the install
+}
+
+/// What the registry said to a package being published.
+pub enum PublishOutcome {
+    Published,
+    OtpRequired, // The account uses two-factor authentication, so the upload has to be sent again with a one-time password
+}
+
+/// What the registry said to a login with a password.
+pub enum LoginOutcome {
+    LoggedIn(String), // The token to authenticate with
+    OtpRequired,
+}
+
+/// The response to a request that is only made once, see HTTPRequest::send_once().
+struct Unretried {
+    status: StatusCode,
+    wants_otp: bool, // The account uses two-factor authentication, and no valid one-time password was sent
+    retry_after: Option<u64>, // In seconds
+    bytes: Bytes,
+}
+
+impl Unretried {
+    /// The reason the registry gave for failing the request, or the status if it gave none.
+    fn error_message(&self) -> String {
+        serde_json::from_slice::<RegistryError>(&self.bytes)
+            .ok()
+            .and_then(|registry_error| registry_error.error.or(registry_error.message))
+            .unwrap_or_else(|| self.status.to_string())
+    }
+}
+
+/// A successful response, or one saying that the copy named by `If-None-Match` has not changed.
+struct Fetched {
+    not_modified: bool,
+    etag: Option<String>,
+    bytes: Bytes,
+}
 
 pub struct HTTPRequest;
 impl HTTPRequest {
     /// Download a file from any specified URL.
     pub async fn get_bytes(client: reqwest::Client, url: String) -> Result<Bytes, CommandError> {
-        client
-            .get(url)
-            .send()
-            .await
-            .map_err(CommandError::HTTPFailed)?
-            .bytes()
-            .await
-            .map_err(CommandError::FailedResponseBytes)
-    }
-
-    /// Make a request to the NPM registry.
-    /// This includes the recommended header to shorten the response size.
-    async fn registry(client: reqwest::Client, route: String) -> Result<String, CommandError> {
-        client
-            .get(format!("{REGISTRY_URL}{route}"))
-            .header(
-                "Accept",
-                "application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8, */*",
-            )
-            .send()
-            .await
-            .map_err(HTTPFailed)?
-            .text()
-            .await
-            .map_err(FailedResponseText)
+        Ok(Self::fetch(client, &url, &[], None).await?.bytes)
+    }
+
+    /// Starts a request, with the credentials of the registry the URL is under if any are configured.
+    /// It is a GET unless it has a body, which is posted as JSON.
+    fn request(
+        client: reqwest::Client,
+        url: &str,
+        body: Option<&[u8]>,
+    ) -> Result<reqwest::RequestBuilder, CommandError> {
+        let request = match body {
+            Some(body) => client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .body(body.to_vec()),
+            None => client.get(url),
+        };
+
+        Ok(match Config::get()?.authorization(url) {
+            Some(authorization) => request.header("Authorization", authorization),
+            None => request,
+        })
+    }
+
+    /// Makes a request to the URL, then to each mirror of the registry in turn while the one before is still unavailable
+    /// after it has been retried.
+    async fn with_mirrors<T, F, Fut>(url: &str, request: F) -> Result<T, CommandError>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<T, CommandError>>,
+    {
+        let sources = Config::get()?.mirrored_urls(url);
+
+        let mut source = 0;
+        loop {
+            match request(sources[source].to_string()).await {
+                Err(err @ RegistryUnavailable { .. }) if source + 1 < sources.len() => {
+                    warn!("{err}, falling back to '{}'", sources[source + 1]);
+                    source += 1;
+                }
+                result => {
+                    if source > 0 && result.is_ok() {
+                        info!("'{url}' was served by the mirror '{}'", sources[source]);
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
+    /// Requests only read from the registry, even those that post a body, so any that fails on the way or with a server
+    /// error can be retried safely. Client errors such as a missing package are returned straight away, as retrying
+    /// would not change them.
+    async fn fetch(
+        client: reqwest::Client,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Option<&[u8]>,
+    ) -> Result<Fetched, CommandError> {
+        Self::with_mirrors(url, |url| {
+            let client = client.clone();
+            async move { Self::fetch_from(client, &url, headers, body).await }
+        })
+        .await
+    }
+
+    async fn fetch_from(
+        client: reqwest::Client,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Option<&[u8]>,
+    ) -> Result<Fetched, CommandError> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+
+            let reason = match Self::send(client.clone(), url, headers, body).await? {
+                Ok(response) => {
+                    let not_modified = response.status() == reqwest::StatusCode::NOT_MODIFIED;
+                    let etag = response
+                        .headers()
+                        .get(reqwest::header::ETAG)
+                        .and_then(|etag| etag.to_str().ok())
+                        .map(str::to_string);
+
+                    match response.bytes().await {
+                        Ok(bytes) => {
+                            return Ok(Fetched {
+                                not_modified,
+                                etag,
+                                bytes,
+                            })
+                        }
+                        Err(err) if err.is_body() || err.is_timeout() => err.to_string(),
+                        Err(err) => return Err(FailedResponseBytes(err)),
+                    }
+                }
+                Err(reason) => reason,
+            };
+
+            Self::wait_to_retry(url, attempts, reason).await?;
+        }
+    }
+
+    /// Starts a download whose body is read with `Response::chunk()` as it arrives, so that it never has to be held in memory.
+    /// Only the request is retried, as by the time the body fails part of it has already been used.
+    /// A download that carries on from byte `from` asks for the rest with a `Range` header, a server that ignores it
+    /// answers with 200 and the whole file rather than 206.
+    pub async fn get_stream(
+        client: reqwest::Client,
+        url: &str,
+        from: u64,
+    ) -> Result<reqwest::Response, CommandError> {
+        let range = format!("bytes={from}-");
+        let headers = match from {
+            0 => vec![],
+            _ => vec![("Range", range.as_str())],
+        };
+
+        Self::with_mirrors(url, |url| {
+            let client = client.clone();
+            let headers = &headers;
+            async move {
+                let mut attempts = 0;
+                loop {
+                    attempts += 1;
+
+                    match Self::send(client.clone(), &url, headers, None).await? {
+                        Ok(response) => return Ok(response),
+                        Err(reason) => Self::wait_to_retry(&url, attempts, reason).await?,
+                    }
+                }
+            }
+        })
+        .await
+    }
+
+    /// Makes a single attempt at a request, returning the reason it failed if it can be retried.
+    async fn send(
+        client: reqwest::Client,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Option<&[u8]>,
+    ) -> Result<Result<reqwest::Response, String>, CommandError> {
+        let method = match body {
+            Some(_) => "POST",
+            None => "GET",
+        };
+
+        let mut request = Self::request(client, url, body)?;
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+
+        let started = Instant::now();
+        let response = request.send().await;
+        match &response {
+            Ok(response) => debug!(
+                "{method} {url} {} ({}ms)",
+                response.status(),
+                started.elapsed().as_millis()
+            ),
+            Err(err) => debug!("{method} {url} failed ({err})"),
+        }
+
+        match response {
+            Ok(response) if Self::is_retryable_status(response.status()) => {
+                Ok(Err(response.status().to_string()))
+            }
+            Ok(response) => Ok(Ok(response.error_for_status().map_err(HTTPFailed)?)),
+            Err(err) if err.is_connect() || err.is_timeout() || err.is_request() => {
+                Ok(Err(err.to_string()))
+            }
+            Err(err) => Err(HTTPFailed(err)),
+        }
+    }
+
+    /// Waits before the next attempt at a request, or fails if it has been attempted as many times as the retry policy allows.
+    async fn wait_to_retry(url: &str, attempts: u32, reason: String) -> Result<(), CommandError> {
+        let retry_policy = &Config::get()?.retry_policy;
+
+        if attempts > retry_policy.retries {
+            return Err(RegistryUnavailable {
+                url: url.to_string(),
+                attempts,
+                reason,
+            });
+        }
+
+        let delay = retry_policy.delay(attempts);
+        debug!("retrying {url} in {}ms", delay.as_millis());
+        tokio::time::sleep(delay).await;
+        Ok(())
+    }
+
+    /// Servers that are overloaded or restarting respond with these until they recover.
+    fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+        status.is_server_error()
+            || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            || status == reqwest::StatusCode::REQUEST_TIMEOUT
+    }
+
+    /// Returns the URL of a route of a package in its registry, `route` follows the package name.
+    fn registry_url(package_name: &str, route: &str) -> Result<String, CommandError> {
+        let registry = Config::get()?.registry_for(package_name);
+
+        // The slash of a scoped package is escaped so that the registry sees a single path segment
+        let escaped_name = package_name.replace('/', "%2f");
+        Ok(format!("{registry}/{escaped_name}{route}"))
     }
 
     /// This makes a request for a specific version of a package.
     /// This method should always be preferred where possible as its response size is significantly smaller than full package data.
     pub async fn version_data(
         client: reqwest::Client,
-        package_name: &String,
+        package_name: &str,
         version: &String,
     ) -> Result<VersionData, CommandError> {
-        let response_raw = Self::registry(client, format!("/{package_name}/{version}")).await?;
-        serde_json::from_str::<VersionData>(&response_raw).map_err(ParsingFailed)
+        let url = Self::registry_url(package_name, &format!("/{version}"))?;
+        let fetched =
+            match Self::fetch(client.clone(), &url, &[("Accept", VERSION_ACCEPT)], None).await {
+                // Either the package or only the version is missing, the packument tells which
+                Err(HTTPFailed(err)) if err.status() == Some(StatusCode::NOT_FOUND) => {
+                    Self::package_data(client, package_name, NetworkMode::Online).await?;
+                    return Err(HTTPFailed(err));
+                }
+                fetched => fetched?,
+            };
+        serde_json::from_slice::<VersionData>(&fetched.bytes).map_err(ParsingFailed)
     }
 
     /// This makes a request for all data for a package including all its versions.
     /// This method should be avoided where possible as its response size is much larger than just requesting version data.
+    /// Packuments are cached with their ETag, so that one which has not changed since the last request is not downloaded again.
+    /// Unless the network mode is online, a cached packument is used without asking the registry whether it has changed.
     pub async fn package_data(
         client: reqwest::Client,
-        package_name: &String,
+        package_name: &str,
+        network_mode: NetworkMode,
     ) -> Result<PackageData, CommandError> {
-        let response_raw = Self::registry(client, format!("/{package_name}")).await?;
-        serde_json::from_str::<PackageData>(&response_raw).map_err(ParsingFailed)
+        let url = Self::registry_url(package_name, "")?;
+        let cached = MetadataCache::read(package_name, &url).await;
+
+        match (&cached, network_mode) {
+            (Some(cached), NetworkMode::PreferOffline | NetworkMode::Offline) => {
+                debug!("using the cached packument of '{package_name}'");
+                return Self::parse_package_data(package_name, cached.body.as_bytes());
+            }
+            (None, NetworkMode::Offline) => {
+                return Err(NotInOfflineCache(package_name.to_string()))
+            }
+            _ => {}
+        }
+
+        let mut headers = vec![("Accept", PACKUMENT_ACCEPT)];
+        if let Some(cached) = &cached {
+            headers.push(("If-None-Match", cached.etag.as_str()));
+        }
+
+        let fetched = match Self::fetch(client.clone(), &url, &headers, None).await {
+            Err(HTTPFailed(err)) if err.status() == Some(StatusCode::NOT_FOUND) => {
+                return Err(Self::not_found(client, package_name).await)
+            }
+            fetched => fetched?,
+        };
+        let response_raw = match cached {
+            Some(cached) if fetched.not_modified => {
+                debug!("the cached packument of '{package_name}' has not changed");
+                Bytes::from(cached.body)
+            }
+            _ => {
+                if let Some(etag) = fetched.etag {
+                    let cached = CachedPackument {
+                        url,
+                        etag,
+                        body: String::from_utf8_lossy(&fetched.bytes).to_string(),
+                    };
+
+                    // The packument has already been downloaded, so the install does not need the cache to carry on
+                    if let Err(err) = MetadataCache::write(package_name, &cached).await {
+                        warn!("failed to cache the packument of '{package_name}' ({err})");
+                    }
+                }
+
+                fetched.bytes
+            }
+        };
+
+        Self::parse_package_data(package_name, &response_raw)
+    }
+
+    /// The error for a package the registry does not have, with the packages it has of a similar name. Those are only
+    /// suggested if the registry can search, failing to is not what went wrong.
+    async fn not_found(client: reqwest::Client, package_name: &str) -> CommandError {
+        let parameters = [
+            ("text", package_name.to_string()),
+            ("size", NOT_FOUND_SEARCH_SIZE.to_string()),
+        ];
+
+        let suggestions = match Self::search(client, &parameters).await {
+            Ok(response) => Suggestions::closest(
+                package_name,
+                response
+                    .objects
+                    .iter()
+                    .map(|object| object.package.name.as_str()),
+            ),
+            Err(err) => {
+                debug!("failed to search for packages named like '{package_name}' ({err})");
+                Vec::new()
+            }
+        };
+
+        PackageNotFound(package_name.to_string(), suggestions)
+    }
+
+    /// Searches the registry, `parameters` are the query string such as the text and how many results to return.
+    pub async fn search(
+        client: reqwest::Client,
+        parameters: &[(&str, String)],
+    ) -> Result<SearchResponse, CommandError> {
+        let url = format!("{}{SEARCH_ROUTE}", Config::get()?.registry);
+        let url = reqwest::Url::parse_with_params(&url, parameters)
+            .map_err(|_| InvalidRegistry(url.to_string()))?;
+
+        let fetched = Self::fetch(client, url.as_str(), &[], None).await?;
+        serde_json::from_slice::<SearchResponse>(&fetched.bytes).map_err(ParsingFailed)
+    }
+
+    /// The keys the registry signs versions with.
+    pub async fn registry_keys(
+        client: reqwest::Client,
+        registry: &str,
+    ) -> Result<RegistryKeys, CommandError> {
+        let url = format!("{}{KEYS_ROUTE}", registry.trim_end_matches('/'));
+        let fetched = Self::fetch(client, &url, &[], None).await?;
+        serde_json::from_slice::<RegistryKeys>(&fetched.bytes).map_err(ParsingFailed)
+    }
+
+    /// The attestations published with a version, from the URL in its `dist.attestations`.
+    pub async fn attestations(
+        client: reqwest::Client,
+        url: &str,
+    ) -> Result<Attestations, CommandError> {
+        let fetched = Self::fetch(client, url, &[], None).await?;
+        serde_json::from_slice::<Attestations>(&fetched.bytes).map_err(ParsingFailed)
+    }
+
+    /// The latest release of click. GitHub refuses requests without a user agent.
+    pub async fn latest_release(
+        client: reqwest::Client,
+        url: &str,
+    ) -> Result<Release, CommandError> {
+        let user_agent = format!("click/{}", env!("CARGO_PKG_VERSION"));
+        let headers = [
+            ("Accept", "application/vnd.github+json"),
+            ("User-Agent", user_agent.as_str()),
+        ];
+
+        let fetched = Self::fetch(client, url, &headers, None).await?;
+        serde_json::from_slice::<Release>(&fetched.bytes).map_err(ParsingFailed)
+    }
+
+    /// The size of a file from the Content-Length of a HEAD request, without downloading it. Returns None if the
+    /// registry does not answer with one, as it is only used to tell how much an install would download.
+    pub async fn content_length(client: reqwest::Client, url: &str) -> Option<u64> {
+        let mut request = client.head(url);
+        if let Some(authorization) = Config::get().ok()?.authorization(url) {
+            request = request.header("Authorization", authorization);
+        }
+
+        let response = request.send().await.ok()?;
+        debug!("HEAD {url} {}", response.status());
+        if !response.status().is_success() {
+            return None;
+        }
+
+        // The body of a HEAD response is always empty, so the length is only in the header
+        response
+            .headers()
+            .get(reqwest::header::CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    /// Checks that the registry answers, returning how long it took. It is only asked once, as how long one request
+    /// takes is what is measured.
+    pub async fn ping(client: reqwest::Client, registry: &str) -> Result<Duration, CommandError> {
+        let url = format!("{registry}{PING_ROUTE}");
+        let started = Instant::now();
+
+        // A registry that does not have the route still answered
+        match Self::send_once(client, Method::GET, &url, None, None).await? {
+            Unretried { status, .. } if status.is_success() || status == StatusCode::NOT_FOUND => {
+                Ok(started.elapsed())
+            }
+            response => Err(RegistryUnavailable {
+                url,
+                attempts: 1,
+                reason: response.error_message(),
+            }),
+        }
+    }
+
+    /// Uploads the packument of a package with its tarball attached.
+    pub async fn publish(
+        client: reqwest::Client,
+        package_name: &str,
+        body: Vec<u8>,
+        otp: Option<&str>,
+    ) -> Result<PublishOutcome, CommandError> {
+        let url = Self::registry_url(package_name, "")?;
+        let response = Self::send_once(client, Method::PUT, &url, Some(body), otp).await?;
+
+        match response {
+            Unretried { status, .. } if status.is_success() => Ok(PublishOutcome::Published),
+            Unretried {
+                wants_otp: true, ..
+            } => Ok(PublishOutcome::OtpRequired),
+            response => Err(PublishFailed(response.error_message())),
+        }
+    }
+
+    /// Starts a login in the browser, returns None if the registry only supports logging in with a password.
+    pub async fn web_login(
+        client: reqwest::Client,
+        registry: &str,
+    ) -> Result<Option<WebLogin>, CommandError> {
+        let url = format!("{registry}{WEB_LOGIN_ROUTE}");
+        let body = b"{}".to_vec();
+        let response = Self::send_once(client, Method::POST, &url, Some(body), None).await?;
+
+        match response.status {
+            status if status.is_success() => serde_json::from_slice::<WebLogin>(&response.bytes)
+                .map(Some)
+                .map_err(ParsingFailed),
+            StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED => Ok(None),
+            _ => Err(LoginFailed(response.error_message())),
+        }
+    }
+
+    /// Waits for a login in the browser to be finished, the registry answers with 202 and how long to wait until then.
+    pub async fn finish_web_login(
+        client: reqwest::Client,
+        done_url: &str,
+    ) -> Result<String, CommandError> {
+        loop {
+            let response =
+                Self::send_once(client.clone(), Method::GET, done_url, None, None).await?;
+
+            match response.status {
+                StatusCode::ACCEPTED => {
+                    let seconds = response.retry_after.unwrap_or(1);
+                    tokio::time::sleep(Duration::from_secs(seconds)).await;
+                }
+                status if status.is_success() => return Self::parse_token(&response.bytes),
+                _ => return Err(LoginFailed(response.error_message())),
+            }
+        }
+    }
+
+    /// Logs in with a username and password the way CouchDB does, which every registry supports.
+    pub async fn password_login(
+        client: reqwest::Client,
+        registry: &str,
+        username: &str,
+        password: &str,
+        otp: Option<&str>,
+    ) -> Result<LoginOutcome, CommandError> {
+        let user_id = format!("org.couchdb.user:{username}");
+        let url = format!("{registry}{USER_ROUTE}/{}", user_id.replace('/', "%2f"));
+        let body = serde_json::json!({
+            "_id": user_id,
+            "name": username,
+            "password": password,
+            "type": "user",
+            "roles": [],
+        });
+        let body = serde_json::to_vec(&body).map_err(FailedToSerializeRequest)?;
+
+        match Self::send_once(client, Method::PUT, &url, Some(body), otp).await? {
+            Unretried { status, bytes, .. } if status.is_success() => {
+                Self::parse_token(&bytes).map(LoginOutcome::LoggedIn)
+            }
+            Unretried {
+                wants_otp: true, ..
+            } => Ok(LoginOutcome::OtpRequired),
+            response => Err(LoginFailed(response.error_message())),
+        }
+    }
+
+    /// Revokes a token so that it can no longer be used, even if a copy of it is kept somewhere.
+    pub async fn revoke_token(
+        client: reqwest::Client,
+        registry: &str,
+        token: &str,
+    ) -> Result<(), CommandError> {
+        let url = format!("{registry}{USER_ROUTE}/token/{token}");
+
+        match Self::send_once(client, Method::DELETE, &url, None, None).await? {
+            Unretried { status, .. } if status.is_success() => Ok(()),
+            response => Err(LogoutFailed(response.error_message())),
+        }
+    }
+
+    fn parse_token(response_raw: &[u8]) -> Result<String, CommandError> {
+        serde_json::from_slice::<TokenResponse>(response_raw)
+            .map(|response| response.token)
+            .map_err(ParsingFailed)
+    }
+
+    /// Makes a request that changes the registry, such as publishing or logging in. Unlike reads these are never
+    /// retried, as the registry may have made the change even if the response was lost. Error statuses are returned
+    /// rather than failing, as what they mean depends on the request.
+    async fn send_once(
+        client: reqwest::Client,
+        method: Method,
+        url: &str,
+        body: Option<Vec<u8>>,
+        otp: Option<&str>,
+    ) -> Result<Unretried, CommandError> {
+        let mut request = client.request(method.clone(), url);
+        if let Some(body) = body {
+            request = request
+                .header("Content-Type", "application/json")
+                .body(body);
+        }
+        if let Some(authorization) = Config::get()?.authorization(url) {
+            request = request.header("Authorization", authorization);
+        }
+        if let Some(otp) = otp {
+            request = request.header("npm-otp", otp);
+        }
+
+        let response = request.send().await.map_err(HTTPFailed)?;
+        let status = response.status();
+        debug!("{method} {url} {status}");
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let authenticate = header(reqwest::header::WWW_AUTHENTICATE);
+        let retry_after = header(reqwest::header::RETRY_AFTER).and_then(|value| value.parse().ok());
+
+        let bytes = response.bytes().await.map_err(FailedResponseBytes)?;
+
+        // The registry asks for a one-time password in the WWW-Authenticate header, older ones only in the message
+        let wants_otp = status == StatusCode::UNAUTHORIZED
+            && (authenticate
+                .is_some_and(|authenticate| authenticate.to_ascii_lowercase().contains("otp"))
+                || String::from_utf8_lossy(&bytes).contains("one-time pass"));
+
+        Ok(Unretried {
+            status,
+            wants_otp,
+            retry_after,
+            bytes,
+        })
+    }
+
+    /// The full packument of a package, with what the abbreviated one used to install leaves out such as maintainers
+    /// and descriptions. Every field is kept as it is, and it is not cached.
+    pub async fn full_package_data(
+        client: reqwest::Client,
+        package_name: &str,
+    ) -> Result<serde_json::Value, CommandError> {
+        let url = Self::registry_url(package_name, "")?;
+        let headers = [("Accept", FULL_PACKUMENT_ACCEPT)];
+
+        let fetched = match Self::fetch(client.clone(), &url, &headers, None).await {
+            Err(HTTPFailed(err)) if err.status() == Some(StatusCode::NOT_FOUND) => {
+                return Err(Self::not_found(client, package_name).await)
+            }
+            fetched => fetched?,
+        };
+        serde_json::from_slice(&fetched.bytes).map_err(ParsingFailed)
+    }
+
+    /// Asks the registry for the advisories of the given versions of each package, keyed by package name.
+    /// Only advisories that affect at least one of the versions are returned. The registry is sent the credentials
+    /// configured for its own URL, which need not be those of the registry packages are installed from.
+    pub async fn bulk_advisories(
+        client: reqwest::Client,
+        registry: &str,
+        packages: &BTreeMap<String, Vec<String>>,
+    ) -> Result<BulkAdvisories, CommandError> {
+        let url = format!("{registry}{ADVISORIES_ROUTE}");
+        let body = serde_json::to_vec(packages).map_err(FailedToSerializeRequest)?;
+
+        let fetched = Self::fetch(client, &url, &[], Some(&body)).await?;
+        serde_json::from_slice::<BulkAdvisories>(&fetched.bytes).map_err(ParsingFailed)
+    }
+
+    fn parse_package_data(
+        package_name: &str,
+        response_raw: &[u8],
+    ) -> Result<PackageData, CommandError> {
+        let package_data =
+            serde_json::from_slice::<PackageData>(response_raw).map_err(ParsingFailed)?;
+
+        if !package_data.invalid_versions.is_empty() {
+            warn!(
+                "ignored {} invalid version(s) of '{}': {}",
+                package_data.invalid_versions.len(),
+                package_name,
+                package_data.invalid_versions.join(", ")
+            );
+        }
+
+        if !package_data.duplicate_versions.is_empty() {
+            warn!(
+                "ignored {} version(s) of '{}' listed more than once under another form: {}",
+                package_data.duplicate_versions.len(),
+                package_name,
+                package_data.duplicate_versions.join(", ")
+            );
+        }
+
+        Ok(package_data)
     }
 }
@@ -1,23 +1,120 @@
 use bytes::Bytes;
-use semver::Comparator;
+use std::env;
 use std::fs::{self};
-use std::path::Path;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use std::{
-    collections::HashMap,
-    sync::{mpsc::Sender, Arc, Mutex},
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex},
 };
 
-use crate::util::TaskAllocator;
+use log::{debug, warn};
+use reqwest::StatusCode;
+use semver::Version;
+use std::str::FromStr;
+use tokio::{io::AsyncReadExt, sync::Semaphore};
+
+use crate::util::{self, ChunkReader, TaskAllocator};
 use crate::{
     cache::{Cache, CACHE_DIRECTORY},
+    config::Config,
+    engines::Engines,
     errors::CommandError::{self},
-    http::HTTPRequest,
-    types::{DependencyMap, PackageLock, VersionData},
-    versions::{Versions, LATEST},
+    http::{HTTPRequest, NetworkMode},
+    integrity::{TarballDigests, TarballHasher},
+    isolated::IsolatedLayout,
+    patch::Patches,
+    platform::Platform,
+    reporter::{ProgressEvent, Reporter},
+    shims::{ShimPlatform, Shims},
+    signatures::Signatures,
+    source::Source,
+    tarball_cache::{TarballCache, TarballWriter},
+    types::{
+        DependencyMap, Dist, ExtractedPackage, InstallState, InstallWarning, IntegrityAllowlist,
+        Lockfile, PackageLock, VersionData, WarningCode,
+    },
+    versions::{Strategy, VersionRange, Versions, LATEST},
 };
 
 pub type DependencyMapMutex = Arc<Mutex<DependencyMap>>;
-pub type PackageBytes = (String, Bytes); // Package destination, package bytes
+
+pub const MODULES_DIRECTORY: &str = "./node_modules";
+pub const DEFAULT_CONCURRENCY: usize = 16;
+pub const BIN_DIRECTORY: &str = "./node_modules/.bin";
+pub const INSTALL_STATE_PATH: &str = "./node_modules/.click-install-state.json";
+pub const GLOBAL_DIRECTORY: &str = ".click/global"; // Relative to the home directory
+const CHUNK_BUFFER: usize = 16; // Chunks of a tarball waiting to be extracted, downloads wait for extraction past this
+const CHUNK_SIZE: usize = 64 * 1024; // Size of each chunk read from a cached tarball
+
+/// Where the chunks of a tarball come from, see Installer::stream_tarball().
+enum TarballSource {
+    Cached(tokio::fs::File),
+    Download(reqwest::Response),
+    Resumed(Option<tokio::fs::File>, reqwest::Response), // The part downloaded before, until it has been read, then the rest
+}
+
+impl TarballSource {
+    /// Returns None once the whole tarball has been read.
+    async fn chunk(&mut self) -> Result<Option<Bytes>, CommandError> {
+        match self {
+            Self::Cached(file) => Self::read_file(file).await,
+            Self::Download(response) => response
+                .chunk()
+                .await
+                .map_err(CommandError::FailedResponseBytes),
+            Self::Resumed(partial, response) => {
+                if let Some(file) = partial {
+                    if let Some(chunk) = Self::read_file(file).await? {
+                        return Ok(Some(chunk));
+                    }
+                    *partial = None;
+                }
+
+                response
+                    .chunk()
+                    .await
+                    .map_err(CommandError::FailedResponseBytes)
+            }
+        }
+    }
+
+    /// Whether the last chunk was received from the registry, rather than read from a file.
+    fn is_downloading(&self) -> bool {
+        matches!(self, Self::Download(_) | Self::Resumed(None, _))
+    }
+
+    async fn read_file(file: &mut tokio::fs::File) -> Result<Option<Bytes>, CommandError> {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let length = file
+            .read(&mut chunk)
+            .await
+            .map_err(CommandError::FailedToReadFile)?;
+
+        chunk.truncate(length);
+        Ok((length > 0).then(|| Bytes::from(chunk)))
+    }
+}
+
+/// How installed packages are laid out in node_modules.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum Linker {
+    #[default]
+    Hoisted, // Every version is linked once at the top of node_modules unless another one is there, see HoistedLayout
+    Isolated, // Each package only sees its own dependencies, see IsolatedLayout
+}
+
+impl Linker {
+    /// Package string is formated as package@version
+    /// Links a cached package into node_modules, returns every package that was linked. The hoisted layout depends on
+    /// every package being installed, so it is only planned and linked once they all are, see HoistedLayout::link().
+    pub fn link(&self, package: String) -> Result<Vec<String>, CommandError> {
+        match self {
+            Self::Hoisted => Cache::cached_packages(&package),
+            Self::Isolated => IsolatedLayout::link(package),
+        }
+    }
+}
 
 pub struct PackageInfo {
     pub version_data: VersionData,
@@ -28,31 +125,389 @@ pub struct PackageInfo {
 #[derive(Clone)]
 pub struct InstallContext {
     pub client: reqwest::Client,
-    pub bytes_sender: Sender<PackageBytes>,
+    pub install_state_mux: Option<Arc<Mutex<InstallState>>>, // Only recorded if the install can be resumed
     pub dependency_map_mux: DependencyMapMutex,
+    pub cached_packages_mux: Arc<Mutex<Vec<String>>>, // Packages linked from the cache rather than downloaded
+    pub errors_mux: Arc<Mutex<Vec<CommandError>>>, // Errors from install tasks, as they cannot be returned directly
+    pub integrity_allowlist: Option<Arc<IntegrityAllowlist>>,
+    pub resumed_packages: Arc<HashMap<String, ExtractedPackage>>, // Extracted by a previous install that was interrupted
+    pub failed_packages_mux: Arc<Mutex<HashSet<String>>>,
+    pub keep_going: bool,
+    pub lockfile: Option<Arc<Lockfile>>, // Locked packages are resolved from this rather than the registry
+    pub request_permits: Arc<Semaphore>, // Limits how many registry requests are made at once
+    pub network_mode: NetworkMode,
+    pub legacy_peer_deps: bool,
+    pub platform: Arc<Platform>,
+    pub engine_strict: bool,
+    pub strict_signatures: bool,
+    pub optional_root: Option<String>, // The optional dependency this package is installed for, all of it is skipped if any of it fails
+    pub skipped_optional_mux: Arc<Mutex<BTreeMap<String, String>>>, // Optional dependencies that failed, to the error they failed with
+    pub warnings_mux: Arc<Mutex<Vec<InstallWarning>>>, // Warnings that did not stop the install, such as an unsupported engine
+    pub reporter: Arc<dyn Reporter>,
+    pub strategy: Strategy,
+}
+
+pub struct CacheOptions {
+    pub integrity_allowlist: Option<Arc<IntegrityAllowlist>>,
+    pub resumable: bool, // Record progress in the install state file so that an interrupted install can be resumed
+    pub keep_going: bool, // Attempt every package even after failures, recording only those that succeeded
+    pub lockfile: Option<Arc<Lockfile>>,
+    pub concurrency: usize, // Maximum number of registry requests in flight
+    pub network_mode: NetworkMode,
+    pub legacy_peer_deps: bool, // Leave peer dependencies for the user to install, like npm before version 7
+    pub platform: Platform,     // Versions that do not support this platform are not installed
+    pub engine_strict: bool, // Refuse versions that do not support the installed Node rather than warning about them
+    pub strict_signatures: bool, // Refuse versions the registry has not signed rather than only checking those it has
+    pub reporter: Arc<dyn Reporter>, // Receives the progress of every package
+    pub strategy: Strategy, // With the lowest versions, cached packages are resolved again as their dependencies may be higher
+}
+
+/// Install errors are collected rather than aborting as soon as one happens, lockfiles are only written if there were none.
+#[derive(Default)]
+pub struct CacheOutcome {
+    pub downloaded: DependencyMap,
+    pub cached: Vec<String>,       // Packages that were already in the cache
+    pub failed: Vec<String>, // Packages that could not be installed, only populated when keep_going is set
+    pub failed_roots: Vec<String>, // Packages cached for themselves that failed or depend on one that did
+    pub skipped_optional: BTreeMap<String, String>, // Optional dependencies that failed to install, to why they did
+    pub warnings: Vec<InstallWarning>,
+    pub errors: Vec<CommandError>,
 }
 
 pub struct Installer;
 impl Installer {
+    /// Downloads a package and all of its dependencies into the cache and writes their lockfiles.
+    /// Nothing is linked into node_modules, use Linker::link() afterwards to do that.
+    pub async fn cache_package(
+        client: reqwest::Client,
+        package_info: PackageInfo,
+        options: CacheOptions,
+    ) -> CacheOutcome {
+        Self::cache_packages(client, vec![package_info], &HashSet::new(), options).await
+    }
+
+    /// Downloads every package and all of their dependencies at the same time, see cache_package(). Dependencies
+    /// shared by them are only downloaded once, and unless `keep_going` is set no lockfile is written if any of them
+    /// fails, so none is left cached. Those in `optional` are skipped if they fail, as optional dependencies are.
+    pub async fn cache_packages(
+        client: reqwest::Client,
+        packages: Vec<PackageInfo>,
+        optional: &HashSet<String>,
+        options: CacheOptions,
+    ) -> CacheOutcome {
+        let install_state = match options.resumable {
+            true => Self::read_install_state(),
+            false => InstallState::default(),
+        };
+        let resumed_packages = Arc::new(install_state.extracted.clone());
+        let install_state_mux = options
+            .resumable
+            .then(|| Arc::new(Mutex::new(install_state)));
+
+        let dependency_map_mux = Arc::new(Mutex::new(HashMap::new()));
+        let cached_packages_mux = Arc::new(Mutex::new(Vec::new()));
+        let errors_mux = Arc::new(Mutex::new(Vec::new()));
+        let failed_packages_mux = Arc::new(Mutex::new(HashSet::new()));
+        let skipped_optional_mux = Arc::new(Mutex::new(BTreeMap::new()));
+        let warnings_mux = Arc::new(Mutex::new(Vec::new()));
+
+        let install_context = InstallContext {
+            client,
+            install_state_mux,
+            dependency_map_mux: Arc::clone(&dependency_map_mux),
+            cached_packages_mux: Arc::clone(&cached_packages_mux),
+            errors_mux: Arc::clone(&errors_mux),
+            integrity_allowlist: options.integrity_allowlist,
+            resumed_packages,
+            failed_packages_mux: Arc::clone(&failed_packages_mux),
+            keep_going: options.keep_going,
+            lockfile: options.lockfile,
+            request_permits: Arc::new(Semaphore::new(options.concurrency.max(1))),
+            network_mode: options.network_mode,
+            legacy_peer_deps: options.legacy_peer_deps,
+            platform: Arc::new(options.platform),
+            engine_strict: options.engine_strict,
+            strict_signatures: options.strict_signatures,
+            optional_root: None,
+            skipped_optional_mux: Arc::clone(&skipped_optional_mux),
+            warnings_mux: Arc::clone(&warnings_mux),
+            reporter: options.reporter,
+            strategy: options.strategy,
+        };
+
+        let roots = packages
+            .iter()
+            .map(|package_info| package_info.stringified.to_string())
+            .collect::<Vec<_>>();
+        let install_results = packages
+            .into_iter()
+            .map(|package_info| {
+                let mut context = install_context.clone();
+                if optional.contains(&package_info.stringified) {
+                    context.optional_root = Some(package_info.stringified.to_string());
+                }
+
+                Self::install_package(context, package_info, None)
+            })
+            .collect::<Vec<_>>();
+
+        TaskAllocator::wait_until_done().await;
+
+        let mut errors = std::mem::take(&mut *errors_mux.lock().unwrap());
+        for (index, err) in install_results
+            .into_iter()
+            .filter_map(Result::err)
+            .enumerate()
+        {
+            errors.insert(index, err);
+        }
+
+        let mut downloaded = std::mem::take(&mut *dependency_map_mux.lock().unwrap());
+        let failed = std::mem::take(&mut *failed_packages_mux.lock().unwrap());
+        let skipped_optional = std::mem::take(&mut *skipped_optional_mux.lock().unwrap());
+        let warnings = std::mem::take(&mut *warnings_mux.lock().unwrap());
+
+        let failed_roots = roots
+            .into_iter()
+            .filter(|root| Self::depends_on(&downloaded, root, &failed))
+            .collect();
+
+        // Skipped optional dependencies are left out as if they were never depended on, even without keep_going
+        Self::remove_failed(&mut downloaded, &skipped_optional.keys().cloned().collect());
+
+        if options.keep_going {
+            Self::remove_failed(&mut downloaded, &failed);
+        }
+
+        if errors.is_empty() || options.keep_going {
+            if let Err(err) = Self::write_lockfiles(&mut downloaded) {
+                errors.push(err);
+            }
+        }
+
+        let mut failed = failed.into_iter().collect::<Vec<_>>();
+        failed.sort();
+
+        // A cached package can be reached through more than one dependent
+        let mut cached = std::mem::take(&mut *cached_packages_mux.lock().unwrap());
+        cached.sort();
+        cached.dedup();
+
+        CacheOutcome {
+            downloaded,
+            cached,
+            failed,
+            failed_roots,
+            skipped_optional,
+            warnings,
+            errors,
+        }
+    }
+
+    /// Whether a package is one of `packages` or depends on one of them, directly or not, while the dependency map
+    /// only has the direct dependencies of each package.
+    fn depends_on(
+        dependency_map: &DependencyMap,
+        package: &String,
+        packages: &HashSet<String>,
+    ) -> bool {
+        let mut visited = HashSet::new();
+        let mut unvisited = vec![package.to_string()];
+
+        while let Some(package) = unvisited.pop() {
+            if packages.contains(&package) {
+                return true;
+            }
+
+            if !visited.insert(package.to_string()) {
+                continue;
+            }
+
+            if let Some(package_lock) = dependency_map.get(&package) {
+                unvisited.extend(package_lock.dependencies.iter().cloned());
+            }
+        }
+
+        false
+    }
+
+    /// Removes packages that failed to install, and every reference to them, so that they are not recorded in any lockfile.
+    fn remove_failed(dependency_map: &mut DependencyMap, failed: &HashSet<String>) {
+        dependency_map.retain(|package, _| !failed.contains(package));
+
+        for package_lock in dependency_map.values_mut() {
+            package_lock
+                .dependencies
+                .retain(|dependency| !failed.contains(dependency));
+        }
+    }
+
+    /// While resolving only the direct dependencies of each package are recorded, this replaces them with every
+    /// package each one depends on, directly or not, which is what lockfiles list so a package can be linked on its own.
+    fn resolve_transitive_dependencies(
+        dependency_map: &mut DependencyMap,
+    ) -> Result<(), CommandError> {
+        let direct_dependencies = dependency_map
+            .iter()
+            .map(|(package, package_lock)| (package.to_string(), package_lock.dependencies.clone()))
+            .collect::<HashMap<_, _>>();
+
+        for (package, package_lock) in dependency_map.iter_mut() {
+            let mut dependencies = HashSet::new();
+            let mut unvisited = package_lock.dependencies.clone();
+
+            while let Some(dependency) = unvisited.pop() {
+                if !dependencies.insert(dependency.to_string()) {
+                    continue;
+                }
+
+                match direct_dependencies.get(&dependency) {
+                    Some(dependency_dependencies) => {
+                        unvisited.extend(dependency_dependencies.clone())
+                    }
+                    // Packages that were already cached are not in the map, but their lockfile already lists everything
+                    None => unvisited.extend(Cache::read_lock(&dependency)?.dependencies),
+                }
+            }
+
+            // A package can end up depending on itself through a cycle
+            dependencies.remove(package);
+            package_lock.dependencies = dependencies.into_iter().collect();
+        }
+
+        Ok(())
+    }
+
+    fn write_lockfiles(dependency_map: &mut DependencyMap) -> Result<(), CommandError> {
+        Self::resolve_transitive_dependencies(dependency_map)?;
+
+        for (package_name, package_lock) in dependency_map.iter_mut() {
+            // Dependencies are collected in whatever order their install tasks ran, sort them so the lockfile does not depend on scheduling
+            package_lock.dependencies.sort();
+
+            // Every package has been extracted by now, so this records the files as they were installed
+            package_lock.content_integrity = Some(Cache::content_integrity(package_name)?);
+
+            Cache::write_lock(package_name, package_lock)?;
+        }
+
+        Ok(())
+    }
+
+    /// Parses a version range, looking dist-tags such as `beta` up in the packument as they are not ranges.
+    /// Returns None for `latest`, which is resolved the same way as a package without a version.
+    pub async fn parse_range(
+        client: reqwest::Client,
+        package_name: &str,
+        range: &str,
+        network_mode: NetworkMode,
+    ) -> Result<Option<VersionRange>, CommandError> {
+        if range == LATEST {
+            return Ok(None);
+        }
+
+        let version = match Versions::is_dist_tag(range) {
+            true => {
+                let package_data =
+                    HTTPRequest::package_data(client, package_name, network_mode).await?;
+                package_data.dist_tags.get(range).cloned().ok_or_else(|| {
+                    CommandError::UnknownDistTag(package_name.to_string(), range.to_string())
+                })?
+            }
+            false => range.to_string(),
+        };
+
+        Versions::parse_semantic_version(&version)
+            .map(Some)
+            .map_err(CommandError::InvalidPackageSpec)
+    }
+
+    /// Where a dependency is installed from, see Source. A range or dist-tag is parsed with parse_range().
+    pub async fn parse_source(
+        client: reqwest::Client,
+        package_name: &str,
+        range: &str,
+        network_mode: NetworkMode,
+    ) -> Result<Source, CommandError> {
+        if let Some(source) = Source::from_spec(range) {
+            return Ok(source);
+        }
+
+        Self::parse_range(client, package_name, range, network_mode)
+            .await
+            .map(Source::Registry)
+    }
+
+    /// Parses a spec formatted as package@range, see parse_range().
+    pub async fn parse_package_spec(
+        client: reqwest::Client,
+        package_spec: String,
+        network_mode: NetworkMode,
+    ) -> Result<(String, Option<VersionRange>), CommandError> {
+        let (package_name, range) = Versions::parse_raw_package_details(package_spec);
+        let semantic_version =
+            Self::parse_range(client, &package_name, &range, network_mode).await?;
+
+        Ok((package_name, semantic_version))
+    }
+
     /// Gets the version data taking in the full version rather than resolving it on its own.
+    /// Only packuments are cached, so unless the network mode is online every version is resolved from the packument.
     pub async fn get_version_data(
         client: reqwest::Client,
-        package_name: &String,
+        package_name: &str,
         full_version: Option<&String>,
-        semantic_version: Option<&Comparator>,
+        semantic_version: Option<&VersionRange>,
+        network_mode: NetworkMode,
     ) -> Result<VersionData, CommandError> {
-        if let Some(version) = full_version {
+        if let (Some(version), NetworkMode::Online) = (full_version, network_mode) {
             return HTTPRequest::version_data(client.clone(), package_name, version).await;
         }
 
-        let mut package_data = HTTPRequest::package_data(client.clone(), package_name).await?;
-        let package_version =
-            Versions::resolve_partial_version(semantic_version, &package_data.versions)?;
+        let mut package_data =
+            HTTPRequest::package_data(client.clone(), package_name, network_mode).await?;
+        let package_version = match full_version {
+            Some(version) if version == LATEST => package_data
+                .dist_tags
+                .get(LATEST)
+                .cloned()
+                .ok_or(CommandError::InvalidVersion)?,
+            Some(version) => version.to_string(),
+            None => Versions::resolve_partial_version(
+                semantic_version,
+                &package_data.versions,
+                Strategy::Highest,
+            )?,
+        };
 
         Ok(package_data
             .versions
             .remove(&package_version)
-            .expect("Failed to find resolved package version in package data"))
+            .ok_or(CommandError::InvalidVersion)?
+            .into())
+    }
+
+    /// The version a range is installed at if it is known before looking at the cache, see
+    /// Versions::resolve_full_version(). The lowest version a range allows is always looked up in the packument, as
+    /// the cache would otherwise resolve the range to the highest version it has.
+    pub async fn full_version(
+        client: reqwest::Client,
+        package_name: &str,
+        semantic_version: Option<&VersionRange>,
+        strategy: Strategy,
+        network_mode: NetworkMode,
+    ) -> Result<Option<String>, CommandError> {
+        let full_version = Versions::resolve_full_version(semantic_version);
+        let is_exact = full_version
+            .as_ref()
+            .is_some_and(|version| version != LATEST);
+        if strategy == Strategy::Highest || semantic_version.is_none() || is_exact {
+            return Ok(full_version);
+        }
+
+        let package_data = HTTPRequest::package_data(client, package_name, network_mode).await?;
+        Versions::resolve_partial_version(semantic_version, &package_data.versions, strategy)
+            .map(Some)
     }
 
     // NOTE(conaticus): To save storage space, it might be an idea to check if the semantic version matches,
@@ -69,127 +524,908 @@ impl Installer {
         let installed_version = dependency_map.get(&stringified_version);
 
         match installed_version {
-            Some(_) => true,
+            Some(_) => {
+                // A package is only optional if every dependent that resolved it so far depends on it optionally
+                if context.optional_root.is_none() {
+                    if let Some(package_lock) = dependency_map.get_mut(&stringified_version) {
+                        package_lock.optional = false;
+                    }
+                }
+
+                true
+            }
             None => {
-                dependency_map.insert(
-                    stringified_version,
-                    PackageLock::new(package_info.is_latest),
-                );
+                let mut package_lock = PackageLock::new(package_info.is_latest);
+                package_lock.optional = context.optional_root.is_some();
+                package_lock.has_install_script = package_info.version_data.has_install_script;
+                package_lock.deprecated = package_info.version_data.deprecated.clone();
+                package_lock.bin = package_info
+                    .version_data
+                    .bin
+                    .as_ref()
+                    .map(|bin| bin.links(&package_info.version_data.name))
+                    .unwrap_or_default();
+
+                dependency_map.insert(stringified_version, package_lock);
                 false
             }
         }
     }
 
-    /// Append a version to a specific parent version, this hashmap will be used to generate package lock files.
-    fn append_version(
-        parents_mux: Arc<Mutex<Vec<String>>>,
-        new_version_name: String,
-        dependency_map_mux: DependencyMapMutex,
-    ) -> Result<(), CommandError> {
+    /// Records a direct dependency of a package, this hashmap will be used to generate package lock files.
+    /// Both are formatted as package@version, the dependent must already be in the hashmap.
+    fn append_dependency(
+        dependency_map_mux: &DependencyMapMutex,
+        dependent: &String,
+        dependency: String,
+    ) {
         let mut dependency_map = dependency_map_mux.lock().unwrap();
-        let parents = parents_mux.lock().unwrap();
+        let package_lock = dependency_map
+            .get_mut(dependent)
+            .expect("Dependent should be resolved before its dependencies");
 
-        for parent_version_name in parents.iter() {
-            let parent_version = dependency_map
-                .entry(parent_version_name.to_string())
-                .or_insert(PackageLock::new(parent_version_name.ends_with(LATEST)));
+        if !package_lock.dependencies.contains(&dependency) {
+            package_lock.dependencies.push(dependency);
+        }
+    }
 
-            parent_version
-                .dependencies
-                .push(new_version_name.to_string());
+    /// If an allowlist is in use, the package is refused unless its registry-provided integrity matches the allowlisted one.
+    fn check_allowlist(
+        context: &InstallContext,
+        package_info: &PackageInfo,
+    ) -> Result<(), CommandError> {
+        let allowlist = match &context.integrity_allowlist {
+            Some(allowlist) => allowlist,
+            None => return Ok(()),
+        };
+
+        let allowed_integrity = allowlist.get(&package_info.stringified);
+        match (allowed_integrity, &package_info.version_data.dist.integrity) {
+            (Some(allowed), Some(integrity)) if allowed == integrity => Ok(()),
+            _ => Err(CommandError::IntegrityNotAllowlisted(
+                package_info.stringified.to_string(),
+            )),
         }
+    }
 
-        Ok(())
+    /// Returns a warning when a version does not support the installed Node, or with engine-strict refuses to install it.
+    pub fn check_engines(
+        package: &String,
+        version_data: &VersionData,
+        engine_strict: bool,
+    ) -> Result<Option<InstallWarning>, CommandError> {
+        let (Some(required), Some(node_version)) = (
+            Engines::unsatisfied_node_range(version_data),
+            Engines::node_version(),
+        ) else {
+            return Ok(None);
+        };
+
+        if engine_strict {
+            return Err(CommandError::UnsupportedEngine {
+                package: package.to_string(),
+                required,
+                installed: node_version.to_string(),
+            });
+        }
+
+        Ok(Some(InstallWarning::new(
+            WarningCode::EngineMismatch,
+            Some(package),
+            format!("'{package}' requires Node {required}, but {node_version} is installed"),
+        )))
     }
 
+    /// `dependent` is the package that depends on this one, or None if this is the package being installed.
     pub fn install_package(
         context: InstallContext,
         package_info: PackageInfo,
-        parents_mux: Arc<Mutex<Vec<String>>>,
+        dependent: Option<String>,
     ) -> Result<(), CommandError> {
+        Self::check_allowlist(&context, &package_info)?;
+
+        // This has to be done even if the package has already been resolved through another dependent
+        if let Some(dependent) = &dependent {
+            Self::append_dependency(
+                &context.dependency_map_mux,
+                dependent,
+                package_info.stringified.to_string(),
+            );
+        }
+
         if Self::already_resolved(&context, &package_info) {
             return Ok(());
         }
 
-        Self::append_version(
-            Arc::clone(&parents_mux),
-            package_info.stringified.to_string(),
-            Arc::clone(&context.dependency_map_mux),
+        context.reporter.report(ProgressEvent::Resolved {
+            package: &package_info.stringified,
+        });
+
+        TaskAllocator::add_task(async move {
+            let errors_mux = Arc::clone(&context.errors_mux);
+            let failed_packages_mux = Arc::clone(&context.failed_packages_mux);
+            let stringified = package_info.stringified.to_string();
+            let optional_root = context.optional_root.clone();
+            let skipped_optional_mux = Arc::clone(&context.skipped_optional_mux);
+            let reporter = Arc::clone(&context.reporter);
+
+            let result = Self::download_package(context, package_info).await;
+            if result.is_err() {
+                reporter.report(ProgressEvent::Failed {
+                    package: &stringified,
+                });
+            }
+
+            match (result, optional_root) {
+                (Ok(_), _) => {}
+                (Err(err), Some(optional_root)) => {
+                    let mut skipped_optional = skipped_optional_mux.lock().unwrap();
+                    skipped_optional
+                        .entry(optional_root)
+                        .or_insert(err.to_string());
+                    skipped_optional
+                        .entry(stringified)
+                        .or_insert(err.to_string());
+                }
+                (Err(err), None) => {
+                    errors_mux.lock().unwrap().push(err);
+                    failed_packages_mux.lock().unwrap().insert(stringified);
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Downloads the package tarball, queues it for extraction and then installs its dependencies.
+    async fn download_package(
+        context: InstallContext,
+        package_info: PackageInfo,
+    ) -> Result<(), CommandError> {
+        let version_data = package_info.version_data;
+        let peer_dependencies = match context.legacy_peer_deps {
+            true => Vec::new(),
+            false => version_data.required_peer_dependencies(),
+        };
+
+        // Locked packages always get the same dependencies, rather than whatever their ranges resolve to now
+        let locked_dependencies = context
+            .lockfile
+            .as_ref()
+            .and_then(|lockfile| lockfile.dependencies(&package_info.stringified));
+        let is_locked = locked_dependencies.is_some();
+
+        // Optional dependencies are also listed as regular dependencies by npm, the optional range is the one used
+        let bundled = version_data.bundled_dependencies();
+        let optional_dependencies = version_data.optional_dependencies.unwrap_or_default();
+        let (dependencies, optional_dependencies) = match (locked_dependencies, &context.lockfile) {
+            (Some(locked_dependencies), Some(lockfile)) => {
+                let optional_dependencies = locked_dependencies
+                    .iter()
+                    .filter(|(name, version)| {
+                        lockfile.is_optional(&Versions::stringify(name, version))
+                    })
+                    .map(|(name, _)| name.to_string())
+                    .collect();
+
+                (locked_dependencies, optional_dependencies)
+            }
+            _ => {
+                let optional_names = optional_dependencies.keys().cloned().collect();
+                let mut dependencies = version_data.dependencies.unwrap_or_default();
+                dependencies.extend(optional_dependencies);
+                dependencies.retain(|name, _| !bundled.contains(name));
+
+                let dependencies = dependencies.into_iter().chain(peer_dependencies).collect();
+                (dependencies, optional_names)
+            }
+        };
+
+        // The package was extracted before the previous install was interrupted so only its dependencies are left to do
+        let resumed = context.resumed_packages.get(&package_info.stringified);
+        if let Some(extracted) = resumed.filter(|_| {
+            Path::new(&format!(
+                "{}/{}",
+                *CACHE_DIRECTORY, package_info.stringified
+            ))
+            .exists()
+        }) {
+            Self::lock_extracted(&context, &package_info.stringified, extracted.clone())?;
+            return Self::install_dependencies(
+                &package_info.stringified,
+                context,
+                dependencies,
+                optional_dependencies,
+            )
+            .await;
+        }
+
+        // Packages from git, files and tarball URLs have build metadata in their version and no signatures, and those
+        // in the lockfile were checked when they were locked
+        let from_registry =
+            Version::parse(&version_data.version).is_ok_and(|version| version.build.is_empty());
+        if from_registry && !is_locked {
+            Signatures::verify(
+                context.client.clone(),
+                &version_data.name,
+                &version_data.version,
+                &version_data.dist,
+                context.strict_signatures,
+            )
+            .await?;
+        }
+
+        let config = Config::get()?;
+        let tarball = config.tarball_url(&version_data.name, &version_data.dist.tarball);
+        let digests = Self::fetch_tarball(
+            &context,
+            &package_info.stringified,
+            &version_data.dist,
+            &tarball,
         )
-        .unwrap();
+        .await?;
 
-        {
-            let mut parents = parents_mux.lock().unwrap();
-            parents.push(package_info.stringified.to_string());
+        context.reporter.report(ProgressEvent::Extracted {
+            package: &package_info.stringified,
+        });
+
+        let extracted = ExtractedPackage {
+            shasum: digests.shasum(),
+            integrity: digests.integrity(),
+            resolved: tarball,
+            registry: config.registry_for(&version_data.name).to_string(),
+        };
+        Self::lock_extracted(&context, &package_info.stringified, extracted)?;
+
+        Self::install_dependencies(
+            &package_info.stringified,
+            context,
+            dependencies,
+            optional_dependencies,
+        )
+        .await
+    }
+
+    /// Extracts the tarball of a package into the cache, from the tarball cache if it is there or otherwise the registry.
+    /// Downloaded tarballs are added to the tarball cache once they have been checked.
+    async fn fetch_tarball(
+        context: &InstallContext,
+        package: &String,
+        dist: &Dist,
+        tarball: &str,
+    ) -> Result<TarballDigests, CommandError> {
+        if let Some(file) = TarballCache::open(dist).await {
+            match Self::stream_tarball(package, dist, TarballSource::Cached(file), None, None)
+                .await?
+            {
+                Some(digests) => return Ok(digests),
+                // The cached tarball has been corrupted, so it is replaced by downloading it again
+                None => {
+                    let _ = TarballCache::remove(dist).await;
+                }
+            }
         }
 
-        TaskAllocator::add_task(async move {
-            let version_data = package_info.version_data;
+        // A package from git is fetched at its locked commit and packed again, which puts it in the tarball cache. A
+        // tarball from a URL is downloaded the same way as one from the registry.
+        let source = Source::from_spec(tarball).filter(|source| !source.is_tarball());
+        if let Some(source) = source {
+            let (package_name, _) = Versions::parse_raw_package_details(package.to_string());
+            source
+                .version_data(
+                    context.client.clone(),
+                    &package_name,
+                    None,
+                    context.network_mode,
+                )
+                .await?;
 
-            let package_bytes =
-                HTTPRequest::get_bytes(context.client.clone(), version_data.dist.tarball)
-                    .await
-                    .unwrap();
+            let file = TarballCache::open(dist)
+                .await
+                .ok_or_else(|| CommandError::IntegrityMismatch(package.to_string()))?;
+            return Self::stream_tarball(package, dist, TarballSource::Cached(file), None, None)
+                .await?
+                .ok_or_else(|| CommandError::IntegrityMismatch(package.to_string()));
+        }
+
+        if context.network_mode == NetworkMode::Offline {
+            return Err(CommandError::NotInOfflineCache(package.to_string()));
+        }
+
+        // The permit is held until the whole tarball has been downloaded
+        let _permit = context.request_permits.acquire().await.unwrap();
+
+        // A download that is cut off is kept, so that the next attempt only asks for the rest of it
+        let retry_policy = &Config::get()?.retry_policy;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+
+            match Self::download_tarball(context, package, dist, tarball).await {
+                Err(CommandError::FailedResponseBytes(err)) if attempts <= retry_policy.retries => {
+                    let delay = retry_policy.delay(attempts);
+                    debug!(
+                        "the download of '{package}' was cut off ({err}), resuming it in {}ms",
+                        delay.as_millis()
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
 
-            let package_destination = format!("{}/{}", *CACHE_DIRECTORY, package_info.stringified);
+    /// Downloads a tarball into the cache, carrying on from the partial download of it if there is one. A tarball that
+    /// does not match its integrity may have been corrupted on the way, so it is downloaded again as many times as
+    /// `fetch-retries` allows, then from each mirror of the registry in turn, before the install fails.
+    async fn download_tarball(
+        context: &InstallContext,
+        package: &String,
+        dist: &Dist,
+        tarball: &str,
+    ) -> Result<TarballDigests, CommandError> {
+        let config = Config::get()?;
+        let sources = config.mirrored_urls(tarball);
+        let mut mirror = 0;
+        let mut attempts = 0;
 
-            // TODO(conaticus): Do this outside of tokio tasks as it's blocking the threads from working at full potential
-            context
-                .bytes_sender
-                .send((package_destination, package_bytes))
-                .unwrap();
+        loop {
+            let tarball = sources[mirror].as_str();
 
-            let dependencies = version_data.dependencies.unwrap_or(HashMap::new());
-            Self::install_dependencies(parents_mux, context, dependencies).await;
+            // The tarball is being downloaded either way, so the install does not need the cache to carry on
+            let mut resumed = TarballCache::resume(dist).await.unwrap_or_else(|err| {
+                warn!("failed to resume the download of '{package}' ({err})");
+                None
+            });
+
+            let from = resumed.as_ref().map_or(0, |(_, _, length)| *length);
+            let response =
+                match HTTPRequest::get_stream(context.client.clone(), tarball, from).await {
+                    Ok(response) => response,
+                    // The partial download is longer than the tarball, so it cannot be part of it
+                    Err(CommandError::HTTPFailed(err))
+                        if err.status() == Some(StatusCode::RANGE_NOT_SATISFIABLE) =>
+                    {
+                        if let Some((writer, ..)) = resumed.take() {
+                            writer.discard().await;
+                        }
+                        HTTPRequest::get_stream(context.client.clone(), tarball, 0).await?
+                    }
+                    Err(err) => return Err(err),
+                };
+            context.reporter.report(ProgressEvent::DownloadStarted {
+                package,
+                size: response.content_length(),
+            });
+
+            let (writer, source) = match resumed {
+                Some((writer, partial, _)) if response.status() == StatusCode::PARTIAL_CONTENT => {
+                    debug!("resuming the download of '{package}' after {from} bytes");
+                    (
+                        Some(writer),
+                        TarballSource::Resumed(Some(partial), response),
+                    )
+                }
+                resumed => {
+                    // The registry ignored the range and sent the whole tarball
+                    if let Some((writer, ..)) = resumed {
+                        writer.discard().await;
+                    }
+
+                    let writer = TarballCache::create(dist).await.unwrap_or_else(|err| {
+                        warn!("failed to cache the tarball of '{package}' ({err})");
+                        None
+                    });
+                    (writer, TarballSource::Download(response))
+                }
+            };
+
+            match Self::stream_tarball(package, dist, source, writer, Some(&*context.reporter))
+                .await?
+            {
+                Some(digests) => return Ok(digests),
+                // The part downloaded before may be what did not match, it has been removed so this starts over
+                None if from > 0 => {
+                    debug!("the resumed download of '{package}' did not match its integrity, downloading it again")
+                }
+                None if attempts < config.retry_policy.retries => {
+                    attempts += 1;
+                    warn!("'{package}' did not match its integrity, downloading it again");
+                    tokio::time::sleep(config.retry_policy.delay(attempts)).await;
+                }
+                None if mirror + 1 < sources.len() => {
+                    mirror += 1;
+                    attempts = 0;
+                    warn!(
+                        "'{package}' did not match its integrity, falling back to '{}'",
+                        sources[mirror]
+                    );
+                }
+                None => return Err(CommandError::IntegrityMismatch(package.to_string())),
+            }
+        }
+    }
+
+    /// Extracts a tarball as it is read, hashing it on the way so that it is never held in memory.
+    /// Files are extracted into a temporary directory, which only replaces the package in the cache if the tarball is
+    /// exactly what the registry says was published. Returns None if it is not.
+    async fn stream_tarball(
+        package: &String,
+        dist: &Dist,
+        mut source: TarballSource,
+        mut writer: Option<TarballWriter>,
+        reporter: Option<&dyn Reporter>, // Told about every chunk, only given for downloads
+    ) -> Result<Option<TarballDigests>, CommandError> {
+        // Versions contain dots, so the suffix is appended rather than replacing an extension
+        let destination = format!("{}/{}", *CACHE_DIRECTORY, package);
+        let temporary_destination = util::long_path(Path::new(&format!(
+            "{destination}.{}.tmp",
+            std::process::id()
+        )))
+        .map_err(CommandError::FailedToWriteFile)?;
+        let destination =
+            util::long_path(Path::new(&destination)).map_err(CommandError::FailedToWriteFile)?;
+        if temporary_destination.exists() {
+            fs::remove_dir_all(&temporary_destination).map_err(CommandError::FailedToWriteFile)?;
+        }
+
+        let (chunk_sender, chunk_receiver) = tokio::sync::mpsc::channel::<Bytes>(CHUNK_BUFFER);
+        let extraction_destination = temporary_destination.clone();
+        let extraction = TaskAllocator::add_blocking(move || {
+            util::extract_tarball(ChunkReader::new(chunk_receiver), &extraction_destination)
         });
 
-        Ok(())
+        let mut hasher = TarballHasher::new(dist);
+        let mut chunk_sender = Some(chunk_sender);
+        let streamed = loop {
+            let chunk = match source.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break Ok(()),
+                Err(err) => break Err(err),
+            };
+
+            hasher.update(&chunk);
+
+            // What was downloaded before is already in the file being written
+            let downloading = source.is_downloading();
+            if let (Some(reporter), true) = (reporter, downloading) {
+                reporter.report(ProgressEvent::DownloadProgress {
+                    package,
+                    bytes: chunk.len(),
+                });
+            }
+
+            if let (Some(cache_writer), true) = (&mut writer, downloading) {
+                if let Err(err) = cache_writer.write(&chunk).await {
+                    warn!("failed to cache the tarball of '{package}' ({err})");
+                    writer.take().unwrap().discard().await;
+                }
+            }
+
+            // Extraction can stop before the end of the tarball, such as at the padding after the last entry
+            if let Some(sender) = &chunk_sender {
+                if sender.send(chunk).await.is_err() {
+                    chunk_sender = None;
+                }
+            }
+        };
+
+        // Extraction finishes once it has been sent everything
+        drop(chunk_sender);
+        let extracted = extraction.await.expect("Failed to join extraction task");
+        let digests = hasher.finish();
+
+        let verified = streamed.is_ok() && digests.verify(dist);
+        if !verified || extracted.is_err() {
+            match (writer, &streamed) {
+                // Only the rest of it has to be downloaded the next time, its hash is checked once it is whole
+                (Some(writer), Err(_)) => {
+                    if let Err(err) = writer.keep_partial().await {
+                        warn!("failed to keep the partial download of '{package}' ({err})");
+                    }
+                }
+                (Some(writer), Ok(_)) => writer.discard().await,
+                (None, _) => {}
+            }
+            if temporary_destination.exists() {
+                fs::remove_dir_all(&temporary_destination)
+                    .map_err(CommandError::FailedToWriteFile)?;
+            }
+
+            return match (streamed, verified) {
+                (Err(err), _) => Err(err),
+                (Ok(_), false) => Ok(None),
+                (Ok(_), true) => extracted.map(|_| None),
+            };
+        }
+
+        if let Some(writer) = writer {
+            if let Err(err) = writer.finish().await {
+                warn!("failed to cache the tarball of '{package}' ({err})");
+            }
+        }
+
+        // A previous install may have been interrupted after extracting part of the package
+        if destination.exists() {
+            fs::remove_dir_all(&destination).map_err(CommandError::FailedToWriteFile)?;
+        }
+        fs::rename(&temporary_destination, &destination)
+            .map_err(CommandError::FailedToWriteFile)?;
+
+        Ok(Some(digests))
     }
 
+    /// Without keep_going the first dependency that fails stops the rest from being installed.
+    /// With it every dependency is attempted, and the first error is returned once they all have been so the dependent is marked as failed.
+    /// Optional dependencies that fail are skipped instead, see InstallContext::optional_root.
     async fn install_dependencies(
-        parents_mux: Arc<Mutex<Vec<String>>>,
+        dependent: &String,
         context: InstallContext,
-        dependencies: HashMap<String, String>,
-    ) {
+        dependencies: Vec<(String, String)>, // Name and version range of each dependency
+        optional_dependencies: HashSet<String>, // Names of the dependencies that are optional
+    ) -> Result<(), CommandError> {
+        let mut first_error = None;
+
         for (name, version) in dependencies {
-            let comparator = Versions::parse_semantic_version(&version)
-                .expect("Failed to parse semantic version"); // TODO(conaticus): Change this to return a result
-            let comparator = Some(&comparator);
+            let optional = optional_dependencies.contains(&name);
+            let result =
+                Self::install_dependency(dependent, &context, name.to_string(), version, optional)
+                    .await;
 
-            let full_version = Versions::resolve_full_version(comparator);
-            let full_version = full_version.as_ref();
+            match (result, &first_error) {
+                (Ok(_), _) => continue,
+                // It could not be resolved, so it is skipped before anything was added for it
+                (Err(err), _) if optional => {
+                    let mut skipped_optional = context.skipped_optional_mux.lock().unwrap();
+                    skipped_optional.entry(name).or_insert(err.to_string());
+                }
+                (Err(err), _) if !context.keep_going => return Err(err),
+                (Err(err), None) => first_error = Some(err),
+                (Err(err), Some(_)) => context.errors_mux.lock().unwrap().push(err),
+            }
+        }
 
-            let (is_cached, cached_version) = Cache::exists(&name, full_version, comparator)
-                .await
-                .unwrap();
+        match first_error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
 
-            if is_cached {
-                let version = cached_version.expect("Could not resolve version of cached package");
-                let stringified = Versions::stringify(&name, &version);
+    async fn install_dependency(
+        dependent: &String,
+        context: &InstallContext,
+        name: String,
+        version: String,
+        optional: bool,
+    ) -> Result<(), CommandError> {
+        let source = Self::parse_source(
+            context.client.clone(),
+            &name,
+            &version,
+            context.network_mode,
+        )
+        .await?;
+        let semantic_version = source.semantic_version();
 
-                // We need to do this check as the pre-cached version may have just been installed (and therefore have no lockfile which is mandatory)
-                let dependency_map = context.dependency_map_mux.lock().unwrap();
-                if dependency_map.get(stringified.as_str()).is_none() {
-                    Cache::load_cached_version(stringified);
-                    continue;
-                }
+        // A locked version from git keeps the build metadata its range would drop, see Source::version_data()
+        let full_version = match Version::from_str(&version) {
+            Ok(exact) if !exact.build.is_empty() => Some(version.to_string()),
+            _ if !source.is_registry() => None,
+            _ => {
+                Self::full_version(
+                    context.client.clone(),
+                    &name,
+                    semantic_version,
+                    context.strategy,
+                    context.network_mode,
+                )
+                .await?
             }
+        };
+        let full_version = full_version.as_ref();
 
-            let version_data =
-                Self::get_version_data(context.client.clone(), &name, full_version, comparator)
-                    .await
-                    .unwrap();
+        let can_use_cache = source.is_registry() || full_version.is_some();
+        let (is_cached, cached_version) =
+            match can_use_cache && context.strategy == Strategy::Highest {
+                true => Cache::exists(&name, full_version, semantic_version).await?,
+                false => (false, None),
+            };
 
-            let stringified = Versions::stringify(&name, &version_data.version);
+        if is_cached {
+            let version = cached_version.expect("Could not resolve version of cached package");
+            let stringified = Versions::stringify(&name, &version);
+            debug!("'{name}@{version}' resolved to {stringified}, which is cached");
 
-            let package_info = PackageInfo {
-                version_data,
-                is_latest: Versions::is_latest(Some(&stringified)),
-                stringified,
+            // We need to do this check as the pre-cached version may have just been installed (and therefore have no lockfile which is mandatory)
+            let just_installed = context
+                .dependency_map_mux
+                .lock()
+                .unwrap()
+                .contains_key(stringified.as_str());
+
+            if !just_installed {
+                // Its own dependencies are read from its lockfile when the dependent's lockfile is written
+                Self::append_dependency(
+                    &context.dependency_map_mux,
+                    dependent,
+                    stringified.to_string(),
+                );
+
+                let cached_packages = Cache::cached_packages(&stringified)?;
+                context
+                    .cached_packages_mux
+                    .lock()
+                    .unwrap()
+                    .extend(cached_packages);
+                return Ok(());
+            }
+        }
+
+        let locked_version_data = context
+            .lockfile
+            .as_ref()
+            .zip(full_version)
+            .and_then(|(lockfile, version)| lockfile.version_data(&name, version));
+
+        let version_data = match locked_version_data {
+            Some(version_data) => {
+                debug!("'{name}@{version}' resolved from the lockfile");
+                version_data
+            }
+            None => {
+                let _permit = context.request_permits.acquire().await.unwrap();
+                source
+                    .version_data(
+                        context.client.clone(),
+                        &name,
+                        full_version,
+                        context.network_mode,
+                    )
+                    .await?
+            }
+        };
+
+        let stringified = Versions::stringify(&name, &version_data.version);
+        debug!("'{name}@{version}' resolved to {stringified}");
+
+        // Packages that ship a binary for each platform list them all as optional dependencies, so those are left out quietly
+        if !context.platform.supports(&version_data) {
+            return match optional {
+                true => Ok(()),
+                false => Err(CommandError::UnsupportedPlatform(
+                    stringified,
+                    context.platform.to_string(),
+                )),
             };
+        }
+
+        if let Some(warning) =
+            Self::check_engines(&stringified, &version_data, context.engine_strict)?
+        {
+            context.warnings_mux.lock().unwrap().push(warning);
+        }
+
+        let package_info = PackageInfo {
+            version_data,
+            is_latest: Versions::is_latest(full_version),
+            stringified,
+        };
+
+        let mut context = context.clone();
+        if optional && context.optional_root.is_none() {
+            context.optional_root = Some(package_info.stringified.to_string());
+        }
+
+        Self::install_package(context, package_info, Some(dependent.to_string()))
+    }
+
+    /// Records what an extracted package is locked with, and that it was extracted in case the install is interrupted.
+    fn lock_extracted(
+        context: &InstallContext,
+        package: &String,
+        extracted: ExtractedPackage,
+    ) -> Result<(), CommandError> {
+        {
+            let mut dependency_map = context.dependency_map_mux.lock().unwrap();
+            if let Some(package_lock) = dependency_map.get_mut(package) {
+                package_lock.shasum = Some(extracted.shasum.clone());
+                package_lock.integrity = Some(extracted.integrity.clone());
+                package_lock.resolved = Some(extracted.resolved.clone());
+                package_lock.registry = Some(extracted.registry.clone());
+            }
+        }
 
-            Self::install_package(context.clone(), package_info, Arc::clone(&parents_mux)).unwrap();
+        if let Some(install_state_mux) = &context.install_state_mux {
+            let mut install_state = install_state_mux.lock().unwrap();
+            install_state
+                .extracted
+                .insert(package.to_string(), extracted);
+            Self::write_install_state(&install_state)?;
         }
+
+        Ok(())
+    }
+
+    /// Reads the progress of a previous install which did not complete.
+    /// If there is no state file, or it cannot be read, the install starts from scratch.
+    pub fn read_install_state() -> InstallState {
+        fs::read_to_string(INSTALL_STATE_PATH)
+            .ok()
+            .and_then(|state_raw| serde_json::from_str::<InstallState>(&state_raw).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn write_install_state(install_state: &InstallState) -> Result<(), CommandError> {
+        let install_state_string = serde_json::to_string(install_state)
+            .map_err(CommandError::FailedToSerializeInstallState)?;
+
+        fs::write(INSTALL_STATE_PATH, install_state_string).map_err(CommandError::FailedToWriteFile)
+    }
+
+    /// Should only be called once an install has fully completed.
+    pub fn clear_install_state() {
+        if Path::new(INSTALL_STATE_PATH).exists() {
+            fs::remove_file(INSTALL_STATE_PATH).expect("Failed to remove install state file");
+        }
+    }
+
+    /// Returns every package linked into node_modules, formatted as package@version.
+    /// Each link points at `<cache>/package@version/package`, or `.click/package@version/node_modules/<name>` with the
+    /// isolated layout, which is how the version is found. Links to workspaces are left out.
+    pub fn linked_packages() -> Result<Vec<String>, CommandError> {
+        let modules_dir = match fs::read_dir(MODULES_DIRECTORY) {
+            Ok(modules_dir) => modules_dir,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(CommandError::FailedToReadFile(err)),
+        };
+
+        let mut packages = Vec::new();
+        let mut entries = modules_dir.collect::<Vec<_>>();
+        while let Some(entry) = entries.pop() {
+            let entry = entry.map_err(CommandError::FailedDirectoryEntry)?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+
+            // Files such as the install state are not packages
+            if file_name.starts_with('.') {
+                continue;
+            }
+
+            // Scoped packages are linked in the directory of their scope
+            if file_name.starts_with('@') && !entry.path().is_symlink() {
+                let scope_dir =
+                    fs::read_dir(entry.path()).map_err(CommandError::FailedToReadFile)?;
+                entries.extend(scope_dir);
+                continue;
+            }
+
+            // A package with dependencies nested in it is a directory of links with the hoisted layout
+            let link_target = match fs::read_link(entry.path()) {
+                Ok(link_target) => link_target,
+                Err(_) => match fs::read_link(entry.path().join("package.json")) {
+                    Ok(file_target) => match file_target.parent() {
+                        Some(link_target) => link_target.to_path_buf(),
+                        None => continue,
+                    },
+                    Err(_) => continue, // Not linked by click
+                },
+            };
+
+            // Anything else, such as a workspace, is not a package from the cache
+            let package = link_target.parent().and_then(|parent| {
+                // The scope of a package is a directory of its own
+                let parent = match parent.file_name()?.to_string_lossy().starts_with('@') {
+                    true => parent.parent()?,
+                    false => parent,
+                };
+                let package = match parent.file_name()? == "node_modules" {
+                    true => IsolatedLayout::package_from_key(
+                        &parent.parent()?.file_name()?.to_string_lossy(),
+                    ),
+                    false => Patches::package_of(link_target.parent()?)?,
+                };
+
+                Some(package)
+            });
+
+            if let Some(package) = package {
+                packages.push(package);
+            }
+        }
+
+        packages.sort();
+        Ok(packages)
+    }
+
+    /// Links the executables of every package in node_modules into node_modules/.bin so that scripts can run them by name,
+    /// and removes those of packages that are no longer installed. Packages installed before executables were recorded
+    /// in their lockfile are not linked until they are downloaded again. `platform` is the kind of shims that are written.
+    /// Nothing is linked if the project was installed with --no-bin-links, so other commands keep it that way.
+    pub fn link_bins(platform: ShimPlatform) -> Result<(), CommandError> {
+        if Lockfile::read()?.no_bin_links {
+            return Ok(());
+        }
+
+        let mut bins = BTreeMap::new(); // Name of each executable to the package providing it and its path within the package
+        for package in Self::linked_packages()? {
+            let (package_name, _) = Versions::parse_raw_package_details(package.to_string());
+
+            for (bin_name, path) in Cache::read_lock(&package)?.bin {
+                bins.insert(bin_name, (package_name.to_string(), path));
+            }
+        }
+
+        Self::remove_stale_bins(&bins)?;
+
+        if bins.is_empty() {
+            return Ok(());
+        }
+
+        fs::create_dir_all(BIN_DIRECTORY).map_err(CommandError::FailedToCreateFile)?;
+
+        for (bin_name, (package_name, path)) in bins.iter() {
+            match Shims::link(bin_name, package_name, path, platform) {
+                Ok(_) => continue,
+                // Some packages are published with executables that are not in their tarball
+                Err(err) if err.kind() == ErrorKind::NotFound => warn!("skipped executable '{bin_name}' of '{package_name}' as '{path}' does not exist"
+                ),
+                Err(err) => return Err(CommandError::FailedToLinkBin(bin_name.to_string(), err)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes every entry of node_modules/.bin that is not one of the given executables.
+    fn remove_stale_bins(bins: &BTreeMap<String, (String, String)>) -> Result<(), CommandError> {
+        let bin_dir = match fs::read_dir(BIN_DIRECTORY) {
+            Ok(bin_dir) => bin_dir,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(CommandError::FailedToReadFile(err)),
+        };
+
+        for entry in bin_dir {
+            let entry = entry.map_err(CommandError::FailedDirectoryEntry)?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+
+            // Shims on Windows are named after the executable with an extension
+            let bin_name = file_name
+                .strip_suffix(".cmd")
+                .or(file_name.strip_suffix(".ps1"))
+                .unwrap_or(&file_name);
+
+            if !bins.contains_key(bin_name) {
+                fs::remove_file(entry.path())
+                    .map_err(|err| CommandError::FailedToLinkBin(bin_name.to_string(), err))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every path an install uses is relative to the working directory, which is the install prefix. Global installs change it
+    /// to a directory in the home directory, which has its own package.json, lockfile and node_modules in the same way a project does.
+    /// Should be called before anything else is read. Returns the global directory.
+    pub fn use_global_prefix() -> Result<PathBuf, CommandError> {
+        let global_directory = dirs::home_dir()
+            .ok_or(CommandError::NoHomeDirectory)?
+            .join(GLOBAL_DIRECTORY);
+
+        fs::create_dir_all(&global_directory).map_err(CommandError::FailedToCreateFile)?;
+        env::set_current_dir(&global_directory).map_err(CommandError::FailedToReadFile)?;
+
+        Ok(global_directory)
+    }
+
+    /// Returns the absolute path of node_modules/.bin if it is not on PATH yet, so that users can be told to add it.
+    pub fn bin_directory_not_on_path() -> Option<PathBuf> {
+        let bin_directory = env::current_dir().ok()?.join(BIN_DIRECTORY);
+        let bin_directory = fs::canonicalize(&bin_directory).unwrap_or(bin_directory);
+
+        let path = env::var_os("PATH").unwrap_or_default();
+        let on_path = env::split_paths(&path)
+            .any(|dir| fs::canonicalize(&dir).unwrap_or(dir) == bin_directory);
+
+        (!on_path).then_some(bin_directory)
     }
 
     /// Creates the node modules folder if it is not present.
@@ -0,0 +1,237 @@
+use std::{fs, io, path::Path};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use openssl::sha::{self, Sha1, Sha256, Sha384, Sha512};
+
+use crate::types::Dist;
+
+// Written into the extracted package by click itself, so it is not part of the package contents.
+const LOCKFILE_NAME: &str = "click-lock.json";
+
+/// Hashes a tarball as it is downloaded, so that it can be checked without holding all of it in memory.
+/// SHA-1 and SHA-512 are always computed as they are recorded in lockfiles, other algorithms only if the integrity uses them.
+pub struct TarballHasher {
+    sha1: Sha1,
+    sha512: Sha512,
+    sha384: Option<Sha384>,
+    sha256: Option<Sha256>,
+}
+
+impl TarballHasher {
+    pub fn new(dist: &Dist) -> Self {
+        let uses = |algorithm: &str| {
+            dist.integrity.as_ref().is_some_and(|integrity| {
+                integrity.split_whitespace().any(|hash| {
+                    hash.split_once('-')
+                        .is_some_and(|(name, _)| name == algorithm)
+                })
+            })
+        };
+
+        Self {
+            sha1: Sha1::new(),
+            sha512: Sha512::new(),
+            sha384: uses("sha384").then(Sha384::new),
+            sha256: uses("sha256").then(Sha256::new),
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.sha1.update(bytes);
+        self.sha512.update(bytes);
+
+        if let Some(sha384) = &mut self.sha384 {
+            sha384.update(bytes);
+        }
+        if let Some(sha256) = &mut self.sha256 {
+            sha256.update(bytes);
+        }
+    }
+
+    pub fn finish(self) -> TarballDigests {
+        TarballDigests {
+            sha1: self.sha1.finish(),
+            sha512: self.sha512.finish(),
+            sha384: self.sha384.map(Sha384::finish),
+            sha256: self.sha256.map(Sha256::finish),
+        }
+    }
+}
+
+pub struct TarballDigests {
+    sha1: [u8; 20],
+    sha512: [u8; 64],
+    sha384: Option<[u8; 48]>,
+    sha256: Option<[u8; 32]>,
+}
+
+impl TarballDigests {
+    /// The hex encoded SHA-1 digest, the same format the registry provides in `dist.shasum`.
+    pub fn shasum(&self) -> String {
+        Integrity::hex(&self.sha1)
+    }
+
+    /// The SHA-512 subresource integrity string, the same format the registry provides in `dist.integrity`.
+    pub fn integrity(&self) -> String {
+        format!("sha512-{}", STANDARD.encode(self.sha512))
+    }
+
+    /// Returns None if the algorithm is not supported.
+    fn get(&self, algorithm: &str) -> Option<&[u8]> {
+        match algorithm {
+            "sha512" => Some(&self.sha512),
+            "sha384" => self.sha384.as_ref().map(|digest| digest.as_slice()),
+            "sha256" => self.sha256.as_ref().map(|digest| digest.as_slice()),
+            "sha1" => Some(&self.sha1),
+            _ => None,
+        }
+    }
+
+    /// Checks the tarball against the integrity the registry provided for it.
+    /// The integrity string can list several hashes (`algorithm-<base64>`), it is enough for one of them to match
+    /// as long as its algorithm is supported. If there is no supported hash the shasum is checked instead,
+    /// and if the registry provided neither there is nothing to check against.
+    pub fn verify(&self, dist: &Dist) -> bool {
+        let mut checked = false;
+
+        let hashes = dist
+            .integrity
+            .iter()
+            .flat_map(|integrity| integrity.split_whitespace());
+        for hash in hashes {
+            let Some((algorithm, expected)) = hash.split_once('-') else {
+                continue;
+            };
+
+            // Options can follow the digest, separated by a question mark
+            let expected = expected.split('?').next().unwrap_or_default();
+
+            if let Some(digest) = self.get(algorithm) {
+                if STANDARD.encode(digest) == expected {
+                    return true;
+                }
+                checked = true;
+            }
+        }
+
+        match &dist.shasum {
+            Some(shasum) if !checked => shasum.eq_ignore_ascii_case(&self.shasum()),
+            _ => !checked,
+        }
+    }
+}
+
+pub struct Integrity;
+impl Integrity {
+    /// Computes the SHA-512 subresource integrity string (`sha512-<base64>`) of a tarball,
+    /// the same format the registry provides in `dist.integrity`.
+    pub fn sha512(bytes: &[u8]) -> String {
+        format!("sha512-{}", STANDARD.encode(sha::sha512(bytes)))
+    }
+
+    /// Computes the hex encoded SHA-1 digest of a tarball, the same format the registry provides in `dist.shasum`.
+    pub fn shasum(bytes: &[u8]) -> String {
+        Self::hex(&sha::sha1(bytes))
+    }
+
+    /// Checks a downloaded tarball against the integrity the registry provided for it, see TarballDigests::verify().
+    pub fn verify(bytes: &[u8], dist: &Dist) -> bool {
+        let mut hasher = TarballHasher::new(dist);
+        hasher.update(bytes);
+        hasher.finish().verify(dist)
+    }
+
+    /// Returns the algorithm and hex encoded digest of the first supported hash the registry provided for a tarball,
+    /// falling back to its shasum. Returns None if there is neither.
+    pub fn first_supported_hash(dist: &Dist) -> Option<(String, String)> {
+        let hash = dist
+            .integrity
+            .iter()
+            .flat_map(|integrity| Self::hex_hashes(integrity))
+            .find(|(algorithm, _)| Self::digest(algorithm, &[]).is_some());
+        if hash.is_some() {
+            return hash;
+        }
+
+        dist.shasum
+            .as_ref()
+            .map(|shasum| (String::from("sha1"), shasum.to_ascii_lowercase()))
+    }
+
+    /// Returns the algorithm and hex encoded digest of every hash in an integrity string such as `sha512-<base64>`,
+    /// leaving out any that cannot be decoded.
+    pub fn hex_hashes(integrity: &str) -> Vec<(String, String)> {
+        integrity
+            .split_whitespace()
+            .filter_map(|hash| {
+                let (algorithm, expected) = hash.split_once('-')?;
+                let expected = expected.split('?').next().unwrap_or_default();
+                let digest = STANDARD.decode(expected).ok()?;
+
+                Some((algorithm.to_string(), Self::hex(&digest)))
+            })
+            .collect()
+    }
+
+    /// Returns None if the algorithm is not supported.
+    pub fn hex_digest(algorithm: &str, bytes: &[u8]) -> Option<String> {
+        Self::digest(algorithm, bytes).map(|digest| Self::hex(&digest))
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Returns None if the algorithm is not supported.
+    fn digest(algorithm: &str, bytes: &[u8]) -> Option<Vec<u8>> {
+        match algorithm {
+            "sha512" => Some(sha::sha512(bytes).to_vec()),
+            "sha384" => Some(sha::sha384(bytes).to_vec()),
+            "sha256" => Some(sha::sha256(bytes).to_vec()),
+            "sha1" => Some(sha::sha1(bytes).to_vec()),
+            _ => None,
+        }
+    }
+
+    /// Computes a SHA-512 digest of the extracted files of a package, used to detect files that have changed since install.
+    /// Every file path is hashed along with its contents, in sorted order so the digest does not depend on the filesystem.
+    pub fn directory(path: &Path) -> Result<String, io::Error> {
+        let mut files = Vec::new();
+        Self::collect_files(path, path, &mut files)?;
+        files.sort();
+
+        let mut hasher = Sha512::new();
+        for file in files {
+            hasher.update(file.as_bytes());
+            hasher.update(&[0]);
+            hasher.update(&fs::read(path.join(&file))?);
+            hasher.update(&[0]);
+        }
+
+        Ok(format!("sha512-{}", STANDARD.encode(hasher.finish())))
+    }
+
+    fn collect_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<(), io::Error> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if entry.file_type()?.is_dir() {
+                Self::collect_files(root, &path, files)?;
+                continue;
+            }
+
+            let relative_path = path
+                .strip_prefix(root)
+                .expect("Directory entry is outside of the package")
+                .to_string_lossy()
+                .to_string();
+
+            if relative_path != LOCKFILE_NAME {
+                files.push(relative_path);
+            }
+        }
+
+        Ok(())
+    }
+}
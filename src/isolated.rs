@@ -0,0 +1,224 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use semver::Version;
+
+use crate::{
+    cache::{Cache, CACHE_DIRECTORY},
+    errors::CommandError,
+    hoisted::HoistedLayout,
+    installer::MODULES_DIRECTORY,
+    manifest::Manifest,
+    patch::Patches,
+    versions::{Versions, EMPTY_VERSION},
+};
+
+pub const ISOLATED_DIRECTORY: &str = "./node_modules/.click";
+
+/// The isolated layout, selected with `install --linker=isolated`, gives every package its own directory at
+/// `node_modules/.click/<package@version>/node_modules/<name>`, with its dependencies linked next to it.
+/// The files of each package are hardlinked from the cache, so Node finds dependencies from the real path of a package
+/// without `--preserve-symlinks`, and only the packages requested are linked at the top of node_modules.
+pub struct IsolatedLayout;
+impl IsolatedLayout {
+    /// Package string is formated as package@version
+    /// Returns every package that was linked, which is the package itself and all of its dependencies.
+    pub fn link(package: String) -> Result<Vec<String>, CommandError> {
+        let packages = Cache::cached_packages(&package)?;
+
+        for cached_package in packages.iter() {
+            Self::materialize(cached_package)?;
+        }
+
+        for cached_package in packages.iter() {
+            Self::link_dependencies(cached_package)?;
+        }
+
+        let (package_name, _) = Versions::parse_raw_package_details(package.to_string());
+        let target = Self::scope_parents(&package_name)
+            .join(".click")
+            .join(Self::key(&package))
+            .join("node_modules")
+            .join(&package_name);
+
+        Self::replace_link(&Path::new(MODULES_DIRECTORY).join(&package_name), &target)?;
+
+        Ok(packages)
+    }
+
+    /// Package string is formated as package@version
+    /// The directory a package is installed to, which is also where Node finds it from its dependents.
+    fn package_directory(package: &String) -> PathBuf {
+        let (package_name, _) = Versions::parse_raw_package_details(package.to_string());
+
+        Path::new(ISOLATED_DIRECTORY)
+            .join(Self::key(package))
+            .join("node_modules")
+            .join(package_name)
+    }
+
+    /// Directory names cannot contain the slash of a scoped package, so it is replaced with `+` the same as pnpm.
+    pub fn key(package: &str) -> String {
+        package.replace('/', "+")
+    }
+
+    /// Returns the package formatted as package@version of a directory in node_modules/.click.
+    pub fn package_from_key(key: &str) -> String {
+        key.replacen('+', "/", 1)
+    }
+
+    /// A link to a scoped package is in the directory of its scope, so relative targets have to go up one more level.
+    fn scope_parents(package_name: &str) -> PathBuf {
+        package_name.matches('/').map(|_| "..").collect()
+    }
+
+    /// Installs the files of a package from the cache, unless a previous install already has.
+    /// Packages with install scripts are linked to the cache instead, as the scripts run there and only run once.
+    fn materialize(package: &String) -> Result<(), CommandError> {
+        let directory = Self::package_directory(package);
+        if directory.symlink_metadata().is_ok() {
+            return Ok(());
+        }
+
+        let source = Patches::source(package);
+        let parent = directory.parent().expect("Package directory has a parent");
+        fs::create_dir_all(parent).map_err(CommandError::FailedToCreateFile)?;
+
+        if Cache::read_lock(package)?.has_install_script {
+            return symlink::symlink_dir(source, directory)
+                .map_err(CommandError::FailedToCreateFile);
+        }
+
+        // Files are linked into a temporary directory first, so an interrupted install does not leave half of a package
+        let temporary_directory = directory.with_extension(format!("{}.tmp", std::process::id()));
+        if temporary_directory.exists() {
+            fs::remove_dir_all(&temporary_directory).map_err(CommandError::FailedToWriteFile)?;
+        }
+
+        Self::hardlink_tree(&source, &temporary_directory)
+            .map_err(CommandError::FailedToCreateFile)?;
+        fs::rename(&temporary_directory, &directory).map_err(CommandError::FailedToWriteFile)
+    }
+
+    /// Package string is formated as package@version
+    /// Installs a package again, such as once it has been patched or its patch has been removed.
+    pub fn relink(package: &String) -> Result<(), CommandError> {
+        HoistedLayout::remove(&Self::package_directory(package))
+            .map_err(CommandError::FailedToWriteFile)?;
+        Self::materialize(package)
+    }
+
+    /// Files that cannot be hardlinked, such as when the cache is on another filesystem, are copied instead.
+    fn hardlink_tree(source: &Path, destination: &Path) -> Result<(), io::Error> {
+        fs::create_dir(destination)?;
+
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let destination = destination.join(entry.file_name());
+
+            if file_type.is_dir() {
+                Self::hardlink_tree(&entry.path(), &destination)?;
+            } else if file_type.is_symlink() {
+                symlink::symlink_auto(fs::read_link(entry.path())?, &destination)?;
+            } else if fs::hard_link(entry.path(), &destination).is_err() {
+                fs::copy(entry.path(), &destination)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Links each dependency listed in the package.json of a package into the node_modules directory it is installed in.
+    /// The version linked is the one the package was installed with, found in its lockfile. Peer dependencies are linked
+    /// the same way when they were installed with the package.
+    fn link_dependencies(package: &String) -> Result<(), CommandError> {
+        let manifest_path = format!("{}/{}/package/package.json", *CACHE_DIRECTORY, package);
+        let Some(manifest) = Manifest::read_from(Path::new(&manifest_path))? else {
+            return Ok(());
+        };
+
+        let locked_dependencies = Cache::read_lock(package)?.dependencies;
+        let directory = Self::package_directory(package);
+        let modules_directory = directory.parent().expect("Package directory has a parent");
+
+        let mut dependencies = manifest.dependencies("dependencies");
+        dependencies.extend(manifest.dependencies("peerDependencies"));
+
+        for (dependency_name, range) in dependencies {
+            // Dependencies that failed to install with --keep-going are not in the lockfile
+            let Some(dependency) = Self::select(&locked_dependencies, &dependency_name, &range)
+            else {
+                continue;
+            };
+
+            let target = Self::scope_parents(&dependency_name)
+                .join("../..")
+                .join(Self::key(&dependency))
+                .join("node_modules")
+                .join(&dependency_name);
+
+            Self::replace_link(&modules_directory.join(&dependency_name), &target)?;
+        }
+
+        Ok(())
+    }
+
+    /// Lockfiles list every indirect dependency too, so more than one version of a package can be in them.
+    /// The highest version matching the range is picked, or the highest version if none of them match.
+    pub fn select(
+        locked_dependencies: &[String],
+        dependency_name: &str,
+        range: &str,
+    ) -> Option<String> {
+        let range = Versions::parse_semantic_version(range).ok();
+        let matches_range = |version: &String| {
+            let version = Version::from_str(version).unwrap_or(EMPTY_VERSION);
+            range.as_ref().is_some_and(|range| range.matches(&version))
+        };
+
+        let versions = locked_dependencies
+            .iter()
+            .map(|dependency| Versions::parse_raw_package_details(dependency.to_string()))
+            .filter(|(name, _)| name == dependency_name)
+            .map(|(_, version)| version)
+            .collect::<Vec<_>>();
+
+        let highest = |versions: Vec<&String>| {
+            versions
+                .into_iter()
+                .reduce(
+                    |highest, version| match Versions::is_newer(version, highest) {
+                        true => version,
+                        false => highest,
+                    },
+                )
+                .cloned()
+        };
+
+        let matching = versions.iter().filter(|version| matches_range(version));
+        highest(matching.collect())
+            .or_else(|| highest(versions.iter().collect()))
+            .map(|version| Versions::stringify(&dependency_name.to_string(), &version))
+    }
+
+    /// Links are replaced if they point somewhere else, anything that is not a link was not created by click so it is kept.
+    fn replace_link(link: &Path, target: &Path) -> Result<(), CommandError> {
+        match fs::read_link(link) {
+            Ok(existing) if existing == target => return Ok(()),
+            Ok(_) => symlink::remove_symlink_dir(link).map_err(CommandError::FailedToRemoveLink)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(_) => return Ok(()),
+        }
+
+        if let Some(parent) = link.parent() {
+            fs::create_dir_all(parent).map_err(CommandError::FailedToCreateFile)?;
+        }
+
+        symlink::symlink_dir(target, link).map_err(CommandError::FailedToCreateFile)
+    }
+}
@@ -0,0 +1,75 @@
+/// A store of secrets, looked up by the service they were stored under and the account they belong to.
+pub trait SecretStore {
+    fn get(&self, service: &str, account: &str) -> Option<String>;
+}
+
+/// Registry tokens kept in a secure store of the OS rather than in `.npmrc` or the environment, looked up with
+/// `keychain-service` set to the service they are stored under. Each token is stored with the registry as its account,
+/// keyed the same as in `.npmrc`, such as `//registry.npmjs.org/`.
+pub struct Keychain {
+    service: String,
+    store: Box<dyn SecretStore>,
+}
+
+impl Keychain {
+    pub fn new(service: &str, store: impl SecretStore + 'static) -> Self {
+        Self {
+            service: service.to_string(),
+            store: Box::new(store),
+        }
+    }
+
+    /// The keychain on macOS, the Secret Service on Linux. Without the `keychain` feature click is built without them,
+    /// so nothing is found and the config is used instead.
+    pub fn system(service: &str) -> Self {
+        #[cfg(not(feature = "keychain"))]
+        log::warn!("'keychain-service' is set, but click was built without the keychain feature");
+
+        Self::new(service, SystemStore)
+    }
+
+    /// Returns None if the store has no token for the registry, or an empty one.
+    pub fn token(&self, registry: &str) -> Option<String> {
+        self.store
+            .get(&self.service, registry)
+            .filter(|token| !token.is_empty())
+    }
+}
+
+struct SystemStore;
+
+#[cfg(feature = "keychain")]
+impl SecretStore for SystemStore {
+    /// Asks the command line tool of the store, which fails if the secret is not there. Windows has no tool that
+    /// reads back what the Credential Manager stores, so nothing is found there.
+    fn get(&self, service: &str, account: &str) -> Option<String> {
+        let mut command = match std::env::consts::OS {
+            "macos" => {
+                let mut command = std::process::Command::new("security");
+                command.args(["find-generic-password", "-s", service, "-a", account, "-w"]);
+                command
+            }
+            "linux" | "freebsd" | "openbsd" => {
+                let mut command = std::process::Command::new("secret-tool");
+                command.args(["lookup", "service", service, "account", account]);
+                command
+            }
+            _ => return None,
+        };
+
+        let output = command.stdin(std::process::Stdio::null()).output().ok()?;
+        if !output.status.success() {
+            log::debug!("no token for '{account}' in the keychain under '{service}'");
+            return None;
+        }
+
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+#[cfg(not(feature = "keychain"))]
+impl SecretStore for SystemStore {
+    fn get(&self, _service: &str, _account: &str) -> Option<String> {
+        None
+    }
+}
@@ -0,0 +1,75 @@
+//! click resolves and installs npm dependencies. The `click` binary is a thin wrapper over this crate, which other
+//! tools can use to do the same without running it, through Registry, Resolver, Installer and Lockfile. Installs can
+//! be followed by implementing Reporter, which the CLI draws its progress with.
+//! Like the binary, everything is relative to the current directory and the configuration is read once.
+
+mod api;
+mod audit;
+mod cache;
+mod cache_index;
+mod commands;
+mod config;
+mod engines;
+mod errors;
+mod hoisted;
+mod http;
+mod installer;
+mod integrity;
+mod isolated;
+mod keychain;
+mod licenses;
+mod lockfile;
+mod logger;
+mod manifest;
+mod metadata_cache;
+mod npm_lockfile;
+mod output;
+mod pack;
+mod patch;
+mod peers;
+mod plan;
+mod platform;
+mod pnpm_lockfile;
+mod prompt;
+mod reporter;
+mod sbom;
+mod scripts;
+mod shims;
+mod signatures;
+mod source;
+mod suggest;
+mod tarball_cache;
+mod toml;
+mod transaction;
+mod tree;
+mod types;
+mod util;
+mod versions;
+mod workspaces;
+mod yaml;
+mod yarn_lockfile;
+
+use std::env::Args;
+
+use commands::command_handler;
+use logger::Logger;
+use output::Output;
+
+pub use api::{Installer, Registry, Resolver};
+pub use errors::{ClickError, CommandError, ErrorKind, ParseError};
+pub use reporter::{ProgressEvent, Reporter};
+pub use types::{Dist, LockedPackage, Lockfile, VersionData};
+
+/// Runs a command the way the binary does, printing what it does, and returns the code to exit with.
+/// The first argument is the path of the binary, as with std::env::args().
+pub async fn run_cli(args: Args) -> i32 {
+    Logger::init();
+
+    match command_handler::handle_args(args).await {
+        Ok(_) => 0,
+        Err(err) => {
+            Output::error(&err);
+            err.exit_code()
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::{cmp::Reverse, collections::BTreeMap, path::Path};
+
+use crate::{
+    cache::CACHE_DIRECTORY,
+    errors::CommandError,
+    manifest::Manifest,
+    types::{LicenseGroup, Lockfile},
+};
+
+/// The license of a package that has none in its package.json, or that is not in the cache.
+pub const UNKNOWN_LICENSE: &str = "UNKNOWN";
+
+/// Which SPDX licenses are allowed with --allow and --deny. A license is allowed if it is in the allow list, or if there
+/// is none, and it is not in the deny list.
+#[derive(Default)]
+pub struct LicensePolicy {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+impl LicensePolicy {
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Whether a package under an SPDX expression such as `(MIT OR Apache-2.0)` may be used. With OR one of the licenses
+    /// has to be allowed and with AND all of them, an exception given with WITH is left out.
+    pub fn permits(&self, expression: &str) -> bool {
+        // Not an expression, but a license file the package comes with
+        if expression.starts_with("SEE LICENSE IN") {
+            return self.allows(expression);
+        }
+
+        let spaced = expression.replace('(', " ( ").replace(')', " ) ");
+        let tokens = spaced.split_whitespace().collect::<Vec<_>>();
+        let mut position = 0;
+
+        self.or(&tokens, &mut position) && position == tokens.len()
+    }
+
+    fn allows(&self, license: &str) -> bool {
+        let listed = |list: &[String]| {
+            list.iter()
+                .any(|listed| listed.eq_ignore_ascii_case(license))
+        };
+
+        (self.allow.is_empty() || listed(&self.allow)) && !listed(&self.deny)
+    }
+
+    fn or(&self, tokens: &[&str], position: &mut usize) -> bool {
+        let mut permitted = self.and(tokens, position);
+        while tokens.get(*position) == Some(&"OR") {
+            *position += 1;
+            permitted |= self.and(tokens, position);
+        }
+
+        permitted
+    }
+
+    fn and(&self, tokens: &[&str], position: &mut usize) -> bool {
+        let mut permitted = self.license(tokens, position);
+        while tokens.get(*position) == Some(&"AND") {
+            *position += 1;
+            permitted &= self.license(tokens, position);
+        }
+
+        permitted
+    }
+
+    fn license(&self, tokens: &[&str], position: &mut usize) -> bool {
+        let Some(token) = tokens.get(*position) else {
+            return false;
+        };
+        *position += 1;
+
+        if *token == "(" {
+            let permitted = self.or(tokens, position);
+            return match tokens.get(*position) {
+                Some(&")") => {
+                    *position += 1;
+                    permitted
+                }
+                _ => false,
+            };
+        }
+
+        if tokens.get(*position) == Some(&"WITH") {
+            *position += 2;
+        }
+
+        // GPL-2.0+ is under GPL-2.0 or any later version, so it is read as GPL-2.0
+        self.allows(token.strip_suffix('+').unwrap_or(token))
+    }
+}
+
+/// Reads the licenses of the packages in the lockfile from their package.json in the cache.
+pub struct Licenses;
+impl Licenses {
+    /// Groups the packages by their license, the licenses with the most packages first.
+    pub fn collect(lockfile: &Lockfile) -> Result<Vec<LicenseGroup>, CommandError> {
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for package in lockfile.packages.keys() {
+            let license = Self::of(package)?.unwrap_or(UNKNOWN_LICENSE.to_string());
+
+            groups.entry(license).or_default().push(package.to_string());
+        }
+
+        let mut groups = groups
+            .into_iter()
+            .map(|(license, packages)| LicenseGroup { license, packages })
+            .collect::<Vec<_>>();
+        groups.sort_by_key(|group| Reverse(group.packages.len()));
+
+        Ok(groups)
+    }
+
+    /// Package string is formated as package@version
+    /// Returns None if the package has no license or is not in the cache.
+    pub fn of(package: &String) -> Result<Option<String>, CommandError> {
+        let manifest_path = format!("{}/{}/package/package.json", *CACHE_DIRECTORY, package);
+        Ok(Manifest::read_from(Path::new(&manifest_path))?
+            .and_then(|manifest| manifest.license())
+            .filter(|license| !license.trim().is_empty()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_spdx_expressions() {
+        let policy = LicensePolicy {
+            allow: vec!["MIT".to_string(), "Apache-2.0".to_string()],
+            deny: Vec::new(),
+        };
+
+        assert!(policy.permits("MIT"));
+        assert!(policy.permits("(MIT OR GPL-3.0)"));
+        assert!(policy.permits("Apache-2.0 WITH LLVM-exception"));
+        assert!(!policy.permits("MIT AND GPL-3.0"));
+        assert!(policy.permits("(MIT AND Apache-2.0) OR GPL-3.0"));
+        assert!(!policy.permits("(MIT OR GPL-3.0) AND ISC"));
+        assert!(!policy.permits(UNKNOWN_LICENSE));
+
+        let policy = LicensePolicy {
+            allow: Vec::new(),
+            deny: vec!["GPL-3.0".to_string()],
+        };
+
+        assert!(policy.permits("MIT"));
+        assert!(policy.permits("MIT OR GPL-3.0"));
+        assert!(!policy.permits("GPL-3.0+"));
+        assert!(!policy.permits("(MIT"));
+    }
+}
@@ -0,0 +1,723 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    fs,
+    io::ErrorKind,
+    path::Path,
+    str::FromStr,
+};
+
+use semver::Version;
+use serde_json::Value;
+
+use crate::{
+    cache::Cache,
+    cache_index::CacheIndex,
+    errors::CommandError,
+    manifest::Manifest,
+    toml::Toml,
+    types::{Bin, Dist, LockedPackage, Lockfile, VersionData},
+    versions::{VersionRange, Versions, EMPTY_VERSION, LATEST},
+};
+
+pub const LOCKFILE_PATH: &str = "./click-lock.json";
+pub const TOML_LOCKFILE_PATH: &str = "./click-lock.toml";
+pub const LOCKFILE_VERSION: u32 = 1;
+
+/// A locked package that other versions of it are replaced with in what packages depend on, see Lockfile::replace().
+struct Replacement {
+    parent: Option<(String, Option<VersionRange>)>, // Only replaced in what this package depends on, if any
+    replaced: (String, Option<VersionRange>), // The name of the package, and the versions replaced if not all of them
+    package: String,                          // Formatted as package@version
+    closure: Vec<String>,                     // The package along with everything it depends on
+}
+
+/// What the project lockfile is written as, chosen with `--lockfile-format` and otherwise kept as it is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LockfileFormat {
+    Json,
+    Toml,
+}
+
+impl LockfileFormat {
+    /// Parses a format given with `--lockfile-format`, returns None if it is neither `json` nor `toml`.
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    /// The format of the project lockfile, JSON unless the project only has click-lock.toml.
+    pub fn current() -> Self {
+        match !Path::new(LOCKFILE_PATH).exists() && Path::new(TOML_LOCKFILE_PATH).exists() {
+            true => Self::Toml,
+            false => Self::Json,
+        }
+    }
+
+    /// Lockfiles are read as TOML if their extension is `.toml`, and as JSON otherwise.
+    pub fn of(path: &Path) -> Self {
+        match path
+            .extension()
+            .is_some_and(|extension| extension == "toml")
+        {
+            true => Self::Toml,
+            false => Self::Json,
+        }
+    }
+
+    pub fn path(self) -> &'static str {
+        match self {
+            Self::Json => LOCKFILE_PATH,
+            Self::Toml => TOML_LOCKFILE_PATH,
+        }
+    }
+}
+
+impl Lockfile {
+    /// Returns an empty lockfile if the project does not have one yet.
+    pub fn read() -> Result<Self, CommandError> {
+        Ok(Self::read_from(Path::new(LockfileFormat::current().path()))?.unwrap_or_else(Self::new))
+    }
+
+    /// Whether the project has a lockfile, in either format.
+    pub fn exists() -> bool {
+        Path::new(LockfileFormat::current().path()).exists()
+    }
+
+    /// Returns None if there is no lockfile at the path.
+    pub fn read_from(path: &Path) -> Result<Option<Self>, CommandError> {
+        let lockfile_raw = match fs::read_to_string(path) {
+            Ok(lockfile_raw) => lockfile_raw,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(CommandError::FailedToReadPackageLock(err)),
+        };
+
+        Self::parse(&lockfile_raw, LockfileFormat::of(path))
+            .map(Some)
+            .map_err(|detail| CommandError::CorruptLockfile {
+                path: path.display().to_string(),
+                detail,
+            })
+    }
+
+    pub fn parse(raw: &str, format: LockfileFormat) -> Result<Self, String> {
+        match format {
+            LockfileFormat::Json => serde_json::from_str(raw).map_err(|err| err.to_string()),
+            LockfileFormat::Toml => serde_json::from_value(Value::Object(Toml::parse(raw)?))
+                .map_err(|err| err.to_string()),
+        }
+    }
+
+    /// Every key is sorted in either format, and the lockfile is always written the same way for the same packages,
+    /// so that it diffs cleanly.
+    pub fn serialize(&self, format: LockfileFormat) -> Result<String, CommandError> {
+        let Value::Object(lockfile) =
+            serde_json::to_value(self).map_err(CommandError::FailedToSerializePackageLock)?
+        else {
+            unreachable!("a lockfile is serialized as a map");
+        };
+
+        match format {
+            LockfileFormat::Json => serde_json::to_string_pretty(&lockfile)
+                .map(|lockfile| lockfile + "\n")
+                .map_err(CommandError::FailedToSerializePackageLock),
+            LockfileFormat::Toml => Ok(Toml::write(&lockfile)),
+        }
+    }
+
+    pub fn new() -> Self {
+        Self {
+            lockfile_version: LOCKFILE_VERSION,
+            ..Default::default()
+        }
+    }
+
+    /// Builds a lockfile from the packages another package manager locked, such as npm in package-lock.json. Each package
+    /// is given with the packages it depends on directly, which are replaced with every package it depends on directly or
+    /// not, and `requested` has each spec to the package it was locked to. Packages nothing requested depends on are left out.
+    pub fn import(
+        mut packages: BTreeMap<String, LockedPackage>,
+        requested: BTreeMap<String, String>,
+    ) -> Self {
+        let direct = packages
+            .iter()
+            .map(|(package, locked_package)| {
+                (package.to_string(), locked_package.dependencies.clone())
+            })
+            .collect::<HashMap<_, _>>();
+
+        for (package, locked_package) in packages.iter_mut() {
+            let mut closure = BTreeSet::new();
+            let mut queue = VecDeque::from([package.to_string()]);
+            while let Some(current) = queue.pop_front() {
+                for dependency in direct.get(&current).into_iter().flatten() {
+                    if dependency != package && closure.insert(dependency.to_string()) {
+                        queue.push_back(dependency.to_string());
+                    }
+                }
+            }
+
+            locked_package.dependencies = closure.into_iter().collect();
+        }
+
+        let mut lockfile = Self {
+            lockfile_version: LOCKFILE_VERSION,
+            requested,
+            packages,
+            ..Default::default()
+        };
+        lockfile.prune();
+        lockfile
+    }
+
+    /// Written in the format the project lockfile already has, see write_as().
+    pub fn write(&self) -> Result<(), CommandError> {
+        self.write_as(LockfileFormat::current())
+    }
+
+    /// The lockfile in the other format is removed, so that a project only ever has one. The project is recorded in the
+    /// cache index, so that the packages it links from the cache are kept by `click cache verify`.
+    pub fn write_as(&self, format: LockfileFormat) -> Result<(), CommandError> {
+        fs::write(format.path(), self.serialize(format)?)
+            .map_err(CommandError::FailedToWriteFile)?;
+
+        let other = match format {
+            LockfileFormat::Json => TOML_LOCKFILE_PATH,
+            LockfileFormat::Toml => LOCKFILE_PATH,
+        };
+        match fs::remove_file(other) {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                return Err(CommandError::FailedToWriteFile(err))
+            }
+            _ => {}
+        }
+        CacheIndex::record_project();
+
+        Ok(())
+    }
+
+    /// Writes the lockfile somewhere other than the project lockfile, as TOML if the path ends with `.toml`. Directories
+    /// on the way to it are created.
+    pub fn write_to(&self, path: &Path) -> Result<(), CommandError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(CommandError::FailedToCreateFile)?;
+        }
+
+        fs::write(path, self.serialize(LockfileFormat::of(path))?)
+            .map_err(CommandError::FailedToWriteFile)
+    }
+
+    /// The key a requested package is recorded under, formatted as package@range.
+    pub fn spec(package_name: &String, semantic_version: Option<&VersionRange>) -> String {
+        match semantic_version {
+            Some(semantic_version) => format!("{}@{}", package_name, semantic_version),
+            None => Versions::stringify(package_name, &LATEST.to_string()),
+        }
+    }
+
+    /// Returns the version that a requested spec was locked to.
+    pub fn locked_version(&self, spec: &String) -> Option<String> {
+        let package = self.requested.get(spec)?;
+        Some(Versions::parse_raw_package_details(package.to_string()).1)
+    }
+
+    /// Builds the version data of a locked package so it can be downloaded without asking the registry for it.
+    /// Returns None if the package is not locked or the lockfile does not have its tarball URL.
+    pub fn version_data(&self, package_name: &String, version: &String) -> Option<VersionData> {
+        let locked_package = self
+            .packages
+            .get(&Versions::stringify(package_name, version))?;
+
+        Some(VersionData {
+            name: package_name.to_string(),
+            version: version.to_string(),
+            dependencies: None, // Locked dependencies are read with Lockfile::dependencies() instead
+            dist: Dist {
+                tarball: locked_package.resolved.clone()?,
+                integrity: locked_package.integrity.clone(),
+                shasum: None,
+                unpacked_size: None,
+                signatures: Vec::new(), // Checked when the version was locked
+                attestations: None,
+            },
+            has_install_script: locked_package.has_install_script,
+            bin: Some(Bin::Multiple(locked_package.bin.clone())),
+            os: None, // Only versions for the platform they were installed on are locked
+            cpu: None,
+            libc: None,
+            engines: None,
+            optional_dependencies: None, // Whether a locked dependency is optional is read with Lockfile::is_optional()
+            peer_dependencies: None, // Peer dependencies that were installed are locked as dependencies
+            peer_dependencies_meta: HashMap::new(),
+            bundle_dependencies: None, // Bundled dependencies are never locked
+            deprecated: None, // Recorded in the lockfile of the package in the cache once it is downloaded
+        })
+    }
+
+    /// A copy without the tarball URL of any package, so that each is downloaded from wherever the registry it is
+    /// installed from has it now rather than the URL it was locked with, such as a mirror, see version_data().
+    pub fn without_urls(&self) -> Self {
+        let mut lockfile = self.clone();
+        for locked_package in lockfile.packages.values_mut() {
+            locked_package.resolved = None;
+        }
+
+        lockfile
+    }
+
+    /// Returns the name and exact version of every locked dependency of a package formatted as package@version.
+    /// A list is returned rather than a map as different versions of the same package can be locked.
+    pub fn dependencies(&self, package: &String) -> Option<Vec<(String, String)>> {
+        let locked_package = self.packages.get(package)?;
+
+        Some(
+            locked_package
+                .dependencies
+                .iter()
+                .map(|dependency| Versions::parse_raw_package_details(dependency.to_string()))
+                .collect(),
+        )
+    }
+
+    /// Package string is formated as package@version
+    /// Returns the range and the locked package@version of each dependency the package itself depends on, see
+    /// Cache::direct_dependencies().
+    pub fn direct_dependencies(&self, package: &String) -> Vec<(String, String)> {
+        match self.packages.get(package) {
+            Some(locked_package) => {
+                Cache::direct_dependencies(package, &locked_package.dependencies)
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Package string is formated as package@version
+    pub fn is_optional(&self, package: &String) -> bool {
+        self.packages
+            .get(package)
+            .is_some_and(|locked_package| locked_package.optional)
+    }
+
+    /// Locks a requested spec to the package it resolved to, along with everything the package depends on.
+    /// Each package is recorded from its lockfile in the cache, so this should be called once the install has finished.
+    pub fn record(&mut self, spec: String, package: &String) -> Result<(), CommandError> {
+        for cached_package in Cache::cached_packages(package)? {
+            let package_lock = Cache::read_lock(&cached_package)?;
+
+            self.packages.insert(
+                cached_package,
+                LockedPackage {
+                    resolved: package_lock.resolved,
+                    registry: package_lock.registry,
+                    integrity: package_lock.integrity,
+                    has_install_script: package_lock.has_install_script,
+                    bin: package_lock.bin,
+                    dependencies: package_lock.dependencies,
+                    optional: package_lock.optional,
+                },
+            );
+        }
+
+        self.requested.insert(spec, package.to_string());
+        Ok(())
+    }
+
+    /// The specs and packages that are locked differently in another lockfile, such as one an install has changed.
+    pub fn changes(&self, other: &Lockfile) -> Vec<String> {
+        let specs = self
+            .requested
+            .keys()
+            .chain(other.requested.keys())
+            .filter(|spec| self.requested.get(*spec) != other.requested.get(*spec));
+        let packages = self
+            .packages
+            .keys()
+            .chain(other.packages.keys())
+            .filter(|package| self.packages.get(*package) != other.packages.get(*package));
+        let links = self
+            .links
+            .keys()
+            .chain(other.links.keys())
+            .filter(|package_name| self.links.get(*package_name) != other.links.get(*package_name));
+        let overrides = self
+            .overrides
+            .keys()
+            .chain(other.overrides.keys())
+            .filter(|key| self.overrides.get(*key) != other.overrides.get(*key));
+        let patches = self
+            .patches
+            .keys()
+            .chain(other.patches.keys())
+            .filter(|package| self.patches.get(*package) != other.patches.get(*package));
+
+        let mut changes = specs
+            .chain(packages)
+            .chain(links)
+            .chain(overrides)
+            .chain(patches)
+            .cloned()
+            .collect::<Vec<_>>();
+        changes.sort();
+        changes.dedup();
+        changes
+    }
+
+    /// A copy without anything the packages were requested as, nor whatever only they depend on, such as a lockfile
+    /// for production without devDependencies.
+    pub fn without(&self, package_names: &HashSet<String>) -> Self {
+        let mut lockfile = self.clone();
+        lockfile.requested.retain(|spec, _| {
+            !package_names.contains(&Versions::parse_raw_package_details(spec.to_string()).0)
+        });
+        lockfile
+            .links
+            .retain(|package_name, _| !package_names.contains(package_name));
+        lockfile.prune();
+
+        lockfile
+    }
+
+    /// Removes every requested spec of a package, returns true if there were any.
+    pub fn remove_requested(&mut self, package_name: &String) -> bool {
+        let length = self.requested.len();
+        self.requested.retain(|spec, _| {
+            &Versions::parse_raw_package_details(spec.to_string()).0 != package_name
+        });
+
+        let was_linked = self.links.remove(package_name).is_some();
+        self.requested.len() != length || was_linked
+    }
+
+    /// Points every locked dependency on an overridden package at the version its override resolved to, along with what
+    /// that version depends on, then prunes the versions nothing depends on anymore. An override keyed with a range such
+    /// as `foo@1.x` only replaces the versions in that range, and one nested under a package such as `foo>bar` only
+    /// replaces them in what that package depends on, taking precedence there, see Manifest::overrides().
+    pub fn apply_overrides(&mut self) {
+        let parse_key = |key: &str| {
+            let (package_name, selector) = Versions::parse_raw_package_details(key.to_string());
+            let selector = match selector == LATEST {
+                true => None,
+                false => Some(Versions::parse_semantic_version(&selector).ok()?),
+            };
+
+            Some((package_name, selector))
+        };
+
+        let mut replacements = self
+            .overrides
+            .iter()
+            .filter_map(|(key, package)| {
+                let (parent, overridden) = Manifest::split_override(key);
+                let parent = match parent {
+                    Some(parent) => Some(parse_key(parent)?),
+                    None => None,
+                };
+
+                Some(self.replacement(parent, parse_key(overridden)?, package))
+            })
+            .collect::<Vec<_>>();
+        replacements.sort_by_key(|replacement| replacement.parent.is_none());
+
+        self.replace(&replacements);
+    }
+
+    /// Dependents resolve their range of a package on their own, each to the highest version it allows, so more than
+    /// one version of a package can be locked where one would do. Once everything is locked, and every range a package
+    /// is depended on with is known, its dependents are all pointed at the highest of its locked versions that every
+    /// one of those ranges allows, if there is one. The versions locked then do not depend on the order dependents
+    /// happened to be resolved in. Packages depended on with something other than a range, such as a git URL, are left
+    /// as they are.
+    pub fn unify_versions(&mut self) {
+        let mut ranges: HashMap<String, Vec<String>> = HashMap::new();
+        for spec in self.requested.keys() {
+            let (package_name, range) = Versions::parse_raw_package_details(spec.to_string());
+            ranges.entry(package_name).or_default().push(range);
+        }
+        for package in self.packages.keys() {
+            for (range, dependency) in self.direct_dependencies(package) {
+                let (package_name, _) = Versions::parse_raw_package_details(dependency);
+                ranges.entry(package_name).or_default().push(range);
+            }
+        }
+
+        let mut versions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for package in self.packages.keys() {
+            let (package_name, version) = Versions::parse_raw_package_details(package.to_string());
+            versions.entry(package_name).or_default().push(version);
+        }
+
+        let mut replacements = Vec::new();
+        for (package_name, versions) in versions.iter().filter(|(_, versions)| versions.len() > 1) {
+            let Some(ranges) = ranges
+                .get(package_name)
+                .map(|ranges| {
+                    ranges
+                        .iter()
+                        .map(|range| Versions::parse_semantic_version(range).ok())
+                        .collect::<Option<Vec<_>>>()
+                })
+                .unwrap_or_default()
+            else {
+                continue;
+            };
+
+            let unified = versions
+                .iter()
+                .filter(|version| {
+                    let version = Version::from_str(version).unwrap_or(EMPTY_VERSION);
+                    ranges.iter().all(|range| range.matches(&version))
+                })
+                .reduce(
+                    |highest, version| match Versions::is_newer(version, highest) {
+                        true => version,
+                        false => highest,
+                    },
+                );
+
+            if let Some(version) = unified {
+                let package = Versions::stringify(package_name, version);
+                replacements.push(self.replacement(
+                    None,
+                    (package_name.to_string(), None),
+                    &package,
+                ));
+            }
+        }
+
+        if replacements.is_empty() {
+            return;
+        }
+
+        for package in self.requested.values_mut() {
+            let (package_name, _) = Versions::parse_raw_package_details(package.to_string());
+            if let Some(replacement) = replacements
+                .iter()
+                .find(|replacement| replacement.replaced.0 == package_name)
+            {
+                *package = replacement.package.to_string();
+            }
+        }
+
+        self.replace(&replacements);
+    }
+
+    fn replacement(
+        &self,
+        parent: Option<(String, Option<VersionRange>)>,
+        replaced: (String, Option<VersionRange>),
+        package: &String,
+    ) -> Replacement {
+        let mut closure = self
+            .packages
+            .get(package)
+            .map(|locked_package| locked_package.dependencies.clone())
+            .unwrap_or_default();
+        closure.push(package.to_string());
+
+        Replacement {
+            parent,
+            replaced,
+            package: package.to_string(),
+            closure,
+        }
+    }
+
+    /// Points every locked dependency that the first replacement it matches replaces at the replacing package, along
+    /// with what that package depends on, then prunes the versions nothing depends on anymore.
+    fn replace(&mut self, replacements: &[Replacement]) {
+        let matches = |(package_name, selector): &(String, Option<VersionRange>), package: &str| {
+            let (name, version) = Versions::parse_raw_package_details(package.to_string());
+            let version = Version::from_str(&version).unwrap_or(EMPTY_VERSION);
+
+            *package_name == name
+                && selector
+                    .as_ref()
+                    .is_none_or(|selector| selector.matches(&version))
+        };
+
+        for (package, locked_package) in self.packages.iter_mut() {
+            let mut dependencies = Vec::new();
+            for dependency in locked_package.dependencies.drain(..) {
+                let replacement = replacements.iter().find(|replacement| {
+                    replacement
+                        .parent
+                        .as_ref()
+                        .is_none_or(|parent| matches(parent, package))
+                        && matches(&replacement.replaced, &dependency)
+                        && replacement.package != dependency
+                });
+
+                match replacement {
+                    Some(replacement) => dependencies.extend(
+                        replacement
+                            .closure
+                            .iter()
+                            .filter(|replacing| *replacing != package)
+                            .cloned(),
+                    ),
+                    None => dependencies.push(dependency),
+                }
+            }
+
+            dependencies.sort();
+            dependencies.dedup();
+            locked_package.dependencies = dependencies;
+        }
+
+        self.prune();
+    }
+
+    /// Keeps only the specs that are requested now, such as those of package.json, along with whatever they depend on.
+    pub fn retain_requested(&mut self, specs: &HashSet<String>) {
+        self.requested.retain(|spec, _| specs.contains(spec));
+        self.prune();
+    }
+
+    /// Locks `spec` to the package `replaced` is locked to, in place of every other spec of the same package, such as
+    /// when a package installed as `aa@latest` is saved to package.json as `aa@^1.0.0`. Returns false if `replaced` is
+    /// not locked, as with a linked package.
+    pub fn replace_requested(&mut self, replaced: &String, spec: String) -> bool {
+        let Some(package) = self.requested.get(replaced).cloned() else {
+            return false;
+        };
+
+        let package_name = Versions::parse_raw_package_details(spec.to_string()).0;
+        self.requested.retain(|requested, _| {
+            Versions::parse_raw_package_details(requested.to_string()).0 != package_name
+        });
+        self.requested.insert(spec, package);
+        true
+    }
+
+    /// Removes packages that are no longer depended on by any requested spec.
+    /// Locked dependencies already include indirect ones, so only one level has to be followed.
+    pub fn prune(&mut self) {
+        let mut required = HashSet::new();
+        for package in self.requested.values() {
+            required.insert(package.to_string());
+
+            if let Some(locked_package) = self.packages.get(package) {
+                required.extend(locked_package.dependencies.iter().cloned());
+            }
+        }
+
+        self.packages
+            .retain(|package, _| required.contains(package));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lockfile() -> Lockfile {
+        let locked = |dependencies: &[&str], optional: bool| LockedPackage {
+            resolved: Some(String::from("https://registry.test/aa/-/aa-1.0.0.tgz")),
+            registry: Some(String::from("https://registry.test")),
+            integrity: Some(String::from("sha512-AAAA")),
+            has_install_script: !optional,
+            bin: BTreeMap::from([(String::from("aa"), String::from("bin/aa.js"))]),
+            dependencies: dependencies
+                .iter()
+                .map(|dependency| dependency.to_string())
+                .collect(),
+            optional,
+        };
+
+        Lockfile {
+            lockfile_version: LOCKFILE_VERSION,
+            requested: BTreeMap::from([
+                (String::from("aa@^1.0.0"), String::from("aa@1.0.0")),
+                (
+                    String::from("@scope/bb@latest"),
+                    String::from("@scope/bb@2.0.0"),
+                ),
+            ]),
+            packages: BTreeMap::from([
+                (
+                    String::from("aa@1.0.0"),
+                    locked(&["@scope/bb@2.0.0"], false),
+                ),
+                (String::from("@scope/bb@2.0.0"), locked(&[], true)),
+            ]),
+            links: BTreeMap::from([(String::from("cc"), String::from("../cc"))]),
+            overrides: BTreeMap::from([(String::from("aa>bb"), String::from("@scope/bb@2.0.0"))]),
+            patches: BTreeMap::new(),
+            no_bin_links: true,
+        }
+    }
+
+    #[test]
+    fn round_trips_the_same_graph_through_json_and_toml() {
+        let lockfile = lockfile();
+        let json = lockfile.serialize(LockfileFormat::Json).unwrap();
+        let toml = lockfile.serialize(LockfileFormat::Toml).unwrap();
+
+        let from_json = Lockfile::parse(&json, LockfileFormat::Json).unwrap();
+        let from_toml = Lockfile::parse(&toml, LockfileFormat::Toml).unwrap();
+        let value = |lockfile: &Lockfile| serde_json::to_value(lockfile).unwrap();
+        assert_eq!(value(&from_json), value(&lockfile));
+        assert_eq!(value(&from_toml), value(&from_json));
+
+        // Each format is written the same way again once read back
+        assert_eq!(from_json.serialize(LockfileFormat::Json).unwrap(), json);
+        assert_eq!(from_toml.serialize(LockfileFormat::Toml).unwrap(), toml);
+        assert_eq!(from_toml.serialize(LockfileFormat::Json).unwrap(), json);
+    }
+
+    #[test]
+    fn writes_keys_in_canonical_order() {
+        let json = lockfile().serialize(LockfileFormat::Json).unwrap();
+        let keys = json
+            .lines()
+            .filter(|line| line.starts_with("  \""))
+            .map(|line| line.trim().split('"').nth(1).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                "links",
+                "lockfileVersion",
+                "noBinLinks",
+                "overrides",
+                "packages",
+                "requested"
+            ]
+        );
+
+        let toml = lockfile().serialize(LockfileFormat::Toml).unwrap();
+        let headers = toml
+            .lines()
+            .filter(|line| line.starts_with('['))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headers,
+            [
+                "[links]",
+                "[overrides]",
+                "[packages]",
+                "[packages.\"@scope/bb@2.0.0\"]",
+                "[packages.\"@scope/bb@2.0.0\".bin]",
+                "[packages.\"aa@1.0.0\"]",
+                "[packages.\"aa@1.0.0\".bin]",
+                "[requested]",
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_lockfiles_by_extension() {
+        assert_eq!(
+            LockfileFormat::of(Path::new("./click-lock.toml")),
+            LockfileFormat::Toml
+        );
+        assert_eq!(
+            LockfileFormat::of(Path::new("./click-lock.json")),
+            LockfileFormat::Json
+        );
+        assert_eq!(LockfileFormat::parse("toml"), Some(LockfileFormat::Toml));
+        assert_eq!(LockfileFormat::parse("yaml"), None);
+    }
+}
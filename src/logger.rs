@@ -0,0 +1,74 @@
+use std::env;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+const LOG_VARIABLE: &str = "CLICK_LOG"; // Sets the level without a flag, such as CLICK_LOG=debug
+
+static LOGGER: Logger = Logger;
+
+/// Prints what is logged with the `log` macros to stderr. Warnings and errors are shown by default, along with messages
+/// such as a discarded lockfile, `-v` adds what click is doing such as each request to the registry, and `-vv` adds
+/// everything down to the logs of the HTTP client. `--quiet` only shows errors.
+pub struct Logger;
+impl Logger {
+    /// Installs the logger, at the level set by CLICK_LOG or at info if it is not set. Flags change the level later.
+    pub fn init() {
+        let level = env::var(LOG_VARIABLE)
+            .ok()
+            .and_then(|level| level.parse::<LevelFilter>().ok())
+            .unwrap_or(LevelFilter::Info);
+
+        if log::set_logger(&LOGGER).is_ok() {
+            log::set_max_level(level);
+        }
+    }
+
+    /// Changes the level if the argument is `-v`, `-vv`, `--verbose`, `-q` or `--quiet`, returns false for anything else.
+    /// Giving `-v` twice is the same as `-vv`.
+    pub fn parse_flag(arg: &str) -> bool {
+        let level = match arg {
+            "-v" | "--verbose" if log::max_level() >= LevelFilter::Debug => LevelFilter::Trace,
+            "-v" | "--verbose" => LevelFilter::Debug,
+            "-vv" => LevelFilter::Trace,
+            "-q" | "--quiet" => LevelFilter::Error,
+            _ => return false,
+        };
+
+        log::set_max_level(level);
+        true
+    }
+
+    /// Whether messages for people reading the output are printed, which `--quiet` turns off.
+    pub fn is_quiet() -> bool {
+        log::max_level() < LevelFilter::Info
+    }
+}
+
+impl Log for Logger {
+    /// Dependencies such as reqwest and hyper log every connection at debug, so they are only shown at trace.
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        match metadata.target().starts_with(env!("CARGO_PKG_NAME")) {
+            true => metadata.level() <= log::max_level(),
+            false => log::max_level() == LevelFilter::Trace,
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        match record.level() {
+            Level::Error | Level::Info => eprintln!("{}", record.args()),
+            Level::Warn => eprintln!("Warning: {}", record.args()),
+            Level::Debug | Level::Trace => eprintln!(
+                "[{}] {}: {}",
+                record.level().as_str().to_lowercase(),
+                record.target(),
+                record.args()
+            ),
+        }
+    }
+
+    fn flush(&self) {}
+}
@@ -0,0 +1,634 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    io::ErrorKind,
+    path::Path,
+};
+
+use serde::{
+    de::{MapAccess, SeqAccess, Visitor},
+    ser::{SerializeMap, SerializeSeq},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_json::ser::PrettyFormatter;
+
+use crate::errors::CommandError;
+
+pub const PACKAGE_JSON_PATH: &str = "./package.json";
+const OVERRIDE_SEPARATOR: &str = ">"; // Between the packages an override is nested under and the one it overrides
+pub const DEPENDENCY_FIELDS: [&str; 4] = [
+    "dependencies",
+    "devDependencies",
+    "optionalDependencies",
+    "peerDependencies",
+];
+
+// Fields that end like a dependency group but are not a map of packages to ranges
+const RESERVED_GROUPS: [&str; 4] = [
+    "peerDependencies",
+    "bundleDependencies",
+    "bundledDependencies",
+    "trustedDependencies",
+];
+
+/// Which field of package.json a package is saved to when it is installed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum DependencyKind {
+    #[default]
+    Production,
+    Development,   // Not installed with `--production`
+    Optional,      // Skipped if it fails to install
+    Group(String), // Any other group such as `buildDependencies`, only installed when it is included
+}
+
+impl DependencyKind {
+    pub fn field(&self) -> &str {
+        match self {
+            Self::Production => "dependencies",
+            Self::Development => "devDependencies",
+            Self::Optional => "optionalDependencies",
+            Self::Group(field) => field,
+        }
+    }
+
+    /// The kind saved to the given field of package.json, or None if it is not a valid name for a group. A custom
+    /// group is named in camelCase and ends with `Dependencies`, like `buildDependencies`.
+    pub fn parse(field: &str) -> Option<Self> {
+        let kind = match field {
+            "dependencies" => Self::Production,
+            "devDependencies" => Self::Development,
+            "optionalDependencies" => Self::Optional,
+            _ => {
+                let prefix = field.strip_suffix("Dependencies")?;
+                let valid = prefix.starts_with(|char: char| char.is_ascii_lowercase())
+                    && prefix.chars().all(|char| char.is_ascii_alphanumeric())
+                    && !RESERVED_GROUPS.contains(&field);
+
+                return valid.then(|| Self::Group(field.to_string()));
+            }
+        };
+
+        Some(kind)
+    }
+}
+
+/// A JSON value that keeps the order of object keys, serde_json's Value sorts them which would reorder
+/// the whole package.json every time it is written back.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OrderedJson {
+    Null,
+    Bool(bool),
+    Number(serde_json::Number),
+    String(String),
+    Array(Vec<OrderedJson>),
+    Object(Vec<(String, OrderedJson)>),
+}
+
+impl OrderedJson {
+    pub fn get(&self, key: &str) -> Option<&OrderedJson> {
+        match self {
+            Self::Object(fields) => fields
+                .iter()
+                .find(|(field, _)| field == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut OrderedJson> {
+        match self {
+            Self::Object(fields) => fields
+                .iter_mut()
+                .find(|(field, _)| field == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Returns the removed value if the key was present.
+    pub fn remove(&mut self, key: &str) -> Option<OrderedJson> {
+        match self {
+            Self::Object(fields) => {
+                let position = fields.iter().position(|(field, _)| field == key)?;
+                Some(fields.remove(position).1)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns every item, or nothing if this is not an array.
+    pub fn items(&self) -> &[OrderedJson] {
+        match self {
+            Self::Array(items) => items,
+            _ => &[],
+        }
+    }
+
+    /// Returns the key and value of every field, or nothing if this is not an object.
+    pub fn fields(&self) -> &[(String, OrderedJson)] {
+        match self {
+            Self::Object(fields) => fields,
+            _ => &[],
+        }
+    }
+}
+
+impl Serialize for OrderedJson {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Null => serializer.serialize_unit(),
+            Self::Bool(value) => serializer.serialize_bool(*value),
+            Self::Number(value) => value.serialize(serializer),
+            Self::String(value) => serializer.serialize_str(value),
+            Self::Array(values) => {
+                let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                for value in values {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            Self::Object(fields) => {
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (key, value) in fields {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+struct OrderedJsonVisitor;
+
+impl<'de> Visitor<'de> for OrderedJsonVisitor {
+    type Value = OrderedJson;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any JSON value")
+    }
+
+    fn visit_unit<E>(self) -> Result<OrderedJson, E> {
+        Ok(OrderedJson::Null)
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<OrderedJson, E> {
+        Ok(OrderedJson::Bool(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<OrderedJson, E> {
+        Ok(OrderedJson::Number(value.into()))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<OrderedJson, E> {
+        Ok(OrderedJson::Number(value.into()))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<OrderedJson, E> {
+        Ok(serde_json::Number::from_f64(value).map_or(OrderedJson::Null, OrderedJson::Number))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<OrderedJson, E> {
+        Ok(OrderedJson::String(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<OrderedJson, E> {
+        Ok(OrderedJson::String(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<OrderedJson, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(OrderedJson::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<OrderedJson, A::Error> {
+        let mut fields = Vec::new();
+        while let Some((key, value)) = map.next_entry()? {
+            fields.push((key, value));
+        }
+        Ok(OrderedJson::Object(fields))
+    }
+}
+
+impl<'de> Deserialize<'de> for OrderedJson {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(OrderedJsonVisitor)
+    }
+}
+
+/// The package.json of the project in the working directory.
+#[derive(Clone)]
+pub struct Manifest {
+    pub json: OrderedJson,
+    indent: String, // The indentation the file was written with, kept when it is written back
+    newline: &'static str, // Either `\n` or `\r\n`, whichever the file used
+}
+
+/// An empty package.json, for when a package is installed in a project that does not have one yet.
+/// It is written with two space indentation, the same as npm.
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            json: OrderedJson::Object(Vec::new()),
+            indent: String::from("  "),
+            newline: "\n",
+        }
+    }
+}
+
+impl Manifest {
+    /// Returns None if there is no package.json.
+    pub fn read() -> Result<Option<Self>, CommandError> {
+        Self::read_from(Path::new(PACKAGE_JSON_PATH))
+    }
+
+    /// Reads the package.json at the given path, such as one of an installed package.
+    pub fn read_from(path: &Path) -> Result<Option<Self>, CommandError> {
+        let manifest_raw = match fs::read_to_string(path) {
+            Ok(manifest_raw) => manifest_raw,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(CommandError::FailedToReadFile(err)),
+        };
+
+        let json = serde_json::from_str::<OrderedJson>(&manifest_raw)
+            .map_err(CommandError::FailedToParseManifest)?;
+
+        // The indentation of the first field is the one used throughout, a file on a single line gets the default
+        let indent = manifest_raw
+            .lines()
+            .nth(1)
+            .map(|line| &line[..line.len() - line.trim_start().len()])
+            .filter(|indent| !indent.is_empty())
+            .unwrap_or("  ")
+            .to_string();
+
+        let newline = match manifest_raw.contains("\r\n") {
+            true => "\r\n",
+            false => "\n",
+        };
+
+        Ok(Some(Self {
+            json,
+            indent,
+            newline,
+        }))
+    }
+
+    /// Written with the indentation and line endings it was read with, and a trailing newline, the same as npm.
+    pub fn write(&self) -> Result<(), CommandError> {
+        self.write_to(Path::new(PACKAGE_JSON_PATH))
+    }
+
+    /// Writes the package.json to the given path, such as one of a workspace.
+    pub fn write_to(&self, path: &Path) -> Result<(), CommandError> {
+        fs::write(path, self.serialize()? + self.newline).map_err(CommandError::FailedToWriteFile)
+    }
+
+    /// Returns the package.json as it is written, without the trailing newline.
+    pub fn serialize(&self) -> Result<String, CommandError> {
+        let mut manifest_bytes = Vec::new();
+        let formatter = PrettyFormatter::with_indent(self.indent.as_bytes());
+        let mut serializer = serde_json::Serializer::with_formatter(&mut manifest_bytes, formatter);
+        self.json
+            .serialize(&mut serializer)
+            .map_err(CommandError::FailedToSerializeManifest)?;
+
+        let manifest_string = String::from_utf8_lossy(&manifest_bytes).to_string();
+        Ok(match self.newline {
+            "\n" => manifest_string,
+            newline => manifest_string.replace('\n', newline),
+        })
+    }
+
+    /// Returns the value of a top level string field, such as `name` or `version`.
+    pub fn field(&self, field: &str) -> Option<&str> {
+        self.json.get(field)?.as_str()
+    }
+
+    /// The range of Node the package requires, in the `node` field of `engines`.
+    pub fn node_engine(&self) -> Option<&str> {
+        self.json.get("engines")?.get("node")?.as_str()
+    }
+
+    /// Returns the SPDX expression in `license`. Older packages have it as `{"type": "MIT"}`, or list each license
+    /// they are under in `licenses`, which are read as the packages being under any of them.
+    pub fn license(&self) -> Option<String> {
+        let license = |json: &OrderedJson| {
+            json.as_str()
+                .or_else(|| json.get("type")?.as_str())
+                .map(str::to_string)
+        };
+
+        if let Some(license) = self.json.get("license").and_then(license) {
+            return Some(license);
+        }
+
+        let licenses = self
+            .json
+            .get("licenses")?
+            .items()
+            .iter()
+            .filter_map(license)
+            .collect::<Vec<_>>();
+        match licenses.len() {
+            0 => None,
+            1 => licenses.into_iter().next(),
+            _ => Some(format!("({})", licenses.join(" OR "))),
+        }
+    }
+
+    /// The packages whose install scripts are trusted to run, from `trustedDependencies`.
+    /// Returns None if package.json does not have the field.
+    pub fn trusted_dependencies(&self) -> Option<Vec<String>> {
+        Some(
+            self.json
+                .get("trustedDependencies")?
+                .items()
+                .iter()
+                .filter_map(OrderedJson::as_str)
+                .map(str::to_string)
+                .collect(),
+        )
+    }
+
+    /// Adds a package to `trustedDependencies`, which is added at the end of package.json if it is not there yet.
+    pub fn trust_dependency(&mut self, package_name: &str) {
+        let mut trusted = self
+            .json
+            .get("trustedDependencies")
+            .map(|trusted| trusted.items().to_vec())
+            .unwrap_or_default();
+
+        trusted.push(OrderedJson::String(package_name.to_string()));
+        self.set_field("trustedDependencies", OrderedJson::Array(trusted));
+    }
+
+    /// Sets a top level field, keeping its position if it is already in package.json or adding it at the end.
+    pub fn set_field(&mut self, field: &str, value: OrderedJson) {
+        let OrderedJson::Object(fields) = &mut self.json else {
+            return;
+        };
+
+        match fields.iter_mut().find(|(name, _)| name == field) {
+            Some((_, existing)) => *existing = value,
+            None => fields.push((field.to_string(), value)),
+        }
+    }
+
+    /// Returns the name and range of every dependency in the given field, such as `dependencies`.
+    pub fn dependencies(&self, field: &str) -> Vec<(String, String)> {
+        self.json
+            .get(field)
+            .map(|dependencies| {
+                dependencies
+                    .fields()
+                    .iter()
+                    .filter_map(|(name, range)| {
+                        Some((name.to_string(), range.as_str()?.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The custom dependency groups in package.json, such as `buildDependencies`.
+    pub fn groups(&self) -> Vec<DependencyKind> {
+        self.json
+            .fields()
+            .iter()
+            .filter_map(|(field, _)| DependencyKind::parse(field))
+            .filter(|kind| matches!(kind, DependencyKind::Group(_)))
+            .collect()
+    }
+
+    /// Lists the package under the field of its kind with the given range. A package can only be one kind,
+    /// so it is removed from the other field if it was listed there, like npm does.
+    pub fn set_dependency(&mut self, kind: &DependencyKind, package_name: &str, range: String) {
+        let mut others = vec![
+            DependencyKind::Production,
+            DependencyKind::Development,
+            DependencyKind::Optional,
+        ];
+        others.extend(self.groups());
+
+        for other in others {
+            if &other == kind {
+                continue;
+            }
+
+            if let Some(dependencies) = self.json.get_mut(other.field()) {
+                dependencies.remove(package_name);
+            }
+        }
+
+        let OrderedJson::Object(fields) = &mut self.json else {
+            return;
+        };
+
+        let dependencies = match fields.iter().position(|(field, _)| field == kind.field()) {
+            Some(position) => &mut fields[position].1,
+            None => {
+                fields.push((kind.field().to_string(), OrderedJson::Object(Vec::new())));
+                &mut fields.last_mut().expect("Field was just added").1
+            }
+        };
+
+        let OrderedJson::Object(dependencies) = dependencies else {
+            return;
+        };
+
+        let range = OrderedJson::String(range);
+        match dependencies
+            .iter_mut()
+            .find(|(name, _)| name == package_name)
+        {
+            Some((_, existing)) => *existing = range,
+            None => dependencies.push((package_name.to_string(), range)),
+        }
+    }
+
+    /// Removes the package from every dependency field, returns true if it was listed in any of them.
+    pub fn remove_dependency(&mut self, package_name: &str) -> bool {
+        let mut removed = false;
+
+        for field in DEPENDENCY_FIELDS {
+            if let Some(dependencies) = self.json.get_mut(field) {
+                removed |= dependencies.remove(package_name).is_some();
+            }
+        }
+
+        removed
+    }
+
+    /// The range each package is forced to, from `overrides` (npm) or `resolutions` (yarn). Overrides are keyed by
+    /// package name, optionally with the range of versions they replace such as `foo@1.x`. Those nested under a package
+    /// only apply to what that package depends on, and are keyed with the packages they are nested under first, such as
+    /// `foo>bar` for `{ "foo": { "bar": "2.0.0" } }`, `.` being the package itself. A yarn path such as `foo/bar` is
+    /// nested the same way, and `**/bar` applies everywhere. A range of `$foo` is the one package.json depends on foo with.
+    pub fn overrides(&self) -> BTreeMap<String, String> {
+        let mut overrides = BTreeMap::new();
+        for field in ["overrides", "resolutions"] {
+            if let Some(json) = self.json.get(field) {
+                Self::flatten_overrides(json, &[], &mut overrides);
+            }
+        }
+
+        let dependencies = DEPENDENCY_FIELDS
+            .iter()
+            .flat_map(|field| self.dependencies(field))
+            .collect::<HashMap<_, _>>();
+
+        overrides
+            .into_iter()
+            .filter_map(|(key, range)| match range.strip_prefix('$') {
+                Some(reference) => Some((key, dependencies.get(reference)?.to_string())),
+                None => Some((key, range)),
+            })
+            .collect()
+    }
+
+    /// `parents` are the packages the overrides are nested under.
+    fn flatten_overrides(
+        json: &OrderedJson,
+        parents: &[String],
+        overrides: &mut BTreeMap<String, String>,
+    ) {
+        for (key, value) in json.fields() {
+            if key == "." {
+                continue;
+            }
+
+            let mut path = parents.to_vec();
+            let mut segments = key
+                .split('/')
+                .filter(|segment| !segment.is_empty() && *segment != "**");
+            while let Some(segment) = segments.next() {
+                match segment.starts_with('@') {
+                    true => path.push(format!("{segment}/{}", segments.next().unwrap_or_default())),
+                    false => path.push(segment.to_string()),
+                }
+            }
+
+            if path.len() == parents.len() {
+                continue;
+            }
+
+            let range = match value {
+                OrderedJson::Object(_) => {
+                    Self::flatten_overrides(value, &path, overrides);
+                    value.get(".").and_then(OrderedJson::as_str)
+                }
+                value => value.as_str(),
+            };
+
+            if let Some(range) = range {
+                overrides
+                    .entry(path.join(OVERRIDE_SEPARATOR))
+                    .or_insert_with(|| range.to_string());
+            }
+        }
+    }
+
+    /// Splits the key of an override into the package it is nested under, if any, and the package it overrides. Only
+    /// the nearest package it is nested under is returned, it is what the override is applied to the dependencies of.
+    pub fn split_override(key: &str) -> (Option<&str>, &str) {
+        match key.rsplit_once(OVERRIDE_SEPARATOR) {
+            Some((parents, overridden)) => (parents.rsplit(OVERRIDE_SEPARATOR).next(), overridden),
+            None => (None, key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(json: &str) -> Vec<(String, String)> {
+        let manifest = Manifest {
+            json: serde_json::from_str(json).unwrap(),
+            ..Manifest::default()
+        };
+
+        manifest.overrides().into_iter().collect()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, range)| (key.to_string(), range.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_the_packages_overrides_are_nested_under() {
+        assert_eq!(
+            overrides(
+                r#"{ "overrides": { "foo": { ".": "1.0.0", "bar": "2.0.0" }, "baz": "3.0.0" } }"#
+            ),
+            pairs(&[("baz", "3.0.0"), ("foo", "1.0.0"), ("foo>bar", "2.0.0")])
+        );
+        assert_eq!(
+            overrides(r#"{ "overrides": { "@acme/foo@1.x": { "@acme/bar": "2.0.0" } } }"#),
+            pairs(&[("@acme/foo@1.x>@acme/bar", "2.0.0")])
+        );
+        assert_eq!(
+            overrides(r#"{ "resolutions": { "**/bar": "2.0.0", "foo/@acme/baz": "1.0.0" } }"#),
+            pairs(&[("bar", "2.0.0"), ("foo>@acme/baz", "1.0.0")])
+        );
+    }
+
+    #[test]
+    fn splits_override_keys() {
+        assert_eq!(Manifest::split_override("bar"), (None, "bar"));
+        assert_eq!(
+            Manifest::split_override("foo@1.x>bar"),
+            (Some("foo@1.x"), "bar")
+        );
+        assert_eq!(Manifest::split_override("a>b>c"), (Some("b"), "c"));
+    }
+
+    #[test]
+    fn parses_dependency_groups() {
+        assert_eq!(
+            DependencyKind::parse("devDependencies"),
+            Some(DependencyKind::Development)
+        );
+        assert_eq!(
+            DependencyKind::parse("buildDependencies"),
+            Some(DependencyKind::Group(String::from("buildDependencies")))
+        );
+        for invalid in [
+            "build",
+            "Dependencies",
+            "BuildDependencies",
+            "build-Dependencies",
+            "peerDependencies",
+        ] {
+            assert_eq!(DependencyKind::parse(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn saving_to_a_group_moves_the_package() {
+        let mut manifest = Manifest {
+            json: serde_json::from_str(r#"{ "buildDependencies": { "foo": "^1" } }"#).unwrap(),
+            ..Manifest::default()
+        };
+
+        manifest.set_dependency(&DependencyKind::Development, "foo", String::from("^2"));
+        assert_eq!(manifest.dependencies("buildDependencies"), vec![]);
+        assert_eq!(
+            manifest.dependencies("devDependencies"),
+            pairs(&[("foo", "^2")])
+        );
+    }
+}
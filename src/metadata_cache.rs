@@ -0,0 +1,62 @@
+use std::{io::ErrorKind, path::PathBuf};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+lazy_static! {
+    // Kept apart from the package cache, whose entries are all named `package@version`
+    pub static ref METADATA_DIRECTORY: PathBuf = dirs::cache_dir()
+        .expect("Failed to find cache directory")
+        .join("click-metadata");
+}
+
+/// A packument as it was last returned by the registry, with the ETag it was returned with.
+#[derive(Serialize, Deserialize)]
+pub struct CachedPackument {
+    pub url: String, // Entries are only used for the registry they came from, in case another one is configured
+    pub etag: String,
+    pub body: String,
+}
+
+/// Packuments are cached on disk by package name so that the registry can be asked whether they have changed with
+/// `If-None-Match`, rather than sending the whole packument again.
+pub struct MetadataCache;
+impl MetadataCache {
+    /// Returns None if the packument of this URL is not cached. A cache entry that cannot be read is treated as missing,
+    /// as it is only there to save a download.
+    pub async fn read(package_name: &str, url: &str) -> Option<CachedPackument> {
+        let cached_raw = fs::read_to_string(Self::path(package_name)).await.ok()?;
+        let cached = serde_json::from_str::<CachedPackument>(&cached_raw).ok()?;
+
+        (cached.url == url).then_some(cached)
+    }
+
+    /// The entry is written to a temporary file first, so that a reader never sees half of it.
+    pub async fn write(package_name: &str, cached: &CachedPackument) -> Result<(), std::io::Error> {
+        let path = Self::path(package_name);
+        let temporary_path = path.with_extension(format!("{}.tmp", std::process::id()));
+
+        match fs::create_dir_all(&*METADATA_DIRECTORY).await {
+            Err(err) if err.kind() != ErrorKind::AlreadyExists => return Err(err),
+            _ => {}
+        }
+
+        fs::write(&temporary_path, serde_json::to_vec(cached)?).await?;
+        fs::rename(&temporary_path, &path).await
+    }
+
+    /// Removes every cached packument. Returns false if there was nothing to remove.
+    pub async fn clean() -> Result<bool, std::io::Error> {
+        match fs::remove_dir_all(&*METADATA_DIRECTORY).await {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The slash of a scoped package is escaped the same way as in registry URLs, so that every entry is a single file.
+    fn path(package_name: &str) -> PathBuf {
+        METADATA_DIRECTORY.join(format!("{}.json", package_name.replace('/', "%2f")))
+    }
+}
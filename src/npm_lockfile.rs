@@ -0,0 +1,471 @@
+use std::{collections::BTreeMap, fs, io::ErrorKind, str::FromStr};
+
+use semver::Version;
+
+use crate::{
+    errors::CommandError,
+    source::Source,
+    types::{LockedPackage, Lockfile, NpmLockedDependency, NpmLockedPackage, NpmPackageLock},
+    versions::Versions,
+};
+
+pub const NPM_LOCKFILE_PATH: &str = "./package-lock.json";
+
+const MODULES_PREFIX: &str = "node_modules/";
+
+/// Converts the package-lock.json of npm into a lockfile, so that a project moving to click installs the exact versions
+/// npm did from the same tarballs. npm locks each package at its path in node_modules, so the version a package depends
+/// on is found from its path the same way Node finds it. lockfileVersion 1 nests packages instead, which is flattened
+/// into paths first. Packages from anywhere but the registry are left out, they are resolved again when installed.
+pub struct NpmLockfile;
+impl NpmLockfile {
+    /// Returns None if the project has no package-lock.json. `requested` has each package requested from the registry.
+    pub fn import(requested: &[(String, Source)]) -> Result<Option<Lockfile>, CommandError> {
+        let npm_lockfile_raw = match fs::read_to_string(NPM_LOCKFILE_PATH) {
+            Ok(npm_lockfile_raw) => npm_lockfile_raw,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(CommandError::FailedToReadPackageLock(err)),
+        };
+
+        Self::convert(&npm_lockfile_raw, requested).map(Some)
+    }
+
+    pub fn convert(
+        npm_lockfile_raw: &str,
+        requested: &[(String, Source)],
+    ) -> Result<Lockfile, CommandError> {
+        let npm_lock = serde_json::from_str::<NpmPackageLock>(npm_lockfile_raw).map_err(|err| {
+            CommandError::CorruptLockfile {
+                path: NPM_LOCKFILE_PATH.to_string(),
+                detail: err.to_string(),
+            }
+        })?;
+
+        let packages = match npm_lock.packages.is_empty() {
+            true => {
+                let mut packages = BTreeMap::new();
+                Self::flatten("", npm_lock.dependencies, &mut packages);
+                packages
+            }
+            false => npm_lock.packages,
+        };
+
+        // The package@version at each path that is imported
+        let located = packages
+            .iter()
+            .filter_map(|(path, npm_package)| {
+                let (_, name) = path.rsplit_once(MODULES_PREFIX)?;
+                let version = npm_package.version.as_ref()?;
+                let from_registry = npm_package.resolved.as_ref().is_none_or(|resolved| {
+                    resolved.starts_with("https://") || resolved.starts_with("http://")
+                });
+
+                let imported = !npm_package.link
+                    && !npm_package.in_bundle
+                    && from_registry
+                    && Version::from_str(version).is_ok();
+                imported.then(|| {
+                    (
+                        path.as_str(),
+                        Versions::stringify(&name.to_string(), version),
+                    )
+                })
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let resolve = |from: &str, name: &String| {
+            let path = Self::resolve(&packages, from, name)?;
+            located.get(path.as_str()).cloned()
+        };
+
+        let mut packages_imported: BTreeMap<String, LockedPackage> = BTreeMap::new();
+        for (path, package) in located.iter() {
+            let npm_package = &packages[*path];
+            let dependencies = npm_package
+                .dependencies
+                .keys()
+                .chain(npm_package.optional_dependencies.keys())
+                .chain(npm_package.peer_dependencies.keys())
+                .filter_map(|name| resolve(path, name));
+
+            // The same version can be at more than one path, with what it depends on found from each
+            let locked_package =
+                packages_imported
+                    .entry(package.to_string())
+                    .or_insert_with(|| LockedPackage {
+                        resolved: npm_package.resolved.clone(),
+                        registry: None, // Not recorded by npm
+                        integrity: npm_package.integrity.clone(),
+                        has_install_script: npm_package.has_install_script,
+                        bin: npm_package.bin.clone(),
+                        dependencies: Vec::new(),
+                        optional: npm_package.optional,
+                    });
+            locked_package.dependencies.extend(dependencies);
+            locked_package.dependencies.sort();
+            locked_package.dependencies.dedup();
+        }
+
+        let requested = requested
+            .iter()
+            .filter_map(|(package_name, source)| {
+                Some((source.spec(package_name), resolve("", package_name)?))
+            })
+            .collect();
+
+        Ok(Lockfile::import(packages_imported, requested))
+    }
+
+    /// The path a package is found at from the package at another path, which is in the node_modules of the package
+    /// itself or else in that of the closest package it is nested in. The project itself is at an empty path.
+    fn resolve(
+        packages: &BTreeMap<String, NpmLockedPackage>,
+        from: &str,
+        name: &String,
+    ) -> Option<String> {
+        let mut from = from;
+        loop {
+            let path = match from.is_empty() {
+                true => format!("{MODULES_PREFIX}{name}"),
+                false => format!("{from}/{MODULES_PREFIX}{name}"),
+            };
+            if packages.contains_key(&path) {
+                return Some(path);
+            }
+
+            if from.is_empty() {
+                return None;
+            }
+
+            from = from
+                .rsplit_once(&format!("/{MODULES_PREFIX}"))
+                .map_or("", |(parent, _)| parent);
+        }
+    }
+
+    /// Places the nested packages of lockfileVersion 1 at their paths in node_modules.
+    fn flatten(
+        parent: &str,
+        dependencies: BTreeMap<String, NpmLockedDependency>,
+        packages: &mut BTreeMap<String, NpmLockedPackage>,
+    ) {
+        for (name, dependency) in dependencies {
+            let path = match parent.is_empty() {
+                true => format!("{MODULES_PREFIX}{name}"),
+                false => format!("{parent}/{MODULES_PREFIX}{name}"),
+            };
+
+            // An alias is locked with the name of the package it installs
+            let version = match dependency.version.strip_prefix("npm:") {
+                Some(aliased) => Versions::parse_raw_package_details(aliased.to_string()).1,
+                None => dependency.version,
+            };
+
+            Self::flatten(&path, dependency.dependencies, packages);
+            packages.insert(
+                path,
+                NpmLockedPackage {
+                    version: Some(version),
+                    resolved: dependency.resolved,
+                    integrity: dependency.integrity,
+                    link: false,
+                    in_bundle: dependency.bundled,
+                    optional: dependency.optional,
+                    has_install_script: false,
+                    bin: BTreeMap::new(),
+                    dependencies: dependency.requires,
+                    optional_dependencies: BTreeMap::new(),
+                    peer_dependencies: BTreeMap::new(),
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hoisted::HoistedPlan, lockfile::LockfileFormat};
+
+    // Written by npm 7 and npm 9 for the same project, with scoped, nested and git dependencies
+    const NPM_LOCKFILE_V2_FIXTURE: &str =
+        include_str!("../tests/fixtures/npm/package-lock.v2.json");
+    const NPM_LOCKFILE_V3_FIXTURE: &str =
+        include_str!("../tests/fixtures/npm/package-lock.v3.json");
+
+    const NPM_LOCKFILE_V3: &str = r#"{
+        "name": "app",
+        "version": "1.0.0",
+        "lockfileVersion": 3,
+        "requires": true,
+        "packages": {
+            "": {
+                "name": "app",
+                "version": "1.0.0",
+                "dependencies": { "debug": "^2.6.9", "ms": "^2.1.3", "@types/node": "^20.8.0", "my-lib": "github:me/my-lib" },
+                "optionalDependencies": { "fsevents": "^2.3.3" }
+            },
+            "node_modules/@types/node": {
+                "version": "20.8.10",
+                "resolved": "https://registry.npmjs.org/@types/node/-/node-20.8.10.tgz",
+                "integrity": "sha512-types-node",
+                "dependencies": { "undici-types": "~5.26.4" }
+            },
+            "node_modules/debug": {
+                "version": "2.6.9",
+                "resolved": "https://registry.npmjs.org/debug/-/debug-2.6.9.tgz",
+                "integrity": "sha512-debug",
+                "dependencies": { "ms": "2.0.0" }
+            },
+            "node_modules/debug/node_modules/ms": {
+                "version": "2.0.0",
+                "resolved": "https://registry.npmjs.org/ms/-/ms-2.0.0.tgz",
+                "integrity": "sha512-ms-2.0.0"
+            },
+            "node_modules/fsevents": {
+                "version": "2.3.3",
+                "resolved": "https://registry.npmjs.org/fsevents/-/fsevents-2.3.3.tgz",
+                "integrity": "sha512-fsevents",
+                "hasInstallScript": true,
+                "optional": true
+            },
+            "node_modules/ms": {
+                "version": "2.1.3",
+                "resolved": "https://registry.npmjs.org/ms/-/ms-2.1.3.tgz",
+                "integrity": "sha512-ms-2.1.3"
+            },
+            "node_modules/my-lib": {
+                "version": "1.0.0",
+                "resolved": "git+ssh://git@github.com/me/my-lib.git#4358a00",
+                "dependencies": { "ms": "^2.1.0" }
+            },
+            "node_modules/undici-types": {
+                "version": "5.26.5",
+                "resolved": "https://registry.npmjs.org/undici-types/-/undici-types-5.26.5.tgz",
+                "integrity": "sha512-undici-types"
+            }
+        }
+    }"#;
+
+    const NPM_LOCKFILE_V1: &str = r#"{
+        "name": "app",
+        "version": "1.0.0",
+        "lockfileVersion": 1,
+        "requires": true,
+        "dependencies": {
+            "@types/node": {
+                "version": "20.8.10",
+                "resolved": "https://registry.npmjs.org/@types/node/-/node-20.8.10.tgz",
+                "integrity": "sha512-types-node",
+                "requires": { "undici-types": "~5.26.4" }
+            },
+            "debug": {
+                "version": "2.6.9",
+                "resolved": "https://registry.npmjs.org/debug/-/debug-2.6.9.tgz",
+                "integrity": "sha512-debug",
+                "requires": { "ms": "2.0.0" },
+                "dependencies": {
+                    "ms": {
+                        "version": "2.0.0",
+                        "resolved": "https://registry.npmjs.org/ms/-/ms-2.0.0.tgz",
+                        "integrity": "sha512-ms-2.0.0"
+                    }
+                }
+            },
+            "fsevents": {
+                "version": "2.3.3",
+                "resolved": "https://registry.npmjs.org/fsevents/-/fsevents-2.3.3.tgz",
+                "integrity": "sha512-fsevents",
+                "optional": true
+            },
+            "ms": {
+                "version": "2.1.3",
+                "resolved": "https://registry.npmjs.org/ms/-/ms-2.1.3.tgz",
+                "integrity": "sha512-ms-2.1.3"
+            },
+            "my-lib": {
+                "version": "git+ssh://git@github.com/me/my-lib.git#4358a00",
+                "from": "my-lib@github:me/my-lib",
+                "requires": { "ms": "^2.1.0" }
+            },
+            "undici-types": {
+                "version": "5.26.5",
+                "resolved": "https://registry.npmjs.org/undici-types/-/undici-types-5.26.5.tgz",
+                "integrity": "sha512-undici-types"
+            }
+        }
+    }"#;
+
+    fn requested() -> Vec<(String, Source)> {
+        [
+            ("debug", "^2.6.9"),
+            ("ms", "^2.1.3"),
+            ("@types/node", "^20.8.0"),
+            ("fsevents", "^2.3.3"),
+        ]
+        .iter()
+        .map(|(package_name, range)| {
+            let semantic_version = Versions::parse_semantic_version(range).ok();
+            (package_name.to_string(), Source::Registry(semantic_version))
+        })
+        .collect()
+    }
+
+    #[test]
+    fn imports_npm_layout() {
+        let lockfile = NpmLockfile::convert(NPM_LOCKFILE_V3, &requested()).unwrap();
+
+        let packages = lockfile.packages.keys().cloned().collect::<Vec<_>>();
+        assert_eq!(
+            packages,
+            [
+                "@types/node@20.8.10",
+                "debug@2.6.9",
+                "fsevents@2.3.3",
+                "ms@2.0.0",
+                "ms@2.1.3",
+                "undici-types@5.26.5"
+            ]
+        );
+        assert_eq!(lockfile.requested["debug@^2.6.9"], "debug@2.6.9");
+        assert_eq!(lockfile.packages["debug@2.6.9"].dependencies, ["ms@2.0.0"]);
+
+        let fsevents = &lockfile.packages["fsevents@2.3.3"];
+        assert!(fsevents.optional && fsevents.has_install_script);
+        assert_eq!(fsevents.integrity.as_deref(), Some("sha512-fsevents"));
+
+        // Every package is linked at the same path npm installed it at
+        let roots = lockfile.requested.values().cloned().collect::<Vec<_>>();
+        let dependencies = lockfile
+            .packages
+            .iter()
+            .map(|(package, locked_package)| {
+                (package.to_string(), locked_package.dependencies.clone())
+            })
+            .collect();
+        let placements = HoistedPlan::new(&roots, &dependencies)
+            .placements
+            .into_iter()
+            .map(|(location, package)| (location.join("/node_modules/"), package))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            placements,
+            [
+                ("@types/node", "@types/node@20.8.10"),
+                ("debug", "debug@2.6.9"),
+                ("debug/node_modules/ms", "ms@2.0.0"),
+                ("fsevents", "fsevents@2.3.3"),
+                ("ms", "ms@2.1.3"),
+                ("undici-types", "undici-types@5.26.5"),
+            ]
+            .map(|(location, package)| (location.to_string(), package.to_string()))
+        );
+    }
+
+    #[test]
+    fn imports_lockfile_version_1() {
+        let imported = NpmLockfile::convert(NPM_LOCKFILE_V1, &requested()).unwrap();
+        let expected = NpmLockfile::convert(NPM_LOCKFILE_V3, &requested()).unwrap();
+
+        // lockfileVersion 1 does not record install scripts
+        let mut expected = serde_json::to_value(expected).unwrap();
+        expected["packages"]["fsevents@2.3.3"]
+            .as_object_mut()
+            .unwrap()
+            .remove("hasInstallScript");
+
+        assert_eq!(serde_json::to_value(imported).unwrap(), expected);
+    }
+
+    fn fixture_requested() -> Vec<(String, Source)> {
+        let npm_lock = serde_json::from_str::<serde_json::Value>(NPM_LOCKFILE_V3_FIXTURE).unwrap();
+        let project = &npm_lock["packages"][""];
+        ["dependencies", "devDependencies"]
+            .iter()
+            .flat_map(|field| project[field].as_object().unwrap())
+            .map(|(package_name, spec)| {
+                let spec = spec.as_str().unwrap();
+                let source = Source::from_spec(spec).unwrap_or_else(|| {
+                    Source::Registry(Versions::parse_semantic_version(spec).ok())
+                });
+                (package_name.to_string(), source)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn imports_npm_fixtures_unchanged() {
+        for fixture in [NPM_LOCKFILE_V2_FIXTURE, NPM_LOCKFILE_V3_FIXTURE] {
+            let lockfile = NpmLockfile::convert(fixture, &fixture_requested()).unwrap();
+
+            // Every package from the registry is locked with the tarball and integrity npm installed it from
+            let npm_lock = serde_json::from_str::<serde_json::Value>(fixture).unwrap();
+            for (path, npm_package) in npm_lock["packages"].as_object().unwrap() {
+                let Some((_, name)) = path.rsplit_once(MODULES_PREFIX) else {
+                    continue;
+                };
+                let package = format!("{name}@{}", npm_package["version"].as_str().unwrap());
+                if name == "my-lib" {
+                    assert!(!lockfile.packages.contains_key(&package));
+                    continue;
+                }
+
+                let locked_package = &lockfile.packages[&package];
+                assert_eq!(
+                    locked_package.resolved.as_deref(),
+                    npm_package["resolved"].as_str()
+                );
+                assert_eq!(
+                    locked_package.integrity.as_deref(),
+                    npm_package["integrity"].as_str()
+                );
+            }
+            assert_eq!(lockfile.packages.len(), 10);
+
+            // Dependencies are found from where each package is nested, a git dependency is resolved again
+            assert_eq!(
+                lockfile.packages["@babel/code-frame@7.22.13"].dependencies,
+                ["@babel/highlight@7.10.4", "chalk@2.4.2", "js-tokens@4.0.0"]
+            );
+            assert_eq!(
+                lockfile.packages["@babel/highlight@7.10.4"].dependencies,
+                ["chalk@2.4.2", "js-tokens@4.0.0"]
+            );
+            assert_eq!(lockfile.packages["debug@2.6.9"].dependencies, ["ms@2.0.0"]);
+            assert_eq!(
+                lockfile.requested["@babel/highlight@^7.22.20"],
+                "@babel/highlight@7.22.20"
+            );
+            assert_eq!(
+                lockfile.requested["@types/node@^20.8.0"],
+                "@types/node@20.8.10"
+            );
+            assert!(!lockfile
+                .requested
+                .keys()
+                .any(|spec| spec.starts_with("my-lib")));
+        }
+    }
+
+    #[test]
+    fn imported_npm_fixtures_are_written_and_read_back_the_same() {
+        let imported = NpmLockfile::convert(NPM_LOCKFILE_V2_FIXTURE, &fixture_requested()).unwrap();
+        let expected = serde_json::to_value(&imported).unwrap();
+        assert_eq!(
+            serde_json::to_value(
+                NpmLockfile::convert(NPM_LOCKFILE_V3_FIXTURE, &fixture_requested()).unwrap()
+            )
+            .unwrap(),
+            expected
+        );
+
+        for format in [LockfileFormat::Json, LockfileFormat::Toml] {
+            let written = imported.serialize(format).unwrap();
+            let read = Lockfile::parse(&written, format).unwrap();
+
+            assert_eq!(serde_json::to_value(&read).unwrap(), expected);
+            assert_eq!(read.serialize(format).unwrap(), written);
+        }
+    }
+}
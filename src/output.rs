@@ -0,0 +1,113 @@
+use std::{
+    fmt::Display,
+    io,
+    process::Stdio,
+    sync::atomic::{AtomicBool, Ordering::SeqCst},
+};
+
+use serde::Serialize;
+
+use crate::{
+    errors::{ClickError, CommandError},
+    logger::Logger,
+};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static PRINTED: AtomicBool = AtomicBool::new(false); // Whether the JSON result has been printed, it is only printed once
+
+/// Where commands print what they do. With `--json` stdout only has the JSON result of the command, so anything
+/// meant for people reading it goes to stderr instead.
+pub struct Output;
+impl Output {
+    /// Switches every command to JSON output, called when a command is given `--json`.
+    pub fn use_json() {
+        JSON.store(true, SeqCst);
+    }
+
+    pub fn is_json() -> bool {
+        JSON.load(SeqCst)
+    }
+
+    /// Prints a message for people to read, to stderr with `--json` and not at all with `--quiet`.
+    pub fn message(message: impl Display) {
+        if Logger::is_quiet() {
+            return;
+        }
+
+        match Self::is_json() {
+            true => eprintln!("{message}"),
+            false => println!("{message}"),
+        }
+    }
+
+    /// Prints the result of a command as JSON. Does nothing without `--json`, as the command prints it as text.
+    pub fn json<T: Serialize>(result: &T) -> Result<(), CommandError> {
+        if !Self::is_json() {
+            return Ok(());
+        }
+
+        let result_string =
+            serde_json::to_string_pretty(result).map_err(CommandError::FailedToSerializeOutput)?;
+        println!("{result_string}");
+        PRINTED.store(true, SeqCst);
+
+        Ok(())
+    }
+
+    /// Prints rows in columns as wide as their widest cell, under a header.
+    pub fn table<const N: usize>(header: [&str; N], rows: &[[&str; N]]) {
+        for line in Self::columns(header, rows) {
+            println!("{line}");
+        }
+    }
+
+    /// Returns the lines of a table without printing them, the header first.
+    pub fn columns<const N: usize>(header: [&str; N], rows: &[[&str; N]]) -> Vec<String> {
+        let mut widths = header.map(|cell| cell.chars().count());
+        for row in rows.iter() {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        [header]
+            .iter()
+            .chain(rows.iter())
+            .map(|row| {
+                let line = row
+                    .iter()
+                    .zip(widths)
+                    .map(|(cell, width)| format!("{cell:width$}"))
+                    .collect::<Vec<_>>()
+                    .join("  ");
+
+                line.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    /// Prints the error click failed with, as an object with the message, its kind and the exit code on stdout with
+    /// `--json`. A command that printed its result before failing, such as an install with its report, already has
+    /// the errors in it.
+    pub fn error(error: &ClickError) {
+        match Self::is_json() && !PRINTED.load(SeqCst) {
+            true => println!(
+                "{}",
+                serde_json::json!({
+                    "error": error.message(),
+                    "kind": error.kind(),
+                    "exitCode": error.exit_code(),
+                })
+            ),
+            false => eprintln!("{error}"),
+        }
+    }
+
+    /// Where the output of a script goes, which is stderr with `--json` so that it is not mistaken for the result.
+    pub fn script_stdout() -> Stdio {
+        match Self::is_json() {
+            true => Stdio::from(io::stderr()),
+            false => Stdio::inherit(),
+        }
+    }
+}
@@ -0,0 +1,420 @@
+use std::{
+    fs::{self, Metadata},
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use flate2::{write::GzEncoder, Compression};
+use tar::{Builder, EntryType, Header};
+
+use crate::{errors::CommandError, manifest::Manifest, types::PackedFile};
+
+const IGNORE_FILES: [&str; 2] = [".npmignore", ".gitignore"]; // A directory uses the first of these it has
+const ALWAYS_INCLUDED: [&str; 3] = ["README", "LICENSE", "LICENCE"]; // At the root, in any case and with any extension
+const MTIME: u64 = 499162500; // 1985-10-26T08:15:00Z, which npm gives every entry so the tarball only depends on its files
+
+// Never packed, wherever they are in the project
+const ALWAYS_IGNORED: [&str; 20] = [
+    ".npmignore",
+    ".gitignore",
+    ".git",
+    ".svn",
+    ".hg",
+    "CVS",
+    "node_modules",
+    ".npmrc",
+    ".DS_Store",
+    ".lock-wscript",
+    "npm-debug.log",
+    "config.gypi",
+    "click-lock.json",
+    "click-lock.toml",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "*.orig",
+    ".*.swp",
+    "._*",
+];
+
+/// A line of an ignore file or an entry of the `files` field of package.json, which use the syntax of .gitignore.
+struct Rule {
+    pattern: String,
+    negated: bool, // Starts with `!`, which brings back what an earlier rule matched
+    directory_only: bool, // Ends with `/`, so it only matches directories
+    anchored: bool, // Has a `/` before the end, so it matches the whole path rather than the name at any depth
+}
+
+impl Rule {
+    /// Returns None for blank lines and comments.
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let (directory_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+
+        let pattern = line.trim_start_matches("./").trim_start_matches('/');
+        if pattern.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            pattern: pattern.to_string(),
+            negated,
+            directory_only,
+            anchored: line.contains('/'),
+        })
+    }
+
+    fn matches(&self, path: &str, is_directory: bool) -> bool {
+        if self.directory_only && !is_directory {
+            return false;
+        }
+
+        let name = path.rsplit('/').next().unwrap_or(path);
+        match self.anchored {
+            true => Self::glob(self.pattern.as_bytes(), path.as_bytes()),
+            false => Self::glob(self.pattern.as_bytes(), name.as_bytes()),
+        }
+    }
+
+    /// Whether the last of the rules that matches the path matched it, or None if none of them match it.
+    fn last_match(rules: &[Rule], path: &str, is_directory: bool) -> Option<bool> {
+        rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(path, is_directory))
+            .map(|rule| !rule.negated)
+    }
+
+    /// `*` and `?` match within a directory, `**` matches across directories and `**/` also matches no directory.
+    fn glob(pattern: &[u8], text: &[u8]) -> bool {
+        match pattern {
+            [] => text.is_empty(),
+            [b'*', b'*', b'/', rest @ ..] => {
+                Self::glob(rest, text)
+                    || (0..text.len()).any(|i| text[i] == b'/' && Self::glob(rest, &text[i + 1..]))
+            }
+            [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| Self::glob(rest, &text[i..])),
+            [b'*', rest @ ..] => {
+                for i in 0..=text.len() {
+                    if Self::glob(rest, &text[i..]) {
+                        return true;
+                    }
+                    if text.get(i) == Some(&b'/') {
+                        return false;
+                    }
+                }
+                false
+            }
+            [b'?', rest @ ..] => {
+                matches!(text, [char, text @ ..] if *char != b'/' && Self::glob(rest, text))
+            }
+            [char, rest @ ..] => {
+                matches!(text, [text_char, text @ ..] if text_char == char && Self::glob(rest, text))
+            }
+        }
+    }
+}
+
+/// Builds the tarball of a package the same way npm does, which is what the registry serves and the installer extracts.
+pub struct Pack;
+impl Pack {
+    /// The files of the project in the directory that go in its tarball, sorted by path. With the `files` field of
+    /// package.json only what it lists is packed, otherwise everything that .npmignore excludes is left out, or
+    /// .gitignore if there is no .npmignore. Each directory can have its own ignore file for what is in it.
+    /// package.json, the README, the LICENSE and the `main` file are always packed.
+    pub fn files(root: &Path, manifest: &Manifest) -> Result<Vec<PackedFile>, CommandError> {
+        let selected = manifest.json.get("files").map(|files| {
+            let patterns = files.items().iter().filter_map(|pattern| pattern.as_str());
+            patterns
+                .filter_map(|pattern| {
+                    // An entry names a path from the root, even without a `/` in it
+                    Rule::parse(pattern).map(|rule| Rule {
+                        anchored: true,
+                        ..rule
+                    })
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let main = manifest
+            .field("main")
+            .map(|main| main.trim_start_matches("./").to_string());
+
+        let walk = PackWalk {
+            root,
+            selected: selected.as_deref(),
+            main: main.as_deref(),
+        };
+
+        let mut files = Vec::new();
+        walk.directory("", &mut Vec::new(), &mut files)
+            .map_err(CommandError::FailedToPack)?;
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(files)
+    }
+
+    /// Gzips the files into a tarball, each one under a `package/` directory as the registry expects. If given,
+    /// `manifest` is packed as package.json instead of the file, see Workspaces::published_manifest().
+    pub fn tarball(
+        root: &Path,
+        files: &[PackedFile],
+        manifest: Option<&str>,
+    ) -> Result<Vec<u8>, CommandError> {
+        Self::build(root, files, manifest).map_err(CommandError::FailedToPack)
+    }
+
+    /// Named the way npm names it, `@scope/name` becomes `scope-name-<version>.tgz`.
+    pub fn filename(package_name: &str, version: &str) -> String {
+        let package_name = package_name.trim_start_matches('@').replace('/', "-");
+        format!("{package_name}-{version}.tgz")
+    }
+
+    fn build(
+        root: &Path,
+        files: &[PackedFile],
+        manifest: Option<&str>,
+    ) -> Result<Vec<u8>, io::Error> {
+        let mut builder = Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+
+        for file in files {
+            let contents = match manifest {
+                Some(manifest) if file.path == "package.json" => manifest.as_bytes().to_vec(),
+                _ => fs::read(root.join(&file.path))?,
+            };
+
+            let mut header = Header::new_ustar();
+            header.set_entry_type(EntryType::Regular);
+            header.set_size(contents.len() as u64);
+            header.set_mode(file.mode);
+            header.set_mtime(MTIME);
+
+            let path = format!("package/{}", file.path);
+            builder.append_data(&mut header, path, contents.as_slice())?;
+        }
+
+        builder.into_inner()?.finish()
+    }
+
+    /// Executables stay executable, everything else can be read by anyone.
+    #[cfg(unix)]
+    fn mode(metadata: &Metadata) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+
+        match metadata.permissions().mode() & 0o111 {
+            0 => 0o644,
+            _ => 0o755,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn mode(_metadata: &Metadata) -> u32 {
+        0o644
+    }
+}
+
+/// What decides which files of the project are packed, see Pack::files().
+struct PackWalk<'a> {
+    root: &'a Path,
+    selected: Option<&'a [Rule]>, // The `files` field of package.json, None if it does not have one
+    main: Option<&'a str>,
+}
+
+impl PackWalk<'_> {
+    /// `directory` is relative to the root, which is an empty string. `ignores` has the rules of the ignore file of
+    /// each directory above it, along with the directory they are relative to.
+    fn directory(
+        &self,
+        directory: &str,
+        ignores: &mut Vec<(String, Vec<Rule>)>,
+        files: &mut Vec<PackedFile>,
+    ) -> Result<(), io::Error> {
+        let path = self.root.join(directory);
+
+        // The `files` field takes the place of the ignore file of the root
+        let ignore_rules = match directory.is_empty() && self.selected.is_some() {
+            true => None,
+            false => Self::ignore_rules(&path)?,
+        };
+        let has_ignore_rules = ignore_rules.is_some();
+        if let Some(rules) = ignore_rules {
+            ignores.push((directory.to_string(), rules));
+        }
+
+        let mut entries = fs::read_dir(&path)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let relative_path = match directory.is_empty() {
+                true => name.to_string(),
+                false => format!("{directory}/{name}"),
+            };
+
+            // Links are left out, as what they point to may not be in the project
+            let metadata = entry.path().symlink_metadata()?;
+            if metadata.is_symlink() {
+                continue;
+            }
+
+            let is_directory = metadata.is_dir();
+            if ALWAYS_IGNORED
+                .iter()
+                .any(|pattern| Rule::glob(pattern.as_bytes(), name.as_bytes()))
+            {
+                continue;
+            }
+
+            if !is_directory && self.always_included(&relative_path) {
+                files.push(Self::packed_file(relative_path, &metadata));
+                continue;
+            }
+
+            if Self::is_ignored(ignores, &relative_path, is_directory) {
+                continue;
+            }
+
+            if is_directory {
+                self.directory(&relative_path, ignores, files)?;
+            } else if self.is_selected(&relative_path) {
+                files.push(Self::packed_file(relative_path, &metadata));
+            }
+        }
+
+        if has_ignore_rules {
+            ignores.pop();
+        }
+
+        Ok(())
+    }
+
+    fn ignore_rules(directory: &Path) -> Result<Option<Vec<Rule>>, io::Error> {
+        for ignore_file in IGNORE_FILES {
+            match fs::read_to_string(directory.join(ignore_file)) {
+                Ok(contents) => {
+                    return Ok(Some(contents.lines().filter_map(Rule::parse).collect()))
+                }
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// The rules of a directory are matched against paths relative to it, and those deeper in the project win.
+    fn is_ignored(ignores: &[(String, Vec<Rule>)], path: &str, is_directory: bool) -> bool {
+        let mut ignored = false;
+        for (directory, rules) in ignores {
+            let relative_path = match directory.is_empty() {
+                true => path,
+                false => path
+                    .strip_prefix(directory.as_str())
+                    .and_then(|path| path.strip_prefix('/'))
+                    .unwrap_or(path),
+            };
+
+            if let Some(matched) = Rule::last_match(rules, relative_path, is_directory) {
+                ignored = matched;
+            }
+        }
+
+        ignored
+    }
+
+    /// A file is selected by the `files` field if it or a directory it is in is listed, and not excluded after that.
+    fn is_selected(&self, path: &str) -> bool {
+        let Some(selected) = self.selected else {
+            return true;
+        };
+
+        let mut is_selected = false;
+        let mut ancestor = String::new();
+        for (i, component) in path.split('/').enumerate() {
+            if i > 0 {
+                ancestor.push('/');
+            }
+            ancestor.push_str(component);
+
+            let is_directory = ancestor.len() < path.len();
+            if let Some(matched) = Rule::last_match(selected, &ancestor, is_directory) {
+                is_selected = matched;
+            }
+        }
+
+        is_selected
+    }
+
+    fn always_included(&self, path: &str) -> bool {
+        if path == "package.json" || Some(path) == self.main {
+            return true;
+        }
+
+        let name = path.to_ascii_uppercase();
+        !path.contains('/')
+            && ALWAYS_INCLUDED.iter().any(|included| {
+                name.strip_prefix(included)
+                    .is_some_and(|extension| extension.is_empty() || extension.starts_with('.'))
+            })
+    }
+
+    fn packed_file(path: String, metadata: &Metadata) -> PackedFile {
+        PackedFile {
+            path,
+            size: metadata.len(),
+            mode: Pack::mode(metadata),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str, is_directory: bool) -> bool {
+        Rule::parse(pattern).is_some_and(|rule| rule.matches(path, is_directory))
+    }
+
+    #[test]
+    fn matches_names_at_any_depth_unless_anchored() {
+        assert!(matches("*.log", "debug.log", false));
+        assert!(matches("*.log", "logs/debug.log", false));
+        assert!(matches("/build", "build", true));
+        assert!(!matches("/build", "src/build", true));
+        assert!(matches("src/*.js", "src/index.js", false));
+        assert!(!matches("src/*.js", "src/lib/index.js", false));
+        assert!(matches("docs/", "docs", true));
+        assert!(!matches("docs/", "docs", false));
+    }
+
+    #[test]
+    fn double_star_matches_across_directories() {
+        assert!(matches("**/test", "test", true));
+        assert!(matches("**/test", "src/lib/test", true));
+        assert!(matches("src/**/*.ts", "src/a/b/index.ts", false));
+        assert!(matches("src/**", "src/a/b", false));
+    }
+
+    #[test]
+    fn last_matching_rule_wins() {
+        let rules = ["*.md", "!README.md"]
+            .into_iter()
+            .filter_map(Rule::parse)
+            .collect::<Vec<_>>();
+
+        assert_eq!(Rule::last_match(&rules, "CHANGES.md", false), Some(true));
+        assert_eq!(Rule::last_match(&rules, "README.md", false), Some(false));
+        assert_eq!(Rule::last_match(&rules, "index.js", false), None);
+    }
+}
@@ -0,0 +1,817 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, fs,
+    io::{self, ErrorKind},
+    path::{Component, Path, PathBuf},
+};
+
+use log::warn;
+
+use crate::{
+    cache::CACHE_DIRECTORY, errors::CommandError, integrity::Integrity, isolated::IsolatedLayout,
+    types::Lockfile,
+};
+
+pub const PATCHES_DIRECTORY: &str = "patches"; // Where `click patch-commit` saves patches, committed with the project
+const PATCH_EXTENSION: &str = "patch";
+const PATCHED_DIRECTORY: &str = "node_modules/.patched"; // The patched copy of each package, laid out the same as the cache
+const APPLIED_FILE: &str = "applied"; // Next to a patched copy, the integrity of the patch it was made with
+const CONTEXT_LINES: usize = 3; // Unchanged lines around each change, the same as diff and git
+const MAX_EDIT_DISTANCE: usize = 2000; // A file that changed more than this is diffed as replaced rather than line by line
+const NO_NEWLINE: &str = "\\ No newline at end of file";
+
+/// Files that are not part of a package as it was published, and are neither diffed nor copied for editing.
+const SKIPPED_FILES: [&str; 2] = ["node_modules", "click-lock.json"];
+
+#[derive(Debug, PartialEq)]
+enum Edit {
+    Equal(usize, usize), // Indices of the line in the original and edited file
+    Delete(usize),
+    Insert(usize),
+}
+
+#[derive(Debug, PartialEq)]
+struct Hunk {
+    old_start: usize, // Line numbers start at 1, a hunk with no old lines inserts after old_start
+    lines: Vec<(char, String)>, // Each line with its marker, a space for context. Lines keep their line break.
+}
+
+#[derive(Debug, PartialEq)]
+struct FilePatch {
+    old_path: Option<String>, // None for a file the patch creates
+    new_path: Option<String>, // None for a file the patch deletes
+    hunks: Vec<Hunk>,
+}
+
+/// A unified diff of the files of a package, the same format as `git diff` and patch-package write. Only text files are
+/// patched, binary files are left as they were published.
+#[derive(Debug, PartialEq)]
+pub struct Patch {
+    files: Vec<FilePatch>,
+}
+
+impl Patch {
+    /// Diffs every file of the edited copy of a package against the original, with paths relative to the package.
+    pub fn diff(original: &Path, edited: &Path) -> Result<String, io::Error> {
+        let mut paths = BTreeSet::new();
+        Self::collect_files(original, original, &mut paths)?;
+        Self::collect_files(edited, edited, &mut paths)?;
+
+        let mut diff = String::new();
+        for path in paths {
+            let read = |root: &Path| match fs::read(root.join(&path)) {
+                Ok(bytes) => Ok(Some(bytes)),
+                Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err),
+            };
+            let (old, new) = (read(original)?, read(edited)?);
+            if old == new {
+                continue;
+            }
+
+            let (Ok(old_text), Ok(new_text)) = (
+                String::from_utf8(old.clone().unwrap_or_default()),
+                String::from_utf8(new.clone().unwrap_or_default()),
+            ) else {
+                warn!("skipped '{path}' as binary files cannot be patched");
+                continue;
+            };
+
+            let old_header = old
+                .as_ref()
+                .map_or(String::from("/dev/null"), |_| format!("a/{path}"));
+            let new_header = new
+                .as_ref()
+                .map_or(String::from("/dev/null"), |_| format!("b/{path}"));
+            diff.push_str(&format!(
+                "diff --git a/{path} b/{path}\n--- {old_header}\n+++ {new_header}\n"
+            ));
+            diff.push_str(&Self::diff_text(&old_text, &new_text));
+        }
+
+        Ok(diff)
+    }
+
+    /// Paths use `/` on every platform, so patches can be shared.
+    fn collect_files(
+        root: &Path,
+        directory: &Path,
+        paths: &mut BTreeSet<String>,
+    ) -> Result<(), io::Error> {
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            if SKIPPED_FILES.contains(&entry.file_name().to_string_lossy().as_ref()) {
+                continue;
+            }
+
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                Self::collect_files(root, &entry.path(), paths)?;
+            } else if file_type.is_file() {
+                let relative = entry.path();
+                let relative = relative.strip_prefix(root).unwrap_or(&relative);
+                let components = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>();
+                paths.insert(components.join("/"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The hunks of a file that changed, each with the unchanged lines around it.
+    fn diff_text(old_text: &str, new_text: &str) -> String {
+        let old = old_text.split_inclusive('\n').collect::<Vec<_>>();
+        let new = new_text.split_inclusive('\n').collect::<Vec<_>>();
+        let edits = Self::edits(&old, &new);
+
+        // Changes closer together than twice the context share a hunk
+        let changes = edits
+            .iter()
+            .enumerate()
+            .filter(|(_, edit)| !matches!(edit, Edit::Equal(..)))
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for change in changes {
+            let start = change.saturating_sub(CONTEXT_LINES);
+            let end = (change + 1 + CONTEXT_LINES).min(edits.len());
+            match ranges.last_mut() {
+                Some(range) if start <= range.1 => range.1 = end,
+                _ => ranges.push((start, end)),
+            }
+        }
+
+        let mut diff = String::new();
+        for (start, end) in ranges {
+            let old_before = edits[..start]
+                .iter()
+                .filter(|edit| !matches!(edit, Edit::Insert(_)))
+                .count();
+            let new_before = edits[..start]
+                .iter()
+                .filter(|edit| !matches!(edit, Edit::Delete(_)))
+                .count();
+
+            let mut lines = String::new();
+            let (mut old_count, mut new_count) = (0, 0);
+            for edit in edits[start..end].iter() {
+                let (marker, line) = match edit {
+                    Edit::Equal(old_index, _) => {
+                        old_count += 1;
+                        new_count += 1;
+                        (' ', old[*old_index])
+                    }
+                    Edit::Delete(old_index) => {
+                        old_count += 1;
+                        ('-', old[*old_index])
+                    }
+                    Edit::Insert(new_index) => {
+                        new_count += 1;
+                        ('+', new[*new_index])
+                    }
+                };
+
+                lines.push(marker);
+                lines.push_str(line);
+                if !line.ends_with('\n') {
+                    lines.push_str(&format!("\n{NO_NEWLINE}\n"));
+                }
+            }
+
+            // An empty range starts at the line before it, as it has no first line
+            let old_start = old_before + usize::from(old_count > 0);
+            let new_start = new_before + usize::from(new_count > 0);
+            diff.push_str(&format!(
+                "@@ -{old_start},{old_count} +{new_start},{new_count} @@\n{lines}"
+            ));
+        }
+
+        diff
+    }
+
+    /// The shortest edit script between the lines of two files, see Myers' "An O(ND) Difference Algorithm".
+    /// Lines the files start and end with are matched first, as most edits only change a few lines.
+    fn edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+        let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let (old_middle, new_middle) = (
+            &old[prefix..old.len() - suffix],
+            &new[prefix..new.len() - suffix],
+        );
+        let middle = Self::shortest_edits(old_middle, new_middle).unwrap_or_else(|| {
+            (0..old_middle.len())
+                .map(Edit::Delete)
+                .chain((0..new_middle.len()).map(Edit::Insert))
+                .collect()
+        });
+
+        let mut edits = (0..prefix)
+            .map(|index| Edit::Equal(index, index))
+            .collect::<Vec<_>>();
+        edits.extend(middle.into_iter().map(|edit| match edit {
+            Edit::Equal(old_index, new_index) => {
+                Edit::Equal(old_index + prefix, new_index + prefix)
+            }
+            Edit::Delete(old_index) => Edit::Delete(old_index + prefix),
+            Edit::Insert(new_index) => Edit::Insert(new_index + prefix),
+        }));
+        edits.extend(
+            (0..suffix)
+                .map(|index| Edit::Equal(old.len() - suffix + index, new.len() - suffix + index)),
+        );
+        edits
+    }
+
+    /// Returns None if the files differ by more than MAX_EDIT_DISTANCE lines, as the search grows with the square of it.
+    fn shortest_edits(old: &[&str], new: &[&str]) -> Option<Vec<Edit>> {
+        let (n, m) = (old.len() as isize, new.len() as isize);
+        let offset = n + m + 1;
+        let mut furthest = vec![0isize; 2 * offset as usize + 1]; // The furthest x reached on each diagonal k = x - y
+        let mut trace = Vec::new();
+
+        'search: for d in 0..=(n + m) {
+            if d as usize > MAX_EDIT_DISTANCE {
+                return None;
+            }
+            // Only the diagonals a step can come from are kept, which are within d of the first
+            trace.push(furthest[(offset - d) as usize..=(offset + d) as usize].to_vec());
+
+            for k in (-d..=d).step_by(2) {
+                let index = (k + offset) as usize;
+                let mut x = match k == -d || (k != d && furthest[index - 1] < furthest[index + 1]) {
+                    true => furthest[index + 1],
+                    false => furthest[index - 1] + 1,
+                };
+                let mut y = x - k;
+                while x < n && y < m && old[x as usize] == new[y as usize] {
+                    x += 1;
+                    y += 1;
+                }
+
+                furthest[index] = x;
+                if x >= n && y >= m {
+                    break 'search;
+                }
+            }
+        }
+
+        // Walk back from the end through the diagonal each step came from
+        let mut edits = Vec::new();
+        let (mut x, mut y) = (n, m);
+        for (d, furthest) in trace.iter().enumerate().rev() {
+            let d = d as isize;
+            if d == 0 {
+                edits.extend((0..x as usize).rev().map(|index| Edit::Equal(index, index)));
+                break;
+            }
+
+            let k = x - y;
+            let index = (k + d) as usize;
+            let previous_k = match k == -d || (k != d && furthest[index - 1] < furthest[index + 1])
+            {
+                true => k + 1,
+                false => k - 1,
+            };
+            let previous_x = furthest[(previous_k + d) as usize];
+            let previous_y = previous_x - previous_k;
+
+            while x > previous_x && y > previous_y {
+                edits.push(Edit::Equal(x as usize - 1, y as usize - 1));
+                x -= 1;
+                y -= 1;
+            }
+            match x == previous_x {
+                true => edits.push(Edit::Insert(y as usize - 1)),
+                false => edits.push(Edit::Delete(x as usize - 1)),
+            }
+
+            x = previous_x;
+            y = previous_y;
+        }
+
+        edits.reverse();
+        Some(edits)
+    }
+
+    /// Reads a unified diff, anything outside of the file headers and hunks such as `diff --git` lines is ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut files: Vec<FilePatch> = Vec::new();
+        let mut old_path = None;
+        let (mut old_remaining, mut new_remaining) = (0, 0); // Lines of the current hunk not read yet
+
+        for (number, line) in text.split_inclusive('\n').enumerate() {
+            let in_hunk = old_remaining > 0 || new_remaining > 0;
+            let invalid = |reason: &str| format!("line {} {reason}", number + 1);
+
+            if !in_hunk {
+                if let Some(path) = line.strip_prefix("--- ") {
+                    old_path = Some(Self::header_path(path, "a/").map_err(|err| invalid(&err))?);
+                } else if let Some(path) = line.strip_prefix("+++ ") {
+                    let old_path = old_path
+                        .take()
+                        .ok_or_else(|| invalid("names a new file without an old one"))?;
+                    let new_path = Self::header_path(path, "b/").map_err(|err| invalid(&err))?;
+                    files.push(FilePatch {
+                        old_path,
+                        new_path,
+                        hunks: Vec::new(),
+                    });
+                } else if let Some(header) = line.strip_prefix("@@ ") {
+                    let file = files
+                        .last_mut()
+                        .ok_or_else(|| invalid("has a hunk before any file"))?;
+                    let (old_start, old_count, new_count) =
+                        Self::hunk_header(header).ok_or_else(|| invalid("is not a hunk header"))?;
+
+                    old_remaining = old_count;
+                    new_remaining = new_count;
+                    file.hunks.push(Hunk {
+                        old_start,
+                        lines: Vec::new(),
+                    });
+                } else if line.starts_with('\\') {
+                    Self::strip_line_break(&mut files);
+                }
+                continue;
+            }
+
+            // Some tools write an empty context line without its space
+            let (marker, content) = match line {
+                "\n" | "\r\n" => (' ', line),
+                _ => {
+                    let mut chars = line.chars();
+                    let marker = chars.next().unwrap_or(' ');
+                    (marker, chars.as_str())
+                }
+            };
+
+            match marker {
+                ' ' => {
+                    old_remaining = old_remaining.saturating_sub(1);
+                    new_remaining = new_remaining.saturating_sub(1);
+                }
+                '-' => old_remaining = old_remaining.saturating_sub(1),
+                '+' => new_remaining = new_remaining.saturating_sub(1),
+                '\\' => {
+                    Self::strip_line_break(&mut files);
+                    continue;
+                }
+                _ => return Err(invalid("is not part of the hunk it is in")),
+            }
+
+            if let Some(hunk) = files.last_mut().and_then(|file| file.hunks.last_mut()) {
+                hunk.lines.push((marker, content.to_string()));
+            }
+        }
+
+        if old_remaining > 0 || new_remaining > 0 {
+            return Err(String::from("the last hunk is cut short"));
+        }
+
+        Ok(Self { files })
+    }
+
+    /// `\ No newline at end of file` is about the line before it.
+    fn strip_line_break(files: &mut [FilePatch]) {
+        let line = files
+            .last_mut()
+            .and_then(|file| file.hunks.last_mut())
+            .and_then(|hunk| hunk.lines.last_mut());
+
+        if let Some((_, line)) = line {
+            let trimmed = line.strip_suffix('\n').unwrap_or(line);
+            *line = trimmed.strip_suffix('\r').unwrap_or(trimmed).to_string();
+        }
+    }
+
+    /// The path of a file header, which cannot leave the package. Returns None for /dev/null.
+    fn header_path(header: &str, prefix: &str) -> Result<Option<String>, String> {
+        // Some tools write a timestamp after the path
+        let path = header.split('\t').next().unwrap_or(header).trim_end();
+        if path == "/dev/null" {
+            return Ok(None);
+        }
+
+        let path = path.strip_prefix(prefix).unwrap_or(path);
+        let inside = Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        match inside && !path.is_empty() {
+            true => Ok(Some(path.to_string())),
+            false => Err(format!("names '{path}', which is not in the package")),
+        }
+    }
+
+    /// Returns the first old line, and the number of old and new lines, of a header such as `-1,3 +1,4 @@`.
+    fn hunk_header(header: &str) -> Option<(usize, usize, usize)> {
+        let mut ranges = header.split_whitespace();
+        let range = |range: Option<&str>, sign: char| {
+            let range = range?.strip_prefix(sign)?;
+            match range.split_once(',') {
+                Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+                None => Some((range.parse().ok()?, 1)),
+            }
+        };
+
+        let (old_start, old_count) = range(ranges.next(), '-')?;
+        let (_, new_count) = range(ranges.next(), '+')?;
+        Some((old_start, old_count, new_count))
+    }
+
+    /// Applies the patch to the files of a package. A hunk is found by its unchanged lines, so it still applies if lines
+    /// were added or removed above it, but not if any of the lines it changes are different.
+    pub fn apply(&self, directory: &Path) -> Result<(), String> {
+        for file in self.files.iter() {
+            let path = file.new_path.as_ref().or(file.old_path.as_ref());
+            let Some(path) = path else {
+                continue;
+            };
+            let file_path = directory.join(path);
+
+            let old_text = match &file.old_path {
+                Some(old_path) => fs::read_to_string(directory.join(old_path))
+                    .map_err(|err| format!("'{old_path}' could not be read ({err})"))?,
+                None => String::new(),
+            };
+            let mut lines = old_text
+                .split_inclusive('\n')
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+
+            let mut shift = 0isize; // How many lines the hunks before moved the ones after
+            for (index, hunk) in file.hunks.iter().enumerate() {
+                let expected = hunk
+                    .lines
+                    .iter()
+                    .filter(|(marker, _)| *marker != '+')
+                    .map(|(_, line)| line.as_str())
+                    .collect::<Vec<_>>();
+                let replacement = hunk
+                    .lines
+                    .iter()
+                    .filter(|(marker, _)| *marker != '-')
+                    .map(|(_, line)| line.to_string())
+                    .collect::<Vec<_>>();
+
+                let start = hunk
+                    .old_start
+                    .saturating_sub(usize::from(!expected.is_empty()));
+                let guess = (start as isize + shift).max(0) as usize;
+                let position = Self::find_lines(&lines, &expected, guess).ok_or_else(|| {
+                    format!("hunk {} of '{path}' does not match the file", index + 1)
+                })?;
+
+                lines.splice(
+                    position..position + expected.len(),
+                    replacement.iter().cloned(),
+                );
+                shift += replacement.len() as isize - expected.len() as isize;
+                shift += position as isize - guess as isize;
+            }
+
+            // Files are replaced rather than written to, as a copy may be hardlinked to the cache
+            if file_path.symlink_metadata().is_ok() {
+                fs::remove_file(&file_path)
+                    .map_err(|err| format!("'{path}' could not be replaced ({err})"))?;
+            }
+            if file.new_path.is_none() {
+                continue;
+            }
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|err| format!("'{path}' could not be created ({err})"))?;
+            }
+            fs::write(&file_path, lines.concat())
+                .map_err(|err| format!("'{path}' could not be written ({err})"))?;
+        }
+
+        Ok(())
+    }
+
+    /// The position nearest to the guess where the lines are, searching further away on both sides in turn.
+    fn find_lines(lines: &[String], expected: &[&str], guess: usize) -> Option<usize> {
+        let last = lines.len().checked_sub(expected.len())?;
+        let matches = |position: usize| {
+            lines[position..position + expected.len()]
+                .iter()
+                .zip(expected)
+                .all(|(line, expected)| line == expected)
+        };
+
+        let guess = guess.min(last);
+        (0..=last.max(guess)).find_map(|distance| {
+            [guess.checked_sub(distance), Some(guess + distance)]
+                .into_iter()
+                .flatten()
+                .find(|position| *position <= last && matches(*position))
+        })
+    }
+}
+
+/// Patches of the project, saved in `patches/` by `click patch-commit` and applied on every install.
+/// The cache is shared by every project, so a patched package is copied into node_modules/.patched and linked from
+/// there instead. Patches are applied once install scripts have run, the same as patch-package.
+pub struct Patches;
+impl Patches {
+    /// Package string is formated as package@version
+    pub fn path(package: &str) -> PathBuf {
+        Path::new(PATCHES_DIRECTORY).join(format!(
+            "{}.{PATCH_EXTENSION}",
+            IsolatedLayout::key(package)
+        ))
+    }
+
+    /// Returns the package of each patch in `patches/`.
+    fn find() -> Result<BTreeMap<String, PathBuf>, CommandError> {
+        let entries = match fs::read_dir(PATCHES_DIRECTORY) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(err) => return Err(CommandError::FailedToReadFile(err)),
+        };
+
+        let mut patches = BTreeMap::new();
+        for entry in entries {
+            let path = entry.map_err(CommandError::FailedDirectoryEntry)?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == PATCH_EXTENSION)
+            {
+                if let Some(key) = path.file_stem() {
+                    let package = IsolatedLayout::package_from_key(&key.to_string_lossy());
+                    patches.insert(package, path);
+                }
+            }
+        }
+
+        Ok(patches)
+    }
+
+    /// Records the integrity of each patch in the lockfile, so that installs from it apply the same patches.
+    /// A patch for a version that is not installed is warned about, as it was usually made for the version before.
+    pub fn lock(lockfile: &mut Lockfile) -> Result<(), CommandError> {
+        lockfile.patches.clear();
+        for (package, path) in Self::find()? {
+            if !lockfile.packages.contains_key(&package) {
+                warn!(
+                    "skipped the patch '{}' as {package} is not installed",
+                    path.display()
+                );
+                continue;
+            }
+
+            let patch = fs::read(&path).map_err(CommandError::FailedToReadFile)?;
+            lockfile.patches.insert(package, Integrity::sha512(&patch));
+        }
+
+        Ok(())
+    }
+
+    /// Makes a patched copy of each package with a patch in the lockfile, unless it has already been made with the same
+    /// patch, and removes the copies of packages that are no longer patched.
+    /// Returns the packages that have to be linked again.
+    pub fn apply(lockfile: &Lockfile) -> Result<Vec<String>, CommandError> {
+        let mut changed = Vec::new();
+        for (package, integrity) in lockfile.patches.iter() {
+            let applied_path = Self::copy_root(package).join(APPLIED_FILE);
+            if fs::read_to_string(&applied_path).is_ok_and(|applied| applied == *integrity) {
+                continue;
+            }
+
+            Self::make_copy(package)?;
+            fs::write(&applied_path, integrity).map_err(CommandError::FailedToWriteFile)?;
+            changed.push(package.to_string());
+        }
+
+        for package in Self::copies()? {
+            if !lockfile.patches.contains_key(&package) {
+                fs::remove_dir_all(Self::copy_root(&package))
+                    .map_err(CommandError::FailedToWriteFile)?;
+                changed.push(package);
+            }
+        }
+
+        Ok(changed)
+    }
+
+    /// Package string is formated as package@version
+    fn make_copy(package: &str) -> Result<(), CommandError> {
+        let patch_path = Self::path(package);
+        let text = fs::read_to_string(&patch_path).map_err(CommandError::FailedToReadFile)?;
+        let patch = Patch::parse(&text).map_err(|reason| {
+            CommandError::InvalidPatch(patch_path.display().to_string(), reason)
+        })?;
+
+        // The copy is made next to where it goes, so an interrupted install does not leave a half patched package
+        let copy_root = Self::copy_root(package);
+        let mut temporary_directory = copy_root.clone().into_os_string();
+        temporary_directory.push(format!(".{}.tmp", std::process::id()));
+        let temporary_directory = PathBuf::from(temporary_directory);
+        if temporary_directory.exists() {
+            fs::remove_dir_all(&temporary_directory).map_err(CommandError::FailedToWriteFile)?;
+        }
+
+        let copy = temporary_directory.join("package");
+        Self::copy_tree(&Self::cache_path(package), &copy, &[])
+            .map_err(CommandError::FailedToCreateFile)?;
+        if let Err(reason) = patch.apply(&copy) {
+            let _ = fs::remove_dir_all(&temporary_directory);
+            return Err(CommandError::FailedToApplyPatch(
+                patch_path.display().to_string(),
+                reason,
+            ));
+        }
+
+        if copy_root.exists() {
+            fs::remove_dir_all(&copy_root).map_err(CommandError::FailedToWriteFile)?;
+        }
+        fs::rename(&temporary_directory, &copy_root).map_err(CommandError::FailedToWriteFile)
+    }
+
+    /// Returns every package with a patched copy.
+    fn copies() -> Result<Vec<String>, CommandError> {
+        let read = |directory: &Path| match fs::read_dir(directory) {
+            Ok(entries) => entries
+                .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().to_string()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(CommandError::FailedDirectoryEntry),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(CommandError::FailedToReadFile(err)),
+        };
+
+        let mut packages = Vec::new();
+        for name in read(Path::new(PATCHED_DIRECTORY))? {
+            match name.starts_with('@') {
+                true => {
+                    let scope = Path::new(PATCHED_DIRECTORY).join(&name);
+                    packages.extend(
+                        read(&scope)?
+                            .into_iter()
+                            .map(|scoped| format!("{name}/{scoped}")),
+                    );
+                }
+                false => packages.push(name),
+            }
+        }
+
+        // Copies that were being made when an install was interrupted
+        packages.retain(|package| !package.ends_with(".tmp"));
+        Ok(packages)
+    }
+
+    /// Copies the files of a package, skipping any of the given names. Nothing is hardlinked, as the copy is edited.
+    pub fn copy_tree(source: &Path, destination: &Path, skipped: &[&str]) -> Result<(), io::Error> {
+        fs::create_dir_all(destination)?;
+
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            if skipped.contains(&entry.file_name().to_string_lossy().as_ref()) {
+                continue;
+            }
+
+            let file_type = entry.file_type()?;
+            let destination = destination.join(entry.file_name());
+            if file_type.is_dir() {
+                Self::copy_tree(&entry.path(), &destination, skipped)?;
+            } else if file_type.is_symlink() {
+                symlink::symlink_auto(fs::read_link(entry.path())?, &destination)?;
+            } else {
+                fs::copy(entry.path(), &destination)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copies a package to edit it, without anything that is not part of the package as it was published.
+    pub fn copy_for_editing(package: &str, destination: &Path) -> Result<(), io::Error> {
+        Self::copy_tree(&Self::source(package), destination, &SKIPPED_FILES)
+    }
+
+    /// The patched copies are linked by absolute path, the same as the cache.
+    fn directory() -> PathBuf {
+        env::current_dir()
+            .unwrap_or_default()
+            .join(PATCHED_DIRECTORY)
+    }
+
+    /// Package string is formated as package@version
+    fn copy_root(package: &str) -> PathBuf {
+        Path::new(PATCHED_DIRECTORY).join(package)
+    }
+
+    /// Package string is formated as package@version
+    pub fn cache_path(package: &str) -> PathBuf {
+        PathBuf::from(format!("{}/{}/package", *CACHE_DIRECTORY, package))
+    }
+
+    /// Package string is formated as package@version
+    /// Where the files of a package are linked from, which is its patched copy if it has one and the cache otherwise.
+    pub fn source(package: &str) -> PathBuf {
+        let copy = Self::directory().join(package).join("package");
+        match copy.exists() {
+            true => copy,
+            false => Self::cache_path(package),
+        }
+    }
+
+    /// Returns the package formated as package@version a directory of the cache or of a patched copy is for, such as
+    /// the parent of a package linked into node_modules. Dependencies bundled in a package are not packages of their own.
+    pub fn package_of(directory: &Path) -> Option<String> {
+        let relative = directory
+            .strip_prefix(CACHE_DIRECTORY.as_str())
+            .or_else(|_| directory.strip_prefix(Self::directory()))
+            .ok()?;
+
+        let components = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>();
+        match components.as_slice() {
+            [package] if !package.starts_with('@') => Some(package.to_string()),
+            [scope, package] if scope.starts_with('@') => Some(format!("{scope}/{package}")),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temporary_directory(name: &str) -> PathBuf {
+        let directory =
+            env::temp_dir().join(format!("click-patch-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn diff_applies_to_the_original() {
+        let root = temporary_directory("roundtrip");
+        let (original, edited) = (root.join("original"), root.join("edited"));
+        fs::create_dir_all(original.join("lib")).unwrap();
+        fs::create_dir_all(edited.join("lib")).unwrap();
+
+        let lines = (1..=20)
+            .map(|line| format!("line {line}\n"))
+            .collect::<String>();
+        fs::write(original.join("lib/index.js"), &lines).unwrap();
+        fs::write(
+            edited.join("lib/index.js"),
+            lines
+                .replace("line 2\n", "changed 2\n")
+                .replace("line 18\n", "")
+                + "last",
+        )
+        .unwrap();
+        fs::write(original.join("removed.js"), "gone\n").unwrap();
+        fs::write(edited.join("added.js"), "new\n").unwrap();
+        fs::write(original.join("click-lock.json"), "{}").unwrap();
+
+        let diff = Patch::diff(&original, &edited).unwrap();
+        assert!(diff.contains("--- /dev/null\n+++ b/added.js\n@@ -0,0 +1,1 @@\n+new\n"));
+        assert!(diff.contains("--- a/removed.js\n+++ /dev/null\n"));
+        assert!(diff.contains(NO_NEWLINE));
+        assert!(!diff.contains("click-lock.json"));
+
+        Patch::parse(&diff).unwrap().apply(&original).unwrap();
+        assert_eq!(Patch::diff(&original, &edited).unwrap(), "");
+        assert!(!original.join("removed.js").exists());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn hunks_apply_after_lines_moved() {
+        let patch =
+            Patch::parse("--- a/index.js\n+++ b/index.js\n@@ -2,3 +2,3 @@\n a\n-b\n+B\n c\n")
+                .unwrap();
+        let root = temporary_directory("offset");
+        fs::write(root.join("index.js"), "x\ny\nz\na\nb\nc\n").unwrap();
+
+        patch.apply(&root).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.join("index.js")).unwrap(),
+            "x\ny\nz\na\nB\nc\n"
+        );
+        assert!(patch.apply(&root).is_err());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn rejects_paths_outside_the_package() {
+        assert!(Patch::parse("--- a/../index.js\n+++ b/../index.js\n").is_err());
+        assert!(Patch::parse("--- a/index.js\n+++ b/index.js\n@@ -1,2 +1,2 @@\n a\n").is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use std::{collections::HashMap, fmt, path::Path, str::FromStr};
+
+use semver::Version;
+
+use crate::{
+    cache::{Cache, CACHE_DIRECTORY},
+    errors::CommandError,
+    installer::{Installer, Linker},
+    isolated::IsolatedLayout,
+    manifest::{Manifest, OrderedJson},
+    versions::{Versions, EMPTY_VERSION},
+};
+
+/// A peer dependency of an installed package that is missing or installed at a version outside of its range.
+pub struct PeerIssue {
+    pub package: String, // Formatted as package@version
+    pub peer: String,
+    pub range: String,
+    pub installed: Option<String>, // The version of the peer that the package finds, None if it finds none
+}
+
+impl fmt::Display for PeerIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.installed {
+            Some(installed) => write!(
+                f,
+                "'{}' requires '{}@{}' as a peer dependency, but '{}@{}' is installed",
+                self.package, self.peer, self.range, self.peer, installed
+            ),
+            None => write!(
+                f,
+                "'{}' requires '{}@{}' as a peer dependency, which is not installed",
+                self.package, self.peer, self.range
+            ),
+        }
+    }
+}
+
+/// Peer dependencies are packages that a package expects its dependent to provide, such as the framework of a plugin.
+/// Unless `--legacy-peer-deps` is given they are installed along with the package, see Installer::download_package(),
+/// and checked against what is in node_modules once everything has been linked.
+pub struct PeerDependencies;
+impl PeerDependencies {
+    /// Returns every peer dependency that is not satisfied, with the peers of a package resolved the same way Node would
+    /// find them from it: the package at the top of node_modules, or with the isolated layout the one linked next to it.
+    pub fn check(linker: Linker) -> Result<Vec<PeerIssue>, CommandError> {
+        let linked_packages = Installer::linked_packages()?;
+
+        let top_level = linked_packages
+            .iter()
+            .map(|package| Versions::parse_raw_package_details(package.to_string()))
+            .collect::<HashMap<_, _>>();
+
+        let mut packages = linked_packages.clone();
+        for package in linked_packages.iter() {
+            packages.extend(Cache::read_lock(package)?.dependencies);
+        }
+        packages.sort();
+        packages.dedup();
+
+        let mut issues = Vec::new();
+        for package in packages {
+            let manifest_path = format!("{}/{}/package/package.json", *CACHE_DIRECTORY, package);
+            let Some(manifest) = Manifest::read_from(Path::new(&manifest_path))? else {
+                continue;
+            };
+
+            let locked_dependencies = match linker {
+                Linker::Hoisted => Vec::new(),
+                Linker::Isolated => Cache::read_lock(&package)?.dependencies,
+            };
+
+            for (peer, range) in manifest.dependencies("peerDependencies") {
+                let installed = IsolatedLayout::select(&locked_dependencies, &peer, &range)
+                    .map(|dependency| Versions::parse_raw_package_details(dependency).1)
+                    .or_else(|| top_level.get(&peer).cloned());
+
+                let satisfied = match &installed {
+                    Some(installed) => Self::satisfies(installed, &range),
+                    None => Self::is_optional(&manifest, &peer),
+                };
+
+                if !satisfied {
+                    issues.push(PeerIssue {
+                        package: package.to_string(),
+                        peer,
+                        range,
+                        installed,
+                    });
+                }
+            }
+        }
+
+        Ok(issues)
+    }
+
+    /// Ranges that cannot be parsed, such as those of git dependencies, are left for Node to deal with.
+    fn satisfies(version: &str, range: &str) -> bool {
+        let version = Version::from_str(version).unwrap_or(EMPTY_VERSION);
+        Versions::parse_semantic_version(range).map_or(true, |range| range.matches(&version))
+    }
+
+    /// Optional peers are only checked when they are installed.
+    fn is_optional(manifest: &Manifest, peer: &str) -> bool {
+        let optional = manifest
+            .json
+            .get("peerDependenciesMeta")
+            .and_then(|meta| meta.get(peer))
+            .and_then(|meta| meta.get("optional"));
+
+        matches!(optional, Some(OrderedJson::Bool(true)))
+    }
+}
@@ -0,0 +1,455 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+use crate::{
+    cache::Cache,
+    errors::CommandError,
+    hoisted::HoistedPlan,
+    http::{HTTPRequest, NetworkMode},
+    installer::{Installer, Linker},
+    platform::Platform,
+    source::Source,
+    types::Lockfile,
+    util::TaskAllocator,
+    versions::{Strategy, VersionRange, Versions},
+};
+
+/// A package an install would link at the top of node_modules.
+#[derive(Serialize)]
+pub struct PlannedPackage {
+    pub package: String,          // Formatted as package@version
+    pub previous: Option<String>, // The version linked now, None if the package is not installed
+}
+
+/// A package that is not in the cache, so an install would download it.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedDownload {
+    pub package: String,            // Formatted as package@version
+    pub unpacked_size: Option<u64>, // In bytes, None if the registry does not list it
+    pub tarball_size: Option<u64>, // In bytes, None until InstallPlan::measure_tarballs() or if the registry does not tell
+    #[serde(skip)]
+    pub tarball: String, // Where it would be downloaded from
+}
+
+/// What an install would do, resolved with `install --dry-run` without downloading or linking anything.
+/// Only the packument cache is written to, the same as any other request to the registry.
+#[derive(Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallPlan {
+    pub added: Vec<PlannedPackage>,
+    pub updated: Vec<PlannedPackage>,
+    pub downloads: Vec<PlannedDownload>,
+    pub skipped_optional: Vec<String>, // Optional dependencies that could not be resolved, which the install would skip
+}
+
+/// How the plan is resolved, matching the options of the install it is for.
+pub struct PlanOptions<'a> {
+    pub lockfile: &'a Lockfile,
+    pub network_mode: NetworkMode,
+    pub platform: &'a Platform,
+    pub legacy_peer_deps: bool,
+    pub linker: Linker,
+    pub strategy: Strategy,
+}
+
+/// A dependency waiting to be resolved.
+struct PendingDependency {
+    name: String,
+    range: String,
+    optional: bool,
+}
+
+/// The version a dependency resolved to.
+struct ResolvedVersion {
+    package: String, // Formatted as package@version
+    dependencies: Vec<PendingDependency>,
+    download: Option<PlannedDownload>, // None if the package is cached
+}
+
+/// Every package reached while resolving, shared by all of the packages being installed.
+#[derive(Default)]
+struct ResolvedPackages {
+    dependencies: HashMap<String, Vec<String>>, // Each package to the packages it depends on directly
+    downloads: HashMap<String, PlannedDownload>, // Packages that are not cached
+    skipped_optional: Vec<String>,
+}
+
+impl InstallPlan {
+    /// Resolves every package and all of its dependencies the same way an install would: locked packages keep their
+    /// version, cached packages are used with the dependencies in their lockfile and anything else is resolved from the
+    /// registry. Which packages are added or updated follows how the linker links them.
+    pub async fn resolve(
+        client: reqwest::Client,
+        packages: &[(String, Option<VersionRange>)],
+        options: PlanOptions<'_>,
+    ) -> Result<Self, CommandError> {
+        let mut resolved = ResolvedPackages::default();
+
+        let linked = Installer::linked_packages()?
+            .into_iter()
+            .map(|package| Versions::parse_raw_package_details(package.to_string()))
+            .collect::<HashMap<_, _>>();
+
+        let mut plan = Self::default();
+        let mut roots = Vec::new();
+        for (package_name, semantic_version) in packages {
+            let spec = Lockfile::spec(package_name, semantic_version.as_ref());
+            // The lowest versions are resolved from the ranges, not whatever was locked before
+            let locked_version = match options.strategy {
+                Strategy::Highest => options.lockfile.locked_version(&spec),
+                Strategy::Lowest => None,
+            };
+            let range = match locked_version {
+                Some(version) => version,
+                None => semantic_version
+                    .as_ref()
+                    .map(|range| range.to_string())
+                    .unwrap_or(String::from("latest")),
+            };
+
+            let root = PendingDependency {
+                name: package_name.to_string(),
+                range,
+                optional: false,
+            };
+
+            let Some(root) =
+                Self::resolve_package(client.clone(), root, &options, &mut resolved).await?
+            else {
+                continue;
+            };
+
+            roots.push(root);
+        }
+
+        let top_level = match options.linker {
+            Linker::Hoisted => Self::hoisted(roots, &resolved, options.lockfile),
+            Linker::Isolated => roots,
+        };
+
+        for package in top_level {
+            let (name, version) = Versions::parse_raw_package_details(package.to_string());
+            match linked.get(&name) {
+                None => plan.added.push(PlannedPackage {
+                    package,
+                    previous: None,
+                }),
+                Some(previous) if previous != &version => plan.updated.push(PlannedPackage {
+                    package,
+                    previous: Some(previous.to_string()),
+                }),
+                Some(_) => continue,
+            }
+        }
+
+        plan.downloads = resolved.downloads.into_values().collect();
+        plan.downloads.sort_by(|a, b| a.package.cmp(&b.package));
+        plan.skipped_optional = resolved.skipped_optional;
+
+        Ok(plan)
+    }
+
+    /// The packages the hoisted layout would link at the top of node_modules. The install plans the layout of everything
+    /// in the lockfile once the new packages are locked, so the packages already locked are planned along with them.
+    fn hoisted(
+        roots: Vec<String>,
+        resolved: &ResolvedPackages,
+        lockfile: &Lockfile,
+    ) -> Vec<String> {
+        let mut dependencies = lockfile
+            .packages
+            .keys()
+            .map(|package| {
+                let direct_dependencies = lockfile.direct_dependencies(package);
+                (
+                    package.to_string(),
+                    direct_dependencies
+                        .into_iter()
+                        .map(|(_, dependency)| dependency)
+                        .collect(),
+                )
+            })
+            .collect::<HashMap<_, Vec<_>>>();
+
+        // Cached packages were resolved with every package in their lockfile, so the direct ones are read from the cache
+        for (package, resolved_dependencies) in resolved.dependencies.iter() {
+            let direct_dependencies = match resolved.downloads.contains_key(package) {
+                true => resolved_dependencies.clone(),
+                false => {
+                    let locked_dependencies = Cache::read_lock(package)
+                        .map(|package_lock| package_lock.dependencies)
+                        .unwrap_or_default();
+                    Cache::direct_dependencies(package, &locked_dependencies)
+                        .into_iter()
+                        .map(|(_, dependency)| dependency)
+                        .collect()
+                }
+            };
+
+            dependencies.insert(package.to_string(), direct_dependencies);
+        }
+
+        let mut all_roots = lockfile.requested.values().cloned().collect::<Vec<_>>();
+        all_roots.extend(roots);
+
+        HoistedPlan::new(&all_roots, &dependencies)
+            .placements
+            .into_iter()
+            .filter(|(location, _)| location.len() == 1)
+            .map(|(_, package)| package)
+            .collect()
+    }
+
+    /// Resolves a package and everything it depends on, returns the package formatted as package@version.
+    /// Returns None if it is an optional dependency that would be skipped.
+    async fn resolve_package(
+        client: reqwest::Client,
+        root: PendingDependency,
+        options: &PlanOptions<'_>,
+        resolved: &mut ResolvedPackages,
+    ) -> Result<Option<String>, CommandError> {
+        let mut root_package = None;
+        let mut pending = VecDeque::from([(None::<String>, root)]);
+
+        while let Some((dependent, dependency)) = pending.pop_front() {
+            let optional = dependency.optional;
+            let resolved_version =
+                match Self::resolve_version(client.clone(), &dependency, options).await {
+                    Ok(Some(resolved_version)) => resolved_version,
+                    Ok(None) => continue,
+                    Err(_) if optional => {
+                        resolved.skipped_optional.push(dependency.name);
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                };
+
+            let package = resolved_version.package;
+            match &dependent {
+                Some(dependent) => resolved
+                    .dependencies
+                    .entry(dependent.to_string())
+                    .or_default()
+                    .push(package.to_string()),
+                None => root_package = Some(package.to_string()),
+            }
+
+            if resolved.dependencies.contains_key(&package) {
+                continue;
+            }
+            resolved
+                .dependencies
+                .insert(package.to_string(), Vec::new());
+
+            if let Some(download) = resolved_version.download {
+                resolved
+                    .downloads
+                    .insert(download.package.to_string(), download);
+            }
+
+            for dependency in resolved_version.dependencies {
+                pending.push_back((Some(package.to_string()), dependency));
+            }
+        }
+
+        Ok(root_package)
+    }
+
+    /// Returns the version a dependency resolves to along with its own dependencies, or None if it would be skipped.
+    /// Cached packages already list every package they depend on in their lockfile, so nothing is left to resolve.
+    async fn resolve_version(
+        client: reqwest::Client,
+        dependency: &PendingDependency,
+        options: &PlanOptions<'_>,
+    ) -> Result<Option<ResolvedVersion>, CommandError> {
+        let name = &dependency.name;
+        if Source::from_spec(&dependency.range).is_some() {
+            return Ok(None); // Only known once it is fetched, which a plan does not do
+        }
+
+        let semantic_version = Installer::parse_range(
+            client.clone(),
+            name,
+            &dependency.range,
+            options.network_mode,
+        )
+        .await?;
+        let semantic_version = semantic_version.as_ref();
+        let full_version = Installer::full_version(
+            client.clone(),
+            name,
+            semantic_version,
+            options.strategy,
+            options.network_mode,
+        )
+        .await?;
+        let full_version = full_version.as_ref();
+
+        // Cached packages are resolved again for the lowest versions, as their dependencies may have been higher
+        let cached = match options.strategy {
+            Strategy::Highest => Cache::exists(name, full_version, semantic_version).await?,
+            Strategy::Lowest => (false, None),
+        };
+        if let (true, Some(version)) = cached {
+            let package = Versions::stringify(name, &version);
+            let dependencies = Cache::read_lock(&package)?
+                .dependencies
+                .into_iter()
+                .map(|dependency| {
+                    let (name, range) = Versions::parse_raw_package_details(dependency);
+                    PendingDependency {
+                        name,
+                        range,
+                        optional: false,
+                    }
+                })
+                .collect();
+
+            return Ok(Some(ResolvedVersion {
+                package,
+                dependencies,
+                download: None,
+            }));
+        }
+
+        let locked_version_data =
+            full_version.and_then(|version| options.lockfile.version_data(name, version));
+        let version_data = match locked_version_data {
+            Some(version_data) => version_data,
+            None => {
+                Installer::get_version_data(
+                    client,
+                    name,
+                    full_version,
+                    semantic_version,
+                    options.network_mode,
+                )
+                .await?
+            }
+        };
+
+        let package = Versions::stringify(name, &version_data.version);
+        if !options.platform.supports(&version_data) {
+            return match dependency.optional {
+                true => Ok(None),
+                false => Err(CommandError::UnsupportedPlatform(
+                    package,
+                    options.platform.to_string(),
+                )),
+            };
+        }
+
+        let locked_dependencies = match options.strategy {
+            Strategy::Highest => options.lockfile.dependencies(&package),
+            Strategy::Lowest => None,
+        };
+        let dependencies = match locked_dependencies {
+            Some(locked_dependencies) => locked_dependencies
+                .into_iter()
+                .map(|(name, version)| PendingDependency {
+                    optional: options
+                        .lockfile
+                        .is_optional(&Versions::stringify(&name, &version)),
+                    name,
+                    range: version,
+                })
+                .collect(),
+            None => {
+                let peer_dependencies = match options.legacy_peer_deps {
+                    true => Vec::new(),
+                    false => version_data.required_peer_dependencies(),
+                };
+                let bundled = version_data.bundled_dependencies();
+                let mut optional_dependencies = version_data
+                    .optional_dependencies
+                    .clone()
+                    .unwrap_or_default();
+                optional_dependencies.retain(|name, _| !bundled.contains(name));
+
+                let mut dependencies = version_data.dependencies.clone().unwrap_or_default();
+                dependencies.retain(|name, _| {
+                    !optional_dependencies.contains_key(name) && !bundled.contains(name)
+                });
+
+                let required =
+                    dependencies
+                        .into_iter()
+                        .chain(peer_dependencies)
+                        .map(|(name, range)| PendingDependency {
+                            name,
+                            range,
+                            optional: false,
+                        });
+                let optional =
+                    optional_dependencies
+                        .into_iter()
+                        .map(|(name, range)| PendingDependency {
+                            name,
+                            range,
+                            optional: true,
+                        });
+
+                required.chain(optional).collect()
+            }
+        };
+
+        let download = PlannedDownload {
+            package: package.to_string(),
+            unpacked_size: version_data.dist.unpacked_size,
+            tarball_size: None,
+            tarball: version_data.dist.tarball.to_string(),
+        };
+
+        Ok(Some(ResolvedVersion {
+            package,
+            dependencies,
+            download: Some(download),
+        }))
+    }
+
+    /// The total unpacked size of the downloads, or None if the registry did not list the size of any of them.
+    pub fn download_size(&self) -> Option<u64> {
+        self.downloads
+            .iter()
+            .filter_map(|download| download.unpacked_size)
+            .reduce(|total, size| total + size)
+    }
+
+    /// The total size of the tarballs that would be downloaded, or None if the size of none of them is known.
+    pub fn tarball_size(&self) -> Option<u64> {
+        self.downloads
+            .iter()
+            .filter_map(|download| download.tarball_size)
+            .reduce(|total, size| total + size)
+    }
+
+    /// Asks the registry how large each tarball is, with `concurrency` requests at most at once. Registries only list
+    /// the unpacked size of versions, and not for older ones.
+    pub async fn measure_tarballs(&mut self, client: reqwest::Client, concurrency: usize) {
+        let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+        let handles = self
+            .downloads
+            .iter()
+            .map(|download| {
+                let client = client.clone();
+                let permits = permits.clone();
+                let tarball = download.tarball.to_string();
+
+                TaskAllocator::add_task(async move {
+                    let _permit = permits.acquire_owned().await;
+                    HTTPRequest::content_length(client, &tarball).await
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for (download, handle) in self.downloads.iter_mut().zip(handles) {
+            download.tarball_size = handle.await.ok().flatten();
+        }
+    }
+}
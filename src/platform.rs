@@ -0,0 +1,149 @@
+use std::{env::consts, fmt};
+
+use crate::types::VersionData;
+
+/// The platform packages are installed for, named the way Node names them in `process.platform` and `process.arch`.
+/// Packages that ship native binaries list the platforms they support in their `os`, `cpu` and `libc` fields.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Platform {
+    pub os: String,
+    pub cpu: String,
+    pub libc: Option<String>, // Only Linux has more than one libc
+}
+
+/// The platform click is running on.
+impl Default for Platform {
+    fn default() -> Self {
+        let os = match consts::OS {
+            "macos" => "darwin",
+            "windows" => "win32",
+            os => os,
+        };
+
+        let cpu = match consts::ARCH {
+            "x86_64" => "x64",
+            "x86" => "ia32",
+            "aarch64" => "arm64",
+            "powerpc64" => "ppc64",
+            "loongarch64" => "loong64",
+            arch => arch,
+        };
+
+        Self {
+            os: os.to_string(),
+            cpu: cpu.to_string(),
+            libc: Self::default_libc(os),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.os, self.cpu)?;
+
+        match &self.libc {
+            Some(libc) => write!(f, "/{libc}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Platform {
+    /// Parses a platform given as `os/cpu` or `os/cpu/libc`, such as `linux/arm64/musl`.
+    /// Returns None if it is not in either form.
+    pub fn parse(platform: &str) -> Option<Self> {
+        let mut parts = platform.split('/');
+        let os = parts.next().filter(|os| !os.is_empty())?.to_string();
+        let cpu = parts.next().filter(|cpu| !cpu.is_empty())?.to_string();
+        let libc = match parts.next() {
+            Some(libc) => Some(libc.to_string()),
+            None => Self::default_libc(&os),
+        };
+
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(Self { os, cpu, libc })
+    }
+
+    fn default_libc(os: &str) -> Option<String> {
+        if os != "linux" {
+            return None;
+        }
+
+        match cfg!(target_env = "musl") {
+            true => Some(String::from("musl")),
+            false => Some(String::from("glibc")),
+        }
+    }
+
+    /// Versions that do not list a field support every value of it, the same as npm.
+    pub fn supports(&self, version_data: &VersionData) -> bool {
+        Self::matches(Some(&self.os), version_data.os.as_ref())
+            && Self::matches(Some(&self.cpu), version_data.cpu.as_ref())
+            && Self::matches(self.libc.as_ref(), version_data.libc.as_ref())
+    }
+
+    /// Values prefixed with `!` are excluded, and any other values are the only ones allowed.
+    fn matches(value: Option<&String>, allowed: Option<&Vec<String>>) -> bool {
+        let Some(allowed) = allowed.filter(|allowed| !allowed.is_empty()) else {
+            return true;
+        };
+        let Some(value) = value else {
+            return false;
+        };
+
+        if allowed
+            .iter()
+            .any(|entry| entry.strip_prefix('!') == Some(value.as_str()))
+        {
+            return false;
+        }
+
+        let included = allowed
+            .iter()
+            .filter(|entry| !entry.starts_with('!'))
+            .collect::<Vec<_>>();
+
+        included.is_empty()
+            || included
+                .iter()
+                .any(|entry| *entry == value || *entry == "any")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(values: &[&str]) -> Option<Vec<String>> {
+        Some(values.iter().map(|value| value.to_string()).collect())
+    }
+
+    #[test]
+    fn matches_allowed_and_excluded_values() {
+        let linux = String::from("linux");
+        assert!(Platform::matches(Some(&linux), None));
+        assert!(Platform::matches(
+            Some(&linux),
+            list(&["darwin", "linux"]).as_ref()
+        ));
+        assert!(!Platform::matches(Some(&linux), list(&["darwin"]).as_ref()));
+        assert!(Platform::matches(Some(&linux), list(&["!win32"]).as_ref()));
+        assert!(!Platform::matches(Some(&linux), list(&["!linux"]).as_ref()));
+        assert!(!Platform::matches(None, list(&["glibc"]).as_ref()));
+    }
+
+    #[test]
+    fn parses_forced_platforms() {
+        let platform = Platform::parse("linux/arm64/musl").expect("Valid platform");
+        assert_eq!(platform.to_string(), "linux/arm64/musl");
+
+        let platform = Platform::parse("darwin/arm64").expect("Valid platform");
+        assert_eq!(platform.libc, None);
+
+        assert_eq!(Platform::parse("linux"), None);
+        assert_eq!(Platform::parse("linux/x64/glibc/extra"), None);
+    }
+}
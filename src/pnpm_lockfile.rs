@@ -0,0 +1,327 @@
+use std::{collections::BTreeMap, fs, io::ErrorKind, str::FromStr};
+
+use semver::Version;
+
+use crate::{
+    errors::CommandError,
+    source::Source,
+    types::{LockedPackage, Lockfile},
+    versions::Versions,
+    yaml::YamlNode,
+};
+
+pub const PNPM_LOCKFILE_PATH: &str = "./pnpm-lock.yaml";
+
+const DEPENDENCY_FIELDS: [&str; 3] = ["dependencies", "devDependencies", "optionalDependencies"];
+
+/// Converts the pnpm-lock.yaml of pnpm into a lockfile, see NpmLockfile. pnpm locks the exact version of every dependency,
+/// so nothing has to be resolved. A package is keyed as `/name@version` since lockfileVersion 6, as `name@version` since
+/// lockfileVersion 9 which locks its dependencies apart in `snapshots`, and as `/name/version` before. The versions of
+/// packages that depend on peer dependencies are followed by them, such as `1.0.0(react@18.2.0)` or `1.0.0_react@18.2.0`.
+pub struct PnpmLockfile;
+impl PnpmLockfile {
+    /// Returns None if the project has no pnpm-lock.yaml. `requested` has each package requested from the registry.
+    pub fn import(requested: &[(String, Source)]) -> Result<Option<Lockfile>, CommandError> {
+        let pnpm_lockfile_raw = match fs::read_to_string(PNPM_LOCKFILE_PATH) {
+            Ok(pnpm_lockfile_raw) => pnpm_lockfile_raw,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(CommandError::FailedToReadPackageLock(err)),
+        };
+
+        Ok(Some(Self::convert(&pnpm_lockfile_raw, requested)))
+    }
+
+    pub fn convert(pnpm_lockfile_raw: &str, requested: &[(String, Source)]) -> Lockfile {
+        let document = YamlNode::parse(pnpm_lockfile_raw);
+
+        let mut packages: BTreeMap<String, LockedPackage> = BTreeMap::new();
+        for entry in document
+            .get("packages")
+            .into_iter()
+            .flat_map(|entries| entries.children.iter())
+        {
+            let Some(package) = Self::package(&entry.key) else {
+                continue;
+            };
+
+            let resolution = entry
+                .get("resolution")
+                .map(Self::mapping)
+                .unwrap_or_default();
+
+            // Such as a git repository or a directory, which are resolved again when installed
+            let resolved = resolution.get("tarball").cloned();
+            let from_registry = !resolution.contains_key("type")
+                && resolved.as_ref().is_none_or(|resolved| {
+                    resolved.starts_with("https://") || resolved.starts_with("http://")
+                });
+            if !from_registry {
+                continue;
+            }
+
+            packages.insert(
+                package,
+                LockedPackage {
+                    resolved,
+                    registry: None, // Not recorded by pnpm
+                    integrity: resolution.get("integrity").cloned(),
+                    has_install_script: entry.value_of("requiresBuild") == Some("true"),
+                    bin: BTreeMap::new(), // Only whether there are any is recorded by pnpm
+                    dependencies: Self::dependencies(entry),
+                    optional: entry.value_of("optional") == Some("true"),
+                },
+            );
+        }
+
+        for snapshot in document
+            .get("snapshots")
+            .into_iter()
+            .flat_map(|snapshots| snapshots.children.iter())
+        {
+            let Some(locked_package) =
+                Self::package(&snapshot.key).and_then(|package| packages.get_mut(&package))
+            else {
+                continue;
+            };
+
+            locked_package
+                .dependencies
+                .extend(Self::dependencies(snapshot));
+            locked_package.dependencies.sort();
+            locked_package.dependencies.dedup();
+            locked_package.optional |= snapshot.value_of("optional") == Some("true");
+        }
+
+        // Since lockfileVersion 9 the project is an importer the same way a workspace is
+        let project = document
+            .get("importers")
+            .and_then(|importers| importers.get("."))
+            .unwrap_or(&document);
+        let roots = DEPENDENCY_FIELDS
+            .iter()
+            .filter_map(|field| project.get(field))
+            .flat_map(|dependencies| dependencies.children.iter())
+            .filter_map(|dependency| {
+                let version = dependency
+                    .value
+                    .as_deref()
+                    .or_else(|| dependency.value_of("version"))?;
+                Some((
+                    dependency.key.to_string(),
+                    Self::dependency(&dependency.key, version)?,
+                ))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let requested = requested
+            .iter()
+            .filter_map(|(package_name, source)| {
+                let package = roots.get(package_name)?;
+                packages
+                    .contains_key(package)
+                    .then(|| (source.spec(package_name), package.to_string()))
+            })
+            .collect();
+
+        Lockfile::import(packages, requested)
+    }
+
+    /// The package@version a package is keyed by, without the peer dependencies it was resolved with.
+    fn package(key: &str) -> Option<String> {
+        let key = key.strip_prefix('/').unwrap_or(key);
+        let key = key.split('(').next().unwrap_or(key);
+
+        // The name of a scoped package has a slash in it too
+        let name_length = match key.starts_with('@') {
+            true => key.find('/').map_or(key.len(), |scope| scope + 1),
+            false => 0,
+        };
+        let (package_name, version) = match key[name_length..].split_once('/') {
+            Some((name, version)) if version.starts_with(|char: char| char.is_ascii_digit()) => (
+                key[..name_length + name.len()].to_string(),
+                version.to_string(),
+            ),
+            _ => Versions::parse_raw_package_details(key.to_string()),
+        };
+
+        Self::dependency(&package_name, &version)
+    }
+
+    /// The package@version of a dependency locked as its version. Aliases and links are locked as a path or a
+    /// protocol, which are resolved again when installed.
+    fn dependency(package_name: &str, version: &str) -> Option<String> {
+        let version = version.split(['(', '_']).next().unwrap_or(version);
+        Version::from_str(version).ok()?;
+
+        Some(Versions::stringify(
+            &package_name.to_string(),
+            &version.to_string(),
+        ))
+    }
+
+    fn dependencies(entry: &YamlNode) -> Vec<String> {
+        ["dependencies", "optionalDependencies"]
+            .iter()
+            .filter_map(|field| entry.get(field))
+            .flat_map(|dependencies| dependencies.children.iter())
+            .filter_map(|dependency| {
+                Self::dependency(&dependency.key, dependency.value.as_deref()?)
+            })
+            .collect()
+    }
+
+    /// A mapping such as `resolution`, which pnpm writes on one line unless it is too long.
+    fn mapping(node: &YamlNode) -> BTreeMap<String, String> {
+        match node.value {
+            Some(_) => node.flow_mapping().into_iter().collect(),
+            None => node
+                .children
+                .iter()
+                .filter_map(|child| Some((child.key.to_string(), child.value.clone()?)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNPM_V6_LOCKFILE: &str = r#"lockfileVersion: '6.0'
+
+settings:
+  autoInstallPeers: true
+  excludeLinksFromLockfile: false
+
+dependencies:
+  '@types/node':
+    specifier: ^20.8.0
+    version: 20.8.10
+  debug:
+    specifier: ^2.6.9
+    version: 2.6.9
+  ms:
+    specifier: ^2.1.3
+    version: 2.1.3
+
+packages:
+
+  /@types/node@20.8.10:
+    resolution: {integrity: sha512-types-node}
+    dependencies:
+      undici-types: 5.26.5
+    dev: false
+
+  /debug@2.6.9:
+    resolution: {integrity: sha512-debug}
+    dependencies:
+      ms: 2.0.0
+    dev: false
+
+  /ms@2.0.0:
+    resolution: {integrity: sha512-ms-2.0.0}
+    dev: false
+
+  /ms@2.1.3:
+    resolution: {integrity: sha512-ms-2.1.3}
+    dev: false
+
+  /undici-types@5.26.5:
+    resolution: {integrity: sha512-undici-types}
+    dev: false
+"#;
+
+    const PNPM_V9_LOCKFILE: &str = r#"lockfileVersion: '9.0'
+
+settings:
+  autoInstallPeers: true
+  excludeLinksFromLockfile: false
+
+importers:
+
+  .:
+    dependencies:
+      '@types/node':
+        specifier: ^20.8.0
+        version: 20.8.10
+      debug:
+        specifier: ^2.6.9
+        version: 2.6.9
+      ms:
+        specifier: ^2.1.3
+        version: 2.1.3
+
+packages:
+
+  '@types/node@20.8.10':
+    resolution: {integrity: sha512-types-node}
+
+  debug@2.6.9:
+    resolution: {integrity: sha512-debug}
+
+  ms@2.0.0:
+    resolution: {integrity: sha512-ms-2.0.0}
+
+  ms@2.1.3:
+    resolution: {integrity: sha512-ms-2.1.3}
+
+  undici-types@5.26.5:
+    resolution: {integrity: sha512-undici-types}
+
+snapshots:
+
+  '@types/node@20.8.10':
+    dependencies:
+      undici-types: 5.26.5
+
+  debug@2.6.9:
+    dependencies:
+      ms: 2.0.0
+
+  ms@2.0.0: {}
+
+  ms@2.1.3: {}
+
+  undici-types@5.26.5: {}
+"#;
+
+    #[test]
+    fn imports_pnpm_lockfiles() {
+        let requested = [
+            ("debug", "^2.6.9"),
+            ("ms", "^2.1.3"),
+            ("@types/node", "^20.8.0"),
+        ]
+        .iter()
+        .map(|(package_name, range)| {
+            let semantic_version = Versions::parse_semantic_version(range).ok();
+            (package_name.to_string(), Source::Registry(semantic_version))
+        })
+        .collect::<Vec<_>>();
+
+        let v6 = PnpmLockfile::convert(PNPM_V6_LOCKFILE, &requested);
+        let v9 = PnpmLockfile::convert(PNPM_V9_LOCKFILE, &requested);
+
+        assert_eq!(
+            v6.packages.keys().collect::<Vec<_>>(),
+            [
+                "@types/node@20.8.10",
+                "debug@2.6.9",
+                "ms@2.0.0",
+                "ms@2.1.3",
+                "undici-types@5.26.5"
+            ]
+        );
+        assert_eq!(v6.packages["debug@2.6.9"].dependencies, ["ms@2.0.0"]);
+        assert_eq!(v6.requested["ms@^2.1.3"], "ms@2.1.3");
+        assert_eq!(
+            v6.packages["ms@2.1.3"].integrity.as_deref(),
+            Some("sha512-ms-2.1.3")
+        );
+
+        assert_eq!(
+            serde_json::to_value(&v6).unwrap(),
+            serde_json::to_value(&v9).unwrap()
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+
+use crate::{errors::CommandError, output::Output};
+
+/// A key read by Prompt::read_key() while the terminal is in raw mode.
+#[derive(PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Space,
+    Enter,
+    Cancel, // q or Ctrl-C, which is read as a key rather than a signal in raw mode
+    Char(char),
+}
+
+/// Keeps the terminal in raw mode, where keys are read as they are pressed without being shown, until it is dropped.
+pub struct RawMode;
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        Prompt::stty(&["-raw", "echo"]);
+    }
+}
+
+/// Asks questions on the terminal, for commands that are interactive such as `click init`.
+pub struct Prompt;
+impl Prompt {
+    /// Returns the answer to a question, or the default if nothing was entered.
+    /// Input that has ended, such as when stdin is not a terminal, also answers with the default.
+    pub fn ask(question: &str, default: &str) -> Result<String, CommandError> {
+        Self::read_answer(question, default)
+    }
+
+    /// Whether there is someone to answer, questions that have a safe default are only asked if there is.
+    pub fn is_interactive() -> bool {
+        io::stdin().is_terminal()
+    }
+
+    /// Asks for a secret such as a password, which is not shown as it is typed when stdin is a terminal.
+    pub fn ask_hidden(question: &str) -> Result<String, CommandError> {
+        let echo_off = Self::stty(&["-echo"]);
+        let answer = Self::read_answer(question, "");
+
+        if echo_off {
+            Self::stty(&["echo"]);
+            Self::newline();
+        }
+
+        answer
+    }
+
+    /// Switches the terminal to raw mode for keys to be read with Prompt::read_key(). Returns None if stdin is not a
+    /// terminal, so that the command can ask with Prompt::ask() instead.
+    pub fn raw_mode() -> Option<RawMode> {
+        (io::stdin().is_terminal() && Self::stty(&["raw", "-echo"])).then_some(RawMode)
+    }
+
+    /// Reads the next key pressed, the arrow keys are read from the escape sequences the terminal sends for them.
+    pub fn read_key() -> Result<Key, CommandError> {
+        let mut stdin = io::stdin().lock();
+        let mut read_byte = || {
+            let mut byte = [0];
+            stdin
+                .read_exact(&mut byte)
+                .map(|_| byte[0])
+                .map_err(CommandError::FailedToReadInput)
+        };
+
+        Ok(match read_byte()? {
+            b'\x1b' => match (read_byte()?, read_byte()?) {
+                (b'[', b'A') => Key::Up,
+                (b'[', b'B') => Key::Down,
+                (b'[', b'C') => Key::Right,
+                (b'[', b'D') => Key::Left,
+                _ => Key::Char('\x1b'),
+            },
+            b' ' => Key::Space,
+            b'\r' | b'\n' => Key::Enter,
+            b'q' | b'\x03' => Key::Cancel,
+            byte => Key::Char(byte as char),
+        })
+    }
+
+    /// Writes text for people to read, to stderr with --json the same way questions are.
+    pub fn write(text: &str) -> Result<(), CommandError> {
+        let written = match Output::is_json() {
+            true => write!(io::stderr(), "{text}").and_then(|_| io::stderr().flush()),
+            false => write!(io::stdout(), "{text}").and_then(|_| io::stdout().flush()),
+        };
+
+        written.map_err(CommandError::FailedToReadInput)
+    }
+
+    /// Returns false if echo could not be changed, such as when stdin is not a terminal.
+    #[cfg(unix)]
+    fn stty(settings: &[&str]) -> bool {
+        std::process::Command::new("stty")
+            .args(settings)
+            .stdin(std::process::Stdio::inherit())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+
+    #[cfg(not(unix))]
+    fn stty(_settings: &[&str]) -> bool {
+        false
+    }
+
+    /// The newline typed after a hidden answer is not shown either.
+    fn newline() {
+        let _ = match Output::is_json() {
+            true => writeln!(io::stderr()),
+            false => writeln!(io::stdout()),
+        };
+    }
+
+    fn read_answer(question: &str, default: &str) -> Result<String, CommandError> {
+        let question = match default.is_empty() {
+            true => format!("{question}: "),
+            false => format!("{question}: ({default}) "),
+        };
+
+        // Questions are not part of the result, so they go to stderr with --json
+        Self::write(&question)?;
+
+        let mut answer = String::new();
+        io::stdin()
+            .lock()
+            .read_line(&mut answer)
+            .map_err(CommandError::FailedToReadInput)?;
+
+        match answer.trim() {
+            "" => Ok(default.to_string()),
+            answer => Ok(answer.to_string()),
+        }
+    }
+}
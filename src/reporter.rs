@@ -0,0 +1,259 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, IsTerminal, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::util::Size;
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(80);
+const MAX_BARS: usize = 5; // Downloads shown at once, the rest are only counted in the overall progress
+const BAR_WIDTH: usize = 20;
+
+/// Something that happened during an install, each package is formatted as package@version unless noted otherwise.
+/// Serialized with its name in `event`, such as `{"event":"resolved","package":"react@18.2.0"}`.
+#[derive(Clone, Copy, Serialize)]
+#[serde(
+    tag = "event",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ProgressEvent<'a> {
+    ResolutionStarted {
+        package: &'a str,
+    }, // A requested package, by name, is about to be resolved along with its dependencies
+    Resolved {
+        package: &'a str,
+    }, // A version was picked and the package queued, whether or not it has to be downloaded
+    DownloadStarted {
+        package: &'a str,
+        size: Option<u64>,
+    }, // With the size of the tarball if the registry sent it
+    DownloadProgress {
+        package: &'a str,
+        bytes: usize,
+    }, // Bytes received since the last event
+    Extracted {
+        package: &'a str,
+    }, // The package is in the cache, which is the end of its download
+    Failed {
+        package: &'a str,
+    }, // The package could not be installed
+    ScriptOutput {
+        package: &'a str,
+        line: &'a str,
+    }, // Only sent to reporters that capture the output of scripts
+    Finished {
+        installed: usize, // Packages linked into node_modules
+        added: usize,     // Packages added to the cache
+        downloaded_bytes: u64,
+        duration_ms: u128,
+    },
+}
+
+/// Receives the events of an install as they happen, from every install task at once. The terminal reporter is the one
+/// the CLI draws with, embedders can give their own to the library's Installer.
+pub trait Reporter: Send + Sync {
+    fn report(&self, event: ProgressEvent);
+
+    /// Removes anything the reporter has drawn, called before the install prints anything else.
+    fn clear(&self) {}
+
+    /// Whether the output of lifecycle scripts is sent as ScriptOutput events rather than printed.
+    fn captures_script_output(&self) -> bool {
+        false
+    }
+}
+
+/// Ignores every event, for commands that fill the cache without installing such as `click resolve`.
+pub struct SilentReporter;
+impl Reporter for SilentReporter {
+    fn report(&self, _: ProgressEvent) {}
+}
+
+/// Sends every event to each of the reporters, in order.
+pub struct Broadcast(pub Vec<Arc<dyn Reporter>>);
+impl Reporter for Broadcast {
+    fn report(&self, event: ProgressEvent) {
+        for reporter in self.0.iter() {
+            reporter.report(event);
+        }
+    }
+
+    fn clear(&self) {
+        for reporter in self.0.iter() {
+            reporter.clear();
+        }
+    }
+
+    fn captures_script_output(&self) -> bool {
+        self.0
+            .iter()
+            .any(|reporter| reporter.captures_script_output())
+    }
+}
+
+/// Prints every event as a line of JSON to stderr, for CI and other tools to follow an install with `--reporter json`.
+pub struct JsonReporter;
+impl Reporter for JsonReporter {
+    fn report(&self, event: ProgressEvent) {
+        if let Ok(line) = serde_json::to_string(&event) {
+            eprintln!("{line}");
+        }
+    }
+
+    fn captures_script_output(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Default)]
+struct TerminalState {
+    resolved: usize,
+    last_resolved: String,
+    extracted: usize,
+    downloaded_bytes: u64,
+    downloads: BTreeMap<String, (u64, Option<u64>)>, // Each download in progress to the bytes received and its size
+    drawn_lines: usize,
+    last_draw: Option<Instant>,
+}
+
+/// Draws the overall progress and a bar for each download on stderr, replacing what it drew before.
+/// Nothing is drawn when stderr is not a terminal, the totals are still kept for the summary.
+#[derive(Default)]
+pub struct TerminalReporter {
+    state: Mutex<TerminalState>,
+    draw: bool,
+}
+
+impl TerminalReporter {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            state: Mutex::new(TerminalState::default()),
+            draw: enabled && io::stderr().is_terminal(),
+        }
+    }
+
+    /// Bytes downloaded from the registry so far, tarballs read from the tarball cache are not counted.
+    pub fn downloaded_bytes(&self) -> u64 {
+        self.state.lock().unwrap().downloaded_bytes
+    }
+
+    fn redraw(&self, state: &mut TerminalState, force: bool) {
+        let now = Instant::now();
+        if !force
+            && state
+                .last_draw
+                .is_some_and(|last_draw| now - last_draw < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        state.last_draw = Some(now);
+
+        let mut lines = vec![format!(
+            "Resolved {} ({}), installed {}, downloaded {}",
+            state.resolved,
+            state.last_resolved,
+            state.extracted,
+            Size(state.downloaded_bytes)
+        )];
+
+        for (package, (received, total)) in state.downloads.iter().take(MAX_BARS) {
+            lines.push(match total {
+                Some(total) if *total > 0 => {
+                    let filled = ((*received as f64 / *total as f64) * BAR_WIDTH as f64) as usize;
+                    let filled = filled.min(BAR_WIDTH);
+                    format!(
+                        "  [{}{}] {package} {}/{}",
+                        "#".repeat(filled),
+                        "-".repeat(BAR_WIDTH - filled),
+                        Size(*received),
+                        Size(*total)
+                    )
+                }
+                _ => format!(
+                    "  [{}] {package} {}",
+                    "?".repeat(BAR_WIDTH),
+                    Size(*received)
+                ),
+            });
+        }
+
+        if state.downloads.len() > MAX_BARS {
+            lines.push(format!(
+                "  and {} more downloading",
+                state.downloads.len() - MAX_BARS
+            ));
+        }
+
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "{}", Self::erase(state.drawn_lines));
+        for line in lines.iter() {
+            let _ = writeln!(stderr, "{line}");
+        }
+        let _ = stderr.flush();
+
+        state.drawn_lines = lines.len();
+    }
+
+    /// Moves the cursor up over the lines drawn before and clears them.
+    fn erase(lines: usize) -> String {
+        "\x1b[1A\x1b[2K".repeat(lines)
+    }
+}
+
+impl Reporter for TerminalReporter {
+    fn report(&self, event: ProgressEvent) {
+        let mut state = self.state.lock().unwrap();
+        let mut force = false;
+
+        match event {
+            ProgressEvent::Resolved { package } => {
+                state.resolved += 1;
+                state.last_resolved = package.to_string();
+            }
+            ProgressEvent::DownloadStarted {
+                package,
+                size: total,
+            } => {
+                state.downloads.insert(package.to_string(), (0, total));
+                force = true;
+            }
+            ProgressEvent::DownloadProgress { package, bytes } => {
+                state.downloaded_bytes += bytes as u64;
+                if let Some((received, _)) = state.downloads.get_mut(package) {
+                    *received += bytes as u64;
+                }
+            }
+            ProgressEvent::Extracted { package } => {
+                state.extracted += 1;
+                force = state.downloads.remove(package).is_some();
+            }
+            ProgressEvent::Failed { package } => force = state.downloads.remove(package).is_some(),
+            ProgressEvent::ResolutionStarted { .. }
+            | ProgressEvent::ScriptOutput { .. }
+            | ProgressEvent::Finished { .. } => return,
+        }
+
+        if self.draw {
+            self.redraw(&mut state, force);
+        }
+    }
+
+    fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.drawn_lines == 0 {
+            return;
+        }
+
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "{}", Self::erase(state.drawn_lines));
+        let _ = stderr.flush();
+
+        state.drawn_lines = 0;
+        state.last_draw = None;
+    }
+}
@@ -0,0 +1,262 @@
+use serde_json::{json, Value};
+
+use crate::{
+    errors::CommandError, integrity::Integrity, licenses::Licenses, manifest::Manifest,
+    types::Lockfile, util, versions::Versions,
+};
+
+const NO_ASSERTION: &str = "NOASSERTION"; // What SPDX records for a field that is not known
+
+/// The formats `click sbom` can write a software bill of materials in.
+#[derive(Clone, Copy, Default)]
+pub enum SbomFormat {
+    #[default]
+    CycloneDx,
+    Spdx,
+}
+
+impl SbomFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "cyclonedx" => Some(Self::CycloneDx),
+            "spdx" => Some(Self::Spdx),
+            _ => None,
+        }
+    }
+}
+
+/// A package of the lockfile as it is listed in a bill of materials.
+struct Component {
+    package: String, // Formatted as package@version
+    name: String,
+    version: String,
+    license: Option<String>,
+    hashes: Vec<(String, String)>, // The algorithm and hex encoded digest of each hash in its integrity
+    resolved: Option<String>,
+    dependencies: Vec<String>, // The packages it depends on itself, formatted as package@version
+}
+
+/// Writes the packages in the lockfile as a CycloneDX 1.5 or SPDX 2.3 document in JSON, with the project as the root
+/// that depends on every package in package.json.
+pub struct Sbom;
+impl Sbom {
+    pub fn generate(
+        format: SbomFormat,
+        manifest: &Manifest,
+        lockfile: &Lockfile,
+    ) -> Result<Value, CommandError> {
+        let mut components = Vec::new();
+        for (package, locked_package) in lockfile.packages.iter() {
+            let (name, version) = Versions::parse_raw_package_details(package.to_string());
+            let dependencies = lockfile
+                .direct_dependencies(package)
+                .into_iter()
+                .map(|(_, dependency)| dependency)
+                .collect();
+
+            components.push(Component {
+                package: package.to_string(),
+                name,
+                version,
+                license: Licenses::of(package)?,
+                hashes: locked_package
+                    .integrity
+                    .as_deref()
+                    .map(Integrity::hex_hashes)
+                    .unwrap_or_default(),
+                resolved: locked_package.resolved.clone(),
+                dependencies,
+            });
+        }
+
+        let mut requested = lockfile.requested.values().cloned().collect::<Vec<_>>();
+        requested.sort();
+        requested.dedup();
+
+        let root = Component {
+            package: String::new(), // Not in the lockfile, it is referred to by its name and version
+            name: manifest.field("name").unwrap_or("project").to_string(),
+            version: manifest.field("version").unwrap_or("0.0.0").to_string(),
+            license: manifest.license(),
+            hashes: Vec::new(),
+            resolved: None,
+            dependencies: requested,
+        };
+
+        Ok(match format {
+            SbomFormat::CycloneDx => Self::cyclonedx(&root, &components),
+            SbomFormat::Spdx => Self::spdx(&root, &components),
+        })
+    }
+
+    fn cyclonedx(root: &Component, components: &[Component]) -> Value {
+        let root_ref = Versions::stringify(&root.name, &root.version);
+        let component = |component: &Component, kind: &str, bom_ref: &str| {
+            let mut value = json!({
+                "type": kind,
+                "bom-ref": bom_ref,
+                "name": component.name,
+                "version": component.version,
+                "purl": Self::purl(component),
+            });
+
+            if let Some(license) = &component.license {
+                value["licenses"] = json!([{ "expression": license }]);
+            }
+            if !component.hashes.is_empty() {
+                let hashes = component.hashes.iter().filter_map(|(algorithm, digest)| {
+                    Some(json!({ "alg": Self::algorithm(algorithm, "-")?, "content": digest }))
+                });
+                value["hashes"] = Value::Array(hashes.collect());
+            }
+            if let Some(resolved) = &component.resolved {
+                value["externalReferences"] = json!([{ "type": "distribution", "url": resolved }]);
+            }
+
+            value
+        };
+
+        let mut dependencies = vec![json!({ "ref": root_ref, "dependsOn": root.dependencies })];
+        dependencies.extend(components.iter().map(
+            |component| json!({ "ref": component.package, "dependsOn": component.dependencies }),
+        ));
+
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": {
+                "timestamp": util::timestamp(),
+                "tools": {
+                    "components": [{
+                        "type": "application",
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                    }],
+                },
+                "component": component(root, "application", &root_ref),
+            },
+            "components": components
+                .iter()
+                .map(|entry| component(entry, "library", &entry.package))
+                .collect::<Vec<_>>(),
+            "dependencies": dependencies,
+        })
+    }
+
+    fn spdx(root: &Component, components: &[Component]) -> Value {
+        let root_id = String::from("SPDXRef-Root");
+        let package = |component: &Component, id: &str| {
+            let checksums = component
+                .hashes
+                .iter()
+                .filter_map(|(algorithm, digest)| {
+                    Some(json!({
+                        "algorithm": Self::algorithm(algorithm, "")?,
+                        "checksumValue": digest,
+                    }))
+                })
+                .collect::<Vec<_>>();
+
+            json!({
+                "name": component.name,
+                "SPDXID": id,
+                "versionInfo": component.version,
+                "downloadLocation": component.resolved.as_deref().unwrap_or(NO_ASSERTION),
+                "filesAnalyzed": false,
+                "licenseConcluded": NO_ASSERTION,
+                "licenseDeclared": component.license.as_deref().unwrap_or(NO_ASSERTION),
+                "copyrightText": NO_ASSERTION,
+                "checksums": checksums,
+                "externalRefs": [{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": Self::purl(component),
+                }],
+            })
+        };
+
+        let depends_on = |id: &str, component: &Component| {
+            component
+                .dependencies
+                .iter()
+                .map(|dependency| {
+                    json!({
+                        "spdxElementId": id,
+                        "relationshipType": "DEPENDS_ON",
+                        "relatedSpdxElement": Self::spdx_id(dependency),
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut packages = vec![package(root, &root_id)];
+        let mut relationships = vec![json!({
+            "spdxElementId": "SPDXRef-DOCUMENT",
+            "relationshipType": "DESCRIBES",
+            "relatedSpdxElement": root_id,
+        })];
+        relationships.extend(depends_on(&root_id, root));
+        for component in components {
+            let id = Self::spdx_id(&component.package);
+            packages.push(package(component, &id));
+            relationships.extend(depends_on(&id, component));
+        }
+
+        let timestamp = util::timestamp();
+        json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": root.name,
+            // Has to be unique to the document, but does not have to resolve to anything
+            "documentNamespace": format!(
+                "https://spdx.org/spdxdocs/{}-{}-{}",
+                root.name.replace('/', "-").trim_start_matches('@'),
+                root.version,
+                timestamp.replace(':', "")
+            ),
+            "creationInfo": {
+                "created": timestamp,
+                "creators": [format!("Tool: {}-{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))],
+            },
+            "packages": packages,
+            "relationships": relationships,
+        })
+    }
+
+    /// Such as `pkg:npm/%40types/node@20.8.10`, the @ of a scope is encoded.
+    fn purl(component: &Component) -> String {
+        format!(
+            "pkg:npm/{}@{}",
+            component.name.replacen('@', "%40", 1),
+            component.version
+        )
+    }
+
+    /// An SPDX identifier can only have letters, digits, dots and dashes in it.
+    fn spdx_id(package: &str) -> String {
+        let id = package
+            .chars()
+            .map(|char| match char.is_ascii_alphanumeric() || char == '.' {
+                true => char,
+                false => '-',
+            })
+            .collect::<String>();
+
+        format!("SPDXRef-Package-{id}")
+    }
+
+    /// The name of a hash algorithm of an integrity string such as sha512, as SHA-512 in CycloneDX and SHA512 in SPDX.
+    fn algorithm(algorithm: &str, separator: &str) -> Option<String> {
+        let bits = match algorithm {
+            "sha1" => "1",
+            "sha256" => "256",
+            "sha384" => "384",
+            "sha512" => "512",
+            _ => return None,
+        };
+
+        Some(format!("SHA{separator}{bits}"))
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    env,
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    thread,
+};
+
+use crate::{
+    cache::Cache,
+    errors::CommandError,
+    installer::MODULES_DIRECTORY,
+    manifest::Manifest,
+    output::Output,
+    reporter::{ProgressEvent, Reporter},
+    versions::Versions,
+};
+
+// In the order npm runs them
+pub const LIFECYCLE_SCRIPTS: [&str; 3] = ["preinstall", "install", "postinstall"];
+const GYP_FILE: &str = "binding.gyp"; // Native addons built with node-gyp, which need no install script of their own
+const GYP_SCRIPT: &str = "node-gyp rebuild";
+
+pub struct Scripts;
+impl Scripts {
+    /// Returns the name and command of every script in a package.json.
+    /// Scripts are an object of strings the same as the dependency fields, so they are read the same way.
+    pub fn scripts(manifest: &Manifest) -> Vec<(String, String)> {
+        manifest.dependencies("scripts")
+    }
+
+    /// Binaries of installed packages can be run by name from scripts, before anything else on PATH.
+    pub fn path_with_bins() -> Result<String, CommandError> {
+        Self::workspace_path_with_bins(None)
+    }
+
+    /// The binaries of a workspace are found before those of the root, the same as Node finds its dependencies.
+    pub fn workspace_path_with_bins(workspace: Option<&Path>) -> Result<String, CommandError> {
+        let root = env::current_dir().map_err(CommandError::ComandFailedError)?;
+        let bin_path = Path::new(MODULES_DIRECTORY).join(".bin");
+
+        let mut paths = Vec::new();
+        if let Some(workspace) = workspace {
+            paths.push(root.join(workspace).join(&bin_path));
+        }
+        paths.push(root.join(bin_path));
+
+        if let Some(path) = env::var_os("PATH") {
+            paths.extend(env::split_paths(&path));
+        }
+
+        let path = env::join_paths(paths).map_err(|err| {
+            CommandError::ComandFailedError(std::io::Error::other(err.to_string()))
+        })?;
+
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    #[cfg(unix)]
+    pub fn shell_command(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        command
+    }
+
+    #[cfg(windows)]
+    pub fn shell_command(script: &str) -> Command {
+        let mut command = Command::new("cmd");
+        command.args(["/d", "/s", "/c", script]);
+        command
+    }
+
+    /// Returns the lifecycle scripts of a package linked into node_modules, in the order they are run.
+    /// The same as npm, a package with a `binding.gyp` and neither a preinstall nor an install script is built with
+    /// `node-gyp rebuild` as its install script.
+    pub fn lifecycle_scripts(package_name: &str) -> Result<Vec<(String, String)>, CommandError> {
+        let manifest_path = Self::package_path(package_name).join("package.json");
+        let scripts = match Manifest::read_from(&manifest_path)? {
+            Some(manifest) => Self::scripts(&manifest),
+            None => return Ok(Vec::new()),
+        };
+
+        let mut lifecycle_scripts = LIFECYCLE_SCRIPTS
+            .iter()
+            .filter_map(|lifecycle| scripts.iter().find(|(name, _)| name == lifecycle))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let builds = lifecycle_scripts
+            .iter()
+            .any(|(name, _)| name == "preinstall" || name == "install");
+        if !builds && Self::package_path(package_name).join(GYP_FILE).exists() {
+            lifecycle_scripts.insert(0, (String::from("install"), GYP_SCRIPT.to_string()));
+        }
+
+        Ok(lifecycle_scripts)
+    }
+
+    /// Package strings are formated as package@version
+    /// Returns the packages that have lifecycle scripts, with dependencies before the packages that depend on them as
+    /// scripts often use them.
+    pub fn in_dependency_order(packages: Vec<String>) -> Result<Vec<String>, CommandError> {
+        let mut ordered = Vec::new();
+        for package in packages {
+            let (package_name, _) = Versions::parse_raw_package_details(package.to_string());
+            if Self::lifecycle_scripts(&package_name)?.is_empty() {
+                continue;
+            }
+
+            // Locked dependencies include indirect ones, so a package always has more than any of its dependencies
+            ordered.push((Cache::read_lock(&package)?.dependencies.len(), package));
+        }
+
+        ordered.sort();
+        Ok(ordered.into_iter().map(|(_, package)| package).collect())
+    }
+
+    /// Package string is formated as package@version
+    /// Runs the lifecycle scripts of a package in its directory in node_modules, so that its dependencies can be found.
+    /// The package is linked from the cache, so anything the scripts build is kept there and they only have to run once.
+    /// What the scripts print is sent to the reporter if it captures it, otherwise it is printed.
+    pub fn run_lifecycle(
+        package: &String,
+        quiet: bool,
+        reporter: &dyn Reporter,
+    ) -> Result<(), CommandError> {
+        let (package_name, version) = Versions::parse_raw_package_details(package.to_string());
+        let path = Self::path_with_bins()?;
+
+        for (lifecycle, script) in Self::lifecycle_scripts(&package_name)? {
+            if !quiet {
+                Output::message(format!("Running {lifecycle} script of '{package}'.."));
+            }
+
+            // Symlinks are preserved so that dependencies are resolved from node_modules rather than the cache
+            let mut command = Self::shell_command(&script);
+            command
+                .current_dir(Self::package_path(&package_name))
+                .env("PATH", &path)
+                .env("NODE_PRESERVE_SYMLINKS", "1")
+                .env("npm_lifecycle_event", &lifecycle)
+                .env("npm_package_name", &package_name)
+                .env("npm_package_version", &version);
+
+            let status = match reporter.captures_script_output() {
+                true => Self::report_output(command, package, reporter)?,
+                false => command
+                    .stdout(Output::script_stdout())
+                    .status()
+                    .map_err(CommandError::ComandFailedError)?,
+            };
+
+            // A script killed by a signal has no exit code
+            if !status.success() {
+                return Err(CommandError::LifecycleScriptFailed {
+                    package: package.to_string(),
+                    script: lifecycle,
+                    code: status.code().unwrap_or(1),
+                });
+            }
+        }
+
+        let mut package_lock = Cache::read_lock(package)?;
+        package_lock.install_scripts_run = true;
+
+        // Scripts usually build files into the package, which would otherwise be reported by `click verify`
+        package_lock.content_integrity = Some(Cache::content_integrity(package)?);
+
+        Cache::write_lock(package, &package_lock)
+    }
+
+    /// Runs the command with each line it prints to stdout or stderr sent to the reporter as it is printed.
+    fn report_output(
+        mut command: Command,
+        package: &str,
+        reporter: &dyn Reporter,
+    ) -> Result<ExitStatus, CommandError> {
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(CommandError::ComandFailedError)?;
+
+        let report_lines = |output: &mut dyn Read| {
+            let mut reader = BufReader::new(output);
+            let mut line = Vec::new();
+            while reader
+                .read_until(b'\n', &mut line)
+                .is_ok_and(|read| read > 0)
+            {
+                let text = String::from_utf8_lossy(&line);
+                reporter.report(ProgressEvent::ScriptOutput {
+                    package,
+                    line: text.trim_end(),
+                });
+                line.clear();
+            }
+        };
+
+        let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+        thread::scope(|scope| {
+            if let Some(mut stdout) = stdout {
+                scope.spawn(move || report_lines(&mut stdout));
+            }
+            if let Some(mut stderr) = stderr {
+                scope.spawn(move || report_lines(&mut stderr));
+            }
+        });
+
+        child.wait().map_err(CommandError::ComandFailedError)
+    }
+
+    fn package_path(package_name: &str) -> PathBuf {
+        Path::new(MODULES_DIRECTORY).join(package_name)
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use crate::{
+    installer::{BIN_DIRECTORY, MODULES_DIRECTORY},
+    platform::Platform,
+};
+
+/// The kind of platform the executables in node_modules/.bin are written for, see Shims::link().
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShimPlatform {
+    Unix,    // Each executable is linked, or run by a shell script where links can't be created
+    Windows, // Each executable is run by a cmd, PowerShell and shell script with the program of its shebang
+}
+
+/// The platform click is running on.
+impl Default for ShimPlatform {
+    fn default() -> Self {
+        match cfg!(windows) {
+            true => Self::Windows,
+            false => Self::Unix,
+        }
+    }
+}
+
+impl ShimPlatform {
+    /// Parses a platform given with `--target-platform`, returns None if it is neither `unix` nor `windows`.
+    pub fn parse(platform: &str) -> Option<Self> {
+        match platform {
+            "unix" => Some(Self::Unix),
+            "windows" => Some(Self::Windows),
+            _ => None,
+        }
+    }
+
+    /// The shims that suit the platform packages are installed for, such as with --force-platform.
+    pub fn of(platform: &Platform) -> Self {
+        match platform.os.as_str() {
+            "win32" => Self::Windows,
+            _ => Self::Unix,
+        }
+    }
+}
+
+/// Writes the entries of node_modules/.bin that run the executables of packages by name.
+pub struct Shims;
+impl Shims {
+    /// Executables are linked relative to node_modules/.bin, so node_modules keeps working if the project is moved.
+    /// `path` is the path of the executable within the package, as its bin field lists it.
+    pub fn link(
+        bin_name: &str,
+        package_name: &str,
+        path: &str,
+        platform: ShimPlatform,
+    ) -> Result<(), io::Error> {
+        match platform {
+            ShimPlatform::Unix => Self::link_unix(bin_name, package_name, path),
+            ShimPlatform::Windows => Self::write_windows(bin_name, package_name, path),
+        }
+    }
+
+    #[cfg(unix)]
+    fn link_unix(bin_name: &str, package_name: &str, path: &str) -> Result<(), io::Error> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let link = Path::new(BIN_DIRECTORY).join(bin_name);
+        let target = Path::new("..").join(package_name).join(path);
+
+        // Packages are not always published with the executable bit set
+        let executable = Path::new(MODULES_DIRECTORY).join(package_name).join(path);
+        let mut permissions = fs::metadata(&executable)?.permissions();
+        permissions.set_mode(permissions.mode() | 0o111);
+        fs::set_permissions(&executable, permissions)?;
+
+        match fs::read_link(&link) {
+            Ok(existing) if existing == target => return Ok(()),
+            Ok(_) => fs::remove_file(&link)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(_) => fs::remove_file(&link)?, // Not a link, such as a shim written for Windows
+        }
+
+        std::os::unix::fs::symlink(target, link)
+    }
+
+    /// Symlinks need extra privileges on Windows, so only the shell script is written when installing for unix there.
+    #[cfg(not(unix))]
+    fn link_unix(bin_name: &str, package_name: &str, path: &str) -> Result<(), io::Error> {
+        let executable = Path::new(MODULES_DIRECTORY).join(package_name).join(path);
+        let program = Self::shebang_program(&executable)?;
+
+        Self::write(
+            &Path::new(BIN_DIRECTORY).join(bin_name),
+            &Self::sh(&program, &format!("{package_name}/{path}")),
+        )
+    }
+
+    /// Executables are not run through their shebang on Windows, so shims are written that run the executable with
+    /// the program of its shebang, or node if it has none. Like npm, there is a shim for cmd, one for PowerShell and one
+    /// for shells such as Git Bash.
+    fn write_windows(bin_name: &str, package_name: &str, path: &str) -> Result<(), io::Error> {
+        let executable = Path::new(MODULES_DIRECTORY).join(package_name).join(path);
+        let program = Self::shebang_program(&executable)?;
+        let target = format!("{package_name}/{path}");
+        let bin_directory = Path::new(BIN_DIRECTORY);
+
+        Self::write(
+            &bin_directory.join(format!("{bin_name}.cmd")),
+            &Self::cmd(&program, &target),
+        )?;
+        Self::write(
+            &bin_directory.join(format!("{bin_name}.ps1")),
+            &Self::powershell(&program, &target),
+        )?;
+        Self::write(&bin_directory.join(bin_name), &Self::sh(&program, &target))
+    }
+
+    /// Replaces what is there rather than writing through it, as it can be a link to the executable itself.
+    fn write(path: &Path, contents: &str) -> Result<(), io::Error> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+
+        fs::write(path, contents)
+    }
+
+    /// `target` is the executable relative to node_modules, with forward slashes.
+    fn cmd(program: &str, target: &str) -> String {
+        let target = target.replace('/', "\\");
+        format!("@ECHO off\r\n{program} \"%~dp0\\..\\{target}\" %*\r\n")
+    }
+
+    fn powershell(program: &str, target: &str) -> String {
+        let target = target.replace('/', "\\");
+        format!("& {program} \"$PSScriptRoot\\..\\{target}\" $args\nexit $LASTEXITCODE\n")
+    }
+
+    fn sh(program: &str, target: &str) -> String {
+        format!(
+            "#!/bin/sh\nbasedir=$(dirname \"$(echo \"$0\" | sed -e 's,\\\\,/,g')\")\nexec {program} \"$basedir/../{target}\" \"$@\"\n"
+        )
+    }
+
+    /// The program and arguments of a shebang such as `#!/usr/bin/env node` or `#!/bin/sh -e`, without the directory
+    /// of the program as it is found on PATH instead.
+    fn shebang_program(executable: &Path) -> Result<String, io::Error> {
+        let contents = fs::read(executable)?;
+        let first_line = contents
+            .split(|byte| *byte == b'\n')
+            .next()
+            .unwrap_or_default();
+
+        Ok(Self::parse_shebang(&String::from_utf8_lossy(first_line)))
+    }
+
+    fn parse_shebang(first_line: &str) -> String {
+        let Some(shebang) = first_line.trim_end().strip_prefix("#!") else {
+            return String::from("node");
+        };
+
+        let mut words = shebang.split_whitespace().collect::<Vec<_>>();
+        if words
+            .first()
+            .is_some_and(|program| program.ends_with("/env"))
+        {
+            words.remove(0);
+            if words.first() == Some(&"-S") {
+                words.remove(0);
+            }
+        }
+
+        let Some(program) = words.first_mut() else {
+            return String::from("node");
+        };
+        let path = *program;
+        *program = path.rsplit('/').next().unwrap_or(path);
+
+        words.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_program_of_shebang() {
+        assert_eq!(Shims::parse_shebang("#!/usr/bin/env node"), "node");
+        assert_eq!(
+            Shims::parse_shebang("#!/usr/bin/env -S node --no-warnings"),
+            "node --no-warnings"
+        );
+        assert_eq!(Shims::parse_shebang("#!/bin/sh -e\r"), "sh -e");
+        assert_eq!(Shims::parse_shebang("module.exports = 1;"), "node");
+    }
+
+    #[test]
+    fn writes_windows_shims_with_backslashes() {
+        assert_eq!(
+            Shims::cmd("node", "@acme/tool/bin/cli.js"),
+            "@ECHO off\r\nnode \"%~dp0\\..\\@acme\\tool\\bin\\cli.js\" %*\r\n"
+        );
+        assert_eq!(
+            Shims::powershell("node", "@acme/tool/bin/cli.js"),
+            "& node \"$PSScriptRoot\\..\\@acme\\tool\\bin\\cli.js\" $args\nexit $LASTEXITCODE\n"
+        );
+    }
+
+    #[test]
+    fn writes_sh_shim_with_forward_slashes() {
+        let shim = Shims::sh("sh -e", "@acme/tool/bin/cli.sh");
+
+        assert!(shim.starts_with("#!/bin/sh\n"));
+        assert!(shim.ends_with("exec sh -e \"$basedir/../@acme/tool/bin/cli.sh\" \"$@\"\n"));
+    }
+
+    #[test]
+    fn follows_the_platform_installed_for() {
+        let windows = Platform::parse("win32/x64").unwrap();
+        let linux = Platform::parse("linux/arm64").unwrap();
+
+        assert_eq!(ShimPlatform::of(&windows), ShimPlatform::Windows);
+        assert_eq!(ShimPlatform::of(&linux), ShimPlatform::Unix);
+        assert_eq!(ShimPlatform::parse("windows"), Some(ShimPlatform::Windows));
+        assert_eq!(ShimPlatform::parse("win32"), None);
+    }
+}
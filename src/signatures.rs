@@ -0,0 +1,416 @@
+use std::{collections::HashMap, sync::Arc};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use openssl::{hash::MessageDigest, pkey::PKey, sign::Verifier};
+use reqwest::StatusCode;
+use tokio::sync::Mutex;
+
+use crate::{
+    config::Config,
+    errors::CommandError,
+    http::HTTPRequest,
+    integrity::Integrity,
+    types::{Attestation, Dist, InTotoStatement, RegistryKey, Signature},
+    util,
+};
+
+const PUBLISH_ATTESTATION: &str = "https://github.com/npm/attestation/tree/main/specs/publish/"; // Signed by the registry
+
+lazy_static! {
+    // The keys of each registry, requested once by the first package from it. None if they could not be requested.
+    static ref REGISTRY_KEYS: Mutex<HashMap<String, Option<Arc<Vec<RegistryKey>>>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Checks what the registry signed a version with against the keys it publishes, the same as `npm audit signatures`.
+pub struct Signatures;
+impl Signatures {
+    /// The registry signs `name@version:integrity` with ECDSA P-256 when a version is published, and the npm publish
+    /// attestation is signed with the same keys. A provenance attestation is checked to be about the same tarball, the
+    /// Sigstore certificate it is signed with is not checked. With `strict` a version that is not signed, or whose
+    /// signature or attestations are not valid, fails to install, otherwise only an invalid signature is warned about.
+    pub async fn verify(
+        client: reqwest::Client,
+        package_name: &str,
+        version: &str,
+        dist: &Dist,
+        strict: bool,
+    ) -> Result<(), CommandError> {
+        let package = format!("{package_name}@{version}");
+        let (Some(integrity), false) = (&dist.integrity, dist.signatures.is_empty()) else {
+            return match strict {
+                true => Err(CommandError::UnsignedPackage(package)),
+                false => Ok(()),
+            };
+        };
+
+        let registry = Config::get()?.registry_for(package_name).to_string();
+        let Some(keys) = Self::keys(client.clone(), &registry, strict).await? else {
+            return Ok(());
+        };
+        if keys.is_empty() {
+            let reason = format!("'{registry}' does not publish the keys it signs with");
+            return match strict {
+                true => Err(CommandError::InvalidSignature(package, reason)),
+                false => Ok(()),
+            };
+        }
+
+        let result = Self::verify_signatures(
+            &keys,
+            &dist.signatures,
+            format!("{package}:{integrity}").as_bytes(),
+        );
+        // Attestations take another request for each package, so they are only checked with `strict`
+        let result = match (result, &dist.attestations) {
+            (Ok(_), Some(attestations)) if strict => {
+                HTTPRequest::attestations(client, &attestations.url)
+                    .await?
+                    .attestations
+                    .iter()
+                    .try_for_each(|attestation| {
+                        Self::verify_attestation(&keys, attestation, &package, integrity)
+                    })
+            }
+            (result, _) => result,
+        };
+
+        match result {
+            Ok(_) => {
+                debug!("the signature of '{package}' is valid");
+                Ok(())
+            }
+            Err(reason) if strict => Err(CommandError::InvalidSignature(package, reason)),
+            Err(reason) => {
+                warn!("{}", CommandError::InvalidSignature(package, reason));
+                Ok(())
+            }
+        }
+    }
+
+    /// A registry without the route has no keys. Returns None without `strict` if they could not be requested, which is
+    /// only warned about once.
+    async fn keys(
+        client: reqwest::Client,
+        registry: &str,
+        strict: bool,
+    ) -> Result<Option<Arc<Vec<RegistryKey>>>, CommandError> {
+        let mut registry_keys = REGISTRY_KEYS.lock().await;
+        if let Some(keys) = registry_keys.get(registry) {
+            return Ok(keys.clone());
+        }
+
+        let keys = match HTTPRequest::registry_keys(client, registry).await {
+            Ok(keys) => Some(Arc::new(keys.keys)),
+            Err(CommandError::HTTPFailed(err)) if err.status() == Some(StatusCode::NOT_FOUND) => {
+                Some(Arc::new(Vec::new()))
+            }
+            Err(err) if strict => return Err(err),
+            Err(err) => {
+                warn!("the signing keys of '{registry}' could not be requested, signatures are not checked ({err})");
+                None
+            }
+        };
+
+        registry_keys.insert(registry.to_string(), keys.clone());
+        Ok(keys)
+    }
+
+    /// Valid if any of the signatures is by a key of the registry that has not expired.
+    fn verify_signatures(
+        keys: &[RegistryKey],
+        signatures: &[Signature],
+        message: &[u8],
+    ) -> Result<(), String> {
+        let mut reason = String::from("it is not signed by any of the keys of the registry");
+        for signature in signatures {
+            let Some(key) = keys.iter().find(|key| key.keyid == signature.keyid) else {
+                continue;
+            };
+
+            if let Some(expires) = key.expires.as_deref().filter(|_| Self::is_expired(key)) {
+                reason = format!(
+                    "it is signed by the key '{}', which expired at {expires}",
+                    key.keyid
+                );
+                continue;
+            }
+
+            match Self::verify_ecdsa(key, &signature.sig, message) {
+                Ok(true) => return Ok(()),
+                Ok(false) => reason = format!("it does not match the key '{}'", key.keyid),
+                Err(err) => reason = err,
+            }
+        }
+
+        Err(reason)
+    }
+
+    /// Expiry dates are in UTC, so up to the seconds they sort the same as the times they are.
+    fn is_expired(key: &RegistryKey) -> bool {
+        let now = util::timestamp();
+        key.expires
+            .as_deref()
+            .is_some_and(|expires| expires.get(..19).unwrap_or(expires) <= &now[..19])
+    }
+
+    fn verify_ecdsa(key: &RegistryKey, signature: &str, message: &[u8]) -> Result<bool, String> {
+        let public_key = STANDARD
+            .decode(&key.key)
+            .map_err(|err| format!("the key '{}' is not base64 ({err})", key.keyid))?;
+        let signature = STANDARD
+            .decode(signature)
+            .map_err(|err| format!("the signature is not base64 ({err})"))?;
+
+        let public_key = PKey::public_key_from_der(&public_key)
+            .map_err(|err| format!("the key '{}' could not be read ({err})", key.keyid))?;
+        let verifier = Verifier::new(MessageDigest::sha256(), &public_key)
+            .and_then(|mut verifier| verifier.update(message).map(|_| verifier))
+            .map_err(|err| err.to_string())?;
+
+        Ok(verifier.verify(&signature).unwrap_or(false))
+    }
+
+    /// An attestation has to be about the tarball of this version, and the publish attestation has to be signed by the
+    /// registry. The envelope is signed over its pre-authentication encoding, see https://github.com/secure-systems-lab/dsse.
+    fn verify_attestation(
+        keys: &[RegistryKey],
+        attestation: &Attestation,
+        package: &str,
+        integrity: &str,
+    ) -> Result<(), String> {
+        let envelope = &attestation.bundle.dsse_envelope;
+        let payload = STANDARD
+            .decode(&envelope.payload)
+            .map_err(|err| format!("its attestation is not base64 ({err})"))?;
+        let statement = serde_json::from_slice::<InTotoStatement>(&payload)
+            .map_err(|err| format!("its attestation could not be parsed ({err})"))?;
+
+        // Scoped names are percent encoded in package URLs
+        let subject_name = format!("pkg:npm/{package}").replacen("pkg:npm/@", "pkg:npm/%40", 1);
+        let digests = Integrity::hex_hashes(integrity);
+        let matches = statement.subject.iter().any(|subject| {
+            subject.name == subject_name
+                && digests
+                    .iter()
+                    .any(|(algorithm, digest)| subject.digest.get(algorithm) == Some(digest))
+        });
+        if !matches {
+            return Err(format!(
+                "its {} attestation is about another tarball",
+                attestation.predicate_type
+            ));
+        }
+
+        if !attestation.predicate_type.starts_with(PUBLISH_ATTESTATION) {
+            return Ok(());
+        }
+
+        let hint = attestation
+            .bundle
+            .verification_material
+            .public_key
+            .as_ref()
+            .map(|public_key| public_key.hint.as_str())
+            .unwrap_or_default();
+        let signatures = envelope
+            .signatures
+            .iter()
+            .map(|signature| Signature {
+                keyid: hint.to_string(),
+                sig: signature.sig.to_string(),
+            })
+            .collect::<Vec<_>>();
+
+        let message = Self::pre_authentication_encoding(&envelope.payload_type, &payload);
+        Self::verify_signatures(keys, &signatures, &message)
+            .map_err(|reason| format!("its publish attestation is not valid, {reason}"))
+    }
+
+    /// What a DSSE envelope is signed over, its payload along with the type of it.
+    fn pre_authentication_encoding(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+        let mut message = format!(
+            "DSSEv1 {} {payload_type} {} ",
+            payload_type.len(),
+            payload.len()
+        )
+        .into_bytes();
+        message.extend(payload);
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use openssl::{
+        ec::{EcGroup, EcKey},
+        nid::Nid,
+        pkey::Private,
+        sha::sha512,
+        sign::Signer,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    const INTEGRITY: &str = "sha512-AAAA";
+
+    fn private_key() -> PKey<Private> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+    }
+
+    fn registry_key(private_key: &PKey<Private>, expires: Option<&str>) -> RegistryKey {
+        RegistryKey {
+            keyid: String::from("SHA256:registry"),
+            key: STANDARD.encode(private_key.public_key_to_der().unwrap()),
+            expires: expires.map(str::to_string),
+        }
+    }
+
+    fn sign(private_key: &PKey<Private>, keyid: &str, message: &[u8]) -> Signature {
+        let mut signer = Signer::new(MessageDigest::sha256(), private_key).unwrap();
+        signer.update(message).unwrap();
+
+        Signature {
+            keyid: keyid.to_string(),
+            sig: STANDARD.encode(signer.sign_to_vec().unwrap()),
+        }
+    }
+
+    #[test]
+    fn verifies_the_registry_signature_of_a_version() {
+        let private_key = private_key();
+        let key = registry_key(&private_key, None);
+        let message = format!("aa@1.0.0:{INTEGRITY}");
+        let signature = sign(&private_key, &key.keyid, message.as_bytes());
+
+        assert_eq!(
+            Signatures::verify_signatures(&[key], &[signature], message.as_bytes()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_a_tampered_integrity_and_unknown_or_expired_keys() {
+        let private_key = private_key();
+        let key = registry_key(&private_key, None);
+        let message = format!("aa@1.0.0:{INTEGRITY}");
+        let signature = sign(&private_key, &key.keyid, message.as_bytes());
+
+        assert_eq!(
+            Signatures::verify_signatures(
+                &[registry_key(&private_key, None)],
+                &[sign(&private_key, &key.keyid, message.as_bytes())],
+                b"aa@1.0.0:sha512-BBBB",
+            ),
+            Err(String::from("it does not match the key 'SHA256:registry'"))
+        );
+
+        assert_eq!(
+            Signatures::verify_signatures(
+                &[key],
+                &[sign(&private_key, "SHA256:other", message.as_bytes())],
+                message.as_bytes(),
+            ),
+            Err(String::from(
+                "it is not signed by any of the keys of the registry"
+            ))
+        );
+
+        let expired = registry_key(&private_key, Some("2000-01-01T00:00:00.000Z"));
+        assert_eq!(
+            Signatures::verify_signatures(&[expired], &[signature], message.as_bytes()),
+            Err(String::from(
+                "it is signed by the key 'SHA256:registry', which expired at 2000-01-01T00:00:00.000Z"
+            ))
+        );
+
+        // A key that was rotated out but has not expired yet is still valid
+        let expiring = registry_key(&private_key, Some("2999-01-01T00:00:00.000Z"));
+        let signature = sign(&private_key, &expiring.keyid, message.as_bytes());
+        assert_eq!(
+            Signatures::verify_signatures(&[expiring], &[signature], message.as_bytes()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn encodes_dsse_envelopes_before_authenticating_them() {
+        // The example of the DSSE protocol
+        assert_eq!(
+            Signatures::pre_authentication_encoding(
+                "http://example.com/HelloWorld",
+                b"hello world"
+            ),
+            b"DSSEv1 29 http://example.com/HelloWorld 11 hello world"
+        );
+    }
+
+    #[test]
+    fn verifies_the_publish_attestation_over_its_encoding() {
+        let tarball = b"tarball";
+        let integrity = format!("sha512-{}", STANDARD.encode(sha512(tarball)));
+        let payload = json!({
+            "subject": [{
+                "name": "pkg:npm/%40scope/aa@1.0.0",
+                "digest": { "sha512": Integrity::hex_digest("sha512", tarball).unwrap() },
+            }],
+        })
+        .to_string();
+        let payload_type = "application/vnd.in-toto+json";
+
+        let private_key = private_key();
+        let key = registry_key(&private_key, None);
+        let signed = Signatures::pre_authentication_encoding(payload_type, payload.as_bytes());
+        let attestation = |signed: &[u8]| -> Attestation {
+            serde_json::from_value(json!({
+                "predicateType": format!("{PUBLISH_ATTESTATION}v0.1"),
+                "bundle": {
+                    "verificationMaterial": { "publicKey": { "hint": key.keyid } },
+                    "dsseEnvelope": {
+                        "payload": STANDARD.encode(&payload),
+                        "payloadType": payload_type,
+                        "signatures": [{ "sig": sign(&private_key, "", signed).sig }],
+                    },
+                },
+            }))
+            .unwrap()
+        };
+
+        let keys = [registry_key(&private_key, None)];
+        assert_eq!(
+            Signatures::verify_attestation(
+                &keys,
+                &attestation(&signed),
+                "@scope/aa@1.0.0",
+                &integrity
+            ),
+            Ok(())
+        );
+
+        // Signed over the payload alone rather than its encoding
+        assert_eq!(
+            Signatures::verify_attestation(
+                &keys,
+                &attestation(payload.as_bytes()),
+                "@scope/aa@1.0.0",
+                &integrity
+            ),
+            Err(String::from(
+                "its publish attestation is not valid, it does not match the key 'SHA256:registry'"
+            ))
+        );
+
+        // About another tarball
+        assert!(Signatures::verify_attestation(
+            &keys,
+            &attestation(&signed),
+            "@scope/aa@1.0.0",
+            INTEGRITY
+        )
+        .is_err_and(|reason| reason.contains("is about another tarball")));
+    }
+}
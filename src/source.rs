@@ -0,0 +1,758 @@
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering::SeqCst},
+};
+
+use flate2::read::GzDecoder;
+use lazy_static::lazy_static;
+use log::debug;
+use semver::{BuildMetadata, Version};
+use serde_json::{json, Value};
+use tar::Archive;
+
+use crate::{
+    config::Config,
+    errors::CommandError,
+    http::{HTTPRequest, NetworkMode},
+    installer::Installer,
+    integrity::Integrity,
+    manifest::Manifest,
+    pack::Pack,
+    scripts::Scripts,
+    tarball_cache::TarballCache,
+    types::{Lockfile, VersionData},
+    util::TaskAllocator,
+    versions::{VersionRange, Versions, LATEST},
+};
+
+const PREPARE_SCRIPT: &str = "prepare"; // Builds a package from git, as its repository is not what would be published
+const NO_MANIFEST: &str = "it has no package.json";
+const SHORT_HASH_LENGTH: usize = 7; // Of the commit or the shasum of the tarball in the version of a package
+
+// Hosts that can be named with a shorthand such as `github:user/repo`, a shorthand without one is on GitHub
+const GIT_HOSTS: [(&str, &str); 3] = [
+    ("github", "https://github.com"),
+    ("gitlab", "https://gitlab.com"),
+    ("bitbucket", "https://bitbucket.org"),
+];
+
+lazy_static! {
+    // Repositories are cloned here to be packed, each into its own directory that is removed afterwards
+    static ref GIT_DIRECTORY: PathBuf = dirs::cache_dir()
+        .expect("Failed to find cache directory")
+        .join("click-git");
+
+    // The version data of each source fetched by this install, so that one depended on more than once is only fetched once
+    static ref FETCHED: tokio::sync::Mutex<HashMap<String, Value>> =
+        tokio::sync::Mutex::new(HashMap::new());
+}
+
+static CLONES: AtomicUsize = AtomicUsize::new(0); // Names the directory of each clone
+
+/// Where a package is installed from, told apart by the form of the range it is depended on with. Packages from
+/// anywhere but the registry are packed into a tarball the same way `click pack` does and put in the tarball cache,
+/// so from then on they are extracted, locked and linked the same way as packages from the registry. The exception is
+/// a linked directory in package.json, which is linked into node_modules as it is, see InstallHandler::link_local().
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Registry(Option<VersionRange>), // The range the version is resolved from, None for the latest version
+    Alias(String, Option<VersionRange>), // Another package from the registry by its name and range, `npm:lodash@^3`
+    Git(GitSource),
+    Tarball(String), // A URL to a gzipped tarball, such as `https://example.com/package.tgz`
+    File(PathBuf), // A directory relative to the project, such as `file:../my-lib`, installed as a copy
+    Link(PathBuf), // Such as `link:../my-lib`, a dependency of another package is installed as a copy instead
+    Workspace(WorkspaceRange), // Another workspace of the project, such as `workspace:^`, which is linked instead
+}
+
+/// The range of a dependency on another workspace, which the version of that workspace has to match. `*`, `^` and `~`
+/// match whatever its version is, and are replaced by it when the package is packed, as `1.2.3`, `^1.2.3` and `~1.2.3`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WorkspaceRange {
+    Any,   // `workspace:*`
+    Caret, // `workspace:^`
+    Tilde, // `workspace:~`
+    Range(VersionRange),
+}
+
+/// A package in a git repository, such as `git+https://github.com/user/repo#semver:^1.2` or `user/repo#branch`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GitSource {
+    url: String,                  // What is cloned, without the `git+` prefix
+    committish: Option<String>,   // A branch, tag or commit, the default branch if None
+    semver: Option<VersionRange>, // Given as `#semver:<range>`, the highest tag in the range is used
+}
+
+impl Source {
+    /// Returns None for a range or dist-tag, which are resolved from the registry, see Installer::parse_source().
+    pub fn from_spec(spec: &str) -> Option<Self> {
+        // A range that cannot be parsed is left to be reported as an invalid range, dist-tags are not supported
+        if let Some(aliased) = spec.strip_prefix("npm:") {
+            let (package_name, range) = Versions::parse_raw_package_details(aliased.to_string());
+            let semantic_version = match range == LATEST {
+                true => None,
+                false => Some(Versions::parse_semantic_version(&range).ok()?),
+            };
+            return Some(Self::Alias(package_name, semantic_version));
+        }
+
+        if let Some(path) = spec.strip_prefix("file:") {
+            return Some(Self::File(PathBuf::from(path)));
+        }
+
+        if let Some(path) = spec.strip_prefix("link:") {
+            return Some(Self::Link(PathBuf::from(path)));
+        }
+
+        if let Some(range) = spec.strip_prefix("workspace:") {
+            return WorkspaceRange::parse(range).map(Self::Workspace);
+        }
+
+        if spec.starts_with("https://") || spec.starts_with("http://") {
+            return Some(Self::Tarball(spec.to_string()));
+        }
+
+        GitSource::parse(spec).map(Self::Git)
+    }
+
+    /// The key the package is recorded under in the lockfile, formatted as package@range.
+    pub fn spec(&self, package_name: &String) -> String {
+        match self {
+            Self::Registry(semantic_version) => {
+                Lockfile::spec(package_name, semantic_version.as_ref())
+            }
+            source => format!("{package_name}@{source}"),
+        }
+    }
+
+    /// The range of a package from the registry, None for the latest version and for any other source.
+    pub fn semantic_version(&self) -> Option<&VersionRange> {
+        match self {
+            Self::Registry(semantic_version) | Self::Alias(_, semantic_version) => {
+                semantic_version.as_ref()
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the package is resolved from the registry, under its own name or an alias.
+    pub fn is_registry(&self) -> bool {
+        matches!(self, Self::Registry(_) | Self::Alias(..))
+    }
+
+    pub fn is_tarball(&self) -> bool {
+        matches!(self, Self::Tarball(_))
+    }
+
+    pub fn is_workspace(&self) -> bool {
+        matches!(self, Self::Workspace(_))
+    }
+
+    /// The version data of the package, a package from any other source than the registry is fetched and its tarball
+    /// added to the tarball cache. Its version has the commit it was fetched at or the shasum of its tarball as build
+    /// metadata, such as `1.2.0+git.1a2b3c4`, so that it is never mistaken for the same version from the registry.
+    pub async fn version_data(
+        &self,
+        client: reqwest::Client,
+        package_name: &str,
+        full_version: Option<&String>,
+        network_mode: NetworkMode,
+    ) -> Result<VersionData, CommandError> {
+        match self {
+            Self::Registry(semantic_version) => {
+                return Installer::get_version_data(
+                    client,
+                    package_name,
+                    full_version,
+                    semantic_version.as_ref(),
+                    network_mode,
+                )
+                .await;
+            }
+            // The package is installed under the alias, so it is cached and locked under it too
+            Self::Alias(registry_name, semantic_version) => {
+                let full_version = full_version
+                    .cloned()
+                    .or_else(|| Versions::resolve_full_version(semantic_version.as_ref()));
+                let mut version_data = Installer::get_version_data(
+                    client,
+                    registry_name,
+                    full_version.as_ref(),
+                    semantic_version.as_ref(),
+                    network_mode,
+                )
+                .await?;
+
+                if !package_name.is_empty() {
+                    version_data.name = package_name.to_string();
+                }
+                return Ok(version_data);
+            }
+            // Workspaces are linked by InstallHandler, so a range of them anywhere else has nothing to install from
+            Self::Workspace(_) => {
+                return Err(CommandError::InvalidPackageSource(
+                    format!("{package_name}@{self}"),
+                    String::from("only workspaces of a project can depend on each other with it"),
+                ));
+            }
+            // Only a directory on disk can be packed when offline
+            Self::Git(_) | Self::Tarball(_) if network_mode == NetworkMode::Offline => {
+                return Err(CommandError::NotInOfflineCache(format!(
+                    "{package_name}@{self}"
+                )));
+            }
+            _ => {}
+        }
+
+        let mut fetched = FETCHED.lock().await;
+        let key = self.to_string();
+        let mut version_data = match fetched.get(&key) {
+            Some(version_data) => version_data.clone(),
+            None => {
+                let source = self.clone();
+                let (version_data, tarball) = match self {
+                    Self::Tarball(url) => {
+                        let tarball = HTTPRequest::get_bytes(client, url.to_string()).await?;
+                        let url = url.to_string();
+                        TaskAllocator::add_blocking(move || {
+                            Self::read_tarball(url, tarball.to_vec())
+                        })
+                        .await
+                    }
+                    _ => TaskAllocator::add_blocking(move || source.fetch()).await,
+                }
+                .expect("Failed to join fetch task")?;
+                Self::cache_tarball(&version_data, &tarball).await?;
+
+                fetched.insert(key, version_data.clone());
+                version_data
+            }
+        };
+
+        // The package is installed under the name it is depended on with, as npm does
+        if !package_name.is_empty() {
+            version_data["name"] = Value::from(package_name);
+        }
+
+        serde_json::from_value(version_data).map_err(CommandError::FailedToParseManifest)
+    }
+
+    /// Fetches the package and packs it, see version_data(). Blocks until it is packed.
+    fn fetch(&self) -> Result<(Value, Vec<u8>), CommandError> {
+        match self {
+            Self::Git(git) => git.fetch(),
+            Self::File(path) | Self::Link(path) => Self::pack(path, self.to_string(), |tarball| {
+                format!("file.{}", Self::short_hash(tarball))
+            }),
+            Self::Registry(_) | Self::Alias(..) | Self::Tarball(_) => {
+                unreachable!("Tarballs are downloaded")
+            }
+            Self::Workspace(_) => unreachable!("Workspaces are linked"),
+        }
+    }
+
+    /// Packs a directory the same way `click pack` does. Returns the version data from its package.json, with the
+    /// tarball as its `dist` resolved to where it was packed from, and the tarball. The version is given the build
+    /// metadata made from the tarball.
+    fn pack(
+        directory: &Path,
+        resolved: String,
+        build: impl FnOnce(&[u8]) -> String,
+    ) -> Result<(Value, Vec<u8>), CommandError> {
+        let manifest = Manifest::read_from(&directory.join("package.json"))?.ok_or_else(|| {
+            CommandError::InvalidPackageSource(resolved.to_string(), NO_MANIFEST.to_string())
+        })?;
+        let files = Pack::files(directory, &manifest)?;
+        let tarball = Pack::tarball(directory, &files, None)?;
+
+        let manifest_raw = fs::read_to_string(directory.join("package.json"))
+            .map_err(CommandError::FailedToReadFile)?;
+        let unpacked_size = files.iter().map(|file| file.size).sum();
+        let build = build(&tarball);
+        let version_data =
+            Self::describe(&manifest_raw, resolved, &tarball, &build, unpacked_size)?;
+
+        Ok((version_data, tarball))
+    }
+
+    /// Reads the package.json of a tarball downloaded from a URL, which is in its top directory. Returns its version
+    /// data the same way as pack(), with the shasum of the tarball as build metadata.
+    fn read_tarball(url: String, tarball: Vec<u8>) -> Result<(Value, Vec<u8>), CommandError> {
+        let mut archive = Archive::new(GzDecoder::new(&tarball[..]));
+
+        let mut manifest_raw = None;
+        let mut unpacked_size = 0;
+        for entry in archive.entries().map_err(CommandError::ExtractionFailed)? {
+            let mut entry = entry.map_err(CommandError::ExtractionFailed)?;
+            unpacked_size += entry.size();
+
+            let path = entry.path().map_err(CommandError::ExtractionFailed)?;
+            let is_manifest = path.components().count() == 2 && path.ends_with("package.json");
+            if is_manifest && manifest_raw.is_none() {
+                let mut contents = String::new();
+                entry
+                    .read_to_string(&mut contents)
+                    .map_err(CommandError::ExtractionFailed)?;
+                manifest_raw = Some(contents);
+            }
+        }
+
+        let Some(manifest_raw) = manifest_raw else {
+            return Err(CommandError::InvalidPackageSource(
+                url,
+                NO_MANIFEST.to_string(),
+            ));
+        };
+
+        let build = format!("tarball.{}", Self::short_hash(&tarball));
+        let version_data = Self::describe(&manifest_raw, url, &tarball, &build, unpacked_size)?;
+        Ok((version_data, tarball))
+    }
+
+    /// The version data of a package from its package.json, with the build metadata added to its version and its
+    /// `dist` describing the tarball it was packed into.
+    fn describe(
+        manifest_raw: &str,
+        resolved: String,
+        tarball: &[u8],
+        build: &str,
+        unpacked_size: u64,
+    ) -> Result<Value, CommandError> {
+        let mut version_data = serde_json::from_str::<Value>(manifest_raw)
+            .map_err(CommandError::FailedToParseManifest)?;
+
+        let Some(mut version) = version_data["version"]
+            .as_str()
+            .and_then(|version| Version::from_str(version).ok())
+        else {
+            return Err(CommandError::InvalidPackageSource(
+                resolved,
+                String::from("its package.json has no valid version"),
+            ));
+        };
+        version.build = BuildMetadata::new(build).expect("A hash is valid build metadata");
+
+        version_data["version"] = Value::from(version.to_string());
+        version_data["dist"] = json!({
+            "tarball": resolved,
+            "integrity": Integrity::sha512(tarball),
+            "shasum": Integrity::shasum(tarball),
+            "unpackedSize": unpacked_size,
+        });
+
+        Ok(version_data)
+    }
+
+    fn short_hash(tarball: &[u8]) -> String {
+        Integrity::shasum(tarball)[..SHORT_HASH_LENGTH].to_string()
+    }
+
+    /// Adds the packed tarball to the tarball cache, where the installer finds it as if it had been downloaded.
+    async fn cache_tarball(version_data: &Value, tarball: &[u8]) -> Result<(), CommandError> {
+        let dist = serde_json::from_value(version_data["dist"].clone())
+            .map_err(CommandError::FailedToParseManifest)?;
+
+        let write = async {
+            if let Some(mut writer) = TarballCache::create(&dist).await? {
+                writer.write(tarball).await?;
+                writer.finish().await?;
+            }
+            Ok::<_, std::io::Error>(())
+        };
+
+        write.await.map_err(CommandError::FailedToWriteFile)
+    }
+}
+
+impl fmt::Display for Source {
+    /// The range as it is recorded in the lockfile, git URLs are written out in full.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Registry(Some(semantic_version)) => write!(f, "{semantic_version}"),
+            Self::Registry(None) => write!(f, "{LATEST}"),
+            Self::Git(git) => write!(f, "{git}"),
+            Self::Alias(package_name, semantic_version) => {
+                let spec = Lockfile::spec(package_name, semantic_version.as_ref());
+                write!(f, "npm:{spec}")
+            }
+            Self::Tarball(url) => write!(f, "{url}"),
+            Self::File(path) => write!(f, "file:{}", path.display()),
+            Self::Link(path) => write!(f, "link:{}", path.display()),
+            Self::Workspace(range) => write!(f, "workspace:{range}"),
+        }
+    }
+}
+
+impl WorkspaceRange {
+    /// Returns None if the range cannot be parsed, an empty range is the same as `*`.
+    fn parse(range: &str) -> Option<Self> {
+        match range {
+            "" | "*" => Some(Self::Any),
+            "^" => Some(Self::Caret),
+            "~" => Some(Self::Tilde),
+            range => Versions::parse_semantic_version(range)
+                .ok()
+                .map(Self::Range),
+        }
+    }
+
+    pub fn matches(&self, version: &Version) -> bool {
+        match self {
+            Self::Range(semantic_version) => semantic_version.matches(version),
+            _ => true,
+        }
+    }
+
+    /// The range the dependency is published with, given the version of the workspace it refers to.
+    pub fn published(&self, version: &str) -> String {
+        match self {
+            Self::Any => version.to_string(),
+            Self::Caret => format!("^{version}"),
+            Self::Tilde => format!("~{version}"),
+            Self::Range(semantic_version) => semantic_version.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for WorkspaceRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "*"),
+            Self::Caret => write!(f, "^"),
+            Self::Tilde => write!(f, "~"),
+            Self::Range(semantic_version) => write!(f, "{semantic_version}"),
+        }
+    }
+}
+
+impl GitSource {
+    /// Returns None if the spec is not a git URL or a shorthand for one.
+    fn parse(spec: &str) -> Option<Self> {
+        let (location, fragment) = match spec.split_once('#') {
+            Some((location, fragment)) => (location, Some(fragment)),
+            None => (spec, None),
+        };
+
+        let url = if let Some(url) = location.strip_prefix("git+") {
+            url.to_string()
+        } else if location.starts_with("git://") {
+            location.to_string()
+        } else if let Some((host, path)) = location.split_once(':') {
+            let (_, host_url) = GIT_HOSTS.iter().find(|(name, _)| *name == host)?;
+            Self::hosted_url(host_url, path)?
+        } else if Self::is_shorthand(location) {
+            Self::hosted_url(GIT_HOSTS[0].1, location)?
+        } else {
+            return None;
+        };
+
+        let mut source = Self {
+            url,
+            committish: None,
+            semver: None,
+        };
+
+        if let Some(fragment) = fragment.filter(|fragment| !fragment.is_empty()) {
+            match fragment.strip_prefix("semver:") {
+                Some(range) => source.semver = Some(Versions::parse_semantic_version(range).ok()?),
+                None => source.committish = Some(fragment.to_string()),
+            }
+        }
+
+        Some(source)
+    }
+
+    /// `user/repo`, which a range or the name of a package never looks like.
+    fn is_shorthand(location: &str) -> bool {
+        let mut parts = location.split('/');
+        let (Some(user), Some(repository), None) = (parts.next(), parts.next(), parts.next())
+        else {
+            return false;
+        };
+
+        [user, repository].iter().all(|part| {
+            !part.is_empty()
+                && !part.starts_with(['.', '@', '~'])
+                && part
+                    .chars()
+                    .all(|char| char.is_ascii_alphanumeric() || matches!(char, '-' | '_' | '.'))
+        })
+    }
+
+    fn hosted_url(host_url: &str, path: &str) -> Option<String> {
+        let path = path.trim_end_matches(".git");
+        Self::is_shorthand(path).then(|| format!("{host_url}/{path}.git"))
+    }
+
+    /// The URL a package fetched at a commit is locked to, so that installing it again fetches the same commit.
+    fn resolved(&self, commit: &str) -> String {
+        let source = Self {
+            url: self.url.to_string(),
+            committish: Some(commit.to_string()),
+            semver: None,
+        };
+
+        source.to_string()
+    }
+
+    /// Clones the repository at the commit the spec resolves to, runs its prepare script and packs it. Returns the version
+    /// data of the package from its package.json, with the tarball as its `dist`, and the tarball. Blocks until git is done.
+    fn fetch(&self) -> Result<(Value, Vec<u8>), CommandError> {
+        fs::create_dir_all(&*GIT_DIRECTORY).map_err(CommandError::FailedToCreateFile)?;
+
+        let directory = GIT_DIRECTORY.join(format!(
+            "{}-{}",
+            std::process::id(),
+            CLONES.fetch_add(1, SeqCst)
+        ));
+        if directory.exists() {
+            fs::remove_dir_all(&directory).map_err(CommandError::FailedToWriteFile)?;
+        }
+
+        let result = self.fetch_into(&directory);
+        let _ = fs::remove_dir_all(&directory);
+
+        result
+    }
+
+    fn fetch_into(&self, directory: &Path) -> Result<(Value, Vec<u8>), CommandError> {
+        debug!("cloning {}", self.url);
+        let clone_target = directory.to_string_lossy().to_string();
+        self.git(
+            &GIT_DIRECTORY,
+            &["clone", "--quiet", &self.url, &clone_target],
+        )?;
+
+        let commit = self.resolve_commit(directory)?;
+        self.git(directory, &["checkout", "--quiet", "--detach", &commit])?;
+
+        let has_prepare =
+            Manifest::read_from(&directory.join("package.json"))?.is_some_and(|manifest| {
+                Scripts::scripts(&manifest)
+                    .iter()
+                    .any(|(name, _)| name == PREPARE_SCRIPT)
+            });
+        if has_prepare {
+            self.prepare(directory)?;
+        }
+
+        Source::pack(directory, self.resolved(&commit), |_| {
+            format!("git.{}", &commit[..SHORT_HASH_LENGTH])
+        })
+    }
+
+    /// The highest tag in the range for `#semver:`, the branch, tag or commit given, or the default branch.
+    fn resolve_commit(&self, directory: &Path) -> Result<String, CommandError> {
+        let revision = match (&self.semver, &self.committish) {
+            (Some(range), _) => {
+                let tags = self.git(directory, &["tag", "--list"])?;
+                let highest = tags
+                    .lines()
+                    .filter_map(|tag| {
+                        let version = Version::from_str(tag.trim_start_matches('v')).ok()?;
+                        range.matches(&version).then_some((version, tag))
+                    })
+                    .max_by(|(a, _), (b, _)| a.cmp(b));
+
+                match highest {
+                    Some((_, tag)) => tag.to_string(),
+                    None => return Err(CommandError::GitRefNotFound(self.to_string())),
+                }
+            }
+            // A branch is found as the branch of the remote, which is where the clone has every branch but the default
+            (None, Some(committish)) => {
+                let remote_branch = format!("origin/{committish}^{{commit}}");
+                match self.git(
+                    directory,
+                    &["rev-parse", "--verify", "--quiet", &remote_branch],
+                ) {
+                    Ok(commit) => return Ok(commit),
+                    Err(_) => committish.to_string(),
+                }
+            }
+            (None, None) => String::from("HEAD"),
+        };
+
+        self.git(
+            directory,
+            &[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("{revision}^{{commit}}"),
+            ],
+        )
+        .map_err(|_| CommandError::GitRefNotFound(self.to_string()))
+    }
+
+    /// Installs the dependencies of the repository with click itself and runs its prepare script, the same as npm
+    /// does before packing a package from git.
+    fn prepare(&self, directory: &Path) -> Result<(), CommandError> {
+        let click = env::current_exe().map_err(CommandError::ComandFailedError)?;
+        let status = Command::new(click)
+            .args(["install", "--quiet", "--registry", &Config::get()?.registry])
+            .current_dir(directory)
+            .stdin(Stdio::null())
+            .status()
+            .map_err(CommandError::ComandFailedError)?;
+
+        if !status.success() {
+            return Err(self.failed("its dependencies failed to install"));
+        }
+
+        let (_, script) = Scripts::scripts(
+            &Manifest::read_from(&directory.join("package.json"))?.unwrap_or_default(),
+        )
+        .into_iter()
+        .find(|(name, _)| name == PREPARE_SCRIPT)
+        .unwrap_or_default();
+
+        let bin_path = directory.join("node_modules").join(".bin");
+        let mut paths = vec![bin_path];
+        if let Some(path) = env::var_os("PATH") {
+            paths.extend(env::split_paths(&path));
+        }
+        let path = env::join_paths(paths).map_err(|err| {
+            CommandError::ComandFailedError(std::io::Error::other(err.to_string()))
+        })?;
+
+        let status = Scripts::shell_command(&script)
+            .current_dir(directory)
+            .env("PATH", path)
+            .env("npm_lifecycle_event", PREPARE_SCRIPT)
+            .stdin(Stdio::null())
+            .status()
+            .map_err(CommandError::ComandFailedError)?;
+
+        if !status.success() {
+            return Err(CommandError::LifecycleScriptFailed {
+                package: self.to_string(),
+                script: PREPARE_SCRIPT.to_string(),
+                code: status.code().unwrap_or(1),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Runs git in a directory, returning what it printed. It is never left waiting for a password.
+    fn git(&self, directory: &Path, args: &[&str]) -> Result<String, CommandError> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(directory)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .stdin(Stdio::null())
+            .output()
+            .map_err(CommandError::FailedToRunGit)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let detail = stderr.lines().last().unwrap_or("it exited with an error");
+            return Err(self.failed(detail.trim()));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn failed(&self, detail: &str) -> CommandError {
+        CommandError::GitFailed(self.url.to_string(), detail.to_string())
+    }
+}
+
+impl fmt::Display for GitSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.url.starts_with("git://") {
+            true => write!(f, "{}", self.url)?,
+            false => write!(f, "git+{}", self.url)?,
+        }
+
+        match (&self.semver, &self.committish) {
+            (Some(range), _) => write!(f, "#semver:{range}"),
+            (None, Some(committish)) => write!(f, "#{committish}"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_git_specs() {
+        let source = GitSource::parse("user/repo#main").expect("Valid shorthand");
+        assert_eq!(source.url, "https://github.com/user/repo.git");
+        assert_eq!(source.committish.as_deref(), Some("main"));
+
+        let source = GitSource::parse("git+https://github.com/user/repo#semver:^1.2")
+            .expect("Valid git URL");
+        assert_eq!(source.url, "https://github.com/user/repo");
+        assert!(source.semver.is_some());
+
+        let source = GitSource::parse("gitlab:user/repo").expect("Valid hosted shorthand");
+        assert_eq!(source.url, "https://gitlab.com/user/repo.git");
+
+        assert_eq!(GitSource::parse("^1.2.0"), None);
+        assert_eq!(GitSource::parse("@scope/package"), None);
+        assert_eq!(GitSource::parse("git+https://host/repo#semver:nope"), None);
+    }
+
+    #[test]
+    fn parses_protocol_specs() {
+        let source = Source::from_spec("file:../my-lib");
+        assert_eq!(source, Some(Source::File(PathBuf::from("../my-lib"))));
+
+        let source = Source::from_spec("link:../my-lib").expect("Valid link");
+        assert_eq!(
+            source.spec(&String::from("my-lib")),
+            "my-lib@link:../my-lib"
+        );
+
+        let source = Source::from_spec("npm:@scope/real@^3").expect("Valid alias");
+        assert_eq!(
+            source.spec(&String::from("alias")),
+            "alias@npm:@scope/real@^3"
+        );
+        assert!(source.is_registry());
+
+        assert_eq!(
+            Source::from_spec("workspace:^"),
+            Some(Source::Workspace(WorkspaceRange::Caret))
+        );
+        let source = Source::from_spec("workspace:^1.2.0").expect("Valid workspace range");
+        assert_eq!(source.to_string(), "workspace:^1.2.0");
+        assert_eq!(
+            Source::from_spec("workspace:"),
+            Source::from_spec("workspace:*")
+        );
+        assert_eq!(Source::from_spec("workspace:not a range"), None);
+
+        assert_eq!(Source::from_spec("npm:real@not a range"), None);
+        assert_eq!(Source::from_spec("^1.0.0"), None);
+    }
+
+    #[test]
+    fn matches_and_publishes_workspace_ranges() {
+        let version = Version::parse("1.4.0").expect("Valid version");
+
+        assert!(WorkspaceRange::Caret.matches(&version));
+        assert_eq!(WorkspaceRange::Caret.published("1.4.0"), "^1.4.0");
+        assert_eq!(WorkspaceRange::Tilde.published("1.4.0"), "~1.4.0");
+        assert_eq!(WorkspaceRange::Any.published("1.4.0"), "1.4.0");
+
+        let Some(Source::Workspace(range)) = Source::from_spec("workspace:^1.2.0") else {
+            panic!("Expected a workspace range");
+        };
+        assert!(range.matches(&version));
+        assert_eq!(range.published("1.4.0"), "^1.2.0");
+
+        let Some(Source::Workspace(range)) = Source::from_spec("workspace:^2") else {
+            panic!("Expected a workspace range");
+        };
+        assert!(!range.matches(&version));
+    }
+}
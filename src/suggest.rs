@@ -0,0 +1,112 @@
+/// Finds what was probably meant by a name that does not exist, such as a misspelled command or package.
+pub struct Suggestions;
+
+const MAX_SUGGESTIONS: usize = 3;
+
+impl Suggestions {
+    /// The number of characters that have to be inserted, removed, replaced or swapped with the next to turn one name
+    /// into the other. Swapped characters count once, as they are the most common typo.
+    pub fn distance(a: &str, b: &str) -> usize {
+        let a = a.chars().collect::<Vec<_>>();
+        let b = b.chars().collect::<Vec<_>>();
+
+        // Each row holds the distances from a prefix of `a` to every prefix of `b`
+        let mut before_previous = vec![0; b.len() + 1];
+        let mut previous = (0..=b.len()).collect::<Vec<_>>();
+        for i in 1..=a.len() {
+            let mut current = vec![i; b.len() + 1];
+            for j in 1..=b.len() {
+                let cost = usize::from(a[i - 1] != b[j - 1]);
+                current[j] = (previous[j] + 1)
+                    .min(current[j - 1] + 1)
+                    .min(previous[j - 1] + cost);
+
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                    current[j] = current[j].min(before_previous[j - 2] + 1);
+                }
+            }
+
+            before_previous = previous;
+            previous = current;
+        }
+
+        previous[b.len()]
+    }
+
+    /// The candidates close enough to the name to be what was meant, closest first. The longer the name, the more
+    /// typos it is allowed, a third of its length and at least one.
+    pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        let name = name.to_lowercase();
+        let allowed = (name.chars().count() / 3).max(1);
+
+        let mut close = candidates
+            .into_iter()
+            .filter(|candidate| *candidate != name)
+            .map(|candidate| (Self::distance(&name, &candidate.to_lowercase()), candidate))
+            .filter(|(distance, _)| *distance <= allowed)
+            .collect::<Vec<_>>();
+        close.sort();
+        close.dedup();
+
+        close
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, candidate)| candidate.to_string())
+            .collect()
+    }
+
+    /// Formats suggestions to follow an error message, such as `, did you mean 'install'?`, or nothing if there are none.
+    pub fn hint(suggestions: &[String]) -> String {
+        let quoted = suggestions
+            .iter()
+            .map(|suggestion| format!("'{suggestion}'"))
+            .collect::<Vec<_>>();
+
+        match quoted.split_last() {
+            None => String::new(),
+            Some((last, [])) => format!(", did you mean {last}?"),
+            Some((last, rest)) => format!(", did you mean {} or {last}?", rest.join(", ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_edits_and_swaps() {
+        assert_eq!(Suggestions::distance("install", "install"), 0);
+        assert_eq!(Suggestions::distance("instal", "install"), 1);
+        assert_eq!(Suggestions::distance("isntall", "install"), 1);
+        assert_eq!(Suggestions::distance("", "du"), 2);
+        assert_eq!(Suggestions::distance("lodahs", "lodash"), 1);
+        assert_eq!(Suggestions::distance("react", "preact"), 1);
+    }
+
+    #[test]
+    fn suggests_closest_candidates_first() {
+        let commands = ["install", "init", "info", "uninstall", "list"];
+
+        assert_eq!(Suggestions::closest("instal", commands), vec!["install"]);
+        assert_eq!(Suggestions::closest("inf", commands), vec!["info"]);
+        assert_eq!(
+            Suggestions::closest("unistall", commands),
+            vec!["uninstall", "install"]
+        );
+        assert!(Suggestions::closest("publish", commands).is_empty());
+    }
+
+    #[test]
+    fn formats_hint() {
+        assert_eq!(Suggestions::hint(&[]), "");
+        assert_eq!(
+            Suggestions::hint(&[String::from("install")]),
+            ", did you mean 'install'?"
+        );
+        assert_eq!(
+            Suggestions::hint(&[String::from("a"), String::from("b"), String::from("c")]),
+            ", did you mean 'a', 'b' or 'c'?"
+        );
+    }
+}
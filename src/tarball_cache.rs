@@ -0,0 +1,245 @@
+use std::{collections::HashSet, io::ErrorKind, path::PathBuf};
+
+use bytes::Bytes;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tokio::{
+    fs::{self, OpenOptions},
+    io::AsyncWriteExt,
+};
+
+use crate::{integrity::Integrity, types::Dist};
+
+const PARTIAL_EXTENSION: &str = "partial"; // A download that was cut off, which the next one carries on from
+
+lazy_static! {
+    // Shared by every project, unlike node_modules, so a tarball is only ever downloaded once
+    pub static ref TARBALL_DIRECTORY: PathBuf = dirs::home_dir()
+        .expect("Failed to find home directory")
+        .join(".click/cache");
+}
+
+/// A tarball being added to the cache while it is downloaded, see TarballCache::create().
+pub struct TarballWriter {
+    file: fs::File,
+    temporary_path: PathBuf,
+    path: PathBuf,
+}
+
+impl TarballWriter {
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<(), std::io::Error> {
+        self.file.write_all(chunk).await
+    }
+
+    /// Should only be called once the tarball has been checked against its integrity.
+    pub async fn finish(mut self) -> Result<(), std::io::Error> {
+        self.file.flush().await?;
+        fs::rename(&self.temporary_path, &self.path).await
+    }
+
+    /// Removes what has been written, for a download that failed or did not match its integrity.
+    pub async fn discard(self) {
+        drop(self.file);
+        let _ = fs::remove_file(&self.temporary_path).await;
+    }
+
+    /// Keeps what has been written of a download that was cut off, so that TarballCache::resume() can carry on from it.
+    pub async fn keep_partial(mut self) -> Result<(), std::io::Error> {
+        self.file.flush().await?;
+        fs::rename(
+            &self.temporary_path,
+            self.path.with_extension(PARTIAL_EXTENSION),
+        )
+        .await
+    }
+}
+
+/// The outcome of checking every tarball in the cache, see TarballCache::verify().
+#[derive(Default, Serialize)]
+pub struct VerifyOutcome {
+    pub verified: usize,
+    pub removed: Vec<String>, // Entries whose contents no longer match the hash they are stored under
+    pub reclaimed: u64,       // Bytes taken by the removed entries
+}
+
+/// Downloaded tarballs are stored by their hash as `<algorithm>/<hex digest>`, so that any install of the same
+/// contents can use them without asking the registry, whichever project or registry it is from.
+pub struct TarballCache;
+impl TarballCache {
+    /// Returns None if the tarball is not cached. An entry that does not match the hash it is stored under is removed,
+    /// so that it is downloaded again rather than failing every install that needs it.
+    pub async fn read(dist: &Dist) -> Option<Bytes> {
+        let path = Self::path(dist)?;
+        let bytes = Bytes::from(fs::read(&path).await.ok()?);
+
+        if !Integrity::verify(&bytes, dist) {
+            let _ = fs::remove_file(&path).await;
+            return None;
+        }
+
+        Some(bytes)
+    }
+
+    /// Opens a cached tarball so that it can be read as a stream. Returns None if it is not cached.
+    /// As it is never read whole, whoever reads it has to check it and remove it if it has been corrupted.
+    pub async fn open(dist: &Dist) -> Option<fs::File> {
+        fs::File::open(Self::path(dist)?).await.ok()
+    }
+
+    pub async fn remove(dist: &Dist) -> Result<(), std::io::Error> {
+        match Self::path(dist) {
+            Some(path) => fs::remove_file(path).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Starts writing a tarball to the cache as it is downloaded. Returns None if the registry provided no hash to store
+    /// the tarball under. The entry is written to a temporary file, which only replaces it with TarballWriter::finish().
+    pub async fn create(dist: &Dist) -> Result<Option<TarballWriter>, std::io::Error> {
+        let Some(path) = Self::path(dist) else {
+            return Ok(None);
+        };
+        let temporary_path = path.with_extension(format!("{}.tmp", std::process::id()));
+
+        if let Some(parent) = path.parent() {
+            match fs::create_dir_all(parent).await {
+                Err(err) if err.kind() != ErrorKind::AlreadyExists => return Err(err),
+                _ => {}
+            }
+        }
+
+        let file = fs::File::create(&temporary_path).await?;
+        Ok(Some(TarballWriter {
+            file,
+            temporary_path,
+            path,
+        }))
+    }
+
+    /// Takes over the partial download of a tarball, returning a writer that appends to it along with the part that has
+    /// already been downloaded and its length. Returns None if there is no partial download.
+    /// The partial file is renamed before it is used, so two installs never append to the same one.
+    pub async fn resume(
+        dist: &Dist,
+    ) -> Result<Option<(TarballWriter, fs::File, u64)>, std::io::Error> {
+        let Some(path) = Self::path(dist) else {
+            return Ok(None);
+        };
+        let temporary_path = path.with_extension(format!("{}.tmp", std::process::id()));
+
+        match fs::rename(path.with_extension(PARTIAL_EXTENSION), &temporary_path).await {
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            result => result?,
+        }
+
+        let partial = fs::File::open(&temporary_path).await?;
+        let length = partial.metadata().await?.len();
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&temporary_path)
+            .await?;
+
+        Ok(Some((
+            TarballWriter {
+                file,
+                temporary_path,
+                path,
+            },
+            partial,
+            length,
+        )))
+    }
+
+    /// Rehashes every cached tarball and removes those that have been corrupted.
+    pub async fn verify() -> Result<VerifyOutcome, std::io::Error> {
+        let mut outcome = VerifyOutcome::default();
+
+        let mut algorithms = match fs::read_dir(&*TARBALL_DIRECTORY).await {
+            Ok(algorithms) => algorithms,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(outcome),
+            Err(err) => return Err(err),
+        };
+
+        while let Some(algorithm_entry) = algorithms.next_entry().await? {
+            if !algorithm_entry.file_type().await?.is_dir() {
+                continue;
+            }
+
+            let algorithm = algorithm_entry.file_name().to_string_lossy().to_string();
+            let mut entries = fs::read_dir(algorithm_entry.path()).await?;
+
+            while let Some(entry) = entries.next_entry().await? {
+                let digest = entry.file_name().to_string_lossy().to_string();
+                let bytes = fs::read(entry.path()).await?;
+
+                // Temporary files left by an interrupted write are not named after their digest either
+                if Integrity::hex_digest(&algorithm, &bytes).as_ref() == Some(&digest) {
+                    outcome.verified += 1;
+                    continue;
+                }
+
+                fs::remove_file(entry.path()).await?;
+                outcome.removed.push(format!("{algorithm}/{digest}"));
+                outcome.reclaimed += bytes.len() as u64;
+            }
+        }
+
+        outcome.removed.sort();
+        Ok(outcome)
+    }
+
+    /// Removes every cached tarball that is not in `referenced`, each formatted as `<algorithm>/<hex digest>`.
+    /// Returns the removed entries and the bytes they took. A partial download is kept along with its tarball.
+    pub async fn remove_unreferenced(
+        referenced: &HashSet<String>,
+    ) -> Result<(Vec<String>, u64), std::io::Error> {
+        let mut removed = Vec::new();
+        let mut reclaimed = 0;
+
+        let mut algorithms = match fs::read_dir(&*TARBALL_DIRECTORY).await {
+            Ok(algorithms) => algorithms,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok((removed, reclaimed)),
+            Err(err) => return Err(err),
+        };
+
+        while let Some(algorithm_entry) = algorithms.next_entry().await? {
+            if !algorithm_entry.file_type().await?.is_dir() {
+                continue;
+            }
+
+            let algorithm = algorithm_entry.file_name().to_string_lossy().to_string();
+            let mut entries = fs::read_dir(algorithm_entry.path()).await?;
+
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let digest = name.split('.').next().unwrap_or_default();
+                if referenced.contains(&format!("{algorithm}/{digest}")) {
+                    continue;
+                }
+
+                reclaimed += entry.metadata().await?.len();
+                fs::remove_file(entry.path()).await?;
+                removed.push(format!("{algorithm}/{name}"));
+            }
+        }
+
+        removed.sort();
+        Ok((removed, reclaimed))
+    }
+
+    /// Removes every cached tarball. Returns false if there was nothing to remove.
+    pub async fn clean() -> Result<bool, std::io::Error> {
+        match fs::remove_dir_all(&*TARBALL_DIRECTORY).await {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Tarballs are stored under the first supported hash in their integrity, or their shasum if they only have that.
+    /// Digests are hex encoded, as base64 can contain slashes.
+    fn path(dist: &Dist) -> Option<PathBuf> {
+        let (algorithm, digest) = Integrity::first_supported_hash(dist)?;
+        Some(TARBALL_DIRECTORY.join(algorithm).join(digest))
+    }
+}
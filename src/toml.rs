@@ -0,0 +1,398 @@
+use serde_json::{Map, Value};
+
+/// Writes and reads the lockfile as TOML, with `--lockfile-format toml`. Only what a lockfile is made of is supported:
+/// tables, strings, integers, booleans and arrays of them. Keys are written sorted, each table under its own header
+/// and each item of an array on its own line, so that a change to one package is a change to its own lines.
+pub struct Toml;
+
+impl Toml {
+    /// Nulls are left out, as TOML has none and a missing key is read as None anyway.
+    pub fn write(value: &Map<String, Value>) -> String {
+        let mut output = String::new();
+        Self::write_table(&mut output, &[], value);
+        output
+    }
+
+    fn write_table(output: &mut String, path: &[&str], table: &Map<String, Value>) {
+        let (tables, values): (Vec<_>, Vec<_>) = table
+            .iter()
+            .filter(|(_, value)| !value.is_null())
+            .partition(|(_, value)| value.is_object());
+
+        if !path.is_empty() {
+            if !output.is_empty() {
+                output.push('\n');
+            }
+
+            let header = path.iter().map(|key| Self::key(key)).collect::<Vec<_>>();
+            output.push_str(&format!("[{}]\n", header.join(".")));
+        }
+
+        for (key, value) in values {
+            output.push_str(&format!("{} = {}\n", Self::key(key), Self::value(value)));
+        }
+
+        for (key, value) in tables {
+            let Value::Object(table) = value else {
+                continue;
+            };
+
+            let path = path
+                .iter()
+                .copied()
+                .chain([key.as_str()])
+                .collect::<Vec<_>>();
+            Self::write_table(output, &path, table);
+        }
+    }
+
+    /// Keys such as `lockfileVersion` are written bare, and specs such as `aa@^1.0.0` quoted.
+    fn key(key: &str) -> String {
+        let bare = !key.is_empty()
+            && key
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || char == '-' || char == '_');
+
+        match bare {
+            true => key.to_string(),
+            false => Self::string(key),
+        }
+    }
+
+    fn value(value: &Value) -> String {
+        match value {
+            Value::String(string) => Self::string(string),
+            Value::Array(items) if items.is_empty() => String::from("[]"),
+            Value::Array(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| format!("  {},\n", Self::value(item)))
+                    .collect::<String>();
+                format!("[\n{items}]")
+            }
+            value => value.to_string(), // Numbers and booleans are written the same as in JSON
+        }
+    }
+
+    fn string(string: &str) -> String {
+        let mut quoted = String::from('"');
+        for char in string.chars() {
+            match char {
+                '"' => quoted.push_str("\\\""),
+                '\\' => quoted.push_str("\\\\"),
+                '\n' => quoted.push_str("\\n"),
+                '\t' => quoted.push_str("\\t"),
+                '\r' => quoted.push_str("\\r"),
+                char if char.is_control() => quoted.push_str(&format!("\\u{:04X}", char as u32)),
+                char => quoted.push(char),
+            }
+        }
+
+        quoted.push('"');
+        quoted
+    }
+
+    /// Reads what write() writes. Fails with where the document stopped making sense, such as
+    /// `expected '=' at line 3 column 13`.
+    pub fn parse(raw: &str) -> Result<Map<String, Value>, String> {
+        let mut root = Map::new();
+        let mut path: Vec<String> = Vec::new();
+
+        let mut lines = raw.lines().enumerate();
+        while let Some((index, line)) = lines.next() {
+            let indent = line.len() - line.trim_start().len();
+            let line = line.trim();
+            // Failures are located by what was left of the line when they happened
+            let at = |detail: &str, rest: &str| {
+                let column = indent + line.len() - rest.len() + 1;
+                format!("{detail} at line {} column {column}", index + 1)
+            };
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[') {
+                let (keys, rest) =
+                    Self::parse_keys(header).map_err(|(detail, rest)| at(&detail, rest))?;
+                let rest = rest
+                    .strip_prefix(']')
+                    .ok_or_else(|| at("expected ']'", rest))?;
+                if !rest.trim().is_empty() {
+                    return Err(at("expected the end of the header", rest.trim_start()));
+                }
+
+                Self::table(&mut root, &keys).map_err(|detail| at(&detail, line))?;
+                path = keys;
+                continue;
+            }
+
+            let (keys, rest) =
+                Self::parse_keys(line).map_err(|(detail, rest)| at(&detail, rest))?;
+            let rest = rest
+                .strip_prefix('=')
+                .ok_or_else(|| at("expected '='", rest))?
+                .trim_start();
+
+            // Arrays are written with an item on each line, each of which is where in the value it starts, the line
+            // and the column it starts at
+            let mut raw_value = rest.to_string();
+            let mut parts = vec![(0, index, indent + line.len() - rest.len())];
+            if raw_value.starts_with('[') && !raw_value.ends_with(']') {
+                for (index, line) in lines.by_ref() {
+                    parts.push((raw_value.len(), index, line.len() - line.trim_start().len()));
+                    raw_value.push_str(line.trim());
+                    if line.trim().ends_with(']') {
+                        break;
+                    }
+                }
+            }
+            let at_value = |detail: &str, rest: &str| {
+                let offset = raw_value.len() - rest.len();
+                let (start, index, column) = parts
+                    .iter()
+                    .rev()
+                    .find(|(start, ..)| *start <= offset)
+                    .copied()
+                    .unwrap_or_default();
+                format!(
+                    "{detail} at line {} column {}",
+                    index + 1,
+                    column + offset - start + 1
+                )
+            };
+
+            let (value, rest) =
+                Self::parse_value(&raw_value).map_err(|(detail, rest)| at_value(&detail, rest))?;
+            if !rest.trim().is_empty() && !rest.trim().starts_with('#') {
+                return Err(at_value("expected the end of the value", rest.trim_start()));
+            }
+
+            let full_path = path
+                .iter()
+                .chain(&keys[..keys.len() - 1])
+                .cloned()
+                .collect::<Vec<_>>();
+            let table = Self::table(&mut root, &full_path).map_err(|detail| at(&detail, line))?;
+            table.insert(keys[keys.len() - 1].to_string(), value);
+        }
+
+        Ok(root)
+    }
+
+    /// The table at a path, created along with the tables above it if they do not exist yet.
+    fn table<'a>(
+        root: &'a mut Map<String, Value>,
+        path: &[String],
+    ) -> Result<&'a mut Map<String, Value>, String> {
+        let mut table = root;
+        for key in path {
+            table = match table
+                .entry(key.to_string())
+                .or_insert_with(|| Value::Object(Map::new()))
+            {
+                Value::Object(table) => table,
+                _ => return Err(format!("'{key}' is not a table")),
+            };
+        }
+
+        Ok(table)
+    }
+
+    /// Reads keys separated by dots, such as `packages."aa@1.0.0".bin`, and returns what follows them. Failures are
+    /// returned along with what was left of `raw` when they happened, see parse().
+    fn parse_keys(raw: &str) -> Result<(Vec<String>, &str), (String, &str)> {
+        let mut keys = Vec::new();
+        let mut rest = raw.trim_start();
+
+        loop {
+            let (key, after) = match rest.strip_prefix('"') {
+                Some(_) => Self::parse_string(rest)?,
+                None => {
+                    let end = rest
+                        .find(|char: char| {
+                            !char.is_ascii_alphanumeric() && char != '-' && char != '_'
+                        })
+                        .unwrap_or(rest.len());
+                    if end == 0 {
+                        return Err((String::from("expected a key"), rest));
+                    }
+                    (rest[..end].to_string(), &rest[end..])
+                }
+            };
+
+            keys.push(key);
+            rest = after.trim_start();
+            match rest.strip_prefix('.') {
+                Some(after) => rest = after.trim_start(),
+                None => return Ok((keys, rest)),
+            }
+        }
+    }
+
+    fn parse_value(raw: &str) -> Result<(Value, &str), (String, &str)> {
+        if raw.starts_with('"') {
+            let (string, rest) = Self::parse_string(raw)?;
+            return Ok((Value::String(string), rest));
+        }
+
+        if let Some(mut rest) = raw.strip_prefix('[') {
+            let mut items = Vec::new();
+            loop {
+                rest = rest.trim_start();
+                if let Some(after) = rest.strip_prefix(']') {
+                    return Ok((Value::Array(items), after));
+                }
+
+                let (item, after) = Self::parse_value(rest)?;
+                items.push(item);
+                rest = after.trim_start();
+                rest = match (rest.strip_prefix(','), rest.starts_with(']')) {
+                    (Some(after), _) => after,
+                    (None, true) => rest,
+                    (None, false) => return Err((String::from("expected ',' or ']'"), rest)),
+                };
+            }
+        }
+
+        let end = raw
+            .find(|char: char| char == ',' || char == ']' || char.is_whitespace())
+            .unwrap_or(raw.len());
+        let (scalar, rest) = raw.split_at(end);
+        let value = match scalar {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            number => Value::Number(
+                number
+                    .parse::<i64>()
+                    .map_err(|_| (format!("expected a value, found '{number}'"), raw))?
+                    .into(),
+            ),
+        };
+
+        Ok((value, rest))
+    }
+
+    /// Reads a basic string from its opening quote, and returns what follows its closing one.
+    fn parse_string(raw: &str) -> Result<(String, &str), (String, &str)> {
+        let mut string = String::new();
+        let mut chars = raw.char_indices().skip(1);
+
+        while let Some((index, char)) = chars.next() {
+            match char {
+                '"' => return Ok((string, &raw[index + 1..])),
+                '\\' => match chars.next().map(|(_, escaped)| escaped) {
+                    Some('"') => string.push('"'),
+                    Some('\\') => string.push('\\'),
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some('r') => string.push('\r'),
+                    Some('u') => {
+                        let code = (0..4)
+                            .filter_map(|_| chars.next().map(|(_, digit)| digit))
+                            .collect::<String>();
+                        let char = u32::from_str_radix(&code, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| {
+                                (format!("invalid escape '\\u{code}'"), &raw[index..])
+                            })?;
+                        string.push(char);
+                    }
+                    escaped => {
+                        let detail = format!("invalid escape '\\{}'", escaped.unwrap_or(' '));
+                        return Err((detail, &raw[index..]));
+                    }
+                },
+                char => string.push(char),
+            }
+        }
+
+        Err((String::from("expected '\"'"), &raw[raw.len()..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn writes_tables_under_headers_and_reads_them_back() {
+        let value = json!({
+            "lockfileVersion": 1,
+            "noBinLinks": true,
+            "requested": { "aa@^1.0.0": "aa@1.0.0" },
+            "packages": {
+                "aa@1.0.0": {
+                    "bin": { "tool": "bin/cli.js" },
+                    "dependencies": ["bb@1.0.0", "cc@2.0.0"],
+                    "resolved": "https://registry.test/aa/-/aa-1.0.0.tgz",
+                },
+                "bb@1.0.0": { "dependencies": [], "optional": true },
+            },
+            "links": {},
+        });
+        let Value::Object(table) = value.clone() else {
+            unreachable!();
+        };
+
+        let written = Toml::write(&table);
+        assert_eq!(
+            written,
+            r#"lockfileVersion = 1
+noBinLinks = true
+
+[links]
+
+[packages]
+
+[packages."aa@1.0.0"]
+dependencies = [
+  "bb@1.0.0",
+  "cc@2.0.0",
+]
+resolved = "https://registry.test/aa/-/aa-1.0.0.tgz"
+
+[packages."aa@1.0.0".bin]
+tool = "bin/cli.js"
+
+[packages."bb@1.0.0"]
+dependencies = []
+optional = true
+
+[requested]
+"aa@^1.0.0" = "aa@1.0.0"
+"#
+        );
+        assert_eq!(Value::Object(Toml::parse(&written).unwrap()), value);
+    }
+
+    #[test]
+    fn escapes_strings_and_reports_where_parsing_failed() {
+        let value = json!({ "a \"b\"": "c\\d\n\u{1}" });
+        let Value::Object(table) = value.clone() else {
+            unreachable!();
+        };
+        assert_eq!(
+            Value::Object(Toml::parse(&Toml::write(&table)).unwrap()),
+            value
+        );
+
+        assert_eq!(
+            Toml::parse("[requested]\n\"aa@^1.0.0\" \"aa@1.0.0\""),
+            Err(String::from("expected '=' at line 2 column 13"))
+        );
+        assert_eq!(
+            Toml::parse("[packages\n"),
+            Err(String::from("expected ']' at line 1 column 10"))
+        );
+        // Within an array, the line of the item is the one reported
+        assert_eq!(
+            Toml::parse("[packages.\"aa@1.0.0\"]\ndependencies = [\n  \"bb@1.0.0\",\n  cc,\n]\n"),
+            Err(String::from(
+                "expected a value, found 'cc' at line 4 column 3"
+            ))
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    errors::CommandError,
+    installer::{BIN_DIRECTORY, INSTALL_STATE_PATH, MODULES_DIRECTORY},
+    isolated::ISOLATED_DIRECTORY,
+    lockfile::{LOCKFILE_PATH, TOML_LOCKFILE_PATH},
+    manifest::PACKAGE_JSON_PATH,
+};
+
+/// What was at a path in node_modules before the install.
+#[derive(PartialEq)]
+enum Entry {
+    Link(PathBuf), // A link to this target, such as a package in the cache
+    File(Vec<u8>), // The contents of a file, such as a shim in node_modules/.bin on Windows
+    Directory,     // Such as the directory of a scope, or of a package with the isolated layout
+}
+
+/// An install as a whole, so that one that fails can be undone. Packages are extracted into a temporary directory in the
+/// cache and renamed into place once they are complete, so the install only changes node_modules by adding and
+/// replacing links, which are recorded here before it starts. Rolling back removes whatever was added and puts back
+/// what was replaced, along with the lockfile and package.json.
+pub struct Transaction {
+    entries: BTreeMap<PathBuf, Entry>, // Each entry of node_modules before the install
+    files: Vec<(&'static str, Option<Vec<u8>>)>, // The contents of files the install writes, None if it did not exist
+}
+
+impl Transaction {
+    /// Records node_modules as it is before anything is installed.
+    pub fn begin() -> Result<Self, CommandError> {
+        let files = [LOCKFILE_PATH, TOML_LOCKFILE_PATH, PACKAGE_JSON_PATH]
+            .into_iter()
+            .map(|path| match fs::read(path) {
+                Ok(contents) => Ok((path, Some(contents))),
+                Err(err) if err.kind() == ErrorKind::NotFound => Ok((path, None)),
+                Err(err) => Err(CommandError::FailedToReadFile(err)),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            entries: Self::entries().map_err(CommandError::FailedToReadFile)?,
+            files,
+        })
+    }
+
+    /// Puts node_modules, the lockfile and package.json back the way they were before the install.
+    /// Packages stay in the cache, so installing again does not download them again.
+    pub fn rollback(self) -> Result<(), CommandError> {
+        self.restore().map_err(CommandError::FailedToRollBack)
+    }
+
+    fn restore(self) -> Result<(), io::Error> {
+        let current = Self::entries()?;
+
+        // Deepest first, so the links in a directory are removed before the directory itself
+        let mut added = current
+            .keys()
+            .filter(|path| !self.entries.contains_key(*path))
+            .collect::<Vec<_>>();
+        added.sort_by_key(|path| std::cmp::Reverse(path.components().count()));
+
+        for path in added {
+            Self::remove(path)?;
+        }
+
+        // Shallowest first, so a directory is back before the links in it
+        for (path, entry) in self.entries.iter() {
+            if current.get(path) == Some(entry) {
+                continue;
+            }
+
+            match entry {
+                Entry::Directory => fs::create_dir_all(path)?,
+                Entry::Link(target) => {
+                    Self::remove(path)?;
+                    symlink::symlink_auto(target, path)?;
+                }
+                Entry::File(contents) => fs::write(path, contents)?,
+            }
+        }
+
+        for (path, contents) in self.files {
+            match contents {
+                Some(contents) => fs::write(path, contents)?,
+                None if Path::new(path).exists() => fs::remove_file(path)?,
+                None => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes a link, file or directory, only what is in a directory that is not a link is removed with it.
+    fn remove(path: &Path) -> Result<(), io::Error> {
+        let metadata = match path.symlink_metadata() {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        if metadata.is_symlink() {
+            symlink::remove_symlink_auto(path)
+        } else if metadata.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+    }
+
+    /// The entries of node_modules that an install changes, which are packages and scopes at the top of it along with
+    /// what is nested in them, the executables in node_modules/.bin and the packages of the isolated layout.
+    fn entries() -> Result<BTreeMap<PathBuf, Entry>, io::Error> {
+        let mut directories = BTreeSet::from([
+            PathBuf::from(MODULES_DIRECTORY),
+            PathBuf::from(BIN_DIRECTORY),
+            PathBuf::from(ISOLATED_DIRECTORY),
+        ]);
+
+        let mut entries = BTreeMap::new();
+        while let Some(directory) = directories.pop_first() {
+            let directory_entries = match fs::read_dir(&directory) {
+                Ok(directory_entries) => directory_entries,
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+
+            for directory_entry in directory_entries {
+                let path = directory_entry?.path();
+                if path == Path::new(INSTALL_STATE_PATH) {
+                    continue; // Kept so that the install can be resumed, see Installer::read_install_state()
+                }
+
+                let metadata = path.symlink_metadata()?;
+                let entry = if metadata.is_symlink() {
+                    Entry::Link(fs::read_link(&path)?)
+                } else if metadata.is_dir() {
+                    Entry::Directory
+                } else {
+                    Entry::File(fs::read(&path)?)
+                };
+
+                // Packages of a scope are in its directory, and a package with dependencies nested in it is a directory
+                // of links with the hoisted layout, see HoistedLayout
+                let in_modules = path.parent().is_some_and(|parent| {
+                    parent
+                        .file_name()
+                        .is_some_and(|name| name == "node_modules")
+                });
+                let is_scope = in_modules
+                    && path
+                        .file_name()
+                        .is_some_and(|name| name.to_string_lossy().starts_with('@'));
+                let is_nested_modules = path.file_name().is_some_and(|name| name == "node_modules");
+                let is_package_directory = path.join("package.json").is_symlink();
+                if entry == Entry::Directory
+                    && (is_scope || is_nested_modules || is_package_directory)
+                {
+                    directories.insert(path.clone());
+                }
+
+                entries.insert(path, entry);
+            }
+        }
+
+        Ok(entries)
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cache::Cache,
+    errors::CommandError,
+    installer::MODULES_DIRECTORY,
+    manifest::{Manifest, DEPENDENCY_FIELDS},
+    types::InstalledPackage,
+    versions::Versions,
+    workspaces::Workspace,
+};
+
+const PACKAGE_JSON_NAME: &str = "package.json";
+
+pub struct DependencyTree;
+impl DependencyTree {
+    /// Reconstructs the tree of installed packages from the package.json of each of them, rooted at the project.
+    /// The top level is every package listed in the project package.json, and every installed package nothing else depends on.
+    /// If `depth` is Some, dependencies are only listed that many levels below the top level.
+    pub fn read(depth: Option<usize>) -> Result<InstalledPackage, CommandError> {
+        let manifest = Manifest::read()?;
+        let installed = Self::installed_packages()?;
+
+        let mut dependents = HashSet::new();
+        for package_name in installed.iter() {
+            let package_path = Path::new(MODULES_DIRECTORY).join(package_name);
+            if let Some(package_manifest) = Self::read_package_manifest(&package_path)? {
+                dependents.extend(
+                    package_manifest
+                        .dependencies("dependencies")
+                        .into_iter()
+                        .map(|(name, _)| name),
+                );
+            }
+        }
+
+        let mut top_level = installed
+            .into_iter()
+            .filter(|package_name| !dependents.contains(package_name))
+            .collect::<Vec<_>>();
+
+        if let Some(manifest) = &manifest {
+            for field in DEPENDENCY_FIELDS {
+                top_level.extend(
+                    manifest
+                        .dependencies(field)
+                        .into_iter()
+                        .map(|(name, _)| name),
+                );
+            }
+        }
+
+        let dependencies = Self::walk_top_level(Path::new("."), top_level, depth)?;
+
+        let field = |field| {
+            manifest
+                .as_ref()
+                .and_then(|manifest| manifest.field(field))
+                .map(str::to_string)
+        };
+
+        Ok(InstalledPackage {
+            name: field("name").unwrap_or_else(|| String::from(".")),
+            version: field("version"),
+            registry: None,
+            circular: false,
+            dependencies,
+        })
+    }
+
+    /// The tree of the packages a workspace lists in its package.json, rooted at the workspace. They are found from the
+    /// directory of the workspace, so those linked into the root node_modules are found there as Node would.
+    pub fn read_workspace(
+        workspace: &Workspace,
+        depth: Option<usize>,
+    ) -> Result<InstalledPackage, CommandError> {
+        let top_level = DEPENDENCY_FIELDS
+            .iter()
+            .flat_map(|field| workspace.manifest.dependencies(field))
+            .map(|(name, _)| name)
+            .collect();
+
+        Ok(InstalledPackage {
+            name: workspace.name.to_string(),
+            version: workspace.manifest.field("version").map(str::to_string),
+            registry: None,
+            circular: false,
+            dependencies: Self::walk_top_level(&workspace.path, top_level, depth)?,
+        })
+    }
+
+    fn walk_top_level(
+        from: &Path,
+        mut top_level: Vec<String>,
+        depth: Option<usize>,
+    ) -> Result<Vec<InstalledPackage>, CommandError> {
+        top_level.sort();
+        top_level.dedup();
+
+        top_level
+            .into_iter()
+            .map(|package_name| {
+                let package_path = Self::locate(from, &package_name);
+                Self::walk(package_name, package_path, &mut Vec::new(), depth)
+            })
+            .collect()
+    }
+
+    /// `ancestors` are the packages above this one in the tree, used to stop at circular dependencies.
+    fn walk(
+        package_name: String,
+        package_path: Option<PathBuf>,
+        ancestors: &mut Vec<PathBuf>,
+        depth: Option<usize>,
+    ) -> Result<InstalledPackage, CommandError> {
+        let mut installed_package = InstalledPackage {
+            name: package_name,
+            version: None,
+            registry: None,
+            circular: false,
+            dependencies: Vec::new(),
+        };
+
+        let package_path = match package_path {
+            Some(package_path) => package_path,
+            None => return Ok(installed_package),
+        };
+
+        let package_manifest = match Self::read_package_manifest(&package_path)? {
+            Some(package_manifest) => package_manifest,
+            None => return Ok(installed_package),
+        };
+
+        installed_package.version = package_manifest.field("version").map(str::to_string);
+        installed_package.registry = installed_package
+            .version
+            .as_ref()
+            .and_then(|version| Self::registry(&installed_package.name, version));
+
+        // Packages are linked from the cache, so the real path is the same wherever a package appears
+        let real_path = fs::canonicalize(&package_path).map_err(CommandError::FailedToReadFile)?;
+        if ancestors.contains(&real_path) {
+            installed_package.circular = true;
+            return Ok(installed_package);
+        }
+
+        if depth == Some(0) {
+            return Ok(installed_package);
+        }
+
+        ancestors.push(real_path.clone());
+        // Dependencies next to the real path are those of the isolated layout, which Node finds first
+        for (dependency_name, _) in package_manifest.dependencies("dependencies") {
+            let dependency_path = Self::locate(&real_path, &dependency_name)
+                .or_else(|| Self::locate(&package_path, &dependency_name));
+            let dependency = Self::walk(
+                dependency_name,
+                dependency_path,
+                ancestors,
+                depth.map(|depth| depth - 1),
+            )?;
+
+            installed_package.dependencies.push(dependency);
+        }
+        ancestors.pop();
+
+        Ok(installed_package)
+    }
+
+    /// Finds the directory a dependency is installed in the same way Node resolves it, by looking in the node_modules
+    /// of the package and then those of each directory above it. Returns None if it is not installed.
+    fn locate(package_path: &Path, dependency_name: &str) -> Option<PathBuf> {
+        package_path
+            .ancestors()
+            .filter(|dir| dir.file_name().is_none_or(|name| name != "node_modules"))
+            .map(|dir| dir.join("node_modules").join(dependency_name))
+            .find(|dependency_path| dependency_path.exists())
+    }
+
+    /// The registry the cached package was resolved from, as recorded in its lockfile in the cache.
+    fn registry(package_name: &String, version: &String) -> Option<String> {
+        let package = Versions::stringify(package_name, version);
+        if !Cache::has_lock(&package) {
+            return None;
+        }
+
+        Cache::read_lock(&package).ok()?.registry
+    }
+
+    /// Returns None if the package has no package.json, which means it was not fully installed.
+    fn read_package_manifest(package_path: &Path) -> Result<Option<Manifest>, CommandError> {
+        Manifest::read_from(&package_path.join(PACKAGE_JSON_NAME))
+    }
+
+    /// Returns the name of every package at the top of node_modules, including scoped packages such as `@scope/name`.
+    fn installed_packages() -> Result<Vec<String>, CommandError> {
+        let mut packages = Vec::new();
+        for entry in Self::read_dir(Path::new(MODULES_DIRECTORY))? {
+            // Files such as the install state are not packages
+            if entry.starts_with('.') {
+                continue;
+            }
+
+            if entry.starts_with('@') {
+                for scoped in Self::read_dir(&Path::new(MODULES_DIRECTORY).join(&entry))? {
+                    packages.push(format!("{entry}/{scoped}"));
+                }
+                continue;
+            }
+
+            packages.push(entry);
+        }
+
+        Ok(packages)
+    }
+
+    /// Returns the name of every entry in a directory, nothing if it does not exist.
+    fn read_dir(path: &Path) -> Result<Vec<String>, CommandError> {
+        let dir = match fs::read_dir(path) {
+            Ok(dir) => dir,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(CommandError::FailedToReadFile(err)),
+        };
+
+        dir.map(|entry| {
+            entry
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .map_err(CommandError::FailedDirectoryEntry)
+        })
+        .collect()
+    }
+}
@@ -1,6 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
-use serde::{Deserialize, Serialize};
+use semver::Version;
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Debug, Deserialize)]
 pub struct VersionData {
@@ -8,17 +9,334 @@ pub struct VersionData {
     pub version: String,
     pub dependencies: Option<HashMap<String, String>>,
     pub dist: Dist,
+    #[serde(rename = "hasInstallScript", default)]
+    pub has_install_script: bool, // Used to warn about the packages whose lifecycle scripts were skipped with --ignore-scripts
+    pub bin: Option<Bin>,
+    // Platforms the version supports, see Platform::supports()
+    pub os: Option<Vec<String>>,
+    pub cpu: Option<Vec<String>>,
+    pub libc: Option<Vec<String>>,
+    pub engines: Option<serde_json::Value>, // Only an object in newer packages, see VersionData::node_engine()
+    #[serde(rename = "optionalDependencies")]
+    pub optional_dependencies: Option<HashMap<String, String>>, // Skipped rather than failing the install if they fail
+    #[serde(rename = "peerDependencies")]
+    pub peer_dependencies: Option<HashMap<String, String>>,
+    #[serde(rename = "peerDependenciesMeta", default)]
+    pub peer_dependencies_meta: HashMap<String, PeerDependencyMeta>,
+    #[serde(rename = "bundleDependencies", alias = "bundledDependencies")]
+    pub bundle_dependencies: Option<serde_json::Value>, // Names, or true for every dependency, see VersionData::bundled_dependencies()
+    #[serde(default, deserialize_with = "deprecation")]
+    pub deprecated: Option<String>, // The message the version was deprecated with
+}
+
+impl VersionData {
+    /// The range of Node the version supports. Some old packages list their engines as an array, which npm ignores.
+    pub fn node_engine(&self) -> Option<&str> {
+        self.engines.as_ref()?.get("node")?.as_str()
+    }
+
+    /// Dependencies shipped in the tarball of the package, in its own node_modules. They are not installed separately,
+    /// as Node finds the bundled copy first.
+    pub fn bundled_dependencies(&self) -> HashSet<String> {
+        match &self.bundle_dependencies {
+            Some(serde_json::Value::Bool(true)) => self
+                .dependencies
+                .iter()
+                .flatten()
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            Some(serde_json::Value::Array(names)) => names
+                .iter()
+                .filter_map(serde_json::Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            _ => HashSet::new(),
+        }
+    }
+
+    /// Peer dependencies that are installed along with the package like npm 7 and later do, which leaves out those
+    /// marked optional and those that are also regular dependencies.
+    pub fn required_peer_dependencies(&self) -> Vec<(String, String)> {
+        let dependencies = self.dependencies.as_ref();
+
+        self.peer_dependencies
+            .iter()
+            .flatten()
+            .filter(|(name, _)| {
+                !dependencies.is_some_and(|dependencies| dependencies.contains_key(*name))
+            })
+            .filter(|(name, _)| {
+                !self
+                    .peer_dependencies_meta
+                    .get(*name)
+                    .is_some_and(|meta| meta.optional)
+            })
+            .map(|(name, range)| (name.to_string(), range.to_string()))
+            .collect()
+    }
+}
+
+/// Some old packuments mark versions that are not deprecated with `"deprecated": false`.
+fn deprecation<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(message) if !message.is_empty() => Ok(Some(message)),
+        _ => Ok(None),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PeerDependencyMeta {
+    #[serde(default)]
+    pub optional: bool,
+}
+
+/// The fields `click exec` reads from the package.json of an installed package.
+#[derive(Debug, Deserialize)]
+pub struct PackageExecutables {
+    pub version: String,
+    pub bin: Option<Bin>,
+}
+
+/// The executables a package provides, either a single one named after the package or a map of names to paths.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Bin {
+    Single(String),
+    Multiple(BTreeMap<String, String>),
+}
+
+impl Bin {
+    /// Maps the name of each executable to its path within the package, the same way npm normalizes them.
+    /// A single executable of a scoped package is named without the scope. Names that could point outside of
+    /// node_modules/.bin and paths that could point outside of the package are dropped.
+    pub fn links(&self, package_name: &str) -> BTreeMap<String, String> {
+        let links = match self {
+            Self::Single(path) => {
+                let name = package_name.rsplit('/').next().unwrap_or(package_name);
+                BTreeMap::from([(name.to_string(), path.to_string())])
+            }
+            Self::Multiple(links) => links.clone(),
+        };
+
+        links
+            .into_iter()
+            .filter(|(name, _)| !name.is_empty() && !name.contains(['/', '\\']) && name != "..")
+            .filter(|(_, path)| !path.split(['/', '\\']).any(|component| component == ".."))
+            .map(|(name, path)| {
+                let path = path.trim_start_matches("./").to_string();
+                (name, path)
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Dist {
     pub tarball: String,
+    pub integrity: Option<String>, // Subresource integrity string, older packages only have a shasum
+    pub shasum: Option<String>,
+    #[serde(rename = "unpackedSize")]
+    pub unpacked_size: Option<u64>, // Size of the extracted files in bytes, not listed by older packages
+    #[serde(default)]
+    pub signatures: Vec<Signature>, // Made by the registry, see Signatures::verify()
+    pub attestations: Option<DistAttestations>, // Published with the version, such as its provenance
+}
+
+/// A signature by one of the keys of the registry, base64 encoded.
+#[derive(Debug, Deserialize)]
+pub struct Signature {
+    #[serde(default)]
+    pub keyid: String,
+    pub sig: String,
+}
+
+/// Where the attestations of a version can be downloaded from.
+#[derive(Debug, Deserialize)]
+pub struct DistAttestations {
+    pub url: String,
+}
+
+/// The keys a registry signs versions with, from `/-/npm/v1/keys`.
+#[derive(Deserialize)]
+pub struct RegistryKeys {
+    pub keys: Vec<RegistryKey>,
+}
+
+#[derive(Deserialize)]
+pub struct RegistryKey {
+    pub keyid: String,
+    pub key: String, // A base64 encoded ECDSA P-256 public key in DER
+    #[serde(default)]
+    pub expires: Option<String>, // When a key the registry rotated out expired, such as `2025-01-29T00:00:00.000Z`
+}
+
+/// The attestations of a version, each a Sigstore bundle.
+#[derive(Deserialize)]
+pub struct Attestations {
+    pub attestations: Vec<Attestation>,
+}
+
+#[derive(Deserialize)]
+pub struct Attestation {
+    #[serde(rename = "predicateType")]
+    pub predicate_type: String, // Such as `https://slsa.dev/provenance/v1`
+    pub bundle: AttestationBundle,
+}
+
+#[derive(Deserialize)]
+pub struct AttestationBundle {
+    #[serde(rename = "verificationMaterial")]
+    pub verification_material: VerificationMaterial,
+    #[serde(rename = "dsseEnvelope")]
+    pub dsse_envelope: DsseEnvelope,
+}
+
+#[derive(Deserialize)]
+pub struct VerificationMaterial {
+    #[serde(rename = "publicKey")]
+    pub public_key: Option<PublicKeyHint>, // Set when the registry signed it, rather than a Sigstore certificate
+}
+
+#[derive(Deserialize)]
+pub struct PublicKeyHint {
+    pub hint: String, // The keyid of the registry key
+}
+
+/// A signed in-toto statement, see https://github.com/secure-systems-lab/dsse.
+#[derive(Deserialize)]
+pub struct DsseEnvelope {
+    pub payload: String, // Base64 encoded
+    #[serde(rename = "payloadType")]
+    pub payload_type: String,
+    pub signatures: Vec<Signature>,
+}
+
+/// The artifacts an attestation is about.
+#[derive(Deserialize)]
+pub struct InTotoStatement {
+    pub subject: Vec<InTotoSubject>,
+}
+
+#[derive(Deserialize)]
+pub struct InTotoSubject {
+    pub name: String,                    // Such as `pkg:npm/%40scope/name@1.0.0`
+    pub digest: HashMap<String, String>, // Hex encoded, by algorithm
+}
+
+/// A version as listed in the abbreviated packument (`application/vnd.npm.install-v1+json`), which leaves out
+/// everything that is not needed to install it such as the readme and scripts. Only the fields needed to resolve
+/// and install a version are kept.
+#[derive(Debug, Deserialize)]
+pub struct AbbreviatedVersionData {
+    pub name: String,
+    pub version: String,
+    pub dependencies: Option<HashMap<String, String>>,
+    pub dist: Dist,
+    #[serde(rename = "hasInstallScript", default)]
+    pub has_install_script: bool,
+    pub bin: Option<Bin>,
+    // Platforms the version supports, see Platform::supports()
+    pub os: Option<Vec<String>>,
+    pub cpu: Option<Vec<String>>,
+    pub libc: Option<Vec<String>>,
+    pub engines: Option<serde_json::Value>, // Only an object in newer packages, see VersionData::node_engine()
+    #[serde(rename = "optionalDependencies")]
+    pub optional_dependencies: Option<HashMap<String, String>>, // Skipped rather than failing the install if they fail
+    #[serde(rename = "peerDependencies")]
+    pub peer_dependencies: Option<HashMap<String, String>>,
+    #[serde(rename = "peerDependenciesMeta", default)]
+    pub peer_dependencies_meta: HashMap<String, PeerDependencyMeta>,
+    #[serde(rename = "bundleDependencies", alias = "bundledDependencies")]
+    pub bundle_dependencies: Option<serde_json::Value>, // Names, or true for every dependency, see VersionData::bundled_dependencies()
+    #[serde(default, deserialize_with = "deprecation")]
+    pub deprecated: Option<String>, // The message the version was deprecated with
+}
+
+impl From<AbbreviatedVersionData> for VersionData {
+    fn from(abbreviated: AbbreviatedVersionData) -> Self {
+        Self {
+            name: abbreviated.name,
+            version: abbreviated.version,
+            dependencies: abbreviated.dependencies,
+            dist: abbreviated.dist,
+            has_install_script: abbreviated.has_install_script,
+            bin: abbreviated.bin,
+            os: abbreviated.os,
+            cpu: abbreviated.cpu,
+            libc: abbreviated.libc,
+            engines: abbreviated.engines,
+            optional_dependencies: abbreviated.optional_dependencies,
+            peer_dependencies: abbreviated.peer_dependencies,
+            peer_dependencies_meta: abbreviated.peer_dependencies_meta,
+            bundle_dependencies: abbreviated.bundle_dependencies,
+            deprecated: abbreviated.deprecated,
+        }
+    }
 }
 
-// This does not include the full package data as we don't need it at the moment.
+// Parsed from the abbreviated packument, which does not include the full package data as we don't need it at the moment.
 #[derive(Deserialize)]
+#[serde(from = "RawPackageData")]
 pub struct PackageData {
-    pub versions: HashMap<String, VersionData>,
+    pub versions: HashMap<String, AbbreviatedVersionData>, // Every key is a valid semver version in its canonical form
+    pub invalid_versions: Vec<String>, // Keys dropped from `versions` as they are not valid semver
+    pub duplicate_versions: Vec<String>, // Keys dropped as another key is the same version, such as `v1.2.3` and `1.2.3`
+    pub dist_tags: HashMap<String, String>, // Such as `latest`, to the version it points at
+}
+
+#[derive(Deserialize)]
+struct RawPackageData {
+    versions: HashMap<String, AbbreviatedVersionData>,
+    #[serde(rename = "dist-tags", default)]
+    dist_tags: HashMap<String, String>,
+}
+
+impl From<RawPackageData> for PackageData {
+    /// Registry version keys are used directly as HashMap keys and parsed again later on,
+    /// so they are validated once here. Keys like `v1.2.3` or ` 1.2.3` are normalized to `1.2.3`.
+    /// When more than one key normalizes to the same version, the one already in canonical form is kept.
+    fn from(raw: RawPackageData) -> Self {
+        let mut versions = HashMap::new();
+        let mut kept_keys: HashMap<String, String> = HashMap::new();
+        let mut invalid_versions = Vec::new();
+        let mut duplicate_versions = Vec::new();
+
+        // Sorted so that which key is kept does not depend on the order of the HashMap
+        let mut raw_versions = raw.versions.into_iter().collect::<Vec<_>>();
+        raw_versions.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (version_key, version_data) in raw_versions {
+            let trimmed = version_key.trim();
+            let trimmed = trimmed.strip_prefix(['v', '=']).unwrap_or(trimmed);
+
+            let version = match Version::parse(trimmed) {
+                Ok(version) => version.to_string(),
+                Err(_) => {
+                    invalid_versions.push(version_key);
+                    continue;
+                }
+            };
+
+            match kept_keys.get(&version) {
+                Some(kept_key) if *kept_key == version || version_key != version => {
+                    duplicate_versions.push(version_key);
+                    continue;
+                }
+                Some(kept_key) => duplicate_versions.push(kept_key.to_string()),
+                None => {}
+            }
+
+            kept_keys.insert(version.to_string(), version_key);
+            versions.insert(version, version_data);
+        }
+
+        Self {
+            versions,
+            invalid_versions,
+            duplicate_versions,
+            dist_tags: raw.dist_tags,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -26,6 +344,42 @@ pub struct PackageLock {
     #[serde(rename = "isLatest")]
     pub is_latest: bool,
     pub dependencies: Vec<String>,
+    #[serde(rename = "hasInstallScript", default, skip_serializing_if = "is_false")]
+    pub has_install_script: bool,
+    // Older lockfiles only recorded the SHA-1 shasum, these are upgraded to SHA-512 integrity when next installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shasum: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<String>, // The tarball URL the package was downloaded from
+    // The registry the package was resolved from, which may differ from the host of the tarball URL. Not recorded by
+    // older lockfiles.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    // Digest of the extracted files, checked by `click verify`. Not recorded by older lockfiles.
+    #[serde(
+        rename = "contentIntegrity",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub content_integrity: Option<String>,
+    // Name of each executable linked into node_modules/.bin to its path within the package
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub bin: BTreeMap<String, String>,
+    // Lifecycle scripts run in the cache, so they only have to run the first time a package is installed
+    #[serde(
+        rename = "installScriptsRun",
+        default,
+        skip_serializing_if = "is_false"
+    )]
+    pub install_scripts_run: bool,
+    // Only installed for an optional dependency, so a later install carries on if it fails
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub optional: bool,
+    // The message the registry deprecated the version with, warned about whenever it is installed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
 }
 
 impl PackageLock {
@@ -33,8 +387,705 @@ impl PackageLock {
         Self {
             is_latest,
             dependencies: Vec::new(),
+            has_install_script: false,
+            shasum: None,
+            integrity: None,
+            resolved: None,
+            registry: None,
+            content_integrity: None,
+            bin: BTreeMap::new(),
+            install_scripts_run: false,
+            optional: false,
+            deprecated: None,
         }
     }
 }
 
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
 pub type DependencyMap = HashMap<String, PackageLock>;
+
+/// The project lockfile, which pins every requested package and everything it depends on to an exact version.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(rename = "lockfileVersion")]
+    pub lockfile_version: u32,
+    pub requested: BTreeMap<String, String>, // Each spec as it was requested (package@range) to the package@version it resolved to
+    pub packages: BTreeMap<String, LockedPackage>, // Keyed by package@version
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub links: BTreeMap<String, String>, // Packages depended on with link:, by name to the directory linked to
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub overrides: BTreeMap<String, String>, // Each override as it is keyed in package.json to the package@version it resolved to
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub patches: BTreeMap<String, String>, // Each patched package@version to the integrity of its patch in patches/
+    #[serde(rename = "noBinLinks", default, skip_serializing_if = "is_false")]
+    pub no_bin_links: bool, // Installed with --no-bin-links, so node_modules/.bin is left out until it is installed without
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedPackage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<String>, // Tarball URL, older cache entries did not record it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>, // The registry the package was resolved from, None for imported lockfiles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+    #[serde(rename = "hasInstallScript", default, skip_serializing_if = "is_false")]
+    pub has_install_script: bool,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub bin: BTreeMap<String, String>,
+    pub dependencies: Vec<String>, // Formatted as package@version
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub optional: bool,
+}
+
+/// An npm package-lock.json, which is imported the first time a project is installed, see NpmLockfile.
+#[derive(Deserialize)]
+pub struct NpmPackageLock {
+    #[serde(default)]
+    pub packages: BTreeMap<String, NpmLockedPackage>, // Keyed by the path in node_modules, "" being the project itself
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, NpmLockedDependency>, // The nested tree of lockfileVersion 1, by name
+}
+
+/// A package as lockfileVersion 2 and 3 lock it, at a path in node_modules.
+#[derive(Deserialize)]
+pub struct NpmLockedPackage {
+    pub version: Option<String>,
+    pub resolved: Option<String>, // Tarball URL, or where a package from anywhere but the registry came from
+    pub integrity: Option<String>,
+    #[serde(default)]
+    pub link: bool, // A workspace or link: dependency, linked rather than installed
+    #[serde(rename = "inBundle", default)]
+    pub in_bundle: bool, // Installed with the package that bundles it
+    #[serde(default)]
+    pub optional: bool,
+    #[serde(rename = "hasInstallScript", default)]
+    pub has_install_script: bool,
+    #[serde(default)]
+    pub bin: BTreeMap<String, String>,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, String>,
+    #[serde(rename = "optionalDependencies", default)]
+    pub optional_dependencies: BTreeMap<String, String>,
+    #[serde(rename = "peerDependencies", default)]
+    pub peer_dependencies: BTreeMap<String, String>,
+}
+
+/// A package as lockfileVersion 1 locks it, with the packages nested in its node_modules.
+#[derive(Deserialize)]
+pub struct NpmLockedDependency {
+    pub version: String, // npm:name@version for an alias
+    pub resolved: Option<String>,
+    pub integrity: Option<String>,
+    #[serde(default)]
+    pub bundled: bool,
+    #[serde(default)]
+    pub optional: bool,
+    #[serde(default)]
+    pub requires: BTreeMap<String, String>, // Its dependencies of every kind, by name to range
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, NpmLockedDependency>,
+}
+
+/// Progress of an install that has not completed yet, so that it can be resumed if interrupted.
+#[derive(Default, Serialize, Deserialize)]
+pub struct InstallState {
+    // Each package formatted as package@version to what it is locked with, which a resumed install locks it with
+    // rather than downloading it again
+    pub extracted: HashMap<String, ExtractedPackage>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ExtractedPackage {
+    pub shasum: String,
+    pub integrity: String,
+    pub resolved: String, // The tarball URL the package was downloaded from
+    pub registry: String, // The registry the package was resolved from
+}
+
+// Maps package@version to the only integrity that version is allowed to have.
+pub type IntegrityAllowlist = HashMap<String, String>;
+
+/// Machine-readable summary of an install, written with `install --report <path>`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallReport {
+    pub success: bool,
+    pub duration_ms: u128,
+    pub summary: InstallSummary,
+    pub packages: Vec<ReportedPackage>,
+    pub warnings: Vec<InstallWarning>,
+    pub errors: Vec<String>,
+}
+
+/// What a warning of an install is about. The codes are stable, so that scripts reading the JSON output can tell
+/// warnings apart without matching their message.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WarningCode {
+    Deprecated,       // A package was installed that its authors have deprecated
+    EngineMismatch,   // A package does not support the installed Node, and engine-strict is not set
+    OptionalSkipped,  // An optional dependency failed to install and was left out
+    PeerDependency,   // A peer dependency is missing, or conflicts with --legacy-peer-deps
+    ScriptsNotRun,    // Packages have install scripts that were not run
+    OverridesIgnored, // Overrides are only applied with the hoisted linker
+}
+
+/// A warning of an install that did not stop it, collected into the report rather than only printed.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct InstallWarning {
+    pub code: WarningCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>, // Formatted as package@version, or None if the warning is not about one package
+    pub message: String,
+}
+
+impl InstallWarning {
+    pub fn new(code: WarningCode, package: Option<&str>, message: String) -> Self {
+        Self {
+            code,
+            package: package.map(str::to_string),
+            message,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct InstallSummary {
+    pub total: usize,
+    pub downloaded: usize,
+    pub cached: usize,
+}
+
+#[derive(Serialize)]
+pub struct ReportedPackage {
+    pub package: String, // Formatted as package@version
+    pub cached: bool,
+    pub integrity: Option<String>,
+    pub dependencies: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+}
+
+/// The dependency graph of a package, output by `click resolve`.
+#[derive(Serialize)]
+pub struct ResolvedGraph {
+    pub root: String,                                // Formatted as package@version
+    pub packages: BTreeMap<String, ResolvedPackage>, // Ordered so that the output is stable
+}
+
+#[derive(Serialize)]
+pub struct ResolvedPackage {
+    pub integrity: Option<String>,
+    pub dependencies: Vec<String>,
+}
+
+/// What `click uninstall --json` removed from node_modules.
+#[derive(Default, Serialize)]
+pub struct UninstallResult {
+    pub removed: Vec<String>,  // Formatted as package@version
+    pub kept: Vec<String>, // Packages asked to be uninstalled that other packages still depend on
+    pub orphaned: Vec<String>, // Dependencies removed as nothing needs them anymore
+}
+
+/// A package at a location in node_modules, output by `click dedupe` and `click prune`.
+#[derive(Serialize)]
+pub struct PlacedPackage {
+    pub path: String,    // Such as node_modules/foo/node_modules/bar
+    pub package: String, // Formatted as package@version
+}
+
+/// What `click dedupe --json` changed in node_modules.
+#[derive(Default, Serialize)]
+pub struct DedupeResult {
+    pub removed: Vec<PlacedPackage>, // Copies that are not needed, or that another version took the place of
+    pub added: Vec<PlacedPackage>,
+}
+
+/// How much disk an installed version takes, output by `click du`.
+#[derive(Serialize)]
+pub struct PackageUsage {
+    pub package: String, // Formatted as package@version
+    pub size: u64, // Of its files, which are stored once in the cache however often it is installed
+    pub copies: usize, // How many places in node_modules it is installed at
+}
+
+/// A package installed at more than one version, output by `click du`.
+#[derive(Serialize)]
+pub struct DuplicatedPackage {
+    pub name: String,
+    pub versions: Vec<String>,
+    pub size: u64, // Of every version together
+}
+
+/// What `click du --json` found in node_modules.
+#[derive(Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskUsageResult {
+    pub size: u64,                   // Of every installed version, each counted once
+    pub packages: Vec<PackageUsage>, // Largest first
+    pub duplicates: Vec<DuplicatedPackage>,
+    pub dedupe_savings: u64, // Of the versions that `click dedupe` would no longer install
+    pub store_savings: u64, // What linking from the cache saves over copying a package to every place it is installed
+}
+
+/// A package changed by `click update --json`.
+#[derive(Serialize)]
+pub struct UpdatedPackage {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// How severe an advisory is, in the order the registry ranks them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Moderate,
+    High,
+    Critical,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 5] = [
+        Self::Info,
+        Self::Low,
+        Self::Moderate,
+        Self::High,
+        Self::Critical,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Low => "low",
+            Self::Moderate => "moderate",
+            Self::High => "high",
+            Self::Critical => "critical",
+        }
+    }
+
+    pub fn parse(severity: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|known| known.as_str() == severity)
+    }
+}
+
+/// A security advisory from the bulk advisory endpoint of the registry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Advisory {
+    pub id: u64,
+    pub title: String,
+    pub url: String,
+    pub severity: Severity,
+    #[serde(rename(deserialize = "vulnerable_versions"))]
+    pub vulnerable_versions: String, // The range of versions it affects
+}
+
+// Maps each package name to the advisories that affect any of the versions that were sent
+pub type BulkAdvisories = HashMap<String, Vec<Advisory>>;
+
+/// An installed package that an advisory affects, output by `click audit`.
+#[derive(Serialize)]
+pub struct Vulnerability {
+    pub package: String, // Formatted as package@version
+    pub advisory: Advisory,
+    pub paths: Vec<String>, // How it is depended on from package.json, such as `express > qs`
+}
+
+/// A package in package.json that `click audit fix` bumped.
+#[derive(Serialize)]
+pub struct AuditFix {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+    pub range: String, // Its range in package.json, which is only changed with --force
+    pub fixes: Vec<String>, // The vulnerable packages the bump clears, formatted as package@version
+}
+
+/// What `click audit fix --json` did.
+#[derive(Serialize)]
+pub struct AuditFixResult {
+    pub fixed: Vec<AuditFix>,
+    pub remaining: Vec<Vulnerability>, // Found by auditing again after the install
+}
+
+/// What `click audit --summary-only --json` prints, every severity with how many vulnerabilities have it.
+#[derive(Serialize)]
+pub struct AuditSummary {
+    pub vulnerabilities: usize,
+    pub severities: BTreeMap<Severity, usize>,
+}
+
+/// The packages under a license, output by `click licenses`.
+#[derive(Serialize)]
+pub struct LicenseGroup {
+    pub license: String, // The SPDX expression of the packages, or UNKNOWN if they have none
+    pub packages: Vec<String>, // Formatted as package@version
+}
+
+/// What `click licenses --json` found.
+#[derive(Serialize)]
+pub struct LicenseReport {
+    pub licenses: Vec<LicenseGroup>,
+    pub disallowed: Vec<LicenseGroup>, // The groups that --allow or --deny fail the command for
+}
+
+/// A check of the environment made by `click doctor`.
+#[derive(Serialize)]
+pub struct DoctorCheck {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+    pub fix: Option<String>, // What to do about it if it failed
+}
+
+/// What `click version` changed the version of the package from and to.
+#[derive(Serialize)]
+pub struct VersionBump {
+    pub from: String,
+    pub to: String,
+    pub tag: Option<String>, // The git tag created for it, None with --no-git-tag-version or outside of a repository
+}
+
+/// The body of a response the registry failed with, which has the reason in one of these fields.
+#[derive(Deserialize)]
+pub struct RegistryError {
+    pub error: Option<String>,
+    pub message: Option<String>,
+}
+
+/// Where to log in to a registry in the browser, and where to wait for the token once that is done.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebLogin {
+    pub login_url: String,
+    pub done_url: String,
+}
+
+/// The body of a successful login.
+#[derive(Deserialize)]
+pub struct TokenResponse {
+    pub token: String,
+}
+
+/// The results of a search of the registry, a page at a time.
+#[derive(Deserialize)]
+pub struct SearchResponse {
+    pub objects: Vec<SearchObject>,
+    pub total: u64, // Of every page
+}
+
+#[derive(Deserialize)]
+pub struct SearchObject {
+    pub package: SearchPackage,
+    pub score: SearchScore,
+    pub downloads: Option<SearchDownloads>, // Only sent by some registries
+}
+
+#[derive(Deserialize)]
+pub struct SearchPackage {
+    pub name: String,
+    pub version: String,
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SearchScore {
+    #[serde(rename = "final")]
+    pub final_score: f64, // From 0 to 1, the registry orders the results by it
+}
+
+#[derive(Deserialize)]
+pub struct SearchDownloads {
+    pub weekly: Option<u64>,
+}
+
+/// A release of click, from the `update-endpoint`, which answers the same as the GitHub releases API.
+#[derive(Deserialize)]
+pub struct Release {
+    pub tag_name: String, // The version released, with or without a leading `v`
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+/// How long installs took in one scenario of `click bench`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchTiming {
+    pub tool: String, // click, npm, yarn or pnpm
+    pub version: String,
+    pub scenario: String, // cold cache, warm cache or lockfile
+    pub mean_ms: u128,
+    pub min_ms: u128,
+    pub max_ms: u128,
+    pub runs_ms: Vec<u128>, // Of every install, in the order they were run
+}
+
+/// What `click bench --json` timed.
+#[derive(Serialize)]
+pub struct BenchResult {
+    pub runs: usize, // Of each scenario
+    pub timings: Vec<BenchTiming>,
+    pub skipped: Vec<String>, // Package managers to compare with that are not on PATH
+}
+
+/// What `click self-update --json` found and did.
+#[derive(Serialize)]
+pub struct SelfUpdateResult {
+    pub current: String,
+    pub latest: String,
+    pub updated: bool, // False if click is already the latest version, or with --check
+}
+
+/// A file in the tarball made by `click pack`.
+#[derive(Serialize)]
+pub struct PackedFile {
+    pub path: String, // Relative to the root of the package, without the `package/` directory of the tarball
+    pub size: u64,
+    pub mode: u32,
+}
+
+/// The tarball made by `click pack`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackResult {
+    pub name: String,
+    pub version: String,
+    pub filename: String,
+    pub size: u64,          // Of the tarball
+    pub unpacked_size: u64, // Of every file in it
+    pub shasum: String,
+    pub integrity: String,
+    pub files: Vec<PackedFile>,
+}
+
+/// What `click publish --json` uploaded.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishResult {
+    pub registry: String,
+    pub tag: String,
+    pub access: Option<String>, // None if the registry decides
+    #[serde(flatten)]
+    pub tarball: PackResult,
+}
+
+/// The registry `click login` and `click logout --json` changed the token of.
+#[derive(Serialize)]
+pub struct LoginResult {
+    pub registry: String,
+    pub npmrc: String, // The path of the user .npmrc the token was written to or removed from
+}
+
+/// A key listed by `click config list`, with the file it is set in.
+#[derive(Serialize)]
+pub struct ConfigSetting {
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>, // The section it is set in, only that command uses it
+    pub value: String,
+    pub source: String, // The path of the file, or default for a built in value
+}
+
+/// A key changed by `click config set` or `click config delete`.
+#[derive(Serialize)]
+pub struct ConfigChange {
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    pub value: Option<String>, // None once deleted
+    pub path: String,
+}
+
+/// A package copied to be edited by `click patch`.
+#[derive(Serialize)]
+pub struct PatchEdit {
+    pub package: String,
+    pub directory: String,
+}
+
+/// The patch saved by `click patch-commit`.
+#[derive(Serialize)]
+pub struct PatchCommit {
+    pub package: String,
+    pub path: Option<String>, // None if the package was not changed, and any patch it had was removed
+}
+
+/// A package found by `click search --json`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    pub name: String,
+    pub version: String,
+    pub description: Option<String>,
+    pub score: f64,
+    pub weekly_downloads: Option<u64>,
+}
+
+/// One step of a chain of dependencies, a package along with the range it was depended on with.
+#[derive(Serialize)]
+pub struct DependencyLink {
+    pub name: String,
+    pub range: String,   // As it is in the package.json that depends on it
+    pub version: String, // The version the range was locked to
+}
+
+/// A locked version of a package and every chain of dependencies from package.json down to it, output by `click why`.
+#[derive(Serialize)]
+pub struct InstallReason {
+    pub package: String,                  // Formatted as package@version
+    pub chains: Vec<Vec<DependencyLink>>, // Each starts at a package in package.json and ends at this one
+}
+
+/// A locked package in the graph output by `click graph`.
+#[derive(Serialize)]
+pub struct GraphNode {
+    pub id: String, // Formatted as package@version, or `.` for the project
+    pub name: String,
+    pub version: String,
+}
+
+/// A dependency of a package in the graph output by `click graph`.
+#[derive(Serialize)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    pub range: String, // The range the package depends on it with
+}
+
+/// The whole resolved dependency graph, output by `click graph`.
+#[derive(Serialize)]
+pub struct DependencyGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// A package with a newer version than the one installed, output by `click outdated`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutdatedPackage {
+    pub name: String,
+    pub current: Option<String>, // The locked version, None if it has not been installed
+    pub wanted: String,          // The newest version the range in package.json allows
+    pub latest_compatible: String, // The newest version with no breaking changes from the current one, by semver
+    pub latest: String,
+    pub workspace: String, // The package.json it is in, by the name of its workspace
+}
+
+/// What `click outdated --summary-only --json` prints, the outdated packages counted by how far the current version is
+/// behind the latest one by semver.
+#[derive(Default, Serialize)]
+pub struct OutdatedSummary {
+    pub outdated: usize,
+    pub patch: usize,
+    pub minor: usize,
+    pub major: usize,
+    pub missing: usize, // Not installed, so not behind by anything
+}
+
+/// What `click verify --json` checked.
+#[derive(Default, Serialize)]
+pub struct VerifyResult {
+    pub verified: Vec<String>, // Formatted as package@version
+    pub skipped: Vec<String>, // Installed before content integrity was recorded, so they could not be checked
+}
+
+/// What `click cache add --json` did with each spec.
+#[derive(Default, Serialize)]
+pub struct CacheAddResult {
+    pub added: Vec<String>,   // Formatted as package@version
+    pub skipped: Vec<String>, // Already cached
+}
+
+/// What `click cache verify --json` checked and removed.
+#[derive(Default, Serialize)]
+pub struct CacheVerifyResult {
+    pub verified: usize, // Tarballs and packages whose contents still match their hash
+    pub corrupt: Vec<String>, // Tarballs as algorithm/digest and packages as package@version
+    pub unreferenced: Vec<String>, // Not locked by any project in the cache index
+    pub reclaimed: u64,  // Bytes taken by what was removed
+}
+
+/// The projects that link packages from the cache, see CacheIndex.
+#[derive(Default, Serialize, Deserialize)]
+pub struct CacheReferences {
+    pub projects: BTreeSet<String>, // The directory of each project a lockfile has been written for
+}
+
+/// A package installed in node_modules and everything it depends on, output by `click list`.
+#[derive(Serialize)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: Option<String>, // None if the package is not installed even though it is depended on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>, // None if the package is not installed, linked, or was cached before it was recorded
+    #[serde(skip_serializing_if = "is_false")]
+    pub circular: bool, // Already listed above this package, so its dependencies are not listed again
+    pub dependencies: Vec<InstalledPackage>, // Empty past the depth limit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::versions::{Strategy, Versions};
+
+    fn package_data(versions: &[&str]) -> PackageData {
+        let versions = versions
+            .iter()
+            .map(|version| {
+                let version_data = serde_json::json!({
+                    "name": "aa",
+                    "version": version,
+                    "dist": { "tarball": format!("https://registry.test/aa/-/aa-{version}.tgz") },
+                });
+                (version.to_string(), version_data)
+            })
+            .collect::<serde_json::Map<_, _>>();
+
+        serde_json::from_value(serde_json::json!({ "versions": versions })).unwrap()
+    }
+
+    #[test]
+    fn drops_invalid_version_keys_and_resolves_the_rest() {
+        let package_data = package_data(&["1.0.0", " v1.1.0", "not-a-version", "1.2"]);
+
+        assert_eq!(package_data.invalid_versions.len(), 2);
+        assert!(package_data
+            .invalid_versions
+            .contains(&String::from("not-a-version")));
+        assert!(package_data.versions.contains_key("1.1.0"));
+
+        let range = Versions::parse_semantic_version("^1.0.0").unwrap();
+        let resolved = Versions::resolve_partial_version(
+            Some(&range),
+            &package_data.versions,
+            Strategy::Highest,
+        );
+        assert_eq!(resolved.unwrap(), "1.1.0");
+    }
+
+    #[test]
+    fn keeps_the_canonical_key_of_a_duplicate_version() {
+        let package_data = package_data(&["v1.2.3", "1.2.3", "=1.2.3"]);
+
+        assert_eq!(package_data.versions.len(), 1);
+        assert_eq!(package_data.versions["1.2.3"].version, "1.2.3");
+
+        let mut duplicate_versions = package_data.duplicate_versions;
+        duplicate_versions.sort();
+        assert_eq!(duplicate_versions, ["=1.2.3", "v1.2.3"]);
+    }
+}
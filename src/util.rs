@@ -1,31 +1,144 @@
 use std::{
+    fmt, fs,
     future::Future,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
     sync::atomic::{self, AtomicUsize},
-    thread::{self},
-    time::Duration,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use atomic::Ordering::SeqCst;
 use bytes::Bytes;
-use flate2::bufread::GzDecoder;
+use flate2::read::GzDecoder;
+use lazy_static::lazy_static;
 use tar::Archive;
-use tokio::task::JoinHandle;
+use tokio::{
+    sync::{mpsc::Receiver, Notify},
+    task::JoinHandle,
+};
 
 use crate::errors::CommandError;
 
-pub fn extract_tarball(bytes: Bytes, dest: String) -> Result<(), CommandError> {
-    let bytes = &bytes.to_vec()[..];
-    let gz = GzDecoder::new(bytes);
+/// Extracts a gzipped tarball as it is read, so that it never has to be held in memory.
+/// Like npm, entries are extracted into a `package` directory whatever the top directory of the tarball is named, and
+/// only files and directories are extracted so that a link can not point outside of the package.
+pub fn extract_tarball(reader: impl Read, dest: &Path) -> Result<(), CommandError> {
+    let gz = GzDecoder::new(reader);
     let mut archive = Archive::new(gz);
 
-    // All tarballs contain a /package directory to the module source, this should be removed later to keep things as clean as possible
-    archive
-        .unpack(&dest)
-        .map_err(CommandError::ExtractionFailed)
+    let package_directory = long_path(dest)
+        .map(|dest| dest.join("package"))
+        .map_err(CommandError::ExtractionFailed)?;
+    fs::create_dir_all(&package_directory).map_err(CommandError::ExtractionFailed)?;
+
+    for entry in archive.entries().map_err(CommandError::ExtractionFailed)? {
+        let mut entry = entry.map_err(CommandError::ExtractionFailed)?;
+        let entry_type = entry.header().entry_type();
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            continue;
+        }
+
+        let Some(path) = tarball_entry_path(&entry.path_bytes()) else {
+            continue;
+        };
+        let path = package_directory.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(CommandError::ExtractionFailed)?;
+        }
+
+        entry
+            .unpack(&path)
+            .map_err(CommandError::ExtractionFailed)?;
+    }
+
+    Ok(())
+}
+
+/// The path of a tarball entry within its package, without the top directory. Tarballs packed on Windows can have
+/// backslashes as separators. Returns None for the top directory itself, and for paths that would leave the package.
+fn tarball_entry_path(raw_path: &[u8]) -> Option<PathBuf> {
+    let raw_path = String::from_utf8_lossy(raw_path).replace('\\', "/");
+    let mut components = raw_path
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".");
+    components.next()?;
+
+    let mut path = PathBuf::new();
+    for component in components {
+        // Anything but a name, such as `..` or a drive on Windows, could point elsewhere
+        let mut parsed = Path::new(component).components();
+        match (parsed.next(), parsed.next()) {
+            (Some(Component::Normal(_)), None) => path.push(component),
+            _ => return None,
+        }
+    }
+
+    match path.as_os_str().is_empty() {
+        true => None,
+        false => Some(path),
+    }
+}
+
+/// Paths on Windows are limited to 260 characters unless they are verbatim, which deep dependency trees easily
+/// exceed, so they are made absolute with the `\\?\` prefix.
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> io::Result<PathBuf> {
+    let absolute = std::path::absolute(path)?;
+    let verbatim = absolute
+        .to_str()
+        .and_then(|absolute| match absolute.strip_prefix(r"\\") {
+            // Already verbatim, or a device path
+            Some(rest) if rest.starts_with(r"?\") || rest.starts_with(r".\") => None,
+            Some(share) => Some(format!(r"\\?\UNC\{share}")),
+            None => Some(format!(r"\\?\{absolute}")),
+        });
+
+    Ok(verbatim.map(PathBuf::from).unwrap_or(absolute))
+}
+
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> io::Result<PathBuf> {
+    Ok(path.to_path_buf())
+}
+
+/// Reads the chunks of a download sent from an async task, so that blocking code such as extraction can consume it as it arrives.
+/// The end of the download is reached once the sender is dropped.
+pub struct ChunkReader {
+    receiver: Receiver<Bytes>,
+    chunk: Bytes,
+}
+
+impl ChunkReader {
+    pub fn new(receiver: Receiver<Bytes>) -> Self {
+        Self {
+            receiver,
+            chunk: Bytes::new(),
+        }
+    }
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.chunk.is_empty() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => self.chunk = chunk,
+                None => return Ok(0),
+            }
+        }
+
+        let length = buf.len().min(self.chunk.len());
+        buf[..length].copy_from_slice(&self.chunk.split_to(length));
+
+        Ok(length)
+    }
 }
 
 pub static ACTIVE_TASKS: AtomicUsize = AtomicUsize::new(0);
 
+lazy_static! {
+    static ref TASKS_DONE: Notify = Notify::new(); // Notified whenever the last active task finishes
+}
+
 pub struct TaskAllocator;
 
 impl TaskAllocator {
@@ -34,8 +147,9 @@ impl TaskAllocator {
         T: Future + Send + 'static,
         T::Output: Send + 'static,
     {
+        // The task is counted before it is spawned, otherwise wait_until_done() could return before it starts
+        Self::increment_tasks();
         tokio::spawn(async move {
-            Self::increment_tasks();
             let future_result = future.await;
             Self::decrement_tasks();
 
@@ -48,8 +162,8 @@ impl TaskAllocator {
         F: FnOnce() -> R + Send + 'static,
         R: Send + 'static,
     {
+        Self::increment_tasks();
         tokio::task::spawn_blocking(move || {
-            Self::increment_tasks();
             let task_result = f();
             Self::decrement_tasks();
 
@@ -57,9 +171,19 @@ impl TaskAllocator {
         })
     }
 
-    pub fn block_until_done() {
-        while Self::task_count() != 0 {
-            thread::sleep(Duration::from_millis(1));
+    /// Waits for every task to finish, including ones spawned by tasks while waiting.
+    pub async fn wait_until_done() {
+        loop {
+            let tasks_done = TASKS_DONE.notified();
+            tokio::pin!(tasks_done);
+
+            // The notification is only received by waiters registered before it is sent, so register before checking
+            tasks_done.as_mut().enable();
+            if Self::task_count() == 0 {
+                return;
+            }
+
+            tasks_done.await;
         }
     }
 
@@ -68,10 +192,85 @@ impl TaskAllocator {
     }
 
     fn decrement_tasks() {
-        ACTIVE_TASKS.fetch_sub(1, SeqCst);
+        if ACTIVE_TASKS.fetch_sub(1, SeqCst) == 1 {
+            TASKS_DONE.notify_waiters();
+        }
     }
 
     fn task_count() -> usize {
         ACTIVE_TASKS.load(SeqCst)
     }
 }
+
+/// Formats a number of bytes the way npm does, with decimal units.
+pub struct Size(pub u64);
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 4] = ["kB", "MB", "GB", "TB"];
+
+        if self.0 < 1000 {
+            return write!(f, "{} B", self.0);
+        }
+
+        let mut size = self.0 as f64;
+        let mut unit = UNITS[0];
+        for next_unit in UNITS {
+            size /= 1000.0;
+            unit = next_unit;
+            if size < 1000.0 {
+                break;
+            }
+        }
+
+        write!(f, "{size:.1} {unit}")
+    }
+}
+
+/// Symlinks are not followed, so packages linked from the cache are only counted once.
+pub fn directory_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| Some((entry.path(), entry.file_type().ok()?)))
+        .map(|(path, file_type)| match file_type {
+            file_type if file_type.is_dir() => directory_size(&path),
+            file_type if file_type.is_file() => fs::metadata(&path).map_or(0, |meta| meta.len()),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// The current time in UTC such as `2024-01-31T12:00:00Z`.
+pub fn timestamp() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+
+    // The days since 1970-01-01 as a date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
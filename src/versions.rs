@@ -1,10 +1,10 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, fmt, str::FromStr};
 
 use semver::{BuildMetadata, Comparator, Op, Prerelease, Version, VersionReq};
 
 use crate::{
     errors::{CommandError, ParseError},
-    types::VersionData,
+    types::AbbreviatedVersionData,
 };
 
 pub const EMPTY_VERSION: Version = Version {
@@ -17,28 +17,254 @@ pub const EMPTY_VERSION: Version = Version {
 
 pub const LATEST: &str = "latest";
 
-type PackageDetails = (String, Option<Comparator>);
+type PackageDetails = (String, Option<VersionRange>);
+
+/// Which of the versions matching a range is picked.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Strategy {
+    #[default]
+    Highest,
+    Lowest, // Set with --prefer-lowest, to check that the lowest versions a range allows still work
+}
+
+/// How `click version` changes the version of a package, the same increments npm has.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VersionIncrement {
+    Major,
+    Minor,
+    Patch,
+    Premajor,
+    Preminor,
+    Prepatch,
+    Prerelease,
+    Exact(Version), // Set to this version, such as with `click version 2.0.0`
+}
+
+impl VersionIncrement {
+    pub fn parse(increment: &str) -> Option<Self> {
+        Some(match increment {
+            "major" => Self::Major,
+            "minor" => Self::Minor,
+            "patch" => Self::Patch,
+            "premajor" => Self::Premajor,
+            "preminor" => Self::Preminor,
+            "prepatch" => Self::Prepatch,
+            "prerelease" => Self::Prerelease,
+            version => {
+                Self::Exact(Version::from_str(version.strip_prefix('v').unwrap_or(version)).ok()?)
+            }
+        })
+    }
+
+    /// Returns the version after the increment. A prerelease is released by the increment it is a prerelease of, so
+    /// `1.0.0-beta.1` becomes `1.0.0` with major, and its last number is incremented with prerelease. The prerelease
+    /// identifier starts with `preid`, such as `1.0.1-beta.0`, or is only the number without it.
+    pub fn apply(&self, version: &Version, preid: Option<&str>) -> Version {
+        let is_prerelease = !version.pre.is_empty();
+        let release = |major: u64, minor: u64, patch: u64| Version::new(major, minor, patch);
+        let prerelease = |mut version: Version, pre: String| {
+            version.pre = Prerelease::new(&pre).unwrap_or(Prerelease::EMPTY);
+            version
+        };
+        let first_prerelease = |version: Version| {
+            let pre = preid.map_or(String::from("0"), |preid| format!("{preid}.0"));
+            prerelease(version, pre)
+        };
+
+        let (major, minor, patch) = (version.major, version.minor, version.patch);
+        match self {
+            Self::Major if is_prerelease && minor == 0 && patch == 0 => release(major, 0, 0),
+            Self::Major => release(major + 1, 0, 0),
+            Self::Minor if is_prerelease && patch == 0 => release(major, minor, 0),
+            Self::Minor => release(major, minor + 1, 0),
+            Self::Patch if is_prerelease => release(major, minor, patch),
+            Self::Patch => release(major, minor, patch + 1),
+            Self::Premajor => first_prerelease(release(major + 1, 0, 0)),
+            Self::Preminor => first_prerelease(release(major, minor + 1, 0)),
+            Self::Prepatch => first_prerelease(release(major, minor, patch + 1)),
+            Self::Prerelease if !is_prerelease => {
+                first_prerelease(release(major, minor, patch + 1))
+            }
+            Self::Prerelease => {
+                let mut identifiers = version
+                    .pre
+                    .split('.')
+                    .map(str::to_string)
+                    .collect::<Vec<_>>();
+                if preid.is_some_and(|preid| identifiers.first().map(String::as_str) != Some(preid))
+                {
+                    return first_prerelease(release(major, minor, patch));
+                }
+
+                match identifiers
+                    .iter_mut()
+                    .rev()
+                    .find_map(|identifier| Some((identifier.parse::<u64>().ok()?, identifier)))
+                {
+                    Some((number, identifier)) => *identifier = (number + 1).to_string(),
+                    None => identifiers.push(String::from("0")),
+                }
+
+                prerelease(release(major, minor, patch), identifiers.join("."))
+            }
+            Self::Exact(version) => version.clone(),
+        }
+    }
+}
+
+/// An npm range such as `>=1.2.0 <2.0.0` or `^1 || ^2`. Each requirement is a set of comparators that all have to match,
+/// and a version is in the range if any of the requirements match it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionRange {
+    requirements: Vec<VersionReq>,
+}
+
+impl VersionRange {
+    /// Like npm, a prerelease only matches if a comparator of the same requirement is a prerelease of the same
+    /// major.minor.patch, so `^1.1.0-beta.0` matches `1.1.0-beta.1` but not `1.2.0-beta.1`, and `<2.0.0` never matches
+    /// `2.0.0-beta.1` even though it is lower.
+    pub fn matches(&self, version: &Version) -> bool {
+        self.requirements
+            .iter()
+            .any(|requirement| requirement.matches(version))
+    }
+
+    /// Returns the comparator if the range is made of only one, which is the only shape of range that can be resolved
+    /// without every available version, see Versions::resolve_full_version().
+    pub fn comparator(&self) -> Option<&Comparator> {
+        match self.requirements.as_slice() {
+            [requirement] if requirement.comparators.len() == 1 => requirement.comparators.first(),
+            _ => None,
+        }
+    }
+
+    /// Ranges that match any version, such as `*` or an empty range, have no comparators.
+    pub fn is_any(&self) -> bool {
+        self.requirements
+            .iter()
+            .any(|requirement| requirement.comparators.is_empty())
+    }
+
+    pub fn includes_prerelease(&self) -> bool {
+        self.requirements
+            .iter()
+            .flat_map(|requirement| requirement.comparators.iter())
+            .any(|comparator| !comparator.pre.is_empty())
+    }
+}
+
+impl fmt::Display for VersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, requirement) in self.requirements.iter().enumerate() {
+            if index > 0 {
+                write!(f, " || ")?;
+            }
+
+            match requirement.comparators.is_empty() {
+                true => write!(f, "*")?,
+                false => {
+                    let comparators = requirement
+                        .comparators
+                        .iter()
+                        .map(|comparator| comparator.to_string())
+                        .collect::<Vec<_>>();
+                    write!(f, "{}", comparators.join(" "))?
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
 
 pub struct Versions;
 impl Versions {
+    /// Splits a spec formatted as package@range, where the range defaults to latest if there is none.
+    /// Scoped packages such as `@types/node@18` start with an `@` too, so it is only split on the `@` after the name.
     pub fn parse_raw_package_details(details: String) -> (String, String) {
-        let mut split = details.split('@');
+        let scope_length = match details.starts_with('@') {
+            true => 1,
+            false => 0,
+        };
+
+        match details[scope_length..].split_once('@') {
+            Some((name, version_raw)) => (
+                details[..scope_length + name.len()].to_string(),
+                version_raw.to_string(),
+            ),
+            None => (details, LATEST.to_string()),
+        }
+    }
 
-        let name = split
-            .next()
-            .expect("Provided package name is empty")
-            .to_string();
+    /// Parses an npm range, where requirements are separated by `||`, the comparators of a requirement by spaces,
+    /// and `1.2.3 - 2.3.4` is the same as `>=1.2.3 <=2.3.4`.
+    pub fn parse_semantic_version(raw_version: &str) -> Result<VersionRange, ParseError> {
+        let requirements = raw_version
+            .split("||")
+            .map(Self::parse_requirement)
+            .collect::<Result<Vec<_>, _>>()?;
 
-        match split.next() {
-            Some(version_raw) => (name, version_raw.to_string()),
-            None => (name, LATEST.to_string()),
+        Ok(VersionRange { requirements })
+    }
+
+    fn parse_requirement(raw_requirement: &str) -> Result<VersionReq, ParseError> {
+        let mut tokens = raw_requirement.split_whitespace().collect::<Vec<_>>();
+
+        if let [lowest, "-", highest] = tokens.as_slice() {
+            let comparators = vec![
+                Self::parse_comparator(&format!(">={lowest}"))?,
+                Self::parse_comparator(&format!("<={highest}"))?,
+            ];
+            return Ok(VersionReq {
+                comparators: comparators.into_iter().flatten().collect(),
+            });
         }
+
+        // npm allows a space between an operator and its version, such as `>= 1.2.0`
+        let mut comparators = Vec::new();
+        while !tokens.is_empty() {
+            let token = tokens.remove(0);
+            let raw_comparator = match token.chars().all(|char| "<>=^~".contains(char)) {
+                true if !tokens.is_empty() => format!("{token}{}", tokens.remove(0)),
+                _ => token.to_string(),
+            };
+
+            comparators.extend(Self::parse_comparator(&raw_comparator)?);
+        }
+
+        Ok(VersionReq { comparators })
     }
 
-    pub fn parse_semantic_version(raw_version: &str) -> Result<Comparator, ParseError> {
+    /// Returns None for wildcards that match any version, such as `*` or `x`.
+    fn parse_comparator(raw_comparator: &str) -> Result<Option<Comparator>, ParseError> {
         let mut version =
-            VersionReq::parse(raw_version).map_err(ParseError::InvalidVersionNotation)?;
-        Ok(version.comparators.remove(0))
+            VersionReq::parse(raw_comparator).map_err(ParseError::InvalidVersionNotation)?;
+        if version.comparators.is_empty() {
+            return Ok(None);
+        }
+
+        let mut comparator = version.comparators.remove(0);
+
+        // The semver crate follows Cargo, where a bare version is a caret requirement. For npm `1.2.3` is exact,
+        // and `1.2` or `1` are the same as `1.2.x` and `1.x`, which have the range of a tilde requirement.
+        if comparator.op == Op::Caret && !raw_comparator.starts_with('^') {
+            comparator.op = match comparator.patch {
+                Some(_) => Op::Exact,
+                None => Op::Tilde,
+            };
+        }
+
+        Ok(Some(comparator))
+    }
+
+    /// Dist-tags such as `beta` or `next` name a version in the packument rather than a range, which they are told apart from
+    /// by not parsing as one. Like npm, tags have to start with a letter, so a mistyped range is not looked up as a tag.
+    pub fn is_dist_tag(raw_version: &str) -> bool {
+        raw_version.starts_with(|char: char| char.is_ascii_alphabetic())
+            && raw_version
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || matches!(char, '-' | '_' | '.'))
+            && Self::parse_semantic_version(raw_version).is_err()
     }
 
     pub fn parse_semantic_package_details(details: String) -> Result<PackageDetails, ParseError> {
@@ -48,19 +274,24 @@ impl Versions {
             return Ok((name, None));
         }
 
-        let comparator = Self::parse_semantic_version(&version_raw)?;
-        Ok((name, Some(comparator)))
+        let range = Self::parse_semantic_version(&version_raw)?;
+        Ok((name, Some(range)))
     }
 
     /// If a version comparator has the major, patch and minor available a string version will be returned with the resolved version.
     /// This version string can be used to retrieve a package version from the NPM registry.
     /// If the version is not resolvable without requesting the full package data, None will be returned.
-    /// None will also be returned if the version operator is Op::Less (<?.?.?) because we need all versions to get the latest version less than this
-    pub fn resolve_full_version(semantic_version: Option<&Comparator>) -> Option<String> {
+    /// None will also be returned for Op::Less and Op::LessEq (<?.?.?, <=?.?.?) because we need all versions to get the latest version below
+    /// the bound, which does not have to be published itself,
+    /// and for Op::Caret and Op::Tilde as they match a range whose upper bound depends on the leading zeros (^0.2.3 is >=0.2.3 <0.3.0,
+    /// ^0.0.3 is >=0.0.3 <0.0.4), so the highest matching version is picked by resolve_partial_version() using Comparator::matches().
+    /// Ranges of more than one comparator always need every version.
+    pub fn resolve_full_version(semantic_version: Option<&VersionRange>) -> Option<String> {
         let latest = LATEST.to_string();
 
         let semantic_version = match semantic_version {
-            Some(semantic_version) => semantic_version,
+            Some(range) if range.is_any() => return Some(latest),
+            Some(range) => range.comparator()?,
             None => return Some(latest),
         };
 
@@ -71,7 +302,13 @@ impl Versions {
 
         match semantic_version.op {
             Op::Greater | Op::GreaterEq | Op::Wildcard => Some(latest),
-            Op::Exact | Op::LessEq | Op::Tilde | Op::Caret => Some(Self::stringify_from_numbers(
+            // Prereleases are only matched exactly, such as the version a dist-tag like `beta` names
+            Op::Exact if !semantic_version.pre.is_empty() => Some(format!(
+                "{}-{}",
+                Self::stringify_from_numbers(semantic_version.major, minor, patch),
+                semantic_version.pre
+            )),
+            Op::Exact => Some(Self::stringify_from_numbers(
                 semantic_version.major,
                 minor,
                 patch,
@@ -80,13 +317,14 @@ impl Versions {
         }
     }
 
-    /// Should only be executed if the version comparator is missing a minor or patch.
-    /// This can be checked with resolve_full_version() which will return None if this is the case.
+    /// Should only be executed if resolve_full_version() returned None, or to pick the lowest version, as every
+    /// available version is needed to resolve it.
     pub fn resolve_partial_version(
-        semantic_version: Option<&Comparator>,
-        available_versions: &HashMap<String, VersionData>,
+        semantic_version: Option<&VersionRange>,
+        available_versions: &HashMap<String, AbbreviatedVersionData>,
+        strategy: Strategy,
     ) -> Result<String, CommandError> {
-        let semantic_version = semantic_version
+        let range = semantic_version
             .expect("Function should not be called as the version can be resolved to 'latest'");
 
         let mut versions = available_versions.iter().collect::<Vec<_>>();
@@ -94,36 +332,29 @@ impl Versions {
         // Serde scambles the order of the hashmap so we need to reorder it to find the latest versions
         Self::sort(&mut versions);
 
-        if semantic_version.op == Op::Less {
-            // Annoyingly we can't put `if let` and other comparisons on the same line as it's unstable as of writing
-            if let (Some(minor), Some(patch)) = (semantic_version.minor, semantic_version.patch) {
-                let version_position = versions
-                    .iter()
-                    .position(|(ver, _)| {
-                        ver == &&Self::stringify_from_numbers(semantic_version.major, minor, patch)
-                    })
-                    .ok_or(CommandError::InvalidVersion)?;
-
-                return Ok(versions
-                    .get(version_position - 1)
-                    .expect("Invalid version provided (no smaller versions available)")
-                    .0
-                    .to_string());
-            }
-        }
-
-        // Do in reverse order so we find the latest compatible version.
-        for (version_str, _) in versions.iter().rev() {
-            let version = Version::from_str(version_str.as_str()).unwrap_or(EMPTY_VERSION);
+        let mut matching = versions.iter().filter(|(version_str, _)| {
+            range.matches(&Version::from_str(version_str).unwrap_or(EMPTY_VERSION))
+        });
+        let picked = match strategy {
+            Strategy::Highest => matching.next_back(),
+            Strategy::Lowest => matching.next(),
+        };
 
-            if semantic_version.matches(&version) {
-                return Ok(version_str.to_string());
-            }
+        if let Some((version_str, _)) = picked {
+            return Ok(version_str.to_string());
         }
 
         Err(CommandError::InvalidVersion)
     }
 
+    /// Compares two version strings by semver precedence, unparseable versions are treated as 0.0.0.
+    pub fn is_newer(version: &str, than: &str) -> bool {
+        let version = Version::from_str(version).unwrap_or(EMPTY_VERSION);
+        let than = Version::from_str(than).unwrap_or(EMPTY_VERSION);
+
+        version > than
+    }
+
     pub fn stringify(name: &String, version: &String) -> String {
         format!("{}@{}", name, version)
     }
@@ -136,12 +367,255 @@ impl Versions {
         }
     }
 
-    // This might not be effective for versions that include a prerelease in the version (experimental, canary etc)
-    fn sort(versions_vec: &mut [(&String, &VersionData)]) {
-        versions_vec.sort_by(|a, b| a.0.cmp(b.0))
+    /// Sorts by semver precedence, so `10.0.0` comes after `9.0.0` and `1.0.0-beta.2` comes after `1.0.0-beta.1`
+    /// but before `1.0.0`. Unparseable versions are treated as 0.0.0.
+    fn sort(versions_vec: &mut [(&String, &AbbreviatedVersionData)]) {
+        versions_vec
+            .sort_by_cached_key(|(version, _)| Version::from_str(version).unwrap_or(EMPTY_VERSION))
     }
 
     pub fn stringify_from_numbers(major: u64, minor: u64, patch: u64) -> String {
         format!("{}.{}.{}", major, minor, patch)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(details: &str) -> (String, String) {
+        Versions::parse_raw_package_details(details.to_string())
+    }
+
+    #[test]
+    fn parses_bare_name() {
+        assert_eq!(parse("react"), ("react".to_string(), LATEST.to_string()));
+    }
+
+    #[test]
+    fn parses_name_with_range() {
+        assert_eq!(
+            parse("react@^18.2.0"),
+            ("react".to_string(), "^18.2.0".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_scoped_name() {
+        assert_eq!(
+            parse("@types/node"),
+            ("@types/node".to_string(), LATEST.to_string())
+        );
+    }
+
+    #[test]
+    fn parses_scoped_name_with_range() {
+        assert_eq!(
+            parse("@types/node@18"),
+            ("@types/node".to_string(), "18".to_string())
+        );
+    }
+
+    fn available_versions(versions: &[&str]) -> HashMap<String, AbbreviatedVersionData> {
+        versions
+            .iter()
+            .map(|version| {
+                let data = serde_json::json!({
+                    "name": "package",
+                    "version": version,
+                    "dist": { "tarball": "" },
+                });
+                (
+                    version.to_string(),
+                    serde_json::from_value(data).expect("Valid version data"),
+                )
+            })
+            .collect()
+    }
+
+    fn resolve_with(range: &str, versions: &[&str], strategy: Strategy) -> String {
+        let comparator = Versions::parse_semantic_version(range).expect("Valid range");
+        Versions::resolve_partial_version(
+            Some(&comparator),
+            &available_versions(versions),
+            strategy,
+        )
+        .expect("Version resolves")
+    }
+
+    fn resolve(range: &str, versions: &[&str]) -> String {
+        resolve_with(range, versions, Strategy::Highest)
+    }
+
+    #[test]
+    fn sorts_by_semver_precedence() {
+        let available =
+            available_versions(&["10.0.0", "9.0.0", "1.0.0", "1.0.0-beta.2", "1.0.0-beta.10"]);
+        let mut versions = available.iter().collect::<Vec<_>>();
+        Versions::sort(&mut versions);
+
+        let sorted = versions
+            .iter()
+            .map(|(version, _)| version.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sorted,
+            ["1.0.0-beta.2", "1.0.0-beta.10", "1.0.0", "9.0.0", "10.0.0"]
+        );
+    }
+
+    #[test]
+    fn resolves_newest_matching_version() {
+        assert_eq!(
+            resolve("^9.0.0", &["9.0.0", "9.10.0", "9.9.0", "10.0.0"]),
+            "9.10.0"
+        );
+    }
+
+    #[test]
+    fn skips_prereleases_unless_requested() {
+        let versions = ["1.0.0", "1.1.0-beta.1", "2.0.0-rc.1"];
+        assert_eq!(resolve(">=1.0.0", &versions), "1.0.0");
+        assert_eq!(resolve("^1.1.0-beta.0", &versions), "1.1.0-beta.1");
+    }
+
+    #[test]
+    fn resolves_less_than_without_the_bound_published() {
+        let versions = ["1.0.0", "1.2.0", "1.4.0-beta.1", "2.0.0-beta.1", "2.1.0"];
+        assert_eq!(resolve("<1.5.0", &versions), "1.2.0");
+        assert_eq!(resolve("<2.0.0", &versions), "1.2.0");
+        assert_eq!(resolve("<=2.0.0", &versions), "1.2.0");
+        assert_eq!(resolve("<2.0.0-beta.2", &versions), "2.0.0-beta.1");
+
+        let range = Versions::parse_semantic_version("<1.0.0").expect("Valid range");
+        let resolved = Versions::resolve_partial_version(
+            Some(&range),
+            &available_versions(&versions),
+            Strategy::Highest,
+        );
+        assert!(matches!(resolved, Err(CommandError::InvalidVersion)));
+    }
+
+    #[test]
+    fn resolves_lowest_matching_version() {
+        let versions = ["1.0.0", "1.10.0", "1.2.0", "2.0.0-beta.1", "2.0.0"];
+        let resolve_lowest = |range| resolve_with(range, &versions, Strategy::Lowest);
+
+        assert_eq!(resolve_lowest("^1.1.0"), "1.2.0");
+        assert_eq!(resolve_lowest(">=1.5.0"), "1.10.0");
+        assert_eq!(resolve_lowest("*"), "1.0.0");
+        assert_eq!(resolve_lowest("^2.0.0-beta.0"), "2.0.0-beta.1");
+    }
+
+    #[test]
+    fn only_matches_prereleases_of_the_same_version() {
+        let versions = ["1.0.0", "1.1.0-beta.1", "1.0.1-rc.1"];
+        assert_eq!(resolve(">=1.0.0-beta.1", &versions), "1.0.0");
+        assert_eq!(
+            resolve(">=1.0.0-beta.1 || ~1.0.1-rc.0", &versions),
+            "1.0.1-rc.1"
+        );
+    }
+
+    #[test]
+    fn caret_ranges_stop_at_the_first_non_zero_part() {
+        let matches = |range: &str, version: &str| {
+            Versions::parse_semantic_version(range)
+                .expect("Valid range")
+                .matches(&Version::from_str(version).unwrap())
+        };
+
+        assert!(matches("^0.2.3", "0.2.3"));
+        assert!(matches("^0.2.3", "0.2.9"));
+        assert!(!matches("^0.2.3", "0.3.0"));
+        assert!(matches("^0.0.3", "0.0.3"));
+        assert!(!matches("^0.0.3", "0.0.4"));
+        assert!(!matches("^0.0.3", "0.1.0"));
+
+        assert_eq!(resolve("^0.2.3", &["0.2.3", "0.2.9", "0.3.0"]), "0.2.9");
+        assert_eq!(resolve("^0.0.3", &["0.0.3", "0.0.4", "0.1.0"]), "0.0.3");
+    }
+
+    #[test]
+    fn bare_versions_are_exact_and_partial_ones_are_tilde_ranges() {
+        let op = |range: &str| {
+            let range = Versions::parse_semantic_version(range).expect("Valid range");
+            range.comparator().expect("Single comparator").op
+        };
+
+        assert_eq!(op("1.2.3"), Op::Exact);
+        assert_eq!(op("=1.2.3"), Op::Exact);
+        assert_eq!(op("1.2"), Op::Tilde);
+        assert_eq!(op("1"), Op::Tilde);
+        assert_eq!(op("^1.2"), Op::Caret);
+
+        let versions = ["1.2.3", "1.2.9", "1.3.0", "2.0.0"];
+        assert_eq!(resolve("1.2.3", &versions), "1.2.3");
+        assert_eq!(resolve("1.2", &versions), "1.2.9");
+        assert_eq!(resolve("1", &versions), "1.3.0");
+        assert_eq!(resolve("^1.2", &versions), "1.3.0");
+    }
+
+    #[test]
+    fn resolves_ranges_with_multiple_comparators() {
+        let versions = ["1.1.0", "1.2.0", "1.9.0", "2.0.0"];
+        assert_eq!(resolve(">=1.2.0 <2.0.0", &versions), "1.9.0");
+        assert_eq!(resolve(">= 1.0.0 < 1.2.0", &versions), "1.1.0");
+        assert_eq!(resolve("1.1.0 - 1.2", &versions), "1.2.0");
+    }
+
+    #[test]
+    fn resolves_ranges_with_alternatives() {
+        let versions = ["1.5.0", "2.3.0", "3.0.0"];
+        assert_eq!(resolve("^1 || ^2", &versions), "2.3.0");
+        assert_eq!(resolve("^1.0.0 || >=4", &versions), "1.5.0");
+    }
+
+    #[test]
+    fn only_resolves_single_comparators_without_versions() {
+        let resolve_full = |range: &str| {
+            let range = Versions::parse_semantic_version(range).expect("Valid range");
+            Versions::resolve_full_version(Some(&range))
+        };
+
+        assert_eq!(resolve_full("1.2.3"), Some("1.2.3".to_string()));
+        assert_eq!(resolve_full("*"), Some(LATEST.to_string()));
+        assert_eq!(resolve_full("1.2.3 || 1.2.4"), None);
+        assert_eq!(resolve_full("<=1.2.3"), None);
+    }
+
+    #[test]
+    fn parses_scoped_name_with_dist_tag() {
+        assert_eq!(
+            parse("@vue/cli@next"),
+            ("@vue/cli".to_string(), "next".to_string())
+        );
+    }
+
+    #[test]
+    fn increments_versions() {
+        let increment = |version: &str, increment: &str, preid: Option<&str>| {
+            let version = Version::from_str(version).unwrap();
+            VersionIncrement::parse(increment)
+                .unwrap()
+                .apply(&version, preid)
+                .to_string()
+        };
+
+        assert_eq!(increment("1.2.3", "patch", None), "1.2.4");
+        assert_eq!(increment("1.2.3", "minor", None), "1.3.0");
+        assert_eq!(increment("1.2.3", "major", None), "2.0.0");
+        assert_eq!(increment("2.0.0-beta.1", "major", None), "2.0.0");
+        assert_eq!(increment("1.2.3", "premajor", Some("rc")), "2.0.0-rc.0");
+        assert_eq!(increment("1.2.3", "prerelease", None), "1.2.4-0");
+        assert_eq!(
+            increment("1.2.4-beta.1", "prerelease", None),
+            "1.2.4-beta.2"
+        );
+        assert_eq!(
+            increment("1.2.4-beta.1", "prerelease", Some("rc")),
+            "1.2.4-rc.0"
+        );
+        assert_eq!(increment("1.2.3", "v3.0.0", None), "3.0.0");
+    }
+}
@@ -0,0 +1,438 @@
+use std::{
+    collections::HashSet,
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use semver::Version;
+
+use crate::{
+    errors::CommandError,
+    installer::MODULES_DIRECTORY,
+    manifest::{Manifest, OrderedJson, DEPENDENCY_FIELDS, PACKAGE_JSON_PATH},
+    source::{Source, WorkspaceRange},
+};
+
+/// A package of a monorepo, listed by the `workspaces` field of the root package.json.
+pub struct Workspace {
+    pub name: String,
+    pub path: PathBuf, // Relative to the root of the project
+    pub manifest: Manifest,
+}
+
+impl Workspace {
+    /// The version in its package.json, a workspace without one is taken to be `0.0.0` as it is never published.
+    pub fn version(&self) -> &str {
+        self.manifest.field("version").unwrap_or("0.0.0")
+    }
+
+    /// Returns true if any dependency field of the workspace lists the package, whatever its range.
+    pub fn depends_on(&self, package_name: &str) -> bool {
+        DEPENDENCY_FIELDS.iter().any(|field| {
+            self.manifest
+                .dependencies(field)
+                .iter()
+                .any(|(name, _)| name == package_name)
+        })
+    }
+}
+
+/// Workspaces are linked into the root node_modules by name, so they can depend on each other without being published.
+/// Their dependencies are installed along with those of the root, as Node finds them in the root node_modules too.
+pub struct Workspaces;
+impl Workspaces {
+    /// Finds every workspace of the project in the working directory, which is none if it is not a monorepo.
+    /// `workspaces` is either an array of patterns or, as Yarn allows, an object with them in `packages`.
+    /// Patterns can use `*` and `**`, and patterns starting with `!` exclude what they match.
+    pub fn discover() -> Result<Vec<Workspace>, CommandError> {
+        Self::discover_in(Path::new(""))
+    }
+
+    /// Finds the workspaces of the project in a directory, with their paths relative to it.
+    fn discover_in(root: &Path) -> Result<Vec<Workspace>, CommandError> {
+        let Some(manifest) = Manifest::read_from(&root.join(PACKAGE_JSON_PATH))? else {
+            return Ok(Vec::new());
+        };
+
+        let patterns = match manifest.json.get("workspaces") {
+            Some(OrderedJson::Array(patterns)) => patterns,
+            Some(workspaces) => match workspaces.get("packages") {
+                Some(OrderedJson::Array(patterns)) => patterns,
+                _ => return Ok(Vec::new()),
+            },
+            None => return Ok(Vec::new()),
+        };
+
+        let mut included = Vec::new();
+        let mut excluded = HashSet::new();
+        for pattern in patterns.iter().filter_map(OrderedJson::as_str) {
+            match pattern.strip_prefix('!') {
+                Some(pattern) => excluded.extend(Self::expand(root, pattern)),
+                None => included.extend(Self::expand(root, pattern)),
+            }
+        }
+
+        let mut workspaces: Vec<Workspace> = Vec::new();
+        for path in included {
+            if excluded.contains(&path) || workspaces.iter().any(|workspace| workspace.path == path)
+            {
+                continue;
+            }
+
+            let manifest = Manifest::read_from(&root.join(&path).join(PACKAGE_JSON_PATH))?
+                .expect("Workspaces are only matched if they have a package.json");
+            let name = manifest
+                .field("name")
+                .ok_or_else(|| CommandError::UnnamedWorkspace(path.display().to_string()))?
+                .to_string();
+
+            if workspaces.iter().any(|workspace| workspace.name == name) {
+                return Err(CommandError::DuplicateWorkspace(name));
+            }
+
+            workspaces.push(Workspace {
+                name,
+                path,
+                manifest,
+            });
+        }
+
+        workspaces.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(workspaces)
+    }
+
+    /// The workspaces of the monorepo the working directory is a workspace of, found from the closest directory above
+    /// it that lists it as one. None if it is not in a monorepo.
+    fn discover_siblings() -> Result<Vec<Workspace>, CommandError> {
+        let directory = env::current_dir()
+            .and_then(fs::canonicalize)
+            .map_err(CommandError::FailedToReadFile)?;
+
+        for root in directory.ancestors() {
+            let workspaces = Self::discover_in(root)?;
+            if workspaces.iter().any(|workspace| {
+                fs::canonicalize(root.join(&workspace.path)).is_ok_and(|path| path == directory)
+            }) {
+                return Ok(workspaces);
+            }
+        }
+
+        Ok(Vec::new())
+    }
+
+    /// The workspace a dependency on `workspace:` refers to. Fails if there is no workspace of that name, or if its
+    /// version does not match the range.
+    pub fn resolve<'a>(
+        workspaces: &'a [Workspace],
+        package_name: &str,
+        range: &WorkspaceRange,
+    ) -> Result<&'a Workspace, CommandError> {
+        let workspace = workspaces
+            .iter()
+            .find(|workspace| workspace.name == package_name)
+            .ok_or_else(|| CommandError::UnknownWorkspace(package_name.to_string()))?;
+
+        let version = workspace.version();
+        match Version::parse(version) {
+            Ok(parsed) if range.matches(&parsed) => Ok(workspace),
+            _ => Err(CommandError::WorkspaceVersionMismatch(
+                package_name.to_string(),
+                range.to_string(),
+                version.to_string(),
+            )),
+        }
+    }
+
+    /// The package.json of the workspace in the working directory as it is packed and published, with every
+    /// `workspace:` range replaced by the range of the version it resolves to, as nothing outside of the monorepo can
+    /// resolve them. None if it has no such ranges, so that it is packed as it is.
+    pub fn published_manifest(manifest: &Manifest) -> Result<Option<Manifest>, CommandError> {
+        let references =
+            DEPENDENCY_FIELDS
+                .iter()
+                .flat_map(|field| {
+                    manifest.dependencies(field).into_iter().filter_map(
+                        move |(package_name, range)| match Source::from_spec(&range) {
+                            Some(Source::Workspace(range)) => Some((*field, package_name, range)),
+                            _ => None,
+                        },
+                    )
+                })
+                .collect::<Vec<_>>();
+        if references.is_empty() {
+            return Ok(None);
+        }
+
+        let workspaces = Self::discover_siblings()?;
+        let mut published = manifest.clone();
+        for (field, package_name, range) in references {
+            let workspace = Self::resolve(&workspaces, &package_name, &range)?;
+            let Some(OrderedJson::Object(dependencies)) = published.json.get_mut(field) else {
+                continue;
+            };
+
+            for (name, value) in dependencies.iter_mut() {
+                if *name == package_name {
+                    *value = OrderedJson::String(range.published(workspace.version()));
+                }
+            }
+        }
+
+        Ok(Some(published))
+    }
+
+    /// Keeps the workspaces that any of the selectors match, given with `-w` or `--filter`. A selector starting with `.`
+    /// is a path pattern such as `./packages/*`, anything else is a name that can use `*` such as `@acme/*`.
+    /// `...` in front of a selector also keeps the workspaces that depend on those it matches, as with `...@acme/core`,
+    /// and `...` after it those they depend on, directly or through other workspaces either way.
+    /// Fails if a selector matches no workspace, as that is usually a typo.
+    pub fn filter(
+        workspaces: Vec<Workspace>,
+        selectors: &[String],
+    ) -> Result<Vec<Workspace>, CommandError> {
+        let mut selected = vec![false; workspaces.len()];
+
+        for selector in selectors {
+            let (dependents, pattern) = match selector.strip_prefix("...") {
+                Some(pattern) => (true, pattern),
+                None => (false, selector.as_str()),
+            };
+            let (dependencies, pattern) = match pattern.strip_suffix("...") {
+                Some(pattern) => (true, pattern),
+                None => (false, pattern),
+            };
+
+            let paths = match pattern.starts_with('.') {
+                true => Self::expand(Path::new(""), pattern),
+                false => Vec::new(),
+            };
+
+            let mut matched = workspaces
+                .iter()
+                .map(|workspace| {
+                    paths.contains(&workspace.path)
+                        || Self::matches_wildcard(pattern, &workspace.name)
+                })
+                .collect::<Vec<_>>();
+
+            if !matched.contains(&true) {
+                return Err(CommandError::WorkspaceNotFound(selector.to_string()));
+            }
+
+            if dependents {
+                Self::include_related(&workspaces, &mut matched, |workspace, other| {
+                    workspace.depends_on(&other.name)
+                });
+            }
+            if dependencies {
+                Self::include_related(&workspaces, &mut matched, |workspace, other| {
+                    other.depends_on(&workspace.name)
+                });
+            }
+
+            for (selected, matched) in selected.iter_mut().zip(matched) {
+                *selected |= matched;
+            }
+        }
+
+        Ok(workspaces
+            .into_iter()
+            .zip(selected)
+            .filter_map(|(workspace, selected)| selected.then_some(workspace))
+            .collect())
+    }
+
+    /// Includes every workspace that `related` relates to one that is included, until there are no more, where
+    /// `related(workspace, other)` is whether the workspace is included because the other is.
+    fn include_related(
+        workspaces: &[Workspace],
+        included: &mut [bool],
+        related: impl Fn(&Workspace, &Workspace) -> bool,
+    ) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for (index, workspace) in workspaces.iter().enumerate() {
+                if included[index] {
+                    continue;
+                }
+
+                if workspaces
+                    .iter()
+                    .zip(included.iter())
+                    .any(|(other, included)| *included && related(workspace, other))
+                {
+                    included[index] = true;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    /// Orders workspaces so that each comes after the workspaces it depends on, such as for building them.
+    /// Workspaces that do not depend on each other keep the order they were in.
+    pub fn topological_order(
+        mut workspaces: Vec<Workspace>,
+    ) -> Result<Vec<Workspace>, CommandError> {
+        let mut ordered: Vec<Workspace> = Vec::new();
+
+        while !workspaces.is_empty() {
+            // Only dependencies that are being ordered matter, any others are not waited for
+            let ready = workspaces.iter().position(|workspace| {
+                !workspaces
+                    .iter()
+                    .any(|other| other.name != workspace.name && workspace.depends_on(&other.name))
+            });
+
+            let Some(ready) = ready else {
+                let cycle = workspaces
+                    .iter()
+                    .map(|workspace| workspace.name.to_string())
+                    .collect::<Vec<_>>();
+                return Err(CommandError::WorkspaceCycle(cycle.join(", ")));
+            };
+
+            ordered.push(workspaces.remove(ready));
+        }
+
+        Ok(ordered)
+    }
+
+    /// Links each workspace at the top of node_modules, replacing links to anything else of the same name.
+    pub fn link(workspaces: &[Workspace]) -> Result<(), CommandError> {
+        for workspace in workspaces {
+            Self::link_directory(&workspace.name, &workspace.path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Links a directory relative to the root of the project at the top of node_modules under a package name, such as
+    /// a workspace or a dependency on `link:`. Anything in node_modules that is not a link is left as it is.
+    pub fn link_directory(package_name: &str, path: &Path) -> Result<(), CommandError> {
+        let link = Path::new(MODULES_DIRECTORY).join(package_name);
+
+        // Links are relative so node_modules keeps working if the project is moved, scoped names are one level deeper
+        let target = package_name
+            .split('/')
+            .map(|_| "..")
+            .collect::<PathBuf>()
+            .join(path);
+
+        match fs::read_link(&link) {
+            Ok(existing) if existing == target => return Ok(()),
+            Ok(_) => {
+                symlink::remove_symlink_dir(&link).map_err(CommandError::FailedToRemoveLink)?
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(_) => return Ok(()), // Not a link, so it was not created by click
+        }
+
+        if let Some(parent) = link.parent() {
+            fs::create_dir_all(parent).map_err(CommandError::FailedToCreateFile)?;
+        }
+
+        symlink::symlink_dir(target, link).map_err(CommandError::FailedToCreateFile)
+    }
+
+    /// Returns every directory with a package.json that a pattern matches, relative to the root it is matched in.
+    fn expand(root: &Path, pattern: &str) -> Vec<PathBuf> {
+        let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+        let components = pattern
+            .split('/')
+            .filter(|component| !component.is_empty() && *component != ".")
+            .collect::<Vec<_>>();
+
+        let mut matches = Vec::new();
+        Self::walk(root, PathBuf::new(), &components, &mut matches);
+
+        matches.sort();
+        matches.dedup();
+        matches
+    }
+
+    fn walk(root: &Path, directory: PathBuf, components: &[&str], matches: &mut Vec<PathBuf>) {
+        let Some((component, rest)) = components.split_first() else {
+            if root.join(&directory).join(PACKAGE_JSON_PATH).is_file() {
+                matches.push(directory);
+            }
+            return;
+        };
+
+        if !component.contains('*') {
+            let directory = directory.join(component);
+            if root.join(&directory).is_dir() {
+                Self::walk(root, directory, rest, matches);
+            }
+            return;
+        }
+
+        // `**` matches any number of directories, including none
+        if *component == "**" {
+            Self::walk(root, directory.clone(), rest, matches);
+        }
+
+        for subdirectory in Self::subdirectories(root, &directory) {
+            let name = subdirectory
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            match *component {
+                "**" => Self::walk(root, subdirectory, components, matches),
+                _ if Self::matches_wildcard(component, &name) => {
+                    Self::walk(root, subdirectory, rest, matches)
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    /// Hidden directories and node_modules are never workspaces.
+    fn subdirectories(root: &Path, directory: &Path) -> Vec<PathBuf> {
+        let read_path = root.join(directory);
+        let read_path = match read_path.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => &read_path,
+        };
+
+        let Ok(entries) = fs::read_dir(read_path) else {
+            return Vec::new();
+        };
+
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                !name.starts_with('.') && name != "node_modules"
+            })
+            .map(|entry| directory.join(entry.file_name()))
+            .collect()
+    }
+
+    /// Matches a directory name against a pattern where `*` is any number of characters.
+    fn matches_wildcard(pattern: &str, name: &str) -> bool {
+        let mut parts = pattern.split('*');
+        let first = parts.next().unwrap_or_default();
+        let Some(mut remaining) = name.strip_prefix(first) else {
+            return false;
+        };
+
+        let parts = parts.collect::<Vec<_>>();
+        for (index, part) in parts.iter().enumerate() {
+            if index == parts.len() - 1 {
+                return remaining.ends_with(part);
+            }
+
+            match remaining.find(part) {
+                Some(position) => remaining = &remaining[position + part.len()..],
+                None => return false,
+            }
+        }
+
+        remaining.is_empty()
+    }
+}
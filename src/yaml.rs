@@ -0,0 +1,119 @@
+/// A key of a lockfile written in YAML or in the format of yarn v1, which is close to it but leaves out the colon
+/// between a key and its value. Only what lockfiles use is read: each line is a key, with either a value or the keys
+/// indented under it. Quotes around keys and values are removed, and the items of a list are read as keys.
+#[derive(Debug, Default)]
+pub struct YamlNode {
+    pub key: String,
+    pub value: Option<String>,
+    pub children: Vec<YamlNode>,
+}
+
+impl YamlNode {
+    /// Reads a whole document into a node with an empty key, with each top-level key as its children.
+    pub fn parse(raw: &str) -> Self {
+        // The nodes that are still open, each with its indentation
+        let mut stack: Vec<(usize, YamlNode)> = vec![(0, YamlNode::default())];
+
+        for line in raw.lines() {
+            let content = line.trim_start();
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+
+            let indent = line.len() - content.len();
+            while stack.len() > 1 && stack.last().is_some_and(|(open, _)| *open >= indent) {
+                Self::close(&mut stack);
+            }
+
+            let content = content.strip_prefix("- ").unwrap_or(content);
+            let (key, value) = Self::parse_line(content);
+            stack.push((
+                indent,
+                YamlNode {
+                    key,
+                    value,
+                    children: Vec::new(),
+                },
+            ));
+        }
+
+        while stack.len() > 1 {
+            Self::close(&mut stack);
+        }
+
+        stack.pop().map(|(_, root)| root).unwrap_or_default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&YamlNode> {
+        self.children.iter().find(|child| child.key == key)
+    }
+
+    /// The value of a key under this one, such as `version` in a package.
+    pub fn value_of(&self, key: &str) -> Option<&str> {
+        self.get(key)?.value.as_deref()
+    }
+
+    /// The keys and values of a mapping written on one line such as `{integrity: sha512-...}`.
+    pub fn flow_mapping(&self) -> Vec<(String, String)> {
+        let Some(value) = self.value.as_deref() else {
+            return Vec::new();
+        };
+
+        value
+            .trim_start_matches('{')
+            .trim_end_matches('}')
+            .split(", ")
+            .filter_map(|entry| {
+                let (key, value) = entry.split_once(": ")?;
+                Some((Self::unquote(key.trim()), Self::unquote(value.trim())))
+            })
+            .collect()
+    }
+
+    fn close(stack: &mut Vec<(usize, YamlNode)>) {
+        if let Some((_, node)) = stack.pop() {
+            if let Some((_, parent)) = stack.last_mut() {
+                parent.children.push(node);
+            }
+        }
+    }
+
+    /// A key that is quoted can have colons and spaces in it, such as the specs of yarn. Otherwise the key ends at the
+    /// first `: `, or at the first space with yarn v1.
+    fn parse_line(content: &str) -> (String, Option<String>) {
+        if let Some(key) = content.strip_suffix(':') {
+            return (Self::unquote(key), None);
+        }
+
+        let split = match content.starts_with(['"', '\'']) {
+            true => content[1..]
+                .find(&content[..1])
+                .map(|end| (&content[..end + 2], &content[end + 2..])),
+            false => content
+                .split_once(": ")
+                .filter(|(key, _)| !key.contains(' '))
+                .or_else(|| content.split_once(' ')),
+        };
+
+        match split {
+            Some((key, value)) => {
+                let value = value.trim_start().trim_start_matches(':').trim();
+                (Self::unquote(key), Some(Self::unquote(value)))
+            }
+            None => (Self::unquote(content), None),
+        }
+    }
+
+    /// Removes the quotes around a key or value, unless it is a list of them such as the specs of a package with yarn v1.
+    pub fn unquote(raw: &str) -> String {
+        let quoted = raw.len() >= 2
+            && [b'"', b'\''].iter().any(|quote| {
+                raw.as_bytes()[0] == *quote && raw[1..].find(*quote as char) == Some(raw.len() - 2)
+            });
+
+        match quoted {
+            true => raw[1..raw.len() - 1].to_string(),
+            false => raw.to_string(),
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::ErrorKind,
+    str::FromStr,
+};
+
+use semver::Version;
+
+use crate::{
+    errors::CommandError,
+    isolated::IsolatedLayout,
+    source::Source,
+    types::{LockedPackage, Lockfile},
+    versions::Versions,
+    yaml::YamlNode,
+};
+
+pub const YARN_LOCKFILE_PATH: &str = "./yarn.lock";
+
+const NPM_PROTOCOL: &str = "npm:";
+
+/// Converts the yarn.lock of yarn into a lockfile, see NpmLockfile. yarn locks each version once for every range it was
+/// resolved for, such as `"ms@^2.1.1", "ms@^2.1.3":`, so the version a package depends on is found from the range it
+/// depends on it with. Both yarn v1 and the YAML lockfiles of yarn berry are read. berry records neither the tarball URL
+/// nor an integrity npm can check, so the versions it locked are downloaded from the registry.
+pub struct YarnLockfile;
+impl YarnLockfile {
+    /// Returns None if the project has no yarn.lock. `requested` has each package requested from the registry.
+    pub fn import(requested: &[(String, Source)]) -> Result<Option<Lockfile>, CommandError> {
+        let yarn_lockfile_raw = match fs::read_to_string(YARN_LOCKFILE_PATH) {
+            Ok(yarn_lockfile_raw) => yarn_lockfile_raw,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(CommandError::FailedToReadPackageLock(err)),
+        };
+
+        Ok(Some(Self::convert(&yarn_lockfile_raw, requested)))
+    }
+
+    pub fn convert(yarn_lockfile_raw: &str, requested: &[(String, Source)]) -> Lockfile {
+        let document = YamlNode::parse(yarn_lockfile_raw);
+
+        // The package@version each range of a package resolved to
+        let mut ranges: HashMap<(String, String), String> = HashMap::new();
+        let mut entries = Vec::new();
+        for entry in document.children.iter() {
+            // Such as the __metadata of berry, or the version of a workspace
+            let Some(version) = entry
+                .value_of("version")
+                .filter(|version| Version::from_str(version).is_ok())
+            else {
+                continue;
+            };
+
+            let from_registry = match entry.value_of("resolution") {
+                Some(resolution) => resolution.contains(&format!("@{NPM_PROTOCOL}")),
+                None => entry.value_of("resolved").is_none_or(|resolved| {
+                    resolved.starts_with("https://") || resolved.starts_with("http://")
+                }),
+            };
+            if !from_registry {
+                continue;
+            }
+
+            let mut package = None;
+            for spec in entry.key.split(", ").map(YamlNode::unquote) {
+                let (package_name, range) = Versions::parse_raw_package_details(spec);
+                if Source::from_spec(&range).is_some_and(|source| !source.is_registry()) {
+                    continue; // Such as a git repository, which yarn v1 downloads as a tarball
+                }
+
+                let stringified = Versions::stringify(&package_name, &version.to_string());
+
+                ranges.insert((package_name, Self::range(&range)), stringified.to_string());
+                package = Some(stringified);
+            }
+
+            if let Some(package) = package {
+                entries.push((package, entry));
+            }
+        }
+
+        let located = entries
+            .iter()
+            .map(|(package, _)| package.to_string())
+            .collect::<Vec<_>>();
+        let resolve = |package_name: &String, range: &str| {
+            let range = Self::range(range);
+            ranges
+                .get(&(package_name.to_string(), range.to_string()))
+                .cloned()
+                .or_else(|| IsolatedLayout::select(&located, package_name, &range))
+        };
+
+        let mut packages = BTreeMap::new();
+        for (package, entry) in entries.iter() {
+            let dependencies = ["dependencies", "optionalDependencies"]
+                .iter()
+                .filter_map(|field| entry.get(field))
+                .flat_map(|dependencies| dependencies.children.iter())
+                .filter_map(|dependency| resolve(&dependency.key, dependency.value.as_deref()?))
+                .collect();
+
+            // The tarball URL of yarn v1 ends with the shasum of the tarball
+            let resolved = entry
+                .value_of("resolved")
+                .map(|resolved| resolved.split('#').next().unwrap_or(resolved).to_string());
+            let bin = entry
+                .get("bin")
+                .into_iter()
+                .flat_map(|bin| bin.children.iter())
+                .filter_map(|bin| Some((bin.key.to_string(), bin.value.clone()?)))
+                .collect();
+
+            packages.insert(
+                package.to_string(),
+                LockedPackage {
+                    resolved,
+                    registry: None, // Not recorded by yarn
+                    integrity: entry.value_of("integrity").map(str::to_string),
+                    has_install_script: false, // Not recorded by yarn
+                    bin,
+                    dependencies,
+                    optional: false,
+                },
+            );
+        }
+
+        let requested = requested
+            .iter()
+            .filter_map(|(package_name, source)| {
+                let range = source
+                    .semantic_version()
+                    .map_or("*".to_string(), |semantic_version| {
+                        semantic_version.to_string()
+                    });
+                let package = IsolatedLayout::select(&located, package_name, &range)?;
+
+                Some((source.spec(package_name), package))
+            })
+            .collect();
+
+        Lockfile::import(packages, requested)
+    }
+
+    /// berry prefixes ranges from the registry with `npm:`, which is left out of the ranges packages depend on with.
+    /// An alias is locked with the range of the package it installs.
+    fn range(range: &str) -> String {
+        let range = range.strip_prefix(NPM_PROTOCOL).unwrap_or(range);
+        match range.get(1..).and_then(|aliased| aliased.split_once('@')) {
+            Some((_, aliased_range)) => aliased_range.to_string(),
+            None => range.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YARN_V1_LOCKFILE: &str = r#"# THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.
+# yarn lockfile v1
+
+
+"@types/node@^20.8.0":
+  version "20.8.10"
+  resolved "https://registry.yarnpkg.com/@types/node/-/node-20.8.10.tgz#a15e7f0c5b8a2a3b1d5f9e4c6d7a8b9c0d1e2f3a"
+  integrity sha512-types-node
+  dependencies:
+    undici-types "~5.26.4"
+
+debug@^2.6.9:
+  version "2.6.9"
+  resolved "https://registry.yarnpkg.com/debug/-/debug-2.6.9.tgz#5d128515df134ff327e90a4c93f4e077a536341f"
+  integrity sha512-debug
+  dependencies:
+    ms "2.0.0"
+
+ms@2.0.0:
+  version "2.0.0"
+  resolved "https://registry.yarnpkg.com/ms/-/ms-2.0.0.tgz#5608aeadfc00be6c2901df5f9861788de0d597c8"
+  integrity sha512-ms-2.0.0
+
+ms@^2.1.1, ms@^2.1.3:
+  version "2.1.3"
+  resolved "https://registry.yarnpkg.com/ms/-/ms-2.1.3.tgz#574c8138ce1d2b5861f0b44579dbadd60c6615b2"
+  integrity sha512-ms-2.1.3
+
+"my-lib@github:me/my-lib":
+  version "1.0.0"
+  resolved "https://codeload.github.com/me/my-lib/tar.gz/4358a00"
+  dependencies:
+    ms "^2.1.1"
+
+undici-types@~5.26.4:
+  version "5.26.5"
+  resolved "https://registry.yarnpkg.com/undici-types/-/undici-types-5.26.5.tgz#bcd539893d00b56e964fd2657a4866b221a65617"
+  integrity sha512-undici-types
+"#;
+
+    const YARN_BERRY_LOCKFILE: &str = r#"# This file is generated by running "yarn install" inside your project.
+# Manual changes might be lost - proceed with caution!
+
+__metadata:
+  version: 8
+  cacheKey: 10c0
+
+"@types/node@npm:^20.8.0":
+  version: 20.8.10
+  resolution: "@types/node@npm:20.8.10"
+  dependencies:
+    undici-types: "npm:~5.26.4"
+  checksum: 10c0/types-node
+  languageName: node
+  linkType: hard
+
+"app@workspace:.":
+  version: 0.0.0-use.local
+  resolution: "app@workspace:."
+  dependencies:
+    "@types/node": "npm:^20.8.0"
+    debug: "npm:^2.6.9"
+    ms: "npm:^2.1.3"
+  languageName: unknown
+  linkType: soft
+
+"debug@npm:^2.6.9":
+  version: 2.6.9
+  resolution: "debug@npm:2.6.9"
+  dependencies:
+    ms: "npm:2.0.0"
+  checksum: 10c0/debug
+  languageName: node
+  linkType: hard
+
+"ms@npm:2.0.0":
+  version: 2.0.0
+  resolution: "ms@npm:2.0.0"
+  checksum: 10c0/ms-2.0.0
+  languageName: node
+  linkType: hard
+
+"ms@npm:^2.1.1, ms@npm:^2.1.3":
+  version: 2.1.3
+  resolution: "ms@npm:2.1.3"
+  checksum: 10c0/ms-2.1.3
+  languageName: node
+  linkType: hard
+
+"undici-types@npm:~5.26.4":
+  version: 5.26.5
+  resolution: "undici-types@npm:5.26.5"
+  checksum: 10c0/undici-types
+  languageName: node
+  linkType: hard
+"#;
+
+    fn requested() -> Vec<(String, Source)> {
+        [
+            ("debug", "^2.6.9"),
+            ("ms", "^2.1.3"),
+            ("@types/node", "^20.8.0"),
+        ]
+        .iter()
+        .map(|(package_name, range)| {
+            let semantic_version = Versions::parse_semantic_version(range).ok();
+            (package_name.to_string(), Source::Registry(semantic_version))
+        })
+        .collect()
+    }
+
+    fn dependencies(lockfile: &Lockfile) -> Vec<(String, Vec<String>)> {
+        lockfile
+            .packages
+            .iter()
+            .map(|(package, locked_package)| {
+                (package.to_string(), locked_package.dependencies.clone())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn imports_yarn_lockfiles() {
+        let expected = [
+            ("@types/node@20.8.10", vec!["undici-types@5.26.5"]),
+            ("debug@2.6.9", vec!["ms@2.0.0"]),
+            ("ms@2.0.0", vec![]),
+            ("ms@2.1.3", vec![]),
+            ("undici-types@5.26.5", vec![]),
+        ]
+        .map(|(package, dependencies)| {
+            let dependencies = dependencies.iter().map(|dependency| dependency.to_string());
+            (package.to_string(), dependencies.collect::<Vec<_>>())
+        });
+
+        let v1 = YarnLockfile::convert(YARN_V1_LOCKFILE, &requested());
+        assert_eq!(dependencies(&v1), expected);
+        assert_eq!(
+            v1.requested.values().collect::<Vec<_>>(),
+            ["@types/node@20.8.10", "debug@2.6.9", "ms@2.1.3"]
+        );
+
+        let debug = &v1.packages["debug@2.6.9"];
+        assert_eq!(
+            debug.resolved.as_deref(),
+            Some("https://registry.yarnpkg.com/debug/-/debug-2.6.9.tgz")
+        );
+        assert_eq!(debug.integrity.as_deref(), Some("sha512-debug"));
+
+        let berry = YarnLockfile::convert(YARN_BERRY_LOCKFILE, &requested());
+        assert_eq!(dependencies(&berry), expected);
+        assert_eq!(berry.requested, v1.requested);
+        assert!(berry.packages["debug@2.6.9"].resolved.is_none());
+    }
+}
@@ -0,0 +1,455 @@
+//! A registry served from a thread for the tests to install from, and a project with a home and cache of its own for
+//! each test to run click in. Each integration test file uses what it needs of these.
+#![allow(dead_code)]
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env, fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{self, Command, Output},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::{write::GzEncoder, Compression};
+use serde_json::{json, Value};
+
+/// A version of a package the registry serves, with the files of its tarball.
+#[derive(Clone)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    fields: serde_json::Map<String, Value>, // Of its package.json, other than the name and version
+    files: Vec<(String, String)>,           // Relative to the root of the package
+    dist: serde_json::Map<String, Value>,   // Added to the dist the registry serves
+}
+
+impl Package {
+    pub fn new(name: &str, version: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            fields: serde_json::Map::new(),
+            files: vec![(
+                String::from("index.js"),
+                format!("module.exports = '{name}@{version}';\n"),
+            )],
+            dist: serde_json::Map::new(),
+        }
+    }
+
+    pub fn dependency(mut self, name: &str, range: &str) -> Self {
+        self.fields
+            .entry("dependencies")
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .unwrap()
+            .insert(name.to_string(), json!(range));
+        self
+    }
+
+    /// Sets a field of the package.json, which the registry serves along with the version.
+    pub fn field(mut self, key: &str, value: Value) -> Self {
+        self.fields.insert(key.to_string(), value);
+        self
+    }
+
+    pub fn file(mut self, path: &str, contents: &str) -> Self {
+        self.files.push((path.to_string(), contents.to_string()));
+        self
+    }
+
+    pub fn dist(mut self, key: &str, value: Value) -> Self {
+        self.dist.insert(key.to_string(), value);
+        self
+    }
+
+    /// The path of the tarball under the registry, the same as npm's.
+    pub fn tarball_path(&self) -> String {
+        let basename = self.name.rsplit('/').next().unwrap_or(&self.name);
+        format!("/{}/-/{basename}-{}.tgz", self.name, self.version)
+    }
+
+    fn manifest(&self) -> Value {
+        let mut manifest = self.fields.clone();
+        manifest.insert(String::from("name"), json!(self.name));
+        manifest.insert(String::from("version"), json!(self.version));
+        Value::Object(manifest)
+    }
+
+    fn tarball(&self) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let manifest = serde_json::to_string_pretty(&self.manifest()).unwrap();
+        let files = std::iter::once((String::from("package.json"), manifest))
+            .chain(self.files.iter().cloned());
+
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, format!("package/{path}"), contents.as_bytes())
+                .unwrap();
+        }
+
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+}
+
+/// The SRI integrity of a tarball, as the registry lists it.
+pub fn integrity(bytes: &[u8]) -> String {
+    format!("sha512-{}", STANDARD.encode(openssl::sha::sha512(bytes)))
+}
+
+/// A request the registry received.
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub authorization: Option<String>,
+    pub otp: Option<String>, // The one-time password sent in the npm-otp header
+    pub body: String,
+}
+
+#[derive(Default)]
+struct Routes {
+    responses: HashMap<String, (u16, Vec<u8>)>, // By path
+    corrupt: HashMap<String, usize>, // Tarballs served with a flipped byte, by path to how many more times they are
+    otp: Option<String>, // The one-time password uploads are refused without, see Registry::require_otp()
+    requests: Vec<Request>,
+}
+
+/// A registry on a port of its own, serving the packuments, versions and tarballs of its packages until the test ends.
+pub struct Registry {
+    pub url: String, // Without a trailing slash
+    routes: Arc<Mutex<Routes>>,
+    tarballs: HashMap<String, Vec<u8>>, // By name@version
+}
+
+impl Registry {
+    pub fn start(packages: &[Package]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let mut registry = Self {
+            url,
+            routes: Arc::new(Mutex::new(Routes::default())),
+            tarballs: HashMap::new(),
+        };
+        registry.serve(packages);
+
+        let routes = registry.routes.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let routes = routes.clone();
+                thread::spawn(move || Self::respond(stream, &routes));
+            }
+        });
+
+        registry
+    }
+
+    /// Serves the packages, the versions of a name replace those it was served with before.
+    pub fn serve(&mut self, packages: &[Package]) {
+        let mut by_name: BTreeMap<&str, Vec<&Package>> = BTreeMap::new();
+        for package in packages {
+            by_name.entry(&package.name).or_default().push(package);
+        }
+
+        let mut routes = self.routes.lock().unwrap();
+        for (name, versions) in by_name {
+            let mut documents = serde_json::Map::new();
+            for package in versions.iter() {
+                let tarball = package.tarball();
+                let mut dist = package.dist.clone();
+                dist.insert(
+                    String::from("tarball"),
+                    json!(format!("{}{}", self.url, package.tarball_path())),
+                );
+                dist.insert(String::from("integrity"), json!(integrity(&tarball)));
+
+                let mut document = package.manifest();
+                document["dist"] = Value::Object(dist);
+
+                routes.responses.insert(
+                    format!("/{}/{}", name.replace('/', "%2f"), package.version),
+                    (200, document.to_string().into_bytes()),
+                );
+                routes
+                    .responses
+                    .insert(package.tarball_path(), (200, tarball.clone()));
+                documents.insert(package.version.to_string(), document);
+                self.tarballs
+                    .insert(format!("{name}@{}", package.version), tarball);
+            }
+
+            let latest = versions.last().unwrap().version.to_string();
+            routes.responses.insert(
+                format!("/{}/latest", name.replace('/', "%2f")),
+                (200, documents[&latest].to_string().into_bytes()),
+            );
+            let packument =
+                json!({ "name": name, "dist-tags": { "latest": latest }, "versions": documents });
+            routes.responses.insert(
+                format!("/{}", name.replace('/', "%2f")),
+                (200, packument.to_string().into_bytes()),
+            );
+        }
+    }
+
+    /// Answers requests to a path with a fixed response, such as the advisories of an audit.
+    pub fn route(&self, path: &str, status: u16, body: &str) {
+        self.routes
+            .lock()
+            .unwrap()
+            .responses
+            .insert(path.to_string(), (status, body.as_bytes().to_vec()));
+    }
+
+    /// Serves the tarball of a package with a changed byte the next `times` it is requested, as a transfer that was
+    /// corrupted on the way would be.
+    pub fn corrupt(&self, package: &Package, times: usize) {
+        self.routes
+            .lock()
+            .unwrap()
+            .corrupt
+            .insert(package.tarball_path(), times);
+    }
+
+    /// Refuses every PUT that does not send the one-time password, as for an account with two-factor authentication.
+    pub fn require_otp(&self, otp: &str) {
+        self.routes.lock().unwrap().otp = Some(otp.to_string());
+    }
+
+    /// The tarball of name@version as it is served.
+    pub fn tarball(&self, package: &str) -> &[u8] {
+        &self.tarballs[package]
+    }
+
+    /// Every request received so far, in the order they were.
+    pub fn requests(&self) -> Vec<Request> {
+        self.routes.lock().unwrap().requests.clone()
+    }
+
+    /// The paths of the tarballs requested so far.
+    pub fn tarball_requests(&self) -> Vec<String> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.path.ends_with(".tgz"))
+            .map(|request| request.path)
+            .collect()
+    }
+
+    fn respond(stream: TcpStream, routes: &Mutex<Routes>) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).is_err() {
+            return;
+        }
+
+        let mut words = request_line.split_whitespace();
+        let method = words.next().unwrap_or_default().to_string();
+        let path = words.next().unwrap_or_default().to_string();
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+
+        let length = headers
+            .get("content-length")
+            .and_then(|length| length.parse::<usize>().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        let _ = reader.read_exact(&mut body);
+
+        let (status, mut bytes) = {
+            let mut routes = routes.lock().unwrap();
+            routes.requests.push(Request {
+                method: method.to_string(),
+                path: path.to_string(),
+                authorization: headers.get("authorization").cloned(),
+                otp: headers.get("npm-otp").cloned(),
+                body: String::from_utf8_lossy(&body).to_string(),
+            });
+
+            let path = path
+                .split('?')
+                .next()
+                .unwrap_or_default()
+                .trim_end_matches('/');
+            let response = routes
+                .responses
+                .get(path)
+                .or_else(|| routes.responses.get(&path.replace("%2F", "%2f")))
+                .cloned()
+                .unwrap_or((404, b"{\"error\":\"Not found\"}".to_vec()));
+
+            let wants_otp = routes.otp.is_some() && method == "PUT";
+            if wants_otp && routes.otp.as_ref() != headers.get("npm-otp") {
+                let error = b"{\"error\":\"this operation requires a one-time password\"}";
+                (401, error.to_vec())
+            } else {
+                match routes.corrupt.get_mut(path) {
+                    Some(times) if *times > 0 && method == "GET" => {
+                        *times -= 1;
+                        let mut corrupted = response.1.clone();
+                        let middle = corrupted.len() / 2;
+                        corrupted[middle] ^= 0xff;
+                        (response.0, corrupted)
+                    }
+                    _ => response,
+                }
+            }
+        };
+
+        let reason = match status {
+            200 => "OK",
+            404 => "Not Found",
+            _ => "Status",
+        };
+        let mut response = format!(
+            "HTTP/1.1 {status} {reason}\r\nContent-Length: {}\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n",
+            bytes.len()
+        )
+        .into_bytes();
+        if method != "HEAD" {
+            response.append(&mut bytes);
+        }
+
+        let mut stream = stream;
+        let _ = stream.write_all(&response);
+    }
+}
+
+/// A project in a directory of its own, with a home directory and cache that no other test uses.
+pub struct Project {
+    pub root: PathBuf, // Has the home directory and the project
+    pub path: PathBuf,
+    registry: String,
+    env: Vec<(String, String)>,
+}
+
+impl Project {
+    pub fn new(name: &str, registry: &Registry) -> Self {
+        let root = env::temp_dir().join(format!("click-test-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let path = root.join("project");
+        fs::create_dir_all(&path).unwrap();
+        fs::create_dir_all(root.join("home")).unwrap();
+
+        Self {
+            root,
+            path,
+            registry: registry.url.to_string(),
+            env: Vec::new(),
+        }
+    }
+
+    /// Sets an environment variable for every run of click, such as a setting of the config.
+    pub fn env(&mut self, name: &str, value: &str) {
+        self.env.push((name.to_string(), value.to_string()));
+    }
+
+    pub fn write(&self, path: &str, contents: &str) {
+        let path = self.path.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    pub fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.path.join(path)).unwrap()
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.path.join(path).exists()
+    }
+
+    pub fn package_json(&self, package_json: Value) {
+        self.write(
+            "package.json",
+            &serde_json::to_string_pretty(&package_json).unwrap(),
+        );
+    }
+
+    pub fn json(&self, path: &str) -> Value {
+        serde_json::from_str(&self.read(path)).unwrap()
+    }
+
+    pub fn home(&self) -> PathBuf {
+        self.root.join("home")
+    }
+
+    /// Where click caches the extracted files of each package.
+    pub fn cache(&self) -> PathBuf {
+        self.home().join(".cache/node-cache")
+    }
+
+    /// Runs click in the project, with nothing of the environment it was run in but PATH, so that no config of the
+    /// machine is used.
+    pub fn click(&self, args: &[&str]) -> Output {
+        self.click_in("", args)
+    }
+
+    /// Runs click the same way in a directory of the project, such as a workspace.
+    pub fn click_in(&self, directory: &str, args: &[&str]) -> Output {
+        let home = self.home();
+        Command::new(env!("CARGO_BIN_EXE_click"))
+            .args(args)
+            .current_dir(self.path.join(directory))
+            .env_clear()
+            .env("PATH", env::var_os("PATH").unwrap_or_default())
+            .env("HOME", &home)
+            .env("XDG_CACHE_HOME", home.join(".cache"))
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env("XDG_DATA_HOME", home.join(".local/share"))
+            .env("CLICK_REGISTRY", &self.registry)
+            .env("CLICK_FETCH_RETRY_MINTIMEOUT", "10")
+            .env("CLICK_FETCH_RETRY_MAXTIMEOUT", "10")
+            .envs(self.env.iter().map(|(name, value)| (name, value)))
+            .output()
+            .unwrap()
+    }
+
+    /// Runs click and fails the test if it fails, returning what it printed to stdout.
+    pub fn click_ok(&self, args: &[&str]) -> String {
+        let output = self.click(args);
+        assert!(
+            output.status.success(),
+            "click {} failed:\n{}{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+
+    /// Every package@version in the cache with its files extracted.
+    pub fn cached(&self) -> HashSet<String> {
+        fs::read_dir(self.cache())
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.file_name().to_string_lossy().to_string())
+                    .filter(|name| Path::new(&self.cache().join(name).join("package")).exists())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
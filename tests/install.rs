@@ -0,0 +1,1471 @@
+mod common;
+
+use common::{Package, Project, Registry};
+use serde_json::json;
+
+#[test]
+fn quiet_success_prints_nothing_unless_it_fails() {
+    let registry = Registry::start(&[Package::new("aa", "1.0.0")]);
+    let project = Project::new("quiet-success", &registry);
+
+    let output = project.click(&["install", "aa", "--quiet-success"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(output.stdout.is_empty());
+    assert!(project.exists("node_modules/aa/package.json"));
+
+    let output = project.click(&["install", "missing", "--quiet-success"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(!output.stderr.is_empty());
+    assert_eq!(
+        project.json("package.json")["dependencies"],
+        json!({ "aa": "^1.0.0" })
+    );
+}
+
+#[test]
+fn cached_shasum_is_upgraded_to_integrity() {
+    let registry = Registry::start(&[Package::new("aa", "1.0.0")]);
+    let project = Project::new("upgrade-integrity", &registry);
+    project.click_ok(&["install", "aa"]);
+
+    // As a lockfile written before integrity was recorded would have it
+    let lock_path = project.cache().join("aa@1.0.0/package/click-lock.json");
+    let mut package_lock: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&lock_path).unwrap()).unwrap();
+    package_lock.as_object_mut().unwrap().remove("integrity");
+    assert!(package_lock["shasum"].is_string());
+    std::fs::write(&lock_path, package_lock.to_string()).unwrap();
+
+    project.click_ok(&["install"]);
+
+    let package_lock: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&lock_path).unwrap()).unwrap();
+    let expected = common::integrity(registry.tarball("aa@1.0.0"));
+    assert_eq!(package_lock["integrity"], json!(expected));
+    assert_eq!(
+        project.json("click-lock.json")["packages"]["aa@1.0.0"]["integrity"],
+        json!(expected)
+    );
+}
+
+#[test]
+fn truncated_lockfiles_are_reported_and_regenerated() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0").dependency("bb", "^1.0.0"),
+        Package::new("bb", "1.0.0"),
+    ]);
+    let project = Project::new("corrupt-lockfile", &registry);
+    project.click_ok(&["install", "aa"]);
+
+    let lockfile = project.read("click-lock.json");
+    let truncated = &lockfile[..lockfile.len() / 2];
+    project.write("click-lock.json", truncated);
+
+    // The error points at where the lockfile ends
+    let (line, column) = (
+        truncated.matches('\n').count() + 1,
+        truncated.len() - truncated.rfind('\n').map_or(0, |newline| newline + 1),
+    );
+    let output = project.click(&["install"]);
+    assert_eq!(output.status.code(), Some(5));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("click-lock.json' is corrupt (EOF while parsing a"));
+    assert!(
+        stderr.contains(&format!(" at line {line} column {column})")),
+        "{stderr}"
+    );
+    assert!(stderr.contains("--regenerate-lockfile"));
+
+    project.click_ok(&["install", "--regenerate-lockfile"]);
+    let regenerated = project.json("click-lock.json");
+    assert_eq!(regenerated["requested"]["aa@^1.0.0"], json!("aa@1.0.0"));
+    assert!(regenerated["packages"]["bb@1.0.0"].is_object());
+
+    // A truncated lockfile in the cache is removed, along with that of aa which depends on it, so both are locked again
+    let lock_path = project.cache().join("bb@1.0.0/package/click-lock.json");
+    let package_lock = std::fs::read_to_string(&lock_path).unwrap();
+    std::fs::write(&lock_path, &package_lock[..package_lock.len() / 2]).unwrap();
+    std::fs::remove_dir_all(project.path.join("node_modules")).unwrap();
+
+    let output = project.click(&["install"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("bb@1.0.0/package/click-lock.json"));
+
+    project.click_ok(&["install", "--regenerate-lockfile"]);
+    let package_lock: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&lock_path).unwrap()).unwrap();
+    assert_eq!(
+        package_lock["integrity"],
+        json!(common::integrity(registry.tarball("bb@1.0.0")))
+    );
+    assert!(project.exists("node_modules/bb/package.json"));
+}
+
+#[test]
+fn integrity_allowlist_refuses_what_it_does_not_list() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0"),
+        Package::new("bb", "1.0.0"),
+        Package::new("cc", "1.0.0"),
+    ]);
+    let project = Project::new("integrity-allowlist", &registry);
+    project.write(
+        "allowlist.json",
+        &json!({
+            "aa@1.0.0": common::integrity(registry.tarball("aa@1.0.0")),
+            "bb@1.0.0": common::integrity(b"something else"),
+        })
+        .to_string(),
+    );
+
+    project.click_ok(&["install", "aa", "--integrity-allowlist", "allowlist.json"]);
+    assert!(project.exists("node_modules/aa/package.json"));
+
+    for package in ["bb", "cc"] {
+        let output = project.click(&[
+            "install",
+            package,
+            "--integrity-allowlist",
+            "allowlist.json",
+        ]);
+        assert_eq!(output.status.code(), Some(5));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("is not in the allowlist"));
+        assert!(!project.exists(&format!("node_modules/{package}")));
+    }
+}
+
+#[test]
+fn interrupted_install_resumes_without_extracting_again() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0").dependency("bb", "^1.0.0"),
+        Package::new("bb", "1.0.0"),
+    ]);
+    let project = Project::new("resume", &registry);
+    project.click_ok(&["install", "aa"]);
+    assert!(!project.exists("node_modules/.click-install-state.json"));
+    let locked = project.json("click-lock.json")["packages"]["aa@1.0.0"].clone();
+
+    // As an install interrupted after extracting aa would leave it, without its lockfile written
+    std::fs::remove_file(project.cache().join("aa@1.0.0/package/click-lock.json")).unwrap();
+    std::fs::remove_dir_all(project.home().join(".click/cache")).unwrap();
+    std::fs::remove_dir_all(project.path.join("node_modules")).unwrap();
+    std::fs::remove_file(project.path.join("click-lock.json")).unwrap();
+    project.write(
+        "node_modules/.click-install-state.json",
+        &json!({
+            "extracted": {
+                "aa@1.0.0": {
+                    "shasum": openssl::sha::sha1(registry.tarball("aa@1.0.0"))
+                        .iter()
+                        .map(|byte| format!("{byte:02x}"))
+                        .collect::<String>(),
+                    "integrity": locked["integrity"],
+                    "resolved": locked["resolved"],
+                    "registry": locked["registry"],
+                },
+            },
+        })
+        .to_string(),
+    );
+    let requested = registry.tarball_requests().len();
+
+    project.click_ok(&["install"]);
+
+    assert_eq!(registry.tarball_requests().len(), requested);
+    assert!(project.exists("node_modules/aa/package.json"));
+    assert!(project.exists("node_modules/bb/package.json"));
+    assert!(!project.exists("node_modules/.click-install-state.json"));
+
+    // The resumed package is locked the same as if it had been downloaded
+    let resumed = &project.json("click-lock.json")["packages"]["aa@1.0.0"];
+    assert_eq!(
+        resumed["integrity"],
+        json!(common::integrity(registry.tarball("aa@1.0.0")))
+    );
+    assert_eq!(resumed["resolved"], locked["resolved"]);
+    assert!(resumed["resolved"].is_string());
+    let package_lock: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(project.cache().join("aa@1.0.0/package/click-lock.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(package_lock["integrity"], resumed["integrity"]);
+
+    std::fs::remove_dir_all(project.path.join("node_modules")).unwrap();
+    project.click_ok(&["install", "--frozen-lockfile"]);
+    assert!(project.exists("node_modules/aa/package.json"));
+}
+
+#[test]
+fn requested_specs_follow_package_json() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0"),
+        Package::new("aa", "1.1.0"),
+        Package::new("bb", "1.0.0"),
+    ]);
+    let project = Project::new("requested-specs", &registry);
+
+    // A package is locked under the range it is saved with, so installing from package.json finds it locked
+    project.click_ok(&["install", "aa", "bb"]);
+    project.click_ok(&["install"]);
+    assert_eq!(
+        project.json("click-lock.json")["requested"],
+        json!({ "aa@^1.1.0": "aa@1.1.0", "bb@^1.0.0": "bb@1.0.0" })
+    );
+
+    // A range changed in package.json replaces the one it was locked under
+    let mut manifest = project.json("package.json");
+    manifest["dependencies"]["aa"] = json!("~1.0.0");
+    project.package_json(manifest);
+    project.click_ok(&["install"]);
+    let lockfile = project.json("click-lock.json");
+    assert_eq!(
+        lockfile["requested"],
+        json!({ "aa@~1.0.0": "aa@1.0.0", "bb@^1.0.0": "bb@1.0.0" })
+    );
+    assert!(lockfile["packages"].get("aa@1.1.0").is_none());
+
+    // So aa has a single edge from the project
+    let graph = project.click_ok(&["graph"]);
+    assert_eq!(graph.matches("\"aa@").count(), 2, "{graph}");
+    assert!(graph.contains("\".\" -> \"aa@1.0.0\" [label=\"~1.0.0\"];"));
+}
+
+#[test]
+fn packages_installed_together_install_as_a_whole() {
+    let bb = Package::new("bb", "1.0.0");
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0").dependency("cc", "^1.0.0"),
+        bb.clone(),
+        Package::new("cc", "1.0.0"),
+    ]);
+    let project = Project::new("install-together", &registry);
+    registry.corrupt(&bb, usize::MAX);
+
+    // Both are downloaded before either is linked, so when bb fails aa is neither linked, locked nor cached
+    let output = project.click(&["install", "aa", "bb"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("bb@1.0.0"));
+    assert_eq!(
+        registry
+            .tarball_requests()
+            .iter()
+            .filter(|path| path.contains("/aa-"))
+            .count(),
+        1
+    );
+    assert!(!project.exists("node_modules/aa"));
+    assert!(!project.exists("click-lock.json"));
+    assert!(!project.exists("package.json"));
+    assert!(!project
+        .cache()
+        .join("aa@1.0.0/package/click-lock.json")
+        .exists());
+    assert!(!project
+        .cache()
+        .join("cc@1.0.0/package/click-lock.json")
+        .exists());
+
+    // Once it is fixed both install, and are saved and locked together
+    registry.corrupt(&bb, 0);
+    project.click_ok(&["install", "aa", "bb"]);
+    assert!(project.exists("node_modules/aa/package.json"));
+    assert!(project.exists("node_modules/bb/package.json"));
+    assert_eq!(
+        project.json("click-lock.json")["requested"],
+        json!({ "aa@^1.0.0": "aa@1.0.0", "bb@^1.0.0": "bb@1.0.0" })
+    );
+    assert_eq!(
+        project.json("package.json")["dependencies"],
+        json!({ "aa": "^1.0.0", "bb": "^1.0.0" })
+    );
+}
+
+#[test]
+fn target_platform_writes_bin_shims_for_it() {
+    let script = "#!/usr/bin/env node\nconsole.log('tool');\n";
+    let registry = Registry::start(&[Package::new("@acme/tool", "1.0.0")
+        .field("bin", json!({ "tool": "bin/cli.js" }))
+        .file("bin/cli.js", script)]);
+    let project = Project::new("target-platform", &registry);
+
+    project.click_ok(&["install", "@acme/tool", "--target-platform", "unix"]);
+    let link = project.path.join("node_modules/.bin/tool");
+    assert_eq!(
+        std::fs::read_link(&link).unwrap(),
+        std::path::Path::new("../@acme/tool/bin/cli.js")
+    );
+
+    project.click_ok(&["install", "--target-platform", "windows"]);
+    assert_eq!(
+        project.read("node_modules/.bin/tool.cmd"),
+        "@ECHO off\r\nnode \"%~dp0\\..\\@acme\\tool\\bin\\cli.js\" %*\r\n"
+    );
+    assert_eq!(
+        project.read("node_modules/.bin/tool.ps1"),
+        "& node \"$PSScriptRoot\\..\\@acme\\tool\\bin\\cli.js\" $args\nexit $LASTEXITCODE\n"
+    );
+    assert!(project
+        .read("node_modules/.bin/tool")
+        .contains("exec node \"$basedir/../@acme/tool/bin/cli.js\" \"$@\""));
+    // The link was replaced rather than written through
+    assert_eq!(project.read("node_modules/@acme/tool/bin/cli.js"), script);
+
+    let output = project.click(&["install", "--target-platform", "win32"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn no_bin_links_leaves_out_node_modules_bin_and_is_locked() {
+    let registry = Registry::start(&[Package::new("tool", "1.0.0")
+        .field("bin", json!({ "tool": "bin/cli.js" }))
+        .file("bin/cli.js", "#!/usr/bin/env node\n")]);
+    let project = Project::new("no-bin-links", &registry);
+
+    project.click_ok(&["install", "tool", "--no-bin-links"]);
+    assert!(project.exists("node_modules/tool/bin/cli.js"));
+    assert!(!project.exists("node_modules/.bin"));
+    assert_eq!(project.json("click-lock.json")["noBinLinks"], json!(true));
+
+    // Installing without it links them again
+    project.click_ok(&["install"]);
+    assert!(project.exists("node_modules/.bin/tool"));
+    assert!(project.json("click-lock.json").get("noBinLinks").is_none());
+}
+
+#[test]
+fn report_lists_packages_summary_and_warnings() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0").dependency("bb", "^1.0.0"),
+        Package::new("bb", "1.0.0").field("deprecated", json!("use cc instead")),
+    ]);
+    let project = Project::new("report", &registry);
+
+    project.click_ok(&["install", "aa", "--report", "report.json"]);
+
+    let report = project.json("report.json");
+    assert_eq!(report["success"], json!(true));
+    assert_eq!(
+        report["summary"],
+        json!({ "total": 2, "downloaded": 2, "cached": 0 })
+    );
+
+    let packages = report["packages"].as_array().unwrap();
+    let names = packages
+        .iter()
+        .map(|package| package["package"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["aa@1.0.0", "bb@1.0.0"]);
+    assert_eq!(packages[0]["dependencies"], json!(["bb@1.0.0"]));
+    assert_eq!(
+        packages[1]["integrity"],
+        json!(common::integrity(registry.tarball("bb@1.0.0")))
+    );
+    assert_eq!(
+        report["warnings"],
+        json!([{
+            "code": "deprecated",
+            "package": "bb@1.0.0",
+            "message": "'bb@1.0.0' is deprecated: use cc instead",
+        }])
+    );
+    assert_eq!(report["errors"], json!([]));
+}
+
+#[test]
+fn nested_override_only_applies_under_its_parent() {
+    let registry = Registry::start(&[
+        Package::new("foo", "1.0.0").dependency("bar", "^1.0.0"),
+        Package::new("baz", "1.0.0").dependency("bar", "^1.0.0"),
+        Package::new("bar", "1.0.0"),
+        Package::new("bar", "2.0.0"),
+    ]);
+    let project = Project::new("nested-override", &registry);
+    project.package_json(json!({
+        "name": "app",
+        "dependencies": { "foo": "^1.0.0", "baz": "^1.0.0" },
+        "overrides": { "foo": { "bar": "2.0.0" } },
+    }));
+
+    project.click_ok(&["install"]);
+
+    let lockfile = project.json("click-lock.json");
+    assert_eq!(
+        lockfile["packages"]["foo@1.0.0"]["dependencies"],
+        json!(["bar@2.0.0"])
+    );
+    assert_eq!(
+        lockfile["packages"]["baz@1.0.0"]["dependencies"],
+        json!(["bar@1.0.0"])
+    );
+    assert_eq!(lockfile["overrides"], json!({ "foo>bar": "bar@2.0.0" }));
+
+    // Installing again from the lockfile lays it out the same way
+    for _ in 0..2 {
+        let version = |path: &str| project.json(path)["version"].clone();
+        assert_eq!(version("node_modules/bar/package.json"), json!("1.0.0"));
+        assert_eq!(
+            version("node_modules/foo/node_modules/bar/package.json"),
+            json!("2.0.0")
+        );
+
+        std::fs::remove_dir_all(project.path.join("node_modules")).unwrap();
+        project.click_ok(&["install"]);
+    }
+}
+
+#[test]
+fn no_engine_strict_overrides_the_config() {
+    let registry =
+        Registry::start(&[Package::new("aa", "1.0.0").field("engines", json!({ "node": "<1" }))]);
+    let project = Project::new("no-engine-strict", &registry);
+    project.write(".npmrc", "engine-strict=true\n");
+
+    let output = project.click(&["install", "aa"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("requires Node <1"));
+    assert!(!project.exists("node_modules/aa"));
+
+    let output = project.click(&["install", "aa", "--no-engine-strict"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'aa@1.0.0' requires Node <1"));
+    assert!(project.exists("node_modules/aa/package.json"));
+}
+
+#[test]
+fn diamond_resolves_to_the_highest_version_every_range_allows() {
+    let registry = Registry::start(&[
+        Package::new("left", "1.0.0").dependency("shared", "^1.0.0"),
+        Package::new("right", "1.0.0").dependency("shared", "~1.1.0"),
+        Package::new("shared", "1.1.0"),
+        Package::new("shared", "1.1.5"),
+        Package::new("shared", "1.2.0"),
+    ]);
+
+    // Dependents are resolved in either order, with a cold cache and a warm one
+    for run in 0..8 {
+        let project = Project::new(&format!("diamond-{run}"), &registry);
+        let dependencies = match run % 2 {
+            0 => json!({ "left": "^1.0.0", "right": "^1.0.0" }),
+            _ => json!({ "right": "^1.0.0", "left": "^1.0.0" }),
+        };
+        project.package_json(json!({ "name": "app", "dependencies": dependencies }));
+
+        for _ in 0..2 {
+            project.click_ok(&["install"]);
+
+            let lockfile = project.json("click-lock.json");
+            let shared = lockfile["packages"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .filter(|package| package.starts_with("shared@"))
+                .collect::<Vec<_>>();
+            assert_eq!(shared, ["shared@1.1.5"], "run {run}");
+            assert_eq!(
+                lockfile["packages"]["left@1.0.0"]["dependencies"],
+                json!(["shared@1.1.5"])
+            );
+            assert_eq!(
+                project.json("node_modules/shared/package.json")["version"],
+                json!("1.1.5")
+            );
+            assert!(!project.exists("node_modules/left/node_modules"));
+        }
+    }
+}
+
+#[test]
+fn save_to_writes_a_group_only_installed_when_included() {
+    let registry = Registry::start(&[Package::new("foo", "1.0.0"), Package::new("aa", "1.0.0")]);
+    let project = Project::new("save-to", &registry);
+    project.package_json(json!({ "name": "app", "dependencies": { "aa": "^1.0.0" } }));
+
+    project.click_ok(&["install", "--save-to", "buildDependencies", "foo@^1"]);
+    let manifest = project.json("package.json");
+    assert_eq!(manifest["buildDependencies"], json!({ "foo": "^1" }));
+    assert_eq!(manifest["dependencies"], json!({ "aa": "^1.0.0" }));
+    assert!(project.exists("node_modules/foo/package.json"));
+
+    // Resolved from package.json again, the group is left out until it is included
+    std::fs::remove_dir_all(project.path.join("node_modules")).unwrap();
+    std::fs::remove_file(project.path.join("click-lock.json")).unwrap();
+    project.click_ok(&["install"]);
+    assert!(project.exists("node_modules/aa/package.json"));
+    assert!(!project.exists("node_modules/foo"));
+
+    project.click_ok(&["install", "--include", "buildDependencies"]);
+    assert!(project.exists("node_modules/foo/package.json"));
+
+    for group in [
+        "build",
+        "peerDependencies",
+        "BuildDependencies",
+        "build-Dependencies",
+    ] {
+        let output = project.click(&["install", "--save-to", group, "foo"]);
+        assert_eq!(output.status.code(), Some(2), "{group}");
+    }
+}
+
+#[test]
+fn json_output_has_warnings_with_codes_rather_than_text() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0")
+            .dependency("bb", "^1.0.0")
+            .field("engines", json!({ "node": "<1" })),
+        Package::new("bb", "1.0.0").field("deprecated", json!("use cc instead")),
+    ]);
+    let project = Project::new("json-warnings", &registry);
+
+    let output = project.click(&["install", "aa", "--json"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("Warning:"), "{stderr}");
+
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let warnings = result["warnings"].as_array().unwrap();
+    let codes = warnings
+        .iter()
+        .map(|warning| {
+            (
+                warning["code"].as_str().unwrap(),
+                warning["package"].as_str().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [("engineMismatch", "aa@1.0.0"), ("deprecated", "bb@1.0.0")]
+    );
+    assert!(warnings[0]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("'aa@1.0.0' requires Node <1"));
+
+    // Without --json they are printed as text
+    let project = Project::new("text-warnings", &registry);
+    let output = project.click(&["install", "aa"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Warning: 'aa@1.0.0' requires Node <1"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("deprecated package(s) were installed"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn scoped_packages_are_fetched_from_their_registry_with_its_token() {
+    let registry = Registry::start(&[Package::new("aa", "1.0.0")]);
+    let one = Registry::start(&[Package::new("@one/pkg", "1.0.0")]);
+    let two = Registry::start(&[Package::new("@two/pkg", "1.0.0").dependency("aa", "^1.0.0")]);
+    let project = Project::new("scoped-registries", &registry);
+    let key = |registry: &Registry| registry.url.trim_start_matches("http:").to_string();
+    project.write(
+        ".npmrc",
+        &format!(
+            "@one:registry={}/\n@two:registry={}\n{}/:_authToken=token-one\n{}/:_authToken=token-two\n",
+            one.url,
+            two.url,
+            key(&one),
+            key(&two)
+        ),
+    );
+
+    project.click_ok(&["install", "@one/pkg", "@two/pkg", "aa"]);
+    assert!(project.exists("node_modules/@one/pkg/package.json"));
+    assert!(project.exists("node_modules/@two/pkg/package.json"));
+    assert!(project.exists("node_modules/aa/package.json"));
+
+    // Every request a registry received is for its own packages, with its own token
+    for (registry, package, authorization) in [
+        (&registry, "/aa", None),
+        (&one, "/@one", Some("Bearer token-one")),
+        (&two, "/@two", Some("Bearer token-two")),
+    ] {
+        let requests = registry.requests();
+        assert!(!requests.is_empty());
+        for request in requests {
+            assert!(request.path.starts_with(package), "{}", request.path);
+            assert_eq!(
+                request.authorization.as_deref(),
+                authorization,
+                "{}",
+                request.path
+            );
+        }
+    }
+    assert_eq!(one.tarball_requests().len(), 1);
+    assert_eq!(two.tarball_requests().len(), 1);
+    assert_eq!(registry.tarball_requests().len(), 1);
+}
+
+#[test]
+fn list_groups_packages_by_the_registry_they_were_resolved_from() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0"),
+        Package::new("bb", "1.0.0").dependency("aa", "^1.0.0"),
+    ]);
+    let scoped = Registry::start(&[Package::new("@two/pkg", "1.0.0").dependency("aa", "^1.0.0")]);
+    let project = Project::new("group-by-registry", &registry);
+    project.write(".npmrc", &format!("@two:registry={}/\n", scoped.url));
+
+    project.click_ok(&["install", "@two/pkg", "bb"]);
+
+    let lockfile = project.json("click-lock.json");
+    for (package, source) in [
+        ("aa@1.0.0", &registry),
+        ("bb@1.0.0", &registry),
+        ("@two/pkg@1.0.0", &scoped),
+    ] {
+        let recorded = lockfile["packages"][package]["registry"].as_str().unwrap();
+        assert!(recorded.starts_with(&source.url), "{package} {recorded}");
+    }
+
+    // aa is depended on by a package of each registry, but is listed under the one it was resolved from. Registries are
+    // listed in order, which depends on the port each was started on.
+    let mut groups = [
+        format!("{}\n  aa@1.0.0\n  bb@1.0.0\n", registry.url),
+        format!("{}\n  @two/pkg@1.0.0\n", scoped.url),
+    ];
+    groups.sort();
+    assert_eq!(
+        project.click_ok(&["list", "--group-by-registry"]),
+        groups.concat()
+    );
+    assert_eq!(
+        project.click_ok(&["tree", "--group-by-registry"]),
+        groups.concat()
+    );
+
+    let groups: serde_json::Value =
+        serde_json::from_str(&project.click_ok(&["list", "--group-by-registry", "--json"]))
+            .unwrap();
+    assert_eq!(
+        groups,
+        serde_json::json!({
+            registry.url.as_str(): ["aa@1.0.0", "bb@1.0.0"],
+            scoped.url.as_str(): ["@two/pkg@1.0.0"],
+        })
+    );
+}
+
+#[test]
+fn verify_flags_an_installed_package_whose_files_changed() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0").dependency("bb", "^1.0.0"),
+        Package::new("bb", "1.0.0"),
+    ]);
+    let project = Project::new("verify", &registry);
+    project.click_ok(&["install", "aa"]);
+    assert!(project
+        .click_ok(&["verify"])
+        .contains("2 package(s) verified"));
+
+    // node_modules links the package from the cache, so this changes what was installed
+    project.write("node_modules/bb/index.js", "module.exports = 'tampered';\n");
+
+    let output = project.click(&["verify"]);
+    assert_eq!(output.status.code(), Some(5));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("'bb@1.0.0' has changed since it was installed"));
+    assert!(!stdout.contains("'aa@1.0.0' has changed"));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("1 package(s) have changed since they were installed: bb@1.0.0"));
+}
+
+#[test]
+fn cache_add_fills_the_store_for_an_offline_install() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0").dependency("bb", "^1.0.0"),
+        Package::new("bb", "1.0.0"),
+    ]);
+    let project = Project::new("cache-add", &registry);
+    let stored = |package: &str| {
+        let digest = openssl::sha::sha512(registry.tarball(package))
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        project
+            .home()
+            .join(".click/cache/sha512")
+            .join(digest)
+            .is_file()
+    };
+
+    let result: serde_json::Value =
+        serde_json::from_str(&project.click_ok(&["cache", "add", "aa@^1", "--json"])).unwrap();
+    assert_eq!(result, json!({ "added": ["aa@1.0.0"], "skipped": [] }));
+    assert!(stored("aa@1.0.0"));
+    assert!(stored("bb@1.0.0"));
+    assert!(!project.exists("node_modules"));
+    assert!(!project.exists("package.json"));
+
+    project.write("specs.txt", "bb@^1\n");
+    let result: serde_json::Value = serde_json::from_str(&project.click_ok(&[
+        "cache",
+        "add",
+        "aa",
+        "--from-file",
+        "specs.txt",
+        "--json",
+    ]))
+    .unwrap();
+    assert_eq!(
+        result,
+        json!({ "added": [], "skipped": ["aa@1.0.0", "bb@1.0.0"] })
+    );
+
+    let requested = registry.requests().len();
+    project.click_ok(&["install", "aa@^1", "--offline"]);
+    assert_eq!(registry.requests().len(), requested);
+    assert!(project.exists("node_modules/aa/package.json"));
+    assert!(project.exists("node_modules/bb/package.json"));
+}
+
+#[test]
+fn audit_asks_the_audit_registry_with_its_own_token() {
+    let registry = Registry::start(&[Package::new("aa", "1.0.0")]);
+    let audit_registry = Registry::start(&[]);
+    let advisories = "/-/npm/v1/security/advisories/bulk";
+    audit_registry.route(
+        advisories,
+        200,
+        &json!({ "aa": [{
+            "id": 1,
+            "title": "Prototype pollution",
+            "url": "https://example.com/advisories/1",
+            "severity": "high",
+            "vulnerable_versions": "<2.0.0",
+        }] })
+        .to_string(),
+    );
+    let project = Project::new("audit-registry", &registry);
+    project.write(
+        ".npmrc",
+        &format!(
+            "{}/:_authToken=registry-token\n{}/:_authToken=audit-token\n",
+            registry.url.trim_start_matches("http:"),
+            audit_registry.url.trim_start_matches("http:")
+        ),
+    );
+    project.click_ok(&["install", "aa"]);
+    let requested = registry.requests().len();
+
+    let output = project.click(&[
+        "audit",
+        "--audit-registry",
+        &format!("{}/", audit_registry.url),
+        "--json",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    let vulnerabilities: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(vulnerabilities[0]["package"], json!("aa@1.0.0"));
+
+    assert_eq!(registry.requests().len(), requested);
+    let requests = audit_registry.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, advisories);
+    assert_eq!(
+        requests[0].authorization.as_deref(),
+        Some("Bearer audit-token")
+    );
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&requests[0].body).unwrap(),
+        json!({ "aa": ["1.0.0"] })
+    );
+}
+
+#[test]
+fn keep_going_attempts_everything_and_records_only_what_installed() {
+    let broken = Package::new("dd", "1.0.0");
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0"),
+        Package::new("bb", "1.0.0").dependency("gone", "^1.0.0"),
+        Package::new("cc", "1.0.0"),
+        broken.clone(),
+    ]);
+    registry.corrupt(&broken, usize::MAX);
+    let project = Project::new("keep-going", &registry);
+    project.package_json(json!({ "name": "app" }));
+
+    let output = project.click(&["install", "aa", "bb", "dd", "cc", "--keep-going"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("package 'gone' is not in the registry"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("integrity check failed for 'dd@1.0.0'"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("2 package(s) failed to install: bb@1.0.0, dd@1.0.0"),
+        "{stderr}"
+    );
+
+    // Every package was attempted, cc after the two that failed before it
+    let paths = registry
+        .requests()
+        .into_iter()
+        .map(|request| request.path)
+        .collect::<Vec<_>>();
+    for path in [
+        "/aa/-/aa-1.0.0.tgz",
+        "/bb/-/bb-1.0.0.tgz",
+        "/gone",
+        "/dd/-/dd-1.0.0.tgz",
+        "/cc/-/cc-1.0.0.tgz",
+    ] {
+        assert!(paths.iter().any(|requested| requested == path), "{path}");
+    }
+
+    // Those that installed are locked under the range they were saved with
+    let lockfile = project.json("click-lock.json");
+    assert_eq!(
+        lockfile["requested"],
+        json!({ "aa@^1.0.0": "aa@1.0.0", "cc@^1.0.0": "cc@1.0.0" })
+    );
+    let locked = lockfile["packages"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .collect::<Vec<_>>();
+    assert_eq!(locked, ["aa@1.0.0", "cc@1.0.0"]);
+    assert_eq!(
+        project.json("package.json")["dependencies"],
+        json!({ "aa": "^1.0.0", "cc": "^1.0.0" })
+    );
+    assert!(project.exists("node_modules/aa/package.json"));
+    assert!(project.exists("node_modules/cc/package.json"));
+    assert!(!project.exists("node_modules/bb"));
+    assert!(!project.exists("node_modules/dd"));
+}
+
+#[test]
+fn outdated_reports_wanted_latest_compatible_and_latest() {
+    let mut registry = Registry::start(&[Package::new("aa", "1.2.0"), Package::new("bb", "1.2.0")]);
+    let project = Project::new("outdated-compatible", &registry);
+    project.package_json(json!({ "dependencies": { "aa": "^1.2.0", "bb": "~1.2.0" } }));
+    project.click_ok(&["install"]);
+
+    registry.serve(&[
+        Package::new("aa", "1.2.0"),
+        Package::new("aa", "1.9.0"),
+        Package::new("aa", "2.0.0"),
+        Package::new("bb", "1.2.0"),
+        Package::new("bb", "1.2.5"),
+        Package::new("bb", "1.9.0"),
+        Package::new("bb", "2.0.0"),
+    ]);
+    let output = project.click(&["outdated", "--json"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let outdated: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        outdated,
+        json!([
+            {
+                "name": "aa",
+                "current": "1.2.0",
+                "wanted": "1.9.0",
+                "latestCompatible": "1.9.0",
+                "latest": "2.0.0",
+                "workspace": "(root)",
+            },
+            {
+                "name": "bb",
+                "current": "1.2.0",
+                "wanted": "1.2.5",
+                "latestCompatible": "1.9.0",
+                "latest": "2.0.0",
+                "workspace": "(root)",
+            },
+        ])
+    );
+
+    let output = project.click(&["outdated"]);
+    let table = String::from_utf8_lossy(&output.stdout);
+    assert!(table.lines().next().unwrap().contains("Compatible"));
+}
+
+#[test]
+fn locked_packages_are_downloaded_from_their_resolved_url() {
+    let packages = [
+        Package::new("aa", "1.0.0").dependency("bb", "^1.0.0"),
+        Package::new("bb", "1.0.0"),
+    ];
+    let registry = Registry::start(&packages);
+    let mirror = Registry::start(&packages);
+    let project = Project::new("lockfile-urls", &registry);
+    project.package_json(json!({ "dependencies": { "aa": "^1.0.0" } }));
+    project.click_ok(&["install"]);
+
+    let lockfile = project
+        .read("click-lock.json")
+        .replace(&registry.url, &mirror.url);
+    project.write("click-lock.json", &lockfile);
+    let clear = || {
+        std::fs::remove_dir_all(project.home()).unwrap();
+        std::fs::remove_dir_all(project.path.join("node_modules")).unwrap();
+    };
+
+    clear();
+    let requests = registry.tarball_requests().len();
+    project.click_ok(&["install"]);
+    assert_eq!(registry.tarball_requests().len(), requests);
+    assert_eq!(mirror.tarball_requests().len(), 2);
+
+    clear();
+    project.click_ok(&["install", "--ignore-lockfile-urls"]);
+    assert_eq!(registry.tarball_requests().len(), requests + 2);
+    assert_eq!(mirror.tarball_requests().len(), 2);
+}
+
+#[test]
+fn exec_installs_a_missing_package_and_runs_the_bin_given_with_bin() {
+    let script = "#!/bin/sh\necho \"$0 $*\" > ran.txt\n";
+    let registry = Registry::start(&[Package::new("toolkit", "1.0.0")
+        .field(
+            "bin",
+            json!({ "hello": "bin/hello.sh", "bye": "bin/bye.sh" }),
+        )
+        .file("bin/hello.sh", script)
+        .file("bin/bye.sh", script)]);
+    let project = Project::new("exec-bin", &registry);
+
+    // Neither executable is named after the package, so one has to be picked
+    let output = project.click(&["exec", "toolkit@^1.0.0", "--", "x"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("more than one executable"));
+    let output = project.click(&["exec", "--bin", "missing", "toolkit@^1.0.0"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("it has: bye, hello"));
+
+    project.click_ok(&["exec", "--bin", "hello", "toolkit@^1.0.0", "--name", "a b"]);
+    let ran = project.read("ran.txt");
+    assert!(ran.ends_with("hello --name a b\n"), "{ran}");
+    assert!(!project.exists("node_modules"));
+
+    // Kept in the cache, so the next run does not download it again
+    let requests = registry.tarball_requests().len();
+    project.click_ok(&["exec", "--bin", "bye", "toolkit@^1.0.0"]);
+    assert!(project.read("ran.txt").ends_with("bye \n"));
+    assert_eq!(registry.tarball_requests().len(), requests);
+    assert_eq!(requests, 1);
+}
+
+#[test]
+fn lockfile_format_toml_is_written_and_installed_from() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0").dependency("bb", "^1.0.0"),
+        Package::new("bb", "1.0.0"),
+    ]);
+    let project = Project::new("toml-lockfile", &registry);
+    project.write(
+        "package.json",
+        &json!({ "name": "toml-lockfile", "dependencies": { "aa": "^1.0.0" } }).to_string(),
+    );
+    project.click_ok(&["install"]);
+    let json_lockfile = project.json("click-lock.json");
+
+    project.click_ok(&["install", "--lockfile-format", "toml"]);
+    assert!(!project.exists("click-lock.json"));
+    let toml = project.read("click-lock.toml");
+    assert!(
+        toml.contains("[requested]\n\"aa@^1.0.0\" = \"aa@1.0.0\"\n"),
+        "{toml}"
+    );
+
+    // The project keeps its format, and the lockfile reads back as the same graph
+    project.click_ok(&["install", "--frozen-lockfile"]);
+    assert_eq!(project.read("click-lock.toml"), toml);
+    assert!(!project.exists("click-lock.json"));
+
+    // A corrupt TOML lockfile is reported with where it stopped making sense, the same as JSON
+    let string = toml.find("resolved = \"").unwrap() + "resolved = \"".len() + 4;
+    project.write("click-lock.toml", &toml[..string]);
+    let line = toml[..string].lines().count();
+    let output = project.click(&["install"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&format!(
+        "click-lock.toml' is corrupt (expected '\"' at line {line} column 17)"
+    )));
+    project.write("click-lock.toml", &toml);
+
+    project.click_ok(&["install", "--lockfile-format", "json"]);
+    assert!(!project.exists("click-lock.toml"));
+    assert_eq!(project.json("click-lock.json"), json_lockfile);
+
+    let output = project.click(&["install", "--lockfile-format", "yaml"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn production_only_lockfile_leaves_out_the_dev_subtree() {
+    let registry = Registry::start(&[
+        Package::new("app", "1.0.0").dependency("shared", "^1.0.0"),
+        Package::new("shared", "1.0.0"),
+        Package::new("tester", "1.0.0")
+            .dependency("assert", "^1.0.0")
+            .dependency("shared", "^1.0.0"),
+        Package::new("assert", "1.0.0"),
+    ]);
+    let project = Project::new("production-lockfile", &registry);
+    project.write(
+        "package.json",
+        &json!({
+            "name": "production-lockfile",
+            "dependencies": { "app": "^1.0.0" },
+            "devDependencies": { "tester": "^1.0.0" },
+        })
+        .to_string(),
+    );
+    project.click_ok(&[
+        "install",
+        "--production-only-lockfile",
+        "--lockfile-out",
+        "deploy/click-lock.json",
+    ]);
+
+    let packages = |lockfile: &serde_json::Value| {
+        let mut packages = lockfile["packages"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        packages.sort();
+        packages
+    };
+
+    let lockfile = project.json("click-lock.json");
+    assert_eq!(
+        packages(&lockfile),
+        ["app@1.0.0", "assert@1.0.0", "shared@1.0.0", "tester@1.0.0"]
+    );
+    assert!(lockfile["requested"]["tester@^1.0.0"].is_string());
+
+    // shared is kept as app depends on it too
+    let production = project.json("deploy/click-lock.json");
+    assert_eq!(packages(&production), ["app@1.0.0", "shared@1.0.0"]);
+    assert_eq!(
+        production["requested"],
+        json!({ "app@^1.0.0": "app@1.0.0" })
+    );
+    assert_eq!(production["lockfileVersion"], lockfile["lockfileVersion"]);
+}
+
+#[test]
+fn corrupt_downloads_are_retried_then_fetched_from_a_mirror() {
+    let aa = Package::new("aa", "1.0.0");
+    let registry = Registry::start(std::slice::from_ref(&aa));
+    let mirror = Registry::start(std::slice::from_ref(&aa));
+    let project = Project::new("integrity-retry", &registry);
+    project.package_json(json!({ "dependencies": { "aa": "^1.0.0" } }));
+
+    // Corrupted once on the way, the next download is intact
+    registry.corrupt(&aa, 1);
+    let output = project.click(&["install"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("'aa@1.0.0' did not match its integrity, downloading it again"),
+        "{stderr}"
+    );
+    assert_eq!(registry.tarball_requests().len(), 2);
+    assert!(project.exists("node_modules/aa/package.json"));
+    assert_eq!(
+        project.json("click-lock.json")["packages"]["aa@1.0.0"]["integrity"],
+        json!(common::integrity(registry.tarball("aa@1.0.0")))
+    );
+
+    // Once every retry of the registry is corrupted, the mirror is tried
+    std::fs::remove_dir_all(project.home()).unwrap();
+    std::fs::remove_dir_all(project.path.join("node_modules")).unwrap();
+    project.write(".npmrc", &format!("mirrors={}\n", mirror.url));
+    registry.corrupt(&aa, usize::MAX);
+    let output = project.click(&["install"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!("falling back to '{}", mirror.url)),
+        "{stderr}"
+    );
+    assert_eq!(registry.tarball_requests().len(), 2 + 3);
+    assert_eq!(mirror.tarball_requests().len(), 1);
+    assert!(project.exists("node_modules/aa/package.json"));
+
+    // Without a mirror, the install fails once the retries are used up
+    std::fs::remove_dir_all(project.home()).unwrap();
+    std::fs::remove_dir_all(project.path.join("node_modules")).unwrap();
+    project.write(".npmrc", "");
+    let output = project.click(&["install"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("integrity check failed for 'aa@1.0.0'")
+    );
+    assert_eq!(registry.tarball_requests().len(), 2 + 3 + 3);
+}
+
+#[test]
+fn outdated_summary_only_counts_by_how_far_behind_and_omits_the_table() {
+    let mut registry = Registry::start(&[
+        Package::new("aa", "1.0.0"),
+        Package::new("bb", "1.0.0"),
+        Package::new("cc", "1.0.0"),
+        Package::new("dd", "1.0.0"),
+        Package::new("ee", "1.0.0"),
+    ]);
+    let project = Project::new("outdated-summary", &registry);
+    project.package_json(json!({ "dependencies": {
+        "aa": "^1.0.0",
+        "bb": "^1.0.0",
+        "cc": "1.0.0",
+        "dd": "~1.0.0",
+        "ee": "^1.0.0",
+    } }));
+    project.click_ok(&["install"]);
+
+    registry.serve(&[
+        Package::new("aa", "1.0.0"),
+        Package::new("aa", "2.0.0"),
+        Package::new("bb", "1.0.0"),
+        Package::new("bb", "1.1.0"),
+        Package::new("cc", "1.0.0"),
+        Package::new("cc", "1.0.1"),
+        Package::new("dd", "1.0.0"),
+        Package::new("dd", "1.0.5"),
+        Package::new("ee", "1.0.0"),
+    ]);
+    let output = project.click(&["outdated", "--summary-only", "--json"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap(),
+        json!({ "outdated": 4, "patch": 2, "minor": 1, "major": 1, "missing": 0 })
+    );
+
+    let output = project.click(&["outdated", "--summary-only"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "4 outdated: 2 patch, 1 minor, 1 major\n"
+    );
+}
+
+#[test]
+fn audit_summary_only_counts_by_severity_and_omits_the_list() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0"),
+        Package::new("bb", "1.0.0"),
+        Package::new("cc", "1.0.0"),
+    ]);
+    let advisory = |id: u64, severity: &str| {
+        json!([{
+            "id": id,
+            "title": format!("Advisory {id}"),
+            "url": format!("https://example.com/advisories/{id}"),
+            "severity": severity,
+            "vulnerable_versions": "<2.0.0",
+        }])
+    };
+    registry.route(
+        "/-/npm/v1/security/advisories/bulk",
+        200,
+        &json!({
+            "aa": advisory(1, "high"),
+            "bb": advisory(2, "moderate"),
+            "cc": advisory(3, "high"),
+        })
+        .to_string(),
+    );
+    let project = Project::new("audit-summary", &registry);
+    project.package_json(
+        json!({ "dependencies": { "aa": "^1.0.0", "bb": "^1.0.0", "cc": "^1.0.0" } }),
+    );
+    project.click_ok(&["install"]);
+
+    let output = project.click(&["audit", "--summary-only", "--json"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap(),
+        json!({
+            "vulnerabilities": 3,
+            "severities": { "info": 0, "low": 0, "moderate": 1, "high": 2, "critical": 0 },
+        })
+    );
+
+    let output = project.click(&["audit", "--summary-only"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Found 3 vulnerability(s) (2 high, 1 moderate)\n"
+    );
+}
+
+#[test]
+fn ignore_scripts_lists_exactly_the_packages_with_install_scripts() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0")
+            .dependency("cc", "^1.0.0")
+            .field("hasInstallScript", json!(true))
+            .field("scripts", json!({ "postinstall": "echo ran > ran.txt" })),
+        Package::new("bb", "1.0.0")
+            .field("hasInstallScript", json!(true))
+            .field("scripts", json!({ "preinstall": "echo ran > ran.txt" })),
+        Package::new("cc", "1.0.0").field("scripts", json!({ "test": "echo test" })),
+    ]);
+    let project = Project::new("ignore-scripts-summary", &registry);
+    project.package_json(json!({ "dependencies": { "aa": "^1.0.0", "bb": "^1.0.0" } }));
+
+    let output = project.click(&["install", "--ignore-scripts", "--json"]);
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let warnings = result["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|warning| warning["code"] == json!("scriptsNotRun"))
+        .collect::<Vec<_>>();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+        warnings[0]["message"],
+        json!("2 package(s) have install scripts, which were not run as --ignore-scripts was given: aa@1.0.0, bb@1.0.0")
+    );
+    assert!(!project.exists("node_modules/aa/ran.txt"));
+    assert!(!project.exists("node_modules/bb/ran.txt"));
+
+    // Packages linked from the cache are listed the same as those downloaded
+    std::fs::remove_dir_all(project.path.join("node_modules")).unwrap();
+    let output = project.click(&["install", "--ignore-scripts"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Warning: 2 package(s) have install scripts, which were not run as --ignore-scripts was given: aa@1.0.0, bb@1.0.0\n"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn add_saves_to_dependencies_unless_dev_or_save_is_turned_off() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.2.0"),
+        Package::new("bb", "1.0.0"),
+        Package::new("cc", "1.0.0"),
+    ]);
+    let project = Project::new("add", &registry);
+    project.package_json(json!({ "name": "add" }));
+
+    project.click_ok(&["add", "aa@^1"]);
+    project.click_ok(&["add", "--dev", "bb@^1"]);
+    let manifest = project.json("package.json");
+    assert_eq!(manifest["dependencies"], json!({ "aa": "^1" }));
+    assert_eq!(manifest["devDependencies"], json!({ "bb": "^1" }));
+    assert!(project.exists("node_modules/aa/package.json"));
+    assert!(project.exists("node_modules/bb/package.json"));
+
+    // With save=false packages are only installed, unless --save is given
+    project.write(".clickrc", "save=false\n");
+    project.click_ok(&["add", "cc@^1"]);
+    assert!(project.exists("node_modules/cc/package.json"));
+    assert!(project.json("package.json")["dependencies"]
+        .get("cc")
+        .is_none());
+
+    project.click_ok(&["add", "cc@^1", "--save"]);
+    assert_eq!(
+        project.json("package.json")["dependencies"],
+        json!({ "aa": "^1", "cc": "^1" })
+    );
+}
+
+#[test]
+fn resolve_writes_the_graph_without_creating_node_modules() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0").dependency("bb", "^1.0.0"),
+        Package::new("aa", "1.1.0").dependency("bb", "^1.0.0"),
+        Package::new("bb", "1.0.0"),
+    ]);
+    let project = Project::new("resolve", &registry);
+
+    project.click_ok(&["resolve", "aa@^1.0.0", "--output", "graph.json"]);
+    let graph = project.json("graph.json");
+    assert_eq!(graph["root"], json!("aa@1.1.0"));
+    assert_eq!(
+        graph["packages"],
+        json!({
+            "aa@1.1.0": {
+                "integrity": common::integrity(registry.tarball("aa@1.1.0")),
+                "dependencies": ["bb@1.0.0"],
+            },
+            "bb@1.0.0": {
+                "integrity": common::integrity(registry.tarball("bb@1.0.0")),
+                "dependencies": [],
+            },
+        })
+    );
+    assert!(!project.exists("node_modules"));
+    assert!(!project.exists("click-lock.json"));
+
+    // Printed the same with --json, now from the cache
+    let printed = project.click_ok(&["resolve", "aa@^1.0.0", "--json"]);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&printed).unwrap(),
+        graph
+    );
+    assert!(!project.exists("node_modules"));
+}
+
+#[test]
+fn tarball_that_does_not_match_its_integrity_is_not_installed() {
+    let aa = Package::new("aa", "1.0.0").dependency("bb", "^1.0.0");
+    let registry = Registry::start(&[aa.clone(), Package::new("bb", "1.0.0")]);
+    let project = Project::new("integrity-mismatch", &registry);
+    project.package_json(json!({ "dependencies": { "aa": "^1.0.0" } }));
+    registry.corrupt(&aa, usize::MAX);
+
+    let output = project.click(&["install"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("integrity check failed for 'aa@1.0.0'")
+    );
+
+    // Nothing of it is kept, so the next install downloads it again instead of trusting what is left
+    let installed = std::fs::read_dir(project.path.join("node_modules"))
+        .map(|entries| entries.count())
+        .unwrap_or(0);
+    assert_eq!(installed, 0);
+    assert!(!project.exists("click-lock.json"));
+    assert!(!project.cache().join("aa@1.0.0").exists());
+    assert!(!project
+        .cache()
+        .join("bb@1.0.0/package/click-lock.json")
+        .exists());
+}
+
+#[test]
+fn uninstall_removes_orphans_scopes_and_what_was_saved() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0").dependency("bb", "^1.0.0"),
+        Package::new("bb", "1.0.0"),
+        Package::new("cc", "1.0.0"),
+        Package::new("@s/ee", "1.0.0"),
+    ]);
+    let project = Project::new("uninstall", &registry);
+    project.click_ok(&["install", "aa", "cc", "@s/ee"]);
+    assert!(project.exists("node_modules/@s/ee/package.json"));
+
+    let stdout = project.click_ok(&["uninstall", "aa", "@s/ee"]);
+    assert!(stdout.contains("Removed 'aa@1.0.0'"), "{stdout}");
+    assert!(stdout.contains("Removed '@s/ee@1.0.0'"), "{stdout}");
+    assert!(
+        stdout.contains("Removed 1 dependency that is no longer needed"),
+        "{stdout}"
+    );
+
+    // bb was only there for aa, and the scope directory goes with its last package
+    assert!(!project.exists("node_modules/aa"));
+    assert!(!project.exists("node_modules/bb"));
+    assert!(!project.exists("node_modules/@s"));
+    assert!(project.exists("node_modules/cc/package.json"));
+
+    assert_eq!(
+        project.json("package.json")["dependencies"],
+        json!({ "cc": "^1.0.0" })
+    );
+    let lockfile = project.json("click-lock.json");
+    assert_eq!(lockfile["requested"], json!({ "cc@^1.0.0": "cc@1.0.0" }));
+    let locked = lockfile["packages"].as_object().unwrap();
+    assert_eq!(locked.keys().collect::<Vec<_>>(), ["cc@1.0.0"]);
+}
+
+#[test]
+fn ci_reinstalls_from_the_lockfile_without_changing_it() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0").dependency("bb", "^1.0.0"),
+        Package::new("bb", "1.0.0"),
+        Package::new("cc", "1.0.0"),
+    ]);
+    let project = Project::new("ci", &registry);
+    project.package_json(json!({ "dependencies": { "aa": "^1.0.0" } }));
+    project.click_ok(&["install"]);
+    let lockfile = project.read("click-lock.json");
+    let modified = std::fs::metadata(project.path.join("click-lock.json"))
+        .unwrap()
+        .modified()
+        .unwrap();
+
+    // Whatever was in node_modules is removed, not installed over
+    project.write("node_modules/stale/index.js", "");
+    let stdout = project.click_ok(&["ci"]);
+    assert!(stdout.contains("Removed node_modules"), "{stdout}");
+    assert!(!project.exists("node_modules/stale"));
+    assert!(project.exists("node_modules/aa/package.json"));
+    assert!(project.exists("node_modules/bb/package.json"));
+
+    // A package.json the lockfile does not match fails before node_modules is touched
+    project.write("node_modules/stale/index.js", "");
+    project.package_json(json!({ "dependencies": { "aa": "^1.0.0", "cc": "^1.0.0" } }));
+    let output = project.click(&["ci"]);
+    assert_eq!(output.status.code(), Some(5));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("the lockfile is out of date"), "{stderr}");
+    assert!(stderr.contains("cc@^1.0.0"), "{stderr}");
+    assert!(project.exists("node_modules/stale/index.js"));
+    assert!(!project.exists("node_modules/cc"));
+
+    // Neither a clean install nor a failed one writes the lockfile
+    assert_eq!(project.read("click-lock.json"), lockfile);
+    assert_eq!(
+        std::fs::metadata(project.path.join("click-lock.json"))
+            .unwrap()
+            .modified()
+            .unwrap(),
+        modified
+    );
+}
+
+#[test]
+fn frozen_lockfile_installs_what_is_locked_or_fails() {
+    let registry = Registry::start(&[
+        Package::new("aa", "1.0.0").dependency("bb", "^1.0.0"),
+        Package::new("bb", "1.0.0"),
+        Package::new("cc", "1.0.0"),
+    ]);
+    let project = Project::new("frozen-lockfile", &registry);
+    project.package_json(json!({ "dependencies": { "aa": "^1.0.0" } }));
+    project.click_ok(&["install"]);
+    let lockfile = project.read("click-lock.json");
+
+    // From an empty cache and node_modules, everything is installed from the lockfile alone
+    std::fs::remove_dir_all(project.home()).unwrap();
+    std::fs::remove_dir_all(project.path.join("node_modules")).unwrap();
+    project.click_ok(&["install", "--frozen-lockfile"]);
+    assert!(project.exists("node_modules/aa/package.json"));
+    assert!(project.exists("node_modules/bb/package.json"));
+    assert_eq!(project.read("click-lock.json"), lockfile);
+
+    // A dependency the lockfile does not have would change it, so nothing is installed
+    project.package_json(json!({ "dependencies": { "aa": "^1.0.0", "cc": "^1.0.0" } }));
+    let output = project.click(&["install", "--frozen-lockfile"]);
+    assert_eq!(output.status.code(), Some(5));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("the lockfile is out of date"), "{stderr}");
+    assert!(stderr.contains("cc@^1.0.0"), "{stderr}");
+    assert!(!project.exists("node_modules/cc"));
+    assert_eq!(project.read("click-lock.json"), lockfile);
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use std::fs;
+
+use common::{Project, Registry};
+use serde_json::json;
+
+#[test]
+fn login_saves_the_token_of_the_registry_and_logout_removes_it() {
+    let registry = Registry::start(&[]);
+    let project = Project::new("login", &registry);
+    let npmrc = project.home().join(".npmrc");
+    let token_key = format!("{}/:_authToken", registry.url.trim_start_matches("http:"));
+
+    // The token of another registry, and other settings, are kept as they are
+    fs::create_dir_all(project.home()).unwrap();
+    fs::write(
+        &npmrc,
+        "save-exact=true\n//other.test/:_authToken=other-token\n",
+    )
+    .unwrap();
+
+    let login = json!({
+        "loginUrl": format!("{}/login", registry.url),
+        "doneUrl": format!("{}/-/v1/login/done", registry.url),
+    });
+    registry.route("/-/v1/login", 200, &login.to_string());
+    registry.route("/-/v1/login/done", 200, "{\"token\":\"web-token\"}");
+    registry.route("/-/user/token/web-token", 200, "{}");
+
+    let stdout = project.click_ok(&["login"]);
+    assert!(
+        stdout.contains(&format!("Logged in to '{}'", registry.url)),
+        "{stdout}"
+    );
+    assert_eq!(
+        fs::read_to_string(&npmrc).unwrap(),
+        format!("save-exact=true\n//other.test/:_authToken=other-token\n{token_key}=web-token\n")
+    );
+
+    // Logging out revokes the token before removing it
+    let stdout = project.click_ok(&["logout"]);
+    assert!(
+        stdout.contains(&format!("Logged out of '{}'", registry.url)),
+        "{stdout}"
+    );
+    assert!(registry
+        .requests()
+        .iter()
+        .any(|request| request.method == "DELETE" && request.path == "/-/user/token/web-token"));
+    assert_eq!(
+        fs::read_to_string(&npmrc).unwrap(),
+        "save-exact=true\n//other.test/:_authToken=other-token\n"
+    );
+
+    let output = project.click(&["logout"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&format!(
+        "not logged in to '{}', run `click login` first",
+        registry.url
+    )));
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::{Project, Registry, Request};
+use serde_json::{json, Value};
+
+/// A project logged in to the registry, with a package ready to publish.
+fn publishable(name: &str, registry: &Registry) -> Project {
+    let project = Project::new(name, registry);
+    project.write(
+        ".npmrc",
+        &format!(
+            "{}/:_authToken=publish-token\n",
+            registry.url.trim_start_matches("http:")
+        ),
+    );
+    project.package_json(json!({
+        "name": "pub",
+        "version": "1.2.3",
+        "description": "A package to publish",
+    }));
+    project.write("index.js", "module.exports = 1;\n");
+    registry.route("/pub", 200, "{}");
+    project
+}
+
+fn uploads(registry: &Registry) -> Vec<Request> {
+    registry
+        .requests()
+        .into_iter()
+        .filter(|request| request.method == "PUT")
+        .collect()
+}
+
+#[test]
+fn publish_uploads_the_version_and_its_tarball() {
+    let registry = Registry::start(&[]);
+    let project = publishable("publish", &registry);
+
+    let stdout = project.click_ok(&["publish", "--tag", "beta", "--access", "public"]);
+    assert!(stdout.contains("Published pub@1.2.3"), "{stdout}");
+
+    let uploads = uploads(&registry);
+    assert_eq!(uploads.len(), 1);
+    assert_eq!(uploads[0].path, "/pub");
+    assert_eq!(
+        uploads[0].authorization.as_deref(),
+        Some("Bearer publish-token")
+    );
+
+    let body: Value = serde_json::from_str(&uploads[0].body).unwrap();
+    assert_eq!(body["_id"], json!("pub"));
+    assert_eq!(body["name"], json!("pub"));
+    assert_eq!(body["description"], json!("A package to publish"));
+    assert_eq!(body["dist-tags"], json!({ "beta": "1.2.3" }));
+    assert_eq!(body["access"], json!("public"));
+
+    // The attachment is the tarball the version's dist describes
+    let attachment = &body["_attachments"]["pub-1.2.3.tgz"];
+    let tarball = STANDARD
+        .decode(attachment["data"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(attachment["length"], json!(tarball.len()));
+
+    let version = &body["versions"]["1.2.3"];
+    assert_eq!(version["_id"], json!("pub@1.2.3"));
+    assert_eq!(version["name"], json!("pub"));
+    assert_eq!(
+        version["dist"]["tarball"],
+        json!(format!("{}/pub/-/pub-1.2.3.tgz", registry.url))
+    );
+    assert_eq!(
+        version["dist"]["integrity"],
+        json!(common::integrity(&tarball))
+    );
+}
+
+#[test]
+fn publish_is_sent_again_with_a_one_time_password() {
+    let registry = Registry::start(&[]);
+    let project = publishable("publish-otp", &registry);
+    registry.require_otp("123456");
+
+    // Without a terminal to enter one on, the second attempt is sent without one too
+    let output = project.click(&["publish"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("the registry wants a one-time password, give it with --otp"),
+        "{stderr}"
+    );
+    assert_eq!(uploads(&registry).len(), 2);
+
+    let output = project.click(&["publish", "--otp", "654321"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("the one-time password was not accepted"),
+        "{stderr}"
+    );
+    assert_eq!(uploads(&registry).len(), 3);
+
+    project.click_ok(&["publish", "--otp", "123456"]);
+    let uploads = uploads(&registry);
+    assert_eq!(uploads.len(), 4);
+    assert_eq!(uploads[3].otp.as_deref(), Some("123456"));
+}
+
+#[test]
+fn private_packages_are_not_published() {
+    let registry = Registry::start(&[]);
+    let project = publishable("publish-private", &registry);
+    project.package_json(json!({ "name": "pub", "version": "1.2.3", "private": true }));
+
+    let output = project.click(&["publish"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("'pub' is private, remove \"private\" from package.json to publish it"));
+    assert!(uploads(&registry).is_empty());
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use common::{Package, Project, Registry};
+use serde_json::{json, Value};
+
+/// `@acme/app` depends on `@acme/core`, which depends on `@acme/util`, and `tool` depends on nothing.
+fn monorepo(name: &str, registry: &Registry) -> Project {
+    let project = Project::new(name, registry);
+    project.package_json(json!({ "name": "root", "workspaces": ["packages/*"] }));
+
+    let workspaces = [
+        (
+            "app",
+            "@acme/app",
+            json!({ "@acme/core": "workspace:*", "aa": "^1.0.0" }),
+        ),
+        ("core", "@acme/core", json!({ "@acme/util": "workspace:*" })),
+        ("util", "@acme/util", json!({})),
+        ("tool", "tool", json!({ "bb": "^1.0.0" })),
+    ];
+    for (directory, name, dependencies) in workspaces {
+        project.write(
+            &format!("packages/{directory}/package.json"),
+            &json!({ "name": name, "version": "1.0.0", "dependencies": dependencies }).to_string(),
+        );
+    }
+
+    project
+}
+
+fn listed(project: &Project, filter: &str) -> Vec<String> {
+    let stdout = project.click_ok(&["list", "--filter", filter, "--json"]);
+    let trees: Vec<Value> = serde_json::from_str(&stdout).unwrap();
+
+    trees
+        .iter()
+        .map(|tree| tree["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn filter_selects_by_name_glob_and_relatives() {
+    let registry = Registry::start(&[Package::new("aa", "1.0.0"), Package::new("bb", "1.0.0")]);
+    let project = monorepo("filter", &registry);
+    project.click_ok(&["install"]);
+
+    assert_eq!(listed(&project, "tool"), ["tool"]);
+    assert_eq!(
+        listed(&project, "@acme/*"),
+        ["@acme/app", "@acme/core", "@acme/util"]
+    );
+    assert_eq!(listed(&project, "./packages/t*"), ["tool"]);
+    assert_eq!(
+        listed(&project, "...@acme/util"),
+        ["@acme/app", "@acme/core", "@acme/util"]
+    );
+    assert_eq!(
+        listed(&project, "...@acme/core"),
+        ["@acme/app", "@acme/core"]
+    );
+    assert_eq!(
+        listed(&project, "@acme/core..."),
+        ["@acme/core", "@acme/util"]
+    );
+
+    let output = project.click(&["list", "--filter", "@acme/missing"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn list_filter_shows_the_dependencies_of_the_workspace() {
+    let registry = Registry::start(&[Package::new("aa", "1.0.0"), Package::new("bb", "1.0.0")]);
+    let project = monorepo("list-filter", &registry);
+    project.click_ok(&["install"]);
+
+    let stdout = project.click_ok(&["list", "--filter", "tool"]);
+    assert_eq!(stdout, "tool@1.0.0\n└── bb@1.0.0\n");
+}
+
+#[test]
+fn update_filter_only_updates_the_selected_workspaces() {
+    let mut registry = Registry::start(&[Package::new("aa", "1.0.0"), Package::new("bb", "1.0.0")]);
+    let project = monorepo("update-filter", &registry);
+    project.click_ok(&["install"]);
+
+    registry.serve(&[
+        Package::new("aa", "1.0.0"),
+        Package::new("aa", "1.1.0"),
+        Package::new("bb", "1.0.0"),
+        Package::new("bb", "1.1.0"),
+    ]);
+    project.click_ok(&["update", "--filter", "@acme/app"]);
+
+    let requested = &project.json("click-lock.json")["requested"];
+    assert_eq!(requested["aa@^1.0.0"], json!("aa@1.1.0"));
+    assert_eq!(requested["bb@^1.0.0"], json!("bb@1.0.0"));
+}
+
+/// Reads package.json out of a tarball made by `click pack`.
+fn packed_manifest(tarball: &[u8]) -> Value {
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(tarball));
+    let mut entry = archive
+        .entries()
+        .unwrap()
+        .flatten()
+        .find(|entry| entry.path().unwrap().to_str() == Some("package/package.json"))
+        .unwrap();
+
+    let mut manifest = String::new();
+    std::io::Read::read_to_string(&mut entry, &mut manifest).unwrap();
+    serde_json::from_str(&manifest).unwrap()
+}
+
+#[test]
+fn workspace_ranges_resolve_to_the_sibling_and_pack_as_its_version() {
+    let registry = Registry::start(&[Package::new("aa", "1.0.0"), Package::new("bb", "1.0.0")]);
+    let project = monorepo("workspace-ranges", &registry);
+    let app = json!({
+        "name": "@acme/app",
+        "version": "1.0.0",
+        "dependencies": { "@acme/core": "workspace:^", "@acme/util": "workspace:~", "aa": "^1.0.0" },
+    });
+    project.write("packages/app/package.json", &app.to_string());
+
+    project.click_ok(&["install"]);
+    assert!(project.exists("node_modules/@acme/core/package.json"));
+    assert!(project.exists("node_modules/aa/package.json"));
+
+    let output = project.click_in("packages/app", &["pack"]);
+    assert!(output.status.success());
+    let tarball = std::fs::read(project.path.join("packages/app/acme-app-1.0.0.tgz")).unwrap();
+    assert_eq!(
+        packed_manifest(&tarball)["dependencies"],
+        json!({ "@acme/core": "^1.0.0", "@acme/util": "~1.0.0", "aa": "^1.0.0" })
+    );
+
+    // What is on disk keeps its workspace: ranges
+    assert_eq!(
+        project.json("packages/app/package.json")["dependencies"]["@acme/core"],
+        json!("workspace:^")
+    );
+}
+
+#[test]
+fn workspace_ranges_fail_for_unknown_names_and_other_versions() {
+    let registry = Registry::start(&[Package::new("aa", "1.0.0"), Package::new("bb", "1.0.0")]);
+    let project = monorepo("workspace-ranges-fail", &registry);
+
+    let failures = [
+        (
+            json!({ "@acme/missing": "workspace:*" }),
+            "no workspace of the project is named '@acme/missing'",
+        ),
+        (
+            json!({ "@acme/core": "workspace:^2.0.0" }),
+            "the workspace '@acme/core' is at version 1.0.0",
+        ),
+    ];
+    for (dependencies, error) in failures {
+        let app = json!({ "name": "@acme/app", "version": "1.0.0", "dependencies": dependencies });
+        project.write("packages/app/package.json", &app.to_string());
+
+        let output = project.click(&["install"]);
+        assert_eq!(output.status.code(), Some(4));
+        assert!(String::from_utf8_lossy(&output.stderr).contains(error));
+
+        let output = project.click_in("packages/app", &["pack"]);
+        assert!(!output.status.success());
+    }
+}